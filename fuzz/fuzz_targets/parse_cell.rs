@@ -0,0 +1,17 @@
+// Single cells, as sent live by games and OSC: any text must parse
+// without panicking.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use musickbeets::helper::{A4_FREQUENCY_HZ, FrequencyTable};
+use musickbeets::parser::parse_cell_text;
+use std::sync::OnceLock;
+
+fuzz_target!(|data: &[u8]| {
+    static FREQUENCY_TABLE: OnceLock<FrequencyTable> = OnceLock::new();
+    let frequency_table = FREQUENCY_TABLE.get_or_init(FrequencyTable::new);
+    let text = String::from_utf8_lossy(data);
+
+    parse_cell_text(&text, frequency_table, A4_FREQUENCY_HZ);
+});
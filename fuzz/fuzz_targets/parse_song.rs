@@ -0,0 +1,43 @@
+// Whole song files: any bytes must parse into a song without panicking.
+// Seed the corpus with the example songs for faster progress:
+//
+//   cargo +nightly fuzz run parse_song ../assets
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use musickbeets::helper::{A4_FREQUENCY_HZ, FrequencyTable};
+use musickbeets::parser::{
+    AUTO_CHANNEL_COUNT, MissingCellBehavior, parse_song, parse_song_collection,
+};
+use std::sync::OnceLock;
+
+fuzz_target!(|data: &[u8]| {
+    static FREQUENCY_TABLE: OnceLock<FrequencyTable> = OnceLock::new();
+    let frequency_table = FREQUENCY_TABLE.get_or_init(FrequencyTable::new);
+    let text = String::from_utf8_lossy(data);
+
+    let collection = parse_song_collection(
+        &text,
+        frequency_table,
+        A4_FREQUENCY_HZ,
+        AUTO_CHANNEL_COUNT,
+        MissingCellBehavior::SlowRelease,
+    );
+    let song = collection
+        .select(&collection.default_selection())
+        .expect("the default selection always exists");
+    assert!(
+        song.rows
+            .iter()
+            .all(|row| row.len() == song.channel_count())
+    );
+
+    parse_song(
+        &text,
+        frequency_table,
+        A4_FREQUENCY_HZ,
+        12,
+        MissingCellBehavior::Sustain,
+    );
+});
@@ -0,0 +1,21 @@
+// Parses a song and plays its first moments: whatever values the cells
+// hold, the engine must not panic or output anything but numbers.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use musickbeets::parser::AUTO_CHANNEL_COUNT;
+use musickbeets::{Engine, Song};
+
+/// Low rate and a short buffer keep each run quick
+const SAMPLE_RATE: u32 = 8000;
+const RENDER_FRAMES: usize = 2000;
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    let mut engine = Engine::new(Song::parse(&text, AUTO_CHANNEL_COUNT), SAMPLE_RATE);
+
+    let mut buffer = vec![0.0; 2 * RENDER_FRAMES];
+    engine.render_into(&mut buffer);
+    assert!(buffer.iter().all(|sample| sample.is_finite()));
+});
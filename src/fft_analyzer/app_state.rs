@@ -1,8 +1,9 @@
 use std::cell::RefCell;
 use std::collections::VecDeque;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
 
 use fltk::{
@@ -11,10 +12,21 @@ use fltk::{
     prelude::{InputExt, WidgetExt},
 };
 
-use crate::data::{AudioData, FftParams, Spectrogram, TransportState, ViewState};
+use crate::data::{
+    AudioData, ChannelMode, FftParams, GradientPreset, Marker, Spectrogram, TransportState,
+    ViewState,
+};
+use crate::playback::audio_input::AudioInput;
 use crate::playback::audio_player::AudioPlayer;
+use crate::processing::live::{LIVE_HISTORY_SECONDS, LiveAnalysis};
+use crate::processing::noise_reduction::NoiseProfile;
+use crate::processing::partials::{self, PartialParams, PartialTrack};
+use crate::processing::recon_compare::{ReconComparison, ReconStats};
+use crate::processing::spectral_edit::{self, BrushStroke, ERASE_GAIN_DB};
+use crate::processing::tempo::{self, BeatGrid};
 use crate::rendering::spectrogram_renderer::SpectrogramRenderer;
 use crate::rendering::waveform_renderer::WaveformRenderer;
+use crate::session::Session;
 use crate::ui::tooltips::TooltipManager;
 
 // ─── Messages ──────────────────────────────────────────────────────────────────
@@ -52,6 +64,9 @@ pub enum WorkerMessage {
     CsvSaved(Result<(std::path::PathBuf, usize, f64, f64), String>),
     /// CSV/FFT data loaded from disk. Contains Ok((spectrogram, params, recon_params, view_params, filename))
     /// or Err(message).
+    /// The reconstruction was reanalyzed and compared with the focus
+    /// spectrogram it was made from (Difference view and INFO stats).
+    ComparisonComplete(ReconComparison),
     CsvLoaded(
         Result<
             (
@@ -76,6 +91,8 @@ pub enum MouseMode {
     Move,
     SelectZoom,
     RoiSelect,
+    /// Paint on the spectrogram to attenuate or erase what is under the brush
+    Brush,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -93,6 +110,65 @@ pub struct MouseSelection {
     pub current_y: i32,
 }
 
+/// ROI edges grabbed by a drag on the spectrogram (two of them at a corner).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RoiEdges {
+    /// Start
+    pub left: bool,
+    /// Stop
+    pub right: bool,
+    /// Recon Freq Max
+    pub top: bool,
+    /// Recon Freq Min
+    pub bottom: bool,
+}
+
+impl RoiEdges {
+    pub fn any(self) -> bool {
+        self.left || self.right || self.top || self.bottom
+    }
+}
+
+/// An ROI edge drag in progress: the ROI as it will be when the mouse is
+/// released. Drawn as the selection box; the sidebar changes on release.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RoiEdgeDrag {
+    pub edges: RoiEdges,
+    pub start_seconds: f64,
+    pub stop_seconds: f64,
+    pub freq_min_hz: f32,
+    pub freq_max_hz: f32,
+}
+
+impl RoiEdgeDrag {
+    /// Move the grabbed edges to the pointer's time and frequency.
+    pub fn move_to(&mut self, time_seconds: f64, freq_hz: f32) {
+        if self.edges.left {
+            self.start_seconds = time_seconds;
+        }
+        if self.edges.right {
+            self.stop_seconds = time_seconds;
+        }
+        if self.edges.top {
+            self.freq_max_hz = freq_hz;
+        }
+        if self.edges.bottom {
+            self.freq_min_hz = freq_hz;
+        }
+    }
+
+    /// The dragged ROI as (start, stop, freq min, freq max), in order even
+    /// if an edge was dragged past the opposite one.
+    pub fn ordered(&self) -> (f64, f64, f32, f32) {
+        (
+            self.start_seconds.min(self.stop_seconds),
+            self.start_seconds.max(self.stop_seconds),
+            self.freq_min_hz.min(self.freq_max_hz),
+            self.freq_min_hz.max(self.freq_max_hz),
+        )
+    }
+}
+
 // ─── Status Bar Manager ────────────────────────────────────────────────────────
 //
 // Single system managing all status bar writes.
@@ -302,6 +378,15 @@ impl StatusBarManager {
 
 // ─── App State ─────────────────────────────────────────────────────────────────
 
+/// Live input mode (Analysis > Live Input): the capture device and the
+/// rolling analysis drawn as the spectrogram.
+pub struct LiveSession {
+    pub input: AudioInput,
+    pub analysis: LiveAnalysis,
+    /// Paused: captured samples are thrown away and the picture stands still
+    pub paused: bool,
+}
+
 pub struct AppState {
     pub audio_data: Option<Arc<AudioData>>,
     pub spectrogram: Option<Arc<Spectrogram>>,
@@ -327,6 +412,9 @@ pub struct AppState {
     pub reconstructed_audio: Option<AudioData>,
     /// Reconstruction start position in samples (ground truth).
     pub recon_start_sample: usize,
+    /// A/B toggle: the player holds the original audio under the
+    /// reconstruction instead of the reconstruction itself
+    pub playing_original: bool,
     pub is_processing: bool,
     pub dirty: bool,
     /// When true, auto-start playback after the next reconstruction completes.
@@ -336,8 +424,26 @@ pub struct AppState {
     pub render_full_file_outside_roi: bool,
     pub has_audio: bool,
     pub current_filename: String,
+    /// Absolute path of the opened audio file (or tracker song), for Save
+    /// Session. None when the audio came from an FFT CSV or live input.
+    pub audio_path: Option<PathBuf>,
+    /// Session being opened: its file-dependent parts (range, zoom, dB
+    /// ceiling) wait here until the audio has loaded and been analyzed
+    pub pending_session: Option<Session>,
     pub mouse_mode: MouseMode,
     pub mouse_selection: Option<MouseSelection>,
+    /// True while a Shift+drag on the spectrogram draws a new ROI, which
+    /// works like ROI Select whatever the mouse mode is
+    pub roi_box_drag: bool,
+    /// ROI edge drag in progress on the spectrogram
+    pub roi_edge_drag: Option<RoiEdgeDrag>,
+    /// Brush stroke being painted (Brush mode)
+    pub brush_stroke: Option<BrushStroke>,
+    /// Pointer over the spectrogram (widget-local pixels), for the brush outline
+    pub brush_hover: Option<(i32, i32)>,
+    /// Time and frequency under the pointer while it is over the
+    /// spectrogram, where Edit > Add Marker (M) puts the marker
+    pub pointer_time_freq: Option<(f64, f32)>,
 
     pub tooltip_mgr: TooltipManager,
 
@@ -354,6 +460,80 @@ pub struct AppState {
     pub normalize_audio: bool,
     pub normalize_peak: f32,
 
+    /// Which channel of a stereo file is analyzed (Analysis > Channel menu,
+    /// saved to the INI). Applied on load and when changed.
+    pub channel_mode: ChannelMode,
+
+    /// Sample rate opened audio is converted to, 0 = each file's own
+    /// (Analysis > Sample Rate menu, saved to the INI)
+    pub load_sample_rate: u32,
+
+    /// Draw tracked partials over the spectrogram (Display > Show Partials,
+    /// saved to the INI)
+    pub show_partials: bool,
+    /// Note names, a piano strip, and pitch lines on the frequency axis
+    /// (Display > Note Ruler, saved to the INI)
+    pub show_note_ruler: bool,
+    /// Partials of the focus spectrogram they were tracked from. The weak
+    /// pointer is compared on access, so a new FFT retracks automatically.
+    partial_cache: Option<(Weak<Spectrogram>, Arc<Vec<PartialTrack>>)>,
+    /// Beat and bar lines over the spectrogram and waveform (Display > Beat
+    /// Grid, saved to the INI)
+    pub show_beat_grid: bool,
+    /// Beat grid detected in a spectrogram (None if it had no clear beat),
+    /// cached the same way as the partials
+    beat_cache: Option<(Weak<Spectrogram>, Option<BeatGrid>)>,
+    /// dB legend for the colormap in the spectrogram's top-right corner
+    /// (Display > dB Colorbar, saved to the INI)
+    pub show_colorbar: bool,
+    /// Named custom gradients (the gradient editor's preset list, written to
+    /// the INI as soon as one is saved or deleted)
+    pub gradient_presets: Vec<GradientPreset>,
+
+    /// Size of exported spectrogram images in pixels (configurable via INI,
+    /// remembered between exports)
+    pub image_export_width: i32,
+    pub image_export_height: i32,
+
+    /// Tempo offered by File > Export to Tracker CSV (configurable via INI,
+    /// remembered between exports)
+    pub transcription_bpm: f32,
+
+    /// Brush radius in pixels and gain in dB (Edit menu, saved to the INI).
+    /// Gains at or below `ERASE_GAIN_DB` erase.
+    pub brush_radius_px: i32,
+    pub brush_gain_db: f32,
+    /// Brush strokes applied to the focus spectrogram, oldest first. A
+    /// recompute from the source audio applies them again; loading a file
+    /// clears them.
+    pub spectral_edits: Vec<BrushStroke>,
+
+    /// Named markers in time order (Markers panel, saved with the session).
+    /// Loading audio clears them; Open Session brings its own back.
+    pub markers: Vec<Marker>,
+
+    /// Noise profile captured from a noise-only stretch (sidebar button) and
+    /// how many dB the noise is turned down by (slider, saved to the INI).
+    /// Every analysis of the source audio is noise-reduced with them.
+    pub noise_profile: Option<NoiseProfile>,
+    pub noise_reduction_db: f32,
+    /// Whether the spectrogram layers on screen were noise-reduced, so a
+    /// new profile is not captured from already-cleaned frames
+    pub noise_reduced: bool,
+
+    /// Display > Difference View: draw the difference layer inside the ROI
+    /// instead of the focus spectrogram
+    pub show_difference: bool,
+    /// The focus spectrogram compared with a reanalysis of its
+    /// reconstruction, and the error stats for the INFO panel. Cleared when
+    /// either side is replaced.
+    pub difference_spectrogram: Option<Arc<Spectrogram>>,
+    pub recon_stats: Option<ReconStats>,
+
+    /// Live input in progress. While set, the loaded file is gone and the
+    /// spectrogram scrolls with the input.
+    pub live: Option<LiveSession>,
+
     /// Gain factor applied during source audio normalization (1.0 = no change).
     /// Stored so the original peak level can be recovered: original = normalized / gain.
     pub source_norm_gain: f32,
@@ -398,6 +578,7 @@ impl AppState {
 
             reconstructed_audio: None,
             recon_start_sample: 0,
+            playing_original: false,
             is_processing: false,
             dirty: false,
             play_pending: false,
@@ -405,8 +586,15 @@ impl AppState {
             render_full_file_outside_roi: true,
             has_audio: false,
             current_filename: String::new(),
+            audio_path: None,
+            pending_session: None,
             mouse_mode: MouseMode::Time,
             mouse_selection: None,
+            roi_box_drag: false,
+            roi_edge_drag: None,
+            brush_stroke: None,
+            brush_hover: None,
+            pointer_time_freq: None,
 
             tooltip_mgr: TooltipManager::new(),
 
@@ -417,6 +605,29 @@ impl AppState {
 
             normalize_audio: true,
             normalize_peak: 0.97,
+            channel_mode: ChannelMode::default(),
+            load_sample_rate: 0,
+            show_partials: false,
+            show_note_ruler: false,
+            partial_cache: None,
+            show_beat_grid: false,
+            beat_cache: None,
+            show_colorbar: false,
+            gradient_presets: Vec::new(),
+            image_export_width: 1920,
+            image_export_height: 1080,
+            transcription_bpm: 120.0,
+            brush_radius_px: 12,
+            brush_gain_db: ERASE_GAIN_DB,
+            spectral_edits: Vec::new(),
+            markers: Vec::new(),
+            noise_profile: None,
+            noise_reduction_db: 12.0,
+            noise_reduced: false,
+            show_difference: false,
+            difference_spectrogram: None,
+            recon_stats: None,
+            live: None,
             source_norm_gain: 1.0,
             cancel_flag: Arc::new(AtomicBool::new(false)),
             status: StatusBarManager::new(),
@@ -435,6 +646,257 @@ impl AppState {
             .or_else(|| self.spectrogram.clone())
     }
 
+    /// Partial tracks of the spectrogram drawn in the ROI (focus, or the
+    /// single legacy layer). Tracked on first use and cached until that
+    /// spectrogram is replaced.
+    pub fn partial_tracks(&mut self) -> Option<Arc<Vec<PartialTrack>>> {
+        let spec = self
+            .focus_spectrogram
+            .clone()
+            .or_else(|| self.spectrogram.clone())?;
+        if let Some((source, tracks)) = &self.partial_cache
+            && Weak::ptr_eq(source, &Arc::downgrade(&spec))
+        {
+            return Some(tracks.clone());
+        }
+        let tracks = Arc::new(partials::track_partials(&spec, &PartialParams::default()));
+        app_log!(
+            "Partials",
+            "Tracked {} partials over {} frames",
+            tracks.len(),
+            spec.num_frames()
+        );
+        self.partial_cache = Some((Arc::downgrade(&spec), tracks.clone()));
+        Some(tracks)
+    }
+
+    /// Tempo and beat phase of the focus spectrogram (or the single legacy
+    /// layer). Detected on first use and cached until that spectrogram is
+    /// replaced; None when nothing is loaded or no steady beat was found.
+    pub fn beat_grid(&mut self) -> Option<BeatGrid> {
+        let spec = self
+            .focus_spectrogram
+            .clone()
+            .or_else(|| self.spectrogram.clone())?;
+        if let Some((source, grid)) = &self.beat_cache
+            && Weak::ptr_eq(source, &Arc::downgrade(&spec))
+        {
+            return *grid;
+        }
+        let grid = tempo::detect_beat_grid(&spec);
+        if let Some(grid) = grid {
+            app_log!(
+                "Tempo",
+                "Detected {:.1} BPM, first beat at {:.3}s",
+                grid.bpm,
+                grid.first_beat_seconds
+            );
+        } else {
+            app_log!(
+                "Tempo",
+                "No steady beat found over {} frames",
+                spec.num_frames()
+            );
+        }
+        self.beat_cache = Some((Arc::downgrade(&spec), grid));
+        grid
+    }
+
+    /// The mode the drag in progress follows: ROI Select during a
+    /// Shift+drag on the spectrogram, else the mouse mode.
+    pub fn drag_mode(&self) -> MouseMode {
+        if self.roi_box_drag {
+            MouseMode::RoiSelect
+        } else {
+            self.mouse_mode
+        }
+    }
+
+    /// Set the ROI (Start/Stop and Recon Freq Min/Max) from a mouse gesture.
+    /// Like editing the sidebar fields, it takes effect on the next
+    /// recompute; the caller updates the fields.
+    pub fn set_roi(
+        &mut self,
+        start_seconds: f64,
+        stop_seconds: f64,
+        freq_min_hz: f32,
+        freq_max_hz: f32,
+    ) {
+        let sample_rate = self.fft_params.sample_rate as f64;
+        self.fft_params.start_sample = (start_seconds.max(0.0) * sample_rate).round() as usize;
+        self.fft_params.stop_sample = (stop_seconds.max(0.0) * sample_rate).round() as usize;
+        self.view.recon_freq_min_hz = freq_min_hz.max(1.0);
+        self.view.recon_freq_max_hz = freq_max_hz.min(self.view.data_freq_max_hz);
+        self.dirty = true;
+        self.invalidate_all_spectrogram_renderers();
+        self.wave_renderer.invalidate();
+    }
+
+    /// Apply a finished brush stroke to the focus spectrogram and keep it in
+    /// `spectral_edits`. Returns how many bins it touched.
+    pub fn apply_brush_stroke(&mut self, stroke: BrushStroke) -> usize {
+        let Some(mut focus) = self.focus_spectrogram.take() else {
+            return 0;
+        };
+        // Let go of the drawn layer's reference so the frames are edited in
+        // place instead of copied
+        let drawn = self
+            .spectrogram
+            .as_ref()
+            .is_some_and(|spec| Arc::ptr_eq(spec, &focus));
+        if drawn {
+            self.spectrogram = None;
+        }
+        let touched = spectral_edit::apply_stroke(Arc::make_mut(&mut focus), &stroke);
+        if drawn {
+            self.spectrogram = Some(focus.clone());
+        }
+        self.focus_spectrogram = Some(focus);
+        self.spectral_edits.push(stroke);
+        self.clear_comparison();
+        self.invalidate_all_spectrogram_renderers();
+        touched
+    }
+
+    /// Capture the noise profile from the Start..Stop range of the analysis
+    /// on screen: the focus layer when it covers exactly that range and has
+    /// no brush strokes, else the overview. Returns false if no frames fall
+    /// in the range.
+    pub fn capture_noise_profile(&mut self) -> bool {
+        let focus_fits = self.spectral_edits.is_empty()
+            && self.focus_spec_params.as_ref().is_some_and(|p| {
+                p.start_sample == self.fft_params.start_sample
+                    && p.stop_sample == self.fft_params.stop_sample
+            });
+        let source = if focus_fits {
+            self.focus_spectrogram
+                .clone()
+                .zip(self.focus_spec_params.clone())
+        } else {
+            self.overview_spectrogram
+                .clone()
+                .zip(self.overview_spec_params.clone())
+        };
+        let Some((spectrogram, params)) = source else {
+            return false;
+        };
+        let profile = NoiseProfile::capture(
+            &spectrogram,
+            &params,
+            self.fft_params.start_seconds(),
+            self.fft_params.stop_seconds(),
+        );
+        if profile.is_none() {
+            return false;
+        }
+        self.noise_profile = profile;
+        true
+    }
+
+    /// The layer the spectrogram draws inside the ROI in place of the focus
+    /// spectrogram while the Difference view is on and a comparison exists.
+    pub fn difference_layer(&self) -> Option<Arc<Spectrogram>> {
+        self.difference_spectrogram
+            .clone()
+            .filter(|_| self.show_difference)
+    }
+
+    /// Drop the Difference view layer and the reconstruction stats.
+    pub fn clear_comparison(&mut self) {
+        if self.difference_spectrogram.take().is_some() {
+            self.invalidate_all_spectrogram_renderers();
+        }
+        self.recon_stats = None;
+    }
+
+    /// Start capturing from the default input device, dropping the loaded
+    /// file and everything analyzed from it.
+    pub fn start_live(&mut self) -> anyhow::Result<()> {
+        let input = AudioInput::start(self.fft_params.sample_rate)?;
+        let analysis = LiveAnalysis::new(self.fft_params.clone(), LIVE_HISTORY_SECONDS);
+        self.clear_spectrogram_layers();
+        self.audio_data = None;
+        self.has_audio = false;
+        self.reconstructed_audio = None;
+        self.playing_original = false;
+        self.transport.duration_samples = 0;
+        self.transport.position_samples = 0;
+        self.spectral_edits.clear();
+        self.noise_profile = None;
+        self.noise_reduced = false;
+        self.clear_comparison();
+        self.current_filename = "Live input".to_string();
+        self.audio_path = None;
+
+        let nyquist = input.sample_rate() as f32 / 2.0;
+        self.view.data_freq_max_hz = nyquist;
+        self.view.freq_max_hz = self.view.freq_max_hz.min(nyquist);
+        self.view.data_time_min_sec = 0.0;
+        self.view.data_time_max_sec = LIVE_HISTORY_SECONDS;
+        self.view.time_min_sec = 0.0;
+        self.view.time_max_sec = LIVE_HISTORY_SECONDS;
+        self.live = Some(LiveSession {
+            input,
+            analysis,
+            paused: false,
+        });
+        Ok(())
+    }
+
+    /// Analyze the input captured since the last call and scroll the view
+    /// to the newest frame. Call every poll tick; returns true when there is
+    /// something new to draw.
+    pub fn pump_live(&mut self) -> bool {
+        let Some(live) = self.live.as_mut() else {
+            return false;
+        };
+        let samples = live.input.take_samples();
+        if live.paused || samples.is_empty() {
+            return false;
+        }
+        // Let go of the drawn layer so the frames are appended in place
+        self.overview_spectrogram = None;
+        self.spectrogram = None;
+        live.analysis.push(&samples);
+
+        let params = live.analysis.render_params();
+        let end = params.stop_seconds();
+        let start = end - LIVE_HISTORY_SECONDS;
+        self.view.data_time_min_sec = start;
+        self.view.data_time_max_sec = end;
+        self.view.time_min_sec = start;
+        self.view.time_max_sec = end;
+        // The whole input is the processing range, so nothing is grayed out
+        self.fft_params.start_sample = params.start_sample;
+        self.fft_params.stop_sample = params.stop_sample;
+
+        let spec = live.analysis.spectrogram();
+        self.overview_spectrogram = Some(spec.clone());
+        self.spectrogram = Some(spec);
+        self.overview_spec_params = Some(params);
+        self.invalidate_all_spectrogram_renderers();
+        true
+    }
+
+    /// Leave live input mode. Returns the retained input as audio to analyze
+    /// like a file (None if nothing was captured).
+    pub fn stop_live(&mut self) -> Option<AudioData> {
+        let live = self.live.take()?;
+        self.clear_spectrogram_layers();
+        let audio = live.analysis.snapshot_audio();
+        (audio.num_samples() > 0).then_some(audio)
+    }
+
+    fn clear_spectrogram_layers(&mut self) {
+        self.spectrogram = None;
+        self.overview_spectrogram = None;
+        self.focus_spectrogram = None;
+        self.overview_spec_params = None;
+        self.focus_spec_params = None;
+        self.invalidate_all_spectrogram_renderers();
+        self.wave_renderer.invalidate();
+    }
+
     /// Invalidate all spectrogram renderers.
     /// Useful during the transition from one-layer to two-layer rendering.
     #[allow(dead_code)]
@@ -453,6 +915,26 @@ impl AppState {
         params
     }
 
+    /// FFT params the focus layer is drawn with (the ones it was computed with).
+    pub fn focus_render_params(&self) -> FftParams {
+        self.focus_spec_params
+            .clone()
+            .unwrap_or_else(|| self.fft_params.clone())
+    }
+
+    /// FFT params the overview layer is drawn with (the ones it was computed
+    /// with, else the overview defaults for the loaded file).
+    pub fn overview_render_params(&self) -> FftParams {
+        self.overview_spec_params
+            .clone()
+            .or_else(|| {
+                self.audio_data
+                    .as_ref()
+                    .map(|a| self.overview_params_for_audio(a.num_samples()))
+            })
+            .unwrap_or_else(|| self.overview_fft_defaults.clone())
+    }
+
     /// Cancel any in-flight worker, then create a fresh cancellation flag
     /// for the next operation. Returns the new flag (already stored in self).
     pub fn new_cancel_flag(&mut self) -> Arc<AtomicBool> {
@@ -469,6 +951,38 @@ impl AppState {
         self.recon_start_sample as f64 / self.fft_params.sample_rate.max(1) as f64
     }
 
+    /// The analyzed source audio over the samples the reconstruction covers.
+    /// None without a reconstruction or source audio (e.g. a loaded CSV).
+    pub fn original_under_reconstruction(&self) -> Option<&[f32]> {
+        let recon = self.reconstructed_audio.as_ref()?;
+        let audio = self.audio_data.as_ref()?;
+        let start = self.recon_start_sample.min(audio.num_samples());
+        let end = (start + recon.num_samples()).min(audio.num_samples());
+        Some(&audio.samples[start..end])
+    }
+
+    /// Switch the player between the reconstruction and the original audio
+    /// it was made from, keeping the position. The original is the analyzed
+    /// signal over the same samples. Returns false (and stays on the
+    /// reconstruction) when there is no source audio to compare against.
+    pub fn set_playing_original(&mut self, original: bool) -> bool {
+        let Some(recon) = self.reconstructed_audio.as_ref() else {
+            self.playing_original = false;
+            return !original;
+        };
+        let samples = if original {
+            let Some(samples) = self.original_under_reconstruction() else {
+                return false;
+            };
+            Arc::new(samples.to_vec())
+        } else {
+            Arc::clone(&recon.samples)
+        };
+        self.audio_player.swap_samples(samples);
+        self.playing_original = original;
+        true
+    }
+
     /// Compute all derived info values from current params
     pub fn derived_info(&self) -> DerivedInfo {
         let total_samples = if let Some(ref audio) = self.audio_data {
@@ -495,6 +1009,7 @@ impl AppState {
             window_length: self.fft_params.window_length,
             sample_rate: self.fft_params.sample_rate,
             overlap_percent: self.fft_params.overlap_percent,
+            recon_stats: self.recon_stats,
         }
     }
 }
@@ -509,11 +1024,12 @@ pub struct DerivedInfo {
     pub window_length: usize,
     pub sample_rate: u32,
     pub overlap_percent: f32,
+    pub recon_stats: Option<ReconStats>,
 }
 
 impl DerivedInfo {
     pub fn format_info(&self) -> String {
-        format!(
+        let mut info = format!(
             "Segments: {} x {} smp\n\
              Total samples: {}\n\
              Freq bins: {} / segment\n\
@@ -528,7 +1044,18 @@ impl DerivedInfo {
             self.bin_duration_ms,
             self.hop_length,
             self.hop_length as f64 / self.sample_rate.max(1) as f64 * 1000.0,
-        )
+        );
+        if let Some(stats) = self.recon_stats {
+            match stats.snr_db {
+                Some(snr) => info.push_str(&format!("\nRecon SNR: {:.1} dB", snr)),
+                None => info.push_str("\nRecon SNR: n/a"),
+            }
+            info.push_str(&format!(
+                "\nSpectral conv: {:.1} dB",
+                stats.spectral_convergence_db
+            ));
+        }
+        info
     }
 
     pub fn format_segmentation_sentence(&self) -> String {
@@ -619,6 +1146,8 @@ pub struct SharedCallbacks {
     pub set_btn_busy_mode: SharedCb,
     /// Restore the rerun button to normal "Recompute + Rebuild" mode.
     pub set_btn_normal_mode: SharedCb,
+    /// Refill the Markers panel from `AppState::markers`.
+    pub update_markers: SharedCb,
 }
 
 // ─── Message bar helper ───────────────────────────────────────────────────────
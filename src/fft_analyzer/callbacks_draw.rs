@@ -1,21 +1,44 @@
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::sync::mpsc;
 
 use fltk::{
     app,
-    enums::{Event, Font},
+    enums::{ColorDepth, Cursor, Event, Font},
+    image::RgbImage,
+    input::FloatInput,
     prelude::*,
 };
 
 use crate::app_state::format_time;
-use crate::app_state::{AppState, MouseMode, MouseSelection, MouseSurface, SharedCallbacks};
-use crate::data;
+use crate::app_state::{
+    AppState, MouseMode, MouseSelection, MouseSurface, RoiEdgeDrag, RoiEdges, SharedCallbacks,
+    WorkerMessage, update_status_bar,
+};
+use crate::data::{self, Marker};
 use crate::debug_flags;
 use crate::layout::Widgets;
+use crate::processing::spectral_edit::{BrushDab, BrushStroke};
+use crate::processing::tempo::BeatGrid;
+use crate::rendering::color_lut::ColorLUT;
 use crate::ui::theme;
 
 const PLAYBACK_CURSOR_W: i32 = 3;
 const MIN_SELECT_DRAG_PX: i32 = 4;
+/// How close (in pixels) the pointer must be to an ROI edge to grab it
+const ROI_EDGE_GRAB_PX: i32 = 5;
+/// Width of the note ruler's piano strip, on the spectrogram side of the axis
+const PIANO_STRIP_W: i32 = 12;
+/// Closest two note names may sit on the frequency axis (pixels)
+const NOTE_LABEL_GAP_PX: f32 = 11.0;
+/// Closest two pitch lines may sit before only the C lines are drawn (pixels)
+const NOTE_LINE_GAP_PX: f32 = 4.0;
+/// Closest two beat lines may sit before only the bar lines are drawn (pixels)
+const BEAT_LINE_GAP_PX: f64 = 4.0;
+/// Height range of the on-screen dB colorbar, and its gap from the edges
+const COLORBAR_MIN_H: i32 = 80;
+const COLORBAR_MAX_H: i32 = 240;
+const COLORBAR_MARGIN: i32 = 8;
 
 // ═══════════════════════════════════════════════════════════════════════════
 //  DRAW CALLBACKS
@@ -24,10 +47,11 @@ const MIN_SELECT_DRAG_PX: i32 = 4;
 pub fn setup_draw_callbacks(
     widgets: &Widgets,
     state: &Rc<RefCell<AppState>>,
+    tx: &mpsc::Sender<WorkerMessage>,
     shared: &SharedCallbacks,
 ) {
     setup_spectrogram_draw(widgets, state);
-    setup_spectrogram_mouse(widgets, state, shared);
+    setup_spectrogram_mouse(widgets, state, tx, shared);
     setup_waveform_draw(widgets, state);
     setup_waveform_mouse(widgets, state, shared);
     setup_freq_axis_draw(widgets, state);
@@ -72,6 +96,174 @@ fn draw_selection_overlay(w: &impl WidgetExt, selection: MouseSelection) {
     fltk::draw::set_line_style(fltk::draw::LineStyle::Solid, 0);
 }
 
+/// The ROI edges within `ROI_EDGE_GRAB_PX` of a point of the spectrogram
+/// (widget-local pixels). Edges scrolled out of view can't be grabbed, and
+/// when both edges of an axis are in reach only the nearer one is.
+fn roi_edges_at(st: &AppState, mx: i32, my: i32, widget_w: i32, widget_h: i32) -> RoiEdges {
+    let view = &st.view;
+    let (start, stop) = (st.fft_params.start_seconds(), st.fft_params.stop_seconds());
+    let (freq_min, freq_max) = (view.recon_freq_min_hz, view.recon_freq_max_hz);
+    let x_of = |time: f64| (view.time_to_x(time) * widget_w as f64) as i32;
+    let y_of = |freq: f32| ((1.0 - view.freq_to_y(freq)) * widget_h as f32) as i32;
+
+    // Only beside the ROI, not anywhere along the lines its edges lie on
+    if mx < x_of(start) - ROI_EDGE_GRAB_PX
+        || mx > x_of(stop) + ROI_EDGE_GRAB_PX
+        || my < y_of(freq_max) - ROI_EDGE_GRAB_PX
+        || my > y_of(freq_min) + ROI_EDGE_GRAB_PX
+    {
+        return RoiEdges::default();
+    }
+
+    let time_visible = |time: f64| time >= view.time_min_sec && time <= view.time_max_sec;
+    let freq_visible = |freq: f32| freq >= view.freq_min_hz && freq <= view.freq_max_hz;
+    let distance = |visible: bool, edge: i32, pointer: i32| {
+        Some((pointer - edge).abs()).filter(|&d| visible && d <= ROI_EDGE_GRAB_PX)
+    };
+    let nearer = |low: Option<i32>, high: Option<i32>| match (low, high) {
+        (Some(low), Some(high)) => (low <= high, high < low),
+        (low, high) => (low.is_some(), high.is_some()),
+    };
+    let (left, right) = nearer(
+        distance(time_visible(start), x_of(start), mx),
+        distance(time_visible(stop), x_of(stop), mx),
+    );
+    let (top, bottom) = nearer(
+        distance(freq_visible(freq_max), y_of(freq_max), my),
+        distance(freq_visible(freq_min), y_of(freq_min), my),
+    );
+    RoiEdges {
+        left,
+        right,
+        top,
+        bottom,
+    }
+}
+
+/// Mouse pointer shown over grabbable ROI edges.
+fn roi_edge_cursor(edges: RoiEdges) -> Cursor {
+    let horizontal = edges.left || edges.right;
+    let vertical = edges.top || edges.bottom;
+    match (horizontal, vertical) {
+        (true, true) if (edges.left && edges.top) || (edges.right && edges.bottom) => Cursor::NWSE,
+        (true, true) => Cursor::NESW,
+        (true, false) => Cursor::WE,
+        (false, true) => Cursor::NS,
+        (false, false) => Cursor::Default,
+    }
+}
+
+fn set_window_cursor(w: &impl WidgetExt, cursor: Cursor) {
+    if let Some(mut window) = w.window() {
+        window.set_cursor(cursor);
+    }
+}
+
+/// A brush print of `brush_radius_px` around a point of the spectrogram
+/// (widget-local pixels).
+fn brush_dab_at(st: &AppState, mx: i32, my: i32, widget_w: i32, widget_h: i32) -> BrushDab {
+    let radius = st.brush_radius_px as f64;
+    let y_to_freq = |y: f64| {
+        st.view
+            .y_to_freq((1.0 - y / widget_h.max(1) as f64) as f32)
+            .max(0.0)
+    };
+    BrushDab {
+        time_seconds: st.view.x_to_time(mx as f64 / widget_w.max(1) as f64),
+        radius_seconds: radius / widget_w.max(1) as f64 * st.view.visible_time_range(),
+        freq_hz: y_to_freq(my as f64),
+        freq_low_hz: y_to_freq(my as f64 + radius),
+        freq_high_hz: y_to_freq(my as f64 - radius),
+    }
+}
+
+/// Extend the stroke being painted from the last pointer position to
+/// (`mx`, `my`), a dab every half radius so fast drags leave no gaps.
+fn paint_brush_to(st: &mut AppState, mx: i32, my: i32, widget_w: i32, widget_h: i32) {
+    let (last_x, last_y) = st.brush_hover.unwrap_or((mx, my));
+    st.brush_hover = Some((mx, my));
+    let distance = ((mx - last_x) as f64).hypot((my - last_y) as f64);
+    let steps = (distance / (st.brush_radius_px as f64 / 2.0).max(1.0))
+        .ceil()
+        .max(1.0) as i32;
+    let dabs: Vec<BrushDab> = (1..=steps)
+        .map(|step| {
+            let t = step as f64 / steps as f64;
+            let x = last_x + ((mx - last_x) as f64 * t).round() as i32;
+            let y = last_y + ((my - last_y) as f64 * t).round() as i32;
+            brush_dab_at(st, x, y, widget_w, widget_h)
+        })
+        .collect();
+    if let Some(stroke) = st.brush_stroke.as_mut() {
+        stroke.dabs.extend(dabs);
+    }
+}
+
+/// Show the ROI in the sidebar's Start/Stop and Recon Freq Min/Max fields
+/// after a mouse gesture changed it.
+fn show_roi_in_sidebar(
+    st: &AppState,
+    input_start: &mut FloatInput,
+    input_stop: &mut FloatInput,
+    input_recon_freq_min: &mut FloatInput,
+    input_recon_freq_max: &mut FloatInput,
+) {
+    match st.fft_params.time_unit {
+        crate::data::TimeUnit::Seconds => {
+            input_start.set_value(&format!("{:.5}", st.fft_params.start_seconds()));
+            input_stop.set_value(&format!("{:.5}", st.fft_params.stop_seconds()));
+        }
+        crate::data::TimeUnit::Samples => {
+            input_start.set_value(&st.fft_params.start_sample.to_string());
+            input_stop.set_value(&st.fft_params.stop_sample.to_string());
+        }
+    }
+    input_recon_freq_min.set_value(&format!("{:.0}", st.view.recon_freq_min_hz));
+    input_recon_freq_max.set_value(&format!("{:.0}", st.view.recon_freq_max_hz));
+}
+
+/// Pixel rectangle (x, y, w, h) of the ROI - the processing time range and the
+/// reconstruction frequency range - within a `ww` x `wh` spectrogram view,
+/// or None when the ROI is off screen. The focus layer is drawn inside it.
+pub(crate) fn roi_pixel_rect(
+    view: &data::ViewState,
+    proc_time_min: f64,
+    proc_time_max: f64,
+    ww: i32,
+    wh: i32,
+) -> Option<(i32, i32, i32, i32)> {
+    let first_px = (0..ww).find(|&px| {
+        let t = px as f64 / ww as f64;
+        let time = view.x_to_time(t);
+        time >= proc_time_min && time <= proc_time_max
+    });
+    let last_px = (0..ww).rfind(|&px| {
+        let t = px as f64 / ww as f64;
+        let time = view.x_to_time(t);
+        time >= proc_time_min && time <= proc_time_max
+    });
+
+    let first_py = (0..wh).find(|&py| {
+        let flipped_py = wh - 1 - py;
+        let t = flipped_py as f32 / wh as f32;
+        let freq = view.y_to_freq(t);
+        freq >= view.recon_freq_min_hz && freq <= view.recon_freq_max_hz
+    });
+    let last_py = (0..wh).rfind(|&py| {
+        let flipped_py = wh - 1 - py;
+        let t = flipped_py as f32 / wh as f32;
+        let freq = view.y_to_freq(t);
+        freq >= view.recon_freq_min_hz && freq <= view.recon_freq_max_hz
+    });
+
+    match (first_px, last_px, first_py, last_py) {
+        (Some(px0), Some(px1), Some(py0), Some(py1)) if px1 >= px0 && py1 >= py0 => {
+            Some((px0, py0, px1 - px0 + 1, py1 - py0 + 1))
+        }
+        _ => None,
+    }
+}
+
 fn pan_time_view(st: &mut AppState, delta_seconds: f64) {
     let range = st.view.visible_time_range();
     let data_min = st.view.data_time_min_sec;
@@ -153,59 +345,30 @@ fn setup_spectrogram_draw(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
                 return;
             };
             let overview_spec = st.overview_spectrogram.clone();
-            let focus_spec = st.focus_spectrogram.clone();
+            let difference_spec = st.difference_layer();
+            let focus_spec = difference_spec
+                .clone()
+                .or_else(|| st.focus_spectrogram.clone());
             let legacy_spec = st.spectrogram.clone();
             if overview_spec.is_some() || focus_spec.is_some() || legacy_spec.is_some() {
                 let view = st.view.clone();
-                let focus_params = st
-                    .focus_spec_params
-                    .clone()
-                    .unwrap_or_else(|| st.fft_params.clone());
-                let overview_params = st
-                    .overview_spec_params
-                    .clone()
-                    .or_else(|| {
-                        st.audio_data
-                            .as_ref()
-                            .map(|a| st.overview_params_for_audio(a.num_samples()))
-                    })
-                    .unwrap_or_else(|| st.overview_fft_defaults.clone());
+                let focus_view = data::ViewState {
+                    difference_view: difference_spec.is_some(),
+                    ..view.clone()
+                };
+                let focus_params = st.focus_render_params();
+                let overview_params = st.overview_render_params();
                 let proc_time_min = st.fft_params.start_seconds();
                 let proc_time_max = st.fft_params.stop_seconds();
                 let render_full_file_outside_roi = st.render_full_file_outside_roi;
-                let ww = w.w().max(1);
-                let wh = w.h().max(1);
-
-                let first_px = (0..ww).find(|&px| {
-                    let t = px as f64 / ww as f64;
-                    let time = view.x_to_time(t);
-                    time >= proc_time_min && time <= proc_time_max
-                });
-                let last_px = (0..ww).rfind(|&px| {
-                    let t = px as f64 / ww as f64;
-                    let time = view.x_to_time(t);
-                    time >= proc_time_min && time <= proc_time_max
-                });
-
-                let first_py = (0..wh).find(|&py| {
-                    let flipped_py = wh - 1 - py;
-                    let t = flipped_py as f32 / wh as f32;
-                    let freq = view.y_to_freq(t);
-                    freq >= view.recon_freq_min_hz && freq <= view.recon_freq_max_hz
-                });
-                let last_py = (0..wh).rfind(|&py| {
-                    let flipped_py = wh - 1 - py;
-                    let t = flipped_py as f32 / wh as f32;
-                    let freq = view.y_to_freq(t);
-                    freq >= view.recon_freq_min_hz && freq <= view.recon_freq_max_hz
-                });
-
-                let roi_clip = match (first_px, last_px, first_py, last_py) {
-                    (Some(px0), Some(px1), Some(py0), Some(py1)) if px1 >= px0 && py1 >= py0 => {
-                        Some((w.x() + px0, w.y() + py0, px1 - px0 + 1, py1 - py0 + 1))
-                    }
-                    _ => None,
-                };
+                let roi_clip = roi_pixel_rect(
+                    &view,
+                    proc_time_min,
+                    proc_time_max,
+                    w.w().max(1),
+                    w.h().max(1),
+                )
+                .map(|(px0, py0, rw, rh)| (w.x() + px0, w.y() + py0, rw, rh));
 
                 if let Some(spec) = overview_spec.or_else(|| legacy_spec.clone()) {
                     if focus_spec.is_some() {
@@ -272,7 +435,7 @@ fn setup_spectrogram_draw(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
                         fltk::draw::push_clip(clip_x, clip_y, clip_w, clip_h);
                         st.focus_spec_renderer.draw(
                             &spec,
-                            &view,
+                            &focus_view,
                             &focus_params,
                             proc_time_min,
                             proc_time_max,
@@ -298,8 +461,18 @@ fn setup_spectrogram_draw(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
                 } else {
                     None
                 };
+                let partial_tracks = if st.show_partials {
+                    st.partial_tracks()
+                } else {
+                    None
+                };
+                let beat_grid = if st.show_beat_grid {
+                    st.beat_grid()
+                } else {
+                    None
+                };
                 // borrow_mut dropped here at end of block
-                Some(cursor_cx)
+                Some((cursor_cx, partial_tracks, beat_grid, roi_clip))
             } else {
                 None
             }
@@ -307,7 +480,7 @@ fn setup_spectrogram_draw(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
         // State borrow is now released — axis callbacks can borrow freely.
 
         match draw_data {
-            Some(cursor_cx) => {
+            Some((cursor_cx, partial_tracks, beat_grid, roi_clip)) => {
                 let st = match state.try_borrow() {
                     Ok(st) => st,
                     Err(_) => return,
@@ -325,6 +498,12 @@ fn setup_spectrogram_draw(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
                 let freq_to_y_unclamped = |freq_hz: f32| {
                     let min = st.view.freq_min_hz.max(1.0);
                     let max = st.view.freq_max_hz.max(min + 1.0);
+                    if st.view.mel_view {
+                        let mel_min = crate::data::mel::hz_to_mel(min);
+                        let mel_max = crate::data::mel::hz_to_mel(max);
+                        return (crate::data::mel::hz_to_mel(freq_hz) - mel_min)
+                            / (mel_max - mel_min);
+                    }
                     match st.view.freq_scale {
                         crate::data::FreqScale::Linear => (freq_hz - min) / (max - min),
                         crate::data::FreqScale::Log => (freq_hz / min).ln() / (max / min).ln(),
@@ -374,6 +553,47 @@ fn setup_spectrogram_draw(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
                     }
                 };
 
+                if st.show_note_ruler {
+                    draw_note_lines(w, &st.view);
+                }
+                if let Some(grid) = &beat_grid {
+                    draw_beat_lines(w, &st.view, grid);
+                }
+
+                // Partial tracks as ridges over the focus layer
+                if let (Some(tracks), Some((clip_x, clip_y, clip_w, clip_h))) =
+                    (partial_tracks, roi_clip)
+                {
+                    fltk::draw::push_clip(clip_x, clip_y, clip_w, clip_h);
+                    fltk::draw::set_draw_color(theme::color(theme::ACCENT_GREEN));
+                    fltk::draw::set_line_style(fltk::draw::LineStyle::Solid, 2);
+                    let to_px = |time_sec: f64, freq_hz: f32| {
+                        (
+                            w.x() + (time_to_x_unclamped(time_sec) * w.w() as f64) as i32,
+                            w.y() + ((1.0 - freq_to_y_unclamped(freq_hz)) * w.h() as f32) as i32,
+                        )
+                    };
+                    for track in tracks.iter().filter(|track| {
+                        track.death_seconds() >= st.view.time_min_sec
+                            && track.birth_seconds() <= st.view.time_max_sec
+                    }) {
+                        let mut prev = None;
+                        for point in &track.points {
+                            let (px, py) = to_px(point.time_seconds, point.freq_hz);
+                            match prev {
+                                Some((x0, y0)) => fltk::draw::draw_line(x0, y0, px, py),
+                                // Start with a dot so a sub-pixel track still shows
+                                None => fltk::draw::draw_point(px, py),
+                            }
+                            prev = Some((px, py));
+                        }
+                    }
+                    fltk::draw::set_line_style(fltk::draw::LineStyle::Solid, 0);
+                    fltk::draw::pop_clip();
+                }
+
+                draw_markers(w, &st.view, &st.markers, true);
+
                 let roi_t0 = time_to_x_unclamped(st.fft_params.start_seconds());
                 let roi_t1 = time_to_x_unclamped(st.fft_params.stop_seconds());
                 let roi_f0 = freq_to_y_unclamped(st.view.recon_freq_min_hz);
@@ -471,12 +691,81 @@ fn setup_spectrogram_draw(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
                     );
                 }
 
-                if st.mouse_mode != MouseMode::Move
+                if st.drag_mode() != MouseMode::Move
                     && let Some(selection) = st.mouse_selection
                     && selection.surface == MouseSurface::Spectrogram
                 {
                     draw_selection_overlay(w, selection);
                 }
+
+                // ROI edge drag: the ROI it will set, in the selection style
+                if let Some(drag) = st.roi_edge_drag {
+                    let (start, stop, freq_min, freq_max) = drag.ordered();
+                    let left = w.x() + (time_to_x_unclamped(start) * w.w() as f64) as i32;
+                    let right = w.x() + (time_to_x_unclamped(stop) * w.w() as f64) as i32;
+                    let top = w.y() + ((1.0 - freq_to_y_unclamped(freq_max)) * w.h() as f32) as i32;
+                    let bottom =
+                        w.y() + ((1.0 - freq_to_y_unclamped(freq_min)) * w.h() as f32) as i32;
+                    fltk::draw::push_clip(w.x(), w.y(), w.w(), w.h());
+                    fltk::draw::set_draw_color(theme::color(theme::ACCENT_YELLOW));
+                    fltk::draw::set_line_style(fltk::draw::LineStyle::Dash, 1);
+                    fltk::draw::draw_rect(left, top, (right - left).max(1), (bottom - top).max(1));
+                    fltk::draw::set_line_style(fltk::draw::LineStyle::Solid, 0);
+                    fltk::draw::pop_clip();
+                }
+
+                // Brush: the stroke being painted and the brush under the pointer
+                if st.mouse_mode == MouseMode::Brush {
+                    fltk::draw::push_clip(w.x(), w.y(), w.w(), w.h());
+                    fltk::draw::set_draw_color(theme::color(theme::ACCENT_YELLOW));
+                    if let Some(stroke) = &st.brush_stroke {
+                        for dab in &stroke.dabs {
+                            let left = w.x()
+                                + (time_to_x_unclamped(dab.time_seconds - dab.radius_seconds)
+                                    * w.w() as f64) as i32;
+                            let right = w.x()
+                                + (time_to_x_unclamped(dab.time_seconds + dab.radius_seconds)
+                                    * w.w() as f64) as i32;
+                            let top = w.y()
+                                + ((1.0 - freq_to_y_unclamped(dab.freq_high_hz)) * w.h() as f32)
+                                    as i32;
+                            let bottom = w.y()
+                                + ((1.0 - freq_to_y_unclamped(dab.freq_low_hz)) * w.h() as f32)
+                                    as i32;
+                            fltk::draw::draw_pie(
+                                left,
+                                top,
+                                (right - left).max(1),
+                                (bottom - top).max(1),
+                                0.0,
+                                360.0,
+                            );
+                        }
+                    }
+                    if let Some((hx, hy)) = st.brush_hover {
+                        let r = st.brush_radius_px;
+                        fltk::draw::draw_arc(
+                            w.x() + hx - r,
+                            w.y() + hy - r,
+                            2 * r,
+                            2 * r,
+                            0.0,
+                            360.0,
+                        );
+                    }
+                    fltk::draw::pop_clip();
+                }
+
+                if st.show_colorbar {
+                    // The overview layer is drawn whenever there is one; a
+                    // lone focus layer only went through the focus renderer
+                    let lut = if st.overview_spectrogram.is_some() || st.spectrogram.is_some() {
+                        st.overview_spec_renderer.color_lut()
+                    } else {
+                        st.focus_spec_renderer.color_lut()
+                    };
+                    draw_colorbar_legend(w, lut);
+                }
             }
             None => {
                 fltk::draw::set_draw_color(theme::color(theme::BG_DARK));
@@ -493,10 +782,14 @@ fn setup_spectrogram_draw(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
 fn setup_spectrogram_mouse(
     widgets: &Widgets,
     state: &Rc<RefCell<AppState>>,
+    tx: &mpsc::Sender<WorkerMessage>,
     shared: &SharedCallbacks,
 ) {
     let state = state.clone();
+    let tx = tx.clone();
+    let shared = shared.clone();
     let update_info = shared.update_info.clone();
+    let mut status_bar = widgets.status_bar.clone();
     let mut cursor_readout = widgets.cursor_readout.clone();
     let mut spec_display_c = widgets.spec_display.clone();
     let mut waveform_display_c = widgets.waveform_display.clone();
@@ -527,7 +820,29 @@ fn setup_spectrogram_mouse(
                 let mx = app::event_x() - w.x();
                 let my = app::event_y() - w.y();
                 let mut st = state.borrow_mut();
-                match st.mouse_mode {
+
+                // Shift+drag draws a new ROI in any mode; outside Move and
+                // Brush modes, dragging an ROI edge or corner moves it
+                st.roi_box_drag = app::event_state().contains(fltk::enums::Shortcut::Shift);
+                st.roi_edge_drag = None;
+                if !st.roi_box_drag
+                    && st.has_audio
+                    && !matches!(st.mouse_mode, MouseMode::Move | MouseMode::Brush)
+                {
+                    let edges = roi_edges_at(&st, mx, my, w.w(), w.h());
+                    if edges.any() {
+                        st.roi_edge_drag = Some(RoiEdgeDrag {
+                            edges,
+                            start_seconds: st.fft_params.start_seconds(),
+                            stop_seconds: st.fft_params.stop_seconds(),
+                            freq_min_hz: st.view.recon_freq_min_hz,
+                            freq_max_hz: st.view.recon_freq_max_hz,
+                        });
+                        return true;
+                    }
+                }
+
+                match st.drag_mode() {
                     MouseMode::Time => {
                         let time = local_x_to_time(&st, mx, w.w());
                         let audio_pos = (time - st.recon_start_seconds()).max(0.0);
@@ -552,6 +867,16 @@ fn setup_spectrogram_mouse(
                             current_y: clamp_local_y(my, w.h()),
                         });
                     }
+                    MouseMode::Brush => {
+                        // Strokes edit the focus spectrogram, which a running
+                        // FFT or reconstruction is about to replace or read
+                        if !st.is_processing && st.focus_spectrogram.is_some() {
+                            st.brush_hover = Some((mx, my));
+                            let mut stroke = BrushStroke::new(st.brush_gain_db);
+                            stroke.dabs.push(brush_dab_at(&st, mx, my, w.w(), w.h()));
+                            st.brush_stroke = Some(stroke);
+                        }
+                    }
                 }
                 drop(st);
                 spec_display_c.redraw();
@@ -564,9 +889,23 @@ fn setup_spectrogram_mouse(
                 let tx_norm = mx as f64 / w.w() as f64;
                 let ty_norm = 1.0 - (my as f32 / w.h() as f32); // flip Y
 
-                let st = state.borrow();
+                let mut st = state.borrow_mut();
                 let time = st.view.x_to_time(tx_norm);
                 let freq = st.view.y_to_freq(ty_norm);
+                st.pointer_time_freq = Some((time, freq));
+
+                let hover_edges = if st.has_audio
+                    && !matches!(st.mouse_mode, MouseMode::Move | MouseMode::Brush)
+                {
+                    roi_edges_at(&st, mx, my, w.w(), w.h())
+                } else {
+                    RoiEdges::default()
+                };
+                set_window_cursor(w, roi_edge_cursor(hover_edges));
+                if st.mouse_mode == MouseMode::Brush {
+                    st.brush_hover = Some((mx, my));
+                    spec_display_c.redraw();
+                }
 
                 let in_time_roi =
                     time >= st.fft_params.start_seconds() && time <= st.fft_params.stop_seconds();
@@ -618,7 +957,19 @@ fn setup_spectrogram_mouse(
                                 .and_then(|f| f.magnitudes.get(bin_idx))
                             {
                                 let db = data::Spectrogram::magnitude_to_db(*mag);
-                                let text = format!("{:.1} Hz | {:.1} dB | {:.5}s", freq, db, time);
+                                let text = if st.show_note_ruler {
+                                    let (note, cents) = data::pitch::nearest_note(freq);
+                                    format!(
+                                        "{:.1} Hz ({} {:+.0}c) | {:.1} dB | {:.5}s",
+                                        freq,
+                                        data::pitch::note_label(note),
+                                        cents,
+                                        db,
+                                        time
+                                    )
+                                } else {
+                                    format!("{:.1} Hz | {:.1} dB | {:.5}s", freq, db, time)
+                                };
                                 dbg_log!(
                                     debug_flags::CURSOR_DBG,
                                     "Cursor",
@@ -737,7 +1088,17 @@ fn setup_spectrogram_mouse(
                 let mx = app::event_x() - w.x();
                 let my = app::event_y() - w.y();
                 let mut st = state.borrow_mut();
-                match st.mouse_mode {
+                if st.roi_edge_drag.is_some() {
+                    let time = local_x_to_time(&st, mx, w.w());
+                    let freq = local_y_to_freq(&st, my, w.h());
+                    if let Some(drag) = st.roi_edge_drag.as_mut() {
+                        drag.move_to(time, freq);
+                    }
+                    drop(st);
+                    spec_display_c.redraw();
+                    return true;
+                }
+                match st.drag_mode() {
                     MouseMode::Time => {
                         let time = local_x_to_time(&st, mx, w.w());
                         let audio_pos = (time - st.recon_start_seconds()).max(0.0);
@@ -773,6 +1134,13 @@ fn setup_spectrogram_mouse(
                             selection.current_y = clamp_local_y(my, w.h());
                         }
                     }
+                    MouseMode::Brush => {
+                        if st.brush_stroke.is_some() {
+                            paint_brush_to(&mut st, mx, my, w.w(), w.h());
+                        } else {
+                            st.brush_hover = Some((mx, my));
+                        }
+                    }
                 }
                 drop(st);
                 spec_display_c.redraw();
@@ -783,9 +1151,34 @@ fn setup_spectrogram_mouse(
                 let my = app::event_y() - w.y();
                 let mut needs_update_info = false;
                 let mut needs_redraw_all = false;
+                let mut needs_reconstruction = false;
 
                 let mut st = state.borrow_mut();
-                match st.mouse_mode {
+                if let Some(drag) = st.roi_edge_drag.take() {
+                    let (start, stop, freq_min, freq_max) = drag.ordered();
+                    let min_seconds = 1.0 / st.fft_params.sample_rate.max(1) as f64;
+                    if stop - start >= min_seconds && freq_max - freq_min >= 1.0 {
+                        st.set_roi(start, stop, freq_min, freq_max);
+                        show_roi_in_sidebar(
+                            &st,
+                            &mut input_start,
+                            &mut input_stop,
+                            &mut input_recon_freq_min,
+                            &mut input_recon_freq_max,
+                        );
+                        needs_update_info = true;
+                    }
+                    drop(st);
+                    if needs_update_info {
+                        (update_info.borrow_mut())();
+                    }
+                    redraw_all();
+                    return true;
+                }
+
+                let drag_mode = st.drag_mode();
+                st.roi_box_drag = false;
+                match drag_mode {
                     MouseMode::Time => {
                         st.audio_player.set_seeking(false);
                     }
@@ -829,39 +1222,55 @@ fn setup_spectrogram_mouse(
                                 let stop_time = local_x_to_time(&st, x0, w.w()).max(local_x_to_time(&st, x1, w.w()));
                                 let freq_max = local_y_to_freq(&st, y0, w.h());
                                 let freq_min = local_y_to_freq(&st, y1, w.h());
-                                let sample_rate = st.fft_params.sample_rate as f64;
 
-                                st.fft_params.start_sample = (start_time * sample_rate).round() as usize;
-                                st.fft_params.stop_sample = (stop_time * sample_rate).round() as usize;
-                                st.view.recon_freq_min_hz = freq_min.max(1.0);
-                                st.view.recon_freq_max_hz = freq_max.min(st.view.data_freq_max_hz);
-                                st.dirty = true;
-
-                                match st.fft_params.time_unit {
-                                    crate::data::TimeUnit::Seconds => {
-                                        input_start.set_value(&format!("{:.5}", st.fft_params.start_seconds()));
-                                        input_stop.set_value(&format!("{:.5}", st.fft_params.stop_seconds()));
-                                    }
-                                    crate::data::TimeUnit::Samples => {
-                                        input_start.set_value(&st.fft_params.start_sample.to_string());
-                                        input_stop.set_value(&st.fft_params.stop_sample.to_string());
-                                    }
-                                }
-                                input_recon_freq_min.set_value(&format!("{:.0}", st.view.recon_freq_min_hz));
-                                input_recon_freq_max.set_value(&format!("{:.0}", st.view.recon_freq_max_hz));
-                                st.invalidate_all_spectrogram_renderers();
-                                st.wave_renderer.invalidate();
+                                st.set_roi(start_time, stop_time, freq_min, freq_max);
+                                show_roi_in_sidebar(
+                                    &st,
+                                    &mut input_start,
+                                    &mut input_stop,
+                                    &mut input_recon_freq_min,
+                                    &mut input_recon_freq_max,
+                                );
                                 needs_update_info = true;
                                 needs_redraw_all = true;
                             }
                         }
                     }
+                    MouseMode::Brush => {
+                        if let Some(stroke) = st.brush_stroke.take() {
+                            let touched = st.apply_brush_stroke(stroke);
+                            app_log!(
+                                "Brush",
+                                "Stroke {} scaled {} bins",
+                                st.spectral_edits.len(),
+                                touched
+                            );
+                            // With settings waiting for a recompute, that
+                            // recompute applies the stroke along with them
+                            if st.dirty {
+                                st.status
+                                    .set_activity("Brush stroke kept (recompute to hear it)");
+                                update_status_bar(&mut status_bar, &st.status.render());
+                            } else {
+                                needs_reconstruction = true;
+                            }
+                            needs_redraw_all = true;
+                        }
+                    }
                 }
                 drop(st);
 
                 if needs_update_info {
                     (update_info.borrow_mut())();
                 }
+                if needs_reconstruction {
+                    crate::callbacks_file::spawn_reconstruction_only(
+                        &state,
+                        &tx,
+                        &shared,
+                        &mut status_bar,
+                    );
+                }
                 if needs_redraw_all {
                     redraw_all();
                 } else {
@@ -870,6 +1279,12 @@ fn setup_spectrogram_mouse(
                 true
             }
             Event::Leave => {
+                let mut st = state.borrow_mut();
+                st.brush_hover = None;
+                st.pointer_time_freq = None;
+                drop(st);
+                spec_display_c.redraw();
+                set_window_cursor(w, Cursor::Default);
                 cursor_readout.set_label("");
                 cursor_readout.redraw();
                 true
@@ -908,7 +1323,7 @@ fn setup_waveform_mouse(
                 let my = app::event_y() - w.y();
                 let mut st = state.borrow_mut();
                 match st.mouse_mode {
-                    MouseMode::Time => {
+                    MouseMode::Time | MouseMode::Brush => {
                         let time = local_x_to_time(&st, mx, w.w());
                         let audio_pos = (time - st.recon_start_seconds()).max(0.0);
                         st.audio_player.set_seeking(true);
@@ -942,7 +1357,7 @@ fn setup_waveform_mouse(
                 let my = app::event_y() - w.y();
                 let mut st = state.borrow_mut();
                 match st.mouse_mode {
-                    MouseMode::Time => {
+                    MouseMode::Time | MouseMode::Brush => {
                         let time = local_x_to_time(&st, mx, w.w());
                         let audio_pos = (time - st.recon_start_seconds()).max(0.0);
                         st.audio_player.seek_to(audio_pos);
@@ -986,7 +1401,7 @@ fn setup_waveform_mouse(
 
                 let mut st = state.borrow_mut();
                 match st.mouse_mode {
-                    MouseMode::Time => {
+                    MouseMode::Time | MouseMode::Brush => {
                         st.audio_player.set_seeking(false);
                     }
                     MouseMode::Move => {
@@ -1123,6 +1538,13 @@ fn setup_waveform_draw(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
                     .draw(&[], 44100, 0.0, &view, cursor_x, w.x(), w.y(), w.w(), w.h());
             }
             st.reconstructed_audio = audio_opt;
+
+            if st.show_beat_grid
+                && let Some(grid) = st.beat_grid()
+            {
+                draw_beat_lines(w, &view, &grid);
+            }
+            draw_markers(w, &view, &st.markers, false);
         }
         // State borrow released — axis callbacks can borrow freely.
 
@@ -1161,26 +1583,30 @@ fn setup_freq_axis_draw(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
             return;
         }
 
-        // Generate frequency ticks locked to Hz values (stable during scrolling)
-        let ticks = generate_freq_ticks(
-            st.view.freq_min_hz,
-            st.view.freq_max_hz,
-            &|f| st.view.freq_to_y(f),
-            w.h(),
-        );
+        if st.show_note_ruler {
+            draw_note_ruler_axis(w, &st.view);
+        } else {
+            // Generate frequency ticks locked to Hz values (stable during scrolling)
+            let ticks = generate_freq_ticks(
+                st.view.freq_min_hz,
+                st.view.freq_max_hz,
+                &|f| st.view.freq_to_y(f),
+                w.h(),
+            );
 
-        fltk::draw::set_font(Font::Helvetica, 9);
-        for &(freq, y_norm) in &ticks {
-            let py = w.y() + w.h() - (y_norm * w.h() as f32) as i32;
+            fltk::draw::set_font(Font::Helvetica, 9);
+            for &(freq, y_norm) in &ticks {
+                let py = w.y() + w.h() - (y_norm * w.h() as f32) as i32;
 
-            // Notch tick mark (right-aligned, pointing toward spectrogram)
-            fltk::draw::set_draw_color(theme::color(theme::BORDER));
-            fltk::draw::draw_line(w.x() + w.w() - 6, py, w.x() + w.w(), py);
+                // Notch tick mark (right-aligned, pointing toward spectrogram)
+                fltk::draw::set_draw_color(theme::color(theme::BORDER));
+                fltk::draw::draw_line(w.x() + w.w() - 6, py, w.x() + w.w(), py);
 
-            // Label text - always integers with commas
-            let label = format_freq_label(freq);
-            fltk::draw::set_draw_color(theme::color(theme::TEXT_SECONDARY));
-            fltk::draw::draw_text(&label, w.x() + 2, py + 3);
+                // Label text - always integers with commas
+                let label = format_freq_label(freq);
+                fltk::draw::set_draw_color(theme::color(theme::TEXT_SECONDARY));
+                fltk::draw::draw_text(&label, w.x() + 2, py + 3);
+            }
         }
 
         // Draw boundary lines for recon freq range
@@ -1202,6 +1628,213 @@ fn setup_freq_axis_draw(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
     });
 }
 
+// ── Note ruler (Display > Note Ruler) ──
+
+/// Pixel row of a pitch (in semitones above C0, fractional between notes)
+/// inside widget `w`, clamped to the widget like `ViewState::freq_to_y`.
+fn note_py(w: &impl WidgetExt, view: &data::ViewState, semitones: f32) -> f32 {
+    let t = view.freq_to_y(data::pitch::semitones_to_hz(semitones));
+    (w.y() + w.h()) as f32 - t * w.h() as f32
+}
+
+/// Piano keyboard strip beside the spectrogram and note names in place of
+/// the Hz labels.
+fn draw_note_ruler_axis(w: &impl WidgetExt, view: &data::ViewState) {
+    let notes = data::pitch::notes_in_range(view.freq_min_hz, view.freq_max_hz);
+    let strip_x = w.x() + w.w() - PIANO_STRIP_W;
+    fltk::draw::push_clip(w.x(), w.y(), w.w(), w.h());
+
+    // Keys: each note owns the band half a semitone either side of its pitch
+    fltk::draw::set_draw_color(theme::color(theme::TEXT_PRIMARY));
+    fltk::draw::draw_rectf(strip_x, w.y(), PIANO_STRIP_W, w.h());
+    for note in notes.start() - 1..=notes.end() + 1 {
+        let top = note_py(w, view, note as f32 + 0.5).round() as i32;
+        let bottom = note_py(w, view, note as f32 - 0.5).round() as i32;
+        if bottom - top < 2 {
+            // Too narrow for keys; keep just the octave boundaries readable
+            if note.rem_euclid(12) == 0 && bottom > top {
+                fltk::draw::set_draw_color(theme::color(theme::TEXT_DISABLED));
+                fltk::draw::draw_line(strip_x, bottom, strip_x + PIANO_STRIP_W, bottom);
+            }
+            continue;
+        }
+        if data::pitch::is_black_key(note) {
+            fltk::draw::set_draw_color(theme::color(theme::BG_DARK));
+            fltk::draw::draw_rectf(strip_x, top, PIANO_STRIP_W * 2 / 3, bottom - top);
+        } else if matches!(note.rem_euclid(12), 0 | 5) {
+            // B|C and E|F: two white keys meet without a black key between
+            fltk::draw::set_draw_color(theme::color(theme::TEXT_DISABLED));
+            fltk::draw::draw_line(strip_x, bottom, strip_x + PIANO_STRIP_W, bottom);
+        }
+    }
+
+    let note_y = |note: i32| note_py(w, view, note as f32);
+    fltk::draw::set_font(Font::Helvetica, 9);
+    for note in note_ruler_labels(notes, &note_y, NOTE_LABEL_GAP_PX) {
+        let py = note_y(note) as i32;
+        fltk::draw::set_draw_color(theme::color(theme::BORDER));
+        fltk::draw::draw_line(strip_x - 4, py, strip_x, py);
+        let color = if note.rem_euclid(12) == 0 {
+            theme::TEXT_PRIMARY
+        } else {
+            theme::TEXT_SECONDARY
+        };
+        fltk::draw::set_draw_color(theme::color(color));
+        fltk::draw::draw_text(&data::pitch::note_label(note), w.x() + 2, py + 3);
+    }
+    fltk::draw::pop_clip();
+}
+
+/// Notes to name on the note ruler, low to high: every C, plus the other
+/// notes wherever each semitone has room for a label, keeping labels at
+/// least `min_gap` pixels apart.
+fn note_ruler_labels(
+    notes: std::ops::RangeInclusive<i32>,
+    note_y: &dyn Fn(i32) -> f32,
+    min_gap: f32,
+) -> Vec<i32> {
+    let mut labels: Vec<i32> = Vec::new();
+    let mut last_y: Option<f32> = None;
+    for note in notes {
+        let y = note_y(note);
+        let room_for_each = (note_y(note + 1) - y).abs() >= min_gap;
+        if note.rem_euclid(12) != 0 && !room_for_each {
+            continue;
+        }
+        if last_y.is_some_and(|last| (last - y).abs() < min_gap) {
+            continue;
+        }
+        labels.push(note);
+        last_y = Some(y);
+    }
+    labels
+}
+
+/// Faint horizontal lines at the equal-tempered pitches on the spectrogram:
+/// solid at each C, dotted for the other notes where they are far enough
+/// apart to read.
+fn draw_note_lines(w: &impl WidgetExt, view: &data::ViewState) {
+    fltk::draw::push_clip(w.x(), w.y(), w.w(), w.h());
+    for note in data::pitch::notes_in_range(view.freq_min_hz, view.freq_max_hz) {
+        let y = note_py(w, view, note as f32);
+        let py = y as i32;
+        if note.rem_euclid(12) == 0 {
+            fltk::draw::set_draw_color(theme::color(theme::TEXT_DISABLED));
+            fltk::draw::set_line_style(fltk::draw::LineStyle::Solid, 1);
+        } else if (note_py(w, view, note as f32 + 1.0) - y).abs() >= NOTE_LINE_GAP_PX {
+            fltk::draw::set_draw_color(theme::color(theme::BORDER));
+            fltk::draw::set_line_style(fltk::draw::LineStyle::Dot, 1);
+        } else {
+            continue;
+        }
+        fltk::draw::draw_line(w.x(), py, w.x() + w.w(), py);
+    }
+    fltk::draw::set_line_style(fltk::draw::LineStyle::Solid, 0);
+    fltk::draw::pop_clip();
+}
+
+/// Vertical lines on the detected beats: solid where a bar starts, dotted
+/// for the beats in between while they are far enough apart to read.
+fn draw_beat_lines(w: &impl WidgetExt, view: &data::ViewState, grid: &BeatGrid) {
+    let range = view.time_max_sec - view.time_min_sec;
+    if range <= 0.0 {
+        return;
+    }
+    let px_per_sec = w.w() as f64 / range;
+    let beat_px = grid.beat_seconds() * px_per_sec;
+    if beat_px * (grid.beats_per_bar as f64) < BEAT_LINE_GAP_PX {
+        return;
+    }
+
+    fltk::draw::push_clip(w.x(), w.y(), w.w(), w.h());
+    for (time_sec, bar) in grid.beats_in(view.time_min_sec, view.time_max_sec) {
+        if bar {
+            fltk::draw::set_draw_color(theme::color(theme::TEXT_DISABLED));
+            fltk::draw::set_line_style(fltk::draw::LineStyle::Solid, 1);
+        } else if beat_px >= BEAT_LINE_GAP_PX {
+            fltk::draw::set_draw_color(theme::color(theme::BORDER));
+            fltk::draw::set_line_style(fltk::draw::LineStyle::Dot, 1);
+        } else {
+            continue;
+        }
+        let px = w.x() + ((time_sec - view.time_min_sec) * px_per_sec) as i32;
+        fltk::draw::draw_line(px, w.y(), px, w.y() + w.h());
+    }
+    fltk::draw::set_line_style(fltk::draw::LineStyle::Solid, 0);
+    fltk::draw::pop_clip();
+}
+
+/// Markers as vertical lines, with a dot at the frequency of those placed
+/// on the spectrogram and, when `labels` is set, their names along the top.
+fn draw_markers(w: &impl WidgetExt, view: &data::ViewState, markers: &[Marker], labels: bool) {
+    let range = view.time_max_sec - view.time_min_sec;
+    if range <= 0.0 {
+        return;
+    }
+
+    fltk::draw::push_clip(w.x(), w.y(), w.w(), w.h());
+    fltk::draw::set_draw_color(theme::color(theme::ACCENT_YELLOW));
+    fltk::draw::set_font(Font::Helvetica, 10);
+    for marker in markers
+        .iter()
+        .filter(|m| m.time_seconds >= view.time_min_sec && m.time_seconds <= view.time_max_sec)
+    {
+        let px = w.x() + ((marker.time_seconds - view.time_min_sec) / range * w.w() as f64) as i32;
+        fltk::draw::draw_line(px, w.y(), px, w.y() + w.h());
+        if let Some(freq) = marker.freq_hz
+            && (view.freq_min_hz..=view.freq_max_hz).contains(&freq)
+        {
+            let py = w.y() + w.h() - (view.freq_to_y(freq) * w.h() as f32) as i32;
+            fltk::draw::draw_rectf(px - 3, py - 3, 7, 7);
+        }
+        if labels {
+            fltk::draw::draw_text(&marker.name, px + 3, w.y() + 12);
+        }
+    }
+    fltk::draw::pop_clip();
+}
+
+/// The dB colorbar in a small panel in the top-right corner of the
+/// spectrogram (Display > dB Colorbar), from the LUT the spectrogram was
+/// just drawn with, so threshold, ceiling, brightness and gamma all show.
+fn draw_colorbar_legend(w: &impl WidgetExt, lut: &ColorLUT) {
+    let font_size = 10;
+    let tick_len = 4;
+    let pad = 6;
+    let bar_w = 12;
+    let bar_h = (w.h() / 3).clamp(COLORBAR_MIN_H, COLORBAR_MAX_H);
+    let panel_w = pad + bar_w + tick_len + 3 + font_size * 3 + pad;
+    let panel_h = pad + font_size + 4 + bar_h + font_size / 2 + pad;
+    if w.w() < panel_w + 2 * COLORBAR_MARGIN || w.h() < panel_h + 2 * COLORBAR_MARGIN {
+        return;
+    }
+
+    let panel_x = w.x() + w.w() - panel_w - COLORBAR_MARGIN;
+    let panel_y = w.y() + COLORBAR_MARGIN;
+    fltk::draw::push_clip(w.x(), w.y(), w.w(), w.h());
+    fltk::draw::set_draw_color(theme::color(theme::BG_DARK));
+    fltk::draw::draw_rectf(panel_x, panel_y, panel_w, panel_h);
+    fltk::draw::set_draw_color(theme::color(theme::BORDER));
+    fltk::draw::draw_rect(panel_x, panel_y, panel_w, panel_h);
+    if let Err(e) = draw_colorbar(
+        lut,
+        panel_x + pad,
+        panel_y + pad + font_size + 4,
+        bar_w,
+        bar_h,
+        font_size,
+        tick_len,
+    ) {
+        dbg_log!(
+            debug_flags::RENDER_DBG,
+            "Render",
+            "Colorbar draw failed: {:?}",
+            e
+        );
+    }
+    fltk::draw::pop_clip();
+}
+
 // ── Time axis labels ──
 fn setup_time_axis_draw(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
     let state = state.clone();
@@ -1234,42 +1867,18 @@ fn setup_time_axis_draw(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
         let right_gutter = crate::layout::SPEC_RIGHT_GUTTER_W;
         let drawable_w = (w.w() - left_gutter - right_gutter).max(1);
 
-        // Smart adaptive time labels: target ~1 label per 80px, find a nice step
-        let range = st.view.visible_time_range();
-        let target_labels = (drawable_w as f64 / 80.0).max(2.0);
-        let raw_step = range / target_labels;
-        // Snap to nice step values
-        let nice_steps = [
-            0.001, 0.002, 0.005, 0.01, 0.02, 0.05, 0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 15.0, 30.0,
-            60.0, 120.0, 300.0, 600.0,
-        ];
-        let step = nice_steps
-            .iter()
-            .find(|&&s| s >= raw_step)
-            .copied()
-            .unwrap_or(raw_step);
-
-        let mut t = (st.view.time_min_sec / step).ceil() * step;
-        while t <= st.view.time_max_sec {
+        let (step, ticks) =
+            generate_time_ticks(st.view.time_min_sec, st.view.time_max_sec, drawable_w);
+        for t in ticks {
             let x_norm = st.view.time_to_x(t);
             let px = w.x() + left_gutter + ((x_norm * drawable_w as f64) as i32);
-            let label = if step < 0.01 {
-                format!("{:.3}s", t)
-            } else if step < 0.1 {
-                format!("{:.2}s", t)
-            } else if step < 1.0 {
-                format!("{:.1}s", t)
-            } else {
-                format_time(t)
-            };
+            let label = format_time_label(t, step);
             fltk::draw::draw_text(&label, px - 15, w.y() + 14);
 
             // Tick mark
             fltk::draw::set_draw_color(theme::color(theme::BORDER));
             fltk::draw::draw_line(px, w.y(), px, w.y() + 4);
             fltk::draw::set_draw_color(theme::color(theme::TEXT_SECONDARY));
-
-            t += step;
         }
 
         // Draw boundary lines for processing time range
@@ -1365,7 +1974,7 @@ fn setup_scrubber_draw(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
 ///
 /// Ticks are LOCKED to frequency values - scrolling moves them smoothly in pixel space.
 /// Recalculation happens when: zoom changes, window resizes, or scale slider moves.
-fn generate_freq_ticks(
+pub(crate) fn generate_freq_ticks(
     freq_min_hz: f32,
     freq_max_hz: f32,
     freq_to_y: &dyn Fn(f32) -> f32,
@@ -1485,11 +2094,56 @@ fn round_to_nice_freq(freq: f32, local_step: f32) -> f32 {
 /// - 1000 → "1,000"
 /// - 21100 → "21,100"
 /// - 211000 → "211,000"
-fn format_freq_label(freq: f32) -> String {
+pub(crate) fn format_freq_label(freq: f32) -> String {
     let rounded = freq.round() as i64;
     format_with_commas(rounded)
 }
 
+/// Time axis ticks for a view `drawable_w` pixels wide.
+///
+/// Smart adaptive time labels: targets ~1 label per 80px and snaps the step
+/// to a nice value. Returns (step, tick times in seconds).
+pub(crate) fn generate_time_ticks(
+    time_min: f64,
+    time_max: f64,
+    drawable_w: i32,
+) -> (f64, Vec<f64>) {
+    let range = time_max - time_min;
+    let target_labels = (drawable_w as f64 / 80.0).max(2.0);
+    let raw_step = range / target_labels;
+    // Snap to nice step values
+    let nice_steps = [
+        0.001, 0.002, 0.005, 0.01, 0.02, 0.05, 0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 15.0, 30.0,
+        60.0, 120.0, 300.0, 600.0,
+    ];
+    let step = nice_steps
+        .iter()
+        .find(|&&s| s >= raw_step)
+        .copied()
+        .unwrap_or(raw_step);
+
+    let mut ticks = Vec::new();
+    let mut t = (time_min / step).ceil() * step;
+    while t <= time_max {
+        ticks.push(t);
+        t += step;
+    }
+    (step, ticks)
+}
+
+/// Format a time tick label with as many decimals as the tick step needs.
+pub(crate) fn format_time_label(t: f64, step: f64) -> String {
+    if step < 0.01 {
+        format!("{:.3}s", t)
+    } else if step < 0.1 {
+        format!("{:.2}s", t)
+    } else if step < 1.0 {
+        format!("{:.1}s", t)
+    } else {
+        format_time(t)
+    }
+}
+
 /// Format an integer with comma thousand separators.
 fn format_with_commas(n: i64) -> String {
     let (prefix, digits) = if n < 0 {
@@ -1510,13 +2164,69 @@ fn format_with_commas(n: i64) -> String {
     result
 }
 
+/// Draw the dB colorbar: ceiling at the top, threshold at the bottom, with
+/// ticks at a nice dB step. Shared by the on-screen legend and image export.
+pub(crate) fn draw_colorbar(
+    lut: &ColorLUT,
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+    font_size: i32,
+    tick_len: i32,
+) -> Result<(), FltkError> {
+    let (threshold_db, ceiling_db) = lut.db_range();
+    let db_at = |row: i32| -> f32 {
+        let t = row as f32 / (h - 1).max(1) as f32;
+        ceiling_db - (ceiling_db - threshold_db) * t
+    };
+
+    let mut bar = Vec::with_capacity((w * h * 3) as usize);
+    for row in 0..h {
+        let (r, g, b) = lut.lookup_db(db_at(row));
+        for _ in 0..w {
+            bar.extend_from_slice(&[r, g, b]);
+        }
+    }
+    let mut bar_image = RgbImage::new(&bar, w, h, ColorDepth::Rgb8)?;
+    bar_image.draw(x, y, w, h);
+    fltk::draw::set_draw_color(theme::color(theme::BORDER));
+    fltk::draw::draw_rect(x - 1, y - 1, w + 2, h + 2);
+
+    fltk::draw::set_font(Font::Helvetica, font_size);
+    fltk::draw::set_draw_color(theme::color(theme::TEXT_PRIMARY));
+    fltk::draw::draw_text("dB", x, y - font_size / 2);
+
+    let range = ceiling_db - threshold_db;
+    if range <= 0.0 {
+        return Ok(());
+    }
+    // About one label per four lines of text
+    let target_labels = (h as f32 / (font_size * 4) as f32).max(2.0);
+    let step = nice_step_value(range / target_labels);
+    let mut db = (threshold_db / step).ceil() * step;
+    while db <= ceiling_db + 1e-3 {
+        let py = y + ((ceiling_db - db) / range * (h - 1) as f32) as i32;
+        fltk::draw::set_draw_color(theme::color(theme::BORDER));
+        fltk::draw::draw_line(x + w, py, x + w + tick_len, py);
+        fltk::draw::set_draw_color(theme::color(theme::TEXT_SECONDARY));
+        fltk::draw::draw_text(
+            &format!("{}", db.round() as i32),
+            x + w + tick_len + 3,
+            py + font_size / 3,
+        );
+        db += step;
+    }
+    Ok(())
+}
+
 /// Compute a "nice" step value using the 1-2-5 pattern across decades.
 ///
 /// Given any raw step size, rounds it UP to the nearest value from:
 /// ..., 0.5, 1, 2, 5, 10, 20, 50, 100, 200, 500, 1000, 2000, 5000, ...
 ///
 /// This ensures tick spacing uses human-friendly round numbers.
-fn nice_step_value(raw: f32) -> f32 {
+pub(crate) fn nice_step_value(raw: f32) -> f32 {
     if raw <= 0.0 {
         return 1.0;
     }
@@ -1,16 +1,22 @@
 use std::cell::RefCell;
 use std::rc::Rc;
-use std::sync::{mpsc, Arc};
+use std::sync::{Arc, mpsc};
 
 use fltk::{app, dialog, prelude::*};
+use musickbeets::audio::AUDIO_FILE_EXTENSIONS;
 
-use crate::app_state::{update_status_bar, AppState, FftStage, SharedCallbacks, WorkerMessage};
+use crate::app_state::{AppState, FftStage, SharedCallbacks, WorkerMessage, update_status_bar};
+use crate::callbacks_ui;
 use crate::csv_export;
-use crate::data::{AudioData, TimeUnit, WindowType};
+use crate::data::{AudioData, ChannelMode, MelFilterbank, TimeUnit, WindowType};
 use crate::debug_flags;
+use crate::image_export;
 use crate::layout::Widgets;
 use crate::processing::fft_engine::FftEngine;
-use crate::processing::reconstructor::Reconstructor;
+use crate::processing::instrument_export;
+use crate::processing::reconstructor::{DEFAULT_GRIFFIN_LIM_ITERATIONS, Reconstructor};
+use crate::processing::transcription::{self, TranscriptionOptions};
+use crate::session::Session;
 use crate::validation::{parse_or_zero_f32, parse_or_zero_f64, parse_or_zero_usize};
 
 // ═══════════════════════════════════════════════════════════════════════════
@@ -116,7 +122,7 @@ fn setup_open_callback(
         }
 
         let mut chooser = dialog::NativeFileChooser::new(dialog::NativeFileChooserType::BrowseFile);
-        chooser.set_filter("*.wav");
+        chooser.set_filter("Audio files\t*.{wav,mp3,flac,ogg}");
         chooser.show();
 
         let filename = chooser.filename();
@@ -124,83 +130,258 @@ fn setup_open_callback(
             return;
         }
 
-        // Read normalization settings before spawning thread
-        let (do_normalize, norm_peak) = {
-            let st = state.borrow();
-            (st.normalize_audio, st.normalize_peak)
-        };
+        spawn_audio_load(&state, &tx, &shared_cb, &mut status_bar, filename, AudioSource::File);
+    });
+}
 
-        // Mark as processing so re-entry is blocked
-        {
+/// File > Open Tracker Song: render a tracker song (.csv or .json) offline
+/// and analyze it like an opened WAV file.
+pub fn open_tracker_song_dialog(
+    state: &Rc<RefCell<AppState>>,
+    tx: &mpsc::Sender<WorkerMessage>,
+    shared: &SharedCallbacks,
+    status_bar: &mut fltk::output::MultilineOutput,
+) {
+    if state.borrow().is_processing {
+        update_status_bar(status_bar, "Still processing... please wait.");
+        app_log!("Open", "Blocked: still processing");
+        return;
+    }
+
+    let mut chooser = dialog::NativeFileChooser::new(dialog::NativeFileChooserType::BrowseFile);
+    chooser.set_filter("Tracker songs\t*.{csv,json}");
+    chooser.show();
+
+    let filename = chooser.filename();
+    if filename.as_os_str().is_empty() {
+        return;
+    }
+
+    spawn_audio_load(
+        state,
+        tx,
+        shared,
+        status_bar,
+        filename,
+        AudioSource::TrackerSong,
+    );
+}
+
+/// Open a file dropped on the window, given the text FLTK pastes after the
+/// drop. Audio files load like Open, `.csv` like Load FFT, and `.json` like
+/// File > Open Tracker Song. Only the first file of a multi-file drop opens.
+pub fn open_dropped_file(
+    state: &Rc<RefCell<AppState>>,
+    tx: &mpsc::Sender<WorkerMessage>,
+    shared: &SharedCallbacks,
+    status_bar: &mut fltk::output::MultilineOutput,
+    dropped: &str,
+) {
+    let Some(filename) = dropped_file_path(dropped) else {
+        return;
+    };
+    app_log!("Open", "Dropped file: {:?}", filename);
+
+    if state.borrow().is_processing {
+        update_status_bar(status_bar, "Still processing... please wait.");
+        app_log!("Open", "Blocked: still processing");
+        return;
+    }
+
+    let extension = filename
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "csv" => spawn_csv_load(state, tx, shared, status_bar, filename),
+        "json" => spawn_audio_load(
+            state,
+            tx,
+            shared,
+            status_bar,
+            filename,
+            AudioSource::TrackerSong,
+        ),
+        ext if AUDIO_FILE_EXTENSIONS.contains(&ext) => {
+            spawn_audio_load(state, tx, shared, status_bar, filename, AudioSource::File)
+        }
+        _ => {
             let mut st = state.borrow_mut();
-            st.is_processing = true;
-            st.status.set_activity("Loading audio...");
-            st.status.start_timing("Audio load");
+            st.status.set_activity(&format!(
+                "Can't open {}: not an audio file or FFT CSV",
+                filename.file_name().unwrap_or_default().to_string_lossy()
+            ));
+            update_status_bar(status_bar, &st.status.render());
         }
-        (shared_cb.disable_for_processing.borrow_mut())();
-        (shared_cb.set_btn_busy_mode.borrow_mut())();
+    }
+}
 
-        update_status_bar(&mut status_bar, "Loading audio...");
+/// First path in dropped text: one entry per line, either a plain path
+/// (Windows, macOS) or a percent-encoded `file://` URI (X11, Wayland).
+fn dropped_file_path(dropped: &str) -> Option<std::path::PathBuf> {
+    let line = dropped.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let Some(uri_path) = line.strip_prefix("file://") else {
+        return Some(line.into());
+    };
+    // Percent-decode byte by byte so multi-byte UTF-8 names survive
+    let bytes = uri_path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    Some(String::from_utf8_lossy(&decoded).into_owned().into())
+}
 
-        // Move file I/O + normalization to a background thread to keep the GUI responsive.
-        // The heavy work (disk read + peak scan) runs off the main thread.
-        // State setup happens later in the AudioLoaded handler (main_fft.rs poll loop).
-        dbg_log!(
-            debug_flags::FILE_IO_DBG,
-            "File",
-            "Opening audio file: {:?} (normalize={}, peak={:.2})",
-            filename,
-            do_normalize,
-            norm_peak
-        );
-        app_log!("Open", "Loading file: {:?}", filename);
-        let tx_clone = tx.clone();
-        let filename_for_thread = filename.clone();
-        std::thread::spawn(move || {
-            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                let mut audio = AudioData::from_wav_file(&filename_for_thread)
-                    .unwrap_or_else(|e| panic!("Failed to load: {}", e));
+/// Where the audio of an Open comes from.
+#[derive(Clone, Copy, PartialEq)]
+enum AudioSource {
+    /// WAV, MP3, FLAC or Ogg Vorbis file
+    File,
+    /// Rendered offline by the tracker engine
+    TrackerSong,
+}
+
+/// Load (or render) audio on a background thread and hand it to the poll
+/// loop as `WorkerMessage::AudioLoaded`.
+fn spawn_audio_load(
+    state: &Rc<RefCell<AppState>>,
+    tx: &mpsc::Sender<WorkerMessage>,
+    shared: &SharedCallbacks,
+    status_bar: &mut fltk::output::MultilineOutput,
+    filename: std::path::PathBuf,
+    source: AudioSource,
+) {
+    // Read normalization, channel and sample rate settings before spawning thread
+    let (do_normalize, norm_peak, channel_mode, load_sample_rate) = {
+        let st = state.borrow();
+        (
+            st.normalize_audio,
+            st.normalize_peak,
+            st.channel_mode,
+            st.load_sample_rate,
+        )
+    };
+
+    let activity = match source {
+        AudioSource::File => "Loading audio...",
+        AudioSource::TrackerSong => "Rendering tracker song...",
+    };
+
+    // Mark as processing so re-entry is blocked
+    {
+        let mut st = state.borrow_mut();
+        st.is_processing = true;
+        st.status.set_activity(activity);
+        st.status.start_timing("Audio load");
+    }
+    (shared.disable_for_processing.borrow_mut())();
+    (shared.set_btn_busy_mode.borrow_mut())();
+
+    update_status_bar(status_bar, activity);
+
+    // Move file I/O + normalization to a background thread to keep the GUI responsive.
+    // The heavy work (disk read + peak scan, or the song render) runs off the main thread.
+    // State setup happens later in the AudioLoaded handler (main_fft.rs poll loop).
+    dbg_log!(
+        debug_flags::FILE_IO_DBG,
+        "File",
+        "Opening audio file: {:?} (normalize={}, peak={:.2})",
+        filename,
+        do_normalize,
+        norm_peak
+    );
+    app_log!("Open", "Loading file: {:?}", filename);
+    let tx_clone = tx.clone();
+    let filename_for_thread = filename.clone();
+    std::thread::spawn(move || {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut audio = match source {
+                AudioSource::File => AudioData::from_audio_file(&filename_for_thread)
+                    .unwrap_or_else(|e| panic!("Failed to load: {}", e)),
+                AudioSource::TrackerSong => {
+                    let (audio, messages) = AudioData::from_tracker_song(&filename_for_thread)
+                        .unwrap_or_else(|e| panic!("Failed to render: {:#}", e));
+                    for message in &messages {
+                        app_log!("Open", "{}", message);
+                    }
+                    audio
+                }
+            };
+            app_log!(
+                "Open",
+                "File loaded: {} samples, {} Hz, {:.2}s",
+                audio.num_samples(),
+                audio.sample_rate,
+                audio.duration_seconds
+            );
+            if load_sample_rate != 0 && load_sample_rate != audio.sample_rate {
                 app_log!(
                     "Open",
-                    "File loaded: {} samples, {} Hz, {:.2}s",
-                    audio.num_samples(),
+                    "Converting {} Hz to {} Hz",
                     audio.sample_rate,
-                    audio.duration_seconds
+                    load_sample_rate
+                );
+                audio.resample(load_sample_rate);
+            }
+            if channel_mode != ChannelMode::Mix && audio.select_channel(channel_mode) {
+                app_log!(
+                    "Open",
+                    "Channel: {} of {}",
+                    channel_mode.name(),
+                    audio.num_channels()
                 );
+            }
 
-                let norm_gain = if do_normalize {
-                    let gain = audio.normalize(norm_peak);
-                    if gain != 1.0 {
-                        app_log!(
-                            "Open",
-                            "Audio normalized: gain = {:.3}x (original peak = {:.3})",
-                            gain,
-                            norm_peak / gain
-                        );
-                    }
-                    gain
-                } else {
-                    1.0
-                };
-                (audio, norm_gain)
-            }));
-            match result {
-                Ok((audio, norm_gain)) => {
-                    tx_clone.send(WorkerMessage::AudioLoaded(
-                        audio, filename_for_thread, norm_gain
-                    )).ok();
-                }
-                Err(panic) => {
-                    let msg = panic.downcast_ref::<String>().cloned()
-                        .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
-                        .unwrap_or_else(|| "unknown panic".to_string());
-                    app_log!("Open", "PANIC: {}", msg);
-                    tx_clone.send(WorkerMessage::WorkerPanic(msg)).ok();
+            let norm_gain = if do_normalize {
+                let gain = audio.normalize(norm_peak);
+                if gain != 1.0 {
+                    app_log!(
+                        "Open",
+                        "Audio normalized: gain = {:.3}x (original peak = {:.3})",
+                        gain,
+                        norm_peak / gain
+                    );
                 }
+                gain
+            } else {
+                1.0
+            };
+            (audio, norm_gain)
+        }));
+        match result {
+            Ok((audio, norm_gain)) => {
+                tx_clone
+                    .send(WorkerMessage::AudioLoaded(
+                        audio,
+                        filename_for_thread,
+                        norm_gain,
+                    ))
+                    .ok();
             }
-        });
-
-        update_status_bar(&mut status_bar, "Loading audio file...");
+            Err(panic) => {
+                let msg = panic
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                app_log!("Open", "PANIC: {}", msg);
+                tx_clone.send(WorkerMessage::WorkerPanic(msg)).ok();
+            }
+        }
     });
 }
 
@@ -336,62 +517,66 @@ fn setup_load_fft_callback(
             return;
         }
 
-        dbg_log!(
-            debug_flags::FILE_IO_DBG,
-            "File",
-            "Loading FFT CSV from {:?}",
-            filename
-        );
+        spawn_csv_load(&state, &tx, &shared_cb, &mut status_bar, filename);
+    });
+}
 
-        // Start timing and set status
-        {
-            let mut st = state.borrow_mut();
-            st.is_processing = true;
-            st.status.set_activity("Loading FFT data...");
-            st.status.start_timing("FFT load");
-        }
-        (shared_cb.disable_for_processing.borrow_mut())();
-        (shared_cb.set_btn_busy_mode.borrow_mut())();
-        let max_chars = ((status_bar.w() - 16).max(40) / 7).max(20) as usize;
-        update_status_bar(
-            &mut status_bar,
-            &state.borrow().status.render_wrapped(max_chars),
-        );
+/// Import an FFT CSV on a background thread and hand it to the poll loop as
+/// `WorkerMessage::CsvLoaded`.
+fn spawn_csv_load(
+    state: &Rc<RefCell<AppState>>,
+    tx: &mpsc::Sender<WorkerMessage>,
+    shared: &SharedCallbacks,
+    status_bar: &mut fltk::output::MultilineOutput,
+    filename: std::path::PathBuf,
+) {
+    dbg_log!(
+        debug_flags::FILE_IO_DBG,
+        "File",
+        "Loading FFT CSV from {:?}",
+        filename
+    );
 
-        let tx_clone = tx.clone();
-        let filename_for_thread = filename.clone();
-        std::thread::spawn(move || {
-            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                csv_export::import_from_csv(&filename_for_thread)
-            }));
-            match result {
-                Ok(Ok((spec, params, recon, view))) => {
-                    tx_clone
-                        .send(WorkerMessage::CsvLoaded(Ok((
-                            spec,
-                            params,
-                            recon,
-                            view,
-                            filename_for_thread,
-                        ))))
-                        .ok();
-                }
-                Ok(Err(e)) => {
-                    tx_clone
-                        .send(WorkerMessage::CsvLoaded(Err(e.to_string())))
-                        .ok();
-                }
-                Err(panic) => {
-                    let msg = panic
-                        .downcast_ref::<String>()
-                        .cloned()
-                        .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
-                        .unwrap_or_else(|| "unknown panic".to_string());
-                    app_log!("CSV load thread", "PANIC: {}", msg);
-                    tx_clone.send(WorkerMessage::WorkerPanic(msg)).ok();
-                }
+    // Start timing and set status
+    {
+        let mut st = state.borrow_mut();
+        st.is_processing = true;
+        st.status.set_activity("Loading FFT data...");
+        st.status.start_timing("FFT load");
+    }
+    (shared.disable_for_processing.borrow_mut())();
+    (shared.set_btn_busy_mode.borrow_mut())();
+    let max_chars = ((status_bar.w() - 16).max(40) / 7).max(20) as usize;
+    update_status_bar(status_bar, &state.borrow().status.render_wrapped(max_chars));
+
+    let tx_clone = tx.clone();
+    std::thread::spawn(move || {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            csv_export::import_from_csv(&filename)
+        }));
+        match result {
+            Ok(Ok((spec, params, recon, view))) => {
+                tx_clone
+                    .send(WorkerMessage::CsvLoaded(Ok((
+                        spec, params, recon, view, filename,
+                    ))))
+                    .ok();
             }
-        });
+            Ok(Err(e)) => {
+                tx_clone
+                    .send(WorkerMessage::CsvLoaded(Err(e.to_string())))
+                    .ok();
+            }
+            Err(panic) => {
+                let msg = panic
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                app_log!("CSV load thread", "PANIC: {}", msg);
+                tx_clone.send(WorkerMessage::WorkerPanic(msg)).ok();
+            }
+        }
     });
 }
 
@@ -448,6 +633,7 @@ pub fn handle_csv_load_result(
         let mut st = state.borrow_mut();
         st.fft_params = imported_params.clone();
         st.current_filename = csv_fname.clone();
+        st.audio_path = None;
 
         // Compute adaptive dB ceiling from actual data max amplitude
         let max_mag = imported_spec.max_magnitude();
@@ -475,12 +661,28 @@ pub fn handle_csv_load_result(
             st.view.recon_freq_max_hz = fmax;
         }
 
+        // Magnitude-only data: the phases are placeholders, so resynthesize
+        // with Griffin-Lim unless the user already picked an iteration count
+        if !imported_spec.has_phase && st.view.recon_griffin_lim_iterations == 0 {
+            st.view.recon_griffin_lim_iterations = DEFAULT_GRIFFIN_LIM_ITERATIONS;
+            app_log!(
+                "File",
+                "CSV has no phase column: Griffin-Lim on ({} iterations)",
+                DEFAULT_GRIFFIN_LIM_ITERATIONS
+            );
+        }
+
         let imported_spec = Arc::new(imported_spec);
         st.spectrogram = Some(imported_spec.clone());
         st.overview_spectrogram = None;
         st.overview_spec_params = None;
         st.focus_spectrogram = Some(imported_spec);
         st.focus_spec_params = Some(imported_params.clone());
+        st.spectral_edits.clear();
+        st.markers.clear();
+        st.noise_reduced = false;
+        st.clear_comparison();
+        st.live = None;
         st.invalidate_all_spectrogram_renderers();
         st.wave_renderer.invalidate();
         st.recon_start_sample = imported_params.start_sample;
@@ -506,6 +708,7 @@ pub fn handle_csv_load_result(
     (shared.enable_spec_widgets.borrow_mut())();
     (shared.update_info.borrow_mut())();
     (shared.update_seg_label.borrow_mut())();
+    (shared.update_markers.borrow_mut())();
 
     let csv_status = {
         let mut st = state.borrow_mut();
@@ -546,7 +749,11 @@ pub fn handle_csv_load_result(
 
     let progress = state.borrow().progress_counter.clone();
     progress.store(0, std::sync::atomic::Ordering::Relaxed);
-    state.borrow_mut().progress_total = frame_end.saturating_sub(frame_start);
+    {
+        let mut st = state.borrow_mut();
+        st.progress_total =
+            Reconstructor::progress_steps(frame_end.saturating_sub(frame_start), &st.view);
+    }
 
     (shared.set_btn_cancel_mode.borrow_mut())();
 
@@ -664,6 +871,687 @@ fn setup_save_wav_callback(
     });
 }
 
+// ═══════════════════════════════════════════════════════════════════════════
+//  SESSIONS (File > Save Session / Open Session)
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Save the opened file's path with the current analysis, view, and
+/// reconstruction settings to a session JSON file.
+pub fn save_session_dialog(
+    state: &Rc<RefCell<AppState>>,
+    status_bar: &mut fltk::output::MultilineOutput,
+) {
+    let Some(session) = Session::from_app_state(&state.borrow()) else {
+        dialog::alert_default("No audio file to save a session for!\n\nOpen an audio file first.");
+        return;
+    };
+
+    let mut chooser = dialog::NativeFileChooser::new(dialog::NativeFileChooserType::BrowseSaveFile);
+    chooser.set_filter("Sessions\t*.json");
+    let stem = session.audio_path.file_stem().unwrap_or_default();
+    chooser.set_preset_file(&format!("{}_session.json", stem.to_string_lossy()));
+    chooser.show();
+
+    let mut filename = chooser.filename();
+    if filename.as_os_str().is_empty() {
+        return;
+    }
+    if filename.extension().is_none() {
+        filename.set_extension("json");
+    }
+
+    match session.save(&filename) {
+        Ok(()) => {
+            app_log!("File", "Session saved: {:?}", filename);
+            let mut st = state.borrow_mut();
+            st.status.set_activity("Session saved");
+            update_status_bar(status_bar, &st.status.render());
+        }
+        Err(e) => {
+            app_log!("File", "Session save FAILED: {:#}", e);
+            dialog::alert_default(&format!("Error saving session:\n{:#}", e));
+            update_status_bar(status_bar, "Session save failed");
+        }
+    }
+}
+
+/// Open a session file: apply its settings, sync the sidebar, and reload its
+/// audio. The range, zoom, and dB ceiling are restored by the poll loop once
+/// the audio has loaded (`AppState::pending_session`).
+pub fn open_session_dialog(
+    widgets: &Widgets,
+    state: &Rc<RefCell<AppState>>,
+    tx: &mpsc::Sender<WorkerMessage>,
+    shared: &SharedCallbacks,
+    status_bar: &mut fltk::output::MultilineOutput,
+) {
+    if state.borrow().is_processing {
+        update_status_bar(status_bar, "Still processing... please wait.");
+        app_log!("Open", "Blocked: still processing");
+        return;
+    }
+
+    let mut chooser = dialog::NativeFileChooser::new(dialog::NativeFileChooserType::BrowseFile);
+    chooser.set_filter("Sessions\t*.json");
+    chooser.show();
+
+    let filename = chooser.filename();
+    if filename.as_os_str().is_empty() {
+        return;
+    }
+
+    let session = match Session::load(&filename) {
+        Ok(session) => session,
+        Err(e) => {
+            app_log!("Open", "Session open FAILED: {:#}", e);
+            dialog::alert_default(&format!("Error opening session:\n{:#}", e));
+            return;
+        }
+    };
+    if !session.audio_path.is_file() {
+        dialog::alert_default(&format!(
+            "The session's audio file is missing:\n{}",
+            session.audio_path.display()
+        ));
+        return;
+    }
+    app_log!(
+        "Open",
+        "Session {:?}: reopening {:?}",
+        filename,
+        session.audio_path
+    );
+
+    {
+        let mut st = state.borrow_mut();
+        let st = &mut *st;
+        session.apply_settings(&mut st.fft_params, &mut st.view);
+        st.noise_reduction_db = session.noise_reduction_db;
+        st.invalidate_all_spectrogram_renderers();
+    }
+    callbacks_ui::sync_widgets_to_state(widgets, &state.borrow());
+
+    // Songs render through the tracker engine, like File > Open Tracker Song
+    let audio_path = session.audio_path.clone();
+    let source = match audio_path.extension().and_then(|e| e.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("csv") || ext.eq_ignore_ascii_case("json") => {
+            AudioSource::TrackerSong
+        }
+        _ => AudioSource::File,
+    };
+    state.borrow_mut().pending_session = Some(session);
+    spawn_audio_load(state, tx, shared, status_bar, audio_path, source);
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//  EXPORT IMAGE (File > Export Image)
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Ask for a file name and an image size, then write the current spectrogram
+/// view (axes, colorbar, current colormap) as a PNG, or an SVG when the name
+/// ends in `.svg`. Runs on the main thread: drawing needs FLTK.
+pub fn export_image_dialog(
+    state: &Rc<RefCell<AppState>>,
+    status_bar: &mut fltk::output::MultilineOutput,
+) {
+    if state.borrow().active_spectrogram().is_none() {
+        dialog::alert_default("No spectrogram to export!\n\nOpen an audio file first.");
+        return;
+    }
+
+    let mut chooser = dialog::NativeFileChooser::new(dialog::NativeFileChooserType::BrowseSaveFile);
+    chooser.set_filter("PNG Image\t*.png\nSVG Image\t*.svg");
+    chooser.set_preset_file("spectrogram.png");
+    chooser.show();
+
+    let mut filename = chooser.filename();
+    if filename.as_os_str().is_empty() {
+        return;
+    }
+    if filename.extension().is_none() {
+        filename.set_extension("png");
+    }
+
+    let default_size = {
+        let st = state.borrow();
+        format!("{}x{}", st.image_export_width, st.image_export_height)
+    };
+    let Some(size_text) =
+        dialog::input_default("Image size in pixels (width x height):", &default_size)
+    else {
+        return;
+    };
+    let (width, height) = match image_export::parse_image_size(&size_text) {
+        Ok(size) => size,
+        Err(e) => {
+            dialog::alert_default(&format!("Invalid image size:\n{}", e));
+            return;
+        }
+    };
+
+    {
+        let mut st = state.borrow_mut();
+        st.image_export_width = width;
+        st.image_export_height = height;
+        st.status.set_activity("Exporting image...");
+        st.status.start_timing("Image export");
+    }
+    update_status_bar(status_bar, &state.borrow().status.render());
+    dbg_log!(
+        debug_flags::FILE_IO_DBG,
+        "File",
+        "Exporting image: {}x{}, file {:?}",
+        width,
+        height,
+        filename
+    );
+
+    let result = image_export::export_spectrogram_image(&state.borrow(), &filename, width, height);
+    match result {
+        Ok(()) => {
+            dbg_log!(
+                debug_flags::FILE_IO_DBG,
+                "File",
+                "Image export complete: {:?}",
+                filename
+            );
+            let max_chars = ((status_bar.w() - 16).max(40) / 7).max(20) as usize;
+            let done_status = {
+                let mut st = state.borrow_mut();
+                st.status.set_activity("Image saved");
+                st.status.finish_timing();
+                st.status.set_activity("Ready");
+                st.status.render_wrapped(max_chars)
+            };
+            update_status_bar(status_bar, &done_status);
+        }
+        Err(e) => {
+            app_log!("File", "Image export FAILED: {:#}", e);
+            state.borrow_mut().status.cancel_timing();
+            dialog::alert_default(&format!("Error exporting image:\n{:#}", e));
+            update_status_bar(status_bar, "Image export failed");
+        }
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//  EXPORT MEL BANDS (File > Export Mel Bands)
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Ask for a file name, then write the analyzed spectrogram (the focus layer
+/// when there is one) pooled into the current number of mel bands as CSV.
+/// The bands cover 0 Hz to Nyquist, whatever the view is zoomed to.
+pub fn export_mel_csv_dialog(
+    state: &Rc<RefCell<AppState>>,
+    status_bar: &mut fltk::output::MultilineOutput,
+) {
+    let Some(spec) = state.borrow().active_spectrogram() else {
+        dialog::alert_default("No spectrogram to export!\n\nOpen an audio file first.");
+        return;
+    };
+
+    let mut chooser = dialog::NativeFileChooser::new(dialog::NativeFileChooserType::BrowseSaveFile);
+    chooser.set_filter("*.csv");
+    chooser.set_preset_file("mel_bands.csv");
+    chooser.show();
+
+    let mut filename = chooser.filename();
+    if filename.as_os_str().is_empty() {
+        return;
+    }
+    if filename.extension().is_none() {
+        filename.set_extension("csv");
+    }
+
+    let mel_bands = {
+        let mut st = state.borrow_mut();
+        st.status.set_activity("Exporting mel bands...");
+        st.status.start_timing("Mel export");
+        st.view.mel_bands
+    };
+    update_status_bar(status_bar, &state.borrow().status.render());
+
+    let bank = MelFilterbank::new(
+        &spec.frequencies,
+        mel_bands,
+        0.0,
+        spec.frequencies.last().copied().unwrap_or(0.0),
+    );
+    match csv_export::export_mel_csv(&spec, &bank, &filename) {
+        Ok(()) => {
+            let max_chars = ((status_bar.w() - 16).max(40) / 7).max(20) as usize;
+            let done_status = {
+                let mut st = state.borrow_mut();
+                st.status.set_activity("Mel bands saved");
+                st.status.finish_timing();
+                st.status.set_activity("Ready");
+                st.status.render_wrapped(max_chars)
+            };
+            update_status_bar(status_bar, &done_status);
+        }
+        Err(e) => {
+            app_log!("File", "Mel export FAILED: {:#}", e);
+            state.borrow_mut().status.cancel_timing();
+            dialog::alert_default(&format!("Error exporting mel bands:\n{:#}", e));
+            update_status_bar(status_bar, "Mel export failed");
+        }
+    }
+}
+
+/// File > Export to Tracker CSV: transcribe the partials of the ROI into a
+/// song the tracker can play. Asks for the tempo (remembered for the next
+/// export), then for the file.
+pub fn export_tracker_csv_dialog(
+    state: &Rc<RefCell<AppState>>,
+    status_bar: &mut fltk::output::MultilineOutput,
+) {
+    if state.borrow().active_spectrogram().is_none() {
+        dialog::alert_default("No spectrogram to transcribe!\n\nOpen an audio file first.");
+        return;
+    }
+
+    // Offer the detected tempo when there is one, so the rows follow the beat
+    let detected = state.borrow_mut().beat_grid();
+    let (prompt, current) = match detected {
+        Some(grid) => (
+            format!("Tempo of the song (BPM, {:.1} detected):", grid.bpm),
+            format!("{:.1}", grid.bpm),
+        ),
+        None => (
+            "Tempo of the song (BPM):".to_string(),
+            state.borrow().transcription_bpm.to_string(),
+        ),
+    };
+    let Some(text) = dialog::input_default(&prompt, &current) else {
+        return;
+    };
+    let bpm = match text.trim().parse::<f32>() {
+        Ok(bpm) if (20.0..=999.0).contains(&bpm) => bpm,
+        _ => {
+            dialog::alert_default("Tempo must be a number from 20 to 999 BPM.");
+            return;
+        }
+    };
+    state.borrow_mut().transcription_bpm = bpm;
+
+    let mut chooser = dialog::NativeFileChooser::new(dialog::NativeFileChooserType::BrowseSaveFile);
+    chooser.set_filter("*.csv");
+    chooser.set_preset_file("song.csv");
+    chooser.show();
+
+    let mut filename = chooser.filename();
+    if filename.as_os_str().is_empty() {
+        return;
+    }
+    if filename.extension().is_none() {
+        filename.set_extension("csv");
+    }
+
+    let (tracks, start_seconds, title) = {
+        let mut st = state.borrow_mut();
+        st.status.set_activity("Transcribing partials...");
+        st.status.start_timing("Transcription");
+        let title = std::path::Path::new(&st.current_filename)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Transcription".to_string());
+        // At the detected tempo, row 0 goes on the beat at or before the
+        // ROI start so the rows line up with the beat grid
+        let start_seconds = match detected {
+            Some(grid) if (grid.bpm - bpm).abs() < 0.06 => {
+                grid.beat_at_or_before(st.fft_params.start_seconds())
+            }
+            _ => st.fft_params.start_seconds(),
+        };
+        (st.partial_tracks(), start_seconds, title)
+    };
+    update_status_bar(status_bar, &state.borrow().status.render());
+
+    let options = TranscriptionOptions {
+        bpm,
+        ..TranscriptionOptions::default()
+    };
+    let tracks = tracks.unwrap_or_default();
+    let song = transcription::transcribe(&tracks, start_seconds, &title, &options);
+    match std::fs::write(&filename, &song.csv) {
+        Ok(()) => {
+            app_log!(
+                "File",
+                "Transcribed {} notes on {} channels ({} dropped) to {:?}",
+                song.note_count,
+                song.channel_count,
+                song.dropped_notes,
+                filename
+            );
+            let max_chars = ((status_bar.w() - 16).max(40) / 7).max(20) as usize;
+            let done_status = {
+                let mut st = state.borrow_mut();
+                st.status.set_activity(&format!(
+                    "Tracker CSV saved: {} notes, {} rows",
+                    song.note_count, song.row_count
+                ));
+                st.status.finish_timing();
+                st.status.render_wrapped(max_chars)
+            };
+            update_status_bar(status_bar, &done_status);
+        }
+        Err(e) => {
+            app_log!("File", "Tracker CSV export FAILED: {}", e);
+            state.borrow_mut().status.cancel_timing();
+            dialog::alert_default(&format!("Error writing tracker CSV:\n{}", e));
+            update_status_bar(status_bar, "Tracker CSV export failed");
+        }
+    }
+}
+
+/// File > Export as Instrument: save the reconstructed audio (the ROI) as a
+/// WAV next to a tracker instrument file and add a grain instrument that
+/// plays it to that file, so songs can use it by name ("c4 pad").
+pub fn export_instrument_dialog(
+    state: &Rc<RefCell<AppState>>,
+    status_bar: &mut fltk::output::MultilineOutput,
+) {
+    let (audio, source) = {
+        let st = state.borrow();
+        let Some(audio) = st.reconstructed_audio.clone() else {
+            dialog::alert_default("No reconstructed audio to export!\n\nReconstruct audio first.");
+            return;
+        };
+        let source = std::path::Path::new(&st.current_filename)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "the analyzer".to_string());
+        (audio, source)
+    };
+
+    let suggested = std::path::Path::new(&source)
+        .file_stem()
+        .and_then(|stem| instrument_export::instrument_name(&stem.to_string_lossy()).ok())
+        .unwrap_or_else(|| "sample".to_string());
+    let Some(text) =
+        dialog::input_default("Instrument name (used in cells, e.g. c4 pad):", &suggested)
+    else {
+        return;
+    };
+    let name = match instrument_export::instrument_name(&text) {
+        Ok(name) => name,
+        Err(reason) => {
+            dialog::alert_default(&format!("Invalid instrument name.\n\n{}", reason));
+            return;
+        }
+    };
+
+    let mut chooser = dialog::NativeFileChooser::new(dialog::NativeFileChooserType::BrowseSaveFile);
+    chooser.set_filter("Instrument file\t*.toml");
+    chooser.set_preset_file("instruments.toml");
+    chooser.show();
+
+    let mut bank_path = chooser.filename();
+    if bank_path.as_os_str().is_empty() {
+        return;
+    }
+    if bank_path.extension().is_none() {
+        bank_path.set_extension("toml");
+    }
+
+    let bank_text = std::fs::read_to_string(&bank_path).unwrap_or_default();
+    if instrument_export::bank_has_instrument(&bank_text, &name)
+        && dialog::choice2_default(
+            &format!(
+                "{} already has an instrument named '{}'.\n\nReplace it?",
+                bank_path.display(),
+                name
+            ),
+            "Cancel",
+            "Replace",
+            "",
+        ) != Some(1)
+    {
+        return;
+    }
+
+    {
+        let mut st = state.borrow_mut();
+        st.status.set_activity("Exporting instrument...");
+        st.status.start_timing("Instrument export");
+    }
+    update_status_bar(status_bar, &state.borrow().status.render());
+
+    match instrument_export::export_instrument(&audio, &bank_path, &name, &source) {
+        Ok(wav_path) => {
+            app_log!(
+                "File",
+                "Exported instrument '{}' ({:.2}s) to {:?} with sample {:?}",
+                name,
+                audio.duration_seconds,
+                bank_path,
+                wav_path
+            );
+            let max_chars = ((status_bar.w() - 16).max(40) / 7).max(20) as usize;
+            let done_status = {
+                let mut st = state.borrow_mut();
+                st.status
+                    .set_activity(&format!("Instrument '{}' saved", name));
+                st.status.finish_timing();
+                st.status.render_wrapped(max_chars)
+            };
+            update_status_bar(status_bar, &done_status);
+        }
+        Err(e) => {
+            app_log!("File", "Instrument export FAILED: {:#}", e);
+            state.borrow_mut().status.cancel_timing();
+            dialog::alert_default(&format!("Error exporting instrument:\n{:#}", e));
+            update_status_bar(status_bar, "Instrument export failed");
+        }
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//  CHANNEL SELECTION (Analysis > Channel)
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Switch the analyzed channel (L+R, L, R, Mid, Side). The loaded file's
+/// channels are kept in memory, so the new signal is rebuilt (and normalized
+/// again) without reloading, then everything is recomputed via the Rerun
+/// button. With no file loaded, the mode just applies to the next one.
+pub fn set_channel_mode(
+    state: &Rc<RefCell<AppState>>,
+    mode: ChannelMode,
+    btn_rerun: &mut fltk::button::Button,
+    status_bar: &mut fltk::output::MultilineOutput,
+) {
+    let mut st = state.borrow_mut();
+    if st.channel_mode == mode {
+        return;
+    }
+    st.channel_mode = mode;
+
+    let Some(current) = st.audio_data.clone() else {
+        return;
+    };
+    let mut audio = (*current).clone();
+    if !audio.select_channel(mode) {
+        // Reconstructed-only session (FFT data loaded from CSV): no channels
+        return;
+    }
+    let norm_gain = if st.normalize_audio {
+        audio.normalize(st.normalize_peak)
+    } else {
+        1.0
+    };
+    app_log!(
+        "Channel",
+        "Analyzing {} of {} channel(s), norm gain = {:.3}x",
+        mode.name(),
+        audio.num_channels(),
+        norm_gain
+    );
+    st.audio_data = Some(Arc::new(audio));
+    st.source_norm_gain = norm_gain;
+
+    if st.is_processing {
+        // Let the running job finish; the next recompute (Space, Rerun,
+        // or Play) picks up the new channel
+        st.dirty = true;
+        st.status
+            .set_activity(&format!("Channel: {} (recompute to apply)", mode.name()));
+        update_status_bar(status_bar, &st.status.render());
+        return;
+    }
+    st.status.set_activity(&format!("Channel: {}", mode.name()));
+    update_status_bar(status_bar, &st.status.render());
+    drop(st);
+    btn_rerun.do_callback();
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//  LIVE INPUT (Analysis > Live Input)
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Start live input: the loaded file is dropped and the spectrogram scrolls
+/// with the microphone, analyzed with the sidebar's FFT settings. The
+/// controls stay locked until Snapshot or Stop.
+pub fn start_live_input(
+    state: &Rc<RefCell<AppState>>,
+    shared: &SharedCallbacks,
+    status_bar: &mut fltk::output::MultilineOutput,
+    win: &mut fltk::window::Window,
+) {
+    let mut st = state.borrow_mut();
+    if st.live.is_some() {
+        return;
+    }
+    if st.is_processing {
+        drop(st);
+        update_status_bar(status_bar, "Still processing... please wait.");
+        return;
+    }
+    st.audio_player.stop();
+    st.transport.is_playing = false;
+    if let Err(e) = st.start_live() {
+        drop(st);
+        app_log!("Live", "Failed to start: {}", e);
+        dialog::alert_default(&format!("Could not start live input:\n{}", e));
+        return;
+    }
+    let params = st.fft_params.clone();
+    app_log!(
+        "Live",
+        "Capturing at {} Hz (window={}, overlap={}%)",
+        params.sample_rate,
+        params.window_length,
+        params.overlap_percent
+    );
+    st.status.clear_timings();
+    st.status.set_activity("Live input");
+    update_status_bar(status_bar, &st.status.render());
+    drop(st);
+
+    (shared.disable_for_processing.borrow_mut())();
+    (shared.set_btn_busy_mode.borrow_mut())();
+    win.set_label("muSickBeets - Live input");
+}
+
+/// Pause or resume the live spectrogram.
+pub fn toggle_live_pause(
+    state: &Rc<RefCell<AppState>>,
+    status_bar: &mut fltk::output::MultilineOutput,
+) {
+    let mut st = state.borrow_mut();
+    let Some(live) = st.live.as_mut() else {
+        return;
+    };
+    live.paused = !live.paused;
+    let paused = live.paused;
+    st.status.set_activity(if paused {
+        "Live input paused"
+    } else {
+        "Live input"
+    });
+    update_status_bar(status_bar, &st.status.render());
+}
+
+/// Stop live input and analyze the last seconds of it like an opened file,
+/// so it can be edited, reconstructed and exported.
+pub fn snapshot_live_input(
+    state: &Rc<RefCell<AppState>>,
+    tx: &mpsc::Sender<WorkerMessage>,
+    shared: &SharedCallbacks,
+    status_bar: &mut fltk::output::MultilineOutput,
+    win: &mut fltk::window::Window,
+) {
+    let mut st = state.borrow_mut();
+    if st.live.is_none() {
+        return;
+    }
+    let Some(mut audio) = st.stop_live() else {
+        drop(st);
+        live_input_stopped(state, shared, status_bar, win, "Nothing captured yet");
+        return;
+    };
+    let norm_gain = if st.normalize_audio {
+        audio.normalize(st.normalize_peak)
+    } else {
+        1.0
+    };
+    app_log!(
+        "Live",
+        "Snapshot: {} samples ({:.2}s), norm gain = {:.3}x",
+        audio.num_samples(),
+        audio.duration_seconds,
+        norm_gain
+    );
+    // The AudioLoaded handler expects a load in progress
+    st.is_processing = true;
+    st.status.set_activity("Analyzing live snapshot...");
+    st.status.start_timing("Audio load");
+    update_status_bar(status_bar, &st.status.render());
+    drop(st);
+
+    tx.send(WorkerMessage::AudioLoaded(
+        audio,
+        std::path::PathBuf::from("Live snapshot"),
+        norm_gain,
+    ))
+    .ok();
+}
+
+/// Stop live input and discard what was captured.
+pub fn stop_live_input(
+    state: &Rc<RefCell<AppState>>,
+    shared: &SharedCallbacks,
+    status_bar: &mut fltk::output::MultilineOutput,
+    win: &mut fltk::window::Window,
+) {
+    {
+        let mut st = state.borrow_mut();
+        if st.live.is_none() {
+            return;
+        }
+        st.stop_live();
+    }
+    live_input_stopped(state, shared, status_bar, win, "Live input stopped");
+}
+
+/// Back to an empty analyzer after live input ends without a snapshot.
+fn live_input_stopped(
+    state: &Rc<RefCell<AppState>>,
+    shared: &SharedCallbacks,
+    status_bar: &mut fltk::output::MultilineOutput,
+    win: &mut fltk::window::Window,
+    message: &str,
+) {
+    {
+        let mut st = state.borrow_mut();
+        st.current_filename.clear();
+        st.status.set_activity(message);
+        update_status_bar(status_bar, &st.status.render());
+    }
+    (shared.enable_after_processing.borrow_mut())();
+    (shared.set_btn_normal_mode.borrow_mut())();
+    win.set_label("muSickBeets FFT Analyzer");
+}
+
 // ═══════════════════════════════════════════════════════════════════════════
 //  RERUN CALLBACK (Recompute FFT + Reconstruct)
 // ═══════════════════════════════════════════════════════════════════════════
@@ -706,6 +1594,9 @@ pub fn setup_rerun_callback(
             if !has_audio && !has_spectrogram {
                 return; // Nothing to process
             }
+            if st.live.is_some() {
+                return; // Live input has no settled spectrogram to rebuild
+            }
             if st.is_processing {
                 st.cancel_flag.store(true, std::sync::atomic::Ordering::Relaxed);
                 st.status.set_activity("Cancelling...");
@@ -864,106 +1755,120 @@ pub fn setup_rerun_callback(
         } else {
             // ── Reconstruction-only path: spectrogram loaded from CSV, no source audio ──
             // Skip FFT, go straight to reconstruction with updated recon params.
-            let recon_data = {
-                let mut st = state.borrow_mut();
-                st.is_processing = true;
-                st.dirty = false;
-                st.status.clear_timings();
-                st.status.start_timing("Reconstruction");
-                st.status.set_activity("Reconstructing...");
-                let cancel = st.new_cancel_flag();
-
-                let spec = st.active_spectrogram().unwrap();
-                let params = st.fft_params.clone();
-                let view = st.view.clone();
-                let proc_time_min = params.start_seconds();
-                let proc_time_max = params.stop_seconds();
-                (spec, params, view, proc_time_min, proc_time_max, cancel)
-            };
-            (shared_cb.disable_for_processing.borrow_mut())();
-            (shared_cb.set_btn_cancel_mode.borrow_mut())();
+            spawn_reconstruction_only(&state, &tx, &shared_cb, &mut status_bar);
+        }
+    });
+}
 
-            (update_info.borrow_mut())();
+/// Reconstruct the active spectrogram as it is, without an FFT. Used by
+/// Recompute when there is no source audio (a spectrogram loaded from CSV)
+/// and after a brush stroke edits the focus spectrogram.
+pub fn spawn_reconstruction_only(
+    state: &Rc<RefCell<AppState>>,
+    tx: &mpsc::Sender<WorkerMessage>,
+    shared: &SharedCallbacks,
+    status_bar: &mut fltk::output::MultilineOutput,
+) {
+    let recon_data = {
+        let mut st = state.borrow_mut();
+        st.is_processing = true;
+        st.dirty = false;
+        st.status.clear_timings();
+        st.status.start_timing("Reconstruction");
+        st.status.set_activity("Reconstructing...");
+        let cancel = st.new_cancel_flag();
 
-            let recon_status = state.borrow().status.render();
-            update_status_bar(&mut status_bar, &recon_status);
-            app::awake();
+        let spec = st.active_spectrogram().unwrap();
+        let params = st.fft_params.clone();
+        let view = st.view.clone();
+        let proc_time_min = params.start_seconds();
+        let proc_time_max = params.stop_seconds();
+        (spec, params, view, proc_time_min, proc_time_max, cancel)
+    };
+    (shared.disable_for_processing.borrow_mut())();
+    (shared.set_btn_cancel_mode.borrow_mut())();
 
-            let tx_clone = tx.clone();
-            let (spec, params, view, proc_time_min, proc_time_max, cancel) = recon_data;
+    (shared.update_info.borrow_mut())();
 
-            // Compute frame index range for the processing time window
-            let frame_start = spec
-                .frames
-                .iter()
-                .position(|f| f.time_seconds >= proc_time_min)
-                .unwrap_or(0);
-            let frame_end = spec
-                .frames
-                .iter()
-                .rposition(|f| f.time_seconds <= proc_time_max)
-                .map(|i| i + 1)
-                .unwrap_or(0);
-
-            if frame_start < frame_end {
-                if let Some(start_sample) = Reconstructor::reconstruction_start_sample(
-                    &spec,
-                    &params,
-                    frame_start..frame_end,
-                ) {
-                    state.borrow_mut().recon_start_sample = start_sample;
-                }
-            }
+    let recon_status = state.borrow().status.render();
+    update_status_bar(status_bar, &recon_status);
+    app::awake();
 
-            dbg_log!(
-                debug_flags::FFT_DBG,
-                "FFT",
-                "Rerun (recon-only): frames {}..{}, freq_count={}, freq_range={:.0}-{:.0}Hz",
-                frame_start,
-                frame_end,
-                view.recon_freq_count,
-                view.recon_freq_min_hz,
-                view.recon_freq_max_hz
-            );
+    let tx_clone = tx.clone();
+    let (spec, params, view, proc_time_min, proc_time_max, cancel) = recon_data;
 
-            let progress = state.borrow().progress_counter.clone();
-            progress.store(0, std::sync::atomic::Ordering::Relaxed);
-            state.borrow_mut().progress_total = frame_end.saturating_sub(frame_start);
-
-            std::thread::spawn(move || {
-                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                    Reconstructor::reconstruct_range(
-                        &spec,
-                        &params,
-                        &view,
-                        frame_start..frame_end,
-                        &cancel,
-                        Some(&progress),
-                    )
-                }));
-                match result {
-                    Ok(reconstructed) => {
-                        if cancel.load(std::sync::atomic::Ordering::Relaxed) {
-                            tx_clone
-                                .send(WorkerMessage::Cancelled("Reconstruction".to_string()))
-                                .ok();
-                        } else {
-                            tx_clone
-                                .send(WorkerMessage::ReconstructionComplete(reconstructed))
-                                .ok();
-                        }
-                    }
-                    Err(panic) => {
-                        let msg = panic
-                            .downcast_ref::<String>()
-                            .cloned()
-                            .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
-                            .unwrap_or_else(|| "unknown panic".to_string());
-                        app_log!("Reconstruction thread", "PANIC: {}", msg);
-                        tx_clone.send(WorkerMessage::WorkerPanic(msg)).ok();
-                    }
+    // Compute frame index range for the processing time window
+    let frame_start = spec
+        .frames
+        .iter()
+        .position(|f| f.time_seconds >= proc_time_min)
+        .unwrap_or(0);
+    let frame_end = spec
+        .frames
+        .iter()
+        .rposition(|f| f.time_seconds <= proc_time_max)
+        .map(|i| i + 1)
+        .unwrap_or(0);
+
+    if frame_start < frame_end {
+        if let Some(start_sample) =
+            Reconstructor::reconstruction_start_sample(&spec, &params, frame_start..frame_end)
+        {
+            state.borrow_mut().recon_start_sample = start_sample;
+        }
+    }
+
+    dbg_log!(
+        debug_flags::FFT_DBG,
+        "FFT",
+        "Rerun (recon-only): frames {}..{}, freq_count={}, freq_range={:.0}-{:.0}Hz",
+        frame_start,
+        frame_end,
+        view.recon_freq_count,
+        view.recon_freq_min_hz,
+        view.recon_freq_max_hz
+    );
+
+    let progress = state.borrow().progress_counter.clone();
+    progress.store(0, std::sync::atomic::Ordering::Relaxed);
+    {
+        let mut st = state.borrow_mut();
+        st.progress_total =
+            Reconstructor::progress_steps(frame_end.saturating_sub(frame_start), &st.view);
+    }
+
+    std::thread::spawn(move || {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            Reconstructor::reconstruct_range(
+                &spec,
+                &params,
+                &view,
+                frame_start..frame_end,
+                &cancel,
+                Some(&progress),
+            )
+        }));
+        match result {
+            Ok(reconstructed) => {
+                if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+                    tx_clone
+                        .send(WorkerMessage::Cancelled("Reconstruction".to_string()))
+                        .ok();
+                } else {
+                    tx_clone
+                        .send(WorkerMessage::ReconstructionComplete(reconstructed))
+                        .ok();
                 }
-            });
+            }
+            Err(panic) => {
+                let msg = panic
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                app_log!("Reconstruction thread", "PANIC: {}", msg);
+                tx_clone.send(WorkerMessage::WorkerPanic(msg)).ok();
+            }
         }
     });
 }
@@ -1,5 +1,6 @@
 use std::cell::{Cell, RefCell};
 use std::rc::Rc;
+use std::sync::mpsc;
 
 use fltk::{
     app, dialog,
@@ -9,15 +10,17 @@ use fltk::{
     window::Window,
 };
 
-use crate::app_state::AppState;
-use crate::data::TimeUnit;
+use crate::app_state::{AppState, SharedCallbacks, WorkerMessage, update_status_bar};
+use crate::data::{ChannelMode, LOAD_SAMPLE_RATES, MAX_MEL_BANDS, MIN_MEL_BANDS, TimeUnit};
 use crate::layout::Widgets;
+use crate::processing::reconstructor::MAX_GRIFFIN_LIM_ITERATIONS;
+use crate::processing::spectral_edit::{ERASE_GAIN_DB, MAX_BRUSH_RADIUS_PX, MIN_BRUSH_RADIUS_PX};
 use crate::validation::{
     attach_float_validation_with_recompute, attach_uint_validation_with_recompute,
 };
 
 fn shortcut_key_text() -> &'static str {
-    "Keyboard shortcuts\n\n	navigation and analysis\n  Space        Recompute + Rebuild\n  Ctrl+O       Open audio file\n  Ctrl+S       Save FFT data\n  Ctrl+L       Load FFT data\n  Ctrl+E       Export WAV\n  Ctrl+Q       Quit the program\n  Escape       Close this keys window / active dialogs\n\nMouse wheel modifiers\n  Wheel            Zoom time + frequency\n  Ctrl + Wheel     Zoom time only\n  Shift + Wheel    Zoom frequency only\n  Alt + Wheel      Pan frequency\n  Alt+Ctrl+Wheel   Pan time\n  Alt+Shift+Wheel  Pan time + frequency"
+    "Keyboard shortcuts\n\n	navigation and analysis\n  Space        Recompute + Rebuild\n  Ctrl+O       Open audio file\n  Ctrl+S       Save FFT data\n  Ctrl+L       Load FFT data\n  Ctrl+E       Export WAV\n  Ctrl+I       Export spectrogram image\n  Ctrl+Z       Undo brush stroke\n  Ctrl+B       A/B compare (original / reconstruction)\n  M            Add marker (at the pointer over the spectrogram)\n  Ctrl+Q       Quit the program\n  Escape       Close this keys window / active dialogs\n\nMouse wheel modifiers\n  Wheel            Zoom time + frequency\n  Ctrl + Wheel     Zoom time only\n  Shift + Wheel    Zoom frequency only\n  Alt + Wheel      Pan frequency\n  Alt+Ctrl+Wheel   Pan time\n  Alt+Shift+Wheel  Pan time + frequency"
 }
 
 pub fn setup_shortcut_key_button(widgets: &Widgets) {
@@ -32,7 +35,13 @@ pub fn setup_shortcut_key_button(widgets: &Widgets) {
 //  MENU CALLBACKS
 // ═══════════════════════════════════════════════════════════════════════════
 
-pub fn setup_menu_callbacks(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
+pub fn setup_menu_callbacks(
+    widgets: &Widgets,
+    state: &Rc<RefCell<AppState>>,
+    tx: &mpsc::Sender<WorkerMessage>,
+    shared: &SharedCallbacks,
+    win: &Window,
+) {
     let mut menu = widgets.menu.clone();
 
     {
@@ -46,6 +55,58 @@ pub fn setup_menu_callbacks(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
             },
         );
     }
+    {
+        let state_c = state.clone();
+        let tx = tx.clone();
+        let shared = shared.clone();
+        let mut status_bar = widgets.status_bar.clone();
+        menu.add(
+            "&File/Open Tracker Song...\t",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                crate::callbacks_file::open_tracker_song_dialog(
+                    &state_c,
+                    &tx,
+                    &shared,
+                    &mut status_bar,
+                );
+            },
+        );
+    }
+    {
+        let widgets_c = widgets.clone();
+        let state_c = state.clone();
+        let tx = tx.clone();
+        let shared = shared.clone();
+        let mut status_bar = widgets.status_bar.clone();
+        menu.add(
+            "&File/Open Session...\t",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                crate::callbacks_file::open_session_dialog(
+                    &widgets_c,
+                    &state_c,
+                    &tx,
+                    &shared,
+                    &mut status_bar,
+                );
+            },
+        );
+    }
+    {
+        let state_c = state.clone();
+        let mut status_bar = widgets.status_bar.clone();
+        menu.add(
+            "&File/Save Session...\t",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                crate::callbacks_file::save_session_dialog(&state_c, &mut status_bar);
+            },
+        );
+    }
     {
         let mut btn_save_fft = widgets.btn_save_fft.clone();
         menu.add(
@@ -79,6 +140,54 @@ pub fn setup_menu_callbacks(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
             },
         );
     }
+    {
+        let state_c = state.clone();
+        let mut status_bar = widgets.status_bar.clone();
+        menu.add(
+            "&File/Export Image...\t",
+            Shortcut::Ctrl | 'i',
+            MenuFlag::Normal,
+            move |_| {
+                crate::callbacks_file::export_image_dialog(&state_c, &mut status_bar);
+            },
+        );
+    }
+    {
+        let state_c = state.clone();
+        let mut status_bar = widgets.status_bar.clone();
+        menu.add(
+            "&File/Export Mel Bands (CSV)...\t",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                crate::callbacks_file::export_mel_csv_dialog(&state_c, &mut status_bar);
+            },
+        );
+    }
+    {
+        let state_c = state.clone();
+        let mut status_bar = widgets.status_bar.clone();
+        menu.add(
+            "&File/Export to Tracker CSV...\t",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                crate::callbacks_file::export_tracker_csv_dialog(&state_c, &mut status_bar);
+            },
+        );
+    }
+    {
+        let state_c = state.clone();
+        let mut status_bar = widgets.status_bar.clone();
+        menu.add(
+            "&File/Export as Instrument...\t",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                crate::callbacks_file::export_instrument_dialog(&state_c, &mut status_bar);
+            },
+        );
+    }
     menu.add(
         "&File/Quit\t",
         Shortcut::Ctrl | 'q',
@@ -88,6 +197,96 @@ pub fn setup_menu_callbacks(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
         },
     );
 
+    {
+        let state_c = state.clone();
+        let mut btn_rerun = widgets.btn_rerun.clone();
+        menu.add(
+            "&Edit/Undo Brush Stroke\t",
+            Shortcut::Ctrl | 'z',
+            MenuFlag::Normal,
+            move |_| {
+                remove_brush_strokes(&state_c, false, &mut btn_rerun);
+            },
+        );
+    }
+    {
+        let state_c = state.clone();
+        let mut btn_rerun = widgets.btn_rerun.clone();
+        menu.add(
+            "&Edit/Clear Brush Strokes\t",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                remove_brush_strokes(&state_c, true, &mut btn_rerun);
+            },
+        );
+    }
+    {
+        let state_c = state.clone();
+        menu.add(
+            "&Edit/Brush Size...\t",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                let current = state_c.borrow().brush_radius_px.to_string();
+                let prompt = format!(
+                    "Brush radius in pixels ({} to {}):",
+                    MIN_BRUSH_RADIUS_PX, MAX_BRUSH_RADIUS_PX
+                );
+                let Some(text) = dialog::input_default(&prompt, &current) else {
+                    return;
+                };
+                match text.trim().parse::<i32>() {
+                    Ok(radius) if (MIN_BRUSH_RADIUS_PX..=MAX_BRUSH_RADIUS_PX).contains(&radius) => {
+                        state_c.borrow_mut().brush_radius_px = radius;
+                    }
+                    _ => dialog::alert_default(&format!(
+                        "The brush radius must be a whole number from {} to {}.",
+                        MIN_BRUSH_RADIUS_PX, MAX_BRUSH_RADIUS_PX
+                    )),
+                }
+            },
+        );
+    }
+    {
+        let state_c = state.clone();
+        menu.add(
+            "&Edit/Brush Gain...\t",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                let current = state_c.borrow().brush_gain_db.to_string();
+                let prompt = format!(
+                    "Brush gain in dB, 0 or less\n({} or lower erases):",
+                    ERASE_GAIN_DB
+                );
+                let Some(text) = dialog::input_default(&prompt, &current) else {
+                    return;
+                };
+                match text.trim().parse::<f32>() {
+                    Ok(gain_db) if gain_db <= 0.0 => {
+                        state_c.borrow_mut().brush_gain_db = gain_db.max(ERASE_GAIN_DB);
+                    }
+                    _ => dialog::alert_default("The brush gain must be a number of dB, 0 or less."),
+                }
+            },
+        );
+    }
+    {
+        let state_c = state.clone();
+        let widgets_c = widgets.clone();
+        menu.add(
+            "&Edit/Add Marker...\t",
+            Shortcut::None | 'm',
+            MenuFlag::Normal,
+            move |_| {
+                // At the pointer when it is over the spectrogram
+                let at = state_c.borrow().pointer_time_freq;
+                crate::callbacks_ui::add_marker_dialog(&widgets_c, &state_c, at);
+            },
+        );
+    }
+
     {
         let mut btn_rerun = widgets.btn_rerun.clone();
         menu.add(
@@ -99,6 +298,181 @@ pub fn setup_menu_callbacks(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
             },
         );
     }
+    let current_channel = state.borrow().channel_mode;
+    for mode in ChannelMode::ALL {
+        let state_c = state.clone();
+        let mut btn_rerun = widgets.btn_rerun.clone();
+        let mut status_bar = widgets.status_bar.clone();
+        let flag = if mode == current_channel {
+            MenuFlag::Radio | MenuFlag::Value
+        } else {
+            MenuFlag::Radio
+        };
+        menu.add(
+            &format!("&Analysis/Channel/{}\t", mode.menu_label()),
+            Shortcut::None,
+            flag,
+            move |_| {
+                crate::callbacks_file::set_channel_mode(
+                    &state_c,
+                    mode,
+                    &mut btn_rerun,
+                    &mut status_bar,
+                );
+            },
+        );
+    }
+    let current_rate = state.borrow().load_sample_rate;
+    for rate in LOAD_SAMPLE_RATES {
+        let state_c = state.clone();
+        let mut status_bar = widgets.status_bar.clone();
+        let flag = if rate == current_rate {
+            MenuFlag::Radio | MenuFlag::Value
+        } else {
+            MenuFlag::Radio
+        };
+        let label = if rate == 0 {
+            "File Rate".to_string()
+        } else {
+            format!("{} Hz", rate)
+        };
+        menu.add(
+            &format!("&Analysis/Sample Rate/{}\t", label),
+            Shortcut::None,
+            flag,
+            move |_| {
+                let mut st = state_c.borrow_mut();
+                st.load_sample_rate = rate;
+                let msg = if rate == 0 {
+                    "Opened files keep their own sample rate".to_string()
+                } else {
+                    format!("Opened files are converted to {} Hz", rate)
+                };
+                st.status.set_activity(&msg);
+                update_status_bar(&mut status_bar, &st.status.render());
+            },
+        );
+    }
+
+    {
+        let state_c = state.clone();
+        let shared = shared.clone();
+        let mut status_bar = widgets.status_bar.clone();
+        let mut win = win.clone();
+        menu.add(
+            "&Analysis/Live Input/Start\t",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                crate::callbacks_file::start_live_input(
+                    &state_c,
+                    &shared,
+                    &mut status_bar,
+                    &mut win,
+                );
+            },
+        );
+    }
+    {
+        let state_c = state.clone();
+        let mut status_bar = widgets.status_bar.clone();
+        menu.add(
+            "&Analysis/Live Input/Pause or Resume\t",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                crate::callbacks_file::toggle_live_pause(&state_c, &mut status_bar);
+            },
+        );
+    }
+    {
+        let state_c = state.clone();
+        let tx = tx.clone();
+        let shared = shared.clone();
+        let mut status_bar = widgets.status_bar.clone();
+        let mut win = win.clone();
+        menu.add(
+            "&Analysis/Live Input/Snapshot\t",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                crate::callbacks_file::snapshot_live_input(
+                    &state_c,
+                    &tx,
+                    &shared,
+                    &mut status_bar,
+                    &mut win,
+                );
+            },
+        );
+    }
+    {
+        let state_c = state.clone();
+        let shared = shared.clone();
+        let mut status_bar = widgets.status_bar.clone();
+        let mut win = win.clone();
+        menu.add(
+            "&Analysis/Live Input/Stop\t",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                crate::callbacks_file::stop_live_input(
+                    &state_c,
+                    &shared,
+                    &mut status_bar,
+                    &mut win,
+                );
+            },
+        );
+    }
+
+    {
+        let state_c = state.clone();
+        let mut status_bar = widgets.status_bar.clone();
+        menu.add(
+            "&Analysis/Griffin-Lim Iterations...\t",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                let current = state_c
+                    .borrow()
+                    .view
+                    .recon_griffin_lim_iterations
+                    .to_string();
+                let prompt = format!(
+                    "Griffin-Lim phase estimation iterations\n(0 = use stored phases, max {}):",
+                    MAX_GRIFFIN_LIM_ITERATIONS
+                );
+                let Some(text) = dialog::input_default(&prompt, &current) else {
+                    return;
+                };
+                match text.trim().parse::<usize>() {
+                    Ok(iterations) if iterations <= MAX_GRIFFIN_LIM_ITERATIONS => {
+                        let mut st = state_c.borrow_mut();
+                        if st.view.recon_griffin_lim_iterations == iterations {
+                            return;
+                        }
+                        st.view.recon_griffin_lim_iterations = iterations;
+                        st.dirty = true;
+                        let activity = if iterations == 0 {
+                            "Griffin-Lim off (recompute to apply)".to_string()
+                        } else {
+                            format!(
+                                "Griffin-Lim: {} iterations (recompute to apply)",
+                                iterations
+                            )
+                        };
+                        st.status.set_activity(&activity);
+                        update_status_bar(&mut status_bar, &st.status.render());
+                    }
+                    _ => dialog::alert_default(&format!(
+                        "Iterations must be a whole number from 0 to {}.",
+                        MAX_GRIFFIN_LIM_ITERATIONS
+                    )),
+                }
+            },
+        );
+    }
 
     {
         let state_c = state.clone();
@@ -123,6 +497,184 @@ pub fn setup_menu_callbacks(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
             },
         );
     }
+
+    {
+        let state_c = state.clone();
+        let mut spec_display_c = widgets.spec_display.clone();
+        let mut freq_axis_c = widgets.freq_axis.clone();
+        let flag = if state.borrow().view.mel_view {
+            MenuFlag::Toggle | MenuFlag::Value
+        } else {
+            MenuFlag::Toggle
+        };
+        menu.add("&Display/Mel Scale\t", Shortcut::None, flag, move |_| {
+            let mut st = state_c.borrow_mut();
+            st.view.mel_view = !st.view.mel_view;
+            st.invalidate_all_spectrogram_renderers();
+            drop(st);
+            spec_display_c.redraw();
+            freq_axis_c.redraw();
+        });
+    }
+    {
+        let state_c = state.clone();
+        let mut spec_display_c = widgets.spec_display.clone();
+        let mut freq_axis_c = widgets.freq_axis.clone();
+        menu.add(
+            "&Display/Mel Bands...\t",
+            Shortcut::None,
+            MenuFlag::Normal,
+            move |_| {
+                let current = state_c.borrow().view.mel_bands.to_string();
+                let prompt = format!("Mel bands ({} to {}):", MIN_MEL_BANDS, MAX_MEL_BANDS);
+                let Some(text) = dialog::input_default(&prompt, &current) else {
+                    return;
+                };
+                match text.trim().parse::<usize>() {
+                    Ok(bands) if (MIN_MEL_BANDS..=MAX_MEL_BANDS).contains(&bands) => {
+                        let mut st = state_c.borrow_mut();
+                        st.view.mel_bands = bands;
+                        st.invalidate_all_spectrogram_renderers();
+                        drop(st);
+                        spec_display_c.redraw();
+                        freq_axis_c.redraw();
+                    }
+                    _ => dialog::alert_default(&format!(
+                        "Mel bands must be a whole number from {} to {}.",
+                        MIN_MEL_BANDS, MAX_MEL_BANDS
+                    )),
+                }
+            },
+        );
+    }
+    {
+        let state_c = state.clone();
+        let mut spec_display_c = widgets.spec_display.clone();
+        let flag = if state.borrow().show_partials {
+            MenuFlag::Toggle | MenuFlag::Value
+        } else {
+            MenuFlag::Toggle
+        };
+        menu.add(
+            "&Display/Show Partials\t",
+            Shortcut::None,
+            flag,
+            move |_| {
+                let mut st = state_c.borrow_mut();
+                st.show_partials = !st.show_partials;
+                drop(st);
+                spec_display_c.redraw();
+            },
+        );
+    }
+    {
+        let state_c = state.clone();
+        let mut spec_display_c = widgets.spec_display.clone();
+        let mut freq_axis_c = widgets.freq_axis.clone();
+        let flag = if state.borrow().show_note_ruler {
+            MenuFlag::Toggle | MenuFlag::Value
+        } else {
+            MenuFlag::Toggle
+        };
+        menu.add("&Display/Note Ruler\t", Shortcut::None, flag, move |_| {
+            let mut st = state_c.borrow_mut();
+            st.show_note_ruler = !st.show_note_ruler;
+            drop(st);
+            spec_display_c.redraw();
+            freq_axis_c.redraw();
+        });
+    }
+    {
+        let state_c = state.clone();
+        let mut spec_display_c = widgets.spec_display.clone();
+        let mut waveform_c = widgets.waveform_display.clone();
+        let mut status_bar = widgets.status_bar.clone();
+        let flag = if state.borrow().show_beat_grid {
+            MenuFlag::Toggle | MenuFlag::Value
+        } else {
+            MenuFlag::Toggle
+        };
+        menu.add("&Display/Beat Grid\t", Shortcut::None, flag, move |_| {
+            let mut st = state_c.borrow_mut();
+            st.show_beat_grid = !st.show_beat_grid;
+            if st.show_beat_grid && st.active_spectrogram().is_some() {
+                let activity = match st.beat_grid() {
+                    Some(grid) => format!("Beat grid: {:.1} BPM", grid.bpm),
+                    None => "Beat grid: no steady beat found".to_string(),
+                };
+                st.status.set_activity(&activity);
+                update_status_bar(&mut status_bar, &st.status.render());
+            }
+            drop(st);
+            spec_display_c.redraw();
+            waveform_c.redraw();
+        });
+    }
+    {
+        let state_c = state.clone();
+        let mut spec_display_c = widgets.spec_display.clone();
+        let flag = if state.borrow().show_colorbar {
+            MenuFlag::Toggle | MenuFlag::Value
+        } else {
+            MenuFlag::Toggle
+        };
+        menu.add("&Display/dB Colorbar\t", Shortcut::None, flag, move |_| {
+            let mut st = state_c.borrow_mut();
+            st.show_colorbar = !st.show_colorbar;
+            drop(st);
+            spec_display_c.redraw();
+        });
+    }
+    {
+        let state_c = state.clone();
+        let mut spec_display_c = widgets.spec_display.clone();
+        let mut status_bar = widgets.status_bar.clone();
+        menu.add(
+            "&Display/Difference View\t",
+            Shortcut::None,
+            MenuFlag::Toggle,
+            move |_| {
+                let mut st = state_c.borrow_mut();
+                st.show_difference = !st.show_difference;
+                if st.show_difference && st.difference_spectrogram.is_none() {
+                    st.status
+                        .set_activity("Difference view: shown once a reconstruction finishes");
+                    update_status_bar(&mut status_bar, &st.status.render());
+                }
+                st.invalidate_all_spectrogram_renderers();
+                drop(st);
+                spec_display_c.redraw();
+            },
+        );
+    }
+}
+
+/// Drop the last brush stroke (or all of them) and recompute from the
+/// source audio, which paints the remaining strokes onto the new focus
+/// spectrogram. Strokes on a spectrogram loaded from CSV can't be undone:
+/// the magnitudes they scaled are gone.
+fn remove_brush_strokes(
+    state: &Rc<RefCell<AppState>>,
+    all: bool,
+    btn_rerun: &mut fltk::button::Button,
+) {
+    let mut st = state.borrow_mut();
+    if st.spectral_edits.is_empty() || st.is_processing {
+        return;
+    }
+    if st.audio_data.is_none() {
+        drop(st);
+        dialog::alert_default(
+            "Brush strokes on a spectrogram loaded from CSV can't be undone.\nLoad the file again to start over.",
+        );
+        return;
+    }
+    let removed = if all { st.spectral_edits.len() } else { 1 };
+    let kept = st.spectral_edits.len() - removed;
+    st.spectral_edits.truncate(kept);
+    drop(st);
+    app_log!("Brush", "Removed {} stroke(s), {} left", removed, kept);
+    btn_rerun.do_callback();
 }
 
 // ═══════════════════════════════════════════════════════════════════════════
@@ -377,11 +929,24 @@ pub fn setup_snap_to_view(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
 //  SPACEBAR HANDLER
 // ═══════════════════════════════════════════════════════════════════════════
 
-pub fn setup_spacebar_handler(win: &mut Window, widgets: &Widgets) {
+/// Window-level handler: spacebar recompute (layer 3 of the spacebar guard),
+/// status bar placement on resize, and files dropped on the window.
+pub fn setup_spacebar_handler(
+    win: &mut Window,
+    widgets: &Widgets,
+    state: &Rc<RefCell<AppState>>,
+    tx: &mpsc::Sender<WorkerMessage>,
+    shared: &SharedCallbacks,
+) {
     let mut btn_rerun = widgets.btn_rerun.clone();
     let mut status_fft = widgets.status_fft.clone();
     let mut status_bar = widgets.status_bar.clone();
     let mut root = widgets.root.clone();
+    let state = state.clone();
+    let tx = tx.clone();
+    let shared = shared.clone();
+    // Set on DndRelease; the dropped paths arrive in the Paste that follows
+    let mut drop_pending = false;
     win.handle(move |w, event| {
         match event {
             // ── Spacebar handling ──
@@ -391,6 +956,28 @@ pub fn setup_spacebar_handler(win: &mut Window, widgets: &Widgets) {
                 true
             }
 
+            // ── File drop: accept the drag anywhere in the window ──
+            Event::DndEnter | Event::DndDrag => true,
+            Event::DndLeave => {
+                drop_pending = false;
+                true
+            }
+            Event::DndRelease => {
+                drop_pending = true;
+                true
+            }
+            Event::Paste if drop_pending => {
+                drop_pending = false;
+                crate::callbacks_file::open_dropped_file(
+                    &state,
+                    &tx,
+                    &shared,
+                    &mut status_bar,
+                    &app::event_text(),
+                );
+                true
+            }
+
             // ── Window resize: reposition absolute-positioned status bars ──
             Event::Resize => {
                 let win_w = w.w();
@@ -474,16 +1061,24 @@ pub fn setup_spacebar_guards(widgets: &Widgets) {
     block_space!(widgets.btn_time_unit.clone(), btn_rerun);
     block_space!(widgets.btn_rerun.clone(), btn_rerun);
     block_space!(widgets.btn_snap_to_view.clone(), btn_rerun);
+    block_space!(widgets.btn_capture_noise.clone(), btn_rerun);
     block_space!(widgets.btn_freq_max.clone(), btn_rerun);
     block_space!(widgets.btn_home.clone(), btn_rerun);
     block_space!(widgets.btn_save_defaults.clone(), btn_rerun);
     block_space!(widgets.btn_play.clone(), btn_rerun);
     block_space!(widgets.btn_pause.clone(), btn_rerun);
     block_space!(widgets.btn_stop.clone(), btn_rerun);
+    block_space!(widgets.btn_ab.clone(), btn_rerun);
+    block_space!(widgets.btn_marker_add.clone(), btn_rerun);
+    block_space!(widgets.btn_marker_rename.clone(), btn_rerun);
+    block_space!(widgets.btn_marker_delete.clone(), btn_rerun);
+    block_space!(widgets.btn_gradient_save.clone(), btn_rerun);
+    block_space!(widgets.btn_gradient_delete.clone(), btn_rerun);
     block_space!(widgets.btn_mouse_mode_time.clone(), btn_rerun);
     block_space!(widgets.btn_mouse_mode_move.clone(), btn_rerun);
     block_space!(widgets.btn_mouse_mode_zoom.clone(), btn_rerun);
     block_space!(widgets.btn_mouse_mode_roi.clone(), btn_rerun);
+    block_space!(widgets.btn_mouse_mode_brush.clone(), btn_rerun);
     block_space!(widgets.btn_freq_zoom_in.clone(), btn_rerun);
     block_space!(widgets.btn_freq_zoom_out.clone(), btn_rerun);
     block_space!(widgets.btn_time_zoom_in.clone(), btn_rerun);
@@ -496,16 +1091,24 @@ pub fn setup_spacebar_guards(widgets: &Widgets) {
     widgets.btn_time_unit.clone().clear_visible_focus();
     widgets.btn_rerun.clone().clear_visible_focus();
     widgets.btn_snap_to_view.clone().clear_visible_focus();
+    widgets.btn_capture_noise.clone().clear_visible_focus();
     widgets.btn_freq_max.clone().clear_visible_focus();
     widgets.btn_home.clone().clear_visible_focus();
     widgets.btn_save_defaults.clone().clear_visible_focus();
     widgets.btn_play.clone().clear_visible_focus();
     widgets.btn_pause.clone().clear_visible_focus();
     widgets.btn_stop.clone().clear_visible_focus();
+    widgets.btn_ab.clone().clear_visible_focus();
+    widgets.btn_marker_add.clone().clear_visible_focus();
+    widgets.btn_marker_rename.clone().clear_visible_focus();
+    widgets.btn_marker_delete.clone().clear_visible_focus();
+    widgets.btn_gradient_save.clone().clear_visible_focus();
+    widgets.btn_gradient_delete.clone().clear_visible_focus();
     widgets.btn_mouse_mode_time.clone().clear_visible_focus();
     widgets.btn_mouse_mode_move.clone().clear_visible_focus();
     widgets.btn_mouse_mode_zoom.clone().clear_visible_focus();
     widgets.btn_mouse_mode_roi.clone().clear_visible_focus();
+    widgets.btn_mouse_mode_brush.clone().clear_visible_focus();
     widgets.btn_freq_zoom_in.clone().clear_visible_focus();
     widgets.btn_freq_zoom_out.clone().clear_visible_focus();
     widgets.btn_time_zoom_in.clone().clear_visible_focus();
@@ -521,13 +1124,21 @@ pub fn setup_spacebar_guards(widgets: &Widgets) {
     block_space!(widgets.window_type_choice.clone(), btn_rerun);
     block_space!(widgets.zero_pad_choice.clone(), btn_rerun);
     block_space!(widgets.colormap_choice.clone(), btn_rerun);
+    block_space!(widgets.gradient_preset_choice.clone(), btn_rerun);
     block_space!(widgets.repeat_choice.clone(), btn_rerun);
     widgets.seg_preset_choice.clone().clear_visible_focus();
     widgets.window_type_choice.clone().clear_visible_focus();
     widgets.zero_pad_choice.clone().clear_visible_focus();
     widgets.colormap_choice.clone().clear_visible_focus();
+    widgets.gradient_preset_choice.clone().clear_visible_focus();
     widgets.repeat_choice.clone().clear_visible_focus();
 
+    // ── Browsers ──
+    // A focused HoldBrowser would move its selection on space; markers are
+    // picked with the mouse.
+    block_space!(widgets.marker_list.clone(), btn_rerun);
+    widgets.marker_list.clone().clear_visible_focus();
+
     // ── CheckButtons ──
     block_space!(widgets.check_center.clone(), btn_rerun);
     block_space!(widgets.btn_tooltips.clone(), btn_rerun);
@@ -548,12 +1159,14 @@ pub fn setup_spacebar_guards(widgets: &Widgets) {
     block_space!(widgets.slider_ceiling.clone(), btn_rerun);
     block_space!(widgets.slider_brightness.clone(), btn_rerun);
     block_space!(widgets.slider_gamma.clone(), btn_rerun);
+    block_space!(widgets.slider_noise_reduction.clone(), btn_rerun);
     widgets.slider_overlap.clone().clear_visible_focus();
     widgets.slider_scale.clone().clear_visible_focus();
     widgets.slider_threshold.clone().clear_visible_focus();
     widgets.slider_ceiling.clone().clear_visible_focus();
     widgets.slider_brightness.clone().clear_visible_focus();
     widgets.slider_gamma.clone().clear_visible_focus();
+    widgets.slider_noise_reduction.clone().clear_visible_focus();
 
     // ── Scrollbars ──
     block_space!(widgets.x_scroll.clone(), btn_rerun);
@@ -1,14 +1,18 @@
 use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
-use fltk::{enums::CallbackTrigger, prelude::*};
+use fltk::{dialog, enums::CallbackTrigger, prelude::*};
 
-use crate::app_state::{set_msg, AppState, MouseMode, MsgLevel, SharedCallbacks, UpdateThrottle};
+use crate::app_state::{
+    AppState, MouseMode, MsgLevel, SharedCallbacks, UpdateThrottle, format_time, set_msg,
+    update_status_bar,
+};
 use crate::data::{
-    ColormapId, FreqScale, LastEditedField, SolverConstraints, TimeUnit, WindowType,
+    ColormapId, FreqScale, LastEditedField, Marker, SolverConstraints, TimeUnit, WindowType, marker,
 };
 use crate::layout::Widgets;
 use crate::settings::Settings;
+use crate::ui::theme;
 use crate::validation::{attach_float_validation, parse_or_zero_f32, parse_or_zero_usize};
 
 // ═══════════════════════════════════════════════════════════════════════════
@@ -551,6 +555,145 @@ pub fn setup_display_callbacks(widgets: &Widgets, state: &Rc<RefCell<AppState>>)
     }
 }
 
+// ═══════════════════════════════════════════════════════════════════════════
+//  WIDGET SYNC
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Push the analysis, display, and reconstruction settings in `st` into the
+/// sidebar widgets. Used at startup and after Open Session, since Rerun reads
+/// its parameters back from the widgets.
+pub fn sync_widgets_to_state(widgets: &Widgets, st: &AppState) {
+    let params = &st.fft_params;
+    let view = &st.view;
+
+    // ── Analysis ──
+    widgets
+        .input_seg_size
+        .clone()
+        .set_value(&params.window_length.to_string());
+    let preset_idx = find_preset_index(params.window_length)
+        .map(|i| i as i32)
+        .unwrap_or(9);
+    widgets.seg_preset_choice.clone().set_value(preset_idx);
+    widgets
+        .slider_overlap
+        .clone()
+        .set_value(params.overlap_percent as f64);
+    widgets
+        .lbl_overlap_val
+        .clone()
+        .set_label(&format!("Overlap: {}%", params.overlap_percent as i32));
+    let mut input_kaiser_beta = widgets.input_kaiser_beta.clone();
+    let window_idx = match params.window_type {
+        WindowType::Rectangular => 0,
+        WindowType::Hann => 1,
+        WindowType::Hamming => 2,
+        WindowType::Blackman => 3,
+        WindowType::Kaiser(beta) => {
+            input_kaiser_beta.set_value(&format!("{}", beta));
+            4
+        }
+    };
+    widgets.window_type_choice.clone().set_value(window_idx);
+    if window_idx == 4 {
+        input_kaiser_beta.activate();
+    } else {
+        input_kaiser_beta.deactivate();
+    }
+    widgets.check_center.clone().set_checked(params.use_center);
+    let zero_pad_idx = match params.zero_pad_factor {
+        2 => 1,
+        4 => 2,
+        8 => 3,
+        _ => 0,
+    };
+    widgets.zero_pad_choice.clone().set_value(zero_pad_idx);
+
+    // ── Display ──
+    let colormap_idx = ColormapId::ALL
+        .iter()
+        .position(|&c| c == view.colormap)
+        .unwrap_or(0);
+    widgets
+        .colormap_choice
+        .clone()
+        .set_value(colormap_idx as i32);
+    widgets.gradient_preview.clone().redraw();
+    let scale = match view.freq_scale {
+        FreqScale::Linear => 0.0,
+        FreqScale::Log => 1.0,
+        FreqScale::Power(power) => power,
+    };
+    widgets.slider_scale.clone().set_value(scale as f64);
+    widgets.lbl_scale_val.clone().set_label(&if scale <= 0.01 {
+        "Scale: Linear".to_string()
+    } else if scale >= 0.99 {
+        "Scale: Log".to_string()
+    } else {
+        format!("Scale: {:.0}%", scale * 100.0)
+    });
+    widgets
+        .slider_threshold
+        .clone()
+        .set_value(view.threshold_db as f64);
+    widgets
+        .lbl_threshold_val
+        .clone()
+        .set_label(&format!("Threshold: {} dB", view.threshold_db as i32));
+    widgets
+        .slider_ceiling
+        .clone()
+        .set_value(view.db_ceiling as f64);
+    widgets
+        .lbl_ceiling_val
+        .clone()
+        .set_label(&format!("Ceiling: {} dB", view.db_ceiling as i32));
+    widgets
+        .slider_brightness
+        .clone()
+        .set_value(view.brightness as f64);
+    widgets
+        .lbl_brightness_val
+        .clone()
+        .set_label(&format!("Brightness: {:.1}", view.brightness));
+    widgets.slider_gamma.clone().set_value(view.gamma as f64);
+    widgets
+        .lbl_gamma_val
+        .clone()
+        .set_label(&format!("Gamma: {:.1}", view.gamma));
+    widgets
+        .check_render_full_outside_roi
+        .clone()
+        .set_checked(st.render_full_file_outside_roi);
+
+    // ── Reconstruction ──
+    widgets
+        .input_recon_freq_min
+        .clone()
+        .set_value(&format!("{:.0}", view.recon_freq_min_hz));
+    widgets
+        .input_recon_freq_max
+        .clone()
+        .set_value(&format!("{:.0}", view.recon_freq_max_hz));
+    widgets
+        .input_norm_floor
+        .clone()
+        .set_value(&format!("{}", view.recon_norm_floor));
+    widgets.lbl_norm_floor_sci.clone().set_label(&format!(
+        "{} = {}",
+        crate::validation::format_norm_floor_with_commas_f64(view.recon_norm_floor),
+        crate::validation::format_scientific_f64(view.recon_norm_floor)
+    ));
+    widgets
+        .slider_noise_reduction
+        .clone()
+        .set_value(st.noise_reduction_db as f64);
+    widgets.lbl_noise_reduction_val.clone().set_label(&format!(
+        "Noise Reduction: {} dB",
+        st.noise_reduction_db as i32
+    ));
+}
+
 // ═══════════════════════════════════════════════════════════════════════════
 //  PLAYBACK CALLBACKS
 // ═══════════════════════════════════════════════════════════════════════════
@@ -597,6 +740,32 @@ pub fn setup_playback_callbacks(widgets: &Widgets, state: &Rc<RefCell<AppState>>
         });
     }
 
+    // A/B — switch between the reconstruction and the original
+    {
+        let state = state.clone();
+        let mut status_bar = widgets.status_bar.clone();
+
+        let mut btn_ab = widgets.btn_ab.clone();
+        btn_ab.set_callback(move |b| {
+            let mut st = state.borrow_mut();
+            let original = !st.playing_original;
+            if !st.set_playing_original(original) {
+                st.status
+                    .set_activity("A/B: no original audio to compare (FFT loaded from CSV)");
+                update_status_bar(&mut status_bar, &st.status.render());
+                return;
+            }
+            if original {
+                b.set_label("A: Orig");
+                b.set_label_color(theme::color(theme::ACCENT_YELLOW));
+            } else {
+                b.set_label("B: Recon");
+                b.set_label_color(theme::color(theme::TEXT_PRIMARY));
+            }
+            b.redraw();
+        });
+    }
+
     // Scrub slider - seeks within the reconstructed audio
     {
         let state = state.clone();
@@ -749,6 +918,72 @@ pub fn setup_misc_callbacks(
         });
     }
 
+    // Capture Noise Profile — average the Start..Stop range as the noise
+    {
+        let state = state.clone();
+        let mut status_bar = widgets.status_bar.clone();
+
+        let mut btn_capture_noise = widgets.btn_capture_noise.clone();
+        btn_capture_noise.set_callback(move |_| {
+            let mut st = state.borrow_mut();
+            if st.is_processing {
+                return;
+            }
+            if st.noise_reduced {
+                drop(st);
+                dialog::alert_default(
+                    "The spectrogram on screen is already noise-reduced.\n\
+                     Set Noise Reduction to 0 dB and recompute, then capture again.",
+                );
+                return;
+            }
+            if !st.capture_noise_profile() {
+                drop(st);
+                dialog::alert_default(
+                    "No analysis frames fall between Start and Stop.\n\
+                     Recompute, then capture again.",
+                );
+                return;
+            }
+            let (start, stop) = (st.fft_params.start_seconds(), st.fft_params.stop_seconds());
+            app_log!(
+                "NoiseReduction",
+                "Captured noise profile from {:.3}-{:.3}s",
+                start,
+                stop
+            );
+            st.dirty = true;
+            st.status.set_activity(&format!(
+                "Noise profile captured from {:.2}-{:.2}s (set Start/Stop back and recompute)",
+                start, stop
+            ));
+            update_status_bar(&mut status_bar, &st.status.render());
+        });
+    }
+
+    // Noise Reduction slider — dB the noise profile is turned down by
+    {
+        let state = state.clone();
+        let mut lbl = widgets.lbl_noise_reduction_val.clone();
+        let mut status_bar = widgets.status_bar.clone();
+
+        let mut slider_noise_reduction = widgets.slider_noise_reduction.clone();
+        slider_noise_reduction.set_callback(move |s| {
+            let val = s.value() as f32;
+            lbl.set_label(&format!("Noise Reduction: {} dB", val as i32));
+            let mut st = state.borrow_mut();
+            st.noise_reduction_db = val;
+            if st.noise_profile.is_some() {
+                st.dirty = true;
+                st.status.set_activity(&format!(
+                    "Noise reduction: {} dB (recompute to apply)",
+                    val as i32
+                ));
+                update_status_bar(&mut status_bar, &st.status.render());
+            }
+        });
+    }
+
     // Home button — snap viewport to reconstruction time + freq range
     {
         let state = state.clone();
@@ -808,13 +1043,7 @@ pub fn setup_misc_callbacks(
 }
 
 pub fn setup_mouse_mode_callbacks(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
-    fn style_buttons(
-        btn_time: &mut fltk::button::Button,
-        btn_move: &mut fltk::button::Button,
-        btn_zoom: &mut fltk::button::Button,
-        btn_roi: &mut fltk::button::Button,
-        mode: MouseMode,
-    ) {
+    fn style_buttons(buttons: &mut [(fltk::button::Button, MouseMode)], mode: MouseMode) {
         use fltk::enums::Color;
 
         let selected_bg = Color::from_hex(crate::ui::theme::ACCENT_BLUE);
@@ -822,127 +1051,218 @@ pub fn setup_mouse_mode_callbacks(widgets: &Widgets, state: &Rc<RefCell<AppState
         let idle_bg = Color::from_hex(crate::ui::theme::BG_WIDGET);
         let idle_fg = Color::from_hex(crate::ui::theme::TEXT_PRIMARY);
 
-        let is_time = mode == MouseMode::Time;
-        btn_time.set_color(if is_time { selected_bg } else { idle_bg });
-        btn_time.set_label_color(if is_time { selected_fg } else { idle_fg });
+        for (btn, btn_mode) in buttons.iter_mut() {
+            let selected = *btn_mode == mode;
+            btn.set_color(if selected { selected_bg } else { idle_bg });
+            btn.set_label_color(if selected { selected_fg } else { idle_fg });
+            btn.redraw();
+        }
+    }
+
+    let buttons = [
+        (widgets.btn_mouse_mode_time.clone(), MouseMode::Time),
+        (widgets.btn_mouse_mode_move.clone(), MouseMode::Move),
+        (widgets.btn_mouse_mode_zoom.clone(), MouseMode::SelectZoom),
+        (widgets.btn_mouse_mode_roi.clone(), MouseMode::RoiSelect),
+        (widgets.btn_mouse_mode_brush.clone(), MouseMode::Brush),
+    ];
+    style_buttons(&mut buttons.clone(), state.borrow().mouse_mode);
+
+    for (btn, mode) in &buttons {
+        let state = state.clone();
+        let mut buttons_style = buttons.clone();
+        let mode = *mode;
+        let mut btn = btn.clone();
+        btn.set_callback(move |_| {
+            let mut st = state.borrow_mut();
+            st.mouse_mode = mode;
+            st.mouse_selection = None;
+            drop(st);
+            style_buttons(&mut buttons_style, mode);
+        });
+    }
+}
 
-        let is_move = mode == MouseMode::Move;
-        btn_move.set_color(if is_move { selected_bg } else { idle_bg });
-        btn_move.set_label_color(if is_move { selected_fg } else { idle_fg });
+// ═══════════════════════════════════════════════════════════════════════════
+//  MARKERS
+// ═══════════════════════════════════════════════════════════════════════════
 
-        let is_zoom = mode == MouseMode::SelectZoom;
-        btn_zoom.set_color(if is_zoom { selected_bg } else { idle_bg });
-        btn_zoom.set_label_color(if is_zoom { selected_fg } else { idle_fg });
+/// Refill the Markers panel, highlighting the marker at `selected`.
+pub fn fill_marker_list(
+    list: &mut fltk::browser::HoldBrowser,
+    st: &AppState,
+    selected: Option<usize>,
+) {
+    list.clear();
+    for marker in &st.markers {
+        list.add(&marker.list_label());
+    }
+    if let Some(index) = selected.filter(|&i| i < st.markers.len()) {
+        list.select(index as i32 + 1);
+    }
+    list.redraw();
+}
 
-        let is_roi = mode == MouseMode::RoiSelect;
-        btn_roi.set_color(if is_roi { selected_bg } else { idle_bg });
-        btn_roi.set_label_color(if is_roi { selected_fg } else { idle_fg });
+/// Ask for a name and add a marker at `at` (time and frequency), or at the
+/// playback position when `at` is None.
+pub fn add_marker_dialog(widgets: &Widgets, state: &Rc<RefCell<AppState>>, at: Option<(f64, f32)>) {
+    let (time_seconds, freq_hz, default_name) = {
+        let st = state.borrow();
+        if st.active_spectrogram().is_none() {
+            return;
+        }
+        let (time_seconds, freq_hz) = match at {
+            Some((time, freq)) => (time, Some(freq)),
+            None if st.transport.duration_samples > 0 => (
+                st.recon_start_seconds() + st.audio_player.get_position_seconds(),
+                None,
+            ),
+            None => (st.fft_params.start_seconds(), None),
+        };
+        (time_seconds, freq_hz, marker::next_marker_name(&st.markers))
+    };
+
+    let Some(name) = dialog::input_default("Marker name:", &default_name) else {
+        return;
+    };
+    let name = match name.trim() {
+        "" => default_name,
+        trimmed => trimmed.to_string(),
+    };
+    let marker = Marker {
+        name,
+        time_seconds,
+        freq_hz,
+    };
+
+    let mut st = state.borrow_mut();
+    st.status.set_activity(&format!(
+        "Marker added: {} at {}",
+        marker.name,
+        format_time(marker.time_seconds)
+    ));
+    let index = marker::insert_marker(&mut st.markers, marker);
+    fill_marker_list(&mut widgets.marker_list.clone(), &st, Some(index));
+    update_status_bar(&mut widgets.status_bar.clone(), &st.status.render());
+    drop(st);
+    widgets.spec_display.clone().redraw();
+    widgets.waveform_display.clone().redraw();
+}
 
-        btn_time.redraw();
-        btn_move.redraw();
-        btn_zoom.redraw();
-        btn_roi.redraw();
+/// Bring the marker into view (keeping the zoom) and move playback to it
+/// when it lies within the reconstruction.
+fn jump_to_marker(widgets: &Widgets, state: &Rc<RefCell<AppState>>, index: usize) {
+    let mut st = state.borrow_mut();
+    let Some(marker) = st.markers.get(index).cloned() else {
+        return;
+    };
+    let time = marker.time_seconds;
+
+    if time < st.view.time_min_sec || time > st.view.time_max_sec {
+        let range = st.view.visible_time_range();
+        let latest_min = (st.view.data_time_max_sec - range).max(st.view.data_time_min_sec);
+        let min = (time - range / 2.0).clamp(st.view.data_time_min_sec, latest_min);
+        st.view.time_min_sec = min;
+        st.view.time_max_sec = min + range;
+        st.invalidate_all_spectrogram_renderers();
+        st.wave_renderer.invalidate();
     }
 
+    let position = time - st.recon_start_seconds();
+    let activity = if st.transport.duration_samples > 0
+        && (0.0..=st.transport.duration_seconds()).contains(&position)
     {
-        let mut btn_time = widgets.btn_mouse_mode_time.clone();
-        let mut btn_move = widgets.btn_mouse_mode_move.clone();
-        let mut btn_zoom = widgets.btn_mouse_mode_zoom.clone();
-        let mut btn_roi = widgets.btn_mouse_mode_roi.clone();
-        style_buttons(
-            &mut btn_time,
-            &mut btn_move,
-            &mut btn_zoom,
-            &mut btn_roi,
-            state.borrow().mouse_mode,
-        );
-    }
+        st.audio_player.seek_to(position);
+        format!("Jumped to marker: {}", marker.name)
+    } else {
+        format!(
+            "Marker {} is outside the reconstructed range; playback not moved",
+            marker.name
+        )
+    };
+    st.status.set_activity(&activity);
+    update_status_bar(&mut widgets.status_bar.clone(), &st.status.render());
+    drop(st);
+
+    widgets.spec_display.clone().redraw();
+    widgets.waveform_display.clone().redraw();
+    widgets.time_axis.clone().redraw();
+    widgets.scrub_slider.clone().redraw();
+}
 
+pub fn setup_marker_callbacks(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
+    // Click a marker to jump to it
     {
         let state = state.clone();
-        let mut btn_time_style = widgets.btn_mouse_mode_time.clone();
-        let mut btn_move_style = widgets.btn_mouse_mode_move.clone();
-        let mut btn_zoom_style = widgets.btn_mouse_mode_zoom.clone();
-        let mut btn_roi_style = widgets.btn_mouse_mode_roi.clone();
-        let mut btn = widgets.btn_mouse_mode_time.clone();
-        btn.set_callback(move |_| {
-            let mut st = state.borrow_mut();
-            st.mouse_mode = MouseMode::Time;
-            st.mouse_selection = None;
-            drop(st);
-            style_buttons(
-                &mut btn_time_style,
-                &mut btn_move_style,
-                &mut btn_zoom_style,
-                &mut btn_roi_style,
-                MouseMode::Time,
-            );
+        let widgets_c = widgets.clone();
+        let mut marker_list = widgets.marker_list.clone();
+        marker_list.set_callback(move |list| {
+            let line = list.value();
+            if line > 0 {
+                jump_to_marker(&widgets_c, &state, line as usize - 1);
+            }
         });
     }
 
+    // Add at the playback position
     {
         let state = state.clone();
-        let mut btn_time_style = widgets.btn_mouse_mode_time.clone();
-        let mut btn_move_style = widgets.btn_mouse_mode_move.clone();
-        let mut btn_zoom_style = widgets.btn_mouse_mode_zoom.clone();
-        let mut btn_roi_style = widgets.btn_mouse_mode_roi.clone();
-        let mut btn = widgets.btn_mouse_mode_move.clone();
-        btn.set_callback(move |_| {
-            let mut st = state.borrow_mut();
-            st.mouse_mode = MouseMode::Move;
-            st.mouse_selection = None;
-            drop(st);
-            style_buttons(
-                &mut btn_time_style,
-                &mut btn_move_style,
-                &mut btn_zoom_style,
-                &mut btn_roi_style,
-                MouseMode::Move,
-            );
+        let widgets_c = widgets.clone();
+        let mut btn_marker_add = widgets.btn_marker_add.clone();
+        btn_marker_add.set_callback(move |_| {
+            add_marker_dialog(&widgets_c, &state, None);
         });
     }
 
+    // Rename the selected marker
     {
         let state = state.clone();
-        let mut btn_time_style = widgets.btn_mouse_mode_time.clone();
-        let mut btn_move_style = widgets.btn_mouse_mode_move.clone();
-        let mut btn_zoom_style = widgets.btn_mouse_mode_zoom.clone();
-        let mut btn_roi_style = widgets.btn_mouse_mode_roi.clone();
-        let mut btn = widgets.btn_mouse_mode_zoom.clone();
-        btn.set_callback(move |_| {
+        let mut marker_list = widgets.marker_list.clone();
+        let mut spec_display = widgets.spec_display.clone();
+        let mut btn_marker_rename = widgets.btn_marker_rename.clone();
+        btn_marker_rename.set_callback(move |_| {
+            let line = marker_list.value();
+            if line <= 0 {
+                return;
+            }
+            let index = line as usize - 1;
+            let Some(current) = state.borrow().markers.get(index).map(|m| m.name.clone()) else {
+                return;
+            };
+            let Some(name) = dialog::input_default("Marker name:", &current) else {
+                return;
+            };
+            if name.trim().is_empty() {
+                return;
+            }
             let mut st = state.borrow_mut();
-            st.mouse_mode = MouseMode::SelectZoom;
-            st.mouse_selection = None;
+            st.markers[index].name = name.trim().to_string();
+            fill_marker_list(&mut marker_list, &st, Some(index));
             drop(st);
-            style_buttons(
-                &mut btn_time_style,
-                &mut btn_move_style,
-                &mut btn_zoom_style,
-                &mut btn_roi_style,
-                MouseMode::SelectZoom,
-            );
+            spec_display.redraw();
         });
     }
 
+    // Delete the selected marker
     {
         let state = state.clone();
-        let mut btn_time_style = widgets.btn_mouse_mode_time.clone();
-        let mut btn_move_style = widgets.btn_mouse_mode_move.clone();
-        let mut btn_zoom_style = widgets.btn_mouse_mode_zoom.clone();
-        let mut btn_roi_style = widgets.btn_mouse_mode_roi.clone();
-        let mut btn = widgets.btn_mouse_mode_roi.clone();
-        btn.set_callback(move |_| {
+        let mut marker_list = widgets.marker_list.clone();
+        let mut spec_display = widgets.spec_display.clone();
+        let mut waveform_display = widgets.waveform_display.clone();
+        let mut btn_marker_delete = widgets.btn_marker_delete.clone();
+        btn_marker_delete.set_callback(move |_| {
+            let line = marker_list.value();
             let mut st = state.borrow_mut();
-            st.mouse_mode = MouseMode::RoiSelect;
-            st.mouse_selection = None;
+            if line <= 0 || line as usize > st.markers.len() {
+                return;
+            }
+            let index = line as usize - 1;
+            st.markers.remove(index);
+            let next = index.min(st.markers.len().saturating_sub(1));
+            fill_marker_list(&mut marker_list, &st, Some(next));
             drop(st);
-            style_buttons(
-                &mut btn_time_style,
-                &mut btn_move_style,
-                &mut btn_zoom_style,
-                &mut btn_roi_style,
-                MouseMode::RoiSelect,
-            );
+            spec_display.redraw();
+            waveform_display.redraw();
         });
     }
 }
@@ -3,7 +3,8 @@ use std::fs::File;
 use std::path::Path;
 
 use super::data::{
-    FftFrame, FftParams, LastEditedField, Spectrogram, TimeUnit, ViewState, WindowType,
+    FftFrame, FftParams, LastEditedField, MelFilterbank, Spectrogram, TimeUnit, ViewState,
+    WindowType,
 };
 
 /// Reconstruction parameters imported from CSV: (freq_count, freq_min_hz, freq_max_hz).
@@ -117,6 +118,53 @@ pub fn export_to_csv<P: AsRef<Path>>(
     Ok(())
 }
 
+/// Export a mel spectrogram to CSV: one row per frame (`time_sec`, then one
+/// column per mel band, low to high), levels in dB. The header row names each
+/// band by its center frequency in Hz. Meant for ML feature files, so unlike
+/// `export_to_csv` it can't be loaded back.
+pub fn export_mel_csv<P: AsRef<Path>>(
+    spectrogram: &Spectrogram,
+    bank: &MelFilterbank,
+    path: P,
+) -> Result<()> {
+    let file = File::create(&path)
+        .with_context(|| format!("Failed to create CSV file: {:?}", path.as_ref()))?;
+    let mut writer = csv::Writer::from_writer(file);
+
+    let header: Vec<String> = std::iter::once("time_sec".to_string())
+        .chain(bank.centers_hz.iter().map(|hz| format!("{:.2}", hz)))
+        .collect();
+    writer
+        .write_record(&header)
+        .context("Failed to write CSV header")?;
+
+    for frame in &spectrogram.frames {
+        let row: Vec<String> = std::iter::once(format!("{:.10}", frame.time_seconds))
+            .chain(
+                bank.pool(&frame.magnitudes, None)
+                    .iter()
+                    .map(|mag| format!("{:.3}", 20.0 * mag.max(1e-10).log10())),
+            )
+            .collect();
+        writer
+            .write_record(&row)
+            .context("Failed to write CSV record")?;
+    }
+
+    writer.flush().context("Failed to flush CSV writer")?;
+
+    dbg_log!(
+        crate::debug_flags::FILE_IO_DBG,
+        "CSV Export",
+        "Wrote {} frames x {} mel bands to {:?}",
+        spectrogram.num_frames(),
+        bank.num_bands(),
+        path.as_ref()
+    );
+
+    Ok(())
+}
+
 /// Returns (Spectrogram, FftParams, optional recon params, viewport params)
 pub fn import_from_csv<P: AsRef<Path>>(
     path: P,
@@ -273,17 +321,27 @@ pub fn import_from_csv<P: AsRef<Path>>(
     let mut frames_map: std::collections::BTreeMap<String, Vec<(f32, f32, f32)>> =
         std::collections::BTreeMap::new();
 
+    // The phase column is optional (magnitude-only data, e.g. edited or
+    // generated elsewhere); missing phases read as 0 and Griffin-Lim
+    // estimates them at reconstruction time.
+    let mut has_phase = false;
     for result in records {
         let record = result.context("Failed to read CSV record")?;
 
-        if record.len() < 4 {
+        if record.len() < 3 {
             continue;
         }
 
         let time_sec: String = record[0].to_string();
         let frequency_hz: f32 = record[1].parse().unwrap_or(0.0);
         let magnitude: f32 = record[2].parse().unwrap_or(0.0);
-        let phase_rad: f32 = record[3].parse().unwrap_or(0.0);
+        let phase_rad: f32 = match record.get(3).map(str::trim) {
+            Some(phase) if !phase.is_empty() => {
+                has_phase = true;
+                phase.parse().unwrap_or(0.0)
+            }
+            _ => 0.0,
+        };
 
         frames_map
             .entry(time_sec)
@@ -321,8 +379,9 @@ pub fn import_from_csv<P: AsRef<Path>>(
 
     let freq_count = shared_frequencies.as_ref().map_or(0, |f| f.len());
     let frame_count = frames.len();
-    let spectrogram =
+    let mut spectrogram =
         Spectrogram::from_frames_with_frequencies(frames, shared_frequencies.unwrap_or_default());
+    spectrogram.has_phase = has_phase;
 
     dbg_log!(
         crate::debug_flags::FILE_IO_DBG,
@@ -453,4 +512,50 @@ mod tests {
 
         std::fs::remove_file(temp_path).ok();
     }
+
+    #[test]
+    fn test_mel_csv_export() {
+        let frequencies: Vec<f32> = (0..64).map(|i| i as f32 * 100.0).collect();
+        let frames = (0..3)
+            .map(|i| FftFrame {
+                time_seconds: i as f64 * 0.01,
+                magnitudes: vec![0.1; 64],
+                phases: vec![0.0; 64],
+            })
+            .collect();
+        let spec = Spectrogram::from_frames_with_frequencies(frames, frequencies.clone());
+        let bank = MelFilterbank::new(&frequencies, 10, 0.0, 6300.0);
+
+        let temp_path = "/tmp/test_mel_export.csv";
+        export_mel_csv(&spec, &bank, temp_path).expect("Export should succeed");
+        let text = std::fs::read_to_string(temp_path).expect("read back");
+        std::fs::remove_file(temp_path).ok();
+
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("time_sec,"));
+        assert_eq!(lines[0].split(',').count(), 11);
+        // A flat 0.1 spectrum is -20 dB in every band
+        let row: Vec<&str> = lines[2].split(',').collect();
+        assert_eq!(row[0], "0.0100000000");
+        assert!(row[1..].iter().all(|&db| db == "-20.000"));
+    }
+
+    #[test]
+    fn test_csv_import_without_phase_column() {
+        let temp_path = "/tmp/test_magnitude_only.csv";
+        let csv = "48000,8,4,50,Hann,false,1,0,16\n".to_string()
+            + "time_sec,frequency_hz,magnitude\n"
+            + "0.0,0.0,0.5\n"
+            + "0.0,6000.0,0.25\n";
+        std::fs::write(temp_path, csv).expect("write test csv");
+
+        let (spec, _, _, _) = import_from_csv(temp_path).expect("import should succeed");
+        std::fs::remove_file(temp_path).ok();
+
+        assert!(!spec.has_phase);
+        assert_eq!(spec.num_frames(), 1);
+        assert_eq!(spec.frames[0].magnitudes, vec![0.5, 0.25]);
+        assert_eq!(spec.frames[0].phases, vec![0.0, 0.0]);
+    }
 }
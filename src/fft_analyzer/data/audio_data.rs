@@ -1,16 +1,123 @@
 use anyhow::{Context, Result};
 use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use musickbeets::audio::{decode_audio_file, resample};
+use musickbeets::parser::AUTO_CHANNEL_COUNT;
+use musickbeets::{Engine, Song};
 use std::path::Path;
 use std::sync::Arc;
 
+/// Sample rate tracker songs are rendered at (the tracker's own default).
+pub const TRACKER_RENDER_SAMPLE_RATE: u32 = 48000;
+
+/// Choices for Analysis > Sample Rate: opened audio is converted to this
+/// rate (0 keeps each file's own rate).
+pub const LOAD_SAMPLE_RATES: [u32; 4] = [0, 44100, 48000, 96000];
+
+/// Which signal of a stereo (or multichannel) file the analyzer works on.
+/// Mono files only have one signal, so every mode but Side gives the same
+/// samples (Side is silent).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelMode {
+    /// All channels averaged (the mono downmix)
+    #[default]
+    Mix,
+    Left,
+    Right,
+    /// (L + R) / 2: what the two channels share
+    Mid,
+    /// (L - R) / 2: what differs between them (width, reverb, panned parts)
+    Side,
+}
+
+impl ChannelMode {
+    pub const ALL: [ChannelMode; 5] = [
+        ChannelMode::Mix,
+        ChannelMode::Left,
+        ChannelMode::Right,
+        ChannelMode::Mid,
+        ChannelMode::Side,
+    ];
+
+    /// Short name, used in settings.ini and the status bar
+    pub fn name(self) -> &'static str {
+        match self {
+            ChannelMode::Mix => "L+R",
+            ChannelMode::Left => "L",
+            ChannelMode::Right => "R",
+            ChannelMode::Mid => "Mid",
+            ChannelMode::Side => "Side",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Label for the Analysis > Channel menu
+    pub fn menu_label(self) -> &'static str {
+        match self {
+            ChannelMode::Mix => "L+R (mono mix)",
+            ChannelMode::Left => "Left",
+            ChannelMode::Right => "Right",
+            ChannelMode::Mid => "Mid (L+R)\\/2",
+            ChannelMode::Side => "Side (L-R)\\/2",
+        }
+    }
+
+    /// Build this mode's signal from deinterleaved channels. With one
+    /// channel, Left and Right are both that channel.
+    pub fn extract(self, channels: &[Vec<f32>]) -> Vec<f32> {
+        let Some(left) = channels.first() else {
+            return Vec::new();
+        };
+        let right = channels.get(1).unwrap_or(left);
+        match self {
+            ChannelMode::Mix => {
+                let count = channels.len() as f32;
+                (0..left.len())
+                    .map(|i| channels.iter().map(|channel| channel[i]).sum::<f32>() / count)
+                    .collect()
+            }
+            ChannelMode::Left => left.clone(),
+            ChannelMode::Right => right.clone(),
+            ChannelMode::Mid => left.iter().zip(right).map(|(l, r)| (l + r) * 0.5).collect(),
+            ChannelMode::Side => left.iter().zip(right).map(|(l, r)| (l - r) * 0.5).collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AudioData {
+    /// The signal being analyzed: the selected channel mode of the file
+    /// (after normalization), or reconstructed audio
     pub samples: Arc<Vec<f32>>,
     pub sample_rate: u32,
     pub duration_seconds: f64,
+    /// Every channel of the source file, deinterleaved and before
+    /// normalization, so another channel can be selected without reloading.
+    /// Empty for reconstructed audio.
+    pub channels: Arc<Vec<Vec<f32>>>,
+    /// Which channel(s) `samples` was built from
+    pub channel_mode: ChannelMode,
 }
 
 impl AudioData {
+    /// Open any file the tracker's decoder reads: WAV (through
+    /// `from_wav_file`), MP3, FLAC, or Ogg Vorbis, at the file's own rate.
+    pub fn from_audio_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let is_wav = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("wav"));
+        if is_wav {
+            return Self::from_wav_file(path);
+        }
+        let decoded = decode_audio_file(path).map_err(|e| anyhow::anyhow!(e))?;
+        Ok(Self::from_channels(decoded.channels, decoded.sample_rate))
+    }
+
     pub fn from_wav_file<P: AsRef<Path>>(path: P) -> Result<Self> {
         let mut reader = WavReader::open(&path)
             .with_context(|| format!("Failed to open WAV file: {:?}", path.as_ref()))?;
@@ -51,22 +158,129 @@ impl AudioData {
             },
         };
 
-        let mono_samples = if channels == 1 {
-            samples
+        Ok(Self::from_interleaved(
+            &samples,
+            channels as usize,
+            sample_rate,
+        ))
+    }
+
+    /// Render a tracker song offline and load the result, as if it had been
+    /// exported to WAV first (stereo, so Analysis > Channel works on it).
+    /// `.json` files are read as songs saved by `tracker convert`; anything
+    /// else is parsed as CSV (the first song, or the file's [chain]).
+    /// Returns the audio and the parser's messages about the song.
+    pub fn from_tracker_song<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<String>)> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read tracker song: {:?}", path))?;
+        let is_json = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+        let song = if is_json {
+            Song::from_json(&text).map_err(|e| anyhow::anyhow!("{:?}: {}", path, e))?
         } else {
-            samples
-                .chunks(channels as usize)
-                .map(|chunk| chunk.iter().sum::<f32>() / channels as f32)
-                .collect()
+            Song::parse(&text, AUTO_CHANNEL_COUNT)
         };
 
+        let display_path = path.display().to_string();
+        let messages = song
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.format_for_file(&display_path))
+            .collect();
+        if song.row_count() == 0 {
+            anyhow::bail!("Tracker song has no rows to play: {:?}", path);
+        }
+
+        let mut engine = Engine::new(song, TRACKER_RENDER_SAMPLE_RATE);
+        let stereo = engine.playback_engine().render_to_buffer();
+        Ok((
+            Self::from_interleaved(&stereo, 2, TRACKER_RENDER_SAMPLE_RATE),
+            messages,
+        ))
+    }
+
+    /// Deinterleave `channel_count` channels and analyze their mono mix.
+    fn from_interleaved(samples: &[f32], channel_count: usize, sample_rate: u32) -> Self {
+        let channel_count = channel_count.max(1);
+        let frames = samples.len() / channel_count;
+        let deinterleaved: Vec<Vec<f32>> = (0..channel_count)
+            .map(|channel| {
+                (0..frames)
+                    .map(|frame| samples[frame * channel_count + channel])
+                    .collect()
+            })
+            .collect();
+        Self::from_channels(deinterleaved, sample_rate)
+    }
+
+    /// Analyze the mono mix of separate channels.
+    fn from_channels(channels: Vec<Vec<f32>>, sample_rate: u32) -> Self {
+        let mono_samples = ChannelMode::Mix.extract(&channels);
         let duration_seconds = mono_samples.len() as f64 / sample_rate as f64;
 
-        Ok(AudioData {
+        AudioData {
             samples: Arc::new(mono_samples),
             sample_rate,
             duration_seconds,
-        })
+            channels: Arc::new(channels),
+            channel_mode: ChannelMode::Mix,
+        }
+    }
+
+    /// Convert to another sample rate (windowed sinc, see
+    /// `musickbeets::audio::resample`). The source channels are converted
+    /// and `samples` rebuilt from them, so call this before normalizing.
+    pub fn resample(&mut self, sample_rate: u32) {
+        if sample_rate == 0 || sample_rate == self.sample_rate {
+            return;
+        }
+        let from_rate = self.sample_rate;
+        if self.channels.is_empty() {
+            self.samples = Arc::new(resample(&self.samples, from_rate, sample_rate));
+        } else {
+            let channels: Vec<Vec<f32>> = self
+                .channels
+                .iter()
+                .map(|channel| resample(channel, from_rate, sample_rate))
+                .collect();
+            self.samples = Arc::new(self.channel_mode.extract(&channels));
+            self.channels = Arc::new(channels);
+        }
+        self.sample_rate = sample_rate;
+        self.duration_seconds = self.samples.len() as f64 / sample_rate as f64;
+    }
+
+    /// Audio with a single signal and no source channels (reconstructions,
+    /// test signals).
+    pub fn from_samples(samples: Vec<f32>, sample_rate: u32) -> Self {
+        let duration_seconds = samples.len() as f64 / sample_rate.max(1) as f64;
+        AudioData {
+            samples: Arc::new(samples),
+            sample_rate,
+            duration_seconds,
+            channels: Arc::new(Vec::new()),
+            channel_mode: ChannelMode::Mix,
+        }
+    }
+
+    /// Number of channels in the source file (1 for reconstructed audio).
+    pub fn num_channels(&self) -> usize {
+        self.channels.len().max(1)
+    }
+
+    /// Rebuild `samples` from the source channels for another channel mode.
+    /// The new samples are not normalized; call `normalize` afterwards if
+    /// wanted. Returns false (and changes nothing) when there are no source
+    /// channels.
+    pub fn select_channel(&mut self, mode: ChannelMode) -> bool {
+        if self.channels.is_empty() {
+            return false;
+        }
+        self.samples = Arc::new(mode.extract(&self.channels));
+        self.channel_mode = mode;
+        true
     }
 
     pub fn save_wav<P: AsRef<Path>>(&self, path: P) -> Result<()> {
@@ -119,3 +333,102 @@ impl AudioData {
         gain
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_modes_extract_stereo_signals() {
+        let channels = vec![vec![1.0, 0.5, 0.0], vec![0.0, 0.5, 1.0]];
+        assert_eq!(ChannelMode::Mix.extract(&channels), vec![0.5, 0.5, 0.5]);
+        assert_eq!(ChannelMode::Left.extract(&channels), vec![1.0, 0.5, 0.0]);
+        assert_eq!(ChannelMode::Right.extract(&channels), vec![0.0, 0.5, 1.0]);
+        assert_eq!(ChannelMode::Mid.extract(&channels), vec![0.5, 0.5, 0.5]);
+        assert_eq!(ChannelMode::Side.extract(&channels), vec![0.5, 0.0, -0.5]);
+
+        // A mono file: both sides are the one channel, so Side is silent
+        let mono = vec![vec![0.25, -0.25]];
+        assert_eq!(ChannelMode::Right.extract(&mono), vec![0.25, -0.25]);
+        assert_eq!(ChannelMode::Side.extract(&mono), vec![0.0, 0.0]);
+
+        for mode in ChannelMode::ALL {
+            assert_eq!(ChannelMode::from_name(mode.name()), Some(mode));
+        }
+    }
+
+    #[test]
+    fn select_channel_rebuilds_samples() {
+        let mut audio = AudioData::from_samples(vec![0.1, 0.2], 48000);
+        assert!(!audio.select_channel(ChannelMode::Side));
+        assert_eq!(audio.num_channels(), 1);
+
+        audio.channels = Arc::new(vec![vec![0.4, 0.2], vec![0.0, 0.2]]);
+        assert!(audio.select_channel(ChannelMode::Side));
+        assert_eq!(*audio.samples, vec![0.2, 0.0]);
+        assert_eq!(audio.channel_mode, ChannelMode::Side);
+        assert_eq!(audio.num_channels(), 2);
+    }
+
+    #[test]
+    fn resample_converts_channels_and_keeps_the_mode() {
+        let mut audio = AudioData::from_channels(vec![vec![0.5; 441], vec![-0.5; 441]], 44100);
+        audio.select_channel(ChannelMode::Side);
+        audio.resample(48000);
+
+        assert_eq!(audio.sample_rate, 48000);
+        assert_eq!(audio.num_samples(), 480);
+        assert!((audio.duration_seconds - 0.01).abs() < 1e-9);
+        assert_eq!(audio.channels[0].len(), 480);
+        // Side of the converted channels, away from the edges
+        assert!((audio.samples[240] - 0.5).abs() < 1e-3);
+
+        let mut mono = AudioData::from_samples(vec![0.0; 480], 48000);
+        mono.resample(8000);
+        assert_eq!(mono.num_samples(), 80);
+        assert!(mono.channels.is_empty());
+    }
+
+    #[test]
+    fn audio_file_opens_wav_and_rejects_unknown_data() {
+        let path = std::env::temp_dir().join("musickbeets_fft_open_test.wav");
+        AudioData::from_samples(vec![0.5; 100], 22050)
+            .save_wav(&path)
+            .unwrap();
+        let audio = AudioData::from_audio_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(audio.sample_rate, 22050);
+        assert_eq!(audio.num_samples(), 100);
+
+        let flac = std::env::temp_dir().join("musickbeets_fft_open_test.flac");
+        std::fs::write(&flac, "not audio").unwrap();
+        let result = AudioData::from_audio_file(&flac);
+        std::fs::remove_file(&flac).ok();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn tracker_song_renders_to_stereo_audio() {
+        let path = std::env::temp_dir().join("musickbeets_fft_tracker_song_test.csv");
+        std::fs::write(
+            &path,
+            "Lead,Bass\nconfig, tick_duration: 0.25\nc5 sine p:-1,c3 sine p:1\n-,-\n.,.\n",
+        )
+        .unwrap();
+        let (audio, messages) = AudioData::from_tracker_song(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert!(messages.is_empty(), "{:?}", messages);
+        assert_eq!(audio.sample_rate, TRACKER_RENDER_SAMPLE_RATE);
+        assert_eq!(audio.num_channels(), 2);
+        // Two rows held, then the release tail
+        assert!(audio.duration_seconds >= 0.5, "{}", audio.duration_seconds);
+        assert!(audio.samples.iter().any(|s| s.abs() > 0.01));
+        // Hard-panned parts land on different sides
+        assert_ne!(audio.channels[0], audio.channels[1]);
+
+        assert!(
+            AudioData::from_tracker_song(std::env::temp_dir().join("no_such_song.csv")).is_err()
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+/// A named point in the audio, for labeling song sections and artifacts.
+/// Markers placed on the spectrogram also remember the frequency.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Marker {
+    pub name: String,
+    pub time_seconds: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub freq_hz: Option<f32>,
+}
+
+impl Marker {
+    /// One line of the Markers panel: time, name, and the frequency if any
+    pub fn list_label(&self) -> String {
+        let mins = (self.time_seconds.max(0.0) / 60.0) as u32;
+        let secs = self.time_seconds.max(0.0) % 60.0;
+        match self.freq_hz {
+            Some(freq) => format!("{}:{:06.3}  {}  ({:.0} Hz)", mins, secs, self.name, freq),
+            None => format!("{}:{:06.3}  {}", mins, secs, self.name),
+        }
+    }
+}
+
+/// Insert a marker so the list stays sorted by time (a marker at the same
+/// time as another goes after it). Returns its index.
+pub fn insert_marker(markers: &mut Vec<Marker>, marker: Marker) -> usize {
+    let index = markers.partition_point(|m| m.time_seconds <= marker.time_seconds);
+    markers.insert(index, marker);
+    index
+}
+
+/// "Marker N", with N one past the highest number already used that way.
+pub fn next_marker_name(markers: &[Marker]) -> String {
+    let highest = markers
+        .iter()
+        .filter_map(|m| m.name.strip_prefix("Marker ")?.parse::<u32>().ok())
+        .max()
+        .unwrap_or(0);
+    format!("Marker {}", highest + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker(name: &str, time_seconds: f64) -> Marker {
+        Marker {
+            name: name.to_string(),
+            time_seconds,
+            freq_hz: None,
+        }
+    }
+
+    #[test]
+    fn markers_stay_sorted_and_get_numbered_names() {
+        let mut markers = Vec::new();
+        assert_eq!(next_marker_name(&markers), "Marker 1");
+        insert_marker(&mut markers, marker("Chorus", 30.0));
+        insert_marker(&mut markers, marker("Marker 4", 50.0));
+        assert_eq!(insert_marker(&mut markers, marker("Intro", 0.5)), 0);
+        assert_eq!(insert_marker(&mut markers, marker("Click", 30.0)), 2);
+        let names: Vec<&str> = markers.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["Intro", "Chorus", "Click", "Marker 4"]);
+        assert_eq!(next_marker_name(&markers), "Marker 5");
+    }
+
+    #[test]
+    fn list_label_shows_time_name_and_frequency() {
+        let mut m = marker("Verse", 83.25);
+        assert_eq!(m.list_label(), "1:23.250  Verse");
+        m.freq_hz = Some(440.4);
+        assert_eq!(m.list_label(), "1:23.250  Verse  (440 Hz)");
+    }
+}
@@ -0,0 +1,163 @@
+/// Fewest / most mel bands the Display > Mel Bands control accepts.
+pub const MIN_MEL_BANDS: usize = 8;
+pub const MAX_MEL_BANDS: usize = 512;
+
+/// Hz to mel (HTK formula: 2595 * log10(1 + f / 700)).
+pub fn hz_to_mel(hz: f32) -> f32 {
+    2595.0 * (1.0 + hz.max(0.0) / 700.0).log10()
+}
+
+/// Mel to Hz, the inverse of `hz_to_mel`.
+pub fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (10f32.powf(mel / 2595.0) - 1.0)
+}
+
+/// Triangular mel filterbank over a spectrogram's FFT bins.
+///
+/// Band centers are evenly spaced in mel between `min_hz` and `max_hz`; each
+/// band's triangle rises from the previous center and falls to the next one.
+/// Pooling is the weighted RMS of the bin magnitudes under the triangle, so a
+/// pooled value stays on the same dB scale as a single bin (the colormap
+/// threshold/ceiling keep working).
+#[derive(Debug, Clone)]
+pub struct MelFilterbank {
+    /// Center frequency of each band, low to high
+    pub centers_hz: Vec<f32>,
+    mel_min: f32,
+    mel_step: f32,
+    /// Per band: first FFT bin and the weights from that bin on
+    filters: Vec<(usize, Vec<f32>)>,
+}
+
+impl MelFilterbank {
+    pub fn new(bin_freqs: &[f32], num_bands: usize, min_hz: f32, max_hz: f32) -> Self {
+        let num_bands = num_bands.max(1);
+        let mel_min = hz_to_mel(min_hz);
+        let mel_max = hz_to_mel(max_hz.max(min_hz + 1.0));
+        let mel_step = (mel_max - mel_min) / (num_bands + 1) as f32;
+        // Band i spans edges[i]..edges[i + 2] and peaks at edges[i + 1]
+        let edges: Vec<f32> = (0..num_bands + 2)
+            .map(|i| mel_to_hz(mel_min + mel_step * i as f32))
+            .collect();
+
+        let filters = (0..num_bands)
+            .map(|band| {
+                let (lo, center, hi) = (edges[band], edges[band + 1], edges[band + 2]);
+                let first = bin_freqs.partition_point(|&f| f <= lo);
+                let end = bin_freqs.partition_point(|&f| f < hi);
+                let weights: Vec<f32> = bin_freqs[first..end.max(first)]
+                    .iter()
+                    .map(|&f| {
+                        if f <= center {
+                            (f - lo) / (center - lo)
+                        } else {
+                            (hi - f) / (hi - center)
+                        }
+                    })
+                    .collect();
+                if weights.iter().any(|&w| w > 0.0) || bin_freqs.is_empty() {
+                    (first, weights)
+                } else {
+                    // Band narrower than the bin spacing (low bands with a
+                    // short window): take the bin nearest its center
+                    let nearest = bin_freqs
+                        .partition_point(|&f| f < center)
+                        .min(bin_freqs.len() - 1);
+                    let nearest = if nearest > 0
+                        && (center - bin_freqs[nearest - 1]) < (bin_freqs[nearest] - center)
+                    {
+                        nearest - 1
+                    } else {
+                        nearest
+                    };
+                    (nearest, vec![1.0])
+                }
+            })
+            .collect();
+
+        Self {
+            centers_hz: edges[1..=num_bands].to_vec(),
+            mel_min,
+            mel_step,
+            filters,
+        }
+    }
+
+    pub fn num_bands(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// The band whose center is nearest (in mel) to `hz`.
+    pub fn band_for_freq(&self, hz: f32) -> usize {
+        let position = (hz_to_mel(hz) - self.mel_min) / self.mel_step - 1.0;
+        (position.round().max(0.0) as usize).min(self.num_bands() - 1)
+    }
+
+    /// Pool one frame's bin magnitudes into band magnitudes. Bins where
+    /// `keep` is false count as silent (used for the reconstruction ROI's
+    /// active-bin filter).
+    pub fn pool(&self, magnitudes: &[f32], keep: Option<&[bool]>) -> Vec<f32> {
+        self.filters
+            .iter()
+            .map(|(first, weights)| {
+                let mut energy = 0.0f32;
+                let mut total_weight = 0.0f32;
+                for (offset, &weight) in weights.iter().enumerate() {
+                    let bin = first + offset;
+                    let kept = keep.is_none_or(|keep| keep.get(bin).copied().unwrap_or(false));
+                    let mag = if kept {
+                        magnitudes.get(bin).copied().unwrap_or(0.0)
+                    } else {
+                        0.0
+                    };
+                    energy += weight * mag * mag;
+                    total_weight += weight;
+                }
+                if total_weight > 0.0 {
+                    (energy / total_weight).sqrt()
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mel_conversion_roundtrips() {
+        assert!((hz_to_mel(1000.0) - 1000.0).abs() < 1.0);
+        for hz in [0.0, 440.0, 8000.0, 22050.0] {
+            assert!((mel_to_hz(hz_to_mel(hz)) - hz).abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn filterbank_pools_a_tone_into_its_band() {
+        let freqs: Vec<f32> = (0..=512).map(|i| i as f32 * 43.0).collect();
+        let bank = MelFilterbank::new(&freqs, 40, 0.0, 512.0 * 43.0);
+        assert_eq!(bank.num_bands(), 40);
+        assert!(bank.centers_hz.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // One loud bin at 3010 Hz (bin 70)
+        let mut mags = vec![0.0; freqs.len()];
+        mags[70] = 1.0;
+        let pooled = bank.pool(&mags, None);
+        let loudest = (0..pooled.len())
+            .max_by(|&a, &b| pooled[a].total_cmp(&pooled[b]))
+            .unwrap();
+        assert_eq!(loudest, bank.band_for_freq(3010.0));
+
+        // A flat spectrum pools to the same level in every band, including
+        // the low bands that fall between bins
+        let flat = bank.pool(&vec![0.5; freqs.len()], None);
+        assert!(flat.iter().all(|&m| (m - 0.5).abs() < 1e-4));
+
+        // Masked-out bins count as silence
+        let keep = vec![false; freqs.len()];
+        assert!(bank.pool(&mags, Some(&keep)).iter().all(|&m| m == 0.0));
+    }
+}
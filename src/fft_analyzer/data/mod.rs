@@ -1,15 +1,20 @@
 pub mod audio_data;
 pub mod fft_params;
+pub mod marker;
+pub mod mel;
+pub mod pitch;
 pub mod segmentation_solver;
 pub mod spectrogram;
 pub mod view_state;
 
-pub use audio_data::AudioData;
+pub use audio_data::{AudioData, ChannelMode, LOAD_SAMPLE_RATES};
 pub use fft_params::{FftParams, TimeUnit, WindowType};
-pub use spectrogram::{compute_active_bins, FftFrame, Spectrogram};
+pub use marker::Marker;
+pub use mel::{MAX_MEL_BANDS, MIN_MEL_BANDS, MelFilterbank};
+pub use spectrogram::{FftFrame, Spectrogram, compute_active_bins};
 pub use view_state::{
-    default_custom_gradient, eval_gradient, ColormapId, FreqScale, GradientStop, TransportState,
-    ViewState,
+    ColormapId, FreqScale, GradientPreset, GradientStop, TransportState, ViewState,
+    default_custom_gradient, eval_gradient, save_gradient_preset,
 };
 
 pub use segmentation_solver::{LastEditedField, SolverConstraints};
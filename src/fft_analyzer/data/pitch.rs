@@ -0,0 +1,75 @@
+use musickbeets::helper::PitchName;
+
+/// Frequency of C0, the tracker's lowest note (A4 = 440 Hz).
+pub const C0_HZ: f32 = 16.351_598;
+
+/// Equal-tempered pitch in semitones above C0 (fractional between notes).
+pub fn hz_to_semitones(hz: f32) -> f32 {
+    12.0 * (hz.max(1e-6) / C0_HZ).log2()
+}
+
+/// Frequency of a pitch given in semitones above C0.
+pub fn semitones_to_hz(semitones: f32) -> f32 {
+    C0_HZ * 2f32.powf(semitones / 12.0)
+}
+
+/// Note name for the note `semitones` above C0, spelled with sharps and an
+/// uppercase letter as on a keyboard ("C4", "F#2").
+pub fn note_label(semitones: i32) -> String {
+    let pitch = PitchName::from_semitones_from_c0(semitones, 0.0);
+    let sharp = if pitch.accidental > 0 { "#" } else { "" };
+    format!(
+        "{}{}{}",
+        pitch.letter.to_ascii_uppercase(),
+        sharp,
+        pitch.octave
+    )
+}
+
+/// Nearest note and how far off it `hz` is, in cents (-50..50).
+pub fn nearest_note(hz: f32) -> (i32, f32) {
+    let semitones = hz_to_semitones(hz);
+    let note = semitones.round();
+    (note as i32, (semitones - note) * 100.0)
+}
+
+/// Whether the note is a black key on a piano.
+pub fn is_black_key(semitones: i32) -> bool {
+    matches!(semitones.rem_euclid(12), 1 | 3 | 6 | 8 | 10)
+}
+
+/// Notes whose pitch lies within `min_hz..=max_hz`, low to high.
+pub fn notes_in_range(min_hz: f32, max_hz: f32) -> std::ops::RangeInclusive<i32> {
+    let low = hz_to_semitones(min_hz.max(C0_HZ / 2.0)).ceil() as i32;
+    let high = hz_to_semitones(max_hz.max(C0_HZ / 2.0)).floor() as i32;
+    low..=high
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a4_is_440_hz_and_names_are_spelled_with_sharps() {
+        let a4 = 4 * 12 + 9;
+        assert!((semitones_to_hz(a4 as f32) - 440.0).abs() < 0.01);
+        assert_eq!(nearest_note(440.0).0, a4);
+        assert_eq!(note_label(a4), "A4");
+        assert_eq!(note_label(4 * 12), "C4");
+        assert_eq!(note_label(2 * 12 + 6), "F#2");
+        assert!(is_black_key(2 * 12 + 6));
+        assert!(!is_black_key(4 * 12 + 4));
+
+        let (note, cents) = nearest_note(446.0);
+        assert_eq!(note, a4);
+        assert!((cents - 23.4).abs() < 0.5, "cents = {}", cents);
+    }
+
+    #[test]
+    fn notes_in_range_covers_the_notes_inside_the_band() {
+        // A3 (220 Hz) to A4 (440 Hz): both ends are notes
+        let notes = notes_in_range(219.9, 440.1);
+        assert_eq!(notes, 3 * 12 + 9..=4 * 12 + 9);
+        assert_eq!(notes_in_range(225.0, 230.0).count(), 0);
+    }
+}
@@ -23,6 +23,9 @@ pub struct Spectrogram {
     pub max_freq: f32,
     pub min_time: f64,
     pub max_time: f64,
+    /// False when the phases are placeholders (an FFT CSV without a phase
+    /// column); reconstruction then needs Griffin-Lim to sound right.
+    pub has_phase: bool,
 }
 
 impl Spectrogram {
@@ -38,6 +41,7 @@ impl Spectrogram {
                 max_freq: 0.0,
                 min_time: 0.0,
                 max_time: 0.0,
+                has_phase: true,
             };
         }
 
@@ -61,6 +65,7 @@ impl Spectrogram {
             max_freq,
             min_time,
             max_time,
+            has_phase: true,
         }
     }
 
@@ -1,5 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+use super::mel::{hz_to_mel, mel_to_hz};
+
 /// A single color stop in a custom gradient (position 0.0..1.0, color as RGB floats 0.0..1.0).
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct GradientStop {
     pub position: f32,
     pub r: f32,
@@ -63,6 +67,31 @@ pub fn eval_gradient(stops: &[GradientStop], t: f32) -> (f32, f32, f32) {
     )
 }
 
+/// A custom gradient saved under a name from the gradient editor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradientPreset {
+    pub name: String,
+    pub stops: Vec<GradientStop>,
+}
+
+/// Save `preset`, replacing any preset with the same name (ignoring case).
+/// Returns its index.
+pub fn save_gradient_preset(presets: &mut Vec<GradientPreset>, preset: GradientPreset) -> usize {
+    match presets
+        .iter()
+        .position(|p| p.name.eq_ignore_ascii_case(&preset.name))
+    {
+        Some(index) => {
+            presets[index] = preset;
+            index
+        }
+        None => {
+            presets.push(preset);
+            presets.len() - 1
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[allow(dead_code)]
 pub enum FreqScale {
@@ -119,6 +148,14 @@ pub struct ViewState {
     pub freq_min_hz: f32,
     pub freq_max_hz: f32,
     pub freq_scale: FreqScale,
+    /// Mel view: the frequency axis is mel-spaced and each row shows a mel
+    /// band pooled from the FFT bins (overrides `freq_scale` while on)
+    pub mel_view: bool,
+    pub mel_bands: usize,
+    /// Difference view: the spectrogram being drawn holds the
+    /// reconstruction-minus-original level in dB, colored blue (lost) to
+    /// red (added) instead of through the colormap
+    pub difference_view: bool,
 
     // Time axis display range (viewport)
     pub time_min_sec: f64,
@@ -143,6 +180,9 @@ pub struct ViewState {
     /// Default: 1e-6. User-configurable via sidebar "Norm Floor" field.
     /// Uses f64 to allow very small thresholds (down to ~1e-30).
     pub recon_norm_floor: f64,
+    /// Griffin-Lim phase estimation passes before resynthesis (0 = use the
+    /// stored phases). Set from Analysis > Griffin-Lim Iterations.
+    pub recon_griffin_lim_iterations: usize,
 
     // Full data bounds (for reset zoom / unlocked scrolling)
     pub data_freq_max_hz: f32,
@@ -157,6 +197,9 @@ impl Default for ViewState {
             freq_min_hz: 100.0,
             freq_max_hz: 2000.0,
             freq_scale: FreqScale::Power(0.5),
+            mel_view: false,
+            mel_bands: 128,
+            difference_view: false,
 
             time_min_sec: 0.0,
             time_max_sec: 0.0,
@@ -172,6 +215,7 @@ impl Default for ViewState {
             recon_freq_min_hz: 0.0,
             recon_freq_max_hz: 5000.0,
             recon_norm_floor: 1e-6,
+            recon_griffin_lim_iterations: 0,
 
             data_freq_max_hz: 5000.0,
             data_time_min_sec: 0.0,
@@ -188,6 +232,11 @@ impl ViewState {
         let min = self.freq_min_hz.max(1.0);
         let max = self.freq_max_hz.max(min + 1.0);
 
+        if self.mel_view {
+            let (mel_min, mel_max) = (hz_to_mel(min), hz_to_mel(max));
+            return mel_to_hz(mel_min + (mel_max - mel_min) * t);
+        }
+
         match self.freq_scale {
             FreqScale::Linear => min + (max - min) * t,
             FreqScale::Log => min * (max / min).powf(t),
@@ -218,6 +267,11 @@ impl ViewState {
             return 1.0;
         }
 
+        if self.mel_view {
+            let (mel_min, mel_max) = (hz_to_mel(min), hz_to_mel(max));
+            return ((hz_to_mel(freq_hz) - mel_min) / (mel_max - mel_min)).clamp(0.0, 1.0);
+        }
+
         match self.freq_scale {
             FreqScale::Linear => ((freq_hz - min) / (max - min)).clamp(0.0, 1.0),
             FreqScale::Log => ((freq_hz / min).ln() / (max / min).ln()).clamp(0.0, 1.0),
@@ -1,11 +1,12 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use fltk::prelude::*;
+use fltk::{dialog, menu::Choice, prelude::*};
 
-use crate::app_state::AppState;
-use crate::data::{eval_gradient, ColormapId, GradientStop};
+use crate::app_state::{AppState, update_status_bar};
+use crate::data::{ColormapId, GradientPreset, GradientStop, eval_gradient, save_gradient_preset};
 use crate::layout::Widgets;
+use crate::settings::Settings;
 
 // ═══════════════════════════════════════════════════════════════════════════
 //  GRADIENT EDITOR (draw + handle callbacks for the gradient preview widget)
@@ -310,6 +311,164 @@ pub fn setup_gradient_editor(widgets: &Widgets, state: &Rc<RefCell<AppState>>) {
             }
         });
     }
+
+    setup_gradient_presets(widgets, state, &editor_state);
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+//  GRADIENT PRESETS (named custom gradients, saved to the INI)
+// ═══════════════════════════════════════════════════════════════════════════
+
+/// Refill the preset dropdown, showing the preset at `selected` (or none).
+fn fill_preset_choice(choice: &mut Choice, presets: &[GradientPreset], selected: Option<usize>) {
+    choice.clear();
+    for preset in presets {
+        choice.add_choice(&preset_menu_label(&preset.name));
+    }
+    choice.set_value(selected.map_or(-1, |i| i as i32));
+    choice.redraw();
+}
+
+/// A preset name as a menu label: FLTK reads `/` as a submenu, `_` as a
+/// divider, `\` as an escape, and `&` as a shortcut marker.
+fn preset_menu_label(name: &str) -> String {
+    let mut label = String::with_capacity(name.len());
+    for ch in name.chars() {
+        match ch {
+            '/' | '_' | '\\' => {
+                label.push('\\');
+                label.push(ch);
+            }
+            '&' => label.push_str("&&"),
+            _ => label.push(ch),
+        }
+    }
+    label
+}
+
+fn setup_gradient_presets(
+    widgets: &Widgets,
+    state: &Rc<RefCell<AppState>>,
+    editor_state: &Rc<RefCell<GradientEditorState>>,
+) {
+    fill_preset_choice(
+        &mut widgets.gradient_preset_choice.clone(),
+        &state.borrow().gradient_presets,
+        None,
+    );
+
+    // Pick a preset: load it into the editor and show it
+    {
+        let state = state.clone();
+        let editor_state = editor_state.clone();
+        let mut colormap_choice = widgets.colormap_choice.clone();
+        let mut spec_display = widgets.spec_display.clone();
+        let mut gradient_preview = widgets.gradient_preview.clone();
+        let mut status_bar = widgets.status_bar.clone();
+
+        let mut gradient_preset_choice = widgets.gradient_preset_choice.clone();
+        gradient_preset_choice.set_callback(move |c| {
+            let mut st = state.borrow_mut();
+            let Some(preset) = usize::try_from(c.value())
+                .ok()
+                .and_then(|i| st.gradient_presets.get(i).cloned())
+            else {
+                return;
+            };
+            st.view.custom_gradient = preset.stops;
+            st.view.colormap = ColormapId::Custom;
+            st.spec_renderer.invalidate();
+            st.status
+                .set_activity(&format!("Gradient preset: {}", preset.name));
+            update_status_bar(&mut status_bar, &st.status.render());
+            drop(st);
+
+            editor_state.borrow_mut().selected_stop = None;
+            let custom_idx = ColormapId::ALL
+                .iter()
+                .position(|&c| c == ColormapId::Custom)
+                .unwrap_or(0);
+            colormap_choice.set_value(custom_idx as i32);
+            spec_display.redraw();
+            gradient_preview.redraw();
+        });
+    }
+
+    // Save the custom gradient under a name (replacing a preset of that name)
+    {
+        let state = state.clone();
+        let mut gradient_preset_choice = widgets.gradient_preset_choice.clone();
+        let mut status_bar = widgets.status_bar.clone();
+
+        let mut btn_gradient_save = widgets.btn_gradient_save.clone();
+        btn_gradient_save.set_callback(move |_| {
+            let default_name = {
+                let st = state.borrow();
+                usize::try_from(gradient_preset_choice.value())
+                    .ok()
+                    .and_then(|i| st.gradient_presets.get(i))
+                    .map(|p| p.name.clone())
+                    .unwrap_or_else(|| format!("Gradient {}", st.gradient_presets.len() + 1))
+            };
+            let Some(name) = dialog::input_default("Preset name:", &default_name) else {
+                return;
+            };
+            let name = name.trim().to_string();
+            if name.is_empty() {
+                return;
+            }
+            if name.contains('|') {
+                dialog::alert_default("Preset names can't contain '|'.");
+                return;
+            }
+
+            let mut st = state.borrow_mut();
+            let preset = GradientPreset {
+                name,
+                stops: st.view.custom_gradient.clone(),
+            };
+            st.status
+                .set_activity(&format!("Gradient preset saved: {}", preset.name));
+            let index = save_gradient_preset(&mut st.gradient_presets, preset);
+            Settings::save_gradient_presets(&st.gradient_presets);
+            fill_preset_choice(
+                &mut gradient_preset_choice,
+                &st.gradient_presets,
+                Some(index),
+            );
+            update_status_bar(&mut status_bar, &st.status.render());
+        });
+    }
+
+    // Delete the selected preset
+    {
+        let state = state.clone();
+        let mut gradient_preset_choice = widgets.gradient_preset_choice.clone();
+
+        let mut btn_gradient_delete = widgets.btn_gradient_delete.clone();
+        btn_gradient_delete.set_callback(move |_| {
+            let Ok(index) = usize::try_from(gradient_preset_choice.value()) else {
+                return;
+            };
+            let Some(name) = state
+                .borrow()
+                .gradient_presets
+                .get(index)
+                .map(|p| p.name.clone())
+            else {
+                return;
+            };
+            let question = format!("Delete the gradient preset \"{}\"?", name);
+            if dialog::choice2_default(&question, "Cancel", "Delete", "") != Some(1) {
+                return;
+            }
+
+            let mut st = state.borrow_mut();
+            st.gradient_presets.remove(index);
+            Settings::save_gradient_presets(&st.gradient_presets);
+            fill_preset_choice(&mut gradient_preset_choice, &st.gradient_presets, None);
+        });
+    }
 }
 
 /// Find which stop handle (if any) is near the given normalized position.
@@ -0,0 +1,438 @@
+//! Headless batch analysis: `fft_analyzer fft input.wav ...` runs the FFT
+//! engine and the reconstructor on a file without building the GUI, and
+//! writes the FFT CSV, the reconstructed WAV, and/or a spectrogram PNG.
+//!
+//! Defaults are the analyzer's built-in ones; settings.ini is not read, so a
+//! script gives the same result on every machine.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+use anyhow::{Context, Result};
+
+use crate::csv_export;
+use crate::data::{
+    AudioData, ChannelMode, ColormapId, FftParams, Spectrogram, ViewState, WindowType,
+};
+use crate::image_export::{encode_png, parse_image_size};
+use crate::processing::fft_engine::FftEngine;
+use crate::processing::reconstructor::{MAX_GRIFFIN_LIM_ITERATIONS, Reconstructor};
+use crate::rendering::spectrogram_renderer::SpectrogramRenderer;
+
+const USAGE: &str = "\
+Usage: fft_analyzer fft <input.wav|.mp3|.flac|.ogg> [--csv <out.csv>] [--wav <recon.wav>]
+           [--png <spec.png>] [--size <WxH>] [--colormap <name>]
+           [--window <n>] [--overlap <percent>] [--window-type <name>] [--zero-pad <1|2|4|8>]
+           [--center] [--start <sec>] [--stop <sec>] [--freq-min <hz>] [--freq-max <hz>]
+           [--iterations <n>] [--channel <L+R|L|R|Mid|Side>] [--sample-rate <hz>] [--no-normalize]";
+
+/// Peak the input and the reconstruction are normalized to (the GUI default)
+const NORMALIZE_PEAK: f32 = 0.97;
+
+/// Default PNG size (the GUI's File > Export Image default)
+const DEFAULT_IMAGE_SIZE: (i32, i32) = (1920, 1080);
+
+/// Everything `fft_analyzer fft` was asked to do
+struct BatchOptions {
+    input: PathBuf,
+    csv: Option<PathBuf>,
+    wav: Option<PathBuf>,
+    png: Option<PathBuf>,
+    image_size: (i32, i32),
+    colormap: ColormapId,
+
+    /// Window, overlap, window type, zero padding and centering (the time
+    /// range and sample rate are filled in once the file is loaded)
+    params: FftParams,
+    start_seconds: f64,
+    stop_seconds: Option<f64>,
+
+    /// Reconstructed band, also the PNG's frequency axis (None = Nyquist)
+    freq_min_hz: f32,
+    freq_max_hz: Option<f32>,
+    griffin_lim_iterations: usize,
+
+    channel_mode: ChannelMode,
+    sample_rate: Option<u32>,
+    normalize: bool,
+}
+
+impl BatchOptions {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self {
+            input: PathBuf::new(),
+            csv: None,
+            wav: None,
+            png: None,
+            image_size: DEFAULT_IMAGE_SIZE,
+            colormap: ColormapId::Classic,
+            params: FftParams::default(),
+            start_seconds: 0.0,
+            stop_seconds: None,
+            freq_min_hz: 0.0,
+            freq_max_hz: None,
+            griffin_lim_iterations: 0,
+            channel_mode: ChannelMode::default(),
+            sample_rate: None,
+            normalize: true,
+        };
+        let mut input: Option<&String> = None;
+
+        let mut index = 0;
+        while index < args.len() {
+            let arg = args[index].as_str();
+            match arg {
+                "--center" => options.params.use_center = true,
+                "--no-normalize" => options.normalize = false,
+                _ if arg.starts_with("--") => {
+                    let value = args
+                        .get(index + 1)
+                        .ok_or_else(|| format!("{} needs a value", arg))?;
+                    options.parse_option(arg, value)?;
+                    index += 1;
+                }
+                _ if input.is_none() => input = Some(&args[index]),
+                _ => return Err(format!("unexpected argument '{}'", arg)),
+            }
+            index += 1;
+        }
+
+        options.input = input.ok_or("no input file given")?.into();
+        if options.csv.is_none() && options.wav.is_none() && options.png.is_none() {
+            return Err("nothing to write: give --csv, --wav, and/or --png".to_string());
+        }
+        if let Some(stop) = options.stop_seconds
+            && stop <= options.start_seconds
+        {
+            return Err("--stop must be after --start".to_string());
+        }
+        if let Some(max) = options.freq_max_hz
+            && max <= options.freq_min_hz
+        {
+            return Err("--freq-max must be above --freq-min".to_string());
+        }
+        Ok(options)
+    }
+
+    /// Reads one option that takes a value
+    fn parse_option(&mut self, arg: &str, value: &str) -> Result<(), String> {
+        let bad_value = |expected: &str| format!("{} must be {}, got '{}'", arg, expected, value);
+        match arg {
+            "--csv" => self.csv = Some(value.into()),
+            "--wav" => self.wav = Some(value.into()),
+            "--png" => self.png = Some(value.into()),
+            "--size" => {
+                self.image_size = parse_image_size(value).map_err(|e| format!("--size: {}", e))?;
+            }
+            "--colormap" => {
+                self.colormap = ColormapId::ALL
+                    .iter()
+                    .copied()
+                    .find(|c| c.name().eq_ignore_ascii_case(value))
+                    .ok_or_else(|| bad_value("a colormap name (Classic, Viridis, Magma, ...)"))?;
+            }
+            "--window" => match value.parse::<usize>() {
+                // The FFT window must be even, like the sidebar rounds it
+                Ok(n) if n >= 4 && n.is_multiple_of(2) => self.params.window_length = n,
+                _ => return Err(bad_value("an even number of samples, at least 4")),
+            },
+            "--overlap" => match value.parse::<f32>() {
+                Ok(percent) if (0.0..=99.0).contains(&percent) => {
+                    self.params.overlap_percent = percent
+                }
+                _ => return Err(bad_value("0 to 99")),
+            },
+            "--window-type" => {
+                self.params.window_type = match value.to_ascii_lowercase().as_str() {
+                    "rectangular" => WindowType::Rectangular,
+                    "hann" => WindowType::Hann,
+                    "hamming" => WindowType::Hamming,
+                    "blackman" => WindowType::Blackman,
+                    "kaiser" => WindowType::Kaiser(8.6),
+                    _ => return Err(bad_value("rectangular, hann, hamming, blackman, or kaiser")),
+                }
+            }
+            "--zero-pad" => match value.parse::<usize>() {
+                Ok(factor @ (1 | 2 | 4 | 8)) => self.params.zero_pad_factor = factor,
+                _ => return Err(bad_value("1, 2, 4, or 8")),
+            },
+            "--start" => match value.parse::<f64>() {
+                Ok(seconds) if seconds >= 0.0 => self.start_seconds = seconds,
+                _ => return Err(bad_value("a time in seconds")),
+            },
+            "--stop" => match value.parse::<f64>() {
+                Ok(seconds) if seconds > 0.0 => self.stop_seconds = Some(seconds),
+                _ => return Err(bad_value("a time in seconds")),
+            },
+            "--freq-min" => match value.parse::<f32>() {
+                Ok(hz) if hz >= 0.0 => self.freq_min_hz = hz,
+                _ => return Err(bad_value("a frequency in Hz")),
+            },
+            "--freq-max" => match value.parse::<f32>() {
+                Ok(hz) if hz > 0.0 => self.freq_max_hz = Some(hz),
+                _ => return Err(bad_value("a frequency in Hz")),
+            },
+            "--iterations" => match value.parse::<usize>() {
+                Ok(n) if n <= MAX_GRIFFIN_LIM_ITERATIONS => self.griffin_lim_iterations = n,
+                _ => {
+                    return Err(bad_value(&format!(
+                        "0 to {} (Griffin-Lim passes)",
+                        MAX_GRIFFIN_LIM_ITERATIONS
+                    )));
+                }
+            },
+            "--channel" => {
+                self.channel_mode = ChannelMode::from_name(value)
+                    .ok_or_else(|| bad_value("L+R, L, R, Mid, or Side"))?;
+            }
+            "--sample-rate" => match value.parse::<u32>() {
+                Ok(hz) if (8000..=192000).contains(&hz) => self.sample_rate = Some(hz),
+                _ => return Err(bad_value("8000 to 192000")),
+            },
+            _ => return Err(format!("unknown option '{}'", arg)),
+        }
+        Ok(())
+    }
+}
+
+/// Runs `fft_analyzer fft` with the arguments after "fft".
+/// Returns the process exit code: 0 on success, 1 on a bad argument or a
+/// file that can't be read or written.
+pub fn run_fft(args: &[String]) -> i32 {
+    let options = match BatchOptions::parse(args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            eprintln!("{}", USAGE);
+            return 1;
+        }
+    };
+    match run(&options) {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!("{}: error: {:#}", options.input.display(), error);
+            1
+        }
+    }
+}
+
+/// Load, analyze, reconstruct, and write what was asked for
+fn run(options: &BatchOptions) -> Result<()> {
+    let audio = load_audio(options)?;
+    println!(
+        "{}: {} Hz, {:.2} s, {} channel(s)",
+        options.input.display(),
+        audio.sample_rate,
+        audio.duration_seconds,
+        audio.num_channels()
+    );
+
+    let (params, view) = analysis_settings(options, &audio)?;
+    // Nothing cancels a batch run
+    let cancel = AtomicBool::new(false);
+    let spectrogram = FftEngine::process(&audio, &params, &cancel, None);
+    if spectrogram.num_frames() == 0 {
+        anyhow::bail!(
+            "no FFT frames: the analyzed range is shorter than the {}-sample window",
+            params.window_length
+        );
+    }
+    println!(
+        "FFT: {} frames x {} bins (window {}, hop {}, {:.2} Hz per bin)",
+        spectrogram.num_frames(),
+        spectrogram.num_bins(),
+        params.window_length,
+        params.hop_length(),
+        params.frequency_resolution()
+    );
+
+    if let Some(path) = &options.csv {
+        csv_export::export_to_csv(&spectrogram, &params, &view, path, None)?;
+        println!("Wrote {}", path.display());
+    }
+    if let Some(path) = &options.wav {
+        let mut reconstructed =
+            Reconstructor::reconstruct(&spectrogram, &params, &view, &cancel, None);
+        if options.normalize {
+            reconstructed.normalize(NORMALIZE_PEAK);
+        }
+        reconstructed.save_wav(path)?;
+        println!(
+            "Wrote {} ({:.2} s, {:.0}-{:.0} Hz)",
+            path.display(),
+            reconstructed.duration_seconds,
+            view.recon_freq_min_hz,
+            view.recon_freq_max_hz
+        );
+    }
+    if let Some(path) = &options.png {
+        write_png(path, &spectrogram, &params, &view, options.image_size)?;
+        println!(
+            "Wrote {} ({}x{})",
+            path.display(),
+            options.image_size.0,
+            options.image_size.1
+        );
+    }
+    Ok(())
+}
+
+/// Read the input the way Open does: sample rate conversion, channel
+/// selection, then normalization
+fn load_audio(options: &BatchOptions) -> Result<AudioData> {
+    let mut audio = AudioData::from_audio_file(&options.input)?;
+    if let Some(rate) = options.sample_rate
+        && rate != audio.sample_rate
+    {
+        audio.resample(rate);
+    }
+    if options.channel_mode != ChannelMode::Mix {
+        audio.select_channel(options.channel_mode);
+    }
+    if options.normalize {
+        audio.normalize(NORMALIZE_PEAK);
+    }
+    Ok(audio)
+}
+
+/// FFT params and view for the loaded file: the analyzed time range, the
+/// reconstructed band (clamped to Nyquist), and a PNG view of that band
+fn analysis_settings(options: &BatchOptions, audio: &AudioData) -> Result<(FftParams, ViewState)> {
+    let sample_rate = audio.sample_rate;
+    let to_sample = |seconds: f64| (seconds * sample_rate as f64).round() as usize;
+
+    let mut params = options.params.clone();
+    params.sample_rate = sample_rate;
+    params.start_sample = to_sample(options.start_seconds).min(audio.num_samples());
+    params.stop_sample = options
+        .stop_seconds
+        .map_or(audio.num_samples(), to_sample)
+        .min(audio.num_samples());
+    if params.start_sample >= params.stop_sample {
+        anyhow::bail!(
+            "--start {:.2} s is past the end of the file ({:.2} s)",
+            options.start_seconds,
+            audio.duration_seconds
+        );
+    }
+
+    let nyquist = audio.nyquist_freq();
+    let freq_max_hz = options.freq_max_hz.unwrap_or(nyquist).min(nyquist);
+    let freq_min_hz = options.freq_min_hz.min(freq_max_hz);
+    let view = ViewState {
+        freq_min_hz,
+        freq_max_hz,
+        time_min_sec: params.start_seconds(),
+        time_max_sec: params.stop_seconds(),
+        colormap: options.colormap,
+        recon_freq_count: params.num_frequency_bins(),
+        recon_freq_min_hz: freq_min_hz,
+        recon_freq_max_hz: freq_max_hz,
+        recon_griffin_lim_iterations: options.griffin_lim_iterations,
+        data_freq_max_hz: nyquist,
+        data_time_min_sec: 0.0,
+        data_time_max_sec: audio.duration_seconds,
+        max_freq_bins: params.num_frequency_bins(),
+        ..ViewState::default()
+    };
+    Ok((params, view))
+}
+
+/// Render the spectrogram alone (no axes or colorbar: those are drawn with
+/// FLTK, which needs a display) to a PNG, with the dB ceiling set to the
+/// loudest bin like the GUI does after an FFT
+fn write_png(
+    path: &Path,
+    spectrogram: &Spectrogram,
+    params: &FftParams,
+    view: &ViewState,
+    (width, height): (i32, i32),
+) -> Result<()> {
+    let mut view = view.clone();
+    let max_mag = spectrogram.max_magnitude();
+    if max_mag > 0.0 {
+        view.db_ceiling = 20.0 * max_mag.log10();
+    }
+    let pixels = SpectrogramRenderer::new().render_to_rgb(
+        spectrogram,
+        &view,
+        params,
+        params.start_seconds(),
+        params.stop_seconds(),
+        false,
+        width as usize,
+        height as usize,
+    );
+    encode_png(path, &pixels, width as u32, height as u32)
+        .with_context(|| format!("Failed to write the spectrogram image {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn parse_reads_options_and_rejects_bad_values() {
+        let options = BatchOptions::parse(&args(
+            "in.wav --window 4096 --overlap 50 --window-type blackman --center \
+             --csv out.csv --freq-max 8000 --channel Mid",
+        ))
+        .unwrap();
+        assert_eq!(options.input, PathBuf::from("in.wav"));
+        assert_eq!(options.params.window_length, 4096);
+        assert_eq!(options.params.overlap_percent, 50.0);
+        assert_eq!(options.params.window_type, WindowType::Blackman);
+        assert!(options.params.use_center);
+        assert_eq!(options.csv, Some(PathBuf::from("out.csv")));
+        assert_eq!(options.freq_max_hz, Some(8000.0));
+        assert_eq!(options.channel_mode, ChannelMode::Mid);
+
+        assert!(BatchOptions::parse(&args("in.wav")).is_err());
+        assert!(BatchOptions::parse(&args("--csv out.csv")).is_err());
+        assert!(BatchOptions::parse(&args("in.wav --csv out.csv --window 1001")).is_err());
+        assert!(BatchOptions::parse(&args("in.wav --csv out.csv --overlap 100")).is_err());
+        assert!(BatchOptions::parse(&args("in.wav --csv out.csv --window")).is_err());
+        assert!(BatchOptions::parse(&args("in.wav --csv out.csv --start 2 --stop 1")).is_err());
+        assert!(BatchOptions::parse(&args("in.wav --csv out.csv --bogus 1")).is_err());
+    }
+
+    #[test]
+    fn run_writes_csv_wav_and_png() {
+        let dir = std::env::temp_dir().join(format!("musickbeets_headless_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("tone.wav");
+        let sample_rate = 8000;
+        let tone: Vec<f32> = (0..sample_rate)
+            .map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / sample_rate as f32).sin())
+            .collect();
+        AudioData::from_samples(tone, sample_rate)
+            .save_wav(&input)
+            .unwrap();
+
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        let command = format!(
+            "{} --window 512 --overlap 75 --csv {} --wav {} --png {} --size 400x200",
+            input.display(),
+            path("out.csv"),
+            path("recon.wav"),
+            path("spec.png")
+        );
+        let options = BatchOptions::parse(&args(&command)).unwrap();
+        run(&options).unwrap();
+
+        let (spec, params, _, _) = csv_export::import_from_csv(dir.join("out.csv")).unwrap();
+        assert_eq!(params.window_length, 512);
+        assert!(spec.num_frames() > 0);
+        let recon = AudioData::from_wav_file(dir.join("recon.wav")).unwrap();
+        assert_eq!(recon.sample_rate, sample_rate);
+        assert!(recon.num_samples() > sample_rate as usize / 2);
+        let png_header = std::fs::read(dir.join("spec.png")).unwrap();
+        assert_eq!(&png_header[1..4], b"PNG");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,366 @@
+use anyhow::{Context, Result, anyhow, bail};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use fltk::{
+    draw,
+    enums::{ColorDepth, Font},
+    image::RgbImage,
+    prelude::*,
+    surface::{ImageSurface, SvgFileSurface},
+};
+
+use crate::app_state::AppState;
+use crate::callbacks_draw::{
+    draw_colorbar, format_freq_label, format_time_label, generate_freq_ticks, generate_time_ticks,
+    nice_step_value, roi_pixel_rect,
+};
+use crate::data::ViewState;
+use crate::rendering::color_lut::ColorLUT;
+use crate::rendering::spectrogram_renderer::SpectrogramRenderer;
+use crate::ui::theme;
+
+/// Smallest and largest image side accepted by the export dialog (pixels).
+pub const MIN_IMAGE_SIDE: i32 = 200;
+pub const MAX_IMAGE_SIDE: i32 = 16384;
+
+/// Where each part of an exported image goes. The spectrogram ("plot") gets
+/// whatever is left after the axes, titles, and colorbar; their sizes scale
+/// with the font, which scales with the image height.
+struct ImageLayout {
+    font_size: i32,
+    plot_x: i32,
+    plot_y: i32,
+    plot_w: i32,
+    plot_h: i32,
+    bar_x: i32,
+    bar_w: i32,
+}
+
+impl ImageLayout {
+    fn new(width: i32, height: i32) -> Self {
+        let font_size = (height / 70).clamp(10, 32);
+        let plot_x = font_size * 6; // frequency labels + axis title
+        let plot_y = font_size * 2; // title line
+        let right = font_size * 7; // gap + colorbar + dB labels
+        let bottom = font_size * 3; // time labels + axis title
+        Self {
+            font_size,
+            plot_x,
+            plot_y,
+            plot_w: (width - plot_x - right).max(1),
+            plot_h: (height - plot_y - bottom).max(1),
+            bar_x: width - right + font_size,
+            bar_w: font_size * 3 / 2,
+        }
+    }
+}
+
+/// Parse an image size typed as "1920x1080" (spaces, "X", "*", or "," also work).
+pub fn parse_image_size(text: &str) -> Result<(i32, i32)> {
+    let cleaned = text.trim().to_lowercase().replace(' ', "");
+    let (w, h) = cleaned
+        .split_once(['x', '*', ','])
+        .ok_or_else(|| anyhow!("Expected a size like 1920x1080, got \"{}\"", text.trim()))?;
+    let parse_side = |side: &str| -> Result<i32> {
+        let value: i32 = side
+            .parse()
+            .map_err(|_| anyhow!("\"{}\" is not a whole number of pixels", side))?;
+        if !(MIN_IMAGE_SIDE..=MAX_IMAGE_SIDE).contains(&value) {
+            bail!(
+                "Image sides must be {} to {} pixels, got {}",
+                MIN_IMAGE_SIDE,
+                MAX_IMAGE_SIDE,
+                value
+            );
+        }
+        Ok(value)
+    };
+    Ok((parse_side(w)?, parse_side(h)?))
+}
+
+/// True when the path asks for an SVG file (anything else is written as PNG).
+pub fn is_svg_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"))
+}
+
+/// Export the current spectrogram view - same layers, zoom, frequency scale,
+/// and colormap as on screen - with frequency/time axes, a dB colorbar, and a
+/// title, to a `width` x `height` PNG (or SVG when the path ends in `.svg`).
+///
+/// Must run on the main (FLTK) thread: the axes are drawn with fltk::draw
+/// on an offscreen surface.
+pub fn export_spectrogram_image(st: &AppState, path: &Path, width: i32, height: i32) -> Result<()> {
+    let layout = ImageLayout::new(width, height);
+    let (pixels, lut) = render_view_pixels(st, layout.plot_w as usize, layout.plot_h as usize)?;
+    let title = if st.current_filename.is_empty() {
+        format!("Spectrogram ({})", st.view.colormap.name())
+    } else {
+        format!("{} ({})", st.current_filename, st.view.colormap.name())
+    };
+
+    if is_svg_path(path) {
+        // SvgFileSurface panics if it can't create the file, so check first
+        File::create(path).with_context(|| format!("Failed to create SVG file: {:?}", path))?;
+        let surface = SvgFileSurface::new(width, height, path);
+        SvgFileSurface::push_current(&surface);
+        let result = draw_image(&layout, &pixels, &lut, &st.view, &title, width, height);
+        SvgFileSurface::pop_current();
+        // The SVG is written out when the surface is dropped
+        drop(surface);
+        result
+    } else {
+        let surface = ImageSurface::new(width, height, false);
+        ImageSurface::push_current(&surface);
+        let result = draw_image(&layout, &pixels, &lut, &st.view, &title, width, height);
+        ImageSurface::pop_current();
+        result?;
+        let image = surface
+            .image()
+            .ok_or_else(|| anyhow!("Failed to read back the drawn image"))?;
+        write_png(path, &image)
+    }
+}
+
+/// Render the spectrogram layers into an offscreen RGB buffer the way the
+/// spectrogram widget draws them: the overview layer everywhere, then the
+/// focus layer inside the ROI rectangle. Returns the pixels and the color
+/// LUT they were drawn with (for the colorbar).
+fn render_view_pixels(st: &AppState, width: usize, height: usize) -> Result<(Vec<u8>, ColorLUT)> {
+    let overview_spec = st
+        .overview_spectrogram
+        .clone()
+        .or_else(|| st.spectrogram.clone());
+    let difference_spec = st.difference_layer();
+    let focus_spec = difference_spec
+        .clone()
+        .or_else(|| st.focus_spectrogram.clone())
+        .or_else(|| st.spectrogram.clone());
+    if overview_spec.is_none() && focus_spec.is_none() {
+        bail!("No spectrogram to export.\n\nOpen an audio file first.");
+    }
+
+    let proc_time_min = st.fft_params.start_seconds();
+    let proc_time_max = st.fft_params.stop_seconds();
+    let mut renderer = SpectrogramRenderer::new();
+
+    let mut pixels = match &overview_spec {
+        Some(spec) => renderer.render_to_rgb(
+            spec,
+            &st.view,
+            &st.overview_render_params(),
+            proc_time_min,
+            proc_time_max,
+            st.render_full_file_outside_roi,
+            width,
+            height,
+        ),
+        None => {
+            let bg = theme::BG_DARK;
+            let bg_rgb = [(bg >> 16) as u8, (bg >> 8) as u8, bg as u8];
+            bg_rgb.repeat(width * height)
+        }
+    };
+
+    let roi = roi_pixel_rect(
+        &st.view,
+        proc_time_min,
+        proc_time_max,
+        width as i32,
+        height as i32,
+    );
+    if let (Some(spec), Some((x0, y0, roi_w, roi_h))) = (&focus_spec, roi) {
+        let focus_view = ViewState {
+            difference_view: difference_spec.is_some(),
+            ..st.view.clone()
+        };
+        let focus_pixels = renderer.render_to_rgb(
+            spec,
+            &focus_view,
+            &st.focus_render_params(),
+            proc_time_min,
+            proc_time_max,
+            false,
+            width,
+            height,
+        );
+        // Copy the ROI rows over the overview layer
+        let row_bytes = width * 3;
+        for y in y0 as usize..(y0 + roi_h) as usize {
+            let start = y * row_bytes + x0 as usize * 3;
+            let end = start + roi_w as usize * 3;
+            pixels[start..end].copy_from_slice(&focus_pixels[start..end]);
+        }
+    }
+
+    Ok((pixels, renderer.color_lut().clone()))
+}
+
+/// Draw the whole image (background, spectrogram, axes, colorbar, title) on
+/// the current fltk drawing surface.
+fn draw_image(
+    layout: &ImageLayout,
+    pixels: &[u8],
+    lut: &ColorLUT,
+    view: &ViewState,
+    title: &str,
+    width: i32,
+    height: i32,
+) -> Result<()> {
+    let ImageLayout {
+        font_size,
+        plot_x,
+        plot_y,
+        plot_w,
+        plot_h,
+        bar_x,
+        bar_w,
+    } = *layout;
+    let tick_len = (font_size / 2).max(4);
+
+    draw::set_draw_color(theme::color(theme::BG_DARK));
+    draw::draw_rectf(0, 0, width, height);
+
+    // Spectrogram
+    let mut plot = RgbImage::new(pixels, plot_w, plot_h, ColorDepth::Rgb8)
+        .map_err(|e| anyhow!("Failed to create spectrogram image: {:?}", e))?;
+    plot.draw(plot_x, plot_y, plot_w, plot_h);
+    draw::set_draw_color(theme::color(theme::BORDER));
+    draw::draw_rect(plot_x - 1, plot_y - 1, plot_w + 2, plot_h + 2);
+
+    // Title
+    draw::set_font(Font::HelveticaBold, font_size);
+    draw::set_draw_color(theme::color(theme::TEXT_PRIMARY));
+    draw::draw_text(title, plot_x, plot_y - font_size / 2);
+
+    // Frequency axis: ticks locked to nice Hz values, labels right-aligned
+    draw::set_font(Font::Helvetica, font_size);
+    let freq_ticks = generate_freq_ticks(
+        view.freq_min_hz,
+        view.freq_max_hz,
+        &|f| view.freq_to_y(f),
+        plot_h,
+    );
+    for (freq, y_norm) in freq_ticks {
+        let py = plot_y + plot_h - (y_norm * plot_h as f32) as i32;
+        draw::set_draw_color(theme::color(theme::BORDER));
+        draw::draw_line(plot_x - tick_len, py, plot_x - 1, py);
+        let label = format_freq_label(freq);
+        let (label_w, _) = draw::measure(&label, false);
+        draw::set_draw_color(theme::color(theme::TEXT_SECONDARY));
+        draw::draw_text(&label, plot_x - tick_len - 3 - label_w, py + font_size / 3);
+    }
+    let freq_title = "Frequency (Hz)";
+    let (freq_title_w, _) = draw::measure(freq_title, false);
+    draw::set_draw_color(theme::color(theme::TEXT_PRIMARY));
+    draw::draw_text_angled(
+        90,
+        freq_title,
+        font_size + 2,
+        plot_y + (plot_h + freq_title_w) / 2,
+    );
+
+    // Time axis: about one label per 80px at the nice step the widget uses
+    let (step, time_ticks) = generate_time_ticks(view.time_min_sec, view.time_max_sec, plot_w);
+    for t in time_ticks {
+        let px = plot_x + (view.time_to_x(t) * plot_w as f64) as i32;
+        draw::set_draw_color(theme::color(theme::BORDER));
+        draw::draw_line(px, plot_y + plot_h, px, plot_y + plot_h + tick_len);
+        let label = format_time_label(t, step);
+        let (label_w, _) = draw::measure(&label, false);
+        draw::set_draw_color(theme::color(theme::TEXT_SECONDARY));
+        draw::draw_text(
+            &label,
+            px - label_w / 2,
+            plot_y + plot_h + tick_len + font_size,
+        );
+    }
+    let time_title = "Time";
+    let (time_title_w, _) = draw::measure(time_title, false);
+    draw::set_draw_color(theme::color(theme::TEXT_PRIMARY));
+    draw::draw_text(
+        time_title,
+        plot_x + (plot_w - time_title_w) / 2,
+        plot_y + plot_h + tick_len + font_size * 2 + font_size / 2,
+    );
+
+    draw_colorbar(lut, bar_x, plot_y, bar_w, plot_h, font_size, tick_len)
+        .map_err(|e| anyhow!("Failed to create colorbar image: {:?}", e))
+}
+
+/// Write an image read back from an ImageSurface as an 8-bit RGB PNG.
+fn write_png(path: &Path, image: &RgbImage) -> Result<()> {
+    let width = image.data_w() as u32;
+    let height = image.data_h() as u32;
+    let data = image.to_rgb_data();
+    let rgb = match image.depth() {
+        ColorDepth::Rgb8 => data,
+        ColorDepth::Rgba8 => data
+            .chunks_exact(4)
+            .flat_map(|p| [p[0], p[1], p[2]])
+            .collect(),
+        other => bail!(
+            "Unexpected image depth from the drawing surface: {:?}",
+            other
+        ),
+    };
+    encode_png(path, &rgb, width, height)
+}
+
+/// Encode RGB8 pixels (rows top to bottom) to a PNG file.
+pub fn encode_png(path: &Path, rgb: &[u8], width: u32, height: u32) -> Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create PNG file: {:?}", path))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder
+        .write_header()
+        .with_context(|| format!("Failed to write PNG header: {:?}", path))?;
+    writer
+        .write_image_data(rgb)
+        .with_context(|| format!("Failed to write PNG data: {:?}", path))?;
+    writer
+        .finish()
+        .with_context(|| format!("Failed to finish PNG file: {:?}", path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_image_size_accepts_common_forms() {
+        assert_eq!(parse_image_size("1920x1080").unwrap(), (1920, 1080));
+        assert_eq!(parse_image_size(" 800 X 600 ").unwrap(), (800, 600));
+        assert_eq!(parse_image_size("1024*768").unwrap(), (1024, 768));
+        assert!(parse_image_size("1920").is_err());
+        assert!(parse_image_size("wide x tall").is_err());
+        assert!(parse_image_size("100x100").is_err());
+        assert!(parse_image_size("20000x1000").is_err());
+    }
+
+    #[test]
+    fn encode_png_round_trips_size() {
+        let path = std::env::temp_dir().join("musickbeets_image_export_test.png");
+        let (width, height) = (4u32, 3u32);
+        let rgb: Vec<u8> = (0..width * height * 3).map(|i| i as u8).collect();
+        encode_png(&path, &rgb, width, height).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+        // IHDR follows the signature: length, "IHDR", width, height
+        assert_eq!(&bytes[12..16], b"IHDR");
+        assert_eq!(u32::from_be_bytes(bytes[16..20].try_into().unwrap()), width);
+        assert_eq!(
+            u32::from_be_bytes(bytes[20..24].try_into().unwrap()),
+            height
+        );
+        assert!(is_svg_path(Path::new("out.SVG")));
+        assert!(!is_svg_path(Path::new("out.png")));
+    }
+}
@@ -1,6 +1,7 @@
 use fltk::{
+    browser::HoldBrowser,
     button::Button,
-    enums::{Align, FrameType},
+    enums::{Align, FrameType, Shortcut},
     frame::Frame,
     group::Flex,
     input::{FloatInput, Input},
@@ -31,6 +32,7 @@ pub const SPEC_RIGHT_GUTTER_W: i32 = 20;
 // ─── Widgets struct ─────────────────────────────────────────────────────────────
 // Holds cloneable handles to every widget that callbacks need to access.
 
+#[derive(Clone)]
 pub struct Widgets {
     pub root: Flex,
     pub menu: MenuBar,
@@ -57,6 +59,9 @@ pub struct Widgets {
     pub btn_rerun: Button,
     pub colormap_choice: Choice,
     pub gradient_preview: Widget,
+    pub gradient_preset_choice: Choice,
+    pub btn_gradient_save: Button,
+    pub btn_gradient_delete: Button,
     pub slider_scale: HorNiceSlider,
     pub lbl_scale_val: Frame,
     pub slider_threshold: HorNiceSlider,
@@ -74,6 +79,13 @@ pub struct Widgets {
     pub input_norm_floor: FloatInput,
     pub lbl_norm_floor_sci: Frame,
     pub btn_snap_to_view: Button,
+    pub btn_capture_noise: Button,
+    pub slider_noise_reduction: HorNiceSlider,
+    pub lbl_noise_reduction_val: Frame,
+    pub marker_list: HoldBrowser,
+    pub btn_marker_add: Button,
+    pub btn_marker_rename: Button,
+    pub btn_marker_delete: Button,
     pub lbl_info: MultilineOutput,
     pub btn_tooltips: fltk::button::CheckButton,
     pub check_lock_active: fltk::button::CheckButton,
@@ -93,10 +105,12 @@ pub struct Widgets {
     pub btn_play: Button,
     pub btn_pause: Button,
     pub btn_stop: Button,
+    pub btn_ab: Button,
     pub btn_mouse_mode_time: Button,
     pub btn_mouse_mode_move: Button,
     pub btn_mouse_mode_zoom: Button,
     pub btn_mouse_mode_roi: Button,
+    pub btn_mouse_mode_brush: Button,
     pub scrub_slider: Widget,
     pub cursor_readout: Frame,
     pub lbl_time: Frame,
@@ -345,6 +359,18 @@ pub fn build_ui() -> (Window, Widgets) {
     set_tooltip(&mut btn_stop, "Stop playback and reset to start.");
     transport_row.fixed(&btn_stop, 36);
 
+    let mut btn_ab = Button::default().with_label("B: Recon");
+    btn_ab.set_color(theme::color(theme::BG_WIDGET));
+    btn_ab.set_label_color(theme::color(theme::TEXT_PRIMARY));
+    btn_ab.set_label_size(11);
+    btn_ab.set_shortcut(Shortcut::Ctrl | 'b');
+    btn_ab.deactivate();
+    set_tooltip(
+        &mut btn_ab,
+        "A/B compare (Ctrl+B): switch playback between the reconstruction (B) and the original audio (A) at the same position.",
+    );
+    transport_row.fixed(&btn_ab, 64);
+
     let mut mode_gap = Frame::default();
     mode_gap.set_frame(FrameType::FlatBox);
     mode_gap.set_color(theme::color(theme::BG_PANEL));
@@ -396,6 +422,16 @@ pub fn build_ui() -> (Window, Widgets) {
     );
     transport_row.fixed(&btn_mouse_mode_roi, 60);
 
+    let mut btn_mouse_mode_brush = Button::default().with_label("Brush");
+    btn_mouse_mode_brush.set_color(theme::color(theme::BG_WIDGET));
+    btn_mouse_mode_brush.set_label_color(theme::color(theme::TEXT_PRIMARY));
+    btn_mouse_mode_brush.deactivate();
+    set_tooltip(
+        &mut btn_mouse_mode_brush,
+        "Mouse mode: Brush. Paint on the spectrogram to attenuate or erase what is under the brush, then hear the result. Size and gain are in the Edit menu.",
+    );
+    transport_row.fixed(&btn_mouse_mode_brush, 52);
+
     // Flexible spacer pushes everything after it to the right
     Frame::default();
 
@@ -486,6 +522,9 @@ pub fn build_ui() -> (Window, Widgets) {
         btn_rerun: sb.btn_rerun,
         colormap_choice: sb.colormap_choice,
         gradient_preview: sb.gradient_preview,
+        gradient_preset_choice: sb.gradient_preset_choice,
+        btn_gradient_save: sb.btn_gradient_save,
+        btn_gradient_delete: sb.btn_gradient_delete,
         slider_scale: sb.slider_scale,
         lbl_scale_val: sb.lbl_scale_val,
         slider_threshold: sb.slider_threshold,
@@ -503,6 +542,13 @@ pub fn build_ui() -> (Window, Widgets) {
         input_norm_floor: sb.input_norm_floor,
         lbl_norm_floor_sci: sb.lbl_norm_floor_sci,
         btn_snap_to_view: sb.btn_snap_to_view,
+        btn_capture_noise: sb.btn_capture_noise,
+        slider_noise_reduction: sb.slider_noise_reduction,
+        lbl_noise_reduction_val: sb.lbl_noise_reduction_val,
+        marker_list: sb.marker_list,
+        btn_marker_add: sb.btn_marker_add,
+        btn_marker_rename: sb.btn_marker_rename,
+        btn_marker_delete: sb.btn_marker_delete,
         lbl_info: sb.lbl_info,
         btn_tooltips: sb.btn_tooltips,
         check_lock_active: sb.check_lock_active,
@@ -522,10 +568,12 @@ pub fn build_ui() -> (Window, Widgets) {
         btn_play,
         btn_pause,
         btn_stop,
+        btn_ab,
         btn_mouse_mode_time,
         btn_mouse_mode_move,
         btn_mouse_mode_zoom,
         btn_mouse_mode_roi,
+        btn_mouse_mode_brush,
         scrub_slider,
         cursor_readout,
         lbl_time,
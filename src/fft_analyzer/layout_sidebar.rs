@@ -1,4 +1,5 @@
 use fltk::{
+    browser::HoldBrowser,
     button::Button,
     enums::{Align, FrameType},
     frame::Frame,
@@ -12,6 +13,7 @@ use fltk::{
 };
 
 use crate::data::ColormapId;
+use crate::processing::noise_reduction::MAX_NOISE_REDUCTION_DB;
 use crate::ui::theme;
 use crate::ui::tooltips::set_tooltip;
 use crate::validation::{attach_float_validation, attach_uint_validation};
@@ -43,6 +45,9 @@ pub struct SidebarWidgets {
     pub btn_rerun: Button,
     pub colormap_choice: Choice,
     pub gradient_preview: Widget,
+    pub gradient_preset_choice: Choice,
+    pub btn_gradient_save: Button,
+    pub btn_gradient_delete: Button,
     pub slider_scale: HorNiceSlider,
     pub lbl_scale_val: Frame,
     pub slider_threshold: HorNiceSlider,
@@ -60,6 +65,13 @@ pub struct SidebarWidgets {
     pub input_norm_floor: FloatInput,
     pub lbl_norm_floor_sci: Frame,
     pub btn_snap_to_view: Button,
+    pub btn_capture_noise: Button,
+    pub slider_noise_reduction: HorNiceSlider,
+    pub lbl_noise_reduction_val: Frame,
+    pub marker_list: HoldBrowser,
+    pub btn_marker_add: Button,
+    pub btn_marker_rename: Button,
+    pub btn_marker_delete: Button,
     pub lbl_info: MultilineOutput,
     pub btn_tooltips: fltk::button::CheckButton,
     pub check_lock_active: fltk::button::CheckButton,
@@ -398,6 +410,38 @@ If Segments/Active is locked (e.g. 1), bins may be constrained by that lock.",
     );
     left.fixed(&gradient_preview, 30);
 
+    // Gradient presets: load a saved gradient, save or delete one
+    let mut gradient_preset_row = Flex::default().row();
+    gradient_preset_row.set_pad(2);
+
+    let mut gradient_preset_choice = Choice::default();
+    gradient_preset_choice.set_color(theme::color(theme::BG_WIDGET));
+    gradient_preset_choice.set_text_color(theme::color(theme::TEXT_PRIMARY));
+    set_tooltip(
+        &mut gradient_preset_choice,
+        "Saved custom gradients.\nPick one to load it into the editor\n(switches the colormap to Custom).",
+    );
+
+    let mut btn_gradient_save = Button::default().with_label("Save");
+    btn_gradient_save.set_color(theme::color(theme::BG_WIDGET));
+    btn_gradient_save.set_label_color(theme::color(theme::TEXT_PRIMARY));
+    btn_gradient_save.set_label_size(11);
+    set_tooltip(
+        &mut btn_gradient_save,
+        "Save the custom gradient as a named preset.\nSaving under an existing name replaces it.",
+    );
+    gradient_preset_row.fixed(&btn_gradient_save, 40);
+
+    let mut btn_gradient_delete = Button::default().with_label("Delete");
+    btn_gradient_delete.set_color(theme::color(theme::BG_WIDGET));
+    btn_gradient_delete.set_label_color(theme::color(theme::TEXT_PRIMARY));
+    btn_gradient_delete.set_label_size(11);
+    set_tooltip(&mut btn_gradient_delete, "Delete the selected preset.");
+    gradient_preset_row.fixed(&btn_gradient_delete, 46);
+
+    gradient_preset_row.end();
+    left.fixed(&gradient_preset_row, 25);
+
     // Freq Scale Power slider
     let mut slider_scale = HorNiceSlider::default();
     slider_scale.set_minimum(0.0);
@@ -622,12 +666,98 @@ If Segments/Active is locked (e.g. 1), bins may be constrained by that lock.",
     );
     left.fixed(&btn_snap_to_view, 25);
 
+    // Noise reduction (spectral subtraction against a captured noise profile)
+    let mut btn_capture_noise = Button::default().with_label("Capture Noise Profile");
+    btn_capture_noise.set_color(theme::color(theme::BG_WIDGET));
+    btn_capture_noise.set_label_color(theme::color(theme::TEXT_PRIMARY));
+    btn_capture_noise.set_label_size(11);
+    btn_capture_noise.deactivate();
+    set_tooltip(
+        &mut btn_capture_noise,
+        "Average the spectrum between Start and Stop\nand use it as the noise to remove.\n\nSet Start/Stop on a stretch of noise only\n(e.g. Shift+drag), capture, then set them\nback and recompute. Applies to the whole file.",
+    );
+    left.fixed(&btn_capture_noise, 25);
+
+    let mut slider_noise_reduction = HorNiceSlider::default();
+    slider_noise_reduction.set_minimum(0.0);
+    slider_noise_reduction.set_maximum(MAX_NOISE_REDUCTION_DB as f64);
+    slider_noise_reduction.set_step(1.0, 1);
+    slider_noise_reduction.set_value(12.0);
+    slider_noise_reduction.set_color(theme::color(theme::BG_WIDGET));
+    slider_noise_reduction.set_selection_color(theme::accent_color());
+    slider_noise_reduction.deactivate();
+    set_tooltip(
+        &mut slider_noise_reduction,
+        "How far noise-only bins are turned down.\nFunctional range: 0 dB (off) to 40 dB.\nBins well above the noise profile pass.\nHigh values can leave a watery sound.",
+    );
+    left.fixed(&slider_noise_reduction, 22);
+
+    let mut lbl_noise_reduction_val = Frame::default().with_label("Noise Reduction: 12 dB");
+    lbl_noise_reduction_val.set_label_color(theme::color(theme::TEXT_SECONDARY));
+    lbl_noise_reduction_val.set_label_size(11);
+    lbl_noise_reduction_val.set_align(Align::Inside | Align::Right);
+    left.fixed(&lbl_noise_reduction_val, 14);
+
     // Separator
     let mut sep4 = Frame::default();
     sep4.set_frame(FrameType::FlatBox);
     sep4.set_color(theme::color(theme::SEPARATOR));
     left.fixed(&sep4, 1);
 
+    // ════════════════════════════════════════════════════════════════
+    //  SECTION: Markers
+    // ════════════════════════════════════════════════════════════════
+
+    let mut lbl_markers = Frame::default().with_label("MARKERS");
+    lbl_markers.set_label_color(theme::section_header_color());
+    lbl_markers.set_label_size(11);
+    lbl_markers.set_align(Align::Inside | Align::Left);
+    left.fixed(&lbl_markers, 18);
+
+    let mut marker_list = HoldBrowser::default();
+    marker_list.set_color(theme::color(theme::BG_WIDGET));
+    marker_list.set_selection_color(theme::color(theme::BG_PANEL));
+    marker_list.set_text_size(10);
+    set_tooltip(
+        &mut marker_list,
+        "Named markers, in time order.\nClick one to jump the view and playback to it.\nPress M over the spectrogram to add one there.",
+    );
+    left.fixed(&marker_list, 100);
+
+    let mut marker_btn_row = Flex::default().row();
+    marker_btn_row.set_pad(2);
+
+    let mut btn_marker_add = Button::default().with_label("Add");
+    btn_marker_add.set_color(theme::color(theme::BG_WIDGET));
+    btn_marker_add.set_label_color(theme::color(theme::TEXT_PRIMARY));
+    btn_marker_add.set_label_size(11);
+    btn_marker_add.deactivate();
+    set_tooltip(
+        &mut btn_marker_add,
+        "Add a marker at the playback position (M).\nWith the pointer over the spectrogram, M adds\nit at the pointer's time and frequency instead.",
+    );
+
+    let mut btn_marker_rename = Button::default().with_label("Rename");
+    btn_marker_rename.set_color(theme::color(theme::BG_WIDGET));
+    btn_marker_rename.set_label_color(theme::color(theme::TEXT_PRIMARY));
+    btn_marker_rename.set_label_size(11);
+    set_tooltip(&mut btn_marker_rename, "Rename the selected marker.");
+
+    let mut btn_marker_delete = Button::default().with_label("Delete");
+    btn_marker_delete.set_color(theme::color(theme::BG_WIDGET));
+    btn_marker_delete.set_label_color(theme::color(theme::TEXT_PRIMARY));
+    btn_marker_delete.set_label_size(11);
+    set_tooltip(&mut btn_marker_delete, "Delete the selected marker.");
+
+    marker_btn_row.end();
+    left.fixed(&marker_btn_row, 25);
+
+    // Separator
+    let mut sep_markers = Frame::default();
+    sep_markers.set_frame(FrameType::FlatBox);
+    sep_markers.set_color(theme::color(theme::SEPARATOR));
+    left.fixed(&sep_markers, 1);
+
     // ════════════════════════════════════════════════════════════════
     //  SECTION: Info Panel (read-only)
     // ════════════════════════════════════════════════════════════════
@@ -643,7 +773,7 @@ If Segments/Active is locked (e.g. 1), bins may be constrained by that lock.",
     lbl_info.set_text_color(theme::color(theme::TEXT_SECONDARY));
     lbl_info.set_text_size(10);
     lbl_info.set_color(theme::color(theme::BG_WIDGET));
-    left.fixed(&lbl_info, 110);
+    left.fixed(&lbl_info, 136);
 
     // Separator
     let mut sep5 = Frame::default();
@@ -729,6 +859,9 @@ If Segments/Active is locked (e.g. 1), bins may be constrained by that lock.",
         btn_rerun,
         colormap_choice,
         gradient_preview,
+        gradient_preset_choice,
+        btn_gradient_save,
+        btn_gradient_delete,
         slider_scale,
         lbl_scale_val,
         slider_threshold,
@@ -746,6 +879,13 @@ If Segments/Active is locked (e.g. 1), bins may be constrained by that lock.",
         input_norm_floor,
         lbl_norm_floor_sci,
         btn_snap_to_view,
+        btn_capture_noise,
+        slider_noise_reduction,
+        lbl_noise_reduction_val,
+        marker_list,
+        btn_marker_add,
+        btn_marker_rename,
+        btn_marker_delete,
         lbl_info,
         btn_tooltips,
         check_lock_active,
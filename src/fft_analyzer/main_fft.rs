@@ -8,12 +8,15 @@ mod callbacks_ui;
 mod csv_export;
 mod data;
 mod gradient_editor;
+mod headless;
+mod image_export;
 mod layout;
 mod layout_sidebar;
 mod playback;
 mod poll_loop;
 mod processing;
 mod rendering;
+mod session;
 mod settings;
 mod ui;
 mod validation;
@@ -168,6 +171,8 @@ fn create_shared_callbacks(
         let mut check_center = widgets.check_center.clone();
         let mut zero_pad_choice = widgets.zero_pad_choice.clone();
         let mut btn_rerun = widgets.btn_rerun.clone();
+        let mut btn_capture_noise = widgets.btn_capture_noise.clone();
+        let mut slider_noise_reduction = widgets.slider_noise_reduction.clone();
         Rc::new(RefCell::new(Box::new(move || {
             btn_time_unit.activate();
             input_start.activate();
@@ -181,6 +186,8 @@ fn create_shared_callbacks(
             check_center.activate();
             zero_pad_choice.activate();
             btn_rerun.activate();
+            btn_capture_noise.activate();
+            slider_noise_reduction.activate();
         })))
     };
 
@@ -195,13 +202,16 @@ fn create_shared_callbacks(
         let mut btn_mouse_mode_move = widgets.btn_mouse_mode_move.clone();
         let mut btn_mouse_mode_zoom = widgets.btn_mouse_mode_zoom.clone();
         let mut btn_mouse_mode_roi = widgets.btn_mouse_mode_roi.clone();
+        let mut btn_mouse_mode_brush = widgets.btn_mouse_mode_brush.clone();
         let mut btn_play = widgets.btn_play.clone();
         let mut btn_pause = widgets.btn_pause.clone();
         let mut btn_stop = widgets.btn_stop.clone();
+        let mut btn_ab = widgets.btn_ab.clone();
         let mut scrub_slider = widgets.scrub_slider.clone();
         let mut repeat_choice = widgets.repeat_choice.clone();
         let mut btn_snap_to_view = widgets.btn_snap_to_view.clone();
         let mut check_render_full_outside_roi = widgets.check_render_full_outside_roi.clone();
+        let mut btn_marker_add = widgets.btn_marker_add.clone();
         Rc::new(RefCell::new(Box::new(move || {
             btn_save_fft.activate();
             input_freq_count.activate();
@@ -213,13 +223,16 @@ fn create_shared_callbacks(
             btn_mouse_mode_move.activate();
             btn_mouse_mode_zoom.activate();
             btn_mouse_mode_roi.activate();
+            btn_mouse_mode_brush.activate();
             btn_play.activate();
             btn_pause.activate();
             btn_stop.activate();
+            btn_ab.activate();
             scrub_slider.activate();
             repeat_choice.activate();
             btn_snap_to_view.activate();
             check_render_full_outside_roi.activate();
+            btn_marker_add.activate();
         })))
     };
 
@@ -255,7 +268,10 @@ fn create_shared_callbacks(
         let mut btn_mouse_mode_move = widgets.btn_mouse_mode_move.clone();
         let mut btn_mouse_mode_zoom = widgets.btn_mouse_mode_zoom.clone();
         let mut btn_mouse_mode_roi = widgets.btn_mouse_mode_roi.clone();
+        let mut btn_mouse_mode_brush = widgets.btn_mouse_mode_brush.clone();
         let mut btn_snap_to_view = widgets.btn_snap_to_view.clone();
+        let mut btn_capture_noise = widgets.btn_capture_noise.clone();
+        let mut slider_noise_reduction = widgets.slider_noise_reduction.clone();
         let mut check_render_full_outside_roi = widgets.check_render_full_outside_roi.clone();
         Rc::new(RefCell::new(Box::new(move || {
             btn_time_unit.deactivate();
@@ -280,7 +296,10 @@ fn create_shared_callbacks(
             btn_mouse_mode_move.deactivate();
             btn_mouse_mode_zoom.deactivate();
             btn_mouse_mode_roi.deactivate();
+            btn_mouse_mode_brush.deactivate();
             btn_snap_to_view.deactivate();
+            btn_capture_noise.deactivate();
+            slider_noise_reduction.deactivate();
             check_render_full_outside_roi.deactivate();
         })))
     };
@@ -331,6 +350,16 @@ fn create_shared_callbacks(
         })))
     };
 
+    let update_markers: SharedCb = {
+        let state = state.clone();
+        let mut marker_list = widgets.marker_list.clone();
+        Rc::new(RefCell::new(Box::new(move || {
+            if let Ok(st) = state.try_borrow() {
+                callbacks_ui::fill_marker_list(&mut marker_list, &st, None);
+            }
+        })))
+    };
+
     SharedCallbacks {
         update_info,
         update_seg_label,
@@ -342,6 +371,7 @@ fn create_shared_callbacks(
         set_btn_cancel_mode,
         set_btn_busy_mode,
         set_btn_normal_mode,
+        update_markers,
     }
 }
 
@@ -350,6 +380,12 @@ fn create_shared_callbacks(
 // ═══════════════════════════════════════════════════════════════════════════
 
 fn main() {
+    // "fft_analyzer fft input.wav --csv out.csv" analyzes without the GUI
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("fft") {
+        std::process::exit(headless::run_fft(&args[2..]));
+    }
+
     // Disable GTK native file dialogs — they depend on dbus/GVFS volume monitors
     // which hang or freeze in environments without a full GNOME session
     // (Termux chroot, VNC, WSL, containers, etc.). FLTK's own file chooser
@@ -397,15 +433,23 @@ fn main() {
         st.view.freq_min_hz = cfg.view_freq_min_hz;
         st.view.freq_max_hz = cfg.view_freq_max_hz;
         st.view.freq_scale = data::FreqScale::Power(cfg.freq_scale_power);
+        st.view.mel_view = cfg.mel_view;
+        st.view.mel_bands = cfg.mel_bands;
+        st.show_partials = cfg.show_partials;
+        st.show_note_ruler = cfg.show_note_ruler;
+        st.show_beat_grid = cfg.show_beat_grid;
+        st.show_colorbar = cfg.show_colorbar;
         st.view.threshold_db = cfg.threshold_db;
         st.view.brightness = cfg.brightness;
         st.view.gamma = cfg.gamma;
         st.view.colormap = data::ColormapId::from_index(cfg.colormap_index());
         st.view.custom_gradient = cfg.parse_custom_gradient();
+        st.gradient_presets = cfg.gradient_presets.clone();
         st.view.recon_freq_min_hz = cfg.recon_freq_min_hz;
         st.view.recon_freq_max_hz = cfg.recon_freq_max_hz;
         st.view.recon_freq_count = cfg.recon_freq_count;
         st.view.recon_norm_floor = cfg.recon_norm_floor;
+        st.view.recon_griffin_lim_iterations = cfg.griffin_lim_iterations;
         st.lock_to_active = cfg.lock_to_active;
         st.render_full_file_outside_roi = cfg.render_full_file_outside_roi;
         st.time_zoom_factor = cfg.time_zoom_factor;
@@ -414,6 +458,14 @@ fn main() {
         st.swap_zoom_axes = cfg.swap_zoom_axes;
         st.normalize_audio = cfg.normalize_audio;
         st.normalize_peak = cfg.normalize_peak;
+        st.channel_mode = data::ChannelMode::from_name(&cfg.channel_mode).unwrap_or_default();
+        st.load_sample_rate = cfg.load_sample_rate;
+        st.image_export_width = cfg.image_export_width;
+        st.image_export_height = cfg.image_export_height;
+        st.transcription_bpm = cfg.transcription_bpm;
+        st.brush_radius_px = cfg.brush_radius_px;
+        st.brush_gain_db = cfg.brush_gain_db;
+        st.noise_reduction_db = cfg.noise_reduction_db;
         st.view.db_ceiling = cfg.db_ceiling;
         st.fft_params.zero_pad_factor = cfg.zero_pad_factor;
         st.fft_params.target_segments_per_active = if cfg.target_segments_per_active > 0 {
@@ -451,7 +503,7 @@ fn main() {
     let shared = create_shared_callbacks(&widgets, &state, &win);
 
     // Wire up all callbacks
-    callbacks_nav::setup_menu_callbacks(&widgets, &state);
+    callbacks_nav::setup_menu_callbacks(&widgets, &state, &tx, &shared, &win);
     callbacks_nav::setup_shortcut_key_button(&widgets);
     callbacks_file::setup_file_callbacks(&widgets, &state, &tx, &shared, &win);
     callbacks_file::setup_rerun_callback(&widgets, &state, &tx, &shared);
@@ -461,11 +513,12 @@ fn main() {
     callbacks_ui::setup_playback_callbacks(&widgets, &state);
     callbacks_ui::setup_misc_callbacks(&widgets, &state, &win);
     callbacks_ui::setup_mouse_mode_callbacks(&widgets, &state);
-    callbacks_draw::setup_draw_callbacks(&widgets, &state, &shared);
+    callbacks_ui::setup_marker_callbacks(&widgets, &state);
+    callbacks_draw::setup_draw_callbacks(&widgets, &state, &tx, &shared);
     let (x_scroll_gen, y_scroll_gen) = callbacks_nav::setup_scrollbar_callbacks(&widgets, &state);
     callbacks_nav::setup_zoom_callbacks(&widgets, &state);
     callbacks_nav::setup_snap_to_view(&widgets, &state);
-    callbacks_nav::setup_spacebar_handler(&mut win, &widgets);
+    callbacks_nav::setup_spacebar_handler(&mut win, &widgets, &state, &tx, &shared);
     // Per-widget spacebar guards MUST be last — they set handle() on widgets,
     // which would be overwritten if any later setup also calls handle().
     callbacks_nav::setup_spacebar_guards(&widgets);
@@ -474,49 +527,19 @@ fn main() {
     // Layout hardcodes default values (e.g. "8192" for segment size). After
     // loading the real settings into AppState, push the values into the widgets
     // so the UI matches state from the start.
-    {
-        let st = state.borrow();
-        widgets
-            .input_seg_size
-            .clone()
-            .set_value(&st.fft_params.window_length.to_string());
-        let preset_idx = match st.fft_params.window_length {
-            256 => 0,
-            512 => 1,
-            1024 => 2,
-            2048 => 3,
-            4096 => 4,
-            8192 => 5,
-            16384 => 6,
-            32768 => 7,
-            65536 => 8,
-            _ => 9,
-        };
-        widgets.seg_preset_choice.clone().set_value(preset_idx);
-        widgets
-            .check_render_full_outside_roi
-            .clone()
-            .set_checked(st.render_full_file_outside_roi);
-        widgets
-            .slider_overlap
-            .clone()
-            .set_value(st.fft_params.overlap_percent as f64);
-        widgets
-            .input_norm_floor
-            .clone()
-            .set_value(&format!("{}", st.view.recon_norm_floor));
-        widgets
-            .lbl_norm_floor_sci
-            .clone()
-            .set_label(&format!(
-                "{} = {}",
-                crate::validation::format_norm_floor_with_commas_f64(st.view.recon_norm_floor),
-                crate::validation::format_scientific_f64(st.view.recon_norm_floor)
-            ));
-    }
+    callbacks_ui::sync_widgets_to_state(&widgets, &state.borrow());
 
     // ── Start the 16ms poll loop (worker messages, scrollbar sync, transport) ──
-    poll_loop::start_poll_loop(&state, &widgets, &shared, &tx, rx, x_scroll_gen, y_scroll_gen, &win);
+    poll_loop::start_poll_loop(
+        &state,
+        &widgets,
+        &shared,
+        &tx,
+        rx,
+        x_scroll_gen,
+        y_scroll_gen,
+        &win,
+    );
 
     win.show();
     app.run().unwrap();
@@ -0,0 +1,68 @@
+use miniaudio::{Device, DeviceConfig, DeviceType, Format};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Captured samples are dropped (oldest first) past this many seconds, in
+/// case the UI stops draining them.
+const MAX_PENDING_SECONDS: usize = 2;
+
+/// Lock the capture buffer, recovering from poison like the player does:
+/// the samples are still usable after a panic elsewhere.
+fn lock_pending(mutex: &Mutex<Vec<f32>>) -> MutexGuard<'_, Vec<f32>> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        app_log!("AudioInput", "Warning: mutex was poisoned, recovering");
+        poisoned.into_inner()
+    })
+}
+
+/// Mono capture from the default input device. The audio callback appends
+/// to a shared buffer that the UI thread drains with `take_samples`.
+/// Capture stops when this is dropped.
+pub struct AudioInput {
+    _device: Device,
+    sample_rate: u32,
+    pending: Arc<Mutex<Vec<f32>>>,
+}
+
+impl AudioInput {
+    /// Open the default input device at `sample_rate` (miniaudio converts
+    /// from the device's own rate and mixes its channels down) and start
+    /// capturing.
+    pub fn start(sample_rate: u32) -> anyhow::Result<Self> {
+        let pending = Arc::new(Mutex::new(Vec::new()));
+        let max_pending = sample_rate as usize * MAX_PENDING_SECONDS;
+
+        let mut config = DeviceConfig::new(DeviceType::Capture);
+        config.capture_mut().set_format(Format::F32);
+        config.capture_mut().set_channels(1);
+        config.set_sample_rate(sample_rate);
+
+        let callback_pending = Arc::clone(&pending);
+        config.set_data_callback(move |_device, _output, input| {
+            let mut buffer = lock_pending(&callback_pending);
+            buffer.extend_from_slice(input.as_samples::<f32>());
+            let excess = buffer.len().saturating_sub(max_pending);
+            buffer.drain(..excess);
+        });
+
+        let device = Device::new(None, &config)
+            .map_err(|e| anyhow::anyhow!("Failed to open the input device: {:?}", e))?;
+        device
+            .start()
+            .map_err(|e| anyhow::anyhow!("Failed to start the input device: {:?}", e))?;
+
+        Ok(Self {
+            _device: device,
+            sample_rate,
+            pending,
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The samples captured since the last call.
+    pub fn take_samples(&self) -> Vec<f32> {
+        std::mem::take(&mut *lock_pending(&self.pending))
+    }
+}
@@ -80,6 +80,15 @@ impl AudioPlayer {
         Ok(())
     }
 
+    /// Swap in another buffer at the same sample rate without stopping:
+    /// position and play state carry over (clamped to the new length).
+    pub fn swap_samples(&self, samples: Arc<Vec<f32>>) {
+        let mut data = lock_playback(&self.playback_data);
+        data.end_sample = samples.len();
+        data.position = data.position.min(data.end_sample);
+        data.samples = samples;
+    }
+
     fn init_device(&mut self, sample_rate: u32) -> anyhow::Result<()> {
         let playback_data = Arc::clone(&self.playback_data);
 
@@ -1 +1,2 @@
+pub mod audio_input;
 pub mod audio_player;
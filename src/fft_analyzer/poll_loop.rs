@@ -11,7 +11,9 @@ use crate::app_state::{
 use crate::callbacks_file;
 use crate::data::TimeUnit;
 use crate::playback::audio_player::PlaybackState;
+use crate::processing::fft_engine::FftEngine;
 use crate::processing::reconstructor::Reconstructor;
+use crate::processing::{noise_reduction, recon_compare, spectral_edit};
 
 // ═══════════════════════════════════════════════════════════════════════════
 //  POLL LOOP (16ms timer — worker messages, scrollbar sync, transport)
@@ -74,6 +76,16 @@ pub fn start_poll_loop(
     let mut status_refresh_counter: u32 = 0;
 
     app::add_timeout3(0.016, move |handle| {
+        // ── Live input: analyze what was captured since the last tick ──
+        let live_updated = state
+            .try_borrow_mut()
+            .map(|mut st| st.pump_live())
+            .unwrap_or(false);
+        if live_updated {
+            spec_display.redraw();
+            time_axis.redraw();
+        }
+
         // Skip expensive per-tick work when idle: no audio and no spectrogram
         // means no scrollbars to sync, no transport to update, no info to refresh.
         // Worker messages (rx) are still polled so FFT completion is handled.
@@ -182,8 +194,25 @@ pub fn start_poll_loop(
                         &mut freq_axis,
                         &mut time_axis,
                         &enable_wav_export,
+                        &tx,
                     );
                 }
+                WorkerMessage::ComparisonComplete(comparison) => {
+                    {
+                        let mut st = state.borrow_mut();
+                        app_log!(
+                            "Compare",
+                            "Spectral convergence {:.1} dB, SNR {:?} dB",
+                            comparison.stats.spectral_convergence_db,
+                            comparison.stats.snr_db
+                        );
+                        st.recon_stats = Some(comparison.stats);
+                        st.difference_spectrogram = Some(Arc::new(comparison.difference));
+                        st.invalidate_all_spectrogram_renderers();
+                    }
+                    (update_info.borrow_mut())();
+                    spec_display.redraw();
+                }
                 WorkerMessage::AudioLoaded(audio, filename, norm_gain) => {
                     handle_audio_loaded(
                         audio,
@@ -194,6 +223,7 @@ pub fn start_poll_loop(
                         &mut status_bar,
                         &mut spec_display,
                         &mut waveform_display,
+                        &mut input_start,
                         &mut input_stop,
                         &mut input_recon_freq_max,
                         &mut input_norm_floor,
@@ -279,6 +309,7 @@ pub fn start_poll_loop(
                         let mut st = state.borrow_mut();
                         st.is_processing = false;
                         st.play_pending = false;
+                        st.pending_session = None;
                         st.progress_total = 0;
                         st.status.set_progress(None);
                         st.status.set_activity("Error: worker crashed");
@@ -326,6 +357,7 @@ pub fn start_poll_loop(
                     {
                         let mut st = state.borrow_mut();
                         st.is_processing = false;
+                        st.pending_session = None;
                         st.progress_total = 0;
                         st.status.set_progress(None);
                         st.status.set_activity("Ready");
@@ -454,7 +486,7 @@ fn sync_scrollbars(
 
 fn handle_fft_complete(
     stage: FftStage,
-    spectrogram: crate::data::Spectrogram,
+    mut spectrogram: crate::data::Spectrogram,
     state: &Rc<RefCell<AppState>>,
     slider_ceiling: &mut fltk::valuator::HorNiceSlider,
     lbl_ceiling_val: &mut fltk::frame::Frame,
@@ -484,6 +516,31 @@ fn handle_fft_complete(
             st.view.db_ceiling = 20.0 * max_mag.log10();
         }
 
+        // Noise reduction covers both layers, so the whole file is cleaned
+        let stage_params = match stage {
+            FftStage::Overview => st
+                .audio_data
+                .as_ref()
+                .map(|a| st.overview_params_for_audio(a.num_samples())),
+            FftStage::Focus => Some(st.fft_params.clone()),
+        };
+        st.noise_reduced = false;
+        if let Some(profile) = &st.noise_profile
+            && let Some(params) = &stage_params
+            && st.noise_reduction_db > 0.0
+        {
+            let noise = profile.levels_for(&spectrogram.frequencies, params);
+            noise_reduction::reduce_noise(&mut spectrogram, &noise, st.noise_reduction_db);
+            st.noise_reduced = true;
+        }
+
+        // Brush strokes survive a recompute: paint them onto the new focus
+        if matches!(stage, FftStage::Focus) {
+            for stroke in &st.spectral_edits {
+                spectral_edit::apply_stroke(&mut spectrogram, stroke);
+            }
+        }
+
         let spec_arc = Arc::new(spectrogram);
         let (min_t, max_t, max_f) = (spec_arc.min_time, spec_arc.max_time, spec_arc.max_freq);
 
@@ -537,9 +594,14 @@ fn handle_fft_complete(
                 return;
             }
             FftStage::Focus => {
+                // Open Session: the saved ceiling wins over the auto one
+                if let Some(session) = st.pending_session.take() {
+                    st.view.db_ceiling = session.db_ceiling;
+                }
                 st.focus_spectrogram = Some(spec_arc.clone());
                 st.focus_spec_params = Some(st.fft_params.clone());
                 st.spectrogram = Some(spec_arc);
+                st.clear_comparison();
                 st.view.max_freq_bins = st.fft_params.num_frequency_bins();
 
                 let spec = st.focus_spectrogram.clone().unwrap();
@@ -616,7 +678,11 @@ fn handle_fft_complete(
     // Set up progress tracking for reconstruction
     let progress = state.borrow().progress_counter.clone();
     progress.store(0, Ordering::Relaxed);
-    state.borrow_mut().progress_total = frame_end.saturating_sub(frame_start);
+    {
+        let mut st = state.borrow_mut();
+        st.progress_total =
+            Reconstructor::progress_steps(frame_end.saturating_sub(frame_start), &st.view);
+    }
 
     dbg_log!(
         crate::debug_flags::FFT_DBG,
@@ -683,6 +749,7 @@ fn handle_reconstruction_complete(
     freq_axis: &mut fltk::widget::Widget,
     time_axis: &mut fltk::widget::Widget,
     enable_wav_export: &SharedCb,
+    tx: &mpsc::Sender<WorkerMessage>,
 ) {
     dbg_log!(
         crate::debug_flags::FFT_DBG,
@@ -726,6 +793,10 @@ fn handle_reconstruction_complete(
                 st.wave_renderer.invalidate();
 
                 st.reconstructed_audio = Some(reconstructed);
+                // A/B stays on the original across recomputes
+                if st.playing_original {
+                    st.set_playing_original(true);
+                }
                 st.is_processing = false;
                 st.dirty = false;
 
@@ -750,6 +821,7 @@ fn handle_reconstruction_complete(
         Ok((_num_smp, _sr)) => {
             (enable_wav_export.borrow_mut())();
             update_status_bar(status_bar, &ready_status);
+            spawn_comparison(state, tx);
             spec_display.redraw();
             waveform_display.redraw();
             freq_axis.redraw();
@@ -798,6 +870,65 @@ fn handle_reconstruction_complete(
     (shared.set_btn_normal_mode.borrow_mut())();
 }
 
+/// Reanalyze the reconstruction with the focus params in the background
+/// and compare it with the focus spectrogram it was made from, for the
+/// Difference view and the INFO stats. Shares the reconstruction's cancel
+/// flag, so the next recompute drops a stale comparison.
+fn spawn_comparison(state: &Rc<RefCell<AppState>>, tx: &mpsc::Sender<WorkerMessage>) {
+    let st = state.borrow();
+    let (Some(focus), Some(recon)) = (st.focus_spectrogram.clone(), st.reconstructed_audio.clone())
+    else {
+        return;
+    };
+    let mut params = st.focus_render_params();
+    params.start_sample = 0;
+    params.stop_sample = recon.num_samples();
+    let original = st.original_under_reconstruction().map(<[f32]>::to_vec);
+    let time_offset = st.recon_start_seconds();
+    let (freq_min, freq_max) = (st.view.recon_freq_min_hz, st.view.recon_freq_max_hz);
+    let cancel = st.cancel_flag.clone();
+    drop(st);
+
+    let tx_clone = tx.clone();
+    std::thread::spawn(move || {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let analyzed = FftEngine::process(&recon, &params, &cancel, None);
+            let hop_seconds = params.hop_length() as f64 / params.sample_rate.max(1) as f64;
+            let mut comparison = recon_compare::compare(
+                &focus,
+                &analyzed,
+                time_offset,
+                hop_seconds,
+                freq_min,
+                freq_max,
+            )?;
+            comparison.stats.snr_db = original
+                .as_deref()
+                .and_then(|original| recon_compare::snr_db(original, &recon.samples));
+            Some(comparison)
+        }));
+        match result {
+            Ok(Some(comparison)) => {
+                if !cancel.load(Ordering::Relaxed) {
+                    tx_clone
+                        .send(WorkerMessage::ComparisonComplete(comparison))
+                        .ok();
+                }
+            }
+            Ok(None) => app_log!("Compare", "No frames to compare"),
+            Err(panic) => {
+                // The reconstruction itself is fine; only the stats are missing
+                let msg = panic
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                app_log!("Compare thread", "PANIC: {}", msg);
+            }
+        }
+    });
+}
+
 fn handle_audio_loaded(
     audio: crate::data::AudioData,
     filename: std::path::PathBuf,
@@ -807,6 +938,7 @@ fn handle_audio_loaded(
     status_bar: &mut fltk::output::MultilineOutput,
     spec_display: &mut fltk::widget::Widget,
     waveform_display: &mut fltk::widget::Widget,
+    input_start: &mut fltk::input::FloatInput,
     input_stop: &mut fltk::input::FloatInput,
     input_recon_freq_max: &mut fltk::input::FloatInput,
     input_norm_floor: &mut fltk::input::FloatInput,
@@ -846,6 +978,11 @@ fn handle_audio_loaded(
         st.focus_spectrogram = None;
         st.overview_spec_params = None;
         st.focus_spec_params = None;
+        st.spectral_edits.clear();
+        st.noise_profile = None;
+        st.noise_reduced = false;
+        st.clear_comparison();
+        st.live = None;
         st.audio_data = Some(audio.clone());
         st.has_audio = true;
         st.source_norm_gain = norm_gain;
@@ -861,6 +998,21 @@ fn handle_audio_loaded(
         st.view.max_freq_bins = st.fft_params.num_frequency_bins();
         st.view.recon_freq_count = st.fft_params.num_frequency_bins();
 
+        // Open Session: put back the saved range and zoom now that the
+        // file's length and Nyquist are known
+        {
+            let st = &mut *st;
+            if let Some(session) = &st.pending_session {
+                session.apply_to_loaded_audio(&mut st.fft_params, &mut st.view, num_smp, nyquist);
+            }
+            // Markers belong to the file; an opened session brings its own
+            st.markers = st
+                .pending_session
+                .as_ref()
+                .map(|session| session.markers.clone())
+                .unwrap_or_default();
+        }
+
         st.transport.duration_samples = num_smp;
         st.transport.sample_rate = sample_rate;
         st.transport.position_samples = 0;
@@ -877,6 +1029,8 @@ fn handle_audio_loaded(
             .to_string_lossy()
             .to_string();
         st.current_filename = fname.clone();
+        // A live snapshot has no file behind it
+        st.audio_path = std::fs::canonicalize(&filename).ok();
         drop(st);
         win_poll.set_label(&format!("muSickBeets - {}", fname));
     }
@@ -884,12 +1038,15 @@ fn handle_audio_loaded(
     // Sync UI widgets
     {
         let st = state.borrow();
-        match st.fft_params.time_unit {
+        let params = &st.fft_params;
+        match params.time_unit {
             crate::data::TimeUnit::Seconds => {
-                input_stop.set_value(&format!("{:.5}", duration));
+                input_start.set_value(&format!("{:.5}", params.start_seconds()));
+                input_stop.set_value(&format!("{:.5}", params.stop_seconds()));
             }
             crate::data::TimeUnit::Samples => {
-                input_stop.set_value(&num_smp.to_string());
+                input_start.set_value(&params.start_sample.to_string());
+                input_stop.set_value(&params.stop_sample.to_string());
             }
         }
         input_recon_freq_max.set_value(&format!("{:.0}", st.view.recon_freq_max_hz));
@@ -904,6 +1061,7 @@ fn handle_audio_loaded(
     (enable_audio_widgets.borrow_mut())();
     (update_info.borrow_mut())();
     (update_seg_label.borrow_mut())();
+    (shared.update_markers.borrow_mut())();
 
     // Launch overview FFT first; focused FFT and reconstruction follow.
     app_log!(
@@ -1,6 +1,7 @@
 use std::cell::RefCell;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::collections::VecDeque;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 use rayon::prelude::*;
 use realfft::RealFftPlanner;
@@ -14,6 +15,50 @@ thread_local! {
     static FFT_PLANNER: RefCell<RealFftPlanner<f32>> = RefCell::new(RealFftPlanner::new());
 }
 
+/// Window `samples` (the first `window.len()` of them), zero-pad to
+/// `n_fft` and transform. Returns the magnitudes (normalized by the FFT size,
+/// doubled for the bins between DC and Nyquist) and phases.
+fn analyze_frame(samples: &[f32], window: &[f32], n_fft: usize) -> (Vec<f32>, Vec<f32>) {
+    let fft = FFT_PLANNER.with(|p| p.borrow_mut().plan_fft_forward(n_fft));
+
+    let mut indata = vec![0.0f32; n_fft];
+    let mut spectrum = fft.make_output_vec();
+
+    // Apply window to first window_len samples; rest stays zero (zero-padding)
+    for ((out, &sample), &w) in indata.iter_mut().zip(samples).zip(window) {
+        *out = sample * w;
+    }
+
+    fft.process(&mut indata, &mut spectrum)
+        .expect("FFT processing failed");
+
+    let spec_bins = spectrum.len();
+    let mut magnitudes = Vec::with_capacity(spec_bins);
+    let mut phases = Vec::with_capacity(spec_bins);
+
+    for (bin_idx, complex_val) in spectrum.iter().enumerate() {
+        // Normalize magnitude by FFT size and scale by 2 for non-DC/Nyquist bins
+        let amplitude_scale = if bin_idx == 0 || bin_idx == spec_bins - 1 {
+            1.0
+        } else {
+            2.0
+        };
+        magnitudes.push((complex_val.norm() / n_fft as f32) * amplitude_scale);
+
+        phases.push(complex_val.arg());
+    }
+
+    (magnitudes, phases)
+}
+
+/// Frequency of each bin of an `n_fft`-point transform.
+fn bin_frequencies(sample_rate: u32, n_fft: usize) -> Vec<f32> {
+    let freq_resolution = sample_rate as f32 / n_fft as f32;
+    (0..n_fft / 2 + 1)
+        .map(|bin_idx| bin_idx as f32 * freq_resolution)
+        .collect()
+}
+
 pub struct FftEngine;
 
 impl FftEngine {
@@ -77,16 +122,12 @@ impl FftEngine {
         }
 
         let window = params.generate_window();
-        let freq_resolution = audio.sample_rate as f32 / n_fft as f32;
         let padded_arc = Arc::new(padded_audio);
         let window_arc = Arc::new(window);
 
         // Compute frequency bin values once — shared across all frames.
         // Previously each frame stored its own copy (~16 MB waste for 1000 frames).
-        let num_bins = n_fft / 2 + 1; // realfft output size
-        let frequencies: Vec<f32> = (0..num_bins)
-            .map(|bin_idx| bin_idx as f32 * freq_resolution)
-            .collect();
+        let frequencies = bin_frequencies(audio.sample_rate, n_fft);
 
         // Parallel FFT: each frame is independent.
         // Each frame checks the cancellation flag before doing work;
@@ -99,39 +140,13 @@ impl FftEngine {
                     return None;
                 }
 
-                let fft = FFT_PLANNER.with(|p| p.borrow_mut().plan_fft_forward(n_fft));
-
                 let start = frame_idx * hop;
-                let mut indata = vec![0.0f32; n_fft];
-                let mut spectrum = fft.make_output_vec();
-
-                // Apply window to first window_len samples; rest stays zero (zero-padding)
-                for i in 0..window_len {
-                    indata[i] = padded_arc[start + i] * window_arc[i];
-                }
-
-                fft.process(&mut indata, &mut spectrum)
-                    .expect("FFT processing failed");
+                let (magnitudes, phases) =
+                    analyze_frame(&padded_arc[start..start + window_len], &window_arc, n_fft);
 
                 let actual_sample = start_sample + frame_idx * hop;
                 let time_seconds = actual_sample as f64 / audio.sample_rate as f64;
 
-                let spec_bins = spectrum.len();
-                let mut magnitudes = Vec::with_capacity(spec_bins);
-                let mut phases = Vec::with_capacity(spec_bins);
-
-                for (bin_idx, complex_val) in spectrum.iter().enumerate() {
-                    // Normalize magnitude by FFT size and scale by 2 for non-DC/Nyquist bins
-                    let amplitude_scale = if bin_idx == 0 || bin_idx == spec_bins - 1 {
-                        1.0
-                    } else {
-                        2.0
-                    };
-                    magnitudes.push((complex_val.norm() / n_fft as f32) * amplitude_scale);
-
-                    phases.push(complex_val.arg());
-                }
-
                 if let Some(ctr) = progress {
                     ctr.fetch_add(1, Ordering::Relaxed);
                 }
@@ -159,3 +174,146 @@ impl FftEngine {
         Spectrogram::from_frames_with_frequencies(frames, frequencies)
     }
 }
+
+/// Incremental STFT for live input. Samples are pushed as they arrive and
+/// every frame whose window is complete comes out, with the same window,
+/// hop and scaling as `FftEngine::process`. Samples wait in a ring buffer
+/// until the frames that need them are done, so memory stays at about one
+/// window however long the stream runs.
+///
+/// Frame times count from the first sample pushed. There is no center
+/// padding before the first sample; with `use_center` the time is the
+/// middle of the window, like the centered batch frames.
+pub struct StreamingFft {
+    params: FftParams,
+    window: Vec<f32>,
+    frequencies: Vec<f32>,
+    /// Samples not yet hopped past, oldest first
+    ring: VecDeque<f32>,
+    /// Stream index of `ring[0]`
+    ring_start: usize,
+}
+
+impl StreamingFft {
+    pub fn new(params: FftParams) -> Self {
+        let window = params.generate_window();
+        let frequencies = bin_frequencies(params.sample_rate, params.n_fft_padded());
+        Self {
+            ring: VecDeque::with_capacity(params.window_length * 2),
+            params,
+            window,
+            frequencies,
+            ring_start: 0,
+        }
+    }
+
+    pub fn params(&self) -> &FftParams {
+        &self.params
+    }
+
+    /// Frequency of each bin of the frames `push` returns.
+    pub fn frequencies(&self) -> &[f32] {
+        &self.frequencies
+    }
+
+    /// Number of samples pushed so far.
+    pub fn samples_seen(&self) -> usize {
+        self.ring_start + self.ring.len()
+    }
+
+    /// Add `samples` to the stream and return the frames they completed,
+    /// oldest first.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<FftFrame> {
+        self.ring.extend(samples);
+
+        let window_len = self.params.window_length;
+        let hop = self.params.hop_length().max(1);
+        let n_fft = self.params.n_fft_padded();
+        let sample_rate = self.params.sample_rate.max(1) as f64;
+        let time_shift = if self.params.use_center {
+            window_len / 2
+        } else {
+            0
+        };
+
+        let mut frames = Vec::new();
+        while window_len > 0 && self.ring.len() >= window_len {
+            let (magnitudes, phases) = analyze_frame(
+                &self.ring.make_contiguous()[..window_len],
+                &self.window,
+                n_fft,
+            );
+            frames.push(FftFrame {
+                time_seconds: (self.ring_start + time_shift) as f64 / sample_rate,
+                magnitudes,
+                phases,
+            });
+            self.ring.drain(..hop.min(self.ring.len()));
+            self.ring_start += hop;
+        }
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(len: usize, sample_rate: u32) -> Vec<f32> {
+        (0..len)
+            .map(|i| (i as f32 * 440.0 * std::f32::consts::TAU / sample_rate as f32).sin())
+            .collect()
+    }
+
+    fn params(sample_rate: u32, len: usize) -> FftParams {
+        FftParams {
+            window_length: 256,
+            overlap_percent: 75.0,
+            use_center: false,
+            sample_rate,
+            start_sample: 0,
+            stop_sample: len,
+            ..FftParams::default()
+        }
+    }
+
+    #[test]
+    fn streaming_matches_batch_in_any_chunks() {
+        let samples = tone(4000, 8000);
+        let params = params(8000, samples.len());
+        let batch = FftEngine::process(
+            &AudioData::from_samples(samples.clone(), 8000),
+            &params,
+            &AtomicBool::new(false),
+            None,
+        );
+
+        let mut stream = StreamingFft::new(params);
+        let mut frames = Vec::new();
+        for chunk in samples.chunks(37) {
+            frames.extend(stream.push(chunk));
+        }
+
+        assert_eq!(stream.frequencies(), batch.frequencies.as_slice());
+        assert_eq!(frames.len(), batch.num_frames());
+        for (live, offline) in frames.iter().zip(&batch.frames) {
+            assert!((live.time_seconds - offline.time_seconds).abs() < 1e-12);
+            for (a, b) in live.magnitudes.iter().zip(&offline.magnitudes) {
+                assert!((a - b).abs() < 1e-6);
+            }
+        }
+        assert_eq!(stream.samples_seen(), samples.len());
+    }
+
+    #[test]
+    fn ring_buffer_holds_less_than_a_window() {
+        let mut stream = StreamingFft::new(params(8000, 0));
+        let mut count = 0;
+        for chunk in tone(80_000, 8000).chunks(500) {
+            count += stream.push(chunk).len();
+            assert!(stream.ring.len() < 256);
+        }
+        // 64-sample hop: one frame per hop once the first window is full
+        assert_eq!(count, (80_000 - 256) / 64 + 1);
+    }
+}
@@ -0,0 +1,185 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use musickbeets::helper::parse_pitch_name;
+use musickbeets::instrument_file::parse_instrument_text;
+use musickbeets::instruments::find_instrument_by_name;
+
+use crate::data::AudioData;
+
+/// Grain parameters written for a new instrument: the tracker's defaults
+/// (size, density, position, spray), spelled out so they are easy to edit.
+const GRAIN_PARAMETERS: &str = "[0.08, 20, 0, 0.05]";
+
+/// Turn what the user typed into a name cells can use ("Soft Pad" ->
+/// "soft_pad"), or say why it can't be one. The tracker rejects preset
+/// names that read as an instrument or a note, so those are refused here.
+pub fn instrument_name(text: &str) -> Result<String, String> {
+    let name: String = text
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_whitespace() || c == '-' {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    if name.is_empty() {
+        return Err("The name is empty.".to_string());
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!(
+            "'{}' can only use letters, digits, and _.",
+            text.trim()
+        ));
+    }
+    if find_instrument_by_name(&name).is_some() {
+        return Err(format!("'{}' is already a tracker instrument.", name));
+    }
+    if parse_pitch_name(&name).is_some() || matches!(name.as_str(), "clear" | "cl") {
+        return Err(format!("'{}' would be read as a note or command.", name));
+    }
+    Ok(name)
+}
+
+/// The instrument file entry for one exported sample.
+pub fn bank_entry(name: &str, wav_file_name: &str, source: &str, audio: &AudioData) -> String {
+    format!(
+        "[{}]\n# From {}, {:.2} s at {} Hz\nsample = \"{}\"\nparameters = {}   # grain size, density, position, spray\n",
+        name, source, audio.duration_seconds, audio.sample_rate, wav_file_name, GRAIN_PARAMETERS
+    )
+}
+
+/// Whether an instrument file already defines `name`.
+pub fn bank_has_instrument(bank_text: &str, name: &str) -> bool {
+    parse_instrument_text(bank_text)
+        .map(|instruments| instruments.iter().any(|i| i.name == name))
+        .unwrap_or(false)
+}
+
+/// Add `entry` (one instrument table) to the end of an instrument file,
+/// removing the old table of the same name if there is one.
+pub fn add_to_bank(bank_text: &str, name: &str, entry: &str) -> String {
+    let mut kept: Vec<&str> = Vec::new();
+    let mut in_replaced_table = false;
+    for line in bank_text.lines() {
+        if let Some(table) = table_name(line) {
+            in_replaced_table = table == name;
+        }
+        if !in_replaced_table {
+            kept.push(line);
+        }
+    }
+    while kept.last().is_some_and(|line| line.trim().is_empty()) {
+        kept.pop();
+    }
+
+    let mut text = kept.join("\n");
+    if !text.is_empty() {
+        text.push_str("\n\n");
+    }
+    text.push_str(entry);
+    text
+}
+
+/// The instrument a "[name]" (or "[instruments.name]") line starts, as the
+/// tracker reads it.
+fn table_name(line: &str) -> Option<String> {
+    let inner = line.trim().strip_prefix('[')?.split(']').next()?.trim();
+    Some(
+        inner
+            .rsplit('.')
+            .next()
+            .unwrap_or(inner)
+            .trim()
+            .to_lowercase(),
+    )
+}
+
+/// Write `audio` as `<name>.wav` next to the instrument file at `bank_path`
+/// and add (or replace) the instrument `name` in that file, creating it if
+/// needed. Returns the WAV path.
+pub fn export_instrument(
+    audio: &AudioData,
+    bank_path: &Path,
+    name: &str,
+    source: &str,
+) -> Result<PathBuf> {
+    let bank_text = if bank_path.exists() {
+        std::fs::read_to_string(bank_path)
+            .with_context(|| format!("Failed to read instrument file: {:?}", bank_path))?
+    } else {
+        String::new()
+    };
+    if let Err(e) = parse_instrument_text(&bank_text) {
+        bail!("{:?} is not a tracker instrument file ({})", bank_path, e);
+    }
+
+    let wav_file_name = format!("{}.wav", name);
+    let wav_path = bank_path
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(&wav_file_name);
+    audio.save_wav(&wav_path)?;
+
+    let entry = bank_entry(name, &wav_file_name, source, audio);
+    std::fs::write(bank_path, add_to_bank(&bank_text, name, &entry))
+        .with_context(|| format!("Failed to write instrument file: {:?}", bank_path))?;
+    Ok(wav_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instrument_names_follow_the_tracker_rules() {
+        assert_eq!(instrument_name(" Soft Pad "), Ok("soft_pad".to_string()));
+        assert_eq!(instrument_name("bell-2"), Ok("bell_2".to_string()));
+        assert!(instrument_name("").is_err());
+        assert!(instrument_name("pad!").is_err());
+        assert!(instrument_name("square").is_err());
+        assert!(instrument_name("c4").is_err());
+    }
+
+    #[test]
+    fn exported_instrument_loads_in_the_tracker() {
+        let folder = std::env::temp_dir().join("fft_instrument_export_test");
+        std::fs::create_dir_all(&folder).unwrap();
+        let bank_path = folder.join("bank.toml");
+        std::fs::write(
+            &bank_path,
+            "# My sounds\n[lead]\noscillator = \"saw\"\n\n[pad]\nsample = \"old.wav\"\n\n[bass]\noscillator = \"sine\"\n",
+        )
+        .unwrap();
+
+        let audio = AudioData::from_samples(vec![0.25; 4800], 48000);
+        let wav_path = export_instrument(&audio, &bank_path, "pad", "take.wav").unwrap();
+        let bank_text = std::fs::read_to_string(&bank_path).unwrap();
+        let instruments =
+            musickbeets::instrument_file::load_instrument_file(&bank_path.display().to_string());
+        let reloaded = AudioData::from_wav_file(&wav_path);
+        let _ = std::fs::remove_dir_all(&folder);
+
+        // The old pad is replaced, the other instruments stay in order
+        assert!(bank_text.starts_with("# My sounds\n[lead]"));
+        assert!(bank_text.contains("# From take.wav, 0.10 s at 48000 Hz"));
+        let instruments = instruments.unwrap();
+        let names: Vec<&str> = instruments.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, ["lead", "bass", "pad"]);
+        assert_eq!(
+            instruments[2].sample.as_deref(),
+            Some(wav_path.display().to_string().as_str())
+        );
+        assert_eq!(instruments[2].parameters, vec![0.08, 20.0, 0.0, 0.05]);
+        assert!(bank_has_instrument(&bank_text, "pad"));
+        assert!(!bank_has_instrument(&bank_text, "drone"));
+
+        let reloaded = reloaded.unwrap();
+        assert_eq!(reloaded.num_samples(), 4800);
+        assert!((reloaded.samples[0] - 0.25).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use super::fft_engine::StreamingFft;
+use crate::data::{AudioData, FftParams, Spectrogram};
+
+/// How many seconds of live input stay on screen and go into a snapshot.
+pub const LIVE_HISTORY_SECONDS: f64 = 10.0;
+
+/// Rolling analysis of a live input stream: the last `history_seconds` of
+/// samples and their spectrogram, which grows at the new end and is trimmed
+/// at the old end as samples are pushed.
+pub struct LiveAnalysis {
+    fft: StreamingFft,
+    history_samples: usize,
+    /// The retained input, oldest first
+    samples: VecDeque<f32>,
+    spectrogram: Arc<Spectrogram>,
+}
+
+impl LiveAnalysis {
+    /// `params` gives the window, overlap and sample rate; its Start/Stop
+    /// are ignored.
+    pub fn new(params: FftParams, history_seconds: f64) -> Self {
+        let history_samples = (history_seconds.max(0.0) * params.sample_rate as f64) as usize;
+        let fft = StreamingFft::new(params);
+        let spectrogram =
+            Spectrogram::from_frames_with_frequencies(Vec::new(), fft.frequencies().to_vec());
+        Self {
+            fft,
+            history_samples,
+            samples: VecDeque::with_capacity(history_samples),
+            spectrogram: Arc::new(spectrogram),
+        }
+    }
+
+    /// The frames of the retained input. Drop the returned handle before the
+    /// next `push`, or that push copies every frame instead of appending.
+    pub fn spectrogram(&self) -> Arc<Spectrogram> {
+        self.spectrogram.clone()
+    }
+
+    /// Stream index of the oldest retained sample.
+    pub fn first_sample(&self) -> usize {
+        self.fft.samples_seen() - self.samples.len()
+    }
+
+    /// The FFT params to draw the spectrogram with: Start/Stop span the
+    /// retained input, in stream time.
+    pub fn render_params(&self) -> FftParams {
+        let mut params = self.fft.params().clone();
+        params.start_sample = self.first_sample();
+        params.stop_sample = self.fft.samples_seen();
+        params
+    }
+
+    /// Add captured samples. Returns how many frames were added.
+    pub fn push(&mut self, samples: &[f32]) -> usize {
+        let new_frames = self.fft.push(samples);
+        let added = new_frames.len();
+
+        self.samples.extend(samples);
+        let excess = self.samples.len().saturating_sub(self.history_samples);
+        self.samples.drain(..excess);
+
+        let oldest = self.first_sample() as f64 / self.fft.params().sample_rate.max(1) as f64;
+        let spec = Arc::make_mut(&mut self.spectrogram);
+        spec.frames.extend(new_frames);
+        let expired = spec.frames.partition_point(|f| f.time_seconds < oldest);
+        spec.frames.drain(..expired);
+        if let (Some(first), Some(last)) = (spec.frames.first(), spec.frames.last()) {
+            spec.min_time = first.time_seconds;
+            spec.max_time = last.time_seconds;
+            spec.max_freq = spec.frequencies.last().copied().unwrap_or(0.0);
+        }
+        added
+    }
+
+    /// The retained input as audio starting at 0 s, to analyze like a file.
+    pub fn snapshot_audio(&self) -> AudioData {
+        AudioData::from_samples(
+            self.samples.iter().copied().collect(),
+            self.fft.params().sample_rate,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> FftParams {
+        FftParams {
+            window_length: 256,
+            overlap_percent: 50.0,
+            sample_rate: 8000,
+            ..FftParams::default()
+        }
+    }
+
+    #[test]
+    fn keeps_only_the_history() {
+        // Half a second of history at 8 kHz
+        let mut live = LiveAnalysis::new(params(), 0.5);
+        let block = vec![0.25f32; 800];
+        let mut added = 0;
+        for _ in 0..20 {
+            added += live.push(&block);
+        }
+        // 2 s pushed: frames every 128 samples once the first window is full
+        assert_eq!(added, (16_000 - 256) / 128 + 1);
+        assert_eq!(live.first_sample(), 12_000);
+
+        let spec = live.spectrogram();
+        assert!(spec.min_time >= 1.5);
+        assert!((spec.max_time - (16_000 - 256) as f64 / 8000.0).abs() < 1e-9);
+        assert_eq!(spec.num_bins(), 129);
+
+        let params = live.render_params();
+        assert_eq!((params.start_sample, params.stop_sample), (12_000, 16_000));
+    }
+
+    #[test]
+    fn snapshot_is_the_retained_input() {
+        let mut live = LiveAnalysis::new(params(), 0.5);
+        let ramp: Vec<f32> = (0..6000).map(|i| i as f32).collect();
+        live.push(&ramp);
+
+        let audio = live.snapshot_audio();
+        assert_eq!(audio.sample_rate, 8000);
+        assert_eq!(audio.num_samples(), 4000);
+        assert_eq!(audio.samples[0], 2000.0);
+        assert_eq!(audio.samples[3999], 5999.0);
+    }
+}
@@ -1,2 +1,10 @@
 pub mod fft_engine;
+pub mod instrument_export;
+pub mod live;
+pub mod noise_reduction;
+pub mod partials;
+pub mod recon_compare;
 pub mod reconstructor;
+pub mod spectral_edit;
+pub mod tempo;
+pub mod transcription;
@@ -0,0 +1,240 @@
+use rayon::prelude::*;
+
+use crate::data::{FftParams, Spectrogram};
+
+/// Upper end of the Noise Reduction slider (dB).
+pub const MAX_NOISE_REDUCTION_DB: f32 = 40.0;
+
+/// Spectral subtraction removes this many times the measured noise level,
+/// so bins that only just poke above the average noise are gated too.
+const OVERSUBTRACTION: f32 = 1.5;
+
+/// The average spectrum of a noise-only stretch of a recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseProfile {
+    pub frequencies: Vec<f32>,
+    /// Mean magnitude per bin over the captured frames
+    pub magnitudes: Vec<f32>,
+    pub start_seconds: f64,
+    pub stop_seconds: f64,
+    /// Noise level factor of the analysis it was captured with
+    level_scale: f32,
+}
+
+/// How large broadband noise comes out in a bin for these params.
+///
+/// Magnitudes are normalized by the FFT size, but noise adds up with the
+/// root of the window energy, so a longer window or more zero padding shows
+/// the same noise at a different height.
+fn noise_level_scale(params: &FftParams) -> f32 {
+    let energy: f32 = params.generate_window().iter().map(|w| w * w).sum();
+    energy.sqrt() / params.n_fft_padded().max(1) as f32
+}
+
+impl NoiseProfile {
+    /// Average the frames of `spectrogram` (analyzed with `params`) between
+    /// `start_seconds` and `stop_seconds`. None if no frame falls inside.
+    pub fn capture(
+        spectrogram: &Spectrogram,
+        params: &FftParams,
+        start_seconds: f64,
+        stop_seconds: f64,
+    ) -> Option<Self> {
+        let frames: Vec<_> = spectrogram
+            .frames
+            .iter()
+            .filter(|f| f.time_seconds >= start_seconds && f.time_seconds <= stop_seconds)
+            .collect();
+        if frames.is_empty() {
+            return None;
+        }
+
+        let mut magnitudes = vec![0.0f32; spectrogram.frequencies.len()];
+        for frame in &frames {
+            for (sum, &mag) in magnitudes.iter_mut().zip(&frame.magnitudes) {
+                *sum += mag;
+            }
+        }
+        let count = frames.len() as f32;
+        for mag in &mut magnitudes {
+            *mag /= count;
+        }
+
+        Some(Self {
+            frequencies: spectrogram.frequencies.clone(),
+            magnitudes,
+            start_seconds,
+            stop_seconds,
+            level_scale: noise_level_scale(params),
+        })
+    }
+
+    /// The noise level at each of `frequencies` in an analysis made with
+    /// `params`: interpolated across bins and rescaled for the window.
+    pub fn levels_for(&self, frequencies: &[f32], params: &FftParams) -> Vec<f32> {
+        let scale = if self.level_scale > 0.0 {
+            noise_level_scale(params) / self.level_scale
+        } else {
+            1.0
+        };
+        let (Some(&first), Some(&last)) = (self.magnitudes.first(), self.magnitudes.last()) else {
+            return vec![0.0; frequencies.len()];
+        };
+
+        frequencies
+            .iter()
+            .map(|&freq| {
+                let upper = self.frequencies.partition_point(|&f| f < freq);
+                let level = if upper == 0 {
+                    first
+                } else if upper >= self.frequencies.len() {
+                    last
+                } else {
+                    let (f0, f1) = (self.frequencies[upper - 1], self.frequencies[upper]);
+                    let t = if f1 > f0 {
+                        (freq - f0) / (f1 - f0)
+                    } else {
+                        0.0
+                    };
+                    self.magnitudes[upper - 1]
+                        + (self.magnitudes[upper] - self.magnitudes[upper - 1]) * t
+                };
+                level * scale
+            })
+            .collect()
+    }
+}
+
+/// Spectral subtraction with a gate: each bin is scaled by
+/// `1 - 1.5 * noise / magnitude`, but never by less than the floor
+/// `reduction_db` sets. Bins well above the noise pass almost untouched and
+/// noise-only bins drop by `reduction_db`. Gains are averaged over three
+/// frames to keep the leftover noise from twinkling. Phases are kept.
+pub fn reduce_noise(spectrogram: &mut Spectrogram, noise: &[f32], reduction_db: f32) {
+    let floor = 10f32.powf(-reduction_db.clamp(0.0, MAX_NOISE_REDUCTION_DB) / 20.0);
+    if floor >= 1.0 || spectrogram.frames.is_empty() {
+        return;
+    }
+
+    let gains: Vec<Vec<f32>> = spectrogram
+        .frames
+        .par_iter()
+        .map(|frame| {
+            frame
+                .magnitudes
+                .iter()
+                .enumerate()
+                .map(|(bin, &mag)| {
+                    let level = noise.get(bin).copied().unwrap_or(0.0);
+                    if mag > 0.0 {
+                        (1.0 - OVERSUBTRACTION * level / mag).clamp(floor, 1.0)
+                    } else {
+                        floor
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let last = gains.len() - 1;
+    spectrogram
+        .frames
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(i, frame)| {
+            let (prev, here, next) = (
+                &gains[i.saturating_sub(1)],
+                &gains[i],
+                &gains[(i + 1).min(last)],
+            );
+            for (bin, mag) in frame.magnitudes.iter_mut().enumerate() {
+                *mag *= (prev[bin] + here[bin] + next[bin]) / 3.0;
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::FftFrame;
+
+    /// 20 frames 10 ms apart, 64 bins 10 Hz apart, noise at 0.01 everywhere
+    /// and a 1.0 tone in bin 20 from frame 10 on.
+    fn noisy_spectrogram() -> Spectrogram {
+        let frames = (0..20)
+            .map(|i| {
+                let mut magnitudes = vec![0.01; 64];
+                if i >= 10 {
+                    magnitudes[20] = 1.0;
+                }
+                FftFrame {
+                    time_seconds: i as f64 * 0.01,
+                    magnitudes,
+                    phases: vec![0.25; 64],
+                }
+            })
+            .collect();
+        let frequencies = (0..64).map(|bin| bin as f32 * 10.0).collect();
+        Spectrogram::from_frames_with_frequencies(frames, frequencies)
+    }
+
+    #[test]
+    fn capture_averages_the_selected_frames() {
+        let spec = noisy_spectrogram();
+        let params = FftParams::default();
+        let profile = NoiseProfile::capture(&spec, &params, 0.0, 0.095).unwrap();
+        assert!(profile.magnitudes.iter().all(|&m| (m - 0.01).abs() < 1e-6));
+
+        // Half the frames carry the tone: its bin averages to the midpoint
+        let whole = NoiseProfile::capture(&spec, &params, 0.0, 1.0).unwrap();
+        assert!((whole.magnitudes[20] - 0.505).abs() < 1e-5);
+
+        assert!(NoiseProfile::capture(&spec, &params, 5.0, 6.0).is_none());
+    }
+
+    #[test]
+    fn levels_follow_bins_and_window() {
+        let spec = noisy_spectrogram();
+        let params = FftParams::default();
+        let profile = NoiseProfile::capture(&spec, &params, 0.0, 1.0).unwrap();
+
+        // Same analysis: the profile comes back as captured
+        let same = profile.levels_for(&spec.frequencies, &params);
+        assert!((same[20] - 0.505).abs() < 1e-5);
+        // Between bins it interpolates, past the ends it holds
+        let between = profile.levels_for(&[195.0, 5000.0], &params);
+        assert!((between[0] - (0.01 + 0.495 * 0.5)).abs() < 1e-5);
+        assert!((between[1] - 0.01).abs() < 1e-6);
+
+        // Four times the zero padding: same noise energy spread over 4x the
+        // normalization, so the level drops to a quarter
+        let padded = FftParams {
+            zero_pad_factor: 4,
+            ..FftParams::default()
+        };
+        let quarter = profile.levels_for(&[0.0], &padded);
+        assert!((quarter[0] - 0.0025).abs() < 1e-6);
+    }
+
+    #[test]
+    fn reduction_gates_noise_and_keeps_the_tone() {
+        let mut spec = noisy_spectrogram();
+        let params = FftParams::default();
+        let profile = NoiseProfile::capture(&spec, &params, 0.0, 0.095).unwrap();
+        let noise = profile.levels_for(&spec.frequencies, &params);
+        reduce_noise(&mut spec, &noise, 20.0);
+
+        // Noise-only bins fall by the full 20 dB
+        assert!((spec.frames[3].magnitudes[5] - 0.001).abs() < 1e-6);
+        assert!((spec.frames[15].magnitudes[40] - 0.001).abs() < 1e-6);
+        // The tone loses only its share of the noise
+        let tone = spec.frames[15].magnitudes[20];
+        assert!(tone > 0.98 && tone < 1.0);
+        assert!(spec.frames[15].phases.iter().all(|&p| p == 0.25));
+
+        // 0 dB leaves everything alone
+        let mut untouched = noisy_spectrogram();
+        reduce_noise(&mut untouched, &noise, 0.0);
+        assert_eq!(untouched.frames[3].magnitudes[5], 0.01);
+    }
+}
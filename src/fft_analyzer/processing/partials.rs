@@ -0,0 +1,322 @@
+use rayon::prelude::*;
+
+use crate::data::Spectrogram;
+
+/// Settings for picking spectral peaks and linking them into partials.
+#[derive(Debug, Clone)]
+pub struct PartialParams {
+    /// Peaks quieter than this many dB below the spectrogram's loudest bin
+    /// are ignored
+    pub floor_db: f32,
+    /// Only the loudest peaks of each frame are tracked
+    pub max_peaks_per_frame: usize,
+    /// Largest frequency jump (in cents) between two frames of one partial.
+    /// A jump of up to one bin is always allowed, so low partials with a
+    /// short window still link.
+    pub max_jump_cents: f32,
+    /// Frames a partial may go missing for before it dies
+    pub max_gap_frames: usize,
+    /// Partials shorter than this many points are dropped as noise
+    pub min_points: usize,
+}
+
+impl Default for PartialParams {
+    fn default() -> Self {
+        Self {
+            floor_db: -60.0,
+            max_peaks_per_frame: 40,
+            max_jump_cents: 60.0,
+            max_gap_frames: 2,
+            min_points: 4,
+        }
+    }
+}
+
+/// A local maximum of one frame's magnitude spectrum.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectralPeak {
+    pub bin: usize,
+    /// Frequency and magnitude refined by parabolic interpolation (in dB)
+    /// across the peak bin and its two neighbours
+    pub freq_hz: f32,
+    pub magnitude: f32,
+}
+
+/// One point on a partial's trajectory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PartialPoint {
+    pub frame: usize,
+    pub time_seconds: f64,
+    pub freq_hz: f32,
+    pub magnitude: f32,
+}
+
+/// A sinusoidal partial: a peak followed from its birth frame to its death
+/// frame. Points are in frame order; frames inside a tolerated gap have no
+/// point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialTrack {
+    pub points: Vec<PartialPoint>,
+}
+
+impl PartialTrack {
+    pub fn birth_seconds(&self) -> f64 {
+        self.points.first().map_or(0.0, |p| p.time_seconds)
+    }
+
+    pub fn death_seconds(&self) -> f64 {
+        self.points.last().map_or(0.0, |p| p.time_seconds)
+    }
+
+    /// Magnitude-weighted mean frequency
+    pub fn mean_freq_hz(&self) -> f32 {
+        let total: f32 = self.points.iter().map(|p| p.magnitude).sum();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        self.points
+            .iter()
+            .map(|p| p.freq_hz * p.magnitude)
+            .sum::<f32>()
+            / total
+    }
+
+    pub fn peak_magnitude(&self) -> f32 {
+        self.points.iter().map(|p| p.magnitude).fold(0.0, f32::max)
+    }
+}
+
+/// Pick the local maxima of one frame above `floor_magnitude`, loudest
+/// first, at most `max_peaks` of them.
+pub fn pick_peaks(
+    magnitudes: &[f32],
+    frequencies: &[f32],
+    floor_magnitude: f32,
+    max_peaks: usize,
+) -> Vec<SpectralPeak> {
+    let n = magnitudes.len().min(frequencies.len());
+    let mut peaks = Vec::new();
+    for bin in 1..n.saturating_sub(1) {
+        let mag = magnitudes[bin];
+        if mag <= floor_magnitude || mag <= magnitudes[bin - 1] || mag < magnitudes[bin + 1] {
+            continue;
+        }
+
+        let below = Spectrogram::magnitude_to_db(magnitudes[bin - 1]);
+        let at = Spectrogram::magnitude_to_db(mag);
+        let above = Spectrogram::magnitude_to_db(magnitudes[bin + 1]);
+        let curvature = below - 2.0 * at + above;
+        let offset = if curvature < 0.0 {
+            (0.5 * (below - above) / curvature).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        let bin_width = frequencies[bin + 1] - frequencies[bin];
+        let peak_db = at - 0.25 * (below - above) * offset;
+
+        peaks.push(SpectralPeak {
+            bin,
+            freq_hz: frequencies[bin] + offset * bin_width,
+            magnitude: 10f32.powf(peak_db / 20.0),
+        });
+    }
+
+    peaks.sort_by(|a, b| b.magnitude.total_cmp(&a.magnitude));
+    peaks.truncate(max_peaks);
+    peaks
+}
+
+/// Pick peaks in every frame and link them into partial tracks.
+///
+/// Linking is greedy, closest frequency first: each live partial takes the
+/// nearest unclaimed peak of the next frame within `max_jump_cents`, peaks
+/// nobody claims start new partials, and partials that find no peak for
+/// more than `max_gap_frames` frames die. Tracks come back sorted by birth.
+pub fn track_partials(spectrogram: &Spectrogram, params: &PartialParams) -> Vec<PartialTrack> {
+    let max_mag = spectrogram.max_magnitude();
+    if max_mag <= 0.0 || spectrogram.num_bins() < 3 {
+        return Vec::new();
+    }
+    let floor = max_mag * 10f32.powf(params.floor_db.min(0.0) / 20.0);
+    let bin_width = spectrogram.frequencies[1] - spectrogram.frequencies[0];
+
+    let frame_peaks: Vec<Vec<SpectralPeak>> = spectrogram
+        .frames
+        .par_iter()
+        .map(|frame| {
+            pick_peaks(
+                &frame.magnitudes,
+                &spectrogram.frequencies,
+                floor,
+                params.max_peaks_per_frame,
+            )
+        })
+        .collect();
+
+    let mut finished: Vec<PartialTrack> = Vec::new();
+    let mut live: Vec<PartialTrack> = Vec::new();
+
+    for (frame_idx, peaks) in frame_peaks.iter().enumerate() {
+        // Every (live track, peak) pair close enough to link, nearest first
+        let mut candidates: Vec<(f32, usize, usize)> = Vec::new();
+        for (track_idx, track) in live.iter().enumerate() {
+            let last_freq = track.points.last().map_or(0.0, |p| p.freq_hz);
+            for (peak_idx, peak) in peaks.iter().enumerate() {
+                let jump_hz = (peak.freq_hz - last_freq).abs();
+                let jump_cents = if last_freq > 0.0 && peak.freq_hz > 0.0 {
+                    1200.0 * (peak.freq_hz / last_freq).log2().abs()
+                } else {
+                    f32::INFINITY
+                };
+                if jump_cents <= params.max_jump_cents || jump_hz <= bin_width {
+                    candidates.push((jump_hz, track_idx, peak_idx));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut track_taken = vec![false; live.len()];
+        let mut peak_taken = vec![false; peaks.len()];
+        for (_, track_idx, peak_idx) in candidates {
+            if track_taken[track_idx] || peak_taken[peak_idx] {
+                continue;
+            }
+            track_taken[track_idx] = true;
+            peak_taken[peak_idx] = true;
+            live[track_idx]
+                .points
+                .push(point(spectrogram, frame_idx, &peaks[peak_idx]));
+        }
+
+        // Retire tracks that have been missing for too long
+        let mut still_live = Vec::with_capacity(live.len());
+        for track in live.drain(..) {
+            let last_frame = track.points.last().map_or(0, |p| p.frame);
+            if frame_idx - last_frame > params.max_gap_frames {
+                finished.push(track);
+            } else {
+                still_live.push(track);
+            }
+        }
+        live = still_live;
+
+        for (peak, _) in peaks.iter().zip(&peak_taken).filter(|(_, taken)| !**taken) {
+            live.push(PartialTrack {
+                points: vec![point(spectrogram, frame_idx, peak)],
+            });
+        }
+    }
+    finished.append(&mut live);
+
+    finished.retain(|track| track.points.len() >= params.min_points.max(1));
+    finished.sort_by(|a, b| {
+        a.points[0]
+            .frame
+            .cmp(&b.points[0].frame)
+            .then(a.points[0].freq_hz.total_cmp(&b.points[0].freq_hz))
+    });
+    finished
+}
+
+fn point(spectrogram: &Spectrogram, frame: usize, peak: &SpectralPeak) -> PartialPoint {
+    PartialPoint {
+        frame,
+        time_seconds: spectrogram.frames[frame].time_seconds,
+        freq_hz: peak.freq_hz,
+        magnitude: peak.magnitude,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::FftFrame;
+
+    const BIN_HZ: f32 = 10.0;
+
+    /// A frame holding Hann-shaped bumps at the given (frequency, magnitude)
+    /// pairs over a faint noise floor.
+    fn frame_with_tones(time_seconds: f64, tones: &[(f32, f32)]) -> FftFrame {
+        let mut magnitudes = vec![1e-4; 256];
+        for &(freq, mag) in tones {
+            for (bin, value) in magnitudes.iter_mut().enumerate() {
+                let distance = (bin as f32 * BIN_HZ - freq).abs() / BIN_HZ;
+                if distance < 2.0 {
+                    let shape = 0.5 + 0.5 * (std::f32::consts::PI * distance / 2.0).cos();
+                    *value += mag * shape;
+                }
+            }
+        }
+        FftFrame {
+            time_seconds,
+            phases: vec![0.0; magnitudes.len()],
+            magnitudes,
+        }
+    }
+
+    fn spectrogram(frames: Vec<FftFrame>) -> Spectrogram {
+        let frequencies = (0..256).map(|bin| bin as f32 * BIN_HZ).collect();
+        Spectrogram::from_frames_with_frequencies(frames, frequencies)
+    }
+
+    #[test]
+    fn pick_peaks_interpolates_between_bins() {
+        let frame = frame_with_tones(0.0, &[(503.0, 1.0), (1200.0, 0.5)]);
+        let freqs: Vec<f32> = (0..256).map(|bin| bin as f32 * BIN_HZ).collect();
+        let peaks = pick_peaks(&frame.magnitudes, &freqs, 0.01, 10);
+        assert_eq!(peaks.len(), 2);
+        assert_eq!(peaks[0].bin, 50);
+        assert!((peaks[0].freq_hz - 503.0).abs() < 1.5, "{:?}", peaks[0]);
+        assert!((peaks[1].freq_hz - 1200.0).abs() < 0.01);
+        assert!(peaks[0].magnitude > peaks[1].magnitude);
+
+        assert_eq!(pick_peaks(&frame.magnitudes, &freqs, 0.01, 1).len(), 1);
+        assert!(pick_peaks(&frame.magnitudes, &freqs, 2.0, 10).is_empty());
+    }
+
+    #[test]
+    fn track_partials_follows_glides_births_and_deaths() {
+        // A tone gliding 400 -> 500 Hz over 40 frames, a second tone at
+        // 1500 Hz living from frame 10 to frame 29, and a one-frame blip
+        let frames = (0..40)
+            .map(|i| {
+                let mut tones = vec![(400.0 + 2.5 * i as f32, 1.0)];
+                if (10..30).contains(&i) {
+                    tones.push((1500.0, 0.3));
+                }
+                if i == 20 {
+                    tones.push((2200.0, 0.3));
+                }
+                frame_with_tones(i as f64 * 0.01, &tones)
+            })
+            .collect();
+        let tracks = track_partials(&spectrogram(frames), &PartialParams::default());
+
+        assert_eq!(tracks.len(), 2, "{:#?}", tracks);
+        let glide = &tracks[0];
+        assert_eq!(glide.points.len(), 40);
+        assert!((glide.points[0].freq_hz - 400.0).abs() < 1.5);
+        assert!((glide.points[39].freq_hz - 497.5).abs() < 1.5);
+        assert!((glide.mean_freq_hz() - 448.75).abs() < 2.0);
+
+        let upper = &tracks[1];
+        assert_eq!(upper.points.first().unwrap().frame, 10);
+        assert_eq!(upper.points.last().unwrap().frame, 29);
+        assert!((upper.birth_seconds() - 0.10).abs() < 1e-9);
+        assert!((upper.death_seconds() - 0.29).abs() < 1e-9);
+        assert!((upper.peak_magnitude() - 0.3).abs() < 0.05);
+    }
+
+    #[test]
+    fn track_partials_bridges_short_gaps() {
+        let frames = (0..12)
+            .map(|i| {
+                let tones: &[(f32, f32)] = if i == 5 { &[] } else { &[(800.0, 1.0)] };
+                frame_with_tones(i as f64 * 0.01, tones)
+            })
+            .collect();
+        let tracks = track_partials(&spectrogram(frames), &PartialParams::default());
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].points.len(), 11);
+    }
+}
@@ -0,0 +1,247 @@
+use rayon::prelude::*;
+
+use crate::data::{FftFrame, Spectrogram};
+
+/// The Difference view's colors saturate at this many dB either way.
+pub const DIFFERENCE_RANGE_DB: f32 = 24.0;
+
+/// Levels below this count as silence, so two near-silent bins compare as
+/// equal instead of as a huge ratio.
+const FLOOR_DB: f32 = -120.0;
+
+/// How far the reconstruction is from what it was made from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconStats {
+    /// Scale-invariant SNR of the reconstructed audio against the original
+    /// audio under it (None without source audio)
+    pub snr_db: Option<f32>,
+    /// Spectral convergence ||S - gR|| / ||S|| in dB over the ROI band:
+    /// 0 dB is as large as the signal itself, lower is better
+    pub spectral_convergence_db: f32,
+}
+
+/// The Difference view layer plus the numbers for the INFO panel.
+#[derive(Debug, Clone)]
+pub struct ReconComparison {
+    /// Same frames and bins as the original; each "magnitude" holds the
+    /// reconstruction's level minus the original's, in dB (negative = lost)
+    pub difference: Spectrogram,
+    pub stats: ReconStats,
+}
+
+fn level_db(magnitude: f32) -> f32 {
+    (20.0 * magnitude.max(1e-10).log10()).max(FLOOR_DB)
+}
+
+/// Compare `original` (the spectrogram that was reconstructed) with
+/// `reconstruction` (the reconstructed audio analyzed with the same params;
+/// its frame times are shifted by `time_offset` seconds to line up).
+///
+/// Frames are paired by nearest time, at most half a hop apart. The
+/// reconstruction is scaled by the least-squares gain first, because the
+/// reconstructed audio is normalized on its own. Statistics only cover
+/// bins between `freq_min_hz` and `freq_max_hz`. None if no frames pair up.
+pub fn compare(
+    original: &Spectrogram,
+    reconstruction: &Spectrogram,
+    time_offset: f64,
+    hop_seconds: f64,
+    freq_min_hz: f32,
+    freq_max_hz: f32,
+) -> Option<ReconComparison> {
+    let recon_times: Vec<f64> = reconstruction
+        .frames
+        .iter()
+        .map(|f| f.time_seconds + time_offset)
+        .collect();
+    let pairs: Vec<(&FftFrame, &FftFrame)> = original
+        .frames
+        .iter()
+        .filter_map(|frame| {
+            let upper = recon_times.partition_point(|&t| t < frame.time_seconds);
+            let nearest = [upper.checked_sub(1), Some(upper)]
+                .into_iter()
+                .flatten()
+                .filter(|&i| i < recon_times.len())
+                .min_by(|&a, &b| {
+                    let da = (recon_times[a] - frame.time_seconds).abs();
+                    let db = (recon_times[b] - frame.time_seconds).abs();
+                    da.total_cmp(&db)
+                })?;
+            ((recon_times[nearest] - frame.time_seconds).abs() <= hop_seconds * 0.5 + 1e-9)
+                .then(|| (frame, &reconstruction.frames[nearest]))
+        })
+        .collect();
+    if pairs.is_empty() {
+        return None;
+    }
+
+    let bins = original.num_bins().min(reconstruction.num_bins());
+    let band_start = original.frequencies.partition_point(|&f| f < freq_min_hz);
+    let band_end = original
+        .frequencies
+        .partition_point(|&f| f <= freq_max_hz)
+        .min(bins)
+        .max(band_start);
+
+    // Least-squares gain that best matches the reconstruction to the original
+    let (cross, recon_energy) = pairs
+        .par_iter()
+        .map(|(orig, recon)| {
+            orig.magnitudes[band_start..band_end]
+                .iter()
+                .zip(&recon.magnitudes[band_start..band_end])
+                .fold((0.0f64, 0.0f64), |(c, e), (&o, &r)| {
+                    (c + o as f64 * r as f64, e + r as f64 * r as f64)
+                })
+        })
+        .reduce(|| (0.0, 0.0), |a, b| (a.0 + b.0, a.1 + b.1));
+    let gain = if recon_energy > 0.0 {
+        (cross / recon_energy) as f32
+    } else {
+        1.0
+    };
+
+    let (error_energy, original_energy) = pairs
+        .par_iter()
+        .map(|(orig, recon)| {
+            orig.magnitudes[band_start..band_end]
+                .iter()
+                .zip(&recon.magnitudes[band_start..band_end])
+                .fold((0.0f64, 0.0f64), |(err, sig), (&o, &r)| {
+                    let diff = (o - gain * r) as f64;
+                    (err + diff * diff, sig + o as f64 * o as f64)
+                })
+        })
+        .reduce(|| (0.0, 0.0), |a, b| (a.0 + b.0, a.1 + b.1));
+    let spectral_convergence_db = if original_energy > 0.0 {
+        10.0 * ((error_energy / original_energy).max(1e-20)).log10() as f32
+    } else {
+        0.0
+    };
+
+    let frames = pairs
+        .par_iter()
+        .map(|(orig, recon)| FftFrame {
+            time_seconds: orig.time_seconds,
+            magnitudes: (0..original.num_bins())
+                .map(|bin| {
+                    let r = recon.magnitudes.get(bin).copied().unwrap_or(0.0);
+                    level_db(gain * r) - level_db(orig.magnitudes[bin])
+                })
+                .collect(),
+            phases: Vec::new(),
+        })
+        .collect();
+
+    Some(ReconComparison {
+        difference: Spectrogram::from_frames_with_frequencies(frames, original.frequencies.clone()),
+        stats: ReconStats {
+            snr_db: None,
+            spectral_convergence_db,
+        },
+    })
+}
+
+/// Scale-invariant SNR of `estimate` against `reference` in dB: the part of
+/// the estimate that follows the reference, over everything else. None when
+/// the reference is silent.
+pub fn snr_db(reference: &[f32], estimate: &[f32]) -> Option<f32> {
+    let len = reference.len().min(estimate.len());
+    let (reference, estimate) = (&reference[..len], &estimate[..len]);
+    let reference_energy: f64 = reference.iter().map(|&x| x as f64 * x as f64).sum();
+    if reference_energy <= 0.0 {
+        return None;
+    }
+    let cross: f64 = reference
+        .iter()
+        .zip(estimate)
+        .map(|(&x, &y)| x as f64 * y as f64)
+        .sum();
+    let scale = cross / reference_energy;
+    let (mut signal, mut noise) = (0.0f64, 0.0f64);
+    for (&x, &y) in reference.iter().zip(estimate) {
+        let target = scale * x as f64;
+        let residual = y as f64 - target;
+        signal += target * target;
+        noise += residual * residual;
+    }
+    Some((10.0 * (signal.max(1e-20) / noise.max(1e-20)).log10()) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames every 10 ms starting at `start`, 8 bins 100 Hz apart, each
+    /// magnitude from `level(frame, bin)`.
+    fn spectrogram(start: f64, frames: usize, level: impl Fn(usize, usize) -> f32) -> Spectrogram {
+        let frames = (0..frames)
+            .map(|i| FftFrame {
+                time_seconds: start + i as f64 * 0.01,
+                magnitudes: (0..8).map(|bin| level(i, bin)).collect(),
+                phases: vec![0.0; 8],
+            })
+            .collect();
+        Spectrogram::from_frames_with_frequencies(
+            frames,
+            (0..8).map(|b| b as f32 * 100.0).collect(),
+        )
+    }
+
+    #[test]
+    fn scaled_copy_matches_exactly() {
+        let original = spectrogram(1.0, 10, |i, bin| 0.1 + (i + bin) as f32 * 0.01);
+        // Half as loud and analyzed from 0 s: the gain and offset undo both
+        let recon = spectrogram(0.0, 10, |i, bin| (0.1 + (i + bin) as f32 * 0.01) * 0.5);
+        let cmp = compare(&original, &recon, 1.0, 0.01, 0.0, 1000.0).unwrap();
+
+        assert_eq!(cmp.difference.num_frames(), 10);
+        assert!(cmp.stats.spectral_convergence_db < -100.0);
+        assert!(
+            cmp.difference
+                .frames
+                .iter()
+                .all(|f| f.magnitudes.iter().all(|d| d.abs() < 1e-3))
+        );
+    }
+
+    #[test]
+    fn lost_bins_show_as_negative_difference() {
+        let original = spectrogram(0.0, 4, |_, _| 0.1);
+        // The reconstruction dropped bin 3 to a tenth (-20 dB)
+        let recon = spectrogram(0.0, 4, |_, bin| if bin == 3 { 0.01 } else { 0.1 });
+        let cmp = compare(&original, &recon, 0.0, 0.01, 0.0, 1000.0).unwrap();
+
+        let frame = &cmp.difference.frames[0].magnitudes;
+        assert!(frame[0].abs() < 0.2);
+        assert!(frame[3] < -19.0);
+        assert!(cmp.stats.spectral_convergence_db > -20.0);
+        assert!(cmp.stats.spectral_convergence_db < 0.0);
+
+        // Only the band counts: without bin 3 the match is exact
+        let band = compare(&original, &recon, 0.0, 0.01, 0.0, 250.0).unwrap();
+        assert!(band.stats.spectral_convergence_db < -100.0);
+
+        // Frames more than half a hop apart don't pair
+        assert!(compare(&original, &recon, 5.0, 0.01, 0.0, 1000.0).is_none());
+    }
+
+    #[test]
+    fn snr_ignores_scale_and_measures_noise() {
+        let reference: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.05).sin()).collect();
+        let louder: Vec<f32> = reference.iter().map(|x| x * 3.0).collect();
+        assert!(snr_db(&reference, &louder).unwrap() > 100.0);
+
+        // Noise at a tenth of the amplitude of a unit sine: about 17 dB
+        let noisy: Vec<f32> = reference
+            .iter()
+            .enumerate()
+            .map(|(i, x)| x + if i % 2 == 0 { 0.1 } else { -0.1 })
+            .collect();
+        let snr = snr_db(&reference, &noisy).unwrap();
+        assert!((snr - 17.0).abs() < 0.5, "snr = {snr}");
+
+        assert!(snr_db(&[0.0; 10], &louder).is_none());
+    }
+}
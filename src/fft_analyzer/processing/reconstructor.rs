@@ -1,7 +1,6 @@
 use std::cell::RefCell;
 use std::ops::Range;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
 
 use rayon::prelude::*;
 use realfft::RealFftPlanner;
@@ -16,6 +15,11 @@ thread_local! {
     static IFFT_PLANNER: RefCell<RealFftPlanner<f32>> = RefCell::new(RealFftPlanner::new());
 }
 
+/// Most Griffin-Lim iterations the Analysis menu accepts.
+pub const MAX_GRIFFIN_LIM_ITERATIONS: usize = 500;
+/// Iterations switched on when an FFT CSV without phases is loaded.
+pub const DEFAULT_GRIFFIN_LIM_ITERATIONS: usize = 32;
+
 /// Reconstructs audio from spectrogram data with configurable frequency filtering.
 pub struct Reconstructor;
 
@@ -83,6 +87,194 @@ impl Reconstructor {
         }
     }
 
+    /// Progress steps `reconstruct_range` reports for `num_frames` frames: one
+    /// per frame for the final pass plus one per frame per Griffin-Lim
+    /// iteration. Callers use it as the status bar's progress total.
+    pub fn progress_steps(num_frames: usize, view: &ViewState) -> usize {
+        num_frames * (view.recon_griffin_lim_iterations + 1)
+    }
+
+    /// Undo the forward-pass scaling to recover raw spectrum values.
+    /// Forward pass stored: mag = (|X[k]| / N) * amplitude_scale
+    ///   DC/Nyquist (amplitude_scale=1): mag = |X[k]| / N  -> recover: mag * N
+    ///   Other bins (amplitude_scale=2):  mag = |X[k]| * 2 / N -> recover: mag * N / 2
+    fn raw_magnitude(mag: f32, bin: usize, num_bins: usize, n_fft: usize) -> f32 {
+        if bin == 0 || bin == num_bins - 1 {
+            mag * n_fft as f32 // undo /N only
+        } else {
+            mag * n_fft as f32 / 2.0 // undo /N and *2
+        }
+    }
+
+    /// IFFT one frame from raw magnitudes and phases, then apply the
+    /// synthesis window to the first `window.len()` samples (the zero-padding
+    /// extension of the IFFT output is discarded).
+    fn synthesize_frame(
+        raw_mags: &[f32],
+        phases: &[f32],
+        n_fft: usize,
+        window: &[f32],
+    ) -> Vec<f32> {
+        let ifft = IFFT_PLANNER.with(|p| p.borrow_mut().plan_fft_inverse(n_fft));
+
+        let mut spectrum = ifft.make_input_vec();
+        let mut time_buffer = ifft.make_output_vec();
+        let last = spectrum.len() - 1;
+        for (i, s) in spectrum.iter_mut().enumerate() {
+            let raw_mag = raw_mags.get(i).copied().unwrap_or(0.0);
+            let phase = phases.get(i).copied().unwrap_or(0.0);
+            *s = if raw_mag == 0.0 {
+                Complex::new(0.0, 0.0)
+            } else if i == 0 || i == last {
+                // DC and Nyquist bins are real-valued
+                Complex::new(raw_mag * phase.cos(), 0.0)
+            } else {
+                Complex::from_polar(raw_mag, phase)
+            };
+        }
+
+        ifft.process(&mut spectrum, &mut time_buffer)
+            .expect("IFFT processing failed");
+
+        // realfft's inverse produces N * x[n], so divide by N
+        let norm = 1.0 / n_fft as f32;
+        time_buffer
+            .iter()
+            .take(window.len())
+            .zip(window.iter())
+            .map(|(&s, &w)| s * norm * w)
+            .collect()
+    }
+
+    /// Estimate phases for `frame_indices` with Griffin-Lim. Starting from the
+    /// stored phases (all zero when a CSV had none), each iteration
+    /// overlap-adds the frames into one signal, re-analyzes it with the same
+    /// window and hop, and keeps the new phases with the original magnitudes.
+    /// The frames grow more consistent with a single signal each time, which
+    /// removes the phasey smear of missing or magnitude-edited phase.
+    /// Returns one phase per bin for each frame.
+    #[allow(clippy::too_many_arguments)]
+    fn griffin_lim_phases(
+        spectrogram: &Spectrogram,
+        params: &FftParams,
+        view: &ViewState,
+        frame_indices: &[usize],
+        window: &[f32],
+        cancel: &AtomicBool,
+        progress: Option<&AtomicUsize>,
+    ) -> Vec<Vec<f32>> {
+        let hop = params.hop_length();
+        let window_len = params.window_length;
+        let n_fft = params.n_fft_padded();
+        let num_bins = n_fft / 2 + 1;
+
+        // Target magnitudes: the bins the reconstruction keeps, unscaled
+        let targets: Vec<Vec<f32>> = frame_indices
+            .par_iter()
+            .map(|&idx| {
+                let frame = &spectrogram.frames[idx];
+                let active = compute_active_bins(
+                    &frame.magnitudes,
+                    &spectrogram.frequencies,
+                    view.recon_freq_min_hz,
+                    view.recon_freq_max_hz,
+                    view.recon_freq_count,
+                );
+                (0..num_bins)
+                    .map(|i| {
+                        if active.get(i).copied().unwrap_or(false) {
+                            Self::raw_magnitude(frame.magnitudes[i], i, num_bins, n_fft)
+                        } else {
+                            0.0
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut phases: Vec<Vec<f32>> = frame_indices
+            .iter()
+            .map(|&idx| {
+                let stored = &spectrogram.frames[idx].phases;
+                (0..num_bins)
+                    .map(|i| stored.get(i).copied().unwrap_or(0.0))
+                    .collect()
+            })
+            .collect();
+
+        let signal_len = (frame_indices.len() - 1) * hop + window_len;
+        let mut window_sum = vec![0.0f32; signal_len];
+        for local_idx in 0..frame_indices.len() {
+            for (i, &w) in window.iter().enumerate() {
+                window_sum[local_idx * hop + i] += w * w;
+            }
+        }
+        let threshold_f32 = view.recon_norm_floor as f32;
+
+        let mut iterations_done = 0;
+        for _ in 0..view.recon_griffin_lim_iterations {
+            if cancel.load(Ordering::Relaxed) {
+                break;
+            }
+
+            // Synthesis: the same per-frame IFFT + overlap-add as the final pass
+            let frames: Vec<Vec<f32>> = targets
+                .par_iter()
+                .zip(phases.par_iter())
+                .map(|(mags, frame_phases)| {
+                    Self::synthesize_frame(mags, frame_phases, n_fft, window)
+                })
+                .collect();
+            let mut signal = vec![0.0f32; signal_len];
+            for (local_idx, frame) in frames.iter().enumerate() {
+                for (i, &sample) in frame.iter().enumerate() {
+                    signal[local_idx * hop + i] += sample;
+                }
+            }
+            for (sample, &wsum) in signal.iter_mut().zip(window_sum.iter()) {
+                *sample = if wsum >= threshold_f32 {
+                    *sample / wsum
+                } else {
+                    0.0
+                };
+            }
+
+            // Analysis: keep each bin's phase from the consistent signal
+            phases
+                .par_iter_mut()
+                .enumerate()
+                .for_each(|(local_idx, frame_phases)| {
+                    let fft = IFFT_PLANNER.with(|p| p.borrow_mut().plan_fft_forward(n_fft));
+                    let mut input = fft.make_input_vec();
+                    let segment = &signal[local_idx * hop..local_idx * hop + window_len];
+                    for ((slot, &sample), &w) in input.iter_mut().zip(segment).zip(window) {
+                        *slot = sample * w;
+                    }
+                    let mut spectrum = fft.make_output_vec();
+                    fft.process(&mut input, &mut spectrum)
+                        .expect("FFT processing failed");
+                    for (phase, bin) in frame_phases.iter_mut().zip(spectrum.iter()) {
+                        *phase = bin.arg();
+                    }
+
+                    if let Some(ctr) = progress {
+                        ctr.fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            iterations_done += 1;
+        }
+
+        dbg_log!(
+            debug_flags::FFT_DBG,
+            "GriffinLim",
+            "Estimated phases for {} frames: {}/{} iterations",
+            frame_indices.len(),
+            iterations_done,
+            view.recon_griffin_lim_iterations
+        );
+
+        phases
+    }
+
     /// Reconstruct audio from all frames in a spectrogram.
     #[allow(dead_code)]
     pub fn reconstruct(
@@ -124,11 +316,7 @@ impl Reconstructor {
         let window = params.generate_window();
 
         if num_frames == 0 {
-            return AudioData {
-                samples: Arc::new(vec![]),
-                sample_rate: params.sample_rate,
-                duration_seconds: 0.0,
-            };
+            return AudioData::from_samples(vec![], params.sample_rate);
         }
 
         let centered_crop = if params.use_center {
@@ -190,9 +378,25 @@ impl Reconstructor {
             output_length
         );
 
+        let frame_indices: Vec<usize> = frame_range.collect();
+
+        // Griffin-Lim: replace the stored phases with estimated ones
+        let estimated_phases = if view.recon_griffin_lim_iterations > 0 {
+            Some(Self::griffin_lim_phases(
+                spectrogram,
+                params,
+                view,
+                &frame_indices,
+                &window,
+                cancel,
+                progress,
+            ))
+        } else {
+            None
+        };
+
         // Phase 1: Parallel IFFT for each frame in the range.
         // Cancelled frames return None and are filtered out.
-        let frame_indices: Vec<usize> = frame_range.collect();
         let frame_results: Vec<(usize, Vec<f32>, usize)> = frame_indices
             .par_iter()
             .enumerate()
@@ -203,10 +407,7 @@ impl Reconstructor {
                 }
 
                 let frame = &spectrogram.frames[global_idx];
-                let ifft = IFFT_PLANNER.with(|p| p.borrow_mut().plan_fft_inverse(n_fft));
-
-                let mut spectrum = ifft.make_input_vec();
-                let mut time_buffer = ifft.make_output_vec();
+                let num_bins = n_fft / 2 + 1;
 
                 // Determine active bins using shared logic (same as renderer).
                 let active = compute_active_bins(
@@ -218,53 +419,22 @@ impl Reconstructor {
                 );
                 let active_count = active.iter().filter(|&&b| b).count();
 
-                // Zero the spectrum, then fill in active bins
-                for s in spectrum.iter_mut() {
-                    *s = Complex::new(0.0, 0.0);
-                }
-
-                for (i, &is_active) in active.iter().enumerate() {
-                    if !is_active || i >= spectrum.len() {
-                        continue;
-                    }
-                    let mag = frame.magnitudes[i];
-                    let phase = frame.phases[i];
-
-                    // Undo the forward-pass scaling to recover raw spectrum values.
-                    // Forward pass stored: mag = (|X[k]| / N) * amplitude_scale
-                    //   DC/Nyquist (amplitude_scale=1): mag = |X[k]| / N  -> recover: mag * N
-                    //   Other bins (amplitude_scale=2):  mag = |X[k]| * 2 / N -> recover: mag * N / 2
-                    let raw_mag = if i == 0 || i == spectrum.len() - 1 {
-                        mag * n_fft as f32 // undo /N only
-                    } else {
-                        mag * n_fft as f32 / 2.0 // undo /N and *2
-                    };
-
-                    if i == 0 || i == spectrum.len() - 1 {
-                        // DC and Nyquist bins are real-valued
-                        spectrum[i] = Complex::new(raw_mag * phase.cos(), 0.0);
-                    } else {
-                        spectrum[i] = Complex::from_polar(raw_mag, phase);
-                    }
-                }
-
-                ifft.process(&mut spectrum, &mut time_buffer)
-                    .expect("IFFT processing failed");
-
-                // realfft's inverse produces N * x[n], so divide by N
-                let norm = 1.0 / n_fft as f32;
-                for s in time_buffer.iter_mut() {
-                    *s *= norm;
-                }
-
-                // Apply synthesis window to first window_len samples only
-                // (discard zero-padding extension from IFFT output)
-                let windowed: Vec<f32> = time_buffer
-                    .iter()
-                    .take(window_len)
-                    .zip(window.iter())
-                    .map(|(&s, &w)| s * w)
+                // Inactive bins stay zero
+                let raw_mags: Vec<f32> = (0..num_bins)
+                    .map(|i| {
+                        if active.get(i).copied().unwrap_or(false) {
+                            Self::raw_magnitude(frame.magnitudes[i], i, num_bins, n_fft)
+                        } else {
+                            0.0
+                        }
+                    })
                     .collect();
+                let phases = match &estimated_phases {
+                    Some(estimated) => &estimated[local_idx],
+                    None => &frame.phases,
+                };
+
+                let windowed = Self::synthesize_frame(&raw_mags, phases, n_fft, &window);
 
                 if let Some(ctr) = progress {
                     ctr.fetch_add(1, Ordering::Relaxed);
@@ -561,13 +731,7 @@ impl Reconstructor {
             }
         }
 
-        let duration_seconds = output.len() as f64 / params.sample_rate as f64;
-
-        AudioData {
-            samples: Arc::new(output),
-            sample_rate: params.sample_rate,
-            duration_seconds,
-        }
+        AudioData::from_samples(output, params.sample_rate)
     }
 }
 
@@ -592,11 +756,7 @@ mod tests {
                 (2.0 * PI * freq_hz * t).sin() * 0.8
             })
             .collect();
-        AudioData {
-            samples: Arc::new(samples),
-            sample_rate,
-            duration_seconds: duration_secs as f64,
-        }
+        AudioData::from_samples(samples, sample_rate)
     }
 
     /// Build FftParams for a given window/overlap/center configuration.
@@ -1258,4 +1418,61 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn griffin_lim_restores_magnitude_only_spectrogram() {
+        // Two partials with a level change, analyzed, then stripped of phase
+        // (as when an FFT CSV has no phase column)
+        let sample_rate = 8000;
+        let samples: Vec<f32> = (0..2000)
+            .map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                let level = if i < 1000 { 0.6 } else { 0.3 };
+                level * (2.0 * PI * 440.0 * t).sin() + 0.2 * (2.0 * PI * 1320.0 * t).sin()
+            })
+            .collect();
+        let audio = AudioData::from_samples(samples, sample_rate);
+        let params = make_params(sample_rate, 0, 2000, 256, 75.0, WindowType::Hann, false);
+        let mut view = full_spectrum_view(4000.0, params.num_frequency_bins());
+        let cancel = AtomicBool::new(false);
+
+        let mut spectrogram = FftEngine::process(&audio, &params, &cancel, None);
+        for frame in &mut spectrogram.frames {
+            frame.phases.iter_mut().for_each(|phase| *phase = 0.0);
+        }
+
+        // Spectral convergence: how far the resynthesized audio's magnitudes
+        // are from the target magnitudes (0 = consistent)
+        let spectral_error = |view: &ViewState| {
+            let recon = Reconstructor::reconstruct(&spectrogram, &params, view, &cancel, None);
+            let mut recon_params = params.clone();
+            recon_params.stop_sample = recon.num_samples();
+            let analyzed = FftEngine::process(&recon, &recon_params, &cancel, None);
+            assert_eq!(analyzed.num_frames(), spectrogram.num_frames());
+            let mut diff = 0.0f64;
+            let mut total = 0.0f64;
+            for (a, b) in analyzed.frames.iter().zip(&spectrogram.frames) {
+                for (&x, &y) in a.magnitudes.iter().zip(&b.magnitudes) {
+                    diff += ((x - y) as f64).powi(2);
+                    total += (y as f64).powi(2);
+                }
+            }
+            (diff / total).sqrt()
+        };
+
+        let zero_phase_error = spectral_error(&view);
+        view.recon_griffin_lim_iterations = 50;
+        let griffin_lim_error = spectral_error(&view);
+        eprintln!(
+            "Spectral convergence: zero phase={:.4} griffin-lim={:.4}",
+            zero_phase_error, griffin_lim_error
+        );
+        assert!(
+            griffin_lim_error < zero_phase_error * 0.5,
+            "Griffin-Lim did not improve consistency: {} vs {}",
+            griffin_lim_error,
+            zero_phase_error
+        );
+        assert_eq!(Reconstructor::progress_steps(10, &view), 510);
+    }
 }
@@ -0,0 +1,191 @@
+use rayon::prelude::*;
+
+use crate::data::Spectrogram;
+
+/// Brush gains at or below this many dB erase (multiply by zero).
+pub const ERASE_GAIN_DB: f32 = -100.0;
+
+/// Smallest / largest brush radius (pixels) Edit > Brush Size accepts.
+pub const MIN_BRUSH_RADIUS_PX: i32 = 2;
+pub const MAX_BRUSH_RADIUS_PX: i32 = 200;
+
+/// One brush print: an ellipse centered on (`time_seconds`, `freq_hz`).
+///
+/// The frequency reach below and above the center is stored separately
+/// because a round brush on a log frequency axis covers fewer Hz below
+/// its center than above it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BrushDab {
+    pub time_seconds: f64,
+    pub radius_seconds: f64,
+    pub freq_hz: f32,
+    /// Lowest / highest frequency covered at the center time
+    pub freq_low_hz: f32,
+    pub freq_high_hz: f32,
+}
+
+impl BrushDab {
+    /// The frequencies this dab covers at `time_seconds`, if any.
+    fn span_at(&self, time_seconds: f64) -> Option<(f32, f32)> {
+        let offset = (time_seconds - self.time_seconds).abs();
+        if offset > self.radius_seconds {
+            return None;
+        }
+        let reach = if self.radius_seconds > 0.0 {
+            (1.0 - (offset / self.radius_seconds).powi(2)).sqrt() as f32
+        } else {
+            1.0
+        };
+        Some((
+            self.freq_hz - (self.freq_hz - self.freq_low_hz) * reach,
+            self.freq_hz + (self.freq_high_hz - self.freq_hz) * reach,
+        ))
+    }
+}
+
+/// One press-drag-release of the brush: every bin under any of its dabs is
+/// scaled by the same gain, once, however many dabs overlap it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrushStroke {
+    pub gain_db: f32,
+    pub dabs: Vec<BrushDab>,
+}
+
+impl BrushStroke {
+    pub fn new(gain_db: f32) -> Self {
+        Self {
+            gain_db,
+            dabs: Vec::new(),
+        }
+    }
+
+    /// Linear magnitude factor (0 when erasing).
+    pub fn gain(&self) -> f32 {
+        if self.gain_db <= ERASE_GAIN_DB {
+            0.0
+        } else {
+            10f32.powf(self.gain_db.min(0.0) / 20.0)
+        }
+    }
+}
+
+/// Scale the magnitudes under `stroke` in place. Phases are kept, so the
+/// reconstruction of the rest of each frame is unchanged. Returns how many
+/// bins were touched.
+pub fn apply_stroke(spectrogram: &mut Spectrogram, stroke: &BrushStroke) -> usize {
+    let gain = stroke.gain();
+    let frequencies = &spectrogram.frequencies;
+
+    spectrogram
+        .frames
+        .par_iter_mut()
+        .map(|frame| {
+            let mut spans: Vec<(f32, f32)> = stroke
+                .dabs
+                .iter()
+                .filter_map(|dab| dab.span_at(frame.time_seconds))
+                .collect();
+            if spans.is_empty() {
+                return 0;
+            }
+            spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            // Merge overlapping spans so no bin is scaled twice
+            let mut merged: Vec<(f32, f32)> = Vec::with_capacity(spans.len());
+            for (low, high) in spans {
+                match merged.last_mut() {
+                    Some(last) if low <= last.1 => last.1 = last.1.max(high),
+                    _ => merged.push((low, high)),
+                }
+            }
+
+            let mut touched = 0;
+            for (low, high) in merged {
+                let first = frequencies.partition_point(|&f| f < low);
+                let end = frequencies
+                    .partition_point(|&f| f <= high)
+                    .min(frame.magnitudes.len());
+                for mag in &mut frame.magnitudes[first..end.max(first)] {
+                    *mag *= gain;
+                }
+                touched += end.saturating_sub(first);
+            }
+            touched
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::FftFrame;
+
+    /// 10 frames 10 ms apart, 100 bins 10 Hz apart, every magnitude 1.
+    fn flat_spectrogram() -> Spectrogram {
+        let frames = (0..10)
+            .map(|i| FftFrame {
+                time_seconds: i as f64 * 0.01,
+                magnitudes: vec![1.0; 100],
+                phases: vec![0.5; 100],
+            })
+            .collect();
+        let frequencies = (0..100).map(|bin| bin as f32 * 10.0).collect();
+        Spectrogram::from_frames_with_frequencies(frames, frequencies)
+    }
+
+    fn dab(time_seconds: f64, freq_hz: f32) -> BrushDab {
+        BrushDab {
+            time_seconds,
+            radius_seconds: 0.02,
+            freq_hz,
+            freq_low_hz: freq_hz - 50.0,
+            freq_high_hz: freq_hz + 50.0,
+        }
+    }
+
+    #[test]
+    fn stroke_scales_an_ellipse_once() {
+        let mut spec = flat_spectrogram();
+        let stroke = BrushStroke {
+            gain_db: -20.0,
+            // Two overlapping dabs on the same spot count once
+            dabs: vec![dab(0.05, 500.0), dab(0.05, 500.0)],
+        };
+        let touched = apply_stroke(&mut spec, &stroke);
+
+        // Centre frame: 450..=550 Hz (11 bins) at -20 dB
+        let center = &spec.frames[5].magnitudes;
+        assert!((center[50] - 0.1).abs() < 1e-6);
+        assert!((center[45] - 0.1).abs() < 1e-6);
+        assert!((center[55] - 0.1).abs() < 1e-6);
+        assert_eq!(center[44], 1.0);
+        assert_eq!(center[56], 1.0);
+        // The ellipse narrows away from the center time and ends at the radius
+        let edge = &spec.frames[6].magnitudes;
+        assert!(edge[46] < 1.0);
+        assert_eq!(edge[45], 1.0);
+        assert_eq!(spec.frames[8].magnitudes[50], 1.0);
+        assert!(touched > 11);
+        // Phases are untouched
+        assert!(spec.frames[5].phases.iter().all(|&p| p == 0.5));
+    }
+
+    #[test]
+    fn erase_gain_zeroes_and_dabs_merge() {
+        let mut spec = flat_spectrogram();
+        let stroke = BrushStroke {
+            gain_db: ERASE_GAIN_DB,
+            dabs: vec![dab(0.0, 200.0), dab(0.0, 260.0)],
+        };
+        assert_eq!(stroke.gain(), 0.0);
+        apply_stroke(&mut spec, &stroke);
+
+        let first = &spec.frames[0].magnitudes;
+        assert!(first[15..=31].iter().all(|&m| m == 0.0));
+        assert_eq!(first[14], 1.0);
+        assert_eq!(first[32], 1.0);
+        assert!(spec.frames[5].magnitudes.iter().all(|&m| m == 1.0));
+
+        assert_eq!(apply_stroke(&mut spec, &BrushStroke::new(-6.0)), 0);
+    }
+}
@@ -0,0 +1,285 @@
+use crate::data::Spectrogram;
+
+/// Tempo range the estimate is searched in
+pub const MIN_BPM: f32 = 60.0;
+pub const MAX_BPM: f32 = 200.0;
+/// Tempo the estimate leans towards when a half or double tempo scores
+/// about as well (the usual "which octave is the beat" ambiguity)
+const PREFERRED_BPM: f32 = 120.0;
+/// Seconds of flux averaged to find the local level onsets must rise above
+const FLUX_MEAN_SECONDS: f64 = 0.5;
+/// Periods tried per frame of lag when refining the tempo
+const REFINE_STEPS: usize = 20;
+
+/// Detected tempo and beat phase. Beats sit at `first_beat_seconds` plus
+/// whole beat lengths (also before it); every `beats_per_bar`-th beat,
+/// counting from the first, starts a bar.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BeatGrid {
+    pub bpm: f32,
+    pub first_beat_seconds: f64,
+    pub beats_per_bar: u32,
+}
+
+impl BeatGrid {
+    pub fn beat_seconds(&self) -> f64 {
+        60.0 / self.bpm.max(1.0) as f64
+    }
+
+    /// Beats within `min_sec..=max_sec` as (time, starts a bar).
+    pub fn beats_in(&self, min_sec: f64, max_sec: f64) -> impl Iterator<Item = (f64, bool)> + '_ {
+        let period = self.beat_seconds();
+        let first = ((min_sec - self.first_beat_seconds) / period).ceil() as i64;
+        let last = ((max_sec - self.first_beat_seconds) / period).floor() as i64;
+        let bar = self.beats_per_bar.max(1) as i64;
+        (first..=last).map(move |n| {
+            (
+                self.first_beat_seconds + n as f64 * period,
+                n.rem_euclid(bar) == 0,
+            )
+        })
+    }
+
+    /// Latest beat at or before `seconds`.
+    pub fn beat_at_or_before(&self, seconds: f64) -> f64 {
+        let period = self.beat_seconds();
+        let n = ((seconds - self.first_beat_seconds) / period + 1e-9).floor();
+        self.first_beat_seconds + n * period
+    }
+}
+
+/// Onset strength per frame: how much the log-compressed spectrum grew
+/// since the previous frame, summed over bins, with the local average
+/// taken off so only onsets that stand out remain.
+pub fn spectral_flux(spectrogram: &Spectrogram) -> Vec<f32> {
+    let max_mag = spectrogram.max_magnitude();
+    if max_mag <= 0.0 || spectrogram.frames.is_empty() {
+        return Vec::new();
+    }
+    let compress = |m: f32| (1.0 + 1000.0 * m / max_mag).ln();
+
+    let mut flux = vec![0.0f32; spectrogram.frames.len()];
+    for (i, pair) in spectrogram.frames.windows(2).enumerate() {
+        flux[i + 1] = pair[1]
+            .magnitudes
+            .iter()
+            .zip(&pair[0].magnitudes)
+            .map(|(&now, &before)| (compress(now) - compress(before)).max(0.0))
+            .sum();
+    }
+
+    let half = ((FLUX_MEAN_SECONDS / frame_seconds(spectrogram)) / 2.0).round() as usize;
+    let mean: Vec<f32> = (0..flux.len())
+        .map(|i| {
+            let window = &flux[i.saturating_sub(half)..(i + half + 1).min(flux.len())];
+            window.iter().sum::<f32>() / window.len() as f32
+        })
+        .collect();
+    flux.iter()
+        .zip(&mean)
+        .map(|(&f, &m)| (f - m).max(0.0))
+        .collect()
+}
+
+/// Estimate the tempo and beat phase of a spectrogram.
+///
+/// The tempo is the strongest period of the flux's autocorrelation within
+/// `MIN_BPM..=MAX_BPM` (leaning towards `PREFERRED_BPM`). Period and phase
+/// are then refined together to the beat comb that lands on the most flux,
+/// and the bar starts on whichever of the four beats is loudest.
+/// None when the audio is too short for two slow beats or has no onsets.
+pub fn detect_beat_grid(spectrogram: &Spectrogram) -> Option<BeatGrid> {
+    // Onsets land between frames; a little smoothing lets a beat comb
+    // that passes between frames still see them
+    let raw = spectral_flux(spectrogram);
+    let flux: Vec<f32> = (0..raw.len())
+        .map(|i| {
+            let before = raw[i.saturating_sub(1)];
+            let after = raw[(i + 1).min(raw.len() - 1)];
+            0.25 * before + 0.5 * raw[i] + 0.25 * after
+        })
+        .collect();
+    let hop = frame_seconds(spectrogram);
+    let min_lag = ((60.0 / MAX_BPM as f64) / hop).floor().max(1.0) as usize;
+    let max_lag = ((60.0 / MIN_BPM as f64) / hop).ceil() as usize;
+    if flux.len() < 2 * max_lag + 2 || flux.iter().all(|&f| f <= 0.0) {
+        return None;
+    }
+
+    let autocorrelation = |lag: usize| -> f32 {
+        let sum: f32 = flux.iter().zip(&flux[lag..]).map(|(a, b)| a * b).sum();
+        sum / (flux.len() - lag) as f32
+    };
+    let scores: Vec<f32> = (min_lag - 1..=max_lag + 1).map(autocorrelation).collect();
+    let weight = |lag: f64| -> f32 {
+        let octaves = ((60.0 / (lag * hop)) as f32 / PREFERRED_BPM).log2();
+        (-0.5 * octaves * octaves).exp()
+    };
+    let best = (1..scores.len() - 1)
+        .filter(|&i| scores[i] >= scores[i - 1] && scores[i] >= scores[i + 1])
+        .max_by(|&a, &b| {
+            let score = |i: usize| scores[i] * weight((min_lag - 1 + i) as f64);
+            score(a).total_cmp(&score(b))
+        })?;
+    if scores[best] <= 0.0 {
+        return None;
+    }
+    let lag = (min_lag - 1 + best) as f64;
+
+    // Flux at a fractional frame, linearly interpolated
+    let flux_at = |frame: f64| -> f32 {
+        let i = frame.floor() as usize;
+        let t = (frame - i as f64) as f32;
+        match (flux.get(i), flux.get(i + 1)) {
+            (Some(&a), Some(&b)) => a + (b - a) * t,
+            (Some(&a), None) => a,
+            _ => 0.0,
+        }
+    };
+    let beats = |period: f64, phase: f64| {
+        (0..)
+            .map(move |k| phase + k as f64 * period)
+            .take_while(|&f| f < flux.len() as f64)
+    };
+    // Mean flux on the beats of a comb, which rewards a period that keeps
+    // landing on onsets over the whole file
+    let comb = |period: f64, phase: f64| -> f32 {
+        let (sum, count) = beats(period, phase).fold((0.0, 0), |(s, c), f| (s + flux_at(f), c + 1));
+        sum / count.max(1) as f32
+    };
+
+    // The autocorrelation lag is a whole number of frames; search a step
+    // either side of it for the period and phase that fit best
+    let (period, phase) = (0..=REFINE_STEPS * 2)
+        .map(|i| lag - 1.0 + i as f64 / REFINE_STEPS as f64)
+        .flat_map(|period| {
+            let steps = (period * 4.0).ceil() as usize;
+            (0..steps).map(move |step| (period, step as f64 * period / steps as f64))
+        })
+        .map(|(period, phase)| (comb(period, phase), period, phase))
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, period, phase)| (period, phase))?;
+
+    let beats_per_bar = 4;
+    // Mean flux on every `beats_per_bar`-th beat from `first` on
+    let bar_energy = |first: usize| -> f32 {
+        let (sum, count) = beats(period, phase)
+            .skip(first)
+            .step_by(beats_per_bar)
+            .fold((0.0, 0), |(s, c), f| (s + flux_at(f), c + 1));
+        sum / count.max(1) as f32
+    };
+    let downbeat = (0..beats_per_bar)
+        .max_by(|&a, &b| bar_energy(a).total_cmp(&bar_energy(b)))
+        .unwrap_or(0);
+
+    Some(BeatGrid {
+        bpm: (60.0 / (period * hop)) as f32,
+        first_beat_seconds: spectrogram.frames[0].time_seconds
+            + (phase + downbeat as f64 * period) * hop,
+        beats_per_bar: beats_per_bar as u32,
+    })
+}
+
+/// Seconds between frames, from the first and last frame times
+fn frame_seconds(spectrogram: &Spectrogram) -> f64 {
+    let frames = &spectrogram.frames;
+    match (frames.first(), frames.last()) {
+        (Some(first), Some(last)) if frames.len() > 1 => {
+            ((last.time_seconds - first.time_seconds) / (frames.len() - 1) as f64).max(1e-6)
+        }
+        _ => 1.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::FftFrame;
+
+    /// A spectrogram of broadband clicks at `bpm`, every fourth one louder,
+    /// over a quiet bed of noise.
+    fn click_track(bpm: f64, first_click: f64, seconds: f64) -> Spectrogram {
+        let hop = 0.01;
+        let bins = 64;
+        let beat = 60.0 / bpm;
+        let frames = (0..(seconds / hop) as usize)
+            .map(|i| {
+                let time_seconds = i as f64 * hop;
+                let beat_index = ((time_seconds - first_click) / beat).ceil() as i64;
+                let click = first_click + beat_index as f64 * beat;
+                let level = if click < time_seconds + hop {
+                    if beat_index.rem_euclid(4) == 0 {
+                        1.0
+                    } else {
+                        0.1
+                    }
+                } else {
+                    0.001 * (((i * 7919) % 13) as f32 + 1.0)
+                };
+                FftFrame {
+                    time_seconds,
+                    magnitudes: vec![level; bins],
+                    phases: vec![0.0; bins],
+                }
+            })
+            .collect();
+        let frequencies = (0..bins).map(|b| b as f32 * 100.0).collect();
+        Spectrogram::from_frames_with_frequencies(frames, frequencies)
+    }
+
+    #[test]
+    fn click_track_tempo_phase_and_downbeat_are_found() {
+        let grid = detect_beat_grid(&click_track(128.0, 0.23, 12.0)).unwrap();
+        assert!((grid.bpm - 128.0).abs() < 1.0, "bpm = {}", grid.bpm);
+        let beat = grid.beat_seconds();
+        let phase = (grid.first_beat_seconds - 0.23).rem_euclid(beat * 4.0);
+        assert!(
+            phase.min(beat * 4.0 - phase) < 0.02,
+            "first beat = {}",
+            grid.first_beat_seconds
+        );
+
+        let grid = detect_beat_grid(&click_track(90.0, 0.0, 12.0)).unwrap();
+        assert!((grid.bpm - 90.0).abs() < 1.0, "bpm = {}", grid.bpm);
+    }
+
+    #[test]
+    fn too_short_or_silent_audio_has_no_grid() {
+        assert_eq!(detect_beat_grid(&click_track(120.0, 0.0, 1.0)), None);
+        let silent = Spectrogram::from_frames_with_frequencies(
+            (0..1000)
+                .map(|i| FftFrame {
+                    time_seconds: i as f64 * 0.01,
+                    magnitudes: vec![0.0; 8],
+                    phases: vec![0.0; 8],
+                })
+                .collect(),
+            (0..8).map(|b| b as f32).collect(),
+        );
+        assert_eq!(detect_beat_grid(&silent), None);
+    }
+
+    #[test]
+    fn grid_lists_beats_and_bars_in_a_range() {
+        let grid = BeatGrid {
+            bpm: 120.0,
+            first_beat_seconds: 1.0,
+            beats_per_bar: 4,
+        };
+        let beats: Vec<(f64, bool)> = grid.beats_in(0.0, 2.6).collect();
+        assert_eq!(
+            beats,
+            vec![
+                (0.0, false),
+                (0.5, false),
+                (1.0, true),
+                (1.5, false),
+                (2.0, false),
+                (2.5, false)
+            ]
+        );
+        assert_eq!(grid.beat_at_or_before(1.74), 1.5);
+        assert_eq!(grid.beat_at_or_before(2.0), 2.0);
+    }
+}
@@ -0,0 +1,312 @@
+use musickbeets::helper::PitchName;
+use musickbeets::midi_import::{config_line, format_number, write_song_csv};
+
+use crate::data::pitch::{C0_HZ, hz_to_semitones};
+use crate::processing::partials::PartialTrack;
+
+/// Largest pitch error (in cents) for a partial to count as a harmonic of
+/// a lower one.
+const HARMONIC_TOLERANCE_CENTS: f32 = 40.0;
+
+/// Settings for File > Export to Tracker CSV.
+#[derive(Debug, Clone)]
+pub struct TranscriptionOptions {
+    pub bpm: f32,
+    /// Row grid: 4 = sixteenth notes
+    pub rows_per_beat: u32,
+    /// Most tracker channels to use (notes that don't fit are dropped)
+    pub max_channels: usize,
+    pub instrument: String,
+    /// Partials whose peak is this many dB below the loudest are left out
+    pub floor_db: f32,
+}
+
+impl Default for TranscriptionOptions {
+    fn default() -> Self {
+        Self {
+            bpm: 120.0,
+            rows_per_beat: 4,
+            max_channels: 12,
+            instrument: "sine".to_string(),
+            floor_db: -30.0,
+        }
+    }
+}
+
+/// One note quantized to the row grid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TranscribedNote {
+    pub semitones_from_c0: i32,
+    pub start_row: usize,
+    /// Row of the release (exclusive end of the held rows)
+    pub end_row: usize,
+    /// Relative to the loudest note (0..1)
+    pub amplitude: f32,
+}
+
+/// A transcribed song.
+#[derive(Debug, Clone)]
+pub struct Transcription {
+    /// The song as tracker CSV text
+    pub csv: String,
+    pub note_count: usize,
+    pub channel_count: usize,
+    pub row_count: usize,
+    /// Notes left out because every channel was busy
+    pub dropped_notes: usize,
+}
+
+/// Turn partial tracks into notes on the row grid.
+///
+/// Only dominant partials become notes: those within `floor_db` of the
+/// loudest, minus partials sitting on a harmonic (2x, 3x, ...) of a lower
+/// partial that sounds at the same time, since those belong to that note's
+/// timbre. Each note's pitch is its partial's magnitude-weighted mean
+/// frequency rounded to the nearest semitone; `start_seconds` is row 0.
+/// Notes of the same pitch that touch after rounding are merged, so a
+/// partial broken up by a short dropout stays one note.
+pub fn notes_from_partials(
+    tracks: &[PartialTrack],
+    start_seconds: f64,
+    options: &TranscriptionOptions,
+) -> Vec<TranscribedNote> {
+    let loudest = tracks
+        .iter()
+        .map(PartialTrack::peak_magnitude)
+        .fold(0.0, f32::max);
+    if loudest <= 0.0 {
+        return Vec::new();
+    }
+    let floor = loudest * 10f32.powf(options.floor_db.min(0.0) / 20.0);
+    let loud: Vec<&PartialTrack> = tracks
+        .iter()
+        .filter(|track| track.peak_magnitude() >= floor)
+        .collect();
+
+    let seconds_per_row =
+        60.0 / (options.bpm.max(1.0) as f64 * options.rows_per_beat.max(1) as f64);
+    let to_row =
+        |seconds: f64| ((seconds - start_seconds).max(0.0) / seconds_per_row).round() as usize;
+
+    let mut notes: Vec<TranscribedNote> = loud
+        .iter()
+        .filter(|track| !loud.iter().any(|lower| is_harmonic_of(track, lower)))
+        .filter_map(|track| {
+            let freq = track.mean_freq_hz();
+            if freq < C0_HZ {
+                return None;
+            }
+            let start_row = to_row(track.birth_seconds());
+            let end_row = to_row(track.death_seconds());
+            (end_row > start_row).then(|| TranscribedNote {
+                semitones_from_c0: hz_to_semitones(freq).round() as i32,
+                start_row,
+                end_row,
+                amplitude: track.peak_magnitude() / loudest,
+            })
+        })
+        .collect();
+
+    notes.sort_by_key(|note| (note.semitones_from_c0, note.start_row));
+    let mut merged: Vec<TranscribedNote> = Vec::with_capacity(notes.len());
+    for note in notes {
+        match merged.last_mut() {
+            Some(prev)
+                if prev.semitones_from_c0 == note.semitones_from_c0
+                    && note.start_row <= prev.end_row =>
+            {
+                prev.end_row = prev.end_row.max(note.end_row);
+                prev.amplitude = prev.amplitude.max(note.amplitude);
+            }
+            _ => merged.push(note),
+        }
+    }
+    merged.sort_by(|a, b| {
+        a.start_row
+            .cmp(&b.start_row)
+            .then(b.amplitude.total_cmp(&a.amplitude))
+    });
+    merged
+}
+
+/// Whether `upper` sits on a harmonic of `lower` while both sound.
+fn is_harmonic_of(upper: &PartialTrack, lower: &PartialTrack) -> bool {
+    let (upper_hz, lower_hz) = (upper.mean_freq_hz(), lower.mean_freq_hz());
+    if lower_hz <= 0.0 || upper_hz < lower_hz * 1.5 {
+        return false;
+    }
+    let overlap = upper.death_seconds().min(lower.death_seconds())
+        - upper.birth_seconds().max(lower.birth_seconds());
+    let upper_length = upper.death_seconds() - upper.birth_seconds();
+    if overlap <= 0.0 || overlap < upper_length * 0.5 {
+        return false;
+    }
+    let ratio = upper_hz / lower_hz;
+    let harmonic = ratio.round();
+    (1200.0 * (ratio / harmonic).log2()).abs() <= HARMONIC_TOLERANCE_CENTS
+}
+
+/// Transcribe partial tracks into a tracker song.
+///
+/// Notes are spread over monophonic channels the way the tracker's MIDI
+/// import does it: each note takes the first channel free at its start row,
+/// holds with "-" and releases with ".", and louder notes pick first when
+/// several start together.
+pub fn transcribe(
+    tracks: &[PartialTrack],
+    start_seconds: f64,
+    title: &str,
+    options: &TranscriptionOptions,
+) -> Transcription {
+    let notes = notes_from_partials(tracks, start_seconds, options);
+
+    let mut columns: Vec<Vec<String>> = Vec::new();
+    // First row each column is free again
+    let mut free_rows: Vec<usize> = Vec::new();
+    let mut dropped_notes = 0;
+
+    for note in &notes {
+        let column_index = match free_rows.iter().position(|&free| free <= note.start_row) {
+            Some(index) => index,
+            None if columns.len() < options.max_channels.max(1) => {
+                columns.push(Vec::new());
+                free_rows.push(0);
+                columns.len() - 1
+            }
+            None => {
+                dropped_notes += 1;
+                continue;
+            }
+        };
+        free_rows[column_index] = note.end_row;
+
+        let column = &mut columns[column_index];
+        if column.len() <= note.end_row {
+            column.resize(note.end_row + 1, String::new());
+        }
+        let pitch = PitchName::from_semitones_from_c0(note.semitones_from_c0, 0.0);
+        column[note.start_row] = format!(
+            "{} {} a:{}",
+            pitch,
+            options.instrument,
+            format_number(note.amplitude.max(0.05))
+        );
+        for cell in &mut column[note.start_row + 1..note.end_row] {
+            *cell = "-".to_string();
+        }
+        column[note.end_row] = ".".to_string();
+    }
+
+    let column_names: Vec<String> = (1..=columns.len())
+        .map(|voice| format!("Voice{}", voice))
+        .collect();
+    let rows_per_beat = options.rows_per_beat.max(1);
+    let preamble = [
+        config_line(title, options.bpm, rows_per_beat),
+        format!(
+            "// Transcribed by the FFT analyzer: {} notes, {} rows per beat",
+            notes.len() - dropped_notes,
+            rows_per_beat
+        ),
+    ];
+
+    Transcription {
+        csv: write_song_csv(&column_names, &columns, &preamble),
+        note_count: notes.len() - dropped_notes,
+        channel_count: columns.len(),
+        row_count: columns.iter().map(Vec::len).max().unwrap_or(0),
+        dropped_notes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processing::partials::PartialPoint;
+
+    /// A steady partial sampled every 10 ms.
+    fn partial(freq_hz: f32, magnitude: f32, birth: f64, death: f64) -> PartialTrack {
+        let frames = ((death - birth) / 0.01).round() as usize;
+        PartialTrack {
+            points: (0..=frames)
+                .map(|i| PartialPoint {
+                    frame: i,
+                    time_seconds: birth + i as f64 * 0.01,
+                    freq_hz,
+                    magnitude,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn notes_keep_fundamentals_and_merge_dropouts() {
+        // 120 BPM, 4 rows per beat: 0.125 s per row
+        let tracks = vec![
+            // A4 for rows 0..4, with its 2nd and 3rd harmonics
+            partial(440.0, 1.0, 0.0, 0.5),
+            partial(881.0, 0.6, 0.0, 0.5),
+            partial(1318.0, 0.4, 0.0, 0.5),
+            // C5 slightly sharp, broken by a dropout, rows 4..8
+            partial(526.0, 0.5, 0.5, 0.74),
+            partial(526.0, 0.5, 0.76, 1.0),
+            // Too quiet to count
+            partial(300.0, 0.01, 0.0, 1.0),
+        ];
+        let notes = notes_from_partials(&tracks, 0.0, &TranscriptionOptions::default());
+        assert_eq!(
+            notes,
+            vec![
+                TranscribedNote {
+                    semitones_from_c0: 57,
+                    start_row: 0,
+                    end_row: 4,
+                    amplitude: 1.0,
+                },
+                TranscribedNote {
+                    semitones_from_c0: 60,
+                    start_row: 4,
+                    end_row: 8,
+                    amplitude: 0.5,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn transcription_is_a_playable_song() {
+        let tracks = vec![
+            partial(261.63, 1.0, 1.0, 1.5),
+            partial(329.63, 0.5, 1.0, 1.25),
+            partial(392.0, 0.5, 1.25, 1.5),
+        ];
+        let song = transcribe(&tracks, 1.0, "Take", &TranscriptionOptions::default());
+        assert_eq!(song.note_count, 3);
+        assert_eq!(song.channel_count, 2);
+        assert_eq!(song.dropped_notes, 0);
+
+        let lines: Vec<&str> = song.csv.lines().collect();
+        assert_eq!(lines[0], "Voice1,Voice2");
+        assert_eq!(
+            lines[1],
+            "config, title: Take, tempo_bpm: 120, tick_duration: 0.125"
+        );
+        assert_eq!(lines[3], "c4 sine a:1,e4 sine a:0.5");
+        assert_eq!(lines[5], "-,g4 sine a:0.5");
+        assert_eq!(lines[7], ".,.");
+
+        let parsed = musickbeets::Song::parse(&song.csv, 2);
+        assert!(
+            parsed.diagnostics().is_empty(),
+            "{:?}",
+            parsed.diagnostics()
+        );
+        assert_eq!(parsed.row_count(), 5);
+
+        let limited = TranscriptionOptions {
+            max_channels: 1,
+            ..TranscriptionOptions::default()
+        };
+        assert_eq!(transcribe(&tracks, 1.0, "Take", &limited).dropped_notes, 2);
+    }
+}
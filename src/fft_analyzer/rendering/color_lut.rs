@@ -102,7 +102,12 @@ impl ColorLUT {
     /// then indexes into the pre-built LUT.
     #[inline(always)]
     pub fn lookup(&self, magnitude: f32) -> (u8, u8, u8) {
-        let db = 20.0 * magnitude.max(1e-10).log10();
+        self.lookup_db(20.0 * magnitude.max(1e-10).log10())
+    }
+
+    /// Look up a color for a level already in dB (used to draw the colorbar).
+    #[inline(always)]
+    pub fn lookup_db(&self, db: f32) -> (u8, u8, u8) {
         let range = self.db_ceiling - self.threshold_db;
         if range <= 0.0 {
             return self.table[0];
@@ -112,6 +117,11 @@ impl ColorLUT {
         self.table[index]
     }
 
+    /// The dB range the colors span: (threshold, ceiling).
+    pub fn db_range(&self) -> (f32, f32) {
+        (self.threshold_db, self.db_ceiling)
+    }
+
     fn map_color(&self, intensity: f32) -> (u8, u8, u8) {
         match self.colormap {
             ColormapId::Classic => Self::colormap_classic(intensity),
@@ -269,6 +279,21 @@ impl ColorLUT {
     }
 }
 
+/// Color for a Difference view level: black where the reconstruction
+/// matches, blue where it lost level, red where it added some, saturating at
+/// `range_db`. The square root keeps small differences visible.
+#[inline(always)]
+pub fn difference_color(db: f32, range_db: f32) -> (u8, u8, u8) {
+    let t = (db.abs() / range_db.max(1e-3)).min(1.0).sqrt();
+    let strong = (t * 255.0) as u8;
+    let soft = (t * 90.0) as u8;
+    if db < 0.0 {
+        (soft / 2, soft, strong)
+    } else {
+        (strong, soft, soft / 2)
+    }
+}
+
 impl Default for ColorLUT {
     fn default() -> Self {
         Self::new(-124.0, 0.0, 1.0, 2.2, ColormapId::Classic)
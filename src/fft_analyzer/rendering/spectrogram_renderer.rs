@@ -4,8 +4,9 @@ use fltk::image::RgbImage;
 use fltk::prelude::ImageExt;
 use rayon::prelude::*;
 
-use super::color_lut::ColorLUT;
-use crate::data::{compute_active_bins, FftParams, Spectrogram, ViewState};
+use super::color_lut::{ColorLUT, difference_color};
+use crate::data::{FftParams, MelFilterbank, Spectrogram, ViewState, compute_active_bins};
+use crate::processing::recon_compare::DIFFERENCE_RANGE_DB;
 
 pub struct SpectrogramRenderer {
     color_lut: ColorLUT,
@@ -69,6 +70,9 @@ impl SpectrogramRenderer {
                 p.to_bits().hash(&mut hasher);
             }
         }
+        view.mel_view.hash(&mut hasher);
+        view.mel_bands.hash(&mut hasher);
+        view.difference_view.hash(&mut hasher);
         view.threshold_db.to_bits().hash(&mut hasher);
         view.db_ceiling.to_bits().hash(&mut hasher);
         view.brightness.to_bits().hash(&mut hasher);
@@ -195,6 +199,45 @@ impl SpectrogramRenderer {
         draw::draw_text("Load an audio file to begin", x + 10, y + h / 2);
     }
 
+    /// Render the spectrogram into a new offscreen RGB buffer (3 bytes per
+    /// pixel, rows top to bottom) instead of the widget. Used by image export,
+    /// so the size can differ from the on-screen widget. Leaves the draw
+    /// cache alone.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_to_rgb(
+        &mut self,
+        spec: &Spectrogram,
+        view: &ViewState,
+        params: &FftParams,
+        proc_time_min: f64,
+        proc_time_max: f64,
+        render_full_file_outside_roi: bool,
+        width: usize,
+        height: usize,
+    ) -> Vec<u8> {
+        self.update_lut(view);
+        let mut buffer = vec![0u8; width * height * 3];
+        if width > 0 && height > 0 && spec.num_frames() > 0 && spec.num_bins() > 0 {
+            self.render_pixels(
+                &mut buffer,
+                spec,
+                view,
+                params,
+                proc_time_min,
+                proc_time_max,
+                render_full_file_outside_roi,
+                width,
+                height,
+            );
+        }
+        buffer
+    }
+
+    /// The color lookup table the last render used (for drawing a colorbar).
+    pub fn color_lut(&self) -> &ColorLUT {
+        &self.color_lut
+    }
+
     fn rebuild_cache(
         &mut self,
         spec: &Spectrogram,
@@ -211,6 +254,55 @@ impl SpectrogramRenderer {
             self.cached_buffer = vec![0u8; buffer_size];
         }
 
+        // Take the buffer out so render_pixels can borrow self alongside it
+        let mut buffer = std::mem::take(&mut self.cached_buffer);
+        self.render_pixels(
+            &mut buffer,
+            spec,
+            view,
+            params,
+            proc_time_min,
+            proc_time_max,
+            render_full_file_outside_roi,
+            width,
+            height,
+        );
+        self.cached_buffer = buffer;
+
+        match RgbImage::new(
+            &self.cached_buffer,
+            width as i32,
+            height as i32,
+            fltk::enums::ColorDepth::Rgb8,
+        ) {
+            Ok(img) => {
+                self.cached_image = Some(img);
+            }
+            Err(e) => {
+                app_log!(
+                    "SpectrogramRenderer",
+                    "Failed to create spectrogram image: {:?}",
+                    e
+                );
+                self.cached_image = None;
+            }
+        }
+    }
+
+    /// Rasterize the spectrogram into `buffer` (width * height * 3 bytes).
+    #[allow(clippy::too_many_arguments)]
+    fn render_pixels(
+        &self,
+        buffer: &mut [u8],
+        spec: &Spectrogram,
+        view: &ViewState,
+        params: &FftParams,
+        proc_time_min: f64,
+        proc_time_max: f64,
+        render_full_file_outside_roi: bool,
+        width: usize,
+        height: usize,
+    ) {
         let num_bins = spec.num_bins();
 
         // Pre-compute active bins per frame based on freq range + freq count filtering.
@@ -220,25 +312,56 @@ impl SpectrogramRenderer {
         let freq_count = view.recon_freq_count;
 
         let spec_freqs = &spec.frequencies;
+        let difference = view.difference_view;
 
-        let active_bins: Vec<Vec<bool>> = spec
-            .frames
-            .par_iter()
-            .map(|frame| {
-                compute_active_bins(
-                    &frame.magnitudes,
-                    spec_freqs,
-                    freq_min,
-                    freq_max,
-                    freq_count,
-                )
-            })
-            .collect();
+        // Difference layers hold dB deltas, not magnitudes: every bin is
+        // shown as is, without the Top-N filter or mel pooling.
+        let active_bins: Vec<Vec<bool>> = if difference {
+            Vec::new()
+        } else {
+            spec.frames
+                .par_iter()
+                .map(|frame| {
+                    compute_active_bins(
+                        &frame.magnitudes,
+                        spec_freqs,
+                        freq_min,
+                        freq_max,
+                        freq_count,
+                    )
+                })
+                .collect()
+        };
 
         let first_in_range = spec_freqs.iter().position(|&f| f >= freq_min);
         let last_in_range = spec_freqs.iter().rposition(|&f| f <= freq_max);
 
-        // Pre-compute frequency bin and frequency ROI flag for each pixel row.
+        // Mel view: pool every frame into mel bands over the whole file's
+        // frequency range (bands stay put while zooming). The ROI-filtered
+        // pooling uses only the active bins, like the per-bin path below.
+        let mel_bank = (view.mel_view && !difference).then(|| {
+            MelFilterbank::new(
+                spec_freqs,
+                view.mel_bands,
+                0.0,
+                spec_freqs.last().copied().unwrap_or(0.0),
+            )
+        });
+        let mel_frames: Option<Vec<(Vec<f32>, Vec<f32>)>> = mel_bank.as_ref().map(|bank| {
+            spec.frames
+                .par_iter()
+                .zip(active_bins.par_iter())
+                .map(|(frame, active)| {
+                    (
+                        bank.pool(&frame.magnitudes, None),
+                        bank.pool(&frame.magnitudes, Some(active.as_slice())),
+                    )
+                })
+                .collect()
+        });
+
+        // Pre-compute frequency bin (or mel band) and frequency ROI flag for
+        // each pixel row.
         let row_data: Vec<(usize, bool)> = (0..height)
             .map(|py| {
                 let flipped_py = height - 1 - py;
@@ -246,7 +369,9 @@ impl SpectrogramRenderer {
                 let freq = view.y_to_freq(t);
                 let in_freq_roi = freq >= freq_min && freq <= freq_max;
 
-                if !spec_freqs.is_empty() {
+                if let Some(bank) = &mel_bank {
+                    (bank.band_for_freq(freq), in_freq_roi)
+                } else if !spec_freqs.is_empty() {
                     let (search_start, search_end) = if in_freq_roi {
                         match (first_in_range, last_in_range) {
                             (Some(start), Some(end)) if start <= end => (start, end),
@@ -345,7 +470,7 @@ impl SpectrogramRenderer {
 
         // Parallel rendering by rows
         let row_size = width * 3;
-        self.cached_buffer
+        buffer
             .par_chunks_mut(row_size)
             .enumerate()
             .for_each(|(py, row)| {
@@ -366,22 +491,33 @@ impl SpectrogramRenderer {
                     // active-bin behavior. Outside the ROI frequency band we
                     // use the raw spectrogram magnitude so the content can be
                     // dimmed instead of going blank.
-                    let max_mag = if let Some(frame) = spec.frames.get(frame_idx) {
-                        if in_freq_roi {
-                            if active_bins[frame_idx].get(bin).copied().unwrap_or(false) {
-                                frame.magnitudes.get(bin).copied().unwrap_or(0.0)
+                    let (r, g, b) = if difference {
+                        let db = spec.frames[frame_idx]
+                            .magnitudes
+                            .get(bin)
+                            .copied()
+                            .unwrap_or(0.0);
+                        difference_color(db, DIFFERENCE_RANGE_DB)
+                    } else {
+                        let max_mag = if let Some(mel) = &mel_frames {
+                            let (raw, active) = &mel[frame_idx];
+                            if in_freq_roi { active[bin] } else { raw[bin] }
+                        } else if let Some(frame) = spec.frames.get(frame_idx) {
+                            if in_freq_roi {
+                                if active_bins[frame_idx].get(bin).copied().unwrap_or(false) {
+                                    frame.magnitudes.get(bin).copied().unwrap_or(0.0)
+                                } else {
+                                    0.0
+                                }
                             } else {
-                                0.0
+                                frame.magnitudes.get(bin).copied().unwrap_or(0.0)
                             }
                         } else {
-                            frame.magnitudes.get(bin).copied().unwrap_or(0.0)
-                        }
-                    } else {
-                        0.0
+                            0.0
+                        };
+                        lut.lookup(max_mag)
                     };
 
-                    let (r, g, b) = lut.lookup(max_mag);
-
                     // Check if this pixel is inside the ROI rectangle.
                     let in_proc_range = time >= proc_time_min && time <= proc_time_max;
                     let in_roi = in_proc_range && in_freq_roi;
@@ -404,25 +540,6 @@ impl SpectrogramRenderer {
                     }
                 }
             });
-
-        match RgbImage::new(
-            &self.cached_buffer,
-            width as i32,
-            height as i32,
-            fltk::enums::ColorDepth::Rgb8,
-        ) {
-            Ok(img) => {
-                self.cached_image = Some(img);
-            }
-            Err(e) => {
-                app_log!(
-                    "SpectrogramRenderer",
-                    "Failed to create spectrogram image: {:?}",
-                    e
-                );
-                self.cached_image = None;
-            }
-        }
     }
 }
 
@@ -0,0 +1,341 @@
+//! Analyzer sessions (File > Save Session / Open Session): the opened audio
+//! file plus the analysis, view, and reconstruction settings, saved as JSON
+//! so a day's work can be picked up again after the app closes.
+//!
+//! The audio itself is not stored. Open Session applies the settings, reloads
+//! the file from `audio_path`, and then restores the parts that depend on the
+//! file (time range, zoom, dB ceiling, markers) once it has loaded.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::app_state::AppState;
+use crate::data::{
+    ColormapId, FftParams, FreqScale, GradientStop, Marker, ViewState, WindowType,
+    default_custom_gradient,
+};
+
+/// Written to every session file; files from later versions are refused
+pub const SESSION_VERSION: u32 = 1;
+
+/// Everything Save Session writes. Fields missing from a file take the
+/// analyzer's defaults, so older session files keep loading.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub version: u32,
+    /// Absolute path of the analyzed audio file (or tracker song)
+    pub audio_path: PathBuf,
+
+    // ── Analysis ──
+    pub window_length: usize,
+    pub overlap_percent: f32,
+    pub window_type: String, // "Rectangular", "Hann", "Hamming", "Blackman", "Kaiser"
+    pub kaiser_beta: f32,
+    pub center_pad: bool,
+    pub zero_pad_factor: usize,
+    pub start_sample: usize,
+    pub stop_sample: usize,
+
+    // ── View ──
+    pub freq_min_hz: f32,
+    pub freq_max_hz: f32,
+    pub freq_scale_power: f32,
+    pub time_min_sec: f64,
+    pub time_max_sec: f64,
+    pub colormap: String, // ColormapId::name()
+    pub custom_gradient: Vec<GradientStop>,
+    pub threshold_db: f32,
+    pub db_ceiling: f32,
+    pub brightness: f32,
+    pub gamma: f32,
+
+    // ── Reconstruction ──
+    pub recon_freq_min_hz: f32,
+    pub recon_freq_max_hz: f32,
+    pub recon_norm_floor: f64,
+    pub griffin_lim_iterations: usize,
+    pub noise_reduction_db: f32,
+
+    // ── Markers ──
+    pub markers: Vec<Marker>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        let params = FftParams::default();
+        let view = ViewState::default();
+        Self {
+            version: SESSION_VERSION,
+            audio_path: PathBuf::new(),
+
+            window_length: params.window_length,
+            overlap_percent: params.overlap_percent,
+            window_type: "Hann".to_string(),
+            kaiser_beta: 8.6,
+            center_pad: params.use_center,
+            zero_pad_factor: params.zero_pad_factor,
+            start_sample: 0,
+            stop_sample: 0,
+
+            freq_min_hz: view.freq_min_hz,
+            freq_max_hz: view.freq_max_hz,
+            freq_scale_power: 0.5,
+            time_min_sec: 0.0,
+            time_max_sec: 0.0,
+            colormap: view.colormap.name().to_string(),
+            custom_gradient: default_custom_gradient(),
+            threshold_db: view.threshold_db,
+            db_ceiling: view.db_ceiling,
+            brightness: view.brightness,
+            gamma: view.gamma,
+
+            recon_freq_min_hz: view.recon_freq_min_hz,
+            recon_freq_max_hz: view.recon_freq_max_hz,
+            recon_norm_floor: view.recon_norm_floor,
+            griffin_lim_iterations: view.recon_griffin_lim_iterations,
+            noise_reduction_db: 12.0,
+
+            markers: Vec::new(),
+        }
+    }
+}
+
+impl Session {
+    /// Capture the current session. None when the audio didn't come from a
+    /// file (nothing loaded, an FFT CSV, or a live input snapshot).
+    pub fn from_app_state(st: &AppState) -> Option<Self> {
+        let audio_path = st.audio_path.clone()?;
+        let params = &st.fft_params;
+        let view = &st.view;
+        let (window_type, kaiser_beta) = match params.window_type {
+            WindowType::Rectangular => ("Rectangular", 8.6),
+            WindowType::Hann => ("Hann", 8.6),
+            WindowType::Hamming => ("Hamming", 8.6),
+            WindowType::Blackman => ("Blackman", 8.6),
+            WindowType::Kaiser(beta) => ("Kaiser", beta),
+        };
+        let freq_scale_power = match view.freq_scale {
+            FreqScale::Linear => 0.0,
+            FreqScale::Log => 1.0,
+            FreqScale::Power(power) => power,
+        };
+        Some(Self {
+            version: SESSION_VERSION,
+            audio_path,
+
+            window_length: params.window_length,
+            overlap_percent: params.overlap_percent,
+            window_type: window_type.to_string(),
+            kaiser_beta,
+            center_pad: params.use_center,
+            zero_pad_factor: params.zero_pad_factor,
+            start_sample: params.start_sample,
+            stop_sample: params.stop_sample,
+
+            freq_min_hz: view.freq_min_hz,
+            freq_max_hz: view.freq_max_hz,
+            freq_scale_power,
+            time_min_sec: view.time_min_sec,
+            time_max_sec: view.time_max_sec,
+            colormap: view.colormap.name().to_string(),
+            custom_gradient: view.custom_gradient.clone(),
+            threshold_db: view.threshold_db,
+            db_ceiling: view.db_ceiling,
+            brightness: view.brightness,
+            gamma: view.gamma,
+
+            recon_freq_min_hz: view.recon_freq_min_hz,
+            recon_freq_max_hz: view.recon_freq_max_hz,
+            recon_norm_floor: view.recon_norm_floor,
+            griffin_lim_iterations: view.recon_griffin_lim_iterations,
+            noise_reduction_db: st.noise_reduction_db,
+
+            markers: st.markers.clone(),
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).context("Failed to encode the session")?;
+        std::fs::write(path, json + "\n")
+            .with_context(|| format!("Failed to write session file: {:?}", path))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read session file: {:?}", path))?;
+        let session: Self = serde_json::from_str(&text)
+            .with_context(|| format!("Not a session file: {:?}", path))?;
+        if session.version > SESSION_VERSION {
+            anyhow::bail!(
+                "Session file version {} is newer than this analyzer (version {})",
+                session.version,
+                SESSION_VERSION
+            );
+        }
+        if session.audio_path.as_os_str().is_empty() {
+            anyhow::bail!("Session file has no audio_path: {:?}", path);
+        }
+        Ok(session)
+    }
+
+    /// Apply the settings that don't depend on the file (window, display,
+    /// reconstruction). Open Session does this before reloading the audio.
+    pub fn apply_settings(&self, params: &mut FftParams, view: &mut ViewState) {
+        params.window_length = self.window_length.max(4);
+        params.overlap_percent = self.overlap_percent.clamp(0.0, 99.0);
+        params.window_type = match self.window_type.as_str() {
+            "Rectangular" => WindowType::Rectangular,
+            "Hamming" => WindowType::Hamming,
+            "Blackman" => WindowType::Blackman,
+            "Kaiser" if self.kaiser_beta > 0.0 => WindowType::Kaiser(self.kaiser_beta),
+            "Kaiser" => WindowType::Kaiser(8.6),
+            _ => WindowType::Hann,
+        };
+        params.use_center = self.center_pad;
+        params.zero_pad_factor = match self.zero_pad_factor {
+            factor @ (1 | 2 | 4 | 8) => factor,
+            _ => 1,
+        };
+        // A fixed window size, like picking it in the sidebar
+        params.target_segments_per_active = None;
+        params.target_bins_per_segment = None;
+
+        view.freq_scale = FreqScale::Power(self.freq_scale_power.clamp(0.0, 1.0));
+        view.colormap = ColormapId::ALL
+            .iter()
+            .copied()
+            .find(|c| c.name() == self.colormap)
+            .unwrap_or(ColormapId::Classic);
+        if self.custom_gradient.len() >= 2 {
+            view.custom_gradient = self.custom_gradient.clone();
+        }
+        view.threshold_db = self.threshold_db;
+        view.brightness = self.brightness;
+        view.gamma = self.gamma;
+
+        view.recon_freq_min_hz = self.recon_freq_min_hz.max(0.0);
+        view.recon_freq_max_hz = self.recon_freq_max_hz.max(view.recon_freq_min_hz);
+        view.recon_norm_floor = self.recon_norm_floor;
+        view.recon_griffin_lim_iterations = self.griffin_lim_iterations;
+    }
+
+    /// Apply the parts that depend on the loaded file: the analyzed range
+    /// and the zoom, clamped to the file's length and Nyquist in case the
+    /// file changed since the session was saved.
+    pub fn apply_to_loaded_audio(
+        &self,
+        params: &mut FftParams,
+        view: &mut ViewState,
+        num_samples: usize,
+        nyquist: f32,
+    ) {
+        let stop = if self.stop_sample == 0 {
+            num_samples
+        } else {
+            self.stop_sample.min(num_samples)
+        };
+        if self.start_sample < stop {
+            params.start_sample = self.start_sample;
+            params.stop_sample = stop;
+        }
+
+        let duration = num_samples as f64 / params.sample_rate.max(1) as f64;
+        let time_max = self.time_max_sec.min(duration);
+        if self.time_min_sec.max(0.0) < time_max {
+            view.time_min_sec = self.time_min_sec.max(0.0);
+            view.time_max_sec = time_max;
+        }
+        let freq_max = self.freq_max_hz.min(nyquist);
+        if self.freq_min_hz < freq_max {
+            view.freq_min_hz = self.freq_min_hz.max(1.0);
+            view.freq_max_hz = freq_max;
+        }
+        view.recon_freq_max_hz = view.recon_freq_max_hz.min(nyquist);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_session() -> Session {
+        Session {
+            audio_path: PathBuf::from("/music/take.wav"),
+            window_length: 4096,
+            overlap_percent: 87.5,
+            window_type: "Kaiser".to_string(),
+            kaiser_beta: 5.0,
+            zero_pad_factor: 2,
+            start_sample: 48000,
+            stop_sample: 96000,
+            freq_min_hz: 200.0,
+            freq_max_hz: 4000.0,
+            time_min_sec: 1.0,
+            time_max_sec: 1.5,
+            colormap: "Magma".to_string(),
+            threshold_db: -70.0,
+            griffin_lim_iterations: 32,
+            markers: vec![
+                Marker {
+                    name: "Chorus".to_string(),
+                    time_seconds: 1.25,
+                    freq_hz: None,
+                },
+                Marker {
+                    name: "Click".to_string(),
+                    time_seconds: 1.4,
+                    freq_hz: Some(3150.0),
+                },
+            ],
+            ..Session::default()
+        }
+    }
+
+    #[test]
+    fn session_roundtrips_through_json_and_fills_missing_fields() {
+        let session = sample_session();
+        let json = serde_json::to_string(&session).unwrap();
+        let restored: Session = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, session);
+
+        let partial: Session =
+            serde_json::from_str(r#"{"audio_path": "a.wav", "window_length": 2048}"#).unwrap();
+        assert_eq!(partial.window_length, 2048);
+        assert_eq!(partial.overlap_percent, Session::default().overlap_percent);
+        assert_eq!(partial.custom_gradient, default_custom_gradient());
+        assert!(partial.markers.is_empty());
+    }
+
+    #[test]
+    fn apply_restores_settings_and_clamps_to_the_loaded_file() {
+        let session = sample_session();
+        let mut params = FftParams {
+            sample_rate: 48000,
+            ..FftParams::default()
+        };
+        let mut view = ViewState::default();
+        session.apply_settings(&mut params, &mut view);
+        assert_eq!(params.window_length, 4096);
+        assert_eq!(params.window_type, WindowType::Kaiser(5.0));
+        assert_eq!(params.zero_pad_factor, 2);
+        assert_eq!(view.colormap, ColormapId::Magma);
+        assert_eq!(view.threshold_db, -70.0);
+        assert_eq!(view.recon_griffin_lim_iterations, 32);
+
+        // The file got shorter and its rate lower since the session was saved
+        session.apply_to_loaded_audio(&mut params, &mut view, 72000, 3000.0);
+        assert_eq!((params.start_sample, params.stop_sample), (48000, 72000));
+        assert_eq!((view.time_min_sec, view.time_max_sec), (1.0, 1.5));
+        assert_eq!((view.freq_min_hz, view.freq_max_hz), (200.0, 3000.0));
+        assert_eq!(view.recon_freq_max_hz, 3000.0);
+
+        // A range past the end of the file leaves the whole-file defaults
+        params.start_sample = 0;
+        params.stop_sample = 24000;
+        session.apply_to_loaded_audio(&mut params, &mut view, 24000, 3000.0);
+        assert_eq!((params.start_sample, params.stop_sample), (0, 24000));
+    }
+}
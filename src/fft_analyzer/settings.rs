@@ -29,6 +29,12 @@ pub struct Settings {
     pub view_freq_min_hz: f32,
     pub view_freq_max_hz: f32,
     pub freq_scale_power: f32, // 0.0 = linear, 1.0 = log, anything in between
+    pub mel_view: bool,        // Display > Mel Scale
+    pub mel_bands: usize,
+    pub show_partials: bool,   // Display > Show Partials
+    pub show_note_ruler: bool, // Display > Note Ruler
+    pub show_beat_grid: bool,  // Display > Beat Grid
+    pub show_colorbar: bool,   // Display > dB Colorbar
 
     // ── View: Display ──
     pub colormap: String, // "Classic", "Viridis", etc.
@@ -42,10 +48,13 @@ pub struct Settings {
     pub recon_freq_max_hz: f32,
     pub recon_freq_count: usize,
     pub recon_norm_floor: f64,
+    pub griffin_lim_iterations: usize, // 0 = use the stored phases
 
     // ── Audio ──
     pub normalize_audio: bool,
-    pub normalize_peak: f32, // 0.97 = 97% of max
+    pub normalize_peak: f32,   // 0.97 = 97% of max
+    pub channel_mode: String,  // "L+R", "L", "R", "Mid", "Side"
+    pub load_sample_rate: u32, // 0 = keep each file's own rate
 
     // ── Zoom ──
     pub time_zoom_factor: f32, // multiplier per click, e.g. 1.5
@@ -66,6 +75,20 @@ pub struct Settings {
     // ── Waveform ──
     pub waveform_height: i32,
 
+    // ── Image Export ──
+    pub image_export_width: i32, // File > Export Image size, in pixels
+    pub image_export_height: i32,
+
+    // ── Transcription ──
+    pub transcription_bpm: f32, // File > Export to Tracker CSV tempo
+
+    // ── Spectral Brush ──
+    pub brush_radius_px: i32, // Edit > Brush Size
+    pub brush_gain_db: f32,   // Edit > Brush Gain (-100 or lower erases)
+
+    // ── Noise Reduction ──
+    pub noise_reduction_db: f32, // Noise Reduction slider (0 = off)
+
     // ── Tooltips ──
     pub show_tooltips: bool,
     pub lock_to_active: bool,
@@ -77,6 +100,8 @@ pub struct Settings {
     // ── Custom Gradient ──
     /// Serialized as "pos:r:g:b|pos:r:g:b|..." (all floats 0..1)
     pub custom_gradient: String,
+    /// Named gradients from the gradient editor's Save button
+    pub gradient_presets: Vec<GradientPreset>,
 
     // ── Colors (hex) ──
     pub color_background: u32,
@@ -116,6 +141,12 @@ impl Default for Settings {
             view_freq_min_hz: 100.0,
             view_freq_max_hz: 2000.0,
             freq_scale_power: 0.5, // halfway between linear and log
+            mel_view: false,
+            mel_bands: 128,
+            show_partials: false,
+            show_note_ruler: false,
+            show_beat_grid: false,
+            show_colorbar: false,
 
             // View: Display
             colormap: "Classic".to_string(),
@@ -129,10 +160,13 @@ impl Default for Settings {
             recon_freq_max_hz: 5000.0,
             recon_freq_count: 4097,
             recon_norm_floor: 1e-6,
+            griffin_lim_iterations: 0,
 
             // Audio
             normalize_audio: true,
             normalize_peak: 0.97,
+            channel_mode: "L+R".to_string(),
+            load_sample_rate: 0,
 
             // Zoom
             time_zoom_factor: 1.5,
@@ -153,6 +187,20 @@ impl Default for Settings {
             // Waveform
             waveform_height: 100,
 
+            // Image Export
+            image_export_width: 1920,
+            image_export_height: 1080,
+
+            // Transcription
+            transcription_bpm: 120.0,
+
+            // Spectral Brush
+            brush_radius_px: 12,
+            brush_gain_db: ERASE_GAIN_DB,
+
+            // Noise Reduction
+            noise_reduction_db: 12.0,
+
             // Tooltips
             show_tooltips: true,
             lock_to_active: false,
@@ -163,6 +211,7 @@ impl Default for Settings {
 
             // Custom Gradient (default: SebLague classic)
             custom_gradient: String::new(),
+            gradient_presets: Vec::new(),
 
             // Colors
             color_background: 0x1e1e2e,
@@ -179,7 +228,13 @@ impl Default for Settings {
 }
 
 use crate::app_state::AppState;
-use crate::data::{FreqScale, GradientStop, default_custom_gradient};
+use crate::data::{
+    ChannelMode, FreqScale, GradientPreset, GradientStop, LOAD_SAMPLE_RATES, MAX_MEL_BANDS,
+    MIN_MEL_BANDS, default_custom_gradient,
+};
+use crate::processing::noise_reduction::MAX_NOISE_REDUCTION_DB;
+use crate::processing::reconstructor::MAX_GRIFFIN_LIM_ITERATIONS;
+use crate::processing::spectral_edit::{ERASE_GAIN_DB, MAX_BRUSH_RADIUS_PX, MIN_BRUSH_RADIUS_PX};
 
 #[allow(dead_code)]
 impl Settings {
@@ -264,6 +319,12 @@ impl Settings {
         // View
         cfg.view_freq_min_hz = st.view.freq_min_hz;
         cfg.view_freq_max_hz = st.view.freq_max_hz;
+        cfg.mel_view = st.view.mel_view;
+        cfg.mel_bands = st.view.mel_bands;
+        cfg.show_partials = st.show_partials;
+        cfg.show_note_ruler = st.show_note_ruler;
+        cfg.show_beat_grid = st.show_beat_grid;
+        cfg.show_colorbar = st.show_colorbar;
         cfg.freq_scale_power = match st.view.freq_scale {
             FreqScale::Linear => 0.0,
             FreqScale::Log => 1.0,
@@ -282,10 +343,13 @@ impl Settings {
         cfg.recon_freq_max_hz = st.view.recon_freq_max_hz;
         cfg.recon_freq_count = st.view.recon_freq_count;
         cfg.recon_norm_floor = st.view.recon_norm_floor;
+        cfg.griffin_lim_iterations = st.view.recon_griffin_lim_iterations;
 
         // Audio
         cfg.normalize_audio = st.normalize_audio;
         cfg.normalize_peak = st.normalize_peak;
+        cfg.channel_mode = st.channel_mode.name().to_string();
+        cfg.load_sample_rate = st.load_sample_rate;
 
         // Zoom
         cfg.time_zoom_factor = st.time_zoom_factor;
@@ -293,12 +357,27 @@ impl Settings {
         cfg.mouse_zoom_factor = st.mouse_zoom_factor;
         cfg.swap_zoom_axes = st.swap_zoom_axes;
 
+        // Image Export
+        cfg.image_export_width = st.image_export_width;
+        cfg.image_export_height = st.image_export_height;
+
+        // Transcription
+        cfg.transcription_bpm = st.transcription_bpm;
+
+        // Spectral Brush
+        cfg.brush_radius_px = st.brush_radius_px;
+        cfg.brush_gain_db = st.brush_gain_db;
+
+        // Noise Reduction
+        cfg.noise_reduction_db = st.noise_reduction_db;
+
         // UI
         cfg.lock_to_active = st.lock_to_active;
         cfg.render_full_file_outside_roi = st.render_full_file_outside_roi;
 
         // Custom Gradient
         cfg.custom_gradient = serialize_gradient(&st.view.custom_gradient);
+        cfg.gradient_presets = st.gradient_presets.clone();
 
         cfg
     }
@@ -370,6 +449,21 @@ impl Settings {
         s.push_str(&format!("view_freq_max_hz = {}\n", self.view_freq_max_hz));
         s.push_str("# freq_scale_power: 0.0 = linear, 1.0 = full log, 0.5 = halfway\n");
         s.push_str(&format!("freq_scale_power = {}\n", self.freq_scale_power));
+        s.push_str("# mel_view: true = mel-spaced axis with mel band pooling\n");
+        s.push_str(&format!("mel_view = {}\n", self.mel_view));
+        s.push_str(&format!(
+            "# mel_bands: {} to {}\n",
+            MIN_MEL_BANDS, MAX_MEL_BANDS
+        ));
+        s.push_str(&format!("mel_bands = {}\n", self.mel_bands));
+        s.push_str("# show_partials: true = draw tracked spectral peaks as ridges\n");
+        s.push_str(&format!("show_partials = {}\n", self.show_partials));
+        s.push_str("# show_note_ruler: true = note names, piano keys and pitch lines\n");
+        s.push_str(&format!("show_note_ruler = {}\n", self.show_note_ruler));
+        s.push_str("# show_beat_grid: true = beat and bar lines at the detected tempo\n");
+        s.push_str(&format!("show_beat_grid = {}\n", self.show_beat_grid));
+        s.push_str("# show_colorbar: true = dB legend for the colormap on the spectrogram\n");
+        s.push_str(&format!("show_colorbar = {}\n", self.show_colorbar));
         s.push('\n');
 
         s.push_str("[Display]\n");
@@ -388,12 +482,21 @@ impl Settings {
         s.push_str(&format!("recon_freq_max_hz = {}\n", self.recon_freq_max_hz));
         s.push_str(&format!("recon_freq_count = {}\n", self.recon_freq_count));
         s.push_str(&format!("recon_norm_floor = {:e}\n", self.recon_norm_floor));
+        s.push_str("# griffin_lim_iterations: phase estimation passes, 0 = use stored phases\n");
+        s.push_str(&format!(
+            "griffin_lim_iterations = {}\n",
+            self.griffin_lim_iterations
+        ));
         s.push('\n');
 
         s.push_str("[Audio]\n");
         s.push_str(&format!("normalize_audio = {}\n", self.normalize_audio));
         s.push_str("# normalize_peak: fraction of max (0.97 = 97%)\n");
         s.push_str(&format!("normalize_peak = {}\n", self.normalize_peak));
+        s.push_str("# channel_mode: L+R (mono mix), L, R, Mid, Side\n");
+        s.push_str(&format!("channel_mode = {}\n", self.channel_mode));
+        s.push_str("# load_sample_rate: 0 (keep the file rate), 44100, 48000, 96000\n");
+        s.push_str(&format!("load_sample_rate = {}\n", self.load_sample_rate));
         s.push('\n');
 
         s.push_str("[Zoom]\n");
@@ -423,6 +526,37 @@ impl Settings {
         s.push_str(&format!("waveform_height = {}\n", self.waveform_height));
         s.push('\n');
 
+        s.push_str("[ImageExport]\n");
+        s.push_str("# Default size (pixels) offered by File > Export Image\n");
+        s.push_str(&format!(
+            "image_export_width = {}\n",
+            self.image_export_width
+        ));
+        s.push_str(&format!(
+            "image_export_height = {}\n",
+            self.image_export_height
+        ));
+        s.push('\n');
+
+        s.push_str("[Transcription]\n");
+        s.push_str("# Tempo (BPM) offered by File > Export to Tracker CSV\n");
+        s.push_str(&format!("transcription_bpm = {}\n", self.transcription_bpm));
+        s.push('\n');
+
+        s.push_str("[SpectralBrush]\n");
+        s.push_str("# Brush mouse mode: radius in pixels, gain in dB (-100 or lower erases)\n");
+        s.push_str(&format!("brush_radius_px = {}\n", self.brush_radius_px));
+        s.push_str(&format!("brush_gain_db = {}\n", self.brush_gain_db));
+        s.push('\n');
+
+        s.push_str("[NoiseReduction]\n");
+        s.push_str("# dB a captured noise profile is turned down by (0 = off, max 40)\n");
+        s.push_str(&format!(
+            "noise_reduction_db = {}\n",
+            self.noise_reduction_db
+        ));
+        s.push('\n');
+
         s.push_str("[UI]\n");
         s.push_str(&format!("show_tooltips = {}\n", self.show_tooltips));
         s.push_str(&format!("lock_to_active = {}\n", self.lock_to_active));
@@ -443,6 +577,20 @@ impl Settings {
             s.push('\n');
         }
 
+        if !self.gradient_presets.is_empty() {
+            s.push_str("[GradientPresets]\n");
+            s.push_str("# Format: name|pos:r:g:b|pos:r:g:b|... (floats 0-1)\n");
+            for (i, preset) in self.gradient_presets.iter().enumerate() {
+                s.push_str(&format!(
+                    "gradient_preset_{} = {}|{}\n",
+                    i + 1,
+                    preset.name,
+                    serialize_gradient(&preset.stops)
+                ));
+            }
+            s.push('\n');
+        }
+
         s.push_str("[Colors]\n");
         s.push_str("# Colors are in hex (0xRRGGBB)\n");
         s.push_str(&format!(
@@ -556,6 +704,26 @@ impl Settings {
         {
             self.freq_scale_power = n;
         }
+        if let Some(v) = map.get("mel_view") {
+            self.mel_view = v == "true";
+        }
+        if let Some(v) = map.get("mel_bands")
+            && let Ok(n) = v.parse::<usize>()
+        {
+            self.mel_bands = n.clamp(MIN_MEL_BANDS, MAX_MEL_BANDS);
+        }
+        if let Some(v) = map.get("show_partials") {
+            self.show_partials = v == "true";
+        }
+        if let Some(v) = map.get("show_note_ruler") {
+            self.show_note_ruler = v == "true";
+        }
+        if let Some(v) = map.get("show_beat_grid") {
+            self.show_beat_grid = v == "true";
+        }
+        if let Some(v) = map.get("show_colorbar") {
+            self.show_colorbar = v == "true";
+        }
 
         // Display
         if let Some(v) = map.get("colormap") {
@@ -603,6 +771,11 @@ impl Settings {
         {
             self.recon_norm_floor = n.clamp(1e-30, 1e-4);
         }
+        if let Some(v) = map.get("griffin_lim_iterations")
+            && let Ok(n) = v.parse::<usize>()
+        {
+            self.griffin_lim_iterations = n.min(MAX_GRIFFIN_LIM_ITERATIONS);
+        }
 
         // Audio
         if let Some(v) = map.get("normalize_audio") {
@@ -613,6 +786,17 @@ impl Settings {
         {
             self.normalize_peak = n;
         }
+        if let Some(v) = map.get("channel_mode")
+            && let Some(mode) = ChannelMode::from_name(v)
+        {
+            self.channel_mode = mode.name().to_string();
+        }
+        if let Some(v) = map.get("load_sample_rate")
+            && let Ok(n) = v.parse()
+            && LOAD_SAMPLE_RATES.contains(&n)
+        {
+            self.load_sample_rate = n;
+        }
 
         // Zoom
         if let Some(v) = map.get("time_zoom_factor")
@@ -678,6 +862,44 @@ impl Settings {
             self.waveform_height = n;
         }
 
+        // Image Export
+        if let Some(v) = map.get("image_export_width")
+            && let Ok(n) = v.parse()
+        {
+            self.image_export_width = n;
+        }
+        if let Some(v) = map.get("image_export_height")
+            && let Ok(n) = v.parse()
+        {
+            self.image_export_height = n;
+        }
+
+        // Transcription
+        if let Some(v) = map.get("transcription_bpm")
+            && let Ok(n) = v.parse::<f32>()
+        {
+            self.transcription_bpm = n.clamp(20.0, 999.0);
+        }
+
+        // Spectral Brush
+        if let Some(v) = map.get("brush_radius_px")
+            && let Ok(n) = v.parse::<i32>()
+        {
+            self.brush_radius_px = n.clamp(MIN_BRUSH_RADIUS_PX, MAX_BRUSH_RADIUS_PX);
+        }
+        if let Some(v) = map.get("brush_gain_db")
+            && let Ok(n) = v.parse::<f32>()
+        {
+            self.brush_gain_db = n.clamp(ERASE_GAIN_DB, 0.0);
+        }
+
+        // Noise Reduction
+        if let Some(v) = map.get("noise_reduction_db")
+            && let Ok(n) = v.parse::<f32>()
+        {
+            self.noise_reduction_db = n.clamp(0.0, MAX_NOISE_REDUCTION_DB);
+        }
+
         // UI
         if let Some(v) = map.get("show_tooltips") {
             self.show_tooltips = v == "true";
@@ -696,6 +918,17 @@ impl Settings {
         if let Some(v) = map.get("custom_gradient") {
             self.custom_gradient = v.clone();
         }
+        let mut presets: Vec<(usize, GradientPreset)> = map
+            .iter()
+            .filter_map(|(key, v)| {
+                let number = key.strip_prefix("gradient_preset_")?.parse().ok()?;
+                Some((number, parse_gradient_preset(v)?))
+            })
+            .collect();
+        if !presets.is_empty() {
+            presets.sort_by_key(|(number, _)| *number);
+            self.gradient_presets = presets.into_iter().map(|(_, preset)| preset).collect();
+        }
 
         // Colors
         if let Some(v) = map.get("color_background")
@@ -776,6 +1009,14 @@ impl Settings {
     pub fn parse_custom_gradient(&self) -> Vec<GradientStop> {
         deserialize_gradient(&self.custom_gradient)
     }
+
+    /// Write `presets` to the settings file right away, leaving the other
+    /// saved settings as they are (they change only with Save as Default).
+    pub fn save_gradient_presets(presets: &[GradientPreset]) {
+        let mut cfg = Self::load_or_create();
+        cfg.gradient_presets = presets.to_vec();
+        cfg.save();
+    }
 }
 
 /// Parse INI content into a flat key-value map (section headers are ignored,
@@ -811,11 +1052,31 @@ fn serialize_gradient(stops: &[GradientStop]) -> String {
         .join("|")
 }
 
+/// Parse a saved preset, "name|pos:r:g:b|...". None if the name is empty or
+/// fewer than two stops parse.
+fn parse_gradient_preset(s: &str) -> Option<GradientPreset> {
+    let (name, stops) = s.split_once('|')?;
+    let stops = parse_gradient_stops(stops);
+    if name.trim().is_empty() || stops.len() < 2 {
+        return None;
+    }
+    Some(GradientPreset {
+        name: name.trim().to_string(),
+        stops,
+    })
+}
+
 /// Deserialize gradient stops from string. Returns default gradient on failure.
 fn deserialize_gradient(s: &str) -> Vec<GradientStop> {
-    if s.trim().is_empty() {
+    let stops = parse_gradient_stops(s);
+    if stops.len() < 2 {
         return default_custom_gradient();
     }
+    stops
+}
+
+/// The "pos:r:g:b" stops of a "|"-separated list that parse, sorted by position.
+fn parse_gradient_stops(s: &str) -> Vec<GradientStop> {
     let mut stops = Vec::new();
     for part in s.split('|') {
         let vals: Vec<f32> = part
@@ -826,9 +1087,6 @@ fn deserialize_gradient(s: &str) -> Vec<GradientStop> {
             stops.push(GradientStop::new(vals[0], vals[1], vals[2], vals[3]));
         }
     }
-    if stops.len() < 2 {
-        return default_custom_gradient();
-    }
     stops.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap());
     stops
 }
@@ -878,4 +1136,162 @@ zero_pad_factor = 1
         assert_eq!(restored.target_bins_per_segment, 999);
         assert_eq!(restored.last_edited_field, "BinsPerSegment");
     }
+
+    #[test]
+    fn image_export_size_roundtrips_in_ini_text() {
+        let mut settings = Settings::default();
+        settings.image_export_width = 3840;
+        settings.image_export_height = 2160;
+
+        let mut restored = Settings::default();
+        restored.parse_ini(&settings.to_ini());
+
+        assert_eq!(restored.image_export_width, 3840);
+        assert_eq!(restored.image_export_height, 2160);
+    }
+
+    #[test]
+    fn transcription_bpm_roundtrips_and_clamps() {
+        let mut settings = Settings::default();
+        settings.transcription_bpm = 96.5;
+
+        let mut restored = Settings::default();
+        restored.parse_ini(&settings.to_ini());
+        assert_eq!(restored.transcription_bpm, 96.5);
+
+        restored.parse_ini("[Transcription]\ntranscription_bpm = 5\n");
+        assert_eq!(restored.transcription_bpm, 20.0);
+    }
+
+    #[test]
+    fn brush_settings_roundtrip_and_clamp() {
+        let mut settings = Settings::default();
+        settings.brush_radius_px = 30;
+        settings.brush_gain_db = -12.5;
+
+        let mut restored = Settings::default();
+        restored.parse_ini(&settings.to_ini());
+        assert_eq!(restored.brush_radius_px, 30);
+        assert_eq!(restored.brush_gain_db, -12.5);
+
+        restored.parse_ini("[SpectralBrush]\nbrush_radius_px = 5000\nbrush_gain_db = -400\n");
+        assert_eq!(restored.brush_radius_px, MAX_BRUSH_RADIUS_PX);
+        assert_eq!(restored.brush_gain_db, ERASE_GAIN_DB);
+    }
+
+    #[test]
+    fn noise_reduction_roundtrips_and_clamps() {
+        let mut settings = Settings::default();
+        settings.noise_reduction_db = 18.0;
+
+        let mut restored = Settings::default();
+        restored.parse_ini(&settings.to_ini());
+        assert_eq!(restored.noise_reduction_db, 18.0);
+
+        restored.parse_ini("[NoiseReduction]\nnoise_reduction_db = 90\n");
+        assert_eq!(restored.noise_reduction_db, MAX_NOISE_REDUCTION_DB);
+        restored.parse_ini("[NoiseReduction]\nnoise_reduction_db = -3\n");
+        assert_eq!(restored.noise_reduction_db, 0.0);
+    }
+
+    #[test]
+    fn mel_view_roundtrips_and_clamps_band_count() {
+        let mut settings = Settings::default();
+        settings.mel_view = true;
+        settings.mel_bands = 64;
+        settings.show_partials = true;
+        settings.show_note_ruler = true;
+        settings.show_beat_grid = true;
+        settings.show_colorbar = true;
+
+        let mut restored = Settings::default();
+        restored.parse_ini(&settings.to_ini());
+        assert!(restored.mel_view);
+        assert!(restored.show_partials);
+        assert!(restored.show_note_ruler);
+        assert!(restored.show_beat_grid);
+        assert!(restored.show_colorbar);
+        assert_eq!(restored.mel_bands, 64);
+
+        restored.parse_ini("[View]\nmel_bands = 100000\n");
+        assert_eq!(restored.mel_bands, MAX_MEL_BANDS);
+    }
+
+    #[test]
+    fn griffin_lim_iterations_roundtrip_and_clamp() {
+        let mut settings = Settings::default();
+        settings.griffin_lim_iterations = 64;
+
+        let mut restored = Settings::default();
+        restored.parse_ini(&settings.to_ini());
+        assert_eq!(restored.griffin_lim_iterations, 64);
+
+        restored.parse_ini("[Reconstruction]\ngriffin_lim_iterations = 99999\n");
+        assert_eq!(restored.griffin_lim_iterations, MAX_GRIFFIN_LIM_ITERATIONS);
+    }
+
+    #[test]
+    fn channel_mode_roundtrips_and_ignores_unknown_names() {
+        let mut settings = Settings::default();
+        settings.channel_mode = "Side".to_string();
+
+        let mut restored = Settings::default();
+        restored.parse_ini(&settings.to_ini());
+        assert_eq!(restored.channel_mode, "Side");
+
+        restored.parse_ini("[Audio]\nchannel_mode = surround\n");
+        assert_eq!(restored.channel_mode, "Side");
+    }
+
+    #[test]
+    fn load_sample_rate_roundtrips_and_ignores_unlisted_rates() {
+        let mut settings = Settings::default();
+        settings.load_sample_rate = 48000;
+
+        let mut restored = Settings::default();
+        restored.parse_ini(&settings.to_ini());
+        assert_eq!(restored.load_sample_rate, 48000);
+
+        restored.parse_ini("[Audio]\nload_sample_rate = 12345\n");
+        assert_eq!(restored.load_sample_rate, 48000);
+        restored.parse_ini("[Audio]\nload_sample_rate = 0\n");
+        assert_eq!(restored.load_sample_rate, 0);
+    }
+
+    #[test]
+    fn gradient_presets_roundtrip_in_order_and_replace_by_name() {
+        use crate::data::save_gradient_preset;
+
+        let preset = |name: &str, top: f32| GradientPreset {
+            name: name.to_string(),
+            stops: vec![
+                GradientStop::new(0.0, 0.0, 0.0, 0.0),
+                GradientStop::new(1.0, top, 0.5, 0.25),
+            ],
+        };
+        let mut settings = Settings::default();
+        save_gradient_preset(&mut settings.gradient_presets, preset("Sunset", 1.0));
+        save_gradient_preset(&mut settings.gradient_presets, preset("Deep Sea", 0.0));
+        let index = save_gradient_preset(&mut settings.gradient_presets, preset("sunset", 0.5));
+        assert_eq!(index, 0);
+        assert_eq!(settings.gradient_presets.len(), 2);
+
+        let mut restored = Settings::default();
+        restored.parse_ini(&settings.to_ini());
+        assert_eq!(restored.gradient_presets, settings.gradient_presets);
+
+        let mut restored = Settings::default();
+        restored.parse_ini(
+            "[GradientPresets]\ngradient_preset_2 = Two|0:0:0:0|1:1:1:1\n\
+             gradient_preset_1 = One|1:1:1:1|0:0:0:0\n\
+             gradient_preset_3 = Broken|0:0:0:0\n",
+        );
+        let names: Vec<&str> = restored
+            .gradient_presets
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(names, ["One", "Two"]);
+        assert_eq!(restored.gradient_presets[0].stops[0].position, 0.0);
+    }
 }
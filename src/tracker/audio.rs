@@ -5,6 +5,9 @@
 // This module handles audio output, including:
 // - Setting up the audio device for real-time playback
 // - Exporting rendered audio to WAV files
+// - Reading sound files back in (the grain instrument's source sounds,
+//   and the FFT analyzer's File > Open): WAV, MP3, FLAC, and Ogg Vorbis
+// - Converting audio from one sample rate to another
 //
 // WAV EXPORT:
 // When enabled, the engine renders the entire song to a buffer first,
@@ -18,9 +21,19 @@
 // pulls samples from the playback engine in real-time.
 // ============================================================================
 
+use crate::helper::RandomNumberGenerator;
+use rayon::prelude::*;
+use std::f64::consts::PI;
 use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::{BufWriter, ErrorKind, Write};
 use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{CODEC_TYPE_NULL, DecoderOptions};
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
 
 // ============================================================================
 // WAV FILE FORMAT
@@ -42,6 +55,32 @@ use std::path::Path;
 const WAV_FORMAT_PCM: u16 = 1; // Standard PCM
 const WAV_FORMAT_IEEE_FLOAT: u16 = 3; // 32-bit float
 
+/// Seed for the dither noise (fixed, so the same song exports the same file)
+const DITHER_SEED: u32 = 0x5EED_D17E;
+
+/// File extensions read_audio_file understands (lowercase)
+pub const AUDIO_FILE_EXTENSIONS: [&str; 4] = ["wav", "mp3", "flac", "ogg"];
+
+/// Zero crossings of the resampling filter on each side of a sample
+/// (more: a sharper cutoff at the new Nyquist, but slower)
+const RESAMPLE_ZERO_CROSSINGS: usize = 32;
+
+// ============================================================================
+// DITHER
+// ============================================================================
+//
+// Rounding to 16 bits throws away everything below one step (1/32767).
+// On loud material that error is random and sounds like faint hiss, but on
+// quiet fades and reverb tails it follows the music and turns into gritty
+// distortion, and the very end of a tail drops to silence in steps.
+//
+// TPDF (triangular) dither adds a tiny bit of noise before rounding: the
+// sum of two random values, each up to half a step, so between -1 and +1
+// step in all. The error is then plain, steady hiss no matter what is
+// playing, and tails fade smoothly into it. Only 16-bit output needs it;
+// 32-bit float keeps every detail.
+// ============================================================================
+
 /// Writes audio data to a WAV file
 ///
 /// Parameters:
@@ -49,6 +88,7 @@ const WAV_FORMAT_IEEE_FLOAT: u16 = 3; // 32-bit float
 /// - samples: Interleaved stereo samples (L R L R ...) in -1.0 to 1.0 range
 /// - sample_rate: Sample rate in Hz
 /// - use_float: If true, writes 32-bit float WAV. If false, writes 16-bit PCM.
+/// - dither: If true, adds TPDF dither before rounding to 16 bits (ignored for float)
 ///
 /// Returns: Ok(()) on success, Err with message on failure
 pub fn write_wav_file(
@@ -56,6 +96,7 @@ pub fn write_wav_file(
     samples: &[f32],
     sample_rate: u32,
     use_float: bool,
+    dither: bool,
 ) -> Result<(), String> {
     // Validate input
     if samples.is_empty() {
@@ -169,10 +210,16 @@ pub fn write_wav_file(
         }
     } else {
         // Convert to 16-bit PCM
+        let mut random = RandomNumberGenerator::new(DITHER_SEED);
         for &sample in samples {
             // Clamp and scale to i16 range
             let clamped = sample.clamp(-1.0, 1.0);
-            let scaled = (clamped * 32767.0) as i16;
+            let scaled = if dither {
+                let noise = random.next_float_0_to_1() - random.next_float_0_to_1();
+                (clamped * 32767.0 + noise).round().clamp(-32768.0, 32767.0) as i16
+            } else {
+                (clamped * 32767.0) as i16
+            };
             writer
                 .write_all(&scaled.to_le_bytes())
                 .map_err(|e| format!("Write error: {}", e))?;
@@ -185,6 +232,233 @@ pub fn write_wav_file(
     Ok(())
 }
 
+/// Audio read from a file: one Vec of samples per channel
+#[derive(Clone, Debug, Default)]
+pub struct DecodedAudio {
+    pub channels: Vec<Vec<f32>>,
+    pub sample_rate: u32,
+}
+
+impl DecodedAudio {
+    /// The channels averaged into one
+    pub fn mono(&self) -> Vec<f32> {
+        let channel_count = self.channels.len().max(1) as f32;
+        let length = self.channels.iter().map(Vec::len).min().unwrap_or(0);
+        (0..length)
+            .map(|index| {
+                self.channels
+                    .iter()
+                    .map(|channel| channel[index])
+                    .sum::<f32>()
+                    / channel_count
+            })
+            .collect()
+    }
+
+    /// Converts every channel to another sample rate (see resample)
+    pub fn resample(&mut self, sample_rate: u32) {
+        if sample_rate == 0 || sample_rate == self.sample_rate {
+            return;
+        }
+        for channel in &mut self.channels {
+            *channel = resample(channel, self.sample_rate, sample_rate);
+        }
+        self.sample_rate = sample_rate;
+    }
+}
+
+/// Reads a WAV file as mono samples
+///
+/// Any channel count, 8-32 bit PCM, or 32-bit float. The channels are
+/// averaged into one, and samples are scaled to -1.0 to 1.0.
+///
+/// Returns: (samples, sample_rate), or Err with message on failure
+pub fn read_wav_file(path: &Path) -> Result<(Vec<f32>, u32), String> {
+    let audio = decode_wav_file(path)?;
+    Ok((audio.mono(), audio.sample_rate))
+}
+
+/// Reads a WAV, MP3, FLAC, or Ogg Vorbis file as mono samples
+/// (see AUDIO_FILE_EXTENSIONS), at the file's own sample rate
+///
+/// Returns: (samples, sample_rate), or Err with message on failure
+pub fn read_audio_file(path: &Path) -> Result<(Vec<f32>, u32), String> {
+    let audio = decode_audio_file(path)?;
+    Ok((audio.mono(), audio.sample_rate))
+}
+
+/// Reads every channel of a sound file
+/// WAV files are read with hound; anything else goes to symphonia, which
+/// finds the format from the file's contents (the extension is a hint).
+pub fn decode_audio_file(path: &Path) -> Result<DecodedAudio, String> {
+    let is_wav = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("wav"));
+    let audio = if is_wav {
+        decode_wav_file(path)?
+    } else {
+        decode_compressed_file(path)?
+    };
+    if audio.sample_rate == 0 {
+        return Err(format!("'{}' has a sample rate of 0", path.display()));
+    }
+    Ok(audio)
+}
+
+/// Reads a WAV file's channels with hound
+fn decode_wav_file(path: &Path) -> Result<DecodedAudio, String> {
+    let mut reader = hound::WavReader::open(path)
+        .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+    let spec = reader.spec();
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+        hound::SampleFormat::Int => {
+            let full_scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|value| value as f32 / full_scale))
+                .collect()
+        }
+    }
+    .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+
+    Ok(DecodedAudio {
+        channels: deinterleave(&interleaved, usize::from(spec.channels.max(1))),
+        sample_rate: spec.sample_rate,
+    })
+}
+
+/// Reads a compressed file's first audio track with symphonia
+/// Damaged packets are skipped, like a player would.
+fn decode_compressed_file(path: &Path) -> Result<DecodedAudio, String> {
+    let failed =
+        |error: &dyn std::fmt::Display| format!("Failed to read '{}': {}", path.display(), error);
+    let file = File::open(path).map_err(|e| failed(&e))?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+        hint.with_extension(extension);
+    }
+    let mut format = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| failed(&e))?
+        .format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| failed(&"no audio track"))?;
+    let track_id = track.id;
+    let mut audio = DecodedAudio {
+        channels: Vec::new(),
+        sample_rate: track.codec_params.sample_rate.unwrap_or(0),
+    };
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| failed(&e))?;
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            // The end of the file
+            Err(DecodeError::IoError(error)) if error.kind() == ErrorKind::UnexpectedEof => break,
+            Err(error) => return Err(failed(&error)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(DecodeError::DecodeError(_)) => continue,
+            Err(error) => return Err(failed(&error)),
+        };
+
+        let spec = *decoded.spec();
+        let channel_count = spec.channels.count().max(1);
+        audio.sample_rate = spec.rate;
+        if audio.channels.len() < channel_count {
+            audio.channels.resize(channel_count, Vec::new());
+        }
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        for frame in buffer.samples().chunks_exact(channel_count) {
+            for (channel, &sample) in audio.channels.iter_mut().zip(frame) {
+                channel.push(sample);
+            }
+        }
+    }
+
+    if audio.channels.iter().all(Vec::is_empty) {
+        return Err(failed(&"no audio could be decoded"));
+    }
+    Ok(audio)
+}
+
+/// Splits interleaved samples into one Vec per channel
+fn deinterleave(interleaved: &[f32], channel_count: usize) -> Vec<Vec<f32>> {
+    (0..channel_count)
+        .map(|channel| {
+            interleaved
+                .iter()
+                .skip(channel)
+                .step_by(channel_count)
+                .copied()
+                .collect()
+        })
+        .collect()
+}
+
+// ============================================================================
+// SAMPLE RATE CONVERSION
+// ============================================================================
+//
+// Each new sample is read between the old ones with a windowed sinc
+// filter: the ideal band-limited interpolation, cut off after
+// RESAMPLE_ZERO_CROSSINGS zero crossings on each side and faded to zero
+// there (Blackman window) so the cut doesn't ring. Going down in rate, the
+// filter is stretched so its cutoff sits at the new Nyquist frequency and
+// anything above it is filtered out instead of folding back down.
+// ============================================================================
+
+/// Converts mono samples from one sample rate to another
+/// The length scales with the rates (1 second stays 1 second).
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || from_rate == 0 || to_rate == 0 || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let step = from_rate as f64 / to_rate as f64;
+    // Fraction of the old band that is kept (all of it going up in rate)
+    let cutoff = (1.0 / step).min(1.0);
+    let half_width = RESAMPLE_ZERO_CROSSINGS as f64 / cutoff;
+    let length = (samples.len() as f64 / step).round() as usize;
+    let last = samples.len() as i64 - 1;
+
+    (0..length)
+        .into_par_iter()
+        .map(|index| {
+            let position = index as f64 * step;
+            let first = ((position - half_width).ceil() as i64).max(0);
+            let end = ((position + half_width).floor() as i64).min(last);
+            let mut sum = 0.0;
+            for source in first..=end {
+                let offset = source as f64 - position;
+                let x = PI * cutoff * offset;
+                let sinc = if x.abs() < 1e-9 { 1.0 } else { x.sin() / x };
+                let w = PI * offset / half_width;
+                let window = 0.42 + 0.5 * w.cos() + 0.08 * (2.0 * w).cos();
+                sum += samples[source as usize] as f64 * cutoff * sinc * window;
+            }
+            sum as f32
+        })
+        .collect()
+}
+
 /// Generates a default output filename based on the input filename
 /// "song.csv" -> "song.wav"
 pub fn generate_wav_filename(csv_path: &str) -> String {
@@ -317,6 +591,111 @@ mod tests {
         assert_eq!(generate_wav_filename("my_music.csv"), "my_music.wav");
     }
 
+    #[test]
+    fn test_read_wav_file() {
+        // A stereo file with the channels opposite reads as silence
+        let samples: Vec<f32> = (0..200)
+            .map(|i| if i % 2 == 0 { 0.5 } else { -0.5 })
+            .collect();
+        let path = std::env::temp_dir().join("read_wav_test.wav");
+        write_wav_file(&path, &samples, 22050, true, false).unwrap();
+        let (mono, sample_rate) = read_wav_file(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(sample_rate, 22050);
+        assert_eq!(mono.len(), 100);
+        assert!(mono.iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn test_decode_keeps_channels_and_reads_any_wav_case() {
+        let samples: Vec<f32> = (0..200)
+            .map(|i| if i % 2 == 0 { 0.5 } else { -0.25 })
+            .collect();
+        let path = std::env::temp_dir().join("decode_test.WAV");
+        write_wav_file(&path, &samples, 44100, true, false).unwrap();
+        let audio = decode_audio_file(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(audio.sample_rate, 44100);
+        assert_eq!(audio.channels.len(), 2);
+        assert!(audio.channels[0].iter().all(|sample| *sample == 0.5));
+        assert!(audio.channels[1].iter().all(|sample| *sample == -0.25));
+        assert_eq!(audio.mono(), vec![0.125; 100]);
+
+        // Not a sound file in any format symphonia knows
+        let text = std::env::temp_dir().join("decode_test.mp3");
+        std::fs::write(&text, "not audio").unwrap();
+        let result = decode_audio_file(&text);
+        let _ = std::fs::remove_file(&text);
+        assert!(result.unwrap_err().contains("decode_test.mp3"));
+    }
+
+    #[test]
+    fn test_resample_keeps_tones_and_filters_above_nyquist() {
+        let tone = |frequency: f64, rate: u32, length: usize| -> Vec<f32> {
+            (0..length)
+                .map(|i| (2.0 * PI * frequency * i as f64 / rate as f64).sin() as f32)
+                .collect()
+        };
+
+        // Up: 1 kHz at 44.1 kHz is the same tone at 48 kHz
+        let up = resample(&tone(1000.0, 44100, 4410), 44100, 48000);
+        assert_eq!(up.len(), 4800);
+        let expected = tone(1000.0, 48000, 4800);
+        let worst = up[200..4600]
+            .iter()
+            .zip(&expected[200..4600])
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        assert!(worst < 1e-3, "worst error {}", worst);
+
+        // Down: a 6 kHz tone can't exist at 8 kHz and is filtered out
+        let down = resample(&tone(6000.0, 48000, 4800), 48000, 8000);
+        assert_eq!(down.len(), 800);
+        let rms = (down[100..700].iter().map(|s| s * s).sum::<f32>() / 600.0).sqrt();
+        assert!(rms < 0.01, "rms {}", rms);
+
+        let mut audio = DecodedAudio {
+            channels: vec![vec![0.0; 441], vec![0.0; 441]],
+            sample_rate: 44100,
+        };
+        audio.resample(48000);
+        assert_eq!(audio.sample_rate, 48000);
+        assert_eq!(audio.channels[1].len(), 480);
+    }
+
+    #[test]
+    fn test_dither_keeps_quiet_tails() {
+        // A tone a third of a 16-bit step high: plain rounding loses it
+        let samples: Vec<f32> = (0..9600)
+            .map(|i| (i as f32 * 0.05).sin() * (0.33 / 32767.0))
+            .collect();
+        let read_back = |dither: bool| -> Vec<i16> {
+            let path = std::env::temp_dir().join(format!("dither_test_{}.wav", dither));
+            write_wav_file(&path, &samples, 48000, false, dither).unwrap();
+            let bytes = std::fs::read(&path).unwrap();
+            let _ = std::fs::remove_file(&path);
+            bytes[44..]
+                .chunks_exact(2)
+                .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+                .collect()
+        };
+
+        assert!(read_back(false).iter().all(|&value| value == 0));
+
+        // With dither the tone survives as a bias in the noise
+        let dithered = read_back(true);
+        let correlation: f32 = dithered
+            .iter()
+            .zip(&samples)
+            .map(|(&value, &sample)| value as f32 * sample.signum())
+            .sum::<f32>()
+            / dithered.len() as f32;
+        assert!(dithered.iter().all(|&value| value.abs() <= 2));
+        assert!(correlation > 0.1, "tone lost: {}", correlation);
+    }
+
     #[test]
     fn test_analyze_audio() {
         // Create a simple sine wave
@@ -0,0 +1,607 @@
+// ============================================================================
+// BACKEND.RS - Audio Output Backends
+// ============================================================================
+//
+// The engine does not talk to the sound card itself. It only knows how to
+// fill a buffer of interleaved stereo samples (L R L R ...). A backend owns
+// the audio device and calls that render callback whenever the device
+// needs more samples.
+//
+// Keeping the device code behind one small trait means the player can
+// switch audio libraries without touching the engine:
+//
+//   tracker song.csv --backend miniaudio   (default)
+//   tracker song.csv --backend cpal        (needs: cargo build --features cpal)
+//   tracker song.csv --backend null        (no sound card, for CI and tests)
+//
+// AVAILABLE BACKENDS:
+// - miniaudio: built by default (cargo feature "miniaudio"); the same
+//   library the FFT analyzer uses. --no-default-features leaves it out,
+//   for machines that can't build its C code.
+// - cpal: optional (cargo feature "cpal"), for platforms where miniaudio
+//   fails to open a device
+// - null: no device at all; a timer thread pulls buffers at the real-time
+//   rate and throws them away, so the full playback path (commands, row
+//   events, transitions) runs on machines without audio hardware
+//
+// AUDIO INPUT:
+// A backend may also open a duplex device (input and output together), so
+// a microphone or line-in reaches the engine on the same callback that
+// fills the output ("tracker live --input"). The callback gets the
+// captured samples first, interleaved stereo like the output. Backends
+// without input support keep the default start_duplex, which says so.
+//
+// HOW TO ADD A NEW BACKEND:
+// 1. Write a struct that implements AudioBackend
+// 2. Add a BackendDefinition for it to BACKEND_REGISTRY
+// 3. "--backend <name>" picks it up automatically
+// ============================================================================
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// ============================================================================
+// BACKEND TRAIT
+// ============================================================================
+
+/// Fills an interleaved stereo buffer (L R L R ...)
+/// Called on the backend's audio thread, so it must never block.
+pub type RenderCallback = Box<dyn FnMut(&mut [f32]) + Send + 'static>;
+
+/// Fills an output buffer from the samples captured at the same moment
+/// Both buffers are interleaved stereo and hold the same number of frames.
+pub type DuplexRenderCallback = Box<dyn FnMut(&[f32], &mut [f32]) + Send + 'static>;
+
+/// Settings a backend opens its device with
+#[derive(Clone, Copy, Debug)]
+pub struct BackendSettings {
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+
+    /// Preferred frames per callback (backends may choose another size)
+    pub buffer_frames: u32,
+}
+
+/// An audio output that pulls samples from a render callback
+pub trait AudioBackend {
+    /// Name used with --backend
+    fn name(&self) -> &'static str;
+
+    /// Opens the default output device and starts calling `render`
+    /// Audio keeps playing until stop() is called or the backend is dropped.
+    fn start(&mut self, settings: BackendSettings, render: RenderCallback) -> Result<(), String>;
+
+    /// Opens the default input and output devices together and starts
+    /// calling `render` with each captured buffer
+    fn start_duplex(
+        &mut self,
+        _settings: BackendSettings,
+        _render: DuplexRenderCallback,
+    ) -> Result<(), String> {
+        Err(format!("The {} backend has no audio input", self.name()))
+    }
+
+    /// Stops playback and closes the device
+    fn stop(&mut self);
+}
+
+// ============================================================================
+// BACKEND REGISTRY
+// ============================================================================
+
+/// Describes one backend that can be picked by name
+pub struct BackendDefinition {
+    /// Primary name (used with --backend)
+    pub name: &'static str,
+
+    /// Alternative names that also work
+    pub aliases: &'static [&'static str],
+
+    /// One-line description for messages
+    pub description: &'static str,
+
+    /// Creates a stopped backend
+    pub create: fn() -> Box<dyn AudioBackend>,
+}
+
+/// All backends compiled into this build (the first one is the default,
+/// so a build without miniaudio or cpal plays through null)
+pub static BACKEND_REGISTRY: &[BackendDefinition] = &[
+    #[cfg(feature = "miniaudio")]
+    BackendDefinition {
+        name: "miniaudio",
+        aliases: &["ma"],
+        description: "miniaudio (default)",
+        create: || Box::new(MiniaudioBackend::default()),
+    },
+    #[cfg(feature = "cpal")]
+    BackendDefinition {
+        name: "cpal",
+        aliases: &[],
+        description: "cpal (ALSA, CoreAudio, WASAPI)",
+        create: || Box::new(cpal_backend::CpalBackend::default()),
+    },
+    BackendDefinition {
+        name: "null",
+        aliases: &["none", "headless"],
+        description: "no audio device (timer-driven, for CI and tests)",
+        create: || Box::new(NullBackend::default()),
+    },
+];
+
+/// Name of the backend used when none is requested
+pub fn default_backend_name() -> &'static str {
+    BACKEND_REGISTRY[0].name
+}
+
+/// Looks up a backend by name or alias (case-insensitive)
+pub fn find_backend_by_name(name: &str) -> Option<&'static BackendDefinition> {
+    let name_lower = name.trim().to_lowercase();
+    BACKEND_REGISTRY
+        .iter()
+        .find(|backend| backend.name == name_lower || backend.aliases.contains(&&*name_lower))
+}
+
+/// Creates a backend by name
+pub fn create_backend(name: &str) -> Result<Box<dyn AudioBackend>, String> {
+    find_backend_by_name(name)
+        .map(|definition| (definition.create)())
+        .ok_or_else(|| unknown_backend_message(name))
+}
+
+/// Message for a --backend name this build doesn't have
+/// It lists the backends this build has, since optional ones may not have
+/// been compiled in.
+pub fn unknown_backend_message(name: &str) -> String {
+    let available: Vec<&str> = BACKEND_REGISTRY
+        .iter()
+        .map(|backend| backend.name)
+        .collect();
+    let name_lower = name.trim().to_lowercase();
+    let feature = match name_lower.as_str() {
+        "cpal" => Some("cpal"),
+        "miniaudio" | "ma" => Some("miniaudio"),
+        _ => None,
+    };
+    let hint = feature
+        .map(|feature| format!(" (build with --features {} to enable it)", feature))
+        .unwrap_or_default();
+    format!(
+        "Unknown audio backend '{}'{}. Available: {}",
+        name,
+        hint,
+        available.join(", ")
+    )
+}
+
+// ============================================================================
+// MINIAUDIO BACKEND (cargo feature "miniaudio", on by default)
+// ============================================================================
+
+#[cfg(feature = "miniaudio")]
+pub use miniaudio_backend::MiniaudioBackend;
+
+#[cfg(feature = "miniaudio")]
+mod miniaudio_backend {
+    use super::{AudioBackend, BackendSettings, DuplexRenderCallback, RenderCallback};
+    use std::sync::{Arc, Mutex};
+
+    /// Plays through miniaudio
+    #[derive(Default)]
+    pub struct MiniaudioBackend {
+        /// The running device (None when stopped)
+        device: Option<miniaudio::Device>,
+    }
+
+    /// Device settings shared by playback and duplex (stereo f32 output)
+    fn miniaudio_config(
+        device_type: miniaudio::DeviceType,
+        settings: BackendSettings,
+    ) -> miniaudio::DeviceConfig {
+        let mut device_config = miniaudio::DeviceConfig::new(device_type);
+        device_config
+            .playback_mut()
+            .set_format(miniaudio::Format::F32);
+        device_config.playback_mut().set_channels(2);
+        device_config.set_sample_rate(settings.sample_rate);
+        device_config.set_period_size_in_frames(settings.buffer_frames);
+        device_config
+    }
+
+    impl MiniaudioBackend {
+        /// Creates and starts the device described by a finished config
+        fn open(&mut self, device_config: &miniaudio::DeviceConfig) -> Result<(), String> {
+            let context = miniaudio::Context::new(&[], None)
+                .map_err(|error| format!("Failed to create audio context: {:?}", error))?;
+            let device = miniaudio::Device::new(Some(context), device_config)
+                .map_err(|error| format!("Failed to create audio device: {:?}", error))?;
+            device
+                .start()
+                .map_err(|error| format!("Failed to start audio device: {:?}", error))?;
+
+            self.device = Some(device);
+            Ok(())
+        }
+    }
+
+    impl AudioBackend for MiniaudioBackend {
+        fn name(&self) -> &'static str {
+            "miniaudio"
+        }
+
+        fn start(
+            &mut self,
+            settings: BackendSettings,
+            render: RenderCallback,
+        ) -> Result<(), String> {
+            use miniaudio::{DeviceType, Frames, FramesMut};
+
+            let mut device_config = miniaudio_config(DeviceType::Playback, settings);
+
+            // miniaudio needs a cloneable callback, so the render callback sits
+            // behind a Mutex, but only the audio thread ever locks it (never
+            // contended, and try_lock never waits anyway)
+            let render = Arc::new(Mutex::new(render));
+            device_config.set_data_callback(
+                move |_device: &miniaudio::RawDevice, output: &mut FramesMut, _input: &Frames| {
+                    let samples = output.as_samples_mut::<f32>();
+                    match render.try_lock() {
+                        Ok(mut render) => render(samples),
+                        Err(_) => samples.fill(0.0),
+                    }
+                },
+            );
+
+            self.open(&device_config)
+        }
+
+        fn start_duplex(
+            &mut self,
+            settings: BackendSettings,
+            render: DuplexRenderCallback,
+        ) -> Result<(), String> {
+            use miniaudio::{DeviceType, Format, Frames, FramesMut};
+
+            let mut device_config = miniaudio_config(DeviceType::Duplex, settings);
+            device_config.capture_mut().set_format(Format::F32);
+            device_config.capture_mut().set_channels(2);
+
+            // Same locking as start(); miniaudio hands over the captured frames
+            // alongside the output buffer
+            let render = Arc::new(Mutex::new(render));
+            device_config.set_data_callback(
+                move |_device: &miniaudio::RawDevice, output: &mut FramesMut, input: &Frames| {
+                    let samples = output.as_samples_mut::<f32>();
+                    match render.try_lock() {
+                        Ok(mut render) => render(input.as_samples::<f32>(), samples),
+                        Err(_) => samples.fill(0.0),
+                    }
+                },
+            );
+
+            self.open(&device_config)
+        }
+
+        fn stop(&mut self) {
+            if let Some(device) = self.device.take() {
+                let _ = device.stop();
+            }
+        }
+    }
+}
+
+// ============================================================================
+// NULL BACKEND
+// ============================================================================
+
+/// Renders on a timer thread without any audio device
+///
+/// Buffers are pulled at the real-time rate (one buffer every
+/// buffer_frames / sample_rate seconds) and discarded, so songs take as
+/// long as they would on speakers. `unpaced()` skips the waiting, which
+/// lets tests run a whole song in a moment.
+pub struct NullBackend {
+    /// Whether to wait between buffers like a real device
+    paced: bool,
+
+    /// Total frames rendered since start (shared with the timer thread)
+    frames_rendered: Arc<AtomicU64>,
+
+    /// Tells the timer thread to finish
+    running: Arc<AtomicBool>,
+
+    /// The timer thread (None when stopped)
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Default for NullBackend {
+    fn default() -> Self {
+        Self {
+            paced: true,
+            frames_rendered: Arc::new(AtomicU64::new(0)),
+            running: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        }
+    }
+}
+
+impl NullBackend {
+    /// A null backend that renders buffers as fast as it can
+    pub fn unpaced() -> Self {
+        let mut backend = Self::default();
+        backend.paced = false;
+        backend
+    }
+
+    /// Total frames rendered since start
+    pub fn frames_rendered(&self) -> u64 {
+        self.frames_rendered.load(Ordering::Relaxed)
+    }
+}
+
+impl AudioBackend for NullBackend {
+    fn name(&self) -> &'static str {
+        "null"
+    }
+
+    fn start(
+        &mut self,
+        settings: BackendSettings,
+        mut render: RenderCallback,
+    ) -> Result<(), String> {
+        self.stop();
+
+        let buffer_frames = settings.buffer_frames.max(1);
+        let buffer_duration =
+            Duration::from_secs_f64(buffer_frames as f64 / settings.sample_rate.max(1) as f64);
+        let paced = self.paced;
+        let frames_rendered = Arc::clone(&self.frames_rendered);
+        let running = Arc::clone(&self.running);
+        frames_rendered.store(0, Ordering::Relaxed);
+        running.store(true, Ordering::Release);
+
+        let thread = thread::Builder::new()
+            .name("null-audio".to_string())
+            .spawn(move || {
+                let mut buffer = vec![0.0; buffer_frames as usize * 2];
+                let start_time = Instant::now();
+                let mut buffer_count: u32 = 0;
+
+                while running.load(Ordering::Acquire) {
+                    render(&mut buffer);
+                    frames_rendered.fetch_add(buffer_frames as u64, Ordering::Relaxed);
+                    buffer_count += 1;
+
+                    if paced {
+                        // Wait for the next deadline (not a fixed sleep, so
+                        // the time spent rendering does not add up as drift)
+                        let deadline = start_time + buffer_duration * buffer_count;
+                        if let Some(wait) = deadline.checked_duration_since(Instant::now()) {
+                            thread::sleep(wait);
+                        }
+                    } else {
+                        // Let the main thread poll events between buffers
+                        thread::yield_now();
+                    }
+                }
+            })
+            .map_err(|error| format!("Failed to start null audio thread: {}", error))?;
+
+        self.thread = Some(thread);
+        Ok(())
+    }
+
+    /// There is no device to capture from, so the input is always silent
+    fn start_duplex(
+        &mut self,
+        settings: BackendSettings,
+        mut render: DuplexRenderCallback,
+    ) -> Result<(), String> {
+        let mut input = Vec::new();
+        self.start(
+            settings,
+            Box::new(move |output| {
+                // Sized on the first buffer, then reused
+                input.resize(output.len(), 0.0);
+                render(&input, output);
+            }),
+        )
+    }
+
+    fn stop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for NullBackend {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// ============================================================================
+// CPAL BACKEND (cargo feature "cpal")
+// ============================================================================
+
+#[cfg(feature = "cpal")]
+mod cpal_backend {
+    use super::{AudioBackend, BackendSettings, RenderCallback};
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+    /// Plays through cpal
+    #[derive(Default)]
+    pub struct CpalBackend {
+        /// The running stream (None when stopped)
+        stream: Option<cpal::Stream>,
+    }
+
+    impl AudioBackend for CpalBackend {
+        fn name(&self) -> &'static str {
+            "cpal"
+        }
+
+        fn start(
+            &mut self,
+            settings: BackendSettings,
+            mut render: RenderCallback,
+        ) -> Result<(), String> {
+            let host = cpal::default_host();
+            let device = host
+                .default_output_device()
+                .ok_or_else(|| "No audio output device found".to_string())?;
+
+            let config = cpal::StreamConfig {
+                channels: 2,
+                sample_rate: cpal::SampleRate(settings.sample_rate),
+                buffer_size: cpal::BufferSize::Fixed(settings.buffer_frames),
+            };
+
+            // cpal hands over plain f32 slices, so the render callback is
+            // moved straight into the stream (no lock needed)
+            let stream = device
+                .build_output_stream(
+                    &config,
+                    move |samples: &mut [f32], _info: &cpal::OutputCallbackInfo| render(samples),
+                    |error| log::error!(target: "audio", "cpal stream error: {}", error),
+                    None,
+                )
+                .map_err(|error| format!("Failed to open cpal stream: {}", error))?;
+            stream
+                .play()
+                .map_err(|error| format!("Failed to start cpal stream: {}", error))?;
+
+            self.stream = Some(stream);
+            Ok(())
+        }
+
+        fn stop(&mut self) {
+            // Dropping the stream closes it
+            self.stream = None;
+        }
+    }
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_find_backend() {
+        #[cfg(feature = "miniaudio")]
+        {
+            assert_eq!(default_backend_name(), "miniaudio");
+            assert_eq!(find_backend_by_name("MA").unwrap().name, "miniaudio");
+        }
+        #[cfg(not(feature = "miniaudio"))]
+        assert!(
+            create_backend("miniaudio")
+                .err()
+                .unwrap()
+                .contains("--features miniaudio")
+        );
+        assert!(find_backend_by_name("jack").is_none());
+
+        let error = create_backend("jack").err().unwrap();
+        assert!(error.contains("Available: "));
+        assert!(error.ends_with("null"));
+        assert_eq!(create_backend("headless").unwrap().name(), "null");
+    }
+
+    #[test]
+    fn test_null_backend_plays_song_through_realtime_engine() {
+        use crate::engine::{EngineConfig, EngineEvent, PlaybackEngine};
+        use crate::helper::{A4_FREQUENCY_HZ, FrequencyTable};
+        use crate::parser::{MissingCellBehavior, parse_song};
+
+        let song = parse_song(
+            "Lead\nc4 sine\ne4 sine a:0.5\n.",
+            &FrequencyTable::new(),
+            A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+        let config = EngineConfig {
+            sample_rate: 8000,
+            channel_count: 1,
+            tick_duration_seconds: 0.01,
+            ..Default::default()
+        };
+        let (mut realtime, mut controller) = PlaybackEngine::new(song, config).into_realtime();
+
+        let mut backend = NullBackend::unpaced();
+        let settings = BackendSettings {
+            sample_rate: 8000,
+            buffer_frames: 64,
+        };
+        backend
+            .start(settings, Box::new(move |samples| realtime.process(samples)))
+            .unwrap();
+
+        // Rows are reported in order, then the end of the song
+        let mut events = Vec::new();
+        let start_time = Instant::now();
+        while events.last() != Some(&EngineEvent::Finished) {
+            assert!(start_time.elapsed() < Duration::from_secs(10));
+            match controller.poll_event() {
+                Some(event) => events.push(event),
+                None => thread::yield_now(),
+            }
+        }
+        backend.stop();
+
+        assert_eq!(
+            events,
+            vec![
+                EngineEvent::RowStarted(1),
+                EngineEvent::RowStarted(2),
+                EngineEvent::RowStarted(3),
+                EngineEvent::Finished,
+            ]
+        );
+        assert!(backend.frames_rendered() >= 3 * 80);
+    }
+
+    #[test]
+    fn test_duplex_input() {
+        // The null backend has nothing to capture, so the input is silent
+        let mut backend = NullBackend::unpaced();
+        let settings = BackendSettings {
+            sample_rate: 8000,
+            buffer_frames: 64,
+        };
+        let buffer_sizes = Arc::new(Mutex::new(Vec::new()));
+        let sizes = Arc::clone(&buffer_sizes);
+        backend
+            .start_duplex(
+                settings,
+                Box::new(move |input, output| {
+                    assert!(input.iter().all(|sample| *sample == 0.0));
+                    sizes.lock().unwrap().push((input.len(), output.len()));
+                }),
+            )
+            .unwrap();
+        let start_time = Instant::now();
+        while backend.frames_rendered() < 64 * 4 {
+            assert!(start_time.elapsed() < Duration::from_secs(10));
+            thread::yield_now();
+        }
+        backend.stop();
+        assert!(
+            buffer_sizes
+                .lock()
+                .unwrap()
+                .iter()
+                .all(|&size| size == (128, 128))
+        );
+    }
+}
@@ -17,18 +17,84 @@
 // - Has per-channel effects (vibrato, tremolo, distortion, etc.)
 // - Supports pitch glides (smooth transition from one note to another)
 // - Supports instrument crossfades (smoothly change from one instrument to another)
+// - Can mix in a sub-oscillator an octave below the note ("sub:0.5")
+// - Can start each note with a short key-click ("click:0.3")
+// - Keeps the grain cloud of the granular instrument (see grain.rs)
 //
 // LIFECYCLE OF A NOTE:
 // 1. Trigger: Note starts playing, envelope enters Attack phase
 // 2. Sustain: Note held, envelope at Sustain level
 // 3. Release: Note released, envelope fades out
 // 4. Idle: Envelope finished, channel silent until next trigger
+//
+// DECLICKING:
+// Retriggering a sounding channel restarts the waveform at phase zero, so
+// the output would jump from wherever the old note was. Instead the gap
+// between the old note's last sample and the new note's first one is
+// added as an offset that fades to zero over DECLICK_SECONDS. Releases also last at least that long, so a
+// note cut ("." with a tiny fast_release_seconds) never drops straight
+// to silence.
+//
+// SUB-OSCILLATOR:
+// A second phase runs at half the speed of the main one, so it stays
+// locked an octave below through glides, vibrato, and detune. When the
+// channel's sub level is above zero, a square (or sine) drawn from that
+// phase is added to the instrument before the envelope. Pitchless
+// instruments like noise have no octave to go below, so they get none.
+//
+// KEY-CLICK:
+// Organs (and other keyboards) make a short burst of noise when a key
+// goes down. With "click:level" set, each fresh trigger starts a noise
+// burst at that level which falls 60 dB over KEY_CLICK_SECONDS. It is
+// added after the envelope, so a slow attack doesn't hide it. Glides
+// (tr:) don't retrigger, so they don't click.
+//
+// TEMPO-SYNCED LFOS:
+// An LFO rate written as a note length ("t:1/16'0.5") is kept in beats
+// per cycle. The channel turns it into Hz with the tempo the engine gave
+// it (set_tempo) whenever its effects change, and again when the tempo
+// changes, so the wobble stays locked to the rows when the BPM moves.
+//
+// An LFO can also restart its cycle ("t:4'0.5'sync" on each new note,
+// "t:4'0.5'row" at every row) instead of running free, so a rhythmic
+// tremolo lands the same way on every hit.
 // ============================================================================
 
-use crate::effects::{ChannelEffectState, apply_channel_effects, calculate_vibrato_multiplier};
+use crate::dsp_load::{DspStage, StageTimer};
+use crate::effects::{
+    ChannelEffectState, ChannelOversamplers, CustomEffectSlot, LfoRestart, SyncedRates,
+    advance_vibrato, apply_channel_effects_block, custom_effect_slots, synced_rate_hz,
+};
 use crate::envelope::{EnvelopePhase, EnvelopeState};
-use crate::helper::{RandomNumberGenerator, calculate_phase_increment, lerp, wrap_phase};
-use crate::instruments::generate_sample;
+use crate::grain::GrainCloud;
+use crate::helper::{
+    RandomNumberGenerator, calculate_phase_increment, cents_to_frequency_ratio, lerp, wrap_phase,
+};
+use crate::instruments::{
+    GRAIN_INSTRUMENT_ID, INPUT_INSTRUMENT_ID, generate_block, instrument_requires_pitch,
+};
+use crate::simd::add_ramped;
+
+/// Room reserved for instrument parameters when a channel is created, so
+/// triggering notes never has to grow the list on the audio thread
+const PREALLOCATED_INSTRUMENT_PARAMETERS: usize = 8;
+
+/// Largest block render_block processes at once (longer buffers are
+/// split); per-block values are recalculated this often
+pub const RENDER_BLOCK_SIZE: usize = 64;
+
+/// How long muting or unmuting a channel fades (short, just to avoid a click)
+pub const MUTE_FADE_SECONDS: f32 = 0.01;
+
+/// How long a retriggered note's leftover offset takes to fade (to -60 dB),
+/// and the shortest release
+pub const DECLICK_SECONDS: f32 = 0.002;
+
+/// How long a key-click takes to fade (to -60 dB)
+pub const KEY_CLICK_SECONDS: f32 = 0.005;
+
+/// Tempo of a new channel until the engine sets one (120 BPM)
+pub const DEFAULT_SECONDS_PER_BEAT: f32 = 0.5;
 
 // ============================================================================
 // TRANSITION STATE
@@ -36,50 +102,143 @@ use crate::instruments::generate_sample;
 //
 // When effects change, they don't jump instantly (which would cause clicks).
 // Instead, they smoothly transition from current to target values.
-// This struct tracks that transition.
+//
+// Every parameter has its own ramp, so transitions can overlap: a tr: on
+// pan starts a pan ramp and leaves an amplitude fade that is already
+// running alone. A cell only starts ramps for the parameters it changes.
 // ============================================================================
 
-/// Tracks a smooth transition of effect parameters
-#[derive(Clone, Debug)]
-pub struct EffectTransition {
-    /// How many samples the transition takes
+/// The effect parameters that move smoothly during a transition
+/// (chorus feedback and the LFO phases change straight away)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EffectParameter {
+    Amplitude,
+    Pan,
+    DetuneCents,
+    VibratoRate,
+    VibratoDepth,
+    TremoloRate,
+    TremoloDepth,
+    BitcrushBits,
+    DecimateRate,
+    Distortion,
+    SubLevel,
+    WahRate,
+    WahDepth,
+    VowelMorph,
+    ChorusMix,
+    ChorusRate,
+    ChorusDepth,
+}
+
+impl EffectParameter {
+    /// Every parameter, in the order of Channel::effect_ramps
+    pub const ALL: [EffectParameter; 17] = [
+        EffectParameter::Amplitude,
+        EffectParameter::Pan,
+        EffectParameter::DetuneCents,
+        EffectParameter::VibratoRate,
+        EffectParameter::VibratoDepth,
+        EffectParameter::TremoloRate,
+        EffectParameter::TremoloDepth,
+        EffectParameter::BitcrushBits,
+        EffectParameter::DecimateRate,
+        EffectParameter::Distortion,
+        EffectParameter::SubLevel,
+        EffectParameter::WahRate,
+        EffectParameter::WahDepth,
+        EffectParameter::VowelMorph,
+        EffectParameter::ChorusMix,
+        EffectParameter::ChorusRate,
+        EffectParameter::ChorusDepth,
+    ];
+
+    /// Reads this parameter from an effect state
+    pub fn get(self, effects: &ChannelEffectState) -> f32 {
+        match self {
+            EffectParameter::Amplitude => effects.amplitude,
+            EffectParameter::Pan => effects.pan,
+            EffectParameter::DetuneCents => effects.detune_cents,
+            EffectParameter::VibratoRate => effects.vibrato_rate_hz,
+            EffectParameter::VibratoDepth => effects.vibrato_depth_semitones,
+            EffectParameter::TremoloRate => effects.tremolo_rate_hz,
+            EffectParameter::TremoloDepth => effects.tremolo_depth,
+            EffectParameter::BitcrushBits => effects.bitcrush_bits as f32,
+            EffectParameter::DecimateRate => effects.decimate_rate_hz,
+            EffectParameter::Distortion => effects.distortion_amount,
+            EffectParameter::SubLevel => effects.sub_level,
+            EffectParameter::WahRate => effects.wah_rate_hz,
+            EffectParameter::WahDepth => effects.wah_depth,
+            EffectParameter::VowelMorph => effects.vowel_morph,
+            EffectParameter::ChorusMix => effects.chorus_mix,
+            EffectParameter::ChorusRate => effects.chorus_rate_hz,
+            EffectParameter::ChorusDepth => effects.chorus_depth_ms,
+        }
+    }
+
+    /// Writes this parameter into an effect state
+    /// Bitcrush moves as a float and rounds to whole bits.
+    pub fn set(self, effects: &mut ChannelEffectState, value: f32) {
+        match self {
+            EffectParameter::Amplitude => effects.amplitude = value,
+            EffectParameter::Pan => effects.pan = value,
+            EffectParameter::DetuneCents => effects.detune_cents = value,
+            EffectParameter::VibratoRate => effects.vibrato_rate_hz = value,
+            EffectParameter::VibratoDepth => effects.vibrato_depth_semitones = value,
+            EffectParameter::TremoloRate => effects.tremolo_rate_hz = value,
+            EffectParameter::TremoloDepth => effects.tremolo_depth = value,
+            EffectParameter::BitcrushBits => effects.bitcrush_bits = value.round() as u8,
+            EffectParameter::DecimateRate => effects.decimate_rate_hz = value,
+            EffectParameter::Distortion => effects.distortion_amount = value,
+            EffectParameter::SubLevel => effects.sub_level = value,
+            EffectParameter::WahRate => effects.wah_rate_hz = value,
+            EffectParameter::WahDepth => effects.wah_depth = value,
+            EffectParameter::VowelMorph => effects.vowel_morph = value,
+            EffectParameter::ChorusMix => effects.chorus_mix = value,
+            EffectParameter::ChorusRate => effects.chorus_rate_hz = value,
+            EffectParameter::ChorusDepth => effects.chorus_depth_ms = value,
+        }
+    }
+}
+
+/// One parameter moving from a start value to a target
+#[derive(Clone, Copy, Debug)]
+pub struct ParameterRamp {
+    /// Value when the ramp started
+    pub start: f32,
+
+    /// Value at the end of the ramp
+    pub target: f32,
+
+    /// How many samples the ramp takes
     pub duration_samples: u32,
 
     /// How many samples have elapsed
     pub elapsed_samples: u32,
-
-    /// The effect state we started from
-    pub start_state: ChannelEffectState,
-
-    /// The effect state we're transitioning to
-    pub target_state: ChannelEffectState,
 }
 
-impl EffectTransition {
-    /// Creates a new effect transition
-    pub fn new(
-        duration_seconds: f32,
-        sample_rate: u32,
-        start_state: ChannelEffectState,
-        target_state: ChannelEffectState,
-    ) -> Self {
+impl ParameterRamp {
+    /// Creates a ramp from start to target over the given time
+    pub fn new(start: f32, target: f32, duration_seconds: f32, sample_rate: u32) -> Self {
         Self {
+            start,
+            target,
             duration_samples: (duration_seconds * sample_rate as f32) as u32,
             elapsed_samples: 0,
-            start_state,
-            target_state,
         }
     }
 
-    /// Calculates the current progress (0.0 to 1.0)
-    pub fn progress(&self) -> f32 {
-        if self.duration_samples == 0 {
-            return 1.0;
+    /// Moves the ramp forward and returns the value it has reached
+    pub fn advance(&mut self, samples: u32) -> f32 {
+        self.elapsed_samples = self.elapsed_samples.saturating_add(samples);
+        if self.is_complete() {
+            return self.target;
         }
-        (self.elapsed_samples as f32 / self.duration_samples as f32).clamp(0.0, 1.0)
+        let progress = self.elapsed_samples as f32 / self.duration_samples as f32;
+        lerp(self.start, self.target, progress)
     }
 
-    /// Returns true if the transition is complete
+    /// Returns true if the ramp has reached its target
     pub fn is_complete(&self) -> bool {
         self.elapsed_samples >= self.duration_samples
     }
@@ -134,9 +293,9 @@ impl PitchSlide {
         self.elapsed_seconds >= self.duration_seconds
     }
 
-    /// Advances the slide by one sample
-    pub fn advance(&mut self, sample_rate: u32) {
-        self.elapsed_seconds += 1.0 / sample_rate as f32;
+    /// Advances the slide by a number of samples
+    pub fn advance(&mut self, samples: usize, sample_rate: u32) {
+        self.elapsed_seconds += samples as f32 / sample_rate as f32;
     }
 }
 
@@ -189,9 +348,9 @@ impl InstrumentCrossfade {
         (from_gain, to_gain)
     }
 
-    /// Advances the crossfade by one sample
-    pub fn advance(&mut self, sample_rate: u32) {
-        self.elapsed_seconds += 1.0 / sample_rate as f32;
+    /// Advances the crossfade by a number of samples
+    pub fn advance(&mut self, samples: usize, sample_rate: u32) {
+        self.elapsed_seconds += samples as f32 / sample_rate as f32;
     }
 }
 
@@ -217,20 +376,44 @@ pub struct Channel {
     /// Current phase position in the waveform (0 to 2*PI)
     pub phase: f32,
 
+    /// Phase of the sub-oscillator, moving at half the speed of `phase`
+    pub sub_phase: f32,
+
     /// Currently playing instrument ID
     pub instrument_id: usize,
 
     /// Parameters for the current instrument (e.g., trisaw shape, pulse width)
     pub instrument_parameters: Vec<f32>,
 
+    /// Grains playing when the instrument is the grain instrument
+    pub grains: GrainCloud,
+
+    /// Captured audio (mono) when the instrument is the input instrument,
+    /// refilled by the engine for each stretch it renders
+    pub input_samples: Vec<f32>,
+
+    /// How much of input_samples has been played
+    pub input_read: usize,
+
+    /// Time spent on the voice and each effect, when measured
+    /// (see dsp_load.rs)
+    pub stage_timer: StageTimer,
+
     /// Envelope state (handles ADSR amplitude shaping)
     pub envelope: EnvelopeState,
 
     /// Per-channel effects state
     pub effects: ChannelEffectState,
 
-    /// Optional effect transition in progress
-    pub effect_transition: Option<EffectTransition>,
+    /// Effect parameters in the middle of a transition, one slot per
+    /// EffectParameter (see EffectParameter::ALL)
+    pub effect_ramps: [Option<ParameterRamp>; EffectParameter::ALL.len()],
+
+    /// This channel's copies of the registered custom effects
+    pub custom_effects: Vec<CustomEffectSlot>,
+
+    /// Filter memory for oversampled bitcrush and distortion
+    pub oversamplers: ChannelOversamplers,
 
     /// Optional pitch slide in progress
     pub pitch_slide: Option<PitchSlide>,
@@ -244,8 +427,33 @@ pub struct Channel {
     /// Sample rate (needed for time calculations)
     pub sample_rate: u32,
 
+    /// Length of a beat, for LFO rates set as note lengths
+    pub seconds_per_beat: f32,
+
     /// Total samples processed (for debugging/timing)
     pub total_samples_processed: u64,
+
+    /// Loudest sample since the level meter last read it (0.0 - 1.0)
+    pub peak_level: f32,
+
+    /// False while muted (or while another channel is soloed)
+    pub audible: bool,
+
+    /// Gain fading toward 1.0 (audible) or 0.0 (muted)
+    pub mute_gain: f32,
+
+    /// Last sample of the oscillator and envelope (before effects)
+    pub last_sample: f32,
+
+    /// Last sample of a note that was just retriggered, until the new
+    /// note renders its first sample (see DECLICKING above)
+    pub declick_from: Option<f32>,
+
+    /// Gap between the old and new note, fading to zero
+    pub declick_offset: f32,
+
+    /// Level of the key-click noise, fading to zero (see KEY-CLICK above)
+    pub key_click_gain: f32,
 }
 
 impl Channel {
@@ -259,16 +467,31 @@ impl Channel {
             is_active: false,
             frequency_hz: 440.0, // Default to A4
             phase: 0.0,
+            sub_phase: 0.0,
             instrument_id: 1, // Default to sine
-            instrument_parameters: Vec::new(),
+            instrument_parameters: Vec::with_capacity(PREALLOCATED_INSTRUMENT_PARAMETERS),
+            grains: GrainCloud::default(),
+            input_samples: Vec::with_capacity(RENDER_BLOCK_SIZE),
+            input_read: 0,
+            stage_timer: StageTimer::default(),
             envelope: EnvelopeState::new_default(sample_rate),
             effects,
-            effect_transition: None,
+            effect_ramps: [None; EffectParameter::ALL.len()],
+            custom_effects: custom_effect_slots(),
+            oversamplers: ChannelOversamplers::default(),
             pitch_slide: None,
             crossfade: None,
             random_generator: RandomNumberGenerator::from_channel_id(channel_id),
             sample_rate,
+            seconds_per_beat: DEFAULT_SECONDS_PER_BEAT,
             total_samples_processed: 0,
+            peak_level: 0.0,
+            audible: true,
+            mute_gain: 1.0,
+            last_sample: 0.0,
+            declick_from: None,
+            declick_offset: 0.0,
+            key_click_gain: 0.0,
         }
     }
 
@@ -281,12 +504,15 @@ impl Channel {
     /// - new_effects: The effect settings for this note
     /// - transition_seconds: How long to transition (0 = instant)
     /// - clear_effects: Whether to reset effects to defaults first
+    ///
+    /// Parameters and effects are copied into the channel's own storage,
+    /// so triggering a note does not allocate on the audio thread.
     pub fn trigger_note(
         &mut self,
         frequency_hz: f32,
         instrument_id: usize,
-        instrument_parameters: Vec<f32>,
-        new_effects: ChannelEffectState,
+        instrument_parameters: &[f32],
+        new_effects: &ChannelEffectState,
         transition_seconds: f32,
         clear_effects: bool,
     ) {
@@ -315,7 +541,12 @@ impl Channel {
 
             // Update instrument parameters if provided
             if !instrument_parameters.is_empty() {
-                self.instrument_parameters = instrument_parameters;
+                self.set_instrument_parameters(instrument_parameters);
+                if instrument_id == GRAIN_INSTRUMENT_ID {
+                    // Grains drift to the new position over the glide
+                    self.grains
+                        .glide(instrument_parameters, transition_seconds, self.sample_rate);
+                }
             }
 
             // Keep the envelope running (don't retrigger attack)
@@ -323,11 +554,18 @@ impl Channel {
         } else {
             // ---- FRESH TRIGGER (new note from scratch) ----
 
+            // Start from where a sounding note left off (fades out quickly)
+            self.declick_from = self.is_active.then_some(self.last_sample);
+
             self.is_active = true;
             self.frequency_hz = frequency_hz;
             self.instrument_id = instrument_id;
-            self.instrument_parameters = instrument_parameters;
+            self.set_instrument_parameters(instrument_parameters);
+            if instrument_id == GRAIN_INSTRUMENT_ID {
+                self.grains.start(instrument_parameters);
+            }
             self.phase = 0.0;
+            self.sub_phase = 0.0;
             self.total_samples_processed = 0;
 
             // Clear any in-progress slides/crossfades
@@ -340,6 +578,36 @@ impl Channel {
 
         // ---- HANDLE EFFECTS ----
         self.setup_effect_transition(new_effects, transition_seconds, clear_effects);
+
+        // A fresh note starts with the channel's key-click (if it has one)
+        // and restarts the LFOs that follow notes
+        if !is_smooth_transition {
+            self.key_click_gain = self.effects.click_level;
+            self.restart_lfos(LfoRestart::Note);
+        }
+    }
+
+    /// Called by the engine as each row starts (after its cells), to
+    /// restart the LFOs that follow rows
+    pub fn start_row(&mut self) {
+        self.restart_lfos(LfoRestart::Row);
+    }
+
+    /// Sends the LFOs set to restart at this moment back to the start of
+    /// their cycle
+    fn restart_lfos(&mut self, moment: LfoRestart) {
+        let restarts = self.effects.lfo_restarts;
+        let effects = &mut self.effects;
+        for (restart, phase) in [
+            (restarts.vibrato, &mut effects.vibrato_phase),
+            (restarts.tremolo, &mut effects.tremolo_phase),
+            (restarts.chorus, &mut effects.chorus_phase),
+            (restarts.wah, &mut effects.wah_phase),
+        ] {
+            if restart == moment {
+                *phase = 0.0;
+            }
+        }
     }
 
     /// Triggers a pitchless instrument (like noise)
@@ -347,8 +615,8 @@ impl Channel {
     pub fn trigger_pitchless(
         &mut self,
         instrument_id: usize,
-        instrument_parameters: Vec<f32>,
-        new_effects: ChannelEffectState,
+        instrument_parameters: &[f32],
+        new_effects: &ChannelEffectState,
         transition_seconds: f32,
         clear_effects: bool,
     ) {
@@ -363,59 +631,185 @@ impl Channel {
         );
     }
 
+    /// Replaces the instrument parameters, reusing the existing storage
+    fn set_instrument_parameters(&mut self, instrument_parameters: &[f32]) {
+        self.instrument_parameters.clear();
+        self.instrument_parameters
+            .extend_from_slice(instrument_parameters);
+    }
+
     /// Sets up an effect transition
+    ///
+    /// Only the parameters this change moves get a new ramp; ramps already
+    /// running on other parameters carry on. The channel's chorus delay
+    /// line and LFO phases stay where they are. The default states used
+    /// here have empty buffers, so nothing is allocated.
     fn setup_effect_transition(
         &mut self,
-        new_effects: ChannelEffectState,
+        new_effects: &ChannelEffectState,
         transition_seconds: f32,
         clear_effects: bool,
     ) {
+        // Where each parameter is heading (running ramps included)
+        let mut heading = ChannelEffectState::default();
+        heading.copy_parameters_from(&self.effects);
+        for (parameter, ramp) in EffectParameter::ALL.iter().zip(&self.effect_ramps) {
+            if let Some(ramp) = ramp {
+                parameter.set(&mut heading, ramp.target);
+            }
+        }
+
         // Determine what we're transitioning to
-        let target_effects = if clear_effects {
-            // Clear to defaults first, then apply any new settings
-            let mut target = ChannelEffectState::default();
-            target.initialize_chorus_buffer(self.sample_rate);
-            merge_effects(&mut target, &new_effects);
-            target
-        } else {
+        let mut target_effects = ChannelEffectState::default();
+        if !clear_effects {
             // Apply new effects on top of current
-            let mut target = self.effects.clone();
-            merge_effects(&mut target, &new_effects);
-            target
-        };
+            target_effects.copy_parameters_from(&heading);
+        }
+        merge_effects(&mut target_effects, new_effects);
+        for (parameter, beats) in synced_rate_parameters(&target_effects.synced_rates) {
+            if beats > 0.0 {
+                parameter.set(
+                    &mut target_effects,
+                    synced_rate_hz(beats, self.seconds_per_beat),
+                );
+            }
+        }
+        self.effects.synced_rates = target_effects.synced_rates;
+        self.effects.lfo_restarts = target_effects.lfo_restarts;
+        self.effects.chorus_feedback = target_effects.chorus_feedback;
+        self.effects.chain = target_effects.chain;
+        self.effects.oversampling = target_effects.oversampling;
+        self.effects.sub_shape = target_effects.sub_shape;
+        self.effects.click_level = target_effects.click_level;
+        self.effects.wah_q = target_effects.wah_q;
+        if new_effects.vowel.is_some() || target_effects.vowel.is_none() {
+            // A vowel token starts its morph over from the first vowel
+            // ("vowel:a>o tr:1" then glides the morph up to 1)
+            self.effects.vowel = target_effects.vowel;
+            self.effects.vowel_morph = 0.0;
+            heading.vowel_morph = 0.0;
+            let morph_index = EffectParameter::ALL
+                .iter()
+                .position(|parameter| *parameter == EffectParameter::VowelMorph);
+            if let Some(index) = morph_index {
+                self.effect_ramps[index] = None;
+            }
+        }
 
-        if transition_seconds > 0.0 {
-            // Smooth transition over time
-            self.effect_transition = Some(EffectTransition::new(
-                transition_seconds,
-                self.sample_rate,
-                self.effects.clone(),
-                target_effects,
-            ));
-        } else {
-            // Instant change
-            self.effects = target_effects;
-            self.effect_transition = None;
+        for (parameter, ramp) in EffectParameter::ALL.iter().zip(&mut self.effect_ramps) {
+            let target = parameter.get(&target_effects);
+            if target == parameter.get(&heading) {
+                // Unchanged: a running ramp keeps going
+                continue;
+            }
+            if transition_seconds > 0.0 {
+                // Smooth transition over time
+                let mut start = parameter.get(&self.effects);
+                let mut target = target;
+                if *parameter == EffectParameter::DecimateRate {
+                    // A decimator that is off (0) glides as if it ran at
+                    // the output rate, so "sr:100 tr:2" drops smoothly
+                    let output_rate = self.sample_rate as f32;
+                    start = if start > 0.0 { start } else { output_rate };
+                    target = if target > 0.0 { target } else { output_rate };
+                }
+                *ramp = Some(ParameterRamp::new(
+                    start,
+                    target,
+                    transition_seconds,
+                    self.sample_rate,
+                ));
+            } else {
+                // Instant change
+                parameter.set(&mut self.effects, target);
+                *ramp = None;
+            }
+        }
+
+        // Custom effects change at once (they have no transitions)
+        if clear_effects {
+            for slot in &mut self.custom_effects {
+                slot.effect.set_parameters(&[]);
+            }
+        }
+        for (name, parameters) in &new_effects.custom_effects {
+            if let Some(slot) = self
+                .custom_effects
+                .iter_mut()
+                .find(|slot| slot.name == *name)
+            {
+                slot.effect.set_parameters(parameters);
+            }
+        }
+    }
+
+    /// Adds a custom effect to this channel, or replaces the one with the
+    /// same name (see effects::register_effect)
+    pub fn add_custom_effect(&mut self, slot: CustomEffectSlot) {
+        match self
+            .custom_effects
+            .iter_mut()
+            .find(|existing| existing.name == slot.name)
+        {
+            Some(existing) => *existing = slot,
+            None => self.custom_effects.push(slot),
         }
     }
 
     /// Releases the note (starts the release phase of the envelope)
     pub fn release(&mut self, release_time_seconds: f32) {
         if self.is_active && self.envelope.current_phase != EnvelopePhase::Release {
-            self.envelope.release_with_time(release_time_seconds);
+            self.envelope
+                .release_with_time(release_time_seconds.max(DECLICK_SECONDS));
         }
     }
 
     /// Updates effects without triggering a new note
     pub fn update_effects(
         &mut self,
-        new_effects: ChannelEffectState,
+        new_effects: &ChannelEffectState,
         transition_seconds: f32,
         clear_effects: bool,
     ) {
         self.setup_effect_transition(new_effects, transition_seconds, clear_effects);
     }
 
+    /// Sets the tempo for LFO rates set as note lengths, and moves any that
+    /// are running to the new tempo straight away
+    pub fn set_tempo(&mut self, seconds_per_beat: f32) {
+        self.seconds_per_beat = seconds_per_beat;
+        for (parameter, beats) in synced_rate_parameters(&self.effects.synced_rates) {
+            if beats > 0.0 {
+                parameter.set(&mut self.effects, synced_rate_hz(beats, seconds_per_beat));
+                if let Some(index) = EffectParameter::ALL.iter().position(|p| *p == parameter) {
+                    self.effect_ramps[index] = None;
+                }
+            }
+        }
+    }
+
+    /// Shifts the sounding note by a number of semitones, gliding over the
+    /// given time (used by "master transpose:N tr:X")
+    pub fn transpose(&mut self, semitones: f32, transition_seconds: f32) {
+        if !self.is_active {
+            return;
+        }
+
+        // If a slide is already running, shift where it was heading
+        let current_target = self
+            .pitch_slide
+            .as_ref()
+            .map(|slide| slide.target_frequency_hz)
+            .unwrap_or(self.frequency_hz);
+        let target_frequency_hz = current_target * cents_to_frequency_ratio(semitones * 100.0);
+
+        self.pitch_slide = Some(PitchSlide::new(
+            self.frequency_hz,
+            target_frequency_hz,
+            transition_seconds,
+        ));
+    }
+
     /// Forces the envelope to sustain (keeps the note playing at sustain level)
     pub fn force_sustain(&mut self) {
         if self.is_active {
@@ -426,23 +820,74 @@ impl Channel {
     /// Renders one sample from this channel
     /// Returns (left_sample, right_sample) for stereo output
     pub fn render_sample(&mut self) -> (f32, f32) {
-        // If channel is not active, return silence
-        if !self.is_active {
-            return (0.0, 0.0);
+        let mut left = [0.0];
+        let mut right = [0.0];
+        self.render_block(&mut left, &mut right);
+        (left[0], right[0])
+    }
+
+    /// Renders a block of samples and ADDS them into `left` and `right`
+    /// (so several channels can mix into the same buffers)
+    ///
+    /// Slow-moving values (effect transitions, pitch slides, crossfades,
+    /// LFOs) are worked out once at each end of a block and ramped in
+    /// between, so the per-sample loop only runs the oscillator, envelope,
+    /// and effect math. Buffers longer than RENDER_BLOCK_SIZE are split.
+    pub fn render_block(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let total_length = left.len().min(right.len());
+        let mut offset = 0;
+        while offset < total_length && self.is_active {
+            let block_length = (total_length - offset).min(RENDER_BLOCK_SIZE);
+            self.render_sub_block(
+                &mut left[offset..offset + block_length],
+                &mut right[offset..offset + block_length],
+            );
+            offset += block_length;
         }
+    }
+
+    /// Renders up to RENDER_BLOCK_SIZE samples (see render_block)
+    fn render_sub_block(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let block_length = left.len();
+        let step = 1.0 / block_length as f32;
+        let voice_started = self.stage_timer.start();
 
         // ---- UPDATE EFFECT TRANSITION ----
-        self.update_effect_transition();
+        // Amplitude and pan glide from where the last block left off
+        let start_amplitude = self.effects.amplitude;
+        let start_pan = self.effects.pan;
+        let start_sub_level = self.effects.sub_level;
+        self.update_effect_transition(block_length as u32);
 
         // ---- UPDATE PITCH SLIDE ----
+        let start_frequency = self
+            .pitch_slide
+            .as_ref()
+            .map(|slide| slide.current_frequency())
+            .unwrap_or(self.frequency_hz);
         if let Some(ref mut slide) = self.pitch_slide {
-            self.frequency_hz = slide.current_frequency();
-            slide.advance(self.sample_rate);
-
-            if slide.is_complete() {
-                self.frequency_hz = slide.target_frequency_hz;
-            }
+            slide.advance(block_length, self.sample_rate);
+            self.frequency_hz = if slide.is_complete() {
+                slide.target_frequency_hz
+            } else {
+                slide.current_frequency()
+            };
         }
+        let end_frequency = self.frequency_hz;
+
+        // ---- UPDATE INSTRUMENT CROSSFADE ----
+        // (from_id, to_id, gains at block start, gains at block end)
+        let crossfade = self.crossfade.as_mut().map(|crossfade| {
+            let start_gains = crossfade.gains();
+            crossfade.advance(block_length, self.sample_rate);
+            (
+                crossfade.from_instrument_id,
+                crossfade.to_instrument_id,
+                start_gains,
+                crossfade.gains(),
+            )
+        });
+
         // Clean up completed slide
         if self
             .pitch_slide
@@ -454,146 +899,262 @@ impl Channel {
             self.crossfade = None; // Crossfade completes with slide
         }
 
-        // ---- CALCULATE VIBRATO ----
-        let vibrato_multiplier = calculate_vibrato_multiplier(&mut self.effects, self.sample_rate);
-        let modulated_frequency = self.frequency_hz * vibrato_multiplier;
+        // ---- CALCULATE VIBRATO AND DETUNE ----
+        let (start_vibrato, end_vibrato) =
+            advance_vibrato(&mut self.effects, self.sample_rate, block_length);
+        // Persistent per-channel offset in cents, applied after vibrato
+        let detune_ratio = if self.effects.detune_cents != 0.0 {
+            cents_to_frequency_ratio(self.effects.detune_cents)
+        } else {
+            1.0
+        };
 
         // ---- ADVANCE PHASE ----
-        let phase_increment = calculate_phase_increment(modulated_frequency, self.sample_rate);
-        self.phase += phase_increment;
-        self.phase = wrap_phase(self.phase);
+        // The phase step glides between its block-start and block-end
+        // values. Each phase depends on the one before, so this stays a
+        // plain loop.
+        let start_increment = calculate_phase_increment(
+            start_frequency * start_vibrato * detune_ratio,
+            self.sample_rate,
+        );
+        let end_increment =
+            calculate_phase_increment(end_frequency * end_vibrato * detune_ratio, self.sample_rate);
+        let mut phases = [0.0; RENDER_BLOCK_SIZE];
+        let mut sub_phases = [0.0; RENDER_BLOCK_SIZE];
+        for (index, (phase, sub_phase)) in phases[..block_length]
+            .iter_mut()
+            .zip(&mut sub_phases[..block_length])
+            .enumerate()
+        {
+            let phase_increment = lerp(start_increment, end_increment, index as f32 * step);
+            self.phase = wrap_phase(self.phase + phase_increment);
+            self.sub_phase = wrap_phase(self.sub_phase + phase_increment * 0.5);
+            *phase = self.phase;
+            *sub_phase = self.sub_phase;
+        }
+        let phases = &phases[..block_length];
+        let voice_frequency = end_frequency * end_vibrato * detune_ratio;
 
-        // ---- GENERATE SAMPLE ----
-        let raw_sample = if let Some(ref mut crossfade) = self.crossfade {
+        // ---- GENERATE SAMPLES ----
+        // Whole-block oscillator calls (vectorized for sine and trisaw)
+        let mut samples = [0.0; RENDER_BLOCK_SIZE];
+        if let Some((from_id, to_id, start_gains, end_gains)) = crossfade {
             // We're crossfading between instruments
-            let (from_gain, to_gain) = crossfade.gains();
-
-            let sample_from = generate_sample(
-                crossfade.from_instrument_id,
-                self.phase,
-                &self.instrument_parameters,
-                &mut self.random_generator,
+            let mut instrument_samples = [0.0; RENDER_BLOCK_SIZE];
+            for (instrument_id, start_gain, end_gain) in [
+                (from_id, start_gains.0, end_gains.0),
+                (to_id, start_gains.1, end_gains.1),
+            ] {
+                self.generate_instrument(
+                    instrument_id,
+                    phases,
+                    voice_frequency,
+                    &mut instrument_samples[..block_length],
+                );
+                add_ramped(
+                    &instrument_samples[..block_length],
+                    &mut samples[..block_length],
+                    start_gain,
+                    end_gain,
+                );
+            }
+        } else {
+            // Normal single-instrument playback
+            self.generate_instrument(
+                self.instrument_id,
+                phases,
+                voice_frequency,
+                &mut samples[..block_length],
             );
+        }
 
-            let sample_to = generate_sample(
-                crossfade.to_instrument_id,
-                self.phase,
-                &self.instrument_parameters,
+        // ---- ADD SUB-OSCILLATOR ----
+        let end_sub_level = self.effects.sub_level;
+        if (start_sub_level > 0.0 || end_sub_level > 0.0)
+            && instrument_requires_pitch(self.instrument_id)
+        {
+            let mut sub_samples = [0.0; RENDER_BLOCK_SIZE];
+            generate_block(
+                self.effects.sub_shape.instrument_id(),
+                &sub_phases[..block_length],
+                &[],
                 &mut self.random_generator,
+                &mut sub_samples[..block_length],
+            );
+            add_ramped(
+                &sub_samples[..block_length],
+                &mut samples[..block_length],
+                start_sub_level,
+                end_sub_level,
             );
+        }
 
-            crossfade.advance(self.sample_rate);
+        // ---- APPLY ENVELOPE ----
+        let mut envelope_end = block_length;
+        for (index, sample) in samples[..block_length].iter_mut().enumerate() {
+            *sample *= self.envelope.process_sample();
+
+            // Stop once the envelope has finished (rest of block is silent)
+            if self.envelope.is_finished() {
+                self.is_active = false;
+                envelope_end = index + 1;
+                break;
+            }
+        }
+        samples[envelope_end..block_length].fill(0.0);
 
-            sample_from * from_gain + sample_to * to_gain
-        } else {
-            // Normal single-instrument playback
-            generate_sample(
-                self.instrument_id,
-                self.phase,
-                &self.instrument_parameters,
-                &mut self.random_generator,
-            )
-        };
+        // ---- DECLICK A RETRIGGER ----
+        if let Some(from) = self.declick_from.take() {
+            self.declick_offset = from - samples[0];
+        }
+        if self.declick_offset != 0.0 {
+            // ln(1000): the offset falls 60 dB in DECLICK_SECONDS
+            let decay = (-6.9 / (DECLICK_SECONDS * self.sample_rate as f32)).exp();
+            for sample in &mut samples[..block_length] {
+                *sample += self.declick_offset;
+                self.declick_offset *= decay;
+            }
+            if self.declick_offset.abs() < 1e-6 {
+                self.declick_offset = 0.0;
+            }
+        }
 
-        // ---- APPLY ENVELOPE ----
-        let envelope_amplitude = self.envelope.process_sample();
-        let enveloped_sample = raw_sample * envelope_amplitude;
+        // ---- ADD KEY-CLICK ----
+        if self.key_click_gain > 0.0 {
+            // ln(1000): the click falls 60 dB in KEY_CLICK_SECONDS
+            let decay = (-6.9 / (KEY_CLICK_SECONDS * self.sample_rate as f32)).exp();
+            for sample in &mut samples[..block_length] {
+                *sample += self.random_generator.next_float_bipolar() * self.key_click_gain;
+                self.key_click_gain *= decay;
+            }
+            if self.key_click_gain < 1e-6 {
+                self.key_click_gain = 0.0;
+            }
+        }
+        self.last_sample = samples[block_length - 1];
+
+        // ---- APPLY MUTE ----
+        // A muted channel keeps playing silently, so it comes back in time
+        let target_gain = if self.audible { 1.0 } else { 0.0 };
+        if !self.audible || self.mute_gain < 1.0 {
+            let start_gain = self.mute_gain;
+            let fade_step = block_length as f32 / (MUTE_FADE_SECONDS * self.sample_rate as f32);
+            self.mute_gain = if target_gain > start_gain {
+                (start_gain + fade_step).min(target_gain)
+            } else {
+                (start_gain - fade_step).max(target_gain)
+            };
+            for (index, sample) in samples[..block_length].iter_mut().enumerate() {
+                *sample *= lerp(start_gain, self.mute_gain, index as f32 * step);
+            }
+        }
+
+        // ---- UPDATE LEVEL METER ----
+        // Measured before the effects, scaled by the channel's volume
+        // (and by the mute, so muted channels read silent)
+        let block_peak = samples[..block_length]
+            .iter()
+            .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+        self.peak_level = self.peak_level.max(block_peak * self.effects.amplitude);
 
         // ---- APPLY CHANNEL EFFECTS ----
-        let (left_sample, right_sample) =
-            apply_channel_effects(enveloped_sample, &mut self.effects, self.sample_rate);
+        self.stage_timer.stop(DspStage::Voices, voice_started);
+        apply_channel_effects_block(
+            &mut samples[..block_length],
+            left,
+            right,
+            &mut self.effects,
+            &mut self.custom_effects,
+            &mut self.oversamplers,
+            self.sample_rate,
+            start_amplitude,
+            start_pan,
+            &mut self.stage_timer,
+        );
 
         // ---- UPDATE STATE ----
-        self.total_samples_processed += 1;
-
-        // Check if we should deactivate (envelope finished)
-        if self.envelope.is_finished() {
-            self.is_active = false;
-        }
-
-        (left_sample, right_sample)
+        self.total_samples_processed += block_length as u64;
+        self.input_read += block_length;
     }
 
-    /// Updates the effect transition (interpolates between start and target)
-    fn update_effect_transition(&mut self) {
-        if let Some(ref mut transition) = self.effect_transition {
-            transition.elapsed_samples += 1;
-            let progress = transition.progress();
-
-            // Interpolate all effect parameters
-            self.effects.amplitude = lerp(
-                transition.start_state.amplitude,
-                transition.target_state.amplitude,
-                progress,
-            );
-            self.effects.pan = lerp(
-                transition.start_state.pan,
-                transition.target_state.pan,
-                progress,
-            );
-            self.effects.vibrato_rate_hz = lerp(
-                transition.start_state.vibrato_rate_hz,
-                transition.target_state.vibrato_rate_hz,
-                progress,
-            );
-            self.effects.vibrato_depth_semitones = lerp(
-                transition.start_state.vibrato_depth_semitones,
-                transition.target_state.vibrato_depth_semitones,
-                progress,
-            );
-            self.effects.tremolo_rate_hz = lerp(
-                transition.start_state.tremolo_rate_hz,
-                transition.target_state.tremolo_rate_hz,
-                progress,
-            );
-            self.effects.tremolo_depth = lerp(
-                transition.start_state.tremolo_depth,
-                transition.target_state.tremolo_depth,
-                progress,
-            );
-            self.effects.distortion_amount = lerp(
-                transition.start_state.distortion_amount,
-                transition.target_state.distortion_amount,
-                progress,
-            );
-            self.effects.chorus_mix = lerp(
-                transition.start_state.chorus_mix,
-                transition.target_state.chorus_mix,
-                progress,
-            );
-            self.effects.chorus_rate_hz = lerp(
-                transition.start_state.chorus_rate_hz,
-                transition.target_state.chorus_rate_hz,
-                progress,
-            );
-            self.effects.chorus_depth_ms = lerp(
-                transition.start_state.chorus_depth_ms,
-                transition.target_state.chorus_depth_ms,
-                progress,
+    /// Fills `output` with an instrument's samples for a block of phases
+    /// The grain instrument plays the channel's grain cloud at the given
+    /// frequency instead (it has no waveform to read phases from), and the
+    /// input instrument copies the captured audio (silence once it runs out).
+    fn generate_instrument(
+        &mut self,
+        instrument_id: usize,
+        phases: &[f32],
+        frequency_hz: f32,
+        output: &mut [f32],
+    ) {
+        if instrument_id == GRAIN_INSTRUMENT_ID {
+            self.grains.render(
+                &self.instrument_parameters,
+                frequency_hz,
+                self.sample_rate,
+                &mut self.random_generator,
+                output,
             );
-
-            // Bitcrush interpolates as float then rounds
-            let bitcrush_float = lerp(
-                transition.start_state.bitcrush_bits as f32,
-                transition.target_state.bitcrush_bits as f32,
-                progress,
+        } else if instrument_id == INPUT_INSTRUMENT_ID {
+            let available = self.input_samples.get(self.input_read..).unwrap_or(&[]);
+            let length = available.len().min(output.len());
+            output[..length].copy_from_slice(&available[..length]);
+            output[length..].fill(0.0);
+        } else {
+            generate_block(
+                instrument_id,
+                phases,
+                &self.instrument_parameters,
+                &mut self.random_generator,
+                output,
             );
-            self.effects.bitcrush_bits = bitcrush_float.round() as u8;
+        }
+    }
 
-            // Check if transition is complete
-            if transition.is_complete() {
-                self.effects = transition.target_state.clone();
+    /// Updates the effect transition (moves every running ramp)
+    /// Moves the transition forward by a number of samples
+    fn update_effect_transition(&mut self, samples: u32) {
+        for (parameter, slot) in EffectParameter::ALL.iter().zip(&mut self.effect_ramps) {
+            if let Some(ramp) = slot {
+                parameter.set(&mut self.effects, ramp.advance(samples));
+                if ramp.is_complete() {
+                    *slot = None;
+                }
             }
         }
+    }
 
-        // Clean up completed transition
-        if self
-            .effect_transition
-            .as_ref()
-            .map(|t| t.is_complete())
-            .unwrap_or(false)
-        {
-            self.effect_transition = None;
+    /// Whether the channel plays captured audio (or crossfades to or from it)
+    pub fn plays_audio_input(&self) -> bool {
+        self.instrument_id == INPUT_INSTRUMENT_ID
+            || self.crossfade.as_ref().is_some_and(|crossfade| {
+                crossfade.from_instrument_id == INPUT_INSTRUMENT_ID
+                    || crossfade.to_instrument_id == INPUT_INSTRUMENT_ID
+            })
+    }
+
+    /// Replaces the captured audio the input instrument plays next
+    /// Takes interleaved stereo and keeps one side or the average of both,
+    /// as the cell's parameter asks (input:1 left, input:2 right).
+    pub fn set_audio_input(&mut self, interleaved: &[f32]) {
+        let side = self.instrument_parameters.first().copied().unwrap_or(0.0) as usize;
+        self.input_samples.clear();
+        self.input_samples
+            .extend(interleaved.chunks_exact(2).map(|frame| match side {
+                1 => frame[0],
+                2 => frame[1],
+                _ => 0.5 * (frame[0] + frame[1]),
+            }));
+        self.input_read = 0;
+    }
+
+    /// Mutes or unmutes the channel (with a short fade while it plays)
+    pub fn set_audible(&mut self, audible: bool) {
+        self.audible = audible;
+        if !self.is_active {
+            // Nothing sounding, so there is nothing to fade
+            self.mute_gain = if audible { 1.0 } else { 0.0 };
         }
     }
 
@@ -601,15 +1162,30 @@ impl Channel {
     pub fn is_playing(&self) -> bool {
         self.is_active
     }
+
+    /// Returns the loudest level since the last call and starts over
+    pub fn take_peak_level(&mut self) -> f32 {
+        std::mem::take(&mut self.peak_level)
+    }
 }
 
 // ============================================================================
 // HELPER FUNCTIONS
 // ============================================================================
 
+/// Each tempo-synced LFO rate with the parameter it sets (0 beats = not synced)
+fn synced_rate_parameters(synced_rates: &SyncedRates) -> [(EffectParameter, f32); 4] {
+    [
+        (EffectParameter::VibratoRate, synced_rates.vibrato_beats),
+        (EffectParameter::TremoloRate, synced_rates.tremolo_beats),
+        (EffectParameter::ChorusRate, synced_rates.chorus_beats),
+        (EffectParameter::WahRate, synced_rates.wah_beats),
+    ]
+}
+
 /// Merges new effect values into current, only updating non-default values
 /// This allows partial effect updates (e.g., just changing amplitude)
-fn merge_effects(current: &mut ChannelEffectState, new: &ChannelEffectState) {
+pub(crate) fn merge_effects(current: &mut ChannelEffectState, new: &ChannelEffectState) {
     let default = ChannelEffectState::default();
 
     // Only update values that differ from default (meaning they were explicitly set)
@@ -619,25 +1195,63 @@ fn merge_effects(current: &mut ChannelEffectState, new: &ChannelEffectState) {
     if new.pan != default.pan {
         current.pan = new.pan;
     }
-    if new.vibrato_rate_hz != default.vibrato_rate_hz {
+    if new.detune_cents != default.detune_cents {
+        current.detune_cents = new.detune_cents;
+    }
+    // A rate set as a note length is 0 Hz until the channel works it out
+    let synced = &new.synced_rates;
+    if new.vibrato_rate_hz != default.vibrato_rate_hz || synced.vibrato_beats > 0.0 {
         current.vibrato_rate_hz = new.vibrato_rate_hz;
         current.vibrato_depth_semitones = new.vibrato_depth_semitones;
+        current.synced_rates.vibrato_beats = synced.vibrato_beats;
+        current.lfo_restarts.vibrato = new.lfo_restarts.vibrato;
     }
-    if new.tremolo_rate_hz != default.tremolo_rate_hz {
+    if new.tremolo_rate_hz != default.tremolo_rate_hz || synced.tremolo_beats > 0.0 {
         current.tremolo_rate_hz = new.tremolo_rate_hz;
         current.tremolo_depth = new.tremolo_depth;
+        current.synced_rates.tremolo_beats = synced.tremolo_beats;
+        current.lfo_restarts.tremolo = new.lfo_restarts.tremolo;
     }
     if new.bitcrush_bits != default.bitcrush_bits {
         current.bitcrush_bits = new.bitcrush_bits;
     }
+    if new.decimate_rate_hz != default.decimate_rate_hz {
+        current.decimate_rate_hz = new.decimate_rate_hz;
+    }
     if new.distortion_amount != default.distortion_amount {
         current.distortion_amount = new.distortion_amount;
     }
+    if new.oversampling != default.oversampling {
+        current.oversampling = new.oversampling;
+    }
+    if new.sub_level != default.sub_level {
+        current.sub_level = new.sub_level;
+        current.sub_shape = new.sub_shape;
+    }
+    if new.click_level != default.click_level {
+        current.click_level = new.click_level;
+    }
+    if new.wah_depth != default.wah_depth {
+        current.wah_rate_hz = new.wah_rate_hz;
+        current.wah_depth = new.wah_depth;
+        current.wah_q = new.wah_q;
+        current.synced_rates.wah_beats = synced.wah_beats;
+        current.lfo_restarts.wah = new.lfo_restarts.wah;
+    }
+    if new.vowel != default.vowel {
+        current.vowel = new.vowel;
+        current.vowel_morph = new.vowel_morph;
+    }
     if new.chorus_mix != default.chorus_mix {
         current.chorus_mix = new.chorus_mix;
         current.chorus_rate_hz = new.chorus_rate_hz;
         current.chorus_depth_ms = new.chorus_depth_ms;
         current.chorus_feedback = new.chorus_feedback;
+        current.synced_rates.chorus_beats = synced.chorus_beats;
+        current.lfo_restarts.chorus = new.lfo_restarts.chorus;
+    }
+    if new.chain != default.chain {
+        current.chain = new.chain;
     }
 }
 
@@ -648,6 +1262,8 @@ fn merge_effects(current: &mut ChannelEffectState, new: &ChannelEffectState) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::effects::{ChannelStage, SubOscillatorShape, parse_chain_order};
+    use crate::wah::Vowel;
 
     #[test]
     fn test_channel_creation() {
@@ -662,19 +1278,34 @@ mod tests {
         let mut channel = Channel::new(0, 48000);
         let effects = ChannelEffectState::default();
 
-        channel.trigger_note(440.0, 1, vec![], effects, 0.0, false);
+        channel.trigger_note(440.0, 1, &[], &effects, 0.0, false);
 
         assert!(channel.is_active);
         assert_eq!(channel.frequency_hz, 440.0);
         assert_eq!(channel.instrument_id, 1);
     }
 
+    #[test]
+    fn test_detune_persists_across_notes() {
+        let mut channel = Channel::new(0, 48000);
+        let effects = ChannelEffectState {
+            detune_cents: -12.0,
+            ..Default::default()
+        };
+
+        channel.update_effects(&effects, 0.0, false);
+        channel.trigger_note(440.0, 1, &[], &ChannelEffectState::default(), 0.0, false);
+
+        // A plain note trigger keeps the channel's detune offset
+        assert_eq!(channel.effects.detune_cents, -12.0);
+    }
+
     #[test]
     fn test_channel_render() {
         let mut channel = Channel::new(0, 48000);
         let effects = ChannelEffectState::default();
 
-        channel.trigger_note(440.0, 1, vec![], effects, 0.0, false);
+        channel.trigger_note(440.0, 1, &[], &effects, 0.0, false);
 
         // Render some samples
         for _ in 0..100 {
@@ -684,4 +1315,314 @@ mod tests {
             assert!(right >= -2.0 && right <= 2.0);
         }
     }
+
+    #[test]
+    fn test_retrigger_does_not_jump() {
+        let mut channel = Channel::new(0, 48000);
+        let effects = ChannelEffectState::default();
+        channel.trigger_note(110.0, 3, &[], &effects, 0.0, false);
+
+        // Let the square wave reach full level, then restart it
+        let mut left = vec![0.0; 4850];
+        let mut right = vec![0.0; 4850];
+        channel.render_block(&mut left, &mut right);
+        let before = left[4849];
+        assert!(before.abs() > 0.1);
+
+        channel.trigger_note(110.0, 3, &[], &effects, 0.0, false);
+        let mut left = vec![0.0; 960];
+        let mut right = vec![0.0; 960];
+        channel.render_block(&mut left, &mut right);
+        assert!(
+            (left[0] - before).abs() < 0.05,
+            "jumped from {} to {}",
+            before,
+            left[0]
+        );
+        assert_eq!(channel.declick_offset, 0.0);
+    }
+
+    #[test]
+    fn test_render_block_matches_single_samples() {
+        // A steady note (no slides or LFOs) renders the same either way
+        let effects = ChannelEffectState {
+            amplitude: 0.8,
+            pan: 0.3,
+            ..Default::default()
+        };
+        let mut by_sample = Channel::new(0, 48000);
+        let mut by_block = Channel::new(0, 48000);
+        by_sample.trigger_note(440.0, 2, &[], &effects, 0.0, false);
+        by_block.trigger_note(440.0, 2, &[], &effects, 0.0, false);
+
+        // Longer than one block, so render_block has to split it
+        let mut left = vec![0.0; RENDER_BLOCK_SIZE * 3 + 5];
+        let mut right = vec![0.0; left.len()];
+        by_block.render_block(&mut left, &mut right);
+
+        for index in 0..left.len() {
+            let (expected_left, expected_right) = by_sample.render_sample();
+            assert!((left[index] - expected_left).abs() < 1e-5);
+            assert!((right[index] - expected_right).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_triggers_reuse_channel_buffers() {
+        let mut channel = Channel::new(0, 48000);
+        let chorus_buffer = channel.effects.chorus_buffer.as_ptr();
+        let parameters = channel.instrument_parameters.as_ptr();
+
+        let effects = ChannelEffectState {
+            amplitude: 0.5,
+            chorus_mix: 0.3,
+            ..Default::default()
+        };
+        channel.trigger_note(440.0, 2, &[0.3, 0.7], &effects, 0.0, false);
+        channel.trigger_note(660.0, 5, &[0.25], &effects, 0.01, true);
+        for _ in 0..1000 {
+            channel.render_sample();
+        }
+
+        // Same storage as when the channel was created: nothing reallocated
+        assert_eq!(channel.effects.chorus_buffer.as_ptr(), chorus_buffer);
+        assert_eq!(channel.instrument_parameters.as_ptr(), parameters);
+        assert_eq!(channel.instrument_parameters, vec![0.25]);
+        assert_eq!(channel.effects.amplitude, 0.5);
+    }
+
+    #[test]
+    fn test_effect_chain_order() {
+        let render = |chain| {
+            let effects = ChannelEffectState {
+                bitcrush_bits: 3,
+                distortion_amount: 0.5,
+                chain,
+                ..Default::default()
+            };
+            let mut channel = Channel::new(0, 48000);
+            channel.trigger_note(440.0, 1, &[], &effects, 0.0, false);
+            let mut left = vec![0.0; 256];
+            let mut right = vec![0.0; 256];
+            channel.render_block(&mut left, &mut right);
+            left
+        };
+
+        // Crushing after the distortion gives a different sound
+        let crush_last = parse_chain_order("d>b").unwrap();
+        assert_ne!(render(crush_last), render(ChannelStage::DEFAULT_ORDER));
+
+        // A cell that doesn't mention the chain keeps the channel's order
+        let mut channel = Channel::new(0, 48000);
+        let reordered = ChannelEffectState {
+            chain: crush_last,
+            ..Default::default()
+        };
+        channel.update_effects(&reordered, 0.0, false);
+        channel.update_effects(&ChannelEffectState::default(), 0.0, false);
+        assert_eq!(channel.effects.chain, crush_last);
+        channel.update_effects(&ChannelEffectState::default(), 0.0, true);
+        assert_eq!(channel.effects.chain, ChannelStage::DEFAULT_ORDER);
+    }
+
+    #[test]
+    fn test_overlapping_effect_transitions() {
+        let mut channel = Channel::new(0, 1000);
+        channel.trigger_note(440.0, 1, &[], &ChannelEffectState::default(), 0.0, false);
+
+        // Fade the amplitude over 1 s, then start a pan move halfway through
+        let fade = ChannelEffectState {
+            amplitude: 0.2,
+            ..Default::default()
+        };
+        channel.update_effects(&fade, 1.0, false);
+        let mut left = vec![0.0; 500];
+        let mut right = vec![0.0; 500];
+        channel.render_block(&mut left, &mut right);
+        let halfway = channel.effects.amplitude;
+        assert!((halfway - 0.6).abs() < 0.01);
+
+        let pan = ChannelEffectState {
+            pan: -0.5,
+            ..Default::default()
+        };
+        channel.update_effects(&pan, 0.25, false);
+
+        // The pan move doesn't restart or cancel the fade
+        channel.render_block(&mut left[..250], &mut right[..250]);
+        assert_eq!(channel.effects.pan, -0.5);
+        assert!((channel.effects.amplitude - 0.4).abs() < 0.01);
+        channel.render_block(&mut left[..250], &mut right[..250]);
+        assert_eq!(channel.effects.amplitude, 0.2);
+        assert!(channel.effect_ramps.iter().all(Option::is_none));
+    }
+    #[test]
+    fn test_sub_oscillator() {
+        /// Left output of a note with and without a sine sub-oscillator
+        fn render(instrument_id: usize, sub_level: f32) -> Vec<f32> {
+            let mut channel = Channel::new(0, 48000);
+            let effects = ChannelEffectState {
+                sub_level,
+                sub_shape: SubOscillatorShape::Sine,
+                ..Default::default()
+            };
+            channel.trigger_note(480.0, instrument_id, &[], &effects, 0.0, false);
+            let mut left = vec![0.0; 24000];
+            let mut right = vec![0.0; 24000];
+            channel.render_block(&mut left, &mut right);
+            left
+        }
+
+        // What the sub adds repeats every 200 samples (240 Hz), so it is
+        // upside down 100 samples (one 480 Hz cycle) later
+        let plain = render(1, 0.0);
+        let with_sub = render(1, 1.0);
+        let added = |index: usize| with_sub[index] - plain[index];
+        let loudest = (23000..23800)
+            .map(|index| added(index).abs())
+            .fold(0.0, f32::max);
+        assert!(loudest > 0.1);
+        for index in 23000..23800 {
+            assert!((added(index) + added(index + 100)).abs() < loudest * 0.02);
+        }
+
+        // Noise has no pitch to go an octave below
+        assert_eq!(render(4, 1.0), render(4, 0.0));
+    }
+
+    #[test]
+    fn test_decimator() {
+        let mut channel = Channel::new(0, 48000);
+        let effects = ChannelEffectState {
+            decimate_rate_hz: 4800.0,
+            ..Default::default()
+        };
+        channel.trigger_note(440.0, 1, &[], &effects, 0.0, false);
+        let mut left = vec![0.0; 960];
+        let mut right = vec![0.0; 960];
+        channel.render_block(&mut left, &mut right);
+
+        // Each sample is held for 10 output samples
+        let changes = left[100..900]
+            .windows(2)
+            .filter(|pair| pair[0] != pair[1])
+            .count();
+        assert!((79..=80).contains(&changes));
+
+        // Turning it off glides up from the set rate to the output rate
+        channel.update_effects(&ChannelEffectState::default(), 0.02, true);
+        channel.render_block(&mut left[..480], &mut right[..480]);
+        let halfway = channel.effects.decimate_rate_hz;
+        assert!((halfway - 26400.0).abs() < 100.0);
+    }
+
+    #[test]
+    fn test_key_click() {
+        /// Left output of a sine note with the given key-click level
+        fn render(click_level: f32) -> Vec<f32> {
+            let mut channel = Channel::new(0, 48000);
+            let effects = ChannelEffectState {
+                click_level,
+                ..Default::default()
+            };
+            channel.trigger_note(440.0, 1, &[], &effects, 0.0, false);
+            let mut left = vec![0.0; 4800];
+            let mut right = vec![0.0; 4800];
+            channel.render_block(&mut left, &mut right);
+            left
+        }
+
+        // The click is there at the start and gone after KEY_CLICK_SECONDS
+        let plain = render(0.0);
+        let clicked = render(0.5);
+        assert!(
+            plain[..48]
+                .iter()
+                .zip(&clicked)
+                .any(|(a, b)| (a - b).abs() > 0.05)
+        );
+        let click_end = (2.0 * KEY_CLICK_SECONDS * 48000.0) as usize;
+        for (a, b) in plain[click_end..].iter().zip(&clicked[click_end..]) {
+            assert!((a - b).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_lfo_restarts() {
+        let mut channel = Channel::new(0, 48000);
+        let mut effects = ChannelEffectState {
+            tremolo_rate_hz: 4.0,
+            tremolo_depth: 0.5,
+            vibrato_rate_hz: 5.0,
+            vibrato_depth_semitones: 0.3,
+            ..Default::default()
+        };
+        effects.lfo_restarts.tremolo = LfoRestart::Note;
+        effects.lfo_restarts.vibrato = LfoRestart::Row;
+        channel.trigger_note(440.0, 1, &[], &effects, 0.0, false);
+        let mut left = vec![0.0; 1000];
+        let mut right = vec![0.0; 1000];
+        channel.render_block(&mut left, &mut right);
+        assert!(channel.effects.tremolo_phase > 0.0);
+
+        // A new row restarts only the row LFO
+        channel.start_row();
+        assert_eq!(channel.effects.vibrato_phase, 0.0);
+        assert!(channel.effects.tremolo_phase > 0.0);
+
+        // A glide keeps the note LFO going; a new note restarts it
+        channel.render_block(&mut left, &mut right);
+        channel.trigger_note(330.0, 1, &[], &ChannelEffectState::default(), 0.1, false);
+        assert!(channel.effects.tremolo_phase > 0.0);
+        channel.trigger_note(440.0, 1, &[], &ChannelEffectState::default(), 0.0, false);
+        assert_eq!(channel.effects.tremolo_phase, 0.0);
+        assert!(channel.effects.vibrato_phase > 0.0);
+    }
+
+    #[test]
+    fn test_wah_and_vowel() {
+        // "vowel:a>o tr:0.02" starts at "a" and is halfway after 10 ms
+        let mut channel = Channel::new(0, 48000);
+        let effects = ChannelEffectState {
+            vowel: Some((Vowel::A, Vowel::O)),
+            vowel_morph: 1.0,
+            ..Default::default()
+        };
+        channel.trigger_note(110.0, 3, &[], &effects, 0.02, false);
+        let mut left = vec![0.0; 480];
+        let mut right = vec![0.0; 480];
+        channel.render_block(&mut left, &mut right);
+        assert!((channel.effects.vowel_morph - 0.5).abs() < 0.05);
+        assert!(left.iter().all(|sample| sample.is_finite()));
+
+        // A new pair starts over from its first vowel; without tr: it
+        // lands on the second straight away
+        let effects = ChannelEffectState {
+            vowel: Some((Vowel::O, Vowel::I)),
+            vowel_morph: 1.0,
+            ..Default::default()
+        };
+        channel.update_effects(&effects, 0.0, false);
+        assert_eq!(channel.effects.vowel, Some((Vowel::O, Vowel::I)));
+        assert_eq!(channel.effects.vowel_morph, 1.0);
+
+        // The wah changes the sound and keeps it in range
+        let render = |wah_depth: f32| {
+            let mut channel = Channel::new(0, 48000);
+            let effects = ChannelEffectState {
+                wah_rate_hz: 2.0,
+                wah_depth,
+                ..Default::default()
+            };
+            channel.trigger_note(110.0, 3, &[], &effects, 0.0, false);
+            let mut left = vec![0.0; 4800];
+            let mut right = vec![0.0; 4800];
+            channel.render_block(&mut left, &mut right);
+            left
+        };
+        let plain = render(0.0);
+        let wah = render(0.8);
+        assert!(plain.iter().zip(&wah).any(|(a, b)| (a - b).abs() > 0.05));
+        assert!(wah.iter().all(|sample| sample.abs() <= 1.0));
+    }
 }
@@ -0,0 +1,218 @@
+// ============================================================================
+// COMMAND_QUEUE.RS - Lock-Free Queue Between the Main and Audio Threads
+// ============================================================================
+//
+// The audio callback must never wait. If it blocks on a lock that the main
+// thread is holding (even for a moment), the sound card runs out of samples
+// and you hear a click or dropout.
+//
+// This module provides a single-producer, single-consumer (SPSC) ring
+// buffer: one thread pushes, the other pops, and neither ever blocks.
+// The engine uses two of them:
+//
+//   main thread  --- commands (trigger cell, jump to row, ...) --->  audio
+//   main thread  <-- events (row started, song finished) ----------  audio
+//
+// HOW IT WORKS:
+// The buffer is a fixed array of slots with two counters. The producer
+// only writes `tail` and the consumer only writes `head`, so each side can
+// read the other's counter with an atomic load and know which slots are
+// safe to touch. When the queue is full, push hands the item back instead
+// of waiting; when it is empty, pop returns None.
+// ============================================================================
+
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// ============================================================================
+// SHARED RING BUFFER
+// ============================================================================
+
+/// The storage shared by one Producer and one Consumer
+struct RingBuffer<T> {
+    /// Item slots; a slot is initialized when head <= index < tail
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+
+    /// Number of items ever popped (only the consumer writes this)
+    head: AtomicUsize,
+
+    /// Number of items ever pushed (only the producer writes this)
+    tail: AtomicUsize,
+}
+
+// SAFETY: The producer and consumer never access the same slot at the same
+// time: the producer only writes slots in [tail, head + capacity), the
+// consumer only reads slots in [head, tail), and each publishes its counter
+// with Release after finishing with a slot.
+unsafe impl<T: Send> Sync for RingBuffer<T> {}
+
+impl<T> RingBuffer<T> {
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+}
+
+impl<T> Drop for RingBuffer<T> {
+    fn drop(&mut self) {
+        // Drop any items that were pushed but never popped
+        let head = *self.head.get_mut();
+        let tail = *self.tail.get_mut();
+        for index in head..tail {
+            let slot = &mut self.slots[index % self.slots.len()];
+            // SAFETY: slots between head and tail hold initialized items
+            unsafe { slot.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+/// Creates a queue that holds up to `capacity` items
+/// Returns the sending half and the receiving half.
+pub fn command_queue<T: Send>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    let capacity = capacity.max(1);
+    let slots = (0..capacity)
+        .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+        .collect();
+    let buffer = Arc::new(RingBuffer {
+        slots,
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+    });
+    (
+        Producer {
+            buffer: Arc::clone(&buffer),
+        },
+        Consumer { buffer },
+    )
+}
+
+// ============================================================================
+// PRODUCER (SENDING HALF)
+// ============================================================================
+
+/// The sending half of a command queue (use from one thread only)
+pub struct Producer<T> {
+    buffer: Arc<RingBuffer<T>>,
+}
+
+impl<T> Producer<T> {
+    /// Adds an item without blocking
+    /// Returns the item back as Err if the queue is full.
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        let tail = self.buffer.tail.load(Ordering::Relaxed);
+        let head = self.buffer.head.load(Ordering::Acquire);
+        if tail - head >= self.buffer.capacity() {
+            return Err(item);
+        }
+
+        let slot = &self.buffer.slots[tail % self.buffer.capacity()];
+        // SAFETY: this slot is outside [head, tail), so the consumer is not
+        // reading it, and only this producer writes slots
+        unsafe { (*slot.get()).write(item) };
+        self.buffer.tail.store(tail + 1, Ordering::Release);
+        Ok(())
+    }
+
+    /// Returns true if push would fail right now
+    pub fn is_full(&self) -> bool {
+        let tail = self.buffer.tail.load(Ordering::Relaxed);
+        let head = self.buffer.head.load(Ordering::Acquire);
+        tail - head >= self.buffer.capacity()
+    }
+}
+
+// ============================================================================
+// CONSUMER (RECEIVING HALF)
+// ============================================================================
+
+/// The receiving half of a command queue (use from one thread only)
+pub struct Consumer<T> {
+    buffer: Arc<RingBuffer<T>>,
+}
+
+impl<T> Consumer<T> {
+    /// Takes the oldest item without blocking, or None if the queue is empty
+    pub fn pop(&mut self) -> Option<T> {
+        let head = self.buffer.head.load(Ordering::Relaxed);
+        let tail = self.buffer.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+
+        let slot = &self.buffer.slots[head % self.buffer.capacity()];
+        // SAFETY: this slot is inside [head, tail), so the producer finished
+        // writing it (Acquire on tail) and will not touch it until head moves
+        let item = unsafe { (*slot.get()).assume_init_read() };
+        self.buffer.head.store(head + 1, Ordering::Release);
+        Some(item)
+    }
+
+    /// Number of items waiting
+    pub fn len(&self) -> usize {
+        let head = self.buffer.head.load(Ordering::Relaxed);
+        let tail = self.buffer.tail.load(Ordering::Acquire);
+        tail - head
+    }
+
+    /// Returns true if there is nothing to pop
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_push_pop_and_full() {
+        let (mut producer, mut consumer) = command_queue(2);
+        assert_eq!(consumer.pop(), None);
+
+        assert_eq!(producer.push(1), Ok(()));
+        assert_eq!(producer.push(2), Ok(()));
+        assert!(producer.is_full());
+        assert_eq!(producer.push(3), Err(3));
+
+        assert_eq!(consumer.len(), 2);
+        assert_eq!(consumer.pop(), Some(1));
+        assert_eq!(producer.push(3), Ok(()));
+        assert_eq!(consumer.pop(), Some(2));
+        assert_eq!(consumer.pop(), Some(3));
+        assert!(consumer.is_empty());
+    }
+
+    #[test]
+    fn test_across_threads_keeps_order() {
+        let (mut producer, mut consumer) = command_queue::<String>(16);
+        let count = 10_000;
+
+        let sender = thread::spawn(move || {
+            for value in 0..count {
+                let mut item = value.to_string();
+                while let Err(returned) = producer.push(item) {
+                    item = returned;
+                    thread::yield_now();
+                }
+            }
+        });
+
+        let mut expected = 0;
+        while expected < count {
+            match consumer.pop() {
+                Some(item) => {
+                    assert_eq!(item, expected.to_string());
+                    expected += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+        sender.join().unwrap();
+    }
+}
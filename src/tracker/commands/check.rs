@@ -0,0 +1,75 @@
+// ============================================================================
+// CHECK.RS - "tracker check": Report Problems in Song Files
+// ============================================================================
+//
+//   tracker check assets/song.csv other.csv
+//
+// Prints every parser message as "file:line: ..." and exits nonzero if a
+// file has errors, so it can run in build scripts.
+// ============================================================================
+
+use std::fs;
+
+use musickbeets::helper::{A4_FREQUENCY_HZ, FrequencyTable};
+use musickbeets::parser::{AUTO_CHANNEL_COUNT, ParseDiagnostics, Severity, parse_song_collection};
+
+use crate::MISSING_CELL_BEHAVIOR;
+
+/// Parses each song file and prints every diagnostic as "file:line: ..."
+/// Returns the process exit code: 0 if no file has errors, 1 otherwise.
+/// Warnings alone do not fail the check, so it can run in build scripts.
+pub fn run(paths: &[String]) -> i32 {
+    if paths.is_empty() {
+        eprintln!("Usage: tracker check <song_file.csv>...");
+        return 1;
+    }
+
+    let frequency_table = FrequencyTable::new();
+    let mut exit_code = 0;
+
+    for path in paths {
+        let song_text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) => {
+                eprintln!("{}: error: failed to read file: {}", path, error);
+                exit_code = 1;
+                continue;
+            }
+        };
+
+        let song_collection = parse_song_collection(
+            &song_text,
+            &frequency_table,
+            A4_FREQUENCY_HZ,
+            AUTO_CHANNEL_COUNT,
+            MISSING_CELL_BEHAVIOR,
+        );
+
+        // Every song in the file, reporting shared lines only once
+        let mut diagnostics = ParseDiagnostics::default();
+        for diagnostic in &song_collection.diagnostics {
+            diagnostics.push_unique(diagnostic.clone());
+        }
+        for song_name in &song_collection.song_order {
+            for diagnostic in &song_collection.songs[song_name].diagnostics {
+                diagnostics.push_unique(diagnostic.clone());
+            }
+        }
+
+        for diagnostic in &diagnostics {
+            println!("{}", diagnostic.format_for_file(path));
+        }
+        println!(
+            "{}: {} errors, {} warnings",
+            path,
+            diagnostics.count(Severity::Error),
+            diagnostics.count(Severity::Warning)
+        );
+
+        if diagnostics.has_errors() {
+            exit_code = 1;
+        }
+    }
+
+    exit_code
+}
@@ -0,0 +1,98 @@
+// ============================================================================
+// CONVERT.RS - "tracker convert": Save a Song as JSON or MIDI
+// ============================================================================
+//
+//   tracker convert assets/song.csv song.json
+//   tracker convert assets/song.csv song.mid
+//
+// The player loads the JSON files too; the MIDI file opens in a DAW
+// (notes, velocities, and tempo).
+// ============================================================================
+
+use std::{fs, path::Path};
+
+use musickbeets::helper::{A4_FREQUENCY_HZ, FrequencyTable};
+use musickbeets::midi_export::export_midi;
+use musickbeets::parser::{AUTO_CHANNEL_COUNT, parse_song_collection};
+
+use super::is_json_path;
+use crate::{MISSING_CELL_BEHAVIOR, TICK_DURATION_SECONDS};
+
+/// Parses a CSV song and writes it as JSON or a MIDI file (by extension)
+/// Multi-song files are saved as they would play: the [chain] or first song.
+/// Returns the process exit code: 0 on success, 1 if a file can't be used.
+pub fn run(paths: &[String]) -> i32 {
+    let [input_path, output_path] = paths else {
+        eprintln!("Usage: tracker convert <song_file.csv> <song_file.json|song_file.mid>");
+        return 1;
+    };
+    let is_midi = is_midi_path(output_path);
+    if !is_json_path(output_path) && !is_midi {
+        eprintln!(
+            "{}: error: output file must end in .json or .mid",
+            output_path
+        );
+        return 1;
+    }
+
+    let song_text = match fs::read_to_string(input_path) {
+        Ok(text) => text,
+        Err(error) => {
+            eprintln!("{}: error: failed to read file: {}", input_path, error);
+            return 1;
+        }
+    };
+
+    let song_collection = parse_song_collection(
+        &song_text,
+        &FrequencyTable::new(),
+        A4_FREQUENCY_HZ,
+        AUTO_CHANNEL_COUNT,
+        MISSING_CELL_BEHAVIOR,
+    );
+    let song_data = match song_collection.select(&song_collection.default_selection()) {
+        Ok(song_data) => song_data,
+        Err(message) => {
+            eprintln!("{}: error: {}", input_path, message);
+            return 1;
+        }
+    };
+
+    // Problems are reported but don't stop the conversion, like playback
+    for diagnostic in &song_data.diagnostics {
+        println!("{}", diagnostic.format_for_file(input_path));
+    }
+
+    let output = if is_midi {
+        let tick_duration = song_data
+            .config
+            .tick_duration
+            .unwrap_or(TICK_DURATION_SECONDS);
+        export_midi(&song_data, tick_duration)
+    } else {
+        song_data.to_json().into_bytes()
+    };
+
+    match fs::write(output_path, output) {
+        Ok(()) => {
+            println!(
+                "{} -> {} ({} rows)",
+                input_path,
+                output_path,
+                song_data.row_count()
+            );
+            0
+        }
+        Err(error) => {
+            eprintln!("{}: error: failed to write file: {}", output_path, error);
+            1
+        }
+    }
+}
+
+/// Returns true for paths ending in ".mid" or ".midi" (any case)
+fn is_midi_path(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|extension| {
+        extension.eq_ignore_ascii_case("mid") || extension.eq_ignore_ascii_case("midi")
+    })
+}
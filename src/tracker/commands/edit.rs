@@ -0,0 +1,110 @@
+// ============================================================================
+// EDIT.RS - "tracker edit": Terminal Pattern Editor
+// ============================================================================
+//
+//   tracker edit assets/song.csv
+//
+// The editor itself is the library's editor.rs; this opens the file and
+// a live engine (live.rs) for Space to play rows on.
+// ============================================================================
+
+use std::fs;
+
+use musickbeets::backend::default_backend_name;
+use musickbeets::editor::{PatternEditor, run_pattern_editor};
+use musickbeets::engine::parse_sample_rate;
+use musickbeets::helper::A4_FREQUENCY_HZ;
+use musickbeets::tuning::parse_reference_frequency;
+
+use super::live::start_live_engine;
+use super::sample_rate_error;
+use crate::{CHANNEL_COUNT, DEFAULT_SAMPLE_RATE};
+
+/// Edits a song file in the terminal, auditioning rows on a live engine
+/// Usage: tracker edit <song.csv> [--a4 hz] [--backend name] [--sample-rate hz]
+/// A file that doesn't exist yet starts as an empty pattern.
+/// Returns the process exit code: 0 when closed, 1 if it can't start.
+pub fn run(args: &[String]) -> i32 {
+    const USAGE: &str =
+        "Usage: tracker edit <song.csv> [--a4 <hz>] [--backend <name>] [--sample-rate <hz>]";
+
+    let mut song_path: Option<&str> = None;
+    let mut reference_frequency_hz = A4_FREQUENCY_HZ;
+    let mut backend_name = default_backend_name().to_string();
+    let mut sample_rate = DEFAULT_SAMPLE_RATE;
+
+    let mut arg_index = 0;
+    while arg_index < args.len() {
+        let arg = args[arg_index].as_str();
+        let value = args.get(arg_index + 1).map(String::as_str);
+        match (arg, value) {
+            ("--backend", Some(value)) => backend_name = value.to_string(),
+            ("--sample-rate", Some(value)) => match parse_sample_rate(value) {
+                Some(rate) => sample_rate = rate,
+                None => {
+                    eprintln!("error: {}", sample_rate_error(value));
+                    return 1;
+                }
+            },
+            ("--a4", Some(value)) => match parse_reference_frequency(value) {
+                Some(reference) => reference_frequency_hz = reference,
+                None => {
+                    eprintln!("error: --a4 must be a frequency from 100 to 1000 Hz");
+                    return 1;
+                }
+            },
+            _ if !arg.starts_with('-') && song_path.is_none() => {
+                song_path = Some(arg);
+                arg_index += 1;
+                continue;
+            }
+            _ => {
+                eprintln!("{}", USAGE);
+                return 1;
+            }
+        }
+        arg_index += 2;
+    }
+    let Some(song_path) = song_path else {
+        eprintln!("{}", USAGE);
+        return 1;
+    };
+
+    let text = match fs::read_to_string(song_path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(error) => {
+            eprintln!("error: failed to read '{}': {}", song_path, error);
+            return 1;
+        }
+    };
+    let mut editor = PatternEditor::new(song_path, &text, CHANNEL_COUNT, reference_frequency_hz);
+
+    // Editing still works without sound (e.g., no audio device)
+    let mut engine = match start_live_engine(&backend_name, sample_rate, None, None, false) {
+        Ok(engine) => Some(engine),
+        Err(message) => {
+            eprintln!("warning: {} (rows won't play)", message);
+            None
+        }
+    };
+    let result = run_pattern_editor(
+        &mut editor,
+        engine.as_mut().map(|(_, controller)| controller),
+    );
+    if let Some((mut backend, _)) = engine {
+        backend.stop();
+    }
+
+    match result {
+        Ok(()) if editor.is_dirty() => {
+            println!("Closed {} without saving the last changes.", song_path);
+            0
+        }
+        Ok(()) => 0,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            1
+        }
+    }
+}
@@ -0,0 +1,91 @@
+// ============================================================================
+// EXPORT.RS - WAV Export Before Playing
+// ============================================================================
+//
+// Unless the song's [config] says "export_wav = false", the player renders
+// the whole song to a WAV file next to it (as fast as the CPU allows,
+// across all cores) before playing it. NORMALIZE_WAV, NORMALIZE_TARGET_PEAK,
+// and DITHER_WAV in main.rs set how the file is written.
+// ============================================================================
+
+use std::path::Path;
+
+use musickbeets::audio::{analyze_audio, generate_wav_filename, write_wav_file};
+use musickbeets::engine::{EngineConfig, PlaybackEngine};
+use musickbeets::parser::SongData;
+use musickbeets::progress::{Progress, ProgressBar, RealtimeClock};
+
+use super::play::PlaybackOptions;
+use crate::NORMALIZE_TARGET_PEAK;
+
+/// Exports the whole song to a WAV file (muted channels are left out)
+pub fn run(
+    song_data: SongData,
+    engine_config: EngineConfig,
+    song_path: &str,
+    normalize_wav: bool,
+    dither_wav: bool,
+    options: &PlaybackOptions,
+) {
+    println!(
+        "\n[EXPORT] Rendering to WAV ({} threads)...",
+        rayon::current_num_threads()
+    );
+
+    // Create engine and render, showing how far it has got
+    let row_count = song_data.row_count();
+    let mut engine = PlaybackEngine::new(song_data, engine_config.clone());
+    options.apply_mutes(&mut engine);
+    let total_seconds = engine.get_total_duration_seconds();
+    let mut progress_bar = ProgressBar::new("[EXPORT]");
+    let clock = RealtimeClock::start();
+    let mut samples = engine.render_to_buffer_with_progress(|row, seconds| {
+        progress_bar.update(Progress {
+            row,
+            row_count: Some(row_count),
+            seconds,
+            total_seconds: Some(total_seconds),
+            realtime_factor: Some(clock.factor(seconds)),
+        });
+    });
+    progress_bar.finish();
+
+    // Analyze
+    let stats = analyze_audio(&samples, engine_config.sample_rate);
+    println!(
+        "[EXPORT] Rendered {} samples ({:.2}s)",
+        stats.sample_count, stats.duration_seconds
+    );
+    println!("[EXPORT] Peak amplitude: {:.3}", stats.peak_amplitude);
+    println!("[EXPORT] RMS amplitude: {:.3}", stats.rms_amplitude);
+
+    if stats.clipped_samples > 0 {
+        println!("[WARNING] {} samples clipped!", stats.clipped_samples);
+    }
+
+    // Normalize if requested
+    if normalize_wav {
+        let gain = musickbeets::audio::normalize_audio(&mut samples, NORMALIZE_TARGET_PEAK);
+        println!("[EXPORT] Normalized with gain: {:.3}", gain);
+    }
+
+    // Generate output filename
+    let wav_path = generate_wav_filename(song_path);
+    println!("[EXPORT] Writing to: {}", wav_path);
+
+    // Write WAV file
+    match write_wav_file(
+        Path::new(&wav_path),
+        &samples,
+        engine_config.sample_rate,
+        false,
+        dither_wav,
+    ) {
+        Ok(()) => {
+            println!("[EXPORT] Successfully wrote WAV file!");
+        }
+        Err(error) => {
+            eprintln!("[ERROR] Failed to write WAV: {}", error);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+// ============================================================================
+// IMPORT.RS - "tracker import": MIDI and MOD Files to CSV Songs
+// ============================================================================
+//
+//   tracker import song.mid -o song.csv
+//   tracker import song.mod -o song.csv
+//
+// The conversions themselves are the library's midi_import.rs and
+// mod_import.rs.
+// ============================================================================
+
+use std::{fs, path::Path};
+
+use musickbeets::midi_import::{MidiImportOptions, import_midi_file};
+use musickbeets::mod_import::{ModImportOptions, import_mod_file, is_mod_path};
+
+use crate::CHANNEL_COUNT;
+
+/// Converts a Standard MIDI File or ProTracker MOD into a CSV song
+/// Usage: tracker import song.mid [-o song.csv] [--rows-per-beat N]
+///                               [--instrument name]
+///        tracker import song.mod [-o song.csv] [--instrument name]
+/// Without -o the CSV is written next to the imported file.
+/// Returns the process exit code: 0 on success, 1 if the file can't be used.
+pub fn run(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: tracker import <song.mid|song.mod> [-o <song.csv>] \
+                         [--rows-per-beat <n>] [--instrument <name>]";
+
+    let mut input_path: Option<&str> = None;
+    let mut output_path: Option<String> = None;
+    let mut options = MidiImportOptions {
+        max_channels: CHANNEL_COUNT,
+        ..Default::default()
+    };
+    let mut instrument: Option<String> = None;
+
+    let mut arg_index = 0;
+    while arg_index < args.len() {
+        let arg = args[arg_index].as_str();
+        let value = args.get(arg_index + 1).map(String::as_str);
+        match (arg, value) {
+            ("-o" | "--output", Some(value)) => {
+                output_path = Some(value.to_string());
+                arg_index += 1;
+            }
+            ("--rows-per-beat", Some(value)) => {
+                match value.parse::<u32>() {
+                    Ok(rows) if (1..=64).contains(&rows) => options.rows_per_beat = rows,
+                    _ => {
+                        eprintln!("error: --rows-per-beat must be 1 to 64, got '{}'", value);
+                        return 1;
+                    }
+                }
+                arg_index += 1;
+            }
+            ("--instrument", Some(value)) => {
+                instrument = Some(value.to_string());
+                arg_index += 1;
+            }
+            _ if !arg.starts_with('-') && input_path.is_none() => input_path = Some(arg),
+            _ => {
+                eprintln!("{}", USAGE);
+                return 1;
+            }
+        }
+        arg_index += 1;
+    }
+
+    let Some(input_path) = input_path else {
+        eprintln!("{}", USAGE);
+        return 1;
+    };
+    let output_path = output_path.unwrap_or_else(|| {
+        Path::new(input_path)
+            .with_extension("csv")
+            .to_string_lossy()
+            .into_owned()
+    });
+
+    // MOD files by extension, anything else is read as MIDI
+    let result = if is_mod_path(input_path) {
+        let mut mod_options = ModImportOptions::default();
+        if let Some(instrument) = instrument {
+            mod_options.instrument = instrument;
+        }
+        import_mod_file(input_path, &mod_options).map(|import| {
+            (
+                import.csv,
+                import.channel_count,
+                import.row_count,
+                import.warnings,
+            )
+        })
+    } else {
+        if let Some(instrument) = instrument {
+            options.instrument = instrument;
+        }
+        import_midi_file(input_path, &options).map(|import| {
+            (
+                import.csv,
+                import.channel_count,
+                import.row_count,
+                import.warnings,
+            )
+        })
+    };
+    let (csv, channel_count, row_count, warnings) = match result {
+        Ok(import) => import,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return 1;
+        }
+    };
+    for warning in &warnings {
+        println!("{}: warning: {}", input_path, warning);
+    }
+
+    match fs::write(&output_path, &csv) {
+        Ok(()) => {
+            println!(
+                "{} -> {} ({} channels, {} rows)",
+                input_path, output_path, channel_count, row_count
+            );
+            0
+        }
+        Err(error) => {
+            eprintln!("{}: error: failed to write file: {}", output_path, error);
+            1
+        }
+    }
+}
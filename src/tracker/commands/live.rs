@@ -0,0 +1,584 @@
+// ============================================================================
+// LIVE.RS - "tracker live": Play the Synth From a Keyboard
+// ============================================================================
+//
+//   tracker live --instrument trisaw          MIDI keyboard (--features midir)
+//   tracker live --keys --instrument trisaw   Computer keyboard (Z S X D C ...)
+//   tracker live --keys --record take.wav --capture idea.csv
+//
+// Notes become cells on a live engine that never ends (the library's
+// live.rs turns MIDI into cells). The live engine and the recording
+// options are shared with "tracker osc" and "tracker edit".
+// ============================================================================
+
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use musickbeets::backend::{AudioBackend, BackendSettings, create_backend, default_backend_name};
+use musickbeets::engine::{EngineConfig, EngineController, PlaybackEngine, parse_sample_rate};
+use musickbeets::live::{
+    DEFAULT_KEYBOARD_OCTAVE, LiveOptions, LivePlayer, computer_key_to_midi, parse_control_mapping,
+    send_actions,
+};
+use musickbeets::parser::{DEFAULT_ROWS_PER_BEAT, SongData};
+use musickbeets::recorder::{EventLog, LiveRecording, RecordTap, start_recording};
+use musickbeets::tuning::parse_reference_frequency;
+
+use super::sample_rate_error;
+use crate::{
+    CAPTURE_BPM, CHANNEL_COUNT, DEFAULT_RELEASE_SECONDS, DEFAULT_SAMPLE_RATE, FAST_RELEASE_SECONDS,
+    KEYBOARD_NOTE_SECONDS, KEYBOARD_VELOCITY, LIVE_BUFFER_SIZE, TICK_DURATION_SECONDS,
+};
+
+/// What "tracker live" and "tracker osc" save when playing stops
+pub struct LiveRecordOptions {
+    /// WAV file for the master output (--record)
+    audio: Option<String>,
+
+    /// CSV file for the cells played, with their times (--record-events)
+    events: Option<String>,
+
+    /// Song CSV for the cells played, quantized to rows (--capture)
+    song: Option<String>,
+
+    /// Row grid for --capture (--bpm, --rows-per-beat)
+    bpm: f32,
+    rows_per_beat: u32,
+}
+
+impl Default for LiveRecordOptions {
+    fn default() -> Self {
+        Self {
+            audio: None,
+            events: None,
+            song: None,
+            bpm: CAPTURE_BPM,
+            rows_per_beat: DEFAULT_ROWS_PER_BEAT as u32,
+        }
+    }
+}
+
+impl LiveRecordOptions {
+    /// Reads a recording option and its value
+    /// Returns None if arg isn't one, or the error for a bad value.
+    pub fn parse_option(&mut self, arg: &str, value: &str) -> Option<Result<(), String>> {
+        match arg {
+            "--record" => self.audio = Some(value.to_string()),
+            "--record-events" => self.events = Some(value.to_string()),
+            "--capture" => self.song = Some(value.to_string()),
+            "--bpm" => match value.parse::<f32>() {
+                Ok(bpm) if (20.0..=999.0).contains(&bpm) => self.bpm = bpm,
+                _ => return Some(Err(format!("--bpm must be 20 to 999, got '{}'", value))),
+            },
+            "--rows-per-beat" => match value.parse::<u32>() {
+                Ok(rows) if (1..=64).contains(&rows) => self.rows_per_beat = rows,
+                _ => {
+                    return Some(Err(format!(
+                        "--rows-per-beat must be 1 to 64, got '{}'",
+                        value
+                    )));
+                }
+            },
+            _ => return None,
+        }
+        Some(Ok(()))
+    }
+
+    /// Whether the cells played need logging (--record-events, --capture)
+    pub fn logs_events(&self) -> bool {
+        self.events.is_some() || self.song.is_some()
+    }
+
+    /// Starts recording the output if --record was given
+    /// The tap goes in the engine's audio callback (start_live_engine).
+    pub fn start_audio(&self, sample_rate: u32) -> (Option<RecordTap>, Option<LiveRecording>) {
+        match self.audio {
+            Some(_) => {
+                let (tap, recording) = start_recording(sample_rate);
+                (Some(tap), Some(recording))
+            }
+            None => (None, None),
+        }
+    }
+
+    /// Saves the take once the backend has stopped
+    /// Returns false if a file couldn't be written.
+    pub fn save_take(&self, recording: Option<LiveRecording>, event_log: Option<EventLog>) -> bool {
+        let mut saved = true;
+        if let (Some(path), Some(recording)) = (&self.audio, recording) {
+            let take = recording.finish();
+            if take.dropped_samples > 0 {
+                eprintln!(
+                    "warning: {} samples were dropped from the recording",
+                    take.dropped_samples
+                );
+            }
+            match take.save(Path::new(path)) {
+                Ok(()) => println!("Recorded {:.1}s to {}", take.duration_seconds(), path),
+                Err(message) => {
+                    eprintln!("error: {}: {}", path, message);
+                    saved = false;
+                }
+            }
+        }
+        let Some(event_log) = event_log else {
+            return saved;
+        };
+        if let Some(path) = &self.events {
+            match event_log.save(Path::new(path)) {
+                Ok(()) => println!("Saved {} events to {}", event_log.events.len(), path),
+                Err(message) => {
+                    eprintln!("error: {}", message);
+                    saved = false;
+                }
+            }
+        }
+        if let Some(path) = &self.song {
+            if event_log.events.is_empty() {
+                println!("Nothing was played, so {} was not written", path);
+                return saved;
+            }
+            match event_log.save_song(Path::new(path), self.bpm, self.rows_per_beat) {
+                Ok(()) => println!(
+                    "Captured the take as a song at {} BPM to {}",
+                    self.bpm, path
+                ),
+                Err(message) => {
+                    eprintln!("error: {}", message);
+                    saved = false;
+                }
+            }
+        }
+        saved
+    }
+}
+
+/// Plays the synth live, from a MIDI keyboard or the computer keyboard
+/// Usage: tracker live [--port n|name] [--instrument name] [--channel n]
+///                     [--voices n] [--midi-channel n] [--cc n=effect]...
+///                     [--a4 hz] [--backend name]
+///                     [--record take.wav] [--record-events take.csv]
+///                     [--capture song.csv] [--bpm n] [--rows-per-beat n]
+///                     [--input] [--input-cell cell] [--sample-rate hz]
+///        tracker live --keys [--instrument name] ...
+///        tracker live --list
+/// Channels and MIDI channels are counted from 1, like in a DAW.
+/// Returns the process exit code: 0 when stopped, 1 if it can't start.
+pub fn run(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: tracker live [--keys] [--port <n|name>] [--instrument <name>] \
+                         [--channel <n>] [--voices <n>] [--midi-channel <n>] \
+                         [--cc <n>=<effect>] [--a4 <hz>] [--backend <name>] \
+                         [--record <file.wav>] [--record-events <file.csv>] \
+                         [--capture <song.csv>] [--bpm <n>] [--rows-per-beat <n>] \
+                         [--input] [--input-cell <cell>] [--sample-rate <hz>] | --list";
+
+    let mut options = LiveOptions::default();
+    let mut record_options = LiveRecordOptions::default();
+    let mut sample_rate = DEFAULT_SAMPLE_RATE;
+    let mut port: Option<String> = None;
+    let mut backend_name = default_backend_name().to_string();
+    let mut list_ports = false;
+    let mut use_computer_keyboard = false;
+
+    let mut arg_index = 0;
+    while arg_index < args.len() {
+        let arg = args[arg_index].as_str();
+        let value = args.get(arg_index + 1).map(String::as_str);
+        let number = value.and_then(|value| value.parse::<usize>().ok());
+        if arg == "--list" || arg == "--keys" || arg == "--input" {
+            list_ports |= arg == "--list";
+            use_computer_keyboard |= arg == "--keys";
+            if arg == "--input" && options.input_cell.is_none() {
+                options.input_cell = Some("input".to_string());
+            }
+            arg_index += 1;
+            continue;
+        }
+        match (arg, value) {
+            ("--port", Some(value)) => port = Some(value.to_string()),
+            ("--instrument", Some(value)) => options.instrument = value.to_string(),
+            ("--backend", Some(value)) => backend_name = value.to_string(),
+            ("--input-cell", Some(value)) => options.input_cell = Some(value.to_string()),
+            ("--sample-rate", Some(value)) => match parse_sample_rate(value) {
+                Some(rate) => sample_rate = rate,
+                None => {
+                    eprintln!("error: {}", sample_rate_error(value));
+                    return 1;
+                }
+            },
+            ("--channel", Some(_)) if number.is_some_and(|n| n >= 1) => {
+                options.first_channel = number.unwrap_or(1) - 1;
+            }
+            ("--voices", Some(_)) if number.is_some_and(|n| n >= 1) => {
+                options.voices = number.unwrap_or(1);
+            }
+            ("--midi-channel", Some(_)) if number.is_some_and(|n| (1..=16).contains(&n)) => {
+                options.midi_channel = number.map(|n| n as u8 - 1);
+            }
+            ("--cc", Some(value)) => match parse_control_mapping(value) {
+                Ok((cc, target)) => {
+                    // A new mapping for a CC replaces the default one
+                    options
+                        .control_changes
+                        .retain(|(existing, _)| *existing != cc);
+                    options.control_changes.push((cc, target));
+                }
+                Err(message) => {
+                    eprintln!("error: {}", message);
+                    return 1;
+                }
+            },
+            ("--a4", Some(value)) => match parse_reference_frequency(value) {
+                Some(reference) => options.reference_frequency_hz = reference,
+                None => {
+                    eprintln!("error: --a4 must be a frequency from 100 to 1000 Hz");
+                    return 1;
+                }
+            },
+            (_, Some(value)) => match record_options.parse_option(arg, value) {
+                Some(Ok(())) => {}
+                Some(Err(message)) => {
+                    eprintln!("error: {}", message);
+                    return 1;
+                }
+                None => {
+                    eprintln!("{}", USAGE);
+                    return 1;
+                }
+            },
+            _ => {
+                eprintln!("{}", USAGE);
+                return 1;
+            }
+        }
+        arg_index += 2;
+    }
+
+    if use_computer_keyboard {
+        play_computer_keyboard(options, &backend_name, sample_rate, &record_options)
+    } else {
+        play_midi_input(
+            options,
+            port.as_deref(),
+            &backend_name,
+            sample_rate,
+            list_ports,
+            &record_options,
+        )
+    }
+}
+
+/// Starts an engine that never ends, so notes can keep coming from live
+/// input after the song (if any) has played. Returns the running backend
+/// and the controller that sends commands to it.
+/// record_tap, if given, receives a copy of everything the engine plays.
+/// With audio_input the device is opened for capture too, and channels
+/// playing the "input" instrument hear it.
+pub fn start_live_engine(
+    backend_name: &str,
+    sample_rate: u32,
+    song_data: Option<SongData>,
+    mut record_tap: Option<RecordTap>,
+    audio_input: bool,
+) -> Result<(Box<dyn AudioBackend>, EngineController), String> {
+    let mut backend = create_backend(backend_name)?;
+
+    let song_data =
+        song_data.unwrap_or_else(|| musickbeets::Song::parse("", CHANNEL_COUNT).data().clone());
+    let engine_config = EngineConfig {
+        sample_rate,
+        channel_count: CHANNEL_COUNT.max(song_data.channel_count()),
+        tick_duration_seconds: song_data
+            .config
+            .tick_duration
+            .unwrap_or(TICK_DURATION_SECONDS),
+        default_release_seconds: DEFAULT_RELEASE_SECONDS,
+        fast_release_seconds: FAST_RELEASE_SECONDS,
+    };
+    let mut engine = PlaybackEngine::new(song_data, engine_config);
+    engine.set_stop_at_end(false);
+    let (mut realtime_engine, controller) = engine.into_realtime();
+
+    let settings = BackendSettings {
+        sample_rate,
+        buffer_frames: LIVE_BUFFER_SIZE,
+    };
+    let mut render = move |input: &[f32], samples: &mut [f32]| {
+        realtime_engine.process_with_input(input, samples);
+        if let Some(tap) = &mut record_tap {
+            tap.capture(samples);
+        }
+    };
+    if audio_input {
+        backend.start_duplex(settings, Box::new(render))?;
+    } else {
+        backend.start(settings, Box::new(move |samples| render(&[], samples)))?;
+    }
+    Ok((backend, controller))
+}
+
+/// Opens the MIDI port and plays until Enter is pressed
+#[cfg(feature = "midir")]
+fn play_midi_input(
+    options: LiveOptions,
+    port: Option<&str>,
+    backend_name: &str,
+    sample_rate: u32,
+    list_ports: bool,
+    record_options: &LiveRecordOptions,
+) -> i32 {
+    use musickbeets::live::midi_input;
+
+    if list_ports {
+        return match midi_input::port_names() {
+            Ok(names) if names.is_empty() => {
+                println!("No MIDI input ports found.");
+                0
+            }
+            Ok(names) => {
+                for (index, name) in names.iter().enumerate() {
+                    println!("{}: {}", index, name);
+                }
+                0
+            }
+            Err(message) => {
+                eprintln!("error: {}", message);
+                1
+            }
+        };
+    }
+
+    let mut player = match LivePlayer::new(options.clone(), CHANNEL_COUNT) {
+        Ok(player) => player,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return 1;
+        }
+    };
+    if record_options.logs_events() {
+        player.log_events();
+    }
+    let (record_tap, recording) = record_options.start_audio(sample_rate);
+    let audio_input = options.input_cell.is_some();
+    let (mut backend, mut controller) =
+        match start_live_engine(backend_name, sample_rate, None, record_tap, audio_input) {
+            Ok(engine) => engine,
+            Err(message) => {
+                eprintln!("error: {}", message);
+                return 1;
+            }
+        };
+    send_actions(&mut controller, player.start_input().into_iter().collect());
+    if audio_input {
+        println!(
+            "Audio input plays on channel {}",
+            options.first_channel + options.voices + 1
+        );
+    }
+    let connection = match midi_input::connect(port, player, controller) {
+        Ok(connection) => connection,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            backend.stop();
+            return 1;
+        }
+    };
+
+    println!(
+        "Playing {} from '{}' on {} voices (channels {}-{}) through {}",
+        options.instrument,
+        connection.port_name,
+        options.voices,
+        options.first_channel + 1,
+        options.first_channel + options.voices,
+        backend_name
+    );
+    for (cc, target) in &options.control_changes {
+        println!("  CC {} -> {}", cc, target);
+    }
+    println!("Press Enter to stop.");
+    let mut line = String::new();
+    let _ = std::io::stdin().read_line(&mut line);
+
+    // Let the released notes fade before closing the device
+    let mut player = connection.close();
+    thread::sleep(Duration::from_secs_f32(DEFAULT_RELEASE_SECONDS));
+    backend.stop();
+    if record_options.save_take(recording, player.take_event_log()) {
+        0
+    } else {
+        1
+    }
+}
+
+/// Without the midir feature there is no MIDI input to listen to
+#[cfg(not(feature = "midir"))]
+fn play_midi_input(
+    options: LiveOptions,
+    _port: Option<&str>,
+    _backend_name: &str,
+    _sample_rate: u32,
+    _list_ports: bool,
+    _record_options: &LiveRecordOptions,
+) -> i32 {
+    // Still check the options, so mistakes show up before rebuilding
+    if let Err(message) = LivePlayer::new(options, CHANNEL_COUNT) {
+        eprintln!("error: {}", message);
+        return 1;
+    }
+    eprintln!(
+        "error: this build has no MIDI input. Rebuild with: cargo build --release --features midir"
+    );
+    eprintln!("hint: tracker live --keys plays from the computer keyboard instead");
+    1
+}
+
+/// Plays notes from the computer keyboard until Esc is pressed
+/// Terminals that report key releases hold notes while the key is down;
+/// others only report presses, so notes end after KEYBOARD_NOTE_SECONDS
+/// (key repeat keeps a held key going).
+fn play_computer_keyboard(
+    options: LiveOptions,
+    backend_name: &str,
+    sample_rate: u32,
+    record_options: &LiveRecordOptions,
+) -> i32 {
+    use crossterm::event::{
+        self, Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    };
+    use crossterm::terminal;
+    use std::collections::HashMap;
+    use std::io::Write;
+
+    let mut player = match LivePlayer::new(options.clone(), CHANNEL_COUNT) {
+        Ok(player) => player,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return 1;
+        }
+    };
+    if record_options.logs_events() {
+        player.log_events();
+    }
+    let (record_tap, recording) = record_options.start_audio(sample_rate);
+    let audio_input = options.input_cell.is_some();
+    let (mut backend, mut controller) =
+        match start_live_engine(backend_name, sample_rate, None, record_tap, audio_input) {
+            Ok(engine) => engine,
+            Err(message) => {
+                eprintln!("error: {}", message);
+                return 1;
+            }
+        };
+    send_actions(&mut controller, player.start_input().into_iter().collect());
+    if audio_input {
+        println!(
+            "Audio input plays on channel {}",
+            options.first_channel + options.voices + 1
+        );
+    }
+
+    println!(
+        "Playing {} on {} voices through {}",
+        options.instrument, options.voices, backend_name
+    );
+    println!("    S D   G H J   L ;          2 3   5 6 7   9 0");
+    println!("   Z X C V B N M , . /        Q W E R T Y U I O P");
+    println!("Up/Down: octave   Left/Right: velocity   Space: release all   Esc: quit");
+
+    if let Err(error) = terminal::enable_raw_mode() {
+        eprintln!("error: can't read single key presses: {}", error);
+        backend.stop();
+        return 1;
+    }
+    let mut stdout = std::io::stdout();
+    let has_key_release = terminal::supports_keyboard_enhancement().unwrap_or(false)
+        && crossterm::execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )
+        .is_ok();
+
+    let mut octave = DEFAULT_KEYBOARD_OCTAVE;
+    let mut velocity = KEYBOARD_VELOCITY;
+    let note_length = Duration::from_secs_f32(KEYBOARD_NOTE_SECONDS);
+
+    // Keys that are sounding: character -> (MIDI key, when it stops)
+    // The MIDI key is kept so an octave change can't strand a note
+    let mut held: HashMap<char, (u8, Instant)> = HashMap::new();
+
+    loop {
+        // Without release events, notes stop on their own
+        if !has_key_release {
+            let now = Instant::now();
+            let expired: Vec<char> = held
+                .iter()
+                .filter(|(_, (_, stop_time))| *stop_time <= now)
+                .map(|(character, _)| *character)
+                .collect();
+            for character in expired {
+                if let Some((midi_key, _)) = held.remove(&character) {
+                    send_actions(&mut controller, player.note_off(midi_key));
+                }
+            }
+        }
+
+        if !event::poll(Duration::from_millis(5)).unwrap_or(false) {
+            continue;
+        }
+        let Ok(Event::Key(key)) = event::read() else {
+            continue;
+        };
+        let is_release = key.kind == KeyEventKind::Release;
+        let old_settings = (octave, velocity);
+        match key.code {
+            KeyCode::Esc => break,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+            KeyCode::Up if !is_release => octave = (octave + 1).min(8),
+            KeyCode::Down if !is_release => octave = (octave - 1).max(0),
+            KeyCode::Right if !is_release => velocity = velocity.saturating_add(16).min(127),
+            KeyCode::Left if !is_release => velocity = velocity.saturating_sub(16).max(1),
+            KeyCode::Char(' ') if !is_release => {
+                held.clear();
+                send_actions(&mut controller, player.release_all());
+            }
+            KeyCode::Char(character) => {
+                let character = character.to_ascii_lowercase();
+                if is_release {
+                    if let Some((midi_key, _)) = held.remove(&character) {
+                        send_actions(&mut controller, player.note_off(midi_key));
+                    }
+                } else if let Some(entry) = held.get_mut(&character) {
+                    // Key repeat: keep the note going
+                    entry.1 = Instant::now() + note_length;
+                } else if let Some(midi_key) = computer_key_to_midi(character, octave) {
+                    held.insert(character, (midi_key, Instant::now() + note_length));
+                    send_actions(&mut controller, player.note_on(midi_key, velocity));
+                }
+            }
+            _ => {}
+        }
+        if (octave, velocity) != old_settings {
+            print!("\rOctave {}  Velocity {}   ", octave, velocity);
+            let _ = stdout.flush();
+        }
+    }
+
+    if has_key_release {
+        let _ = crossterm::execute!(stdout, PopKeyboardEnhancementFlags);
+    }
+    let _ = terminal::disable_raw_mode();
+    println!();
+
+    // Let the released notes fade before closing the device
+    send_actions(&mut controller, player.release_all());
+    thread::sleep(Duration::from_secs_f32(DEFAULT_RELEASE_SECONDS));
+    backend.stop();
+    if record_options.save_take(recording, player.take_event_log()) {
+        0
+    } else {
+        1
+    }
+}
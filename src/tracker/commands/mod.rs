@@ -0,0 +1,86 @@
+// ============================================================================
+// COMMANDS - The Tracker's Subcommands
+// ============================================================================
+//
+// main.rs reads the first argument and hands the rest to one of these:
+//
+//   play.rs       tracker [play] song.csv   Load a song and play it (the default)
+//   check.rs      tracker check             Report problems in song files
+//   convert.rs    tracker convert           Save a song as JSON or MIDI
+//   snapshot.rs   tracker snapshot          Engine state at a row, or a diff of two
+//   import.rs     tracker import            MIDI or MOD file -> CSV song
+//   live.rs       tracker live              Play from a MIDI or computer keyboard
+//   osc.rs        tracker osc               Play over the network with OSC
+//   edit.rs       tracker edit              Terminal pattern editor
+//
+// and the parts of playing a song that the player shares:
+//
+//   realtime.rs   Real-time playback, the progress line, Ctrl+C, MIDI clock
+//   playlist.rs   M3U playlists played back to back
+//   export.rs     WAV export before playing
+//
+// Each subcommand has a run() entry point; the ones main.rs calls return
+// the process exit code. The settings they share (sample rate, buffer
+// sizes, release times, ...) are the CONFIGURATION constants in main.rs.
+// ============================================================================
+
+use std::{fs, path::Path};
+
+use musickbeets::engine::{MAX_SAMPLE_RATE, MIN_SAMPLE_RATE};
+use musickbeets::helper::FrequencyTable;
+use musickbeets::parser::{AUTO_CHANNEL_COUNT, SongData, parse_song_collection};
+
+use crate::MISSING_CELL_BEHAVIOR;
+
+pub mod check;
+pub mod convert;
+pub mod edit;
+pub mod export;
+pub mod import;
+pub mod live;
+pub mod osc;
+pub mod play;
+pub mod playlist;
+pub mod realtime;
+pub mod snapshot;
+
+// ============================================================================
+// SHARED HELPERS
+// ============================================================================
+
+/// Returns true for paths ending in ".json" (any case)
+fn is_json_path(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
+
+/// Reads a CSV (first song, or the file's [chain]) or JSON song
+fn load_song_file(path: &str, reference_frequency_hz: f32) -> Result<SongData, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|error| format!("Failed to read song file '{}': {}", path, error))?;
+    let song_data = if is_json_path(path) {
+        SongData::from_json(&text).map_err(|message| format!("{}: {}", path, message))?
+    } else {
+        let collection = parse_song_collection(
+            &text,
+            &FrequencyTable::new(),
+            reference_frequency_hz,
+            AUTO_CHANNEL_COUNT,
+            MISSING_CELL_BEHAVIOR,
+        );
+        collection.select(&collection.default_selection())?
+    };
+    for diagnostic in song_data.diagnostics.iter() {
+        println!("{}", diagnostic.format_for_file(path));
+    }
+    Ok(song_data)
+}
+
+/// Message for a --sample-rate value parse_sample_rate turned down
+fn sample_rate_error(value: &str) -> String {
+    format!(
+        "--sample-rate must be {} to {} Hz (e.g. 44100, 48000, 96k), got '{}'",
+        MIN_SAMPLE_RATE, MAX_SAMPLE_RATE, value
+    )
+}
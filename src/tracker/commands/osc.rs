@@ -0,0 +1,181 @@
+// ============================================================================
+// OSC.RS - "tracker osc": Play the Engine Over the Network
+// ============================================================================
+//
+//   tracker osc --port 9000
+//   tracker osc assets/song.csv --input
+//
+// TouchOSC, SuperCollider, or a script can then trigger cells, move the
+// master effects, and run the transport (the library's osc.rs). It shares
+// the live engine and recording options with "tracker live" (live.rs).
+// ============================================================================
+
+use std::thread;
+use std::time::Duration;
+
+use musickbeets::backend::default_backend_name;
+use musickbeets::engine::{EngineCommand, parse_sample_rate};
+use musickbeets::helper::A4_FREQUENCY_HZ;
+use musickbeets::osc::{DEFAULT_OSC_PORT, OscRouter, OscServer};
+use musickbeets::parser::CellAction;
+use musickbeets::tuning::parse_reference_frequency;
+
+use super::live::{LiveRecordOptions, start_live_engine};
+use super::{load_song_file, sample_rate_error};
+use crate::{CHANNEL_COUNT, DEFAULT_RELEASE_SECONDS, DEFAULT_SAMPLE_RATE};
+
+/// Runs an OSC server so other programs can play the engine over the
+/// network, until Enter is pressed. With a song file, the song plays first
+/// and can be controlled with the /transport addresses.
+/// Usage: tracker osc [song.csv] [--port n] [--bind address] [--a4 hz]
+///                    [--backend name] [--record take.wav]
+///                    [--record-events take.csv] [--capture song.csv]
+///                    [--bpm n] [--rows-per-beat n] [--input]
+///                    [--sample-rate hz]
+/// Returns the process exit code: 0 when stopped, 1 if it can't start.
+pub fn run(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: tracker osc [song.csv] [--port <n>] [--bind <address>] \
+                         [--a4 <hz>] [--backend <name>] [--record <file.wav>] \
+                         [--record-events <file.csv>] [--capture <song.csv>] [--bpm <n>] \
+                         [--rows-per-beat <n>] [--input] [--sample-rate <hz>]";
+
+    let mut song_path: Option<&str> = None;
+    let mut port = DEFAULT_OSC_PORT;
+    let mut bind_host = "0.0.0.0".to_string();
+    let mut reference_frequency_hz = A4_FREQUENCY_HZ;
+    let mut backend_name = default_backend_name().to_string();
+    let mut record_options = LiveRecordOptions::default();
+    let mut audio_input = false;
+    let mut sample_rate = DEFAULT_SAMPLE_RATE;
+
+    let mut arg_index = 0;
+    while arg_index < args.len() {
+        let arg = args[arg_index].as_str();
+        let value = args.get(arg_index + 1).map(String::as_str);
+        if arg == "--input" {
+            audio_input = true;
+            arg_index += 1;
+            continue;
+        }
+        match (arg, value) {
+            ("--port", Some(value)) => match value.parse::<u16>() {
+                Ok(number) => port = number,
+                Err(_) => {
+                    eprintln!("error: --port must be 0 to 65535, got '{}'", value);
+                    return 1;
+                }
+            },
+            ("--bind", Some(value)) => bind_host = value.to_string(),
+            ("--backend", Some(value)) => backend_name = value.to_string(),
+            ("--sample-rate", Some(value)) => match parse_sample_rate(value) {
+                Some(rate) => sample_rate = rate,
+                None => {
+                    eprintln!("error: {}", sample_rate_error(value));
+                    return 1;
+                }
+            },
+            ("--a4", Some(value)) => match parse_reference_frequency(value) {
+                Some(reference) => reference_frequency_hz = reference,
+                None => {
+                    eprintln!("error: --a4 must be a frequency from 100 to 1000 Hz");
+                    return 1;
+                }
+            },
+            _ if !arg.starts_with('-') && song_path.is_none() => {
+                song_path = Some(arg);
+                arg_index += 1;
+                continue;
+            }
+            (_, Some(value)) => match record_options.parse_option(arg, value) {
+                Some(Ok(())) => {}
+                Some(Err(message)) => {
+                    eprintln!("error: {}", message);
+                    return 1;
+                }
+                None => {
+                    eprintln!("{}", USAGE);
+                    return 1;
+                }
+            },
+            _ => {
+                eprintln!("{}", USAGE);
+                return 1;
+            }
+        }
+        arg_index += 2;
+    }
+
+    // ---- Optional song to play while listening ----
+    let song_data = match song_path {
+        Some(path) => match load_song_file(path, reference_frequency_hz) {
+            Ok(song_data) => {
+                if let Some(reference) = song_data.config.reference_frequency_hz {
+                    reference_frequency_hz = reference;
+                }
+                Some(song_data)
+            }
+            Err(message) => {
+                eprintln!("error: {}", message);
+                return 1;
+            }
+        },
+        None => None,
+    };
+
+    let (record_tap, recording) = record_options.start_audio(sample_rate);
+    let (mut backend, controller) = match start_live_engine(
+        &backend_name,
+        sample_rate,
+        song_data,
+        record_tap,
+        audio_input,
+    ) {
+        Ok(engine) => engine,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return 1;
+        }
+    };
+    let mut router = OscRouter::new(CHANNEL_COUNT, reference_frequency_hz);
+    if record_options.logs_events() {
+        router.log_events();
+    }
+    let bind_address = format!("{}:{}", bind_host, port);
+    let server = match OscServer::start(&bind_address, router, controller) {
+        Ok(server) => server,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            backend.stop();
+            return 1;
+        }
+    };
+
+    println!(
+        "Listening for OSC on {} (UDP), playing through {}",
+        server.local_address, backend_name
+    );
+    println!("  /channel/<n>/trigger \"c4 sine a:0.8\"   /channel/<n>/release   /all/release");
+    println!("  /master/reverb 0.5 0.3   /transport/jump <row>   /transport/pause");
+    println!("Press Enter to stop.");
+    let mut line = String::new();
+    let _ = std::io::stdin().read_line(&mut line);
+
+    // Let the notes fade before closing the device
+    let mut event_log = None;
+    if let Some((mut controller, mut router)) = server.stop() {
+        for channel_index in 0..CHANNEL_COUNT {
+            let _ = controller.send(EngineCommand::Dispatch {
+                channel_index,
+                action: CellAction::SlowRelease,
+            });
+        }
+        event_log = router.take_event_log();
+    }
+    thread::sleep(Duration::from_secs_f32(DEFAULT_RELEASE_SECONDS));
+    backend.stop();
+    if record_options.save_take(recording, event_log) {
+        0
+    } else {
+        1
+    }
+}
@@ -0,0 +1,782 @@
+// ============================================================================
+// PLAY.RS - "tracker [play]": Load a Song and Play It
+// ============================================================================
+//
+//   tracker assets/song.csv --mute 2,3 --start-row 32 --end-row 64 --loop
+//   tracker play set.m3u --crossfade 6
+//   tracker long_song.csv --stream
+//
+// The default subcommand: reads the command line, loads the song, prints
+// what it found, exports a WAV (export.rs) unless the song says not to,
+// and plays it in real time (realtime.rs). Playlists go to playlist.rs,
+// and --stream plays the song while it is parsed.
+// ============================================================================
+
+use std::{env, fs};
+
+use musickbeets::backend::{default_backend_name, find_backend_by_name, unknown_backend_message};
+use musickbeets::engine::{EngineConfig, EngineController, PlaybackEngine, parse_sample_rate};
+use musickbeets::helper::{A4_FREQUENCY_HZ, FrequencyTable};
+use musickbeets::parser::{
+    AUTO_CHANNEL_COUNT, MAX_CHANNEL_COUNT, Severity, SongCollection, SongData,
+    parse_song_collection, split_song_names,
+};
+use musickbeets::playlist::is_playlist_path;
+use musickbeets::simd::instruction_set_name;
+use musickbeets::song_stream::{StreamedSong, stream_song_file};
+use musickbeets::tui::PlaybackView;
+use musickbeets::tuning::parse_reference_frequency;
+
+#[cfg(feature = "midir")]
+use super::realtime::play_following_clock;
+use super::realtime::play_realtime;
+use super::{export, is_json_path, playlist, sample_rate_error};
+use crate::{
+    DEFAULT_LOG_FILTER, DEFAULT_RELEASE_SECONDS, DEFAULT_SAMPLE_RATE, DITHER_WAV, EXPORT_TO_WAV,
+    FAST_RELEASE_SECONDS, LOOP_CROSSFADE_SECONDS, MISSING_CELL_BEHAVIOR, NORMALIZE_WAV,
+    PLAYLIST_CROSSFADE_SECONDS, SONG_FILE_PATH, TICK_DURATION_SECONDS, VALIDATE_ONLY,
+};
+
+/// Plays a song file, a playlist, or (without a file) SONG_FILE_PATH
+/// Usage: tracker [play] [song_file.csv|set.m3u] [--a4 <hz>] [--song <name>[,<name>...]]
+///                [--backend <name>] [--clock-out <port>] [--clock-in <port>] [--tui] [--monitor]
+///                [--mute <n>[,<n>...]] [--solo <n>[,<n>...]]
+///                [--start-row <n>] [--end-row <n>] [--loop] [--crossfade <seconds>]
+///                [--stream] [--sample-rate <hz>] [--dsp-load] [--freeze <n>[,<n>...]]
+/// Problems are printed; playback simply doesn't start.
+pub fn run(args: &[String]) {
+    // Print welcome banner
+    println!("\n╔═══════════════════════════════════════════════════════════╗");
+    println!("║   muSickBeets - CSV-Driven Music Tracker Synthesizer      ║");
+    println!("║   Version 2.0 - Modular Architecture                      ║");
+    println!("╚═══════════════════════════════════════════════════════════╝\n");
+
+    // ---- Parse Command Line Arguments ----
+    let mut song_path = SONG_FILE_PATH;
+    let mut reference_frequency_hz = A4_FREQUENCY_HZ;
+    let mut requested_songs: Vec<String> = Vec::new();
+    let mut backend_name = default_backend_name().to_string();
+    let mut clock_out_port: Option<String> = None;
+    let mut clock_in_port: Option<String> = None;
+    let mut show_tui = false;
+    let mut options = PlaybackOptions {
+        crossfade_seconds: LOOP_CROSSFADE_SECONDS,
+        ..Default::default()
+    };
+    let mut crossfade_given = false;
+    let mut start_row: Option<usize> = None;
+    let mut end_row: Option<usize> = None;
+    let mut stream = false;
+    let mut sample_rate = DEFAULT_SAMPLE_RATE;
+
+    let mut arg_index = 0;
+    while arg_index < args.len() {
+        let arg = args[arg_index].as_str();
+        if arg == "--a4" || arg.starts_with("--a4=") {
+            // Accept both "--a4 432" and "--a4=432"
+            let value = match arg.strip_prefix("--a4=") {
+                Some(value) => value,
+                None => {
+                    arg_index += 1;
+                    args.get(arg_index).map(String::as_str).unwrap_or("")
+                }
+            };
+            match parse_reference_frequency(value) {
+                Some(reference) => reference_frequency_hz = reference,
+                None => {
+                    eprintln!(
+                        "[ERROR] Invalid --a4 value '{}'. Use a frequency from 100 to 1000 Hz.",
+                        value
+                    );
+                    return;
+                }
+            }
+        } else if arg == "--song" || arg.starts_with("--song=") {
+            // "--song main" plays one song, "--song intro,main" chains several
+            let value = match arg.strip_prefix("--song=") {
+                Some(value) => value,
+                None => {
+                    arg_index += 1;
+                    args.get(arg_index).map(String::as_str).unwrap_or("")
+                }
+            };
+            requested_songs = split_song_names(value);
+            if requested_songs.is_empty() {
+                eprintln!("[ERROR] --song needs a song name (e.g. --song main)");
+                return;
+            }
+        } else if arg == "--backend" || arg.starts_with("--backend=") {
+            // "--backend cpal" picks the audio output library
+            let value = match arg.strip_prefix("--backend=") {
+                Some(value) => value,
+                None => {
+                    arg_index += 1;
+                    args.get(arg_index).map(String::as_str).unwrap_or("")
+                }
+            };
+            backend_name = value.to_string();
+        } else if arg == "--sample-rate" || arg.starts_with("--sample-rate=") {
+            // "--sample-rate 96000" renders and plays at another rate
+            let value = match arg.strip_prefix("--sample-rate=") {
+                Some(value) => value,
+                None => {
+                    arg_index += 1;
+                    args.get(arg_index).map(String::as_str).unwrap_or("")
+                }
+            };
+            match parse_sample_rate(value) {
+                Some(rate) => sample_rate = rate,
+                None => {
+                    eprintln!("[ERROR] {}", sample_rate_error(value));
+                    return;
+                }
+            }
+        } else if arg == "--clock-out" || arg.starts_with("--clock-out=") {
+            // "--clock-out 0" sends MIDI clock to a port (number or name)
+            let value = match arg.strip_prefix("--clock-out=") {
+                Some(value) => value,
+                None => {
+                    arg_index += 1;
+                    args.get(arg_index).map(String::as_str).unwrap_or("")
+                }
+            };
+            clock_out_port = Some(value.to_string());
+        } else if arg == "--clock-in" || arg.starts_with("--clock-in=") {
+            // "--clock-in 0" lets the MIDI clock on a port drive the rows
+            let value = match arg.strip_prefix("--clock-in=") {
+                Some(value) => value,
+                None => {
+                    arg_index += 1;
+                    args.get(arg_index).map(String::as_str).unwrap_or("")
+                }
+            };
+            clock_in_port = Some(value.to_string());
+        } else if arg == "--mute" || arg == "--solo" || arg == "--freeze" {
+            // "--mute 2,3" silences channels; "--solo 1" plays only those;
+            // "--freeze 4" renders channel 4 ahead and plays the recording
+            arg_index += 1;
+            let value = args.get(arg_index).map(String::as_str).unwrap_or("");
+            let channels = match parse_channel_list(value) {
+                Ok(channels) => channels,
+                Err(message) => {
+                    eprintln!("[ERROR] {}: {}", arg, message);
+                    return;
+                }
+            };
+            match arg {
+                "--mute" => options.muted.extend(channels),
+                "--solo" => options.soloed.extend(channels),
+                _ => options.frozen.extend(channels),
+            }
+        } else if arg == "--start-row" || arg == "--end-row" {
+            // "--start-row 32 --end-row 64" plays just that section
+            arg_index += 1;
+            let value = args.get(arg_index).map(String::as_str).unwrap_or("");
+            let row = match value.parse::<usize>() {
+                Ok(row) if row >= 1 => row,
+                _ => {
+                    eprintln!("[ERROR] {} needs a row number from 1, got '{}'", arg, value);
+                    return;
+                }
+            };
+            if arg == "--start-row" {
+                start_row = Some(row);
+            } else {
+                end_row = Some(row);
+            }
+        } else if arg == "--loop" {
+            // Repeat the song (or the --start-row/--end-row section)
+            options.looping = true;
+        } else if arg == "--crossfade" {
+            // "--crossfade 8" overlaps the end of the loop with its start
+            // (or, for a playlist, each song with the next)
+            arg_index += 1;
+            let value = args.get(arg_index).map(String::as_str).unwrap_or("");
+            match value.parse::<f32>() {
+                Ok(seconds) if (0.0..=60.0).contains(&seconds) => {
+                    options.crossfade_seconds = seconds;
+                    crossfade_given = true;
+                }
+                _ => {
+                    eprintln!(
+                        "[ERROR] --crossfade needs a time from 0 to 60 seconds, got '{}'",
+                        value
+                    );
+                    return;
+                }
+            }
+        } else if arg == "--stream" {
+            // Parse rows while playing instead of all before
+            stream = true;
+        } else if arg == "--tui" {
+            // Full-screen view with level meters instead of row log lines
+            show_tui = true;
+        } else if arg == "--monitor" {
+            // The --tui view, plus a rolling spectrogram of the output
+            show_tui = true;
+            options.monitor = true;
+        } else if arg == "--dsp-load" {
+            // Time every effect and print the CPU load when the song ends
+            options.dsp_load = true;
+        } else {
+            song_path = arg;
+        }
+        arg_index += 1;
+    }
+
+    let playlist = is_playlist_path(song_path);
+    if crossfade_given && !options.looping && !playlist {
+        eprintln!("[ERROR] --crossfade is for loops and playlists; add --loop.");
+        return;
+    }
+
+    if clock_out_port.is_some() || clock_in_port.is_some() {
+        if !cfg!(feature = "midir") {
+            eprintln!("[ERROR] MIDI clock support was not built into this tracker.");
+            eprintln!("[HINT] Rebuild with: cargo build --release --features midir");
+            return;
+        }
+        if clock_out_port.is_some() && clock_in_port.is_some() {
+            eprintln!("[ERROR] Use --clock-out or --clock-in, not both.");
+            return;
+        }
+        if start_row.is_some() || end_row.is_some() || options.looping {
+            eprintln!("[ERROR] MIDI clock sync plays the whole song once.");
+            eprintln!("[HINT] Leave out --start-row, --end-row, and --loop.");
+            return;
+        }
+    }
+
+    if stream {
+        let unsupported = [
+            (show_tui, "--tui"),
+            (
+                start_row.is_some() || end_row.is_some(),
+                "--start-row/--end-row",
+            ),
+            (options.looping, "--loop"),
+            (!options.frozen.is_empty(), "--freeze"),
+            (
+                clock_out_port.is_some() || clock_in_port.is_some(),
+                "MIDI clock",
+            ),
+            (!requested_songs.is_empty(), "--song"),
+            (is_json_path(song_path), "JSON songs"),
+        ];
+        if let Some((_, name)) = unsupported.iter().find(|(used, _)| *used) {
+            eprintln!(
+                "[ERROR] --stream plays one CSV song from the top; it can't be used with {}.",
+                name
+            );
+            eprintln!("[HINT] Leave out --stream to load the whole song first.");
+            return;
+        }
+    }
+
+    if playlist {
+        let unsupported = [
+            (show_tui, "--tui"),
+            (
+                start_row.is_some() || end_row.is_some(),
+                "--start-row/--end-row",
+            ),
+            (options.looping, "--loop"),
+            (!options.frozen.is_empty(), "--freeze"),
+            (stream, "--stream"),
+            (
+                clock_out_port.is_some() || clock_in_port.is_some(),
+                "MIDI clock",
+            ),
+            (!requested_songs.is_empty(), "--song"),
+        ];
+        if let Some((_, name)) = unsupported.iter().find(|(used, _)| *used) {
+            eprintln!(
+                "[ERROR] A playlist plays each song whole, once; it can't be used with {}.",
+                name
+            );
+            return;
+        }
+        if !crossfade_given {
+            options.crossfade_seconds = PLAYLIST_CROSSFADE_SECONDS;
+        }
+    }
+
+    println!("[MAIN] Song file: {}", song_path);
+    println!("[MAIN] Sample rate: {} Hz", sample_rate);
+    println!("[MAIN] Tick duration: {:.3}s", TICK_DURATION_SECONDS);
+    println!(
+        "[MAIN] Log filter: {}",
+        env::var("RUST_LOG").unwrap_or_else(|_| DEFAULT_LOG_FILTER.to_string())
+    );
+    println!("[MAIN] A4 reference: {} Hz", reference_frequency_hz);
+    println!("[MAIN] SIMD: {}", instruction_set_name());
+    if find_backend_by_name(&backend_name).is_none() {
+        eprintln!("[ERROR] {}", unknown_backend_message(&backend_name));
+        return;
+    }
+    println!("[MAIN] Audio backend: {}", backend_name);
+    options.print();
+
+    if playlist {
+        playlist::run(
+            song_path,
+            reference_frequency_hz,
+            sample_rate,
+            &backend_name,
+            &options,
+        );
+        return;
+    }
+
+    if stream {
+        play_streamed(
+            song_path,
+            reference_frequency_hz,
+            sample_rate,
+            &backend_name,
+            &options,
+        );
+        return;
+    }
+
+    // ---- Load Song File ----
+    let song_text = match fs::read_to_string(song_path) {
+        Ok(text) => {
+            println!("[MAIN] Loaded song file ({} bytes)", text.len());
+            text
+        }
+        Err(error) => {
+            eprintln!(
+                "[ERROR] Failed to read song file '{}': {}",
+                song_path, error
+            );
+            eprintln!("[HINT] Make sure the file exists and is readable.");
+            eprintln!(
+                "[HINT] Usage: tracker [song_file.csv] [--a4 <hz>] [--song <name>] [--backend <name>]"
+            );
+            return;
+        }
+    };
+
+    // ---- Initialize Frequency Table ----
+    // Pre-compute all note frequencies for fast lookup during playback
+    println!("[MAIN] Building frequency table (octaves 0-20)...");
+    let frequency_table = FrequencyTable::new();
+
+    // ---- Parse Song ----
+    println!("[MAIN] Parsing song...");
+    let song_collection = if is_json_path(song_path) {
+        // A song saved with "tracker convert" (or written by another program)
+        match SongData::from_json(&song_text) {
+            Ok(song) => SongCollection::single(song),
+            Err(message) => {
+                eprintln!("[ERROR] {}: {}", song_path, message);
+                return;
+            }
+        }
+    } else {
+        parse_song_collection(
+            &song_text,
+            &frequency_table,
+            reference_frequency_hz,
+            AUTO_CHANNEL_COUNT,
+            MISSING_CELL_BEHAVIOR,
+        )
+    };
+
+    // ---- Select Song(s) ----
+    // Files with [song name] sections hold several songs; pick which to play
+    if song_collection.song_order.len() > 1 {
+        println!(
+            "[MAIN] Songs in file: {}",
+            song_collection.song_order.join(", ")
+        );
+    }
+    let selected_songs = if requested_songs.is_empty() {
+        song_collection.default_selection()
+    } else {
+        requested_songs
+    };
+    let song_data = match song_collection.select(&selected_songs) {
+        Ok(song_data) => song_data,
+        Err(message) => {
+            eprintln!("[ERROR] {}", message);
+            return;
+        }
+    };
+    if song_collection.song_order.len() > 1 {
+        println!("[MAIN] Playing: {}", selected_songs.join(" -> "));
+    }
+
+    // Report parsing results
+    println!(
+        "[MAIN] Parsed {} rows, {} errors, {} warnings",
+        song_data.row_count(),
+        song_data.diagnostics.count(Severity::Error),
+        song_data.diagnostics.count(Severity::Warning)
+    );
+
+    // Print any errors and warnings
+    // The parser skips what it can't understand, so playback goes ahead
+    if !song_data.diagnostics.is_empty() {
+        println!("\n[PARSER MESSAGES]");
+        song_data.diagnostics.print();
+        println!();
+    }
+
+    // Validate-only mode
+    if VALIDATE_ONLY {
+        println!("[MAIN] Validate-only mode - parsing complete.");
+        if song_data.diagnostics.is_empty() {
+            println!("[MAIN] No errors found! Song is valid.");
+        } else {
+            println!(
+                "[MAIN] Found {} warnings/errors.",
+                song_data.diagnostics.len()
+            );
+        }
+        return;
+    }
+
+    // Check for empty song
+    if song_data.row_count() == 0 {
+        eprintln!("[ERROR] Song has no rows to play!");
+        return;
+    }
+
+    // ---- Apply Song Configuration Overrides ----
+    // Settings from the config row in the CSV file override the defaults
+    let tick_duration = song_data
+        .config
+        .tick_duration
+        .unwrap_or(TICK_DURATION_SECONDS);
+    let export_wav = song_data.config.export_wav.unwrap_or(EXPORT_TO_WAV);
+    let normalize_wav = song_data.config.normalize_wav.unwrap_or(NORMALIZE_WAV);
+    let dither_wav = song_data.config.dither_wav.unwrap_or(DITHER_WAV);
+
+    // Print config overrides if any were found
+    if song_data.config.has_any_settings() {
+        println!("[MAIN] Song configuration overrides:");
+        if let Some(title) = &song_data.config.title {
+            println!("[MAIN]   Title: {}", title);
+        }
+        if song_data.config.tick_duration.is_some() {
+            println!("[MAIN]   Tick duration: {:.3}s (overridden)", tick_duration);
+        }
+        if song_data.config.export_wav.is_some() {
+            println!("[MAIN]   Export WAV: {} (overridden)", export_wav);
+        }
+        if song_data.config.normalize_wav.is_some() {
+            println!("[MAIN]   Normalize WAV: {} (overridden)", normalize_wav);
+        }
+        if song_data.config.dither_wav.is_some() {
+            println!("[MAIN]   Dither WAV: {} (overridden)", dither_wav);
+        }
+        if let Some(bpm) = song_data.config.tempo_bpm {
+            println!("[MAIN]   Tempo: {} BPM", bpm);
+        }
+        if let Some(reference) = song_data.config.reference_frequency_hz {
+            println!("[MAIN]   A4 reference: {} Hz (overridden)", reference);
+        }
+        if let Some(seed) = song_data.config.seed {
+            println!("[MAIN]   Random seed: {}", seed);
+        }
+    }
+
+    // ---- Create Engine Configuration ----
+    let engine_config = EngineConfig {
+        sample_rate,
+        channel_count: song_data.channel_count().max(1),
+        tick_duration_seconds: tick_duration,
+        default_release_seconds: DEFAULT_RELEASE_SECONDS,
+        fast_release_seconds: FAST_RELEASE_SECONDS,
+    };
+
+    // Calculate duration
+    let total_duration_seconds = song_data.duration_seconds(tick_duration);
+    println!(
+        "[MAIN] Channels: {} (one per column)",
+        engine_config.channel_count
+    );
+    if song_data.channel_names.iter().any(|name| !name.is_empty()) {
+        let labels: Vec<String> = (0..engine_config.channel_count)
+            .map(|index| song_data.channel_label(index))
+            .collect();
+        println!("[MAIN]   {}", labels.join(", "));
+    }
+    println!(
+        "[MAIN] Song duration: {:.2}s ({} rows)",
+        total_duration_seconds,
+        song_data.row_count()
+    );
+
+    // ---- Rows To Play ----
+    // Rows are counted from 1 on the command line; the last row is included
+    let row_count = song_data.row_count();
+    options.start_row = start_row.map_or(0, |row| row - 1);
+    options.end_row = end_row.map_or(row_count, |row| row.min(row_count));
+    if options.start_row >= options.end_row {
+        eprintln!(
+            "[ERROR] Nothing to play: the song has {} rows, and --start-row must come before --end-row.",
+            row_count
+        );
+        return;
+    }
+    let play_duration_seconds: f32 = song_data.row_durations(tick_duration)
+        [options.start_row..options.end_row]
+        .iter()
+        .sum();
+    if start_row.is_some() || end_row.is_some() || options.looping {
+        println!(
+            "[MAIN] Playing rows {}-{}{} ({:.2}s)",
+            options.start_row + 1,
+            options.end_row,
+            if options.looping { ", looped" } else { "" },
+            play_duration_seconds
+        );
+    }
+    if options.looping && options.crossfade_seconds > 0.0 {
+        println!("[MAIN] Loop crossfade: {:.2}s", options.crossfade_seconds);
+    }
+
+    // ---- WAV Export (if enabled) ----
+    // When export_wav is true, we export first, then also play
+    if export_wav {
+        export::run(
+            song_data.clone(),
+            engine_config.clone(),
+            song_path,
+            normalize_wav,
+            dither_wav,
+            &options,
+        );
+    }
+
+    // ---- Real-Time Playback ----
+    // Always play the song (after exporting, if export was enabled)
+    #[cfg(feature = "midir")]
+    if let Some(port) = &clock_in_port {
+        play_following_clock(song_data, engine_config, port, &backend_name, &options);
+        return;
+    }
+    play_realtime(
+        song_data,
+        engine_config,
+        play_duration_seconds,
+        &backend_name,
+        clock_out_port.as_deref(),
+        show_tui,
+        &options,
+        None,
+    );
+}
+
+/// How to play the song, from the command line (rows and channels are
+/// 0-indexed here)
+#[derive(Default)]
+pub struct PlaybackOptions {
+    /// Channels muted or soloed
+    pub muted: Vec<usize>,
+    pub soloed: Vec<usize>,
+
+    /// First row to play (the rows before it are fast-forwarded)
+    pub start_row: usize,
+
+    /// Row after the last one to play
+    pub end_row: usize,
+
+    /// Whether start_row..end_row repeats until stopped
+    pub looping: bool,
+
+    /// How long the loop crossfades back into its start (0: it jumps)
+    pub crossfade_seconds: f32,
+
+    /// Whether each effect is timed and the DSP load printed at the end
+    pub dsp_load: bool,
+
+    /// Channels rendered ahead and played back as recordings
+    pub frozen: Vec<usize>,
+
+    /// Whether the terminal view shows a spectrogram of the output
+    pub monitor: bool,
+}
+
+impl PlaybackOptions {
+    /// Sets the mutes and solos on an engine before it plays
+    pub fn apply_mutes(&self, engine: &mut PlaybackEngine) {
+        for &channel_index in &self.muted {
+            engine.set_channel_muted(channel_index, true);
+        }
+        for &channel_index in &self.soloed {
+            engine.set_channel_soloed(channel_index, true);
+        }
+    }
+
+    /// Sets everything up on an engine before it plays, fast-forwarding
+    /// to the first row
+    pub fn apply(&self, engine: &mut PlaybackEngine) {
+        self.apply_mutes(engine);
+        engine.set_end_row(Some(self.end_row));
+        if self.looping {
+            engine.set_loop_start(Some(self.start_row));
+            engine.set_loop_crossfade(self.crossfade_seconds);
+        }
+        if self.start_row > 0 {
+            engine.fast_forward_to_row(self.start_row);
+        }
+    }
+
+    /// Renders the --freeze channels (after apply, so the recordings pick
+    /// up at the starting row). Returns false if one can't be frozen.
+    pub fn freeze(&self, engine: &mut PlaybackEngine) -> bool {
+        for &channel_index in &self.frozen {
+            match engine.freeze_channel(channel_index) {
+                Ok(seconds) => println!(
+                    "[MAIN] Froze channel {} ({:.1}s of audio)",
+                    channel_index + 1,
+                    seconds
+                ),
+                Err(message) => {
+                    eprintln!("[ERROR] {}", message);
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Shows the same mutes, solos, and starting point in the terminal view
+    pub fn apply_to_view(&self, view: &mut PlaybackView) {
+        for &channel_index in &self.muted {
+            view.set_muted(channel_index, true);
+        }
+        for &channel_index in &self.soloed {
+            view.set_soloed(channel_index, true);
+        }
+        view.skip_to_row(self.start_row);
+    }
+
+    fn print(&self) {
+        let numbers = |channels: &[usize]| {
+            let numbers: Vec<String> = channels
+                .iter()
+                .map(|index| (index + 1).to_string())
+                .collect();
+            numbers.join(", ")
+        };
+        if !self.muted.is_empty() {
+            println!("[MAIN] Muted channels: {}", numbers(&self.muted));
+        }
+        if !self.soloed.is_empty() {
+            println!("[MAIN] Soloed channels: {}", numbers(&self.soloed));
+        }
+        if self.dsp_load {
+            println!("[MAIN] DSP load: timing every effect");
+        }
+    }
+
+    /// After playback: the whole DSP load report with --dsp-load, and
+    /// otherwise a warning if the audio broke up
+    pub fn print_dsp_load(&self, controller: &EngineController) {
+        let report = controller.dsp_load();
+        if self.dsp_load {
+            println!();
+            for line in report.lines() {
+                println!("{}", line);
+            }
+        } else if report.xruns() > 0 {
+            println!(
+                "\n[WARNING] {} xruns: the audio fell behind (worst DSP load {:.0}%).",
+                report.xruns(),
+                report.worst_load * 100.0
+            );
+            println!("[HINT] Play with --dsp-load to see which effects cost the most.");
+        }
+    }
+}
+
+/// Parses "2,3" into channel indexes (numbers are counted from 1)
+fn parse_channel_list(text: &str) -> Result<Vec<usize>, String> {
+    text.split(',')
+        .map(|part| match part.trim().parse::<usize>() {
+            Ok(number) if (1..=MAX_CHANNEL_COUNT).contains(&number) => Ok(number - 1),
+            _ => Err(format!(
+                "'{}' is not a channel number from 1 to {}",
+                part.trim(),
+                MAX_CHANNEL_COUNT
+            )),
+        })
+        .collect()
+}
+
+/// Plays a CSV song while a background thread parses it, a few rows
+/// ahead of playback (song_stream.rs), so memory stays flat however long
+/// the song is. There is no WAV export: that needs every row up front.
+fn play_streamed(
+    song_path: &str,
+    reference_frequency_hz: f32,
+    sample_rate: u32,
+    backend_name: &str,
+    options: &PlaybackOptions,
+) {
+    println!("[MAIN] Streaming song (rows are parsed while it plays)...");
+    let StreamedSong { head, feed, parser } = match stream_song_file(
+        song_path,
+        reference_frequency_hz,
+        AUTO_CHANNEL_COUNT,
+        MISSING_CELL_BEHAVIOR,
+    ) {
+        Ok(streamed) => streamed,
+        Err(message) => {
+            eprintln!("[ERROR] {}", message);
+            return;
+        }
+    };
+
+    // Only the lines above the first row have been read so far
+    if !head.diagnostics.is_empty() {
+        println!("\n[PARSER MESSAGES]");
+        head.diagnostics.print();
+        println!();
+    }
+    if let Some(title) = &head.config.title {
+        println!("[MAIN] Title: {}", title);
+    }
+    println!("[MAIN] WAV export is skipped while streaming.");
+
+    let engine_config = EngineConfig {
+        sample_rate,
+        channel_count: head.channel_names.len().max(1),
+        tick_duration_seconds: head.config.tick_duration.unwrap_or(TICK_DURATION_SECONDS),
+        default_release_seconds: DEFAULT_RELEASE_SECONDS,
+        fast_release_seconds: FAST_RELEASE_SECONDS,
+    };
+    println!(
+        "[MAIN] Channels: {} (one per column)",
+        engine_config.channel_count
+    );
+
+    play_realtime(
+        head,
+        engine_config,
+        0.0,
+        backend_name,
+        None,
+        false,
+        options,
+        Some(feed),
+    );
+
+    // Messages about the rows, found while they played
+    let (diagnostics, late_rows) = parser.finish();
+    if !diagnostics.is_empty() {
+        println!("[PARSER MESSAGES]");
+        diagnostics.print();
+    }
+    if late_rows > 0 {
+        println!(
+            "[WARNING] Rows were not parsed in time {} times; the song played a little slow there.",
+            late_rows
+        );
+    }
+}
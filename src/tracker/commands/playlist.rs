@@ -0,0 +1,171 @@
+// ============================================================================
+// PLAYLIST.RS - Playing an M3U Playlist
+// ============================================================================
+//
+//   tracker play set.m3u --crossfade 6
+//
+// The songs play back to back on one engine: each is loaded while the one
+// before it plays and queued on the engine, which starts it the moment
+// the current song runs out of rows. Reading the file is the library's
+// playlist.rs.
+// ============================================================================
+
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use musickbeets::engine::{EngineConfig, EngineEvent, LoadedSong, PlaybackEngine};
+use musickbeets::parser::SongData;
+use musickbeets::playlist::{PlaylistEntry, read_playlist};
+
+use super::load_song_file;
+use super::play::PlaybackOptions;
+use super::realtime::{fade_out, start_audio, stop_requested_flag};
+use crate::{
+    AUDIO_BUFFER_SIZE, DEFAULT_RELEASE_SECONDS, FAST_RELEASE_SECONDS, RELEASE_TAIL_SECONDS,
+    TICK_DURATION_SECONDS,
+};
+
+/// Plays the songs of an M3U playlist back to back (playlist.rs) on one
+/// engine, so the master bus keeps running across the joins. Each song is
+/// loaded while the one before it plays; songs that fail to load are
+/// skipped. There is no WAV export: export the songs one at a time.
+pub fn run(
+    playlist_path: &str,
+    reference_frequency_hz: f32,
+    sample_rate: u32,
+    backend_name: &str,
+    options: &PlaybackOptions,
+) {
+    let entries = match read_playlist(playlist_path) {
+        Ok(entries) => entries,
+        Err(message) => {
+            eprintln!("[ERROR] {}", message);
+            return;
+        }
+    };
+    println!(
+        "[PLAYLIST] {} songs, crossfading {:.1}s between them",
+        entries.len(),
+        options.crossfade_seconds
+    );
+    println!("[MAIN] WAV export is skipped for playlists.");
+
+    // The next song that loads: its number, name, song, and settings
+    let mut upcoming = entries.iter().enumerate();
+    let mut load_next = || {
+        for (index, entry) in upcoming.by_ref() {
+            match load_playlist_song(entry, reference_frequency_hz, sample_rate) {
+                Ok((song_data, engine_config)) => {
+                    let name = entry
+                        .title
+                        .clone()
+                        .or_else(|| song_data.config.title.clone())
+                        .unwrap_or_else(|| entry.display_name());
+                    return Some((index, name, song_data, engine_config));
+                }
+                Err(message) => eprintln!("[ERROR] {} (skipped)", message),
+            }
+        }
+        None
+    };
+
+    let Some((first_index, first_name, song_data, engine_config)) = load_next() else {
+        eprintln!("[ERROR] None of the songs in the playlist could be loaded.");
+        return;
+    };
+    let mut engine = PlaybackEngine::new(song_data, engine_config);
+    options.apply_mutes(&mut engine);
+    engine.set_song_crossfade(options.crossfade_seconds);
+    engine.set_stage_timing(options.dsp_load);
+
+    // The song after it is queued before playback starts
+    let mut queued = load_next().map(|(index, name, song_data, engine_config)| {
+        engine.queue_song(LoadedSong::new(song_data, engine_config));
+        (index, name)
+    });
+
+    let (realtime_engine, mut controller) = engine.into_realtime();
+    let Some(mut backend) = start_audio(backend_name, sample_rate, realtime_engine, None) else {
+        return;
+    };
+
+    println!(
+        "\n▶ PLAYING {}/{}: {}",
+        first_index + 1,
+        entries.len(),
+        first_name
+    );
+    println!("Press Ctrl+C to stop.\n");
+
+    // Wait for the last song and its tails, loading each next song as
+    // the one before it starts
+    let stop_requested = stop_requested_flag();
+    let tail_time = Duration::from_secs_f32(RELEASE_TAIL_SECONDS);
+    let mut finished_at: Option<Instant> = None;
+    let played_to_end = 'waiting: loop {
+        while let Some(event) = controller.poll_event() {
+            match event {
+                EngineEvent::SongStarted => {
+                    if let Some((index, name)) = queued.take() {
+                        println!("▶ PLAYING {}/{}: {}", index + 1, entries.len(), name);
+                    }
+                    queued = load_next().and_then(|(index, name, song_data, engine_config)| {
+                        let song = LoadedSong::new(song_data, engine_config);
+                        controller.queue_song(song).ok().map(|_| (index, name))
+                    });
+                }
+                EngineEvent::Finished => {
+                    log::info!(target: "audio", "Last song played, waiting for tails");
+                    finished_at.get_or_insert_with(Instant::now);
+                }
+                EngineEvent::Silent => break 'waiting true,
+                EngineEvent::RowStarted(_) => {}
+            }
+        }
+        controller.free_retired_songs();
+        if stop_requested.load(Ordering::Relaxed) {
+            break false;
+        }
+        if finished_at.is_some_and(|time| time.elapsed() >= tail_time) {
+            break true;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    if !played_to_end {
+        fade_out(&mut controller);
+    }
+
+    // Let the device play what it already has before closing it
+    thread::sleep(Duration::from_secs_f32(
+        AUDIO_BUFFER_SIZE as f32 / sample_rate as f32,
+    ));
+    backend.stop();
+    controller.free_retired_songs();
+    options.print_dsp_load(&controller);
+    println!("\n[MAIN] Playlist finished!");
+}
+
+/// Loads a playlist song, with the engine settings it plays with
+fn load_playlist_song(
+    entry: &PlaylistEntry,
+    reference_frequency_hz: f32,
+    sample_rate: u32,
+) -> Result<(SongData, EngineConfig), String> {
+    let path = entry.path.to_string_lossy();
+    let song_data = load_song_file(&path, reference_frequency_hz)?;
+    if song_data.row_count() == 0 {
+        return Err(format!("{}: the song has no rows to play", path));
+    }
+    let engine_config = EngineConfig {
+        sample_rate,
+        channel_count: song_data.channel_count().max(1),
+        tick_duration_seconds: song_data
+            .config
+            .tick_duration
+            .unwrap_or(TICK_DURATION_SECONDS),
+        default_release_seconds: DEFAULT_RELEASE_SECONDS,
+        fast_release_seconds: FAST_RELEASE_SECONDS,
+    };
+    Ok((song_data, engine_config))
+}
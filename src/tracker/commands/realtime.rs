@@ -0,0 +1,504 @@
+// ============================================================================
+// REALTIME.RS - Real-Time Playback
+// ============================================================================
+//
+// Plays a loaded song on an audio backend and waits for it to end, with
+// a progress line (or the --tui view), Ctrl+C fading the music out instead
+// of cutting it, the DSP load report, and MIDI clock sent (--clock-out) or
+// followed (--clock-in). play.rs and playlist.rs both play through here.
+// ============================================================================
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use musickbeets::backend::{AudioBackend, BackendSettings, create_backend};
+#[cfg(feature = "midir")]
+use musickbeets::engine::ClockSource;
+use musickbeets::engine::{
+    EngineCommand, EngineConfig, EngineController, EngineEvent, PlaybackEngine, RealtimeEngine,
+};
+#[cfg(feature = "midir")]
+use musickbeets::midi_clock::{
+    ClockFollower, clock_schedule,
+    midi_ports::{ClockOutput, follow_clock},
+};
+use musickbeets::monitor::{MonitorTap, start_monitor};
+use musickbeets::parser::SongData;
+use musickbeets::progress::{Progress, ProgressBar, RealtimeClock};
+use musickbeets::song_stream::RowFeed;
+use musickbeets::tui::{PlaybackView, run_playback_view};
+
+use super::play::PlaybackOptions;
+use crate::{AUDIO_BUFFER_SIZE, RELEASE_TAIL_SECONDS, STOP_FADE_SECONDS};
+#[cfg(feature = "midir")]
+use crate::{LIVE_BUFFER_SIZE, MIDI_CLOCK_ROWS_PER_BEAT};
+
+/// Plays the song in real-time
+/// clock_out names a MIDI port to send clock to (needs the midir feature);
+/// show_tui swaps the progress line for the full-screen view (tui.rs).
+/// play_duration_seconds is how long the rows being played take once.
+/// row_feed streams the rows instead (song_data is then just the head).
+#[cfg_attr(not(feature = "midir"), allow(unused_variables))]
+#[allow(clippy::too_many_arguments)]
+pub fn play_realtime(
+    song_data: SongData,
+    engine_config: EngineConfig,
+    play_duration_seconds: f32,
+    backend_name: &str,
+    clock_out: Option<&str>,
+    show_tui: bool,
+    options: &PlaybackOptions,
+    row_feed: Option<RowFeed>,
+) {
+    let mut view = show_tui.then(|| {
+        let title = song_data.config.title.as_deref().unwrap_or("muSickBeets");
+        let mut view = PlaybackView::new(
+            song_data.clone(),
+            title,
+            engine_config.channel_count,
+            engine_config.tick_duration_seconds,
+        );
+        options.apply_to_view(&mut view);
+        view
+    });
+
+    // --monitor: the audio callback sends its output to the view's
+    // spectrogram through a lock-free queue
+    let monitor_tap = match &mut view {
+        Some(view) if options.monitor => {
+            let (tap, monitor) = start_monitor(engine_config.sample_rate);
+            view.set_monitor(monitor);
+            Some(tap)
+        }
+        _ => None,
+    };
+
+    // The clock is worked out before playing, so its pulses stay even
+    #[cfg(feature = "midir")]
+    let clock_messages =
+        clock_out.map(|_| clock_schedule(&song_data, engine_config.tick_duration_seconds));
+
+    // Row times for the progress line (a streamed song has no rows yet)
+    let row_durations = song_data.row_durations(engine_config.tick_duration_seconds);
+    let sample_rate = engine_config.sample_rate;
+
+    // Split the engine: the audio callback owns the RealtimeEngine, and this
+    // thread only talks to it through the lock-free controller queues
+    let mut engine = PlaybackEngine::new(song_data, engine_config);
+    let streaming = row_feed.is_some();
+    match row_feed {
+        Some(row_feed) => {
+            options.apply_mutes(&mut engine);
+            engine.set_row_feed(row_feed);
+        }
+        None => options.apply(&mut engine),
+    }
+    if !options.freeze(&mut engine) {
+        return;
+    }
+    engine.set_stage_timing(options.dsp_load);
+    let (realtime_engine, mut controller) = engine.into_realtime();
+    let Some(mut backend) = start_audio(backend_name, sample_rate, realtime_engine, monitor_tap)
+    else {
+        return;
+    };
+
+    // Delayed by one buffer, about when the first samples are heard
+    #[cfg(feature = "midir")]
+    let clock_output = match clock_messages {
+        Some(messages) => {
+            let delay_seconds = AUDIO_BUFFER_SIZE as f64 / sample_rate as f64;
+            match ClockOutput::start(clock_out, messages, delay_seconds) {
+                Ok(output) => {
+                    println!("[MIDI] Sending clock to: {}", output.port_name);
+                    Some(output)
+                }
+                Err(message) => {
+                    eprintln!("[ERROR] {}", message);
+                    backend.stop();
+                    return;
+                }
+            }
+        }
+        None => None,
+    };
+
+    if streaming {
+        println!("\n▶ PLAYING... (streaming)\n");
+    } else {
+        println!("\n▶ PLAYING... (duration: {:.2}s)\n", play_duration_seconds);
+    }
+
+    let played_to_end = if let Some(mut view) = view {
+        // The view stays up through the release tails (Q quits early)
+        let tail_time = Duration::from_secs_f32(RELEASE_TAIL_SECONDS);
+        run_playback_view(&mut view, &mut controller, tail_time).unwrap_or_else(|message| {
+            eprintln!("[ERROR] {}", message);
+            false
+        })
+    } else {
+        let stop_requested = stop_requested_flag();
+        let time_limit = if options.looping {
+            // A loop never ends by itself
+            println!("Looping. Press Enter or Ctrl+C to stop.");
+            let stop_requested = Arc::clone(&stop_requested);
+            thread::spawn(move || {
+                let mut line = String::new();
+                let _ = std::io::stdin().read_line(&mut line);
+                stop_requested.store(true, Ordering::Relaxed);
+            });
+            None
+        } else if streaming {
+            // The length isn't known until the last row is read
+            None
+        } else {
+            // In case the device stops calling back
+            Some(Duration::from_secs_f32(
+                play_duration_seconds + RELEASE_TAIL_SECONDS + 5.0,
+            ))
+        };
+        let mut progress = PlaybackProgress::new(row_durations, options);
+        wait_for_song_end(
+            &mut controller,
+            &stop_requested,
+            time_limit,
+            Some(&mut progress),
+        )
+    };
+    if !played_to_end {
+        fade_out(&mut controller);
+    }
+
+    // Let the device play what it already has before closing it
+    thread::sleep(Duration::from_secs_f32(
+        AUDIO_BUFFER_SIZE as f32 / sample_rate as f32,
+    ));
+    #[cfg(feature = "midir")]
+    if let Some(output) = clock_output {
+        output.stop();
+    }
+    backend.stop();
+    options.print_dsp_load(&controller);
+
+    // ---- Cleanup ----
+    println!("\n[MAIN] Playback finished!");
+    println!("╔═══════════════════════════════════════════════════════════╗");
+    println!("║                THANK YOU FOR LISTENING!                   ║");
+    println!("╚═══════════════════════════════════════════════════════════╝\n");
+}
+
+/// Opens the audio device and starts the realtime engine playing on it
+/// Errors are printed; returns None if playback couldn't start.
+/// monitor_tap, if given, receives a copy of everything the engine plays.
+pub fn start_audio(
+    backend_name: &str,
+    sample_rate: u32,
+    mut realtime_engine: RealtimeEngine,
+    mut monitor_tap: Option<MonitorTap>,
+) -> Option<Box<dyn AudioBackend>> {
+    // ---- Initialize Audio Device ----
+    println!("\n[AUDIO] Initializing {}...", backend_name);
+
+    let mut backend = match create_backend(backend_name) {
+        Ok(backend) => backend,
+        Err(message) => {
+            eprintln!("[ERROR] {}", message);
+            return None;
+        }
+    };
+
+    // ---- Start Playback ----
+    // The backend calls this whenever the device needs more samples
+    println!("[AUDIO] Starting playback...");
+
+    let settings = BackendSettings {
+        sample_rate,
+        buffer_frames: AUDIO_BUFFER_SIZE,
+    };
+    let render = Box::new(move |samples: &mut [f32]| {
+        realtime_engine.process(samples);
+        if let Some(tap) = &mut monitor_tap {
+            tap.capture(samples);
+        }
+    });
+    if let Err(message) = backend.start(settings, render) {
+        eprintln!("[ERROR] {}", message);
+        return None;
+    }
+    Some(backend)
+}
+
+/// A flag that Ctrl+C (or another termination signal) sets instead of
+/// killing the program, so playback can fade out and close the device
+/// A second Ctrl+C still quits at once, in case stopping hangs.
+pub fn stop_requested_flag() -> Arc<AtomicBool> {
+    let stop_requested = Arc::new(AtomicBool::new(false));
+    for &signal in signal_hook::consts::TERM_SIGNALS {
+        let registered = signal_hook::flag::register_conditional_shutdown(
+            signal,
+            1,
+            Arc::clone(&stop_requested),
+        )
+        .and_then(|_| signal_hook::flag::register(signal, Arc::clone(&stop_requested)));
+        if let Err(error) = registered {
+            log::warn!(target: "audio", "Ctrl+C will not fade out: {}", error);
+        }
+    }
+    stop_requested
+}
+
+/// Waits until the song has played and its tails have died away
+/// (at most RELEASE_TAIL_SECONDS), showing the progress line if given
+/// Returns false if playback was stopped early with stop_requested.
+fn wait_for_song_end(
+    controller: &mut EngineController,
+    stop_requested: &AtomicBool,
+    time_limit: Option<Duration>,
+    mut progress: Option<&mut PlaybackProgress>,
+) -> bool {
+    let start_time = Instant::now();
+    let tail_time = Duration::from_secs_f32(RELEASE_TAIL_SECONDS);
+    let mut finished_at: Option<Instant> = None;
+    let played_to_end = 'waiting: loop {
+        while let Some(event) = controller.poll_event() {
+            match event {
+                EngineEvent::RowStarted(row) => {
+                    log::debug!(target: "audio", "Row {}", row);
+                    if let Some(progress) = progress.as_deref_mut() {
+                        progress.row_started(row);
+                    }
+                }
+                EngineEvent::Finished => {
+                    if let Some(progress) = progress.as_deref_mut() {
+                        progress.bar.finish();
+                    }
+                    log::info!(target: "audio", "Last row played, waiting for tails");
+                    finished_at.get_or_insert_with(Instant::now);
+                }
+                EngineEvent::Silent => break 'waiting true,
+                EngineEvent::SongStarted => {}
+            }
+        }
+        if stop_requested.load(Ordering::Relaxed) {
+            break false;
+        }
+        if finished_at.is_some_and(|time| time.elapsed() >= tail_time)
+            || time_limit.is_some_and(|limit| start_time.elapsed() >= limit)
+        {
+            break true;
+        }
+        if finished_at.is_none()
+            && let Some(progress) = progress.as_deref_mut()
+        {
+            progress.draw();
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    if let Some(progress) = progress {
+        progress.bar.finish();
+    }
+    played_to_end
+}
+
+/// The progress line while a song plays (see progress.rs)
+/// Rows come from RowStarted events; between them the position moves on
+/// with the clock, so the line doesn't stand still during long rows.
+struct PlaybackProgress {
+    bar: ProgressBar,
+
+    /// How long each row plays (empty for a streamed song)
+    row_durations: Vec<f32>,
+
+    /// Seconds from the first played row to the start of each row
+    row_start_seconds: Vec<f32>,
+
+    /// Rows played (start_row..end_row) and how long they take once
+    start_row: usize,
+    end_row: usize,
+    total_seconds: f32,
+
+    /// The row playing, and when it started
+    playing: Option<(usize, Instant)>,
+
+    /// Seconds of song played before the playing row (a loop keeps
+    /// adding), and the clock the realtime factor is measured against
+    played_seconds: f32,
+    clock: Option<RealtimeClock>,
+}
+
+impl PlaybackProgress {
+    fn new(row_durations: Vec<f32>, options: &PlaybackOptions) -> Self {
+        let end_row = options.end_row.min(row_durations.len());
+        let start_row = options.start_row.min(end_row);
+        let mut row_start_seconds = vec![0.0; start_row + 1];
+        for duration in &row_durations[start_row..end_row] {
+            let last = row_start_seconds[row_start_seconds.len() - 1];
+            row_start_seconds.push(last + duration);
+        }
+        Self {
+            bar: ProgressBar::new("[PLAY]"),
+            total_seconds: row_start_seconds[row_start_seconds.len() - 1],
+            row_durations,
+            row_start_seconds,
+            start_row,
+            end_row,
+            playing: None,
+            played_seconds: 0.0,
+            clock: None,
+        }
+    }
+
+    /// Follows EngineEvent::RowStarted(next_row): the row before it is
+    /// now playing
+    fn row_started(&mut self, next_row: usize) {
+        let Some(row) = next_row.checked_sub(1) else {
+            return;
+        };
+        match self.playing {
+            // The clock starts with the first row, after the lead-in
+            None => self.clock = Some(RealtimeClock::start()),
+            Some((previous_row, _)) => {
+                self.played_seconds += self.row_durations.get(previous_row).copied().unwrap_or(0.0)
+            }
+        }
+        self.playing = Some((row, Instant::now()));
+        self.draw();
+    }
+
+    /// Redraws the line (the bar keeps it to a few times a second)
+    fn draw(&mut self) {
+        let (Some((row, row_start_time)), Some(clock)) = (self.playing, &self.clock) else {
+            return;
+        };
+        let progress = if self.row_durations.is_empty() {
+            // Streamed: only the row and the time are known
+            Progress {
+                row,
+                row_count: None,
+                seconds: clock.elapsed_seconds(),
+                total_seconds: None,
+                realtime_factor: None,
+            }
+        } else {
+            let row_duration = self.row_durations.get(row).copied().unwrap_or(0.0);
+            let into_row = row_start_time.elapsed().as_secs_f32().min(row_duration);
+            let row_start = self
+                .row_start_seconds
+                .get(row.clamp(self.start_row, self.end_row))
+                .copied()
+                .unwrap_or(self.total_seconds);
+            Progress {
+                row,
+                row_count: Some(self.row_durations.len()),
+                seconds: row_start + into_row,
+                total_seconds: Some(self.total_seconds),
+                realtime_factor: Some(clock.factor(self.played_seconds + into_row)),
+            }
+        };
+        self.bar.update(progress);
+    }
+}
+
+/// Fades the music out (STOP_FADE_SECONDS) and waits until it has
+pub fn fade_out(controller: &mut EngineController) {
+    println!("\n[MAIN] Stopping...");
+    if controller
+        .send(EngineCommand::FadeOut(STOP_FADE_SECONDS))
+        .is_err()
+    {
+        return;
+    }
+    // Paused playback never renders the fade, so don't wait forever
+    let time_limit = Duration::from_secs_f32(STOP_FADE_SECONDS + 1.0);
+    let start_time = Instant::now();
+    while start_time.elapsed() < time_limit {
+        if std::iter::from_fn(|| controller.poll_event()).any(|event| event == EngineEvent::Silent)
+        {
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Plays the song with rows driven by MIDI clock from another device
+/// Waits for the device's Start, follows its tempo, and pauses on Stop.
+#[cfg(feature = "midir")]
+pub fn play_following_clock(
+    song_data: SongData,
+    engine_config: EngineConfig,
+    port: &str,
+    backend_name: &str,
+    options: &PlaybackOptions,
+) {
+    let follower = match ClockFollower::new(MIDI_CLOCK_ROWS_PER_BEAT) {
+        Ok(follower) => follower,
+        Err(message) => {
+            eprintln!("[ERROR] {}", message);
+            return;
+        }
+    };
+    let sample_rate = engine_config.sample_rate;
+    let mut engine = PlaybackEngine::new(song_data, engine_config);
+    engine.set_clock_source(ClockSource::External);
+    options.apply(&mut engine);
+    if !options.freeze(&mut engine) {
+        return;
+    }
+    engine.set_stage_timing(options.dsp_load);
+    let (mut realtime_engine, controller) = engine.into_realtime();
+
+    let mut backend = match create_backend(backend_name) {
+        Ok(backend) => backend,
+        Err(message) => {
+            eprintln!("[ERROR] {}", message);
+            return;
+        }
+    };
+    // Clock ticks are read between buffers, so a small buffer keeps rows on the beat
+    let settings = BackendSettings {
+        sample_rate,
+        buffer_frames: LIVE_BUFFER_SIZE,
+    };
+    let render = Box::new(move |samples: &mut [f32]| realtime_engine.process(samples));
+    if let Err(message) = backend.start(settings, render) {
+        eprintln!("[ERROR] {}", message);
+        return;
+    }
+
+    let clock_input = match follow_clock(Some(port), follower, controller) {
+        Ok(clock_input) => clock_input,
+        Err(message) => {
+            eprintln!("[ERROR] {}", message);
+            backend.stop();
+            return;
+        }
+    };
+    println!(
+        "\n[MIDI] Following clock from: {} ({} rows per beat)",
+        clock_input.port_name, MIDI_CLOCK_ROWS_PER_BEAT
+    );
+    println!("▶ Waiting for Start on the clock device... (Ctrl+C to stop)\n");
+
+    // No time limit: the other device decides the tempo and when to play
+    let stop_requested = stop_requested_flag();
+    while !clock_input.is_finished() && !stop_requested.load(Ordering::Relaxed) {
+        thread::sleep(Duration::from_millis(10));
+    }
+    let mut controller = clock_input.close();
+    if stop_requested.load(Ordering::Relaxed) {
+        fade_out(&mut controller);
+    } else {
+        let tail_time = Duration::from_secs_f32(RELEASE_TAIL_SECONDS);
+        if !wait_for_song_end(&mut controller, &stop_requested, Some(tail_time), None) {
+            fade_out(&mut controller);
+        }
+    }
+    thread::sleep(Duration::from_secs_f32(
+        LIVE_BUFFER_SIZE as f32 / sample_rate as f32,
+    ));
+    backend.stop();
+    options.print_dsp_load(&controller);
+    println!("\n[MAIN] Playback finished!");
+}
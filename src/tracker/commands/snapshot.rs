@@ -0,0 +1,162 @@
+// ============================================================================
+// SNAPSHOT.RS - "tracker snapshot": Engine State at a Row
+// ============================================================================
+//
+//   tracker snapshot assets/song.csv --row 32 -o after.json
+//   tracker snapshot --diff before.json after.json
+//
+// To see why a song sounds different since some commit, dump the state
+// before and after and compare the two (the snapshot itself is the
+// library's snapshot.rs).
+// ============================================================================
+
+use std::fs;
+
+use musickbeets::engine::{EngineConfig, PlaybackEngine, parse_sample_rate};
+use musickbeets::helper::{A4_FREQUENCY_HZ, FrequencyTable};
+use musickbeets::parser::{AUTO_CHANNEL_COUNT, SongData, parse_song_collection};
+use musickbeets::snapshot::{diff_snapshot_json, parse_snapshot_json};
+
+use super::{is_json_path, sample_rate_error};
+use crate::{
+    DEFAULT_RELEASE_SECONDS, DEFAULT_SAMPLE_RATE, FAST_RELEASE_SECONDS, MISSING_CELL_BEHAVIOR,
+    TICK_DURATION_SECONDS,
+};
+
+/// Dumps the engine state at a row as JSON (snapshot.rs), or compares two
+/// dumps with --diff. Returns the process exit code: for --diff, 1 if the
+/// snapshots differ, like the diff command.
+pub fn run(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: tracker snapshot <song_file.csv> --row <n> [-o <state.json>] [--sample-rate <hz>]\n       tracker snapshot --diff <before.json> <after.json>";
+    if args.first().map(String::as_str) == Some("--diff") {
+        let [_, old_path, new_path] = args else {
+            eprintln!("{}", USAGE);
+            return 2;
+        };
+        let read = |path: &str| {
+            fs::read_to_string(path)
+                .map_err(|error| format!("failed to read file: {}", error))
+                .and_then(|text| parse_snapshot_json(&text))
+                .map_err(|message| format!("{}: error: {}", path, message))
+        };
+        let (old, new) = match (read(old_path), read(new_path)) {
+            (Ok(old), Ok(new)) => (old, new),
+            (Err(message), _) | (_, Err(message)) => {
+                eprintln!("{}", message);
+                return 2;
+            }
+        };
+        let differences = diff_snapshot_json(&old, &new);
+        for line in &differences {
+            println!("{}", line);
+        }
+        if differences.is_empty() {
+            println!("Snapshots match.");
+            return 0;
+        }
+        println!("{} values differ.", differences.len());
+        return 1;
+    }
+
+    let mut song_path: Option<&str> = None;
+    let mut row: Option<usize> = None;
+    let mut output_path: Option<&str> = None;
+    let mut sample_rate = DEFAULT_SAMPLE_RATE;
+    let mut arg_index = 0;
+    while arg_index < args.len() {
+        let arg = args[arg_index].as_str();
+        if arg == "--row" || arg == "-o" || arg == "--sample-rate" {
+            arg_index += 1;
+            let value = args.get(arg_index).map(String::as_str).unwrap_or("");
+            match arg {
+                "--row" => match value.parse::<usize>() {
+                    Ok(number) if number >= 1 => row = Some(number),
+                    _ => {
+                        eprintln!("--row needs a row number from 1, got '{}'", value);
+                        return 1;
+                    }
+                },
+                "-o" => output_path = Some(value),
+                _ => match parse_sample_rate(value) {
+                    Some(rate) => sample_rate = rate,
+                    None => {
+                        eprintln!("{}", sample_rate_error(value));
+                        return 1;
+                    }
+                },
+            }
+        } else {
+            song_path = Some(arg);
+        }
+        arg_index += 1;
+    }
+    let (Some(song_path), Some(row)) = (song_path, row) else {
+        eprintln!("{}", USAGE);
+        return 1;
+    };
+
+    // Like "convert", messages go to stderr so the JSON can be piped
+    let song_data = match fs::read_to_string(song_path)
+        .map_err(|error| format!("failed to read file: {}", error))
+        .and_then(|text| {
+            if is_json_path(song_path) {
+                SongData::from_json(&text)
+            } else {
+                let collection = parse_song_collection(
+                    &text,
+                    &FrequencyTable::new(),
+                    A4_FREQUENCY_HZ,
+                    AUTO_CHANNEL_COUNT,
+                    MISSING_CELL_BEHAVIOR,
+                );
+                collection.select(&collection.default_selection())
+            }
+        }) {
+        Ok(song_data) => song_data,
+        Err(message) => {
+            eprintln!("{}: error: {}", song_path, message);
+            return 1;
+        }
+    };
+    for diagnostic in &song_data.diagnostics {
+        eprintln!("{}", diagnostic.format_for_file(song_path));
+    }
+    if row > song_data.row_count() {
+        eprintln!(
+            "{}: error: the song has {} rows, so there is no row {}",
+            song_path,
+            song_data.row_count(),
+            row
+        );
+        return 1;
+    }
+
+    let engine_config = EngineConfig {
+        sample_rate,
+        channel_count: song_data.channel_count().max(1),
+        tick_duration_seconds: song_data
+            .config
+            .tick_duration
+            .unwrap_or(TICK_DURATION_SECONDS),
+        default_release_seconds: DEFAULT_RELEASE_SECONDS,
+        fast_release_seconds: FAST_RELEASE_SECONDS,
+    };
+    let snapshot = PlaybackEngine::new(song_data, engine_config).snapshot_at_row(row - 1);
+    let json = snapshot.to_json();
+    match output_path {
+        Some(output_path) => match fs::write(output_path, json + "\n") {
+            Ok(()) => {
+                println!("{} row {} -> {}", song_path, row, output_path);
+                0
+            }
+            Err(error) => {
+                eprintln!("{}: error: failed to write file: {}", output_path, error);
+                1
+            }
+        },
+        None => {
+            println!("{}", json);
+            0
+        }
+    }
+}
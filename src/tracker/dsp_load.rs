@@ -0,0 +1,453 @@
+// ============================================================================
+// DSP_LOAD.RS - Callback Timing, Xruns, and DSP Load per Effect
+// ============================================================================
+//
+// How close the synth is to running out of time, for tuning songs on a
+// slow machine like a Raspberry Pi:
+//
+//   tracker assets/song.csv --dsp-load
+//
+//   [DSP] 2250 callbacks of 10.7 ms: average load 18%, worst 64% (6.8 ms)
+//   [DSP] Xruns: 1 (0 over the deadline, 1 late start)
+//   [DSP] Worst load per stage (in any one callback):
+//   [DSP]   voices            21.3%
+//   [DSP]   reverb (rv2)      30.8%
+//
+// WHAT IS MEASURED:
+// - Load: the time a callback took to render its buffer, divided by how
+//   long that buffer takes to play (its deadline). At 100% the samples
+//   arrive just in time; above it the sound breaks up.
+// - Xruns: callbacks that went over their deadline, plus callbacks that
+//   started more than LATE_START_BUFFERS buffers after the one before
+//   (the device probably ran dry while the thread was held up elsewhere).
+// - Stages: with stage timing on, every voice, channel effect, and bus
+//   effect adds up the time it spent during a callback, and the report
+//   keeps the worst load each stage caused in any one callback. Whatever
+//   the stages don't add up to went to the sequencer and the mixing.
+//
+// HOW IT WORKS:
+// Each channel and bus owns a StageTimer, which only reads the clock when
+// stage timing is on, so normal playback pays for two clock reads per
+// callback. At the end of a callback the audio thread's CallbackTimer
+// works out the loads and leaves them in a DspLoadMeter's atomics (like
+// LevelMeters in engine.rs). Loads are stored as f32 bits: for numbers
+// that are never negative, bigger bits mean a bigger number, so fetch_max
+// keeps the worst one. The main thread reads them with report() whenever
+// it likes, without either side waiting.
+// ============================================================================
+
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// A callback that starts this many buffers after the one before it
+/// counts as an xrun (the device had nothing left to play)
+pub const LATE_START_BUFFERS: f64 = 2.0;
+
+/// Stages below this load are left out of the report (an effect that is
+/// off still takes a moment to see that it is)
+const MIN_SHOWN_STAGE_LOAD: f32 = 0.001;
+
+/// How many stages are timed (see DspStage)
+pub const DSP_STAGE_COUNT: usize = 12;
+
+// ============================================================================
+// STAGES
+// ============================================================================
+
+/// A part of the render whose time is measured on its own
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DspStage {
+    /// Oscillators, envelopes, slides, and crossfades of every channel
+    Voices,
+    Wah,
+    Vowel,
+    Chorus,
+    Tremolo,
+    /// Bitcrush and decimate (they are one stage of the chain)
+    Bitcrush,
+    Distortion,
+    CustomEffects,
+    /// The simple reverb (rv) on the master and group buses
+    Reverb,
+    /// The advanced reverb (rv2) on the master and group buses
+    Reverb2,
+    /// The delay on the master and group buses
+    Delay,
+    /// The chorus on the master and group buses
+    BusChorus,
+}
+
+impl DspStage {
+    /// Every stage, in the order reports list them
+    pub const ALL: [DspStage; DSP_STAGE_COUNT] = [
+        DspStage::Voices,
+        DspStage::Wah,
+        DspStage::Vowel,
+        DspStage::Chorus,
+        DspStage::Tremolo,
+        DspStage::Bitcrush,
+        DspStage::Distortion,
+        DspStage::CustomEffects,
+        DspStage::Reverb,
+        DspStage::Reverb2,
+        DspStage::Delay,
+        DspStage::BusChorus,
+    ];
+
+    /// Name shown in reports
+    pub fn name(self) -> &'static str {
+        match self {
+            DspStage::Voices => "voices",
+            DspStage::Wah => "wah",
+            DspStage::Vowel => "vowel",
+            DspStage::Chorus => "chorus",
+            DspStage::Tremolo => "tremolo",
+            DspStage::Bitcrush => "bitcrush",
+            DspStage::Distortion => "distortion",
+            DspStage::CustomEffects => "custom effects",
+            DspStage::Reverb => "reverb (rv)",
+            DspStage::Reverb2 => "reverb (rv2)",
+            DspStage::Delay => "delay",
+            DspStage::BusChorus => "bus chorus",
+        }
+    }
+}
+
+/// Time a channel or bus spent in each stage since it was last drained
+/// Does nothing (and reads no clock) until enabled.
+#[derive(Clone, Debug, Default)]
+pub struct StageTimer {
+    enabled: bool,
+    nanos: [u64; DSP_STAGE_COUNT],
+}
+
+impl StageTimer {
+    /// Turns timing on or off
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Call before a stage runs; hand the result to stop
+    pub fn start(&self) -> Option<Instant> {
+        self.enabled.then(Instant::now)
+    }
+
+    /// Call after a stage ran, with what start returned
+    pub fn stop(&mut self, stage: DspStage, started: Option<Instant>) {
+        if let Some(started) = started {
+            self.nanos[stage as usize] += started.elapsed().as_nanos() as u64;
+        }
+    }
+
+    /// Adds the times to `totals` and starts counting from zero again
+    pub fn drain_into(&mut self, totals: &mut [u64; DSP_STAGE_COUNT]) {
+        for (total, nanos) in totals.iter_mut().zip(&mut self.nanos) {
+            *total += *nanos;
+            *nanos = 0;
+        }
+    }
+}
+
+// ============================================================================
+// SHARED METER
+// ============================================================================
+
+/// Loads and xrun counts the audio thread leaves for the main thread
+pub struct DspLoadMeter {
+    callbacks: AtomicU64,
+    overruns: AtomicU64,
+    late_starts: AtomicU64,
+
+    /// Time spent rendering and time available, over all callbacks
+    busy_nanos: AtomicU64,
+    deadline_nanos: AtomicU64,
+
+    /// Length of the last buffer, in nanoseconds
+    last_deadline_nanos: AtomicU64,
+
+    /// Slowest callback, in nanoseconds
+    worst_callback_nanos: AtomicU64,
+
+    /// Worst load ever, and worst since the last take_recent_load (f32 bits)
+    worst_load: AtomicU32,
+    recent_load: AtomicU32,
+
+    /// Worst load of each stage in any one callback (f32 bits)
+    stage_loads: [AtomicU32; DSP_STAGE_COUNT],
+
+    /// Whether the stages were timed at all
+    stages_timed: bool,
+}
+
+impl DspLoadMeter {
+    /// A meter with nothing recorded yet
+    pub fn new(stages_timed: bool) -> Self {
+        Self {
+            callbacks: AtomicU64::new(0),
+            overruns: AtomicU64::new(0),
+            late_starts: AtomicU64::new(0),
+            busy_nanos: AtomicU64::new(0),
+            deadline_nanos: AtomicU64::new(0),
+            last_deadline_nanos: AtomicU64::new(0),
+            worst_callback_nanos: AtomicU64::new(0),
+            worst_load: AtomicU32::new(0),
+            recent_load: AtomicU32::new(0),
+            stage_loads: std::array::from_fn(|_| AtomicU32::new(0)),
+            stages_timed,
+        }
+    }
+
+    /// Records one callback: how long it took, how long its buffer plays,
+    /// how long after the previous callback it started along with that
+    /// callback's buffer length (None for the first), and the time each
+    /// stage took during it
+    pub fn record_callback(
+        &self,
+        busy: Duration,
+        deadline: Duration,
+        gap: Option<(Duration, Duration)>,
+        stage_nanos: &[u64; DSP_STAGE_COUNT],
+    ) {
+        let busy_nanos = busy.as_nanos() as u64;
+        let deadline_nanos = (deadline.as_nanos() as u64).max(1);
+        self.callbacks.fetch_add(1, Ordering::Relaxed);
+        self.busy_nanos.fetch_add(busy_nanos, Ordering::Relaxed);
+        self.deadline_nanos
+            .fetch_add(deadline_nanos, Ordering::Relaxed);
+        self.last_deadline_nanos
+            .store(deadline_nanos, Ordering::Relaxed);
+        self.worst_callback_nanos
+            .fetch_max(busy_nanos, Ordering::Relaxed);
+        if busy_nanos > deadline_nanos {
+            self.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some((gap, previous_deadline)) = gap
+            && gap.as_secs_f64() > previous_deadline.as_secs_f64() * LATE_START_BUFFERS
+        {
+            self.late_starts.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let load = busy_nanos as f32 / deadline_nanos as f32;
+        self.worst_load.fetch_max(load.to_bits(), Ordering::Relaxed);
+        self.recent_load
+            .fetch_max(load.to_bits(), Ordering::Relaxed);
+        for (slot, nanos) in self.stage_loads.iter().zip(stage_nanos) {
+            let stage_load = *nanos as f32 / deadline_nanos as f32;
+            slot.fetch_max(stage_load.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    /// The worst load since the last call (0.0 = idle, 1.0 = no time left)
+    pub fn take_recent_load(&self) -> f32 {
+        f32::from_bits(self.recent_load.swap(0, Ordering::Relaxed))
+    }
+
+    /// Everything recorded so far
+    pub fn report(&self) -> DspLoadReport {
+        let load = |slot: &AtomicU32| f32::from_bits(slot.load(Ordering::Relaxed));
+        let busy_nanos = self.busy_nanos.load(Ordering::Relaxed);
+        let deadline_nanos = self.deadline_nanos.load(Ordering::Relaxed);
+        DspLoadReport {
+            callbacks: self.callbacks.load(Ordering::Relaxed),
+            overruns: self.overruns.load(Ordering::Relaxed),
+            late_starts: self.late_starts.load(Ordering::Relaxed),
+            average_load: if deadline_nanos == 0 {
+                0.0
+            } else {
+                busy_nanos as f32 / deadline_nanos as f32
+            },
+            worst_load: load(&self.worst_load),
+            worst_callback: Duration::from_nanos(self.worst_callback_nanos.load(Ordering::Relaxed)),
+            buffer_length: Duration::from_nanos(self.last_deadline_nanos.load(Ordering::Relaxed)),
+            stage_loads: self
+                .stages_timed
+                .then(|| std::array::from_fn(|index| load(&self.stage_loads[index]))),
+        }
+    }
+}
+
+/// The audio thread's side: times each callback and records it
+pub struct CallbackTimer {
+    /// Start and buffer length of the previous callback
+    previous: Option<(Instant, Duration)>,
+}
+
+impl CallbackTimer {
+    pub fn new() -> Self {
+        Self { previous: None }
+    }
+
+    /// Call last thing in the callback, with the Instant it started at,
+    /// the buffer's length in frames, and the time each stage took
+    pub fn finish(
+        &mut self,
+        meter: &DspLoadMeter,
+        started: Instant,
+        frames: usize,
+        sample_rate: u32,
+        stage_nanos: &[u64; DSP_STAGE_COUNT],
+    ) {
+        let deadline = Duration::from_secs_f64(frames as f64 / sample_rate.max(1) as f64);
+        let gap = self.previous.map(|(previous_start, previous_deadline)| {
+            (started.duration_since(previous_start), previous_deadline)
+        });
+        meter.record_callback(started.elapsed(), deadline, gap, stage_nanos);
+        self.previous = Some((started, deadline));
+    }
+}
+
+impl Default for CallbackTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================================================
+// REPORT
+// ============================================================================
+
+/// What a DspLoadMeter has recorded (see the top of the file)
+#[derive(Clone, Debug, PartialEq)]
+pub struct DspLoadReport {
+    pub callbacks: u64,
+
+    /// Callbacks that took longer than their buffer plays
+    pub overruns: u64,
+
+    /// Callbacks that started LATE_START_BUFFERS buffers after the last
+    pub late_starts: u64,
+
+    /// Rendering time over playing time, over the whole run
+    pub average_load: f32,
+
+    /// Load of the slowest callback
+    pub worst_load: f32,
+    pub worst_callback: Duration,
+
+    /// How long the last buffer plays
+    pub buffer_length: Duration,
+
+    /// Worst load of each stage (in DspStage::ALL order), if timed
+    pub stage_loads: Option<[f32; DSP_STAGE_COUNT]>,
+}
+
+impl DspLoadReport {
+    /// Every time the sound probably broke up
+    pub fn xruns(&self) -> u64 {
+        self.overruns + self.late_starts
+    }
+
+    /// The report as lines for the terminal, leaving out stages that
+    /// never ran or took too little time to show
+    pub fn lines(&self) -> Vec<String> {
+        if self.callbacks == 0 {
+            return vec!["[DSP] No audio was rendered".to_string()];
+        }
+        let milliseconds = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let mut lines = vec![
+            format!(
+                "[DSP] {} callbacks of {:.1} ms: average load {:.0}%, worst {:.0}% ({:.1} ms)",
+                self.callbacks,
+                milliseconds(self.buffer_length),
+                self.average_load * 100.0,
+                self.worst_load * 100.0,
+                milliseconds(self.worst_callback)
+            ),
+            format!(
+                "[DSP] Xruns: {} ({} over the deadline, {} late start{})",
+                self.xruns(),
+                self.overruns,
+                self.late_starts,
+                if self.late_starts == 1 { "" } else { "s" }
+            ),
+        ];
+        if let Some(stage_loads) = self.stage_loads {
+            lines.push("[DSP] Worst load per stage (in any one callback):".to_string());
+            for (stage, load) in DspStage::ALL.iter().zip(stage_loads) {
+                if load >= MIN_SHOWN_STAGE_LOAD {
+                    lines.push(format!(
+                        "[DSP]   {:<16} {:5.1}%",
+                        stage.name(),
+                        load * 100.0
+                    ));
+                }
+            }
+        }
+        lines
+    }
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_and_xruns() {
+        let meter = DspLoadMeter::new(true);
+        let deadline = Duration::from_millis(10);
+        let mut stage_nanos = [0; DSP_STAGE_COUNT];
+        stage_nanos[DspStage::Reverb2 as usize] = 3_000_000;
+
+        // On time, then over the deadline, then a buffer that starts late
+        meter.record_callback(Duration::from_millis(2), deadline, None, &stage_nanos);
+        meter.record_callback(
+            Duration::from_millis(12),
+            deadline,
+            Some((deadline, deadline)),
+            &[0; DSP_STAGE_COUNT],
+        );
+        meter.record_callback(
+            Duration::from_millis(4),
+            deadline,
+            Some((Duration::from_millis(25), deadline)),
+            &[0; DSP_STAGE_COUNT],
+        );
+
+        let report = meter.report();
+        assert_eq!(report.callbacks, 3);
+        assert_eq!((report.overruns, report.late_starts), (1, 1));
+        assert_eq!(report.xruns(), 2);
+        assert!((report.average_load - 0.6).abs() < 1e-6);
+        assert!((report.worst_load - 1.2).abs() < 1e-6);
+        assert_eq!(report.worst_callback, Duration::from_millis(12));
+        let stage_loads = report.stage_loads.unwrap();
+        assert!((stage_loads[DspStage::Reverb2 as usize] - 0.3).abs() < 1e-6);
+        assert_eq!(stage_loads[DspStage::Voices as usize], 0.0);
+
+        // The recent load starts over once read; the worst never does
+        assert!((meter.take_recent_load() - 1.2).abs() < 1e-6);
+        assert_eq!(meter.take_recent_load(), 0.0);
+        assert!((meter.report().worst_load - 1.2).abs() < 1e-6);
+
+        // Stages that never ran are left out
+        let lines = report.lines();
+        assert!(lines.iter().any(|line| line.contains("reverb (rv2)")));
+        assert!(!lines.iter().any(|line| line.contains("voices")));
+    }
+
+    #[test]
+    fn test_stage_timer() {
+        // Off: nothing is timed
+        let mut timer = StageTimer::default();
+        assert!(timer.start().is_none());
+
+        timer.set_enabled(true);
+        let started = timer.start();
+        std::thread::sleep(Duration::from_millis(1));
+        timer.stop(DspStage::Delay, started);
+
+        let mut totals = [0; DSP_STAGE_COUNT];
+        timer.drain_into(&mut totals);
+        assert!(totals[DspStage::Delay as usize] >= 1_000_000);
+        assert_eq!(totals[DspStage::Voices as usize], 0);
+
+        // Draining starts the count over
+        let mut totals = [0; DSP_STAGE_COUNT];
+        timer.drain_into(&mut totals);
+        assert_eq!(totals, [0; DSP_STAGE_COUNT]);
+    }
+}
@@ -0,0 +1,672 @@
+// ============================================================================
+// EDITOR.RS - Interactive Pattern Editor
+// ============================================================================
+//
+// A small tracker-style editor for song files, in the terminal:
+//
+//   tracker edit assets/song.csv
+//
+// KEYS:
+//   Arrows, PgUp/PgDn, Home/End   Move around the pattern
+//   Enter                         Edit the cell (Enter again to keep it)
+//   Any other text key            Start typing a new cell over the old one
+//   Tab (while typing)            Keep the cell and move right
+//   Esc (while typing)            Throw the typing away
+//   Delete / Backspace            Empty the cell
+//   Space                         Play the row and step down (audition)
+//   Esc                           Silence everything
+//   Insert or Ctrl+N              Add a row below ("-" cells keep notes going)
+//   Ctrl+D                        Delete the row
+//   Ctrl+S                        Save
+//   Ctrl+Q                        Quit (twice if there are unsaved changes)
+//
+// HOW IT WORKS:
+// - The file is kept as its lines, and only the lines the parser reads as
+//   rows are edited, so comments, directives, presets, and the config row
+//   come back out exactly as they went in.
+// - After every change the text is parsed again with parse_song. That
+//   finds which line each row is on (SongData::row_line_numbers) and the
+//   parser's messages for the line under the cursor show in the status bar.
+// - Auditioning dispatches the parsed row's actions to a live engine, so
+//   presets, macros, and tuning from the file all apply.
+// ============================================================================
+
+use crate::engine::{EngineCommand, EngineController};
+use crate::helper::FrequencyTable;
+use crate::parser::{
+    CellAction, MissingCellBehavior, Severity, SongData, parse_song, strip_comments,
+};
+use crate::tui::ROW_CELL_WIDTH;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Cell, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+
+/// Channels in a song started from scratch
+const NEW_SONG_CHANNELS: usize = 4;
+
+/// Rows in a song started from scratch
+const NEW_SONG_ROWS: usize = 16;
+
+/// Rows PgUp/PgDn move
+const PAGE_ROWS: usize = 16;
+
+/// Cell written into rows added with Insert (keeps sounding notes going)
+const NEW_ROW_CELL: &str = "-";
+
+// ============================================================================
+// EDITOR STATE
+// ============================================================================
+
+/// What the editor wants the program to do after a key
+#[derive(Debug)]
+pub enum EditorRequest {
+    /// Nothing beyond redrawing
+    Continue,
+
+    /// Play these (channel, action) pairs right away
+    Play(Vec<(usize, CellAction)>),
+
+    /// Release every channel quickly
+    Silence,
+
+    /// Leave the editor
+    Quit,
+}
+
+/// The song being edited and the cursor
+pub struct PatternEditor {
+    /// Where Ctrl+S saves
+    path: String,
+
+    /// Every line of the file
+    lines: Vec<String>,
+
+    /// The file parsed as it is now
+    song: SongData,
+
+    /// Index into lines of the header line (column names), if any
+    header_line: Option<usize>,
+
+    /// How many channels the parser reads
+    channel_count: usize,
+
+    frequency_table: FrequencyTable,
+    reference_frequency_hz: f32,
+
+    cursor_row: usize,
+    cursor_column: usize,
+
+    /// Text being typed into the cursor cell (None when not typing)
+    edit_buffer: Option<String>,
+
+    /// Changed since the last save
+    dirty: bool,
+
+    /// Ctrl+Q was pressed once with unsaved changes
+    quit_armed: bool,
+
+    /// Message for the status bar (save results and the like)
+    status: String,
+}
+
+impl PatternEditor {
+    /// Opens song text for editing (empty text starts a new song)
+    pub fn new(path: &str, text: &str, channel_count: usize, reference_frequency_hz: f32) -> Self {
+        let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+        if lines
+            .iter()
+            .all(|line| strip_comments(line).trim().is_empty())
+        {
+            let channels = NEW_SONG_CHANNELS.min(channel_count).max(1);
+            let header: Vec<String> = (0..channels)
+                .map(|index| format!("Voice{}", index))
+                .collect();
+            lines.push(header.join(","));
+            lines.extend((0..NEW_SONG_ROWS).map(|_| vec![NEW_ROW_CELL; channels].join(",")));
+        }
+
+        let frequency_table = FrequencyTable::new();
+        let song = parse_song(
+            "",
+            &frequency_table,
+            reference_frequency_hz,
+            channel_count,
+            MissingCellBehavior::SlowRelease,
+        );
+        let mut editor = Self {
+            path: path.to_string(),
+            lines,
+            song,
+            header_line: None,
+            channel_count,
+            frequency_table,
+            reference_frequency_hz,
+            cursor_row: 0,
+            cursor_column: 0,
+            edit_buffer: None,
+            dirty: false,
+            quit_armed: false,
+            status: String::new(),
+        };
+        editor.reparse();
+        editor
+    }
+
+    /// The file as it would be saved
+    pub fn text(&self) -> String {
+        let mut text = self.lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    /// Whether there are unsaved changes
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Writes the file and clears the unsaved-changes flag
+    pub fn save(&mut self) -> Result<(), String> {
+        std::fs::write(&self.path, self.text())
+            .map_err(|error| format!("Failed to save '{}': {}", self.path, error))?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Number of rows in the song
+    pub fn row_count(&self) -> usize {
+        self.song.row_count()
+    }
+
+    /// Columns to show: the header's or the widest row's, within the channels
+    fn column_count(&self) -> usize {
+        let widest = self
+            .header_line
+            .into_iter()
+            .chain(self.row_line_indices())
+            .map(|index| split_cells(&self.lines[index]).0.len())
+            .max()
+            .unwrap_or(1);
+        widest.clamp(1, self.channel_count.max(1))
+    }
+
+    /// Line index of each row
+    fn row_line_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.song.row_line_numbers.iter().map(|number| number - 1)
+    }
+
+    /// Text of one cell, trimmed ("" past the end of the row)
+    pub fn cell(&self, row: usize, column: usize) -> String {
+        self.song
+            .row_line_numbers
+            .get(row)
+            .and_then(|number| split_cells(&self.lines[number - 1]).0.get(column).cloned())
+            .map(|cell| cell.trim().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Replaces one cell, keeping the rest of the line (and its comment)
+    pub fn set_cell(&mut self, row: usize, column: usize, text: &str) {
+        let Some(&line_number) = self.song.row_line_numbers.get(row) else {
+            return;
+        };
+        let line = &mut self.lines[line_number - 1];
+        let (mut cells, comment) = split_cells(line);
+        if cells.len() <= column {
+            cells.resize(column + 1, String::new());
+        }
+        if cells[column].trim() == text.trim() {
+            return;
+        }
+        // Keep the spacing around the cell so aligned columns stay aligned
+        let old = &cells[column];
+        let leading = &old[..old.len() - old.trim_start().len()];
+        let trailing = &old[old.trim_end().len()..];
+        cells[column] = format!("{}{}{}", leading, text.trim(), trailing);
+        *line = format!("{}{}", cells.join(","), comment);
+
+        // A row that would read as blank disappears from the song; keep it
+        if strip_comments(line).trim().is_empty() {
+            *line = format!("{}{}", NEW_ROW_CELL, line);
+        }
+        self.dirty = true;
+        self.reparse();
+    }
+
+    /// Adds a row of "-" cells after the given row (at the top if empty)
+    pub fn insert_row_after(&mut self, row: usize) {
+        let new_line = vec![NEW_ROW_CELL; self.column_count()].join(",");
+        let line_index = match self.song.row_line_numbers.get(row) {
+            Some(number) => *number,
+            None => self.header_line.map_or(self.lines.len(), |index| index + 1),
+        };
+        self.lines.insert(line_index, new_line);
+        self.dirty = true;
+        self.reparse();
+    }
+
+    /// Removes a row (the last row can't be removed)
+    pub fn delete_row(&mut self, row: usize) {
+        if self.row_count() <= 1 {
+            self.status = "The song needs at least one row.".to_string();
+            return;
+        }
+        if let Some(&line_number) = self.song.row_line_numbers.get(row) {
+            self.lines.remove(line_number - 1);
+            self.dirty = true;
+            self.reparse();
+        }
+    }
+
+    /// The parsed actions of a row, for auditioning
+    pub fn row_actions(&self, row: usize) -> Vec<(usize, CellAction)> {
+        self.song
+            .rows
+            .get(row)
+            .map(|actions| actions.iter().cloned().enumerate().collect())
+            .unwrap_or_default()
+    }
+
+    /// Parses the text again and keeps the cursor inside the song
+    fn reparse(&mut self) {
+        self.song = parse_song(
+            &self.lines.join("\n"),
+            &self.frequency_table,
+            self.reference_frequency_hz,
+            self.channel_count,
+            MissingCellBehavior::SlowRelease,
+        );
+        let first_row_line = self
+            .song
+            .row_line_numbers
+            .first()
+            .map_or(self.lines.len(), |number| number - 1);
+        self.header_line = (0..first_row_line).find(|&index| {
+            let code = strip_comments(&self.lines[index]).trim();
+            !code.is_empty() && !code.starts_with('[') && !code.contains('=')
+        });
+        self.cursor_row = self.cursor_row.min(self.row_count().saturating_sub(1));
+        self.cursor_column = self.cursor_column.min(self.channel_count.saturating_sub(1));
+    }
+
+    /// The first parser message for the line under the cursor
+    fn cursor_message(&self) -> Option<(Severity, String)> {
+        let line_number = *self.song.row_line_numbers.get(self.cursor_row)?;
+        self.song
+            .diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.line_number == line_number)
+            .map(|diagnostic| (diagnostic.severity, diagnostic.message.clone()))
+    }
+
+    // ========================================================================
+    // KEYS
+    // ========================================================================
+
+    /// Handles one key press
+    pub fn handle_key(&mut self, key: KeyEvent) -> EditorRequest {
+        if self.edit_buffer.is_some() {
+            self.handle_typing_key(key);
+            return EditorRequest::Continue;
+        }
+
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        let quit_armed = std::mem::take(&mut self.quit_armed);
+        self.status.clear();
+        let last_row = self.row_count().saturating_sub(1);
+
+        match key.code {
+            KeyCode::Char('q') if control => {
+                if self.dirty && !quit_armed {
+                    self.quit_armed = true;
+                    self.status =
+                        "Unsaved changes! Ctrl+S saves, Ctrl+Q again quits without saving."
+                            .to_string();
+                } else {
+                    return EditorRequest::Quit;
+                }
+            }
+            KeyCode::Char('s') if control => {
+                self.status = match self.save() {
+                    Ok(()) => format!("Saved {}", self.path),
+                    Err(message) => message,
+                };
+            }
+            KeyCode::Char('n') if control => {
+                self.insert_row_after(self.cursor_row);
+                self.cursor_row = (self.cursor_row + 1).min(self.row_count().saturating_sub(1));
+            }
+            KeyCode::Insert => {
+                self.insert_row_after(self.cursor_row);
+                self.cursor_row = (self.cursor_row + 1).min(self.row_count().saturating_sub(1));
+            }
+            KeyCode::Char('d') if control => self.delete_row(self.cursor_row),
+            KeyCode::Up => self.cursor_row = self.cursor_row.saturating_sub(1),
+            KeyCode::Down => self.cursor_row = (self.cursor_row + 1).min(last_row),
+            KeyCode::PageUp => self.cursor_row = self.cursor_row.saturating_sub(PAGE_ROWS),
+            KeyCode::PageDown => self.cursor_row = (self.cursor_row + PAGE_ROWS).min(last_row),
+            KeyCode::Home => self.cursor_row = 0,
+            KeyCode::End => self.cursor_row = last_row,
+            KeyCode::Left => self.cursor_column = self.cursor_column.saturating_sub(1),
+            KeyCode::Right | KeyCode::Tab => {
+                self.cursor_column =
+                    (self.cursor_column + 1).min(self.channel_count.saturating_sub(1))
+            }
+            KeyCode::Enter => {
+                self.edit_buffer = Some(self.cell(self.cursor_row, self.cursor_column));
+            }
+            KeyCode::Delete | KeyCode::Backspace => {
+                self.set_cell(self.cursor_row, self.cursor_column, "");
+            }
+            KeyCode::Char(' ') => {
+                let actions = self.row_actions(self.cursor_row);
+                self.cursor_row = (self.cursor_row + 1).min(last_row);
+                return EditorRequest::Play(actions);
+            }
+            KeyCode::Esc => return EditorRequest::Silence,
+            KeyCode::Char(character) if !control => {
+                self.edit_buffer = Some(character.to_string());
+            }
+            _ => {}
+        }
+        EditorRequest::Continue
+    }
+
+    /// Keys while typing into a cell
+    fn handle_typing_key(&mut self, key: KeyEvent) {
+        let Some(buffer) = self.edit_buffer.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Char(character) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                buffer.push(character);
+            }
+            KeyCode::Backspace => {
+                buffer.pop();
+            }
+            KeyCode::Esc => self.edit_buffer = None,
+            KeyCode::Enter | KeyCode::Tab => {
+                let text = self.edit_buffer.take().unwrap_or_default();
+                self.set_cell(self.cursor_row, self.cursor_column, &text);
+                if key.code == KeyCode::Enter {
+                    self.cursor_row = (self.cursor_row + 1).min(self.row_count().saturating_sub(1));
+                } else {
+                    self.cursor_column =
+                        (self.cursor_column + 1).min(self.channel_count.saturating_sub(1));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // ========================================================================
+    // DRAWING
+    // ========================================================================
+
+    /// Draws the whole screen
+    pub fn draw(&self, frame: &mut Frame) {
+        let [grid_area, status_area, help_area] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        self.draw_grid(frame, grid_area);
+
+        let (status_text, status_color) = if !self.status.is_empty() {
+            (self.status.clone(), Color::Cyan)
+        } else {
+            match self.cursor_message() {
+                Some((Severity::Error, message)) => (message, Color::Red),
+                Some((_, message)) => (message, Color::Yellow),
+                None => (String::new(), Color::Reset),
+            }
+        };
+        frame.render_widget(
+            Paragraph::new(format!(" {}", status_text)).style(Style::default().fg(status_color)),
+            status_area,
+        );
+        frame.render_widget(
+            Paragraph::new(
+                " Enter edit  Space play row  Esc silence  Ins add row  Ctrl+D delete row  \
+                 Ctrl+S save  Ctrl+Q quit",
+            )
+            .style(Style::default().fg(Color::DarkGray)),
+            help_area,
+        );
+    }
+
+    fn draw_grid(&self, frame: &mut Frame, area: Rect) {
+        // Scroll so the cursor stays in view (rows centered, columns as needed)
+        let visible_rows = area.height.saturating_sub(3) as usize;
+        let first_row = self
+            .cursor_row
+            .saturating_sub(visible_rows / 2)
+            .min(self.row_count().saturating_sub(visible_rows));
+        let last_row = (first_row + visible_rows).min(self.row_count());
+
+        let columns_shown = self.column_count().max(self.cursor_column + 1);
+        let visible_columns = (area.width.saturating_sub(9) / (ROW_CELL_WIDTH + 1)).max(1) as usize;
+        let first_column = (self.cursor_column + 1).saturating_sub(visible_columns);
+        let last_column = (first_column + visible_columns).min(columns_shown);
+
+        let header_cells = self
+            .header_line
+            .map(|index| split_cells(&self.lines[index]).0)
+            .unwrap_or_default();
+        let header = Row::new(std::iter::once("  Row".to_string()).chain(
+            (first_column..last_column).map(|column| {
+                header_cells
+                    .get(column)
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or_else(|| format!("{}", column + 1))
+            }),
+        ))
+        .style(Style::default().add_modifier(Modifier::BOLD));
+
+        let rows = (first_row..last_row).map(|row| {
+            let mut cells = vec![Cell::from(format!("{:>5}", row + 1))];
+            for column in first_column..last_column {
+                let is_cursor = row == self.cursor_row && column == self.cursor_column;
+                let cell = match (&self.edit_buffer, is_cursor) {
+                    (Some(buffer), true) => Cell::from(format!("{}_", buffer))
+                        .style(Style::default().fg(Color::Black).bg(Color::Yellow)),
+                    (None, true) => Cell::from(self.cell(row, column))
+                        .style(Style::default().add_modifier(Modifier::REVERSED)),
+                    _ => Cell::from(self.cell(row, column)),
+                };
+                cells.push(cell);
+            }
+            let row_widget = Row::new(cells);
+            if row == self.cursor_row {
+                row_widget.style(Style::default().bg(Color::DarkGray))
+            } else {
+                row_widget
+            }
+        });
+
+        let widths = std::iter::once(Constraint::Length(6))
+            .chain((first_column..last_column).map(|_| Constraint::Length(ROW_CELL_WIDTH)));
+        let title = format!(
+            "{}{}  Row {}/{}  Channel {}",
+            self.path,
+            if self.dirty { " *" } else { "" },
+            self.cursor_row + 1,
+            self.row_count(),
+            self.cursor_column + 1
+        );
+        frame.render_widget(
+            Table::new(rows, widths)
+                .header(header)
+                .block(Block::bordered().title(Line::from(title))),
+            area,
+        );
+    }
+}
+
+/// Splits a line into its cells and the comment after them (kept as-is)
+fn split_cells(line: &str) -> (Vec<String>, &str) {
+    let code = strip_comments(line);
+    let comment = &line[code.len()..];
+    (code.split(',').map(str::to_string).collect(), comment)
+}
+
+// ============================================================================
+// RUNNING THE EDITOR
+// ============================================================================
+
+/// Runs the editor until the user quits
+/// With a controller, Space plays rows on it; without one, nothing sounds.
+pub fn run_pattern_editor(
+    editor: &mut PatternEditor,
+    mut controller: Option<&mut EngineController>,
+) -> Result<(), String> {
+    let mut terminal = ratatui::try_init()
+        .map_err(|error| format!("Failed to start the terminal view: {}", error))?;
+    // Parser messages go to the status bar instead of over the screen
+    let log_level = log::max_level();
+    log::set_max_level(log::LevelFilter::Off);
+
+    let result = editor_loop(&mut terminal, editor, &mut controller);
+
+    ratatui::restore();
+    log::set_max_level(log_level);
+    result
+}
+
+/// Body of run_pattern_editor, between setting up and restoring the terminal
+fn editor_loop(
+    terminal: &mut DefaultTerminal,
+    editor: &mut PatternEditor,
+    controller: &mut Option<&mut EngineController>,
+) -> Result<(), String> {
+    loop {
+        terminal
+            .draw(|frame| editor.draw(frame))
+            .map_err(|error| format!("Failed to draw: {}", error))?;
+
+        let Event::Key(key) =
+            event::read().map_err(|error| format!("Terminal error: {}", error))?
+        else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+
+        let commands: Vec<EngineCommand> = match editor.handle_key(key) {
+            EditorRequest::Continue => Vec::new(),
+            EditorRequest::Quit => return Ok(()),
+            EditorRequest::Play(actions) => actions
+                .into_iter()
+                .map(|(channel_index, action)| EngineCommand::Dispatch {
+                    channel_index,
+                    action,
+                })
+                .collect(),
+            EditorRequest::Silence => (0..editor.channel_count)
+                .map(|channel_index| EngineCommand::Dispatch {
+                    channel_index,
+                    action: CellAction::FastRelease,
+                })
+                .collect(),
+        };
+        if let Some(controller) = controller.as_deref_mut() {
+            // Auditioning never waits: commands that don't fit are skipped
+            for command in commands {
+                let _ = controller.send(command);
+            }
+            while controller.poll_event().is_some() {}
+        }
+    }
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::A4_FREQUENCY_HZ;
+
+    const SONG: &str = "Lead,Bass\n\
+                        config,title: Test\n\
+                        // intro\n\
+                        c4 sine,c2 square // first row\n\
+                        -,-\n";
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_edits_keep_the_rest_of_the_file() {
+        let mut editor = PatternEditor::new("test.csv", SONG, 4, A4_FREQUENCY_HZ);
+        assert_eq!(editor.row_count(), 2);
+        assert_eq!(editor.column_count(), 2);
+        assert_eq!(editor.cell(0, 1), "c2 square");
+
+        // Typing replaces the cell; the comment on the line stays
+        for code in [KeyCode::Right, KeyCode::Char('e'), KeyCode::Char('2')] {
+            editor.handle_key(key(code));
+        }
+        editor.handle_key(key(KeyCode::Enter));
+        assert_eq!(editor.cell(0, 1), "e2");
+        assert!(editor.is_dirty());
+
+        // Add a row after the first, then delete the last one
+        editor.handle_key(key(KeyCode::Up));
+        editor.handle_key(key(KeyCode::Insert));
+        assert_eq!(editor.row_count(), 3);
+        editor.delete_row(2);
+        assert_eq!(
+            editor.text(),
+            "Lead,Bass\nconfig,title: Test\n// intro\nc4 sine,e2 // first row\n-,-\n"
+        );
+
+        // Cells past the end of a row pad it out
+        editor.set_cell(1, 3, "g4 pulse");
+        assert_eq!(editor.cell(1, 3), "g4 pulse");
+        assert_eq!(editor.cell(1, 2), "");
+    }
+
+    #[test]
+    fn test_audition_and_messages() {
+        let mut editor = PatternEditor::new("test.csv", SONG, 2, A4_FREQUENCY_HZ);
+        match editor.handle_key(key(KeyCode::Char(' '))) {
+            EditorRequest::Play(actions) => {
+                assert_eq!(actions.len(), 2);
+                assert!(matches!(actions[0].1, CellAction::TriggerNote { .. }));
+            }
+            other => panic!("expected Play, got {:?}", other),
+        }
+        // Space steps down, like a tracker
+        assert_eq!(editor.cursor_row, 1);
+
+        // A bad cell shows the parser's message for the cursor line
+        editor.set_cell(1, 0, "c4 nosuchinstrument");
+        assert!(editor.cursor_message().is_some());
+
+        // Quitting with unsaved changes takes two presses
+        let quit = KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL);
+        assert!(matches!(editor.handle_key(quit), EditorRequest::Continue));
+        assert!(matches!(editor.handle_key(quit), EditorRequest::Quit));
+    }
+
+    #[test]
+    fn test_new_song_template() {
+        let editor = PatternEditor::new("new.csv", "", 12, A4_FREQUENCY_HZ);
+        assert_eq!(editor.row_count(), NEW_SONG_ROWS);
+        assert_eq!(editor.column_count(), NEW_SONG_CHANNELS);
+        assert!(
+            editor
+                .text()
+                .starts_with("Voice0,Voice1,Voice2,Voice3\n-,-,-,-\n")
+        );
+    }
+}
@@ -4,12 +4,54 @@
 //
 // Provides channel-level and master-bus audio effects processing.
 //
-// Channel effects: amplitude, pan, vibrato, tremolo, bitcrush, distortion, chorus
+// Channel effects: amplitude, pan, detune, vibrato, tremolo, bitcrush, decimator, distortion, chorus,
+// wah, vowel
+// The wah ("wah:2'0.6'5") and vowel ("vowel:a>o") filters shape the raw
+// voice before the chain below; their filters live in wah.rs.
+// LFO rates can follow the tempo ("t:1/16'0.5") and restart their cycle
+// on each note or row ("t:4'0.5'sync"); the channel handles both.
+// The decimator ("sr:8000") holds each sample for a while, as if the sound
+// had been recorded at a lower sample rate. It runs in the bitcrush stage,
+// just before the bit reduction (the two are the lo-fi stage together).
+// A channel can also mix in a sub-oscillator ("sub:0.5") an octave below
+// its note, and a key-click ("click:0.3") at the start of each note; the
+// channel renders both, and only their settings live here.
+// Bitcrush and distortion can run oversampled ("q:2", "q:4") to keep their
+// new harmonics from aliasing; see oversampling.rs.
+// The four sound-shaping channel effects (chorus, tremolo, bitcrush,
+// distortion) run in the order of the channel's chain, which a cell can
+// change with "chain:d>b>ch>t". Amplitude and pan always come last, since
+// they turn the mono voice into stereo.
+// Programs using the library can add their own channel effects with
+// register_effect; cells then use them by name like the built-in ones.
 // Master effects: reverb (simple + algorithmic), delay, chorus, amplitude, pan
+// The algorithmic reverb (rv2) is stereo: each side has its own network of
+// delay lines and hears a little of the other (REVERB2_CROSS_FEED).
+// Each master effect can be bypassed (faded out of the signal while it keeps
+// running, so its tail is still there when it comes back), and "wet" blends
+// the whole processed chain with the dry mix.
+//
+// FEEDBACK LINES:
+// Reverb, delay, and chorus feed their own output back in, so a tail keeps
+// getting quieter forever. Once it is far below hearing the numbers turn
+// into denormal floats, which x86 CPUs handle many times slower. Every
+// write into a feedback line goes through flush_denormal, and an effect
+// that is switched off has its lines emptied, so switching it back on
+// never replays old echoes.
 //
 // ============================================================================
 
+use crate::dsp_load::{DspStage, StageTimer};
+use crate::instruments::find_instrument_by_name;
+use crate::oversampling::{Oversampler, nearest_oversampling_factor};
+use crate::simd::{add_ramped, lookup_sine};
+use crate::wah::{
+    DEFAULT_WAH_Q, StateVariableFilter, VOWEL_OUTPUT_GAIN, Vowel, envelope_wah_position,
+    follow_envelope, lfo_wah_position, morph_formants, wah_frequency,
+};
+use serde::{Deserialize, Serialize};
 use std::f32::consts::PI;
+use std::sync::RwLock;
 
 // ============================================================================
 // CONSTANTS
@@ -17,6 +59,10 @@ use std::f32::consts::PI;
 
 pub const TWO_PI: f32 = std::f32::consts::TAU;
 
+/// Feedback samples smaller than this are flushed to zero (about -360 dB,
+/// far below hearing and far above the denormal range)
+pub const DENORMAL_THRESHOLD: f32 = 1e-18;
+
 // ============================================================================
 // HELPER FUNCTIONS
 // ============================================================================
@@ -26,6 +72,13 @@ pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
     a + (b - a) * t
 }
 
+/// Returns zero for values too small to hear, so feedback tails never
+/// decay into slow denormal floats
+#[inline]
+pub fn flush_denormal(x: f32) -> f32 {
+    if x.abs() < DENORMAL_THRESHOLD { 0.0 } else { x }
+}
+
 #[inline]
 fn soft_clip(x: f32) -> f32 {
     if x.abs() < 1.0 {
@@ -35,41 +88,417 @@ fn soft_clip(x: f32) -> f32 {
     }
 }
 
+// ============================================================================
+// CHANNEL EFFECT CHAIN
+// ============================================================================
+
+/// One reorderable stage of the channel effect chain
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChannelStage {
+    Chorus,
+    Tremolo,
+    Bitcrush,
+    Distortion,
+}
+
+/// Number of reorderable channel stages
+pub const CHANNEL_STAGE_COUNT: usize = 4;
+
+impl ChannelStage {
+    /// The order stages run in unless a cell says otherwise
+    pub const DEFAULT_ORDER: [ChannelStage; CHANNEL_STAGE_COUNT] = [
+        ChannelStage::Chorus,
+        ChannelStage::Tremolo,
+        ChannelStage::Bitcrush,
+        ChannelStage::Distortion,
+    ];
+
+    /// Finds a stage by the name its effect has in cells ("d", "chorus", ...)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ch" | "chorus" => Some(ChannelStage::Chorus),
+            "t" | "tremolo" => Some(ChannelStage::Tremolo),
+            "b" | "bitcrush" => Some(ChannelStage::Bitcrush),
+            "d" | "distortion" => Some(ChannelStage::Distortion),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a chain order like "d>b>ch>t"
+///
+/// Stages that aren't named run after the named ones, in their default
+/// order, so "d>b" is enough to put distortion before bitcrush. Amplitude
+/// and pan ("a", "p") may end the list, since that is where they run anyway.
+pub fn parse_chain_order(text: &str) -> Result<[ChannelStage; CHANNEL_STAGE_COUNT], String> {
+    let mut order = Vec::with_capacity(CHANNEL_STAGE_COUNT);
+    let mut output_stage_seen = false;
+    for name in text.split('>').map(|name| name.trim().to_lowercase()) {
+        match name.as_str() {
+            "a" | "amplitude" | "p" | "pan" => output_stage_seen = true,
+            _ => {
+                let Some(stage) = ChannelStage::from_name(&name) else {
+                    return Err(format!(
+                        "'{}' can't be placed in the chain. Use: ch, t, b, d (then a, p)",
+                        name
+                    ));
+                };
+                if output_stage_seen {
+                    return Err("Amplitude and pan always come last in the chain".to_string());
+                }
+                if order.contains(&stage) {
+                    return Err(format!("'{}' appears twice in the chain", name));
+                }
+                order.push(stage);
+            }
+        }
+    }
+    for stage in ChannelStage::DEFAULT_ORDER {
+        if !order.contains(&stage) {
+            order.push(stage);
+        }
+    }
+    let mut chain = ChannelStage::DEFAULT_ORDER;
+    chain.copy_from_slice(&order);
+    Ok(chain)
+}
+
+// ============================================================================
+// CUSTOM EFFECTS
+// ============================================================================
+
+/// A channel effect added at runtime by a program using the library
+///
+/// Every channel gets its own copy (made with clone_box). A cell like
+/// "phaser:2'0.5" calls set_parameters(&[2.0, 0.5]) on that channel's copy,
+/// and "clear" calls it with no parameters, which should turn the effect
+/// off. Until then the effect should pass the sound through unchanged.
+pub trait CustomEffect: Send + Sync {
+    /// Takes the values from a cell (empty: turn the effect off)
+    fn set_parameters(&mut self, parameters: &[f32]);
+
+    /// Processes one block of the channel's mono signal in place
+    /// Runs on the audio thread, so it should not allocate or block.
+    fn process_block(&mut self, samples: &mut [f32], sample_rate: u32);
+
+    /// A fresh copy for another channel
+    fn clone_box(&self) -> Box<dyn CustomEffect>;
+}
+
+/// A named custom effect (one per registered effect in each channel)
+pub struct CustomEffectSlot {
+    pub name: String,
+    pub effect: Box<dyn CustomEffect>,
+}
+
+impl Clone for CustomEffectSlot {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            effect: self.effect.clone_box(),
+        }
+    }
+}
+
+impl std::fmt::Debug for CustomEffectSlot {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("CustomEffectSlot")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+// ============================================================================
+// SUB-OSCILLATOR
+// ============================================================================
+
+/// Waveform of the sub-oscillator ("sub:0.5" is a square, "sub:0.5'sine")
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubOscillatorShape {
+    Square,
+    Sine,
+}
+
+impl SubOscillatorShape {
+    /// Finds a shape by the name used in "sub:" tokens (empty = square)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "sq" | "square" => Some(SubOscillatorShape::Square),
+            "sin" | "sine" => Some(SubOscillatorShape::Sine),
+            _ => None,
+        }
+    }
+
+    /// The built-in instrument that draws this shape
+    pub fn instrument_id(self) -> usize {
+        match self {
+            SubOscillatorShape::Square => 3,
+            SubOscillatorShape::Sine => 1,
+        }
+    }
+}
+
+/// Effect names cells already use; custom effects can't take them
+const BUILT_IN_EFFECT_NAMES: &[&str] = &[
+    "a",
+    "amplitude",
+    "p",
+    "pan",
+    "dt",
+    "detune",
+    "v",
+    "vibrato",
+    "t",
+    "tremolo",
+    "b",
+    "bitcrush",
+    "sr",
+    "decimate",
+    "d",
+    "distortion",
+    "q",
+    "quality",
+    "ch",
+    "chorus",
+    "chain",
+    "tr",
+    "transition",
+    "cl",
+    "clear",
+    "env",
+    "envelope",
+    "seed",
+    "vel",
+    "velocity",
+    "sub",
+    "click",
+    "wah",
+    "vowel",
+];
+
+/// Every registered custom effect, in registration order
+static CUSTOM_EFFECTS: RwLock<Vec<CustomEffectSlot>> = RwLock::new(Vec::new());
+
+/// Adds a channel effect that cells can use by name ("c4 sine phaser:2'0.5")
+///
+/// Register effects before parsing the songs that use them. Registering a
+/// name again replaces the earlier effect. Names are one lowercase word and
+/// can't be a built-in effect or instrument name.
+pub fn register_effect(name: &str, effect: Box<dyn CustomEffect>) -> Result<(), String> {
+    let name = name.to_lowercase();
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || character == '_')
+    {
+        return Err(format!(
+            "'{}' is not a valid effect name (use one word)",
+            name
+        ));
+    }
+    if BUILT_IN_EFFECT_NAMES.contains(&name.as_str()) || find_instrument_by_name(&name).is_some() {
+        return Err(format!("'{}' is already a built-in name", name));
+    }
+
+    let mut effects = CUSTOM_EFFECTS
+        .write()
+        .unwrap_or_else(|error| error.into_inner());
+    let slot = CustomEffectSlot { name, effect };
+    match effects
+        .iter_mut()
+        .find(|existing| existing.name == slot.name)
+    {
+        Some(existing) => *existing = slot,
+        None => effects.push(slot),
+    }
+    Ok(())
+}
+
+/// Returns true if a custom effect with this (lowercase) name is registered
+pub fn is_custom_effect(name: &str) -> bool {
+    CUSTOM_EFFECTS
+        .read()
+        .unwrap_or_else(|error| error.into_inner())
+        .iter()
+        .any(|slot| slot.name == name)
+}
+
+/// Returns true if cells already use this (lowercase) name for an effect,
+/// built in or registered
+pub fn is_effect_name(name: &str) -> bool {
+    BUILT_IN_EFFECT_NAMES.contains(&name) || is_custom_effect(name)
+}
+
+/// A copy of every registered custom effect, for a new channel
+pub fn custom_effect_slots() -> Vec<CustomEffectSlot> {
+    CUSTOM_EFFECTS
+        .read()
+        .unwrap_or_else(|error| error.into_inner())
+        .clone()
+}
+
+// ============================================================================
+// TEMPO-SYNCED RATES
+// ============================================================================
+
+/// LFO rates written as note lengths ("t:1/16'0.5"), in beats per cycle
+///
+/// 0 means the rate is a plain number of Hz. The channel turns the others
+/// into Hz for the current tempo, and again whenever the tempo changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncedRates {
+    pub vibrato_beats: f32,
+    pub tremolo_beats: f32,
+    pub chorus_beats: f32,
+    pub wah_beats: f32,
+}
+
+/// LFO rate in Hz for a cycle this many beats long
+pub fn synced_rate_hz(beats: f32, seconds_per_beat: f32) -> f32 {
+    1.0 / (beats * seconds_per_beat).max(f32::EPSILON)
+}
+
+// ============================================================================
+// LFO RESTARTS
+// ============================================================================
+
+/// When an LFO goes back to the start of its cycle ("t:4'0.5'sync")
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LfoRestart {
+    /// Never: it keeps running across notes and rows
+    #[default]
+    Free,
+    /// At each new note (not on glides)
+    Note,
+    /// At the start of every row
+    Row,
+}
+
+impl LfoRestart {
+    /// Looks up a restart mode by the word cells use for it
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "free" => Some(LfoRestart::Free),
+            "sync" | "note" => Some(LfoRestart::Note),
+            "row" => Some(LfoRestart::Row),
+            _ => None,
+        }
+    }
+}
+
+/// When each channel LFO restarts its cycle
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LfoRestarts {
+    pub vibrato: LfoRestart,
+    pub tremolo: LfoRestart,
+    pub chorus: LfoRestart,
+    pub wah: LfoRestart,
+}
+
 // ============================================================================
 // CHANNEL EFFECT STATE
 // ============================================================================
 
 /// Per-channel effect state
-#[derive(Clone, Debug)]
+///
+/// Serializes only the settings; phases and the chorus buffer are running
+/// state and start from zero. Settings missing from JSON take their defaults.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ChannelEffectState {
     // Basic
     pub amplitude: f32,
     pub pan: f32,
 
+    // Detune (cents, applied to the pitch after vibrato)
+    pub detune_cents: f32,
+
     // Vibrato
     pub vibrato_rate_hz: f32,
     pub vibrato_depth_semitones: f32,
+    #[serde(skip)]
     pub vibrato_phase: f32,
 
     // Tremolo
     pub tremolo_rate_hz: f32,
     pub tremolo_depth: f32,
+    #[serde(skip)]
     pub tremolo_phase: f32,
 
     // Bitcrush
     pub bitcrush_bits: u8,
 
+    // Decimator: the sample rate it holds samples at (0 = off)
+    pub decimate_rate_hz: f32,
+    // How far through the current held sample it is (1.0 = take the next)
+    #[serde(skip)]
+    pub decimate_phase: f32,
+    #[serde(skip)]
+    pub decimate_held_sample: f32,
+
     // Distortion
     pub distortion_amount: f32,
 
+    // Oversampling factor for bitcrush and distortion (1, 2, or 4)
+    pub oversampling: u8,
+
+    // Sub-oscillator an octave below the note (level 0 = off)
+    pub sub_level: f32,
+    pub sub_shape: SubOscillatorShape,
+
+    // Level of the noise burst that starts each note (0 = off)
+    pub click_level: f32,
+
+    // Wah: a band-pass swept by an LFO, or by the note's level when the
+    // rate is 0 (depth 0 = off)
+    pub wah_rate_hz: f32,
+    pub wah_depth: f32,
+    pub wah_q: f32,
+    #[serde(skip)]
+    pub wah_phase: f32,
+    #[serde(skip)]
+    pub wah_envelope: f32,
+    #[serde(skip)]
+    pub wah_filter: StateVariableFilter,
+
+    // Vowel: formants of the first vowel, morphing towards the second
+    // (None = off; the morph runs 0..1)
+    pub vowel: Option<(Vowel, Vowel)>,
+    pub vowel_morph: f32,
+    #[serde(skip)]
+    pub vowel_filters: [StateVariableFilter; 3],
+
     // Chorus
     pub chorus_mix: f32,
     pub chorus_rate_hz: f32,
     pub chorus_depth_ms: f32,
     pub chorus_feedback: f32,
+    #[serde(skip)]
     pub chorus_phase: f32,
+    #[serde(skip)]
     pub chorus_buffer: Vec<f32>,
+    #[serde(skip)]
     pub chorus_write_position: usize,
+    // True while the chorus delay line holds sound (cleared when the chorus
+    // stops, so it starts from silence next time)
+    #[serde(skip)]
+    pub chorus_buffer_used: bool,
+
+    // LFO rates that follow the tempo (set as note lengths)
+    pub synced_rates: SyncedRates,
+
+    // When the LFOs go back to the start of their cycle
+    pub lfo_restarts: LfoRestarts,
+
+    // Order of the chorus, tremolo, bitcrush, and distortion stages
+    pub chain: [ChannelStage; CHANNEL_STAGE_COUNT],
+
+    // Custom effect settings from a cell: (name, parameters)
+    // The channel hands them to its effects; they are not kept here.
+    pub custom_effects: Vec<(String, Vec<f32>)>,
 }
 
 impl Default for ChannelEffectState {
@@ -77,6 +506,7 @@ impl Default for ChannelEffectState {
         Self {
             amplitude: 1.0,
             pan: 0.0,
+            detune_cents: 0.0,
             vibrato_rate_hz: 0.0,
             vibrato_depth_semitones: 0.0,
             vibrato_phase: 0.0,
@@ -84,7 +514,23 @@ impl Default for ChannelEffectState {
             tremolo_depth: 0.0,
             tremolo_phase: 0.0,
             bitcrush_bits: 16,
+            decimate_rate_hz: 0.0,
+            decimate_phase: 1.0,
+            decimate_held_sample: 0.0,
             distortion_amount: 0.0,
+            oversampling: 1,
+            sub_level: 0.0,
+            sub_shape: SubOscillatorShape::Square,
+            click_level: 0.0,
+            wah_rate_hz: 0.0,
+            wah_depth: 0.0,
+            wah_q: DEFAULT_WAH_Q,
+            wah_phase: 0.0,
+            wah_envelope: 0.0,
+            wah_filter: StateVariableFilter::default(),
+            vowel: None,
+            vowel_morph: 0.0,
+            vowel_filters: [StateVariableFilter::default(); 3],
             chorus_mix: 0.0,
             chorus_rate_hz: 0.0,
             chorus_depth_ms: 0.0,
@@ -92,15 +538,104 @@ impl Default for ChannelEffectState {
             chorus_phase: 0.0,
             chorus_buffer: Vec::new(),
             chorus_write_position: 0,
+            chorus_buffer_used: false,
+            synced_rates: SyncedRates::default(),
+            lfo_restarts: LfoRestarts::default(),
+            chain: ChannelStage::DEFAULT_ORDER,
+            custom_effects: Vec::new(),
         }
     }
 }
 
+/// Filter memory for the oversampled stages of one channel's chain
+///
+/// Kept on the channel rather than in ChannelEffectState, which is copied
+/// and compared on every cell and should stay small.
+#[derive(Clone, Debug, Default)]
+pub struct ChannelOversamplers {
+    pub bitcrush: Oversampler,
+    pub distortion: Oversampler,
+}
+
 impl ChannelEffectState {
     pub fn initialize_chorus_buffer(&mut self, sample_rate: u32) {
         let max_delay_samples = ((50.0 / 1000.0) * sample_rate as f32) as usize + 1;
         self.chorus_buffer = vec![0.0; max_delay_samples];
         self.chorus_write_position = 0;
+        self.chorus_buffer_used = false;
+    }
+
+    /// Copies the effect settings from another state, keeping this state's
+    /// running values (LFO phases and the chorus delay line)
+    /// Never allocates, so it is safe on the audio thread. Custom effect
+    /// settings are not copied (the channel applies them directly).
+    pub fn copy_parameters_from(&mut self, other: &ChannelEffectState) {
+        self.amplitude = other.amplitude;
+        self.pan = other.pan;
+        self.detune_cents = other.detune_cents;
+        self.vibrato_rate_hz = other.vibrato_rate_hz;
+        self.vibrato_depth_semitones = other.vibrato_depth_semitones;
+        self.tremolo_rate_hz = other.tremolo_rate_hz;
+        self.tremolo_depth = other.tremolo_depth;
+        self.bitcrush_bits = other.bitcrush_bits;
+        self.decimate_rate_hz = other.decimate_rate_hz;
+        self.distortion_amount = other.distortion_amount;
+        self.oversampling = other.oversampling;
+        self.sub_level = other.sub_level;
+        self.sub_shape = other.sub_shape;
+        self.click_level = other.click_level;
+        self.wah_rate_hz = other.wah_rate_hz;
+        self.wah_depth = other.wah_depth;
+        self.wah_q = other.wah_q;
+        self.vowel = other.vowel;
+        self.vowel_morph = other.vowel_morph;
+        self.chorus_mix = other.chorus_mix;
+        self.chorus_rate_hz = other.chorus_rate_hz;
+        self.chorus_depth_ms = other.chorus_depth_ms;
+        self.chorus_feedback = other.chorus_feedback;
+        self.synced_rates = other.synced_rates;
+        self.lfo_restarts = other.lfo_restarts;
+        self.chain = other.chain;
+    }
+
+    /// Holds every setting to the range the song parser allows
+    /// The chorus depth matters most: the delay line is only 50 ms long, so
+    /// a deeper sweep would read from before its start. A decimate rate or
+    /// chorus rate or depth of 0 means the effect is unused and stays 0.
+    pub fn clamp_to_ranges(&mut self) {
+        let clamp_unless_unused = |value: f32, min: f32, max: f32| {
+            if value == 0.0 {
+                0.0
+            } else {
+                value.clamp(min, max)
+            }
+        };
+
+        self.amplitude = self.amplitude.clamp(0.0, 1.0);
+        self.pan = self.pan.clamp(-1.0, 1.0);
+        self.detune_cents = self.detune_cents.clamp(-1200.0, 1200.0);
+        self.vibrato_rate_hz = self.vibrato_rate_hz.max(0.0);
+        self.vibrato_depth_semitones = self.vibrato_depth_semitones.max(0.0);
+        self.tremolo_rate_hz = self.tremolo_rate_hz.max(0.0);
+        self.tremolo_depth = self.tremolo_depth.clamp(0.0, 1.0);
+        self.bitcrush_bits = self.bitcrush_bits.clamp(1, 16);
+        self.decimate_rate_hz = clamp_unless_unused(self.decimate_rate_hz, 20.0, 192000.0);
+        self.distortion_amount = self.distortion_amount.clamp(0.0, 1.0);
+        self.oversampling = nearest_oversampling_factor(self.oversampling as f32);
+        self.sub_level = self.sub_level.clamp(0.0, 1.0);
+        self.click_level = self.click_level.clamp(0.0, 1.0);
+        self.wah_rate_hz = self.wah_rate_hz.clamp(0.0, 20.0);
+        self.wah_depth = self.wah_depth.clamp(0.0, 1.0);
+        self.wah_q = self.wah_q.clamp(0.5, 30.0);
+        self.vowel_morph = self.vowel_morph.clamp(0.0, 1.0);
+        self.chorus_mix = self.chorus_mix.clamp(0.0, 1.0);
+        self.chorus_rate_hz = clamp_unless_unused(self.chorus_rate_hz, 0.1, 5.0);
+        self.chorus_depth_ms = clamp_unless_unused(self.chorus_depth_ms, 0.5, 10.0);
+        self.chorus_feedback = self.chorus_feedback.clamp(0.0, 0.9);
+        self.synced_rates.vibrato_beats = self.synced_rates.vibrato_beats.max(0.0);
+        self.synced_rates.tremolo_beats = self.synced_rates.tremolo_beats.max(0.0);
+        self.synced_rates.wah_beats = self.synced_rates.wah_beats.max(0.0);
+        self.synced_rates.chorus_beats = self.synced_rates.chorus_beats.max(0.0);
     }
 }
 
@@ -108,12 +643,102 @@ impl ChannelEffectState {
 // MASTER EFFECT STATE
 // ============================================================================
 
+/// Extra length of the right reverb 2 lines (Freeverb's 23 samples at 44.1 kHz)
+pub const REVERB2_STEREO_SPREAD_MS: f32 = 0.52;
+
+/// How much of the opposite side each reverb 2 network hears
+/// (0 = fully separate sides, 0.5 = both hear the same mono mix)
+pub const REVERB2_CROSS_FEED: f32 = 0.25;
+
+/// How long the delay takes to glide most of the way (63%) to a new time
+pub const DELAY_GLIDE_SECONDS: f32 = 0.05;
+
+/// One side of reverb 2: early reflections, then parallel comb filters,
+/// then all-pass filters in series
+#[derive(Clone, Debug, Default)]
+pub struct ReverbNetwork {
+    pub early_buffers: Vec<Vec<f32>>,
+    pub early_positions: Vec<usize>,
+    pub comb_buffers: Vec<Vec<f32>>,
+    pub comb_positions: Vec<usize>,
+    pub comb_filters: Vec<f32>,
+    pub allpass_buffers: Vec<Vec<f32>>,
+    pub allpass_positions: Vec<usize>,
+}
+
+impl ReverbNetwork {
+    /// Builds the delay lines, each spread_ms longer than the base times
+    pub fn new(sample_rate: u32, spread_ms: f32) -> Self {
+        let line = |ms: f32, scale: f32| {
+            let samples = ((ms + spread_ms) / 1000.0 * sample_rate as f32 * scale) as usize;
+            vec![0.0; samples.max(1)]
+        };
+
+        // Early reflections and combs are twice as long as their times,
+        // so the room size can stretch them
+        let early_delay_times_ms = [7.0, 11.0, 13.0, 17.0, 19.0, 23.0];
+        let comb_delay_times_ms = [29.7, 37.1, 41.1, 43.7, 47.6, 53.0, 59.3, 67.0];
+        let allpass_delay_times_ms = [5.0, 1.7];
+
+        Self {
+            early_buffers: early_delay_times_ms
+                .iter()
+                .map(|&ms| line(ms, 2.0))
+                .collect(),
+            early_positions: vec![0; early_delay_times_ms.len()],
+            comb_buffers: comb_delay_times_ms
+                .iter()
+                .map(|&ms| line(ms, 2.0))
+                .collect(),
+            comb_positions: vec![0; comb_delay_times_ms.len()],
+            comb_filters: vec![0.0; comb_delay_times_ms.len()],
+            allpass_buffers: allpass_delay_times_ms
+                .iter()
+                .map(|&ms| line(ms, 1.0))
+                .collect(),
+            allpass_positions: vec![0; allpass_delay_times_ms.len()],
+        }
+    }
+
+    /// Empties every line (never allocates)
+    pub fn clear(&mut self) {
+        for buffer in self
+            .early_buffers
+            .iter_mut()
+            .chain(&mut self.comb_buffers)
+            .chain(&mut self.allpass_buffers)
+        {
+            buffer.fill(0.0);
+        }
+        self.comb_filters.fill(0.0);
+    }
+
+    /// Length of the longest early reflection or comb line, in samples
+    pub fn longest_line(&self) -> usize {
+        self.comb_buffers
+            .iter()
+            .chain(&self.early_buffers)
+            .map(Vec::len)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
 /// Master bus effect state
 #[derive(Clone, Debug)]
 pub struct MasterEffectState {
     pub amplitude: f32,
     pub pan: f32,
 
+    // How much of the processed chain is heard (0 = dry mix, 1 = all effects)
+    pub wet: f32,
+
+    // How much of each effect is heard (1 = in, 0 = bypassed)
+    pub reverb1_level: f32,
+    pub reverb2_level: f32,
+    pub delay_level: f32,
+    pub chorus_level: f32,
+
     // Reverb 1 (simple)
     pub reverb1_enabled: bool,
     pub reverb1_room_size: f32,
@@ -128,17 +753,15 @@ pub struct MasterEffectState {
     pub reverb2_damping: f32,
     pub reverb2_mix: f32,
     pub reverb2_predelay_ms: f32,
-    pub reverb2_early_buffers: Vec<Vec<f32>>,
-    pub reverb2_early_positions: Vec<usize>,
-    pub reverb2_comb_buffers: Vec<Vec<f32>>,
-    pub reverb2_comb_positions: Vec<usize>,
-    pub reverb2_comb_filters: Vec<f32>,
-    pub reverb2_allpass_buffers: Vec<Vec<f32>>,
-    pub reverb2_allpass_positions: Vec<usize>,
+    pub reverb2_left: ReverbNetwork,
+    pub reverb2_right: ReverbNetwork,
 
     // Delay
     pub delay_enabled: bool,
     pub delay_time_samples: u32,
+    // The delay actually read: glides toward delay_time_samples one sample
+    // at a time, so time changes bend the pitch like tape instead of jumping
+    pub delay_glide_samples: f32,
     pub delay_feedback: f32,
     pub delay_buffer_left: Vec<f32>,
     pub delay_buffer_right: Vec<f32>,
@@ -162,6 +785,13 @@ impl MasterEffectState {
             amplitude: 1.0,
             pan: 0.0,
 
+            wet: 1.0,
+
+            reverb1_level: 1.0,
+            reverb2_level: 1.0,
+            delay_level: 1.0,
+            chorus_level: 1.0,
+
             reverb1_enabled: false,
             reverb1_room_size: 0.5,
             reverb1_mix: 0.3,
@@ -174,16 +804,12 @@ impl MasterEffectState {
             reverb2_damping: 0.5,
             reverb2_mix: 0.3,
             reverb2_predelay_ms: 20.0,
-            reverb2_early_buffers: Vec::new(),
-            reverb2_early_positions: Vec::new(),
-            reverb2_comb_buffers: Vec::new(),
-            reverb2_comb_positions: Vec::new(),
-            reverb2_comb_filters: Vec::new(),
-            reverb2_allpass_buffers: Vec::new(),
-            reverb2_allpass_positions: Vec::new(),
+            reverb2_left: ReverbNetwork::default(),
+            reverb2_right: ReverbNetwork::default(),
 
             delay_enabled: false,
             delay_time_samples: 12000,
+            delay_glide_samples: 12000.0,
             delay_feedback: 0.3,
             delay_buffer_left: Vec::new(),
             delay_buffer_right: Vec::new(),
@@ -207,39 +833,10 @@ impl MasterEffectState {
         // Reverb 1
         self.reverb1_buffer = vec![0.0; max_buffer_size];
 
-        // Reverb 2 - early reflections
-        let early_delay_times_ms = [7.0, 11.0, 13.0, 17.0, 19.0, 23.0];
-        self.reverb2_early_buffers = early_delay_times_ms
-            .iter()
-            .map(|&ms| {
-                let samples = ((ms / 1000.0) * sample_rate as f32 * 2.0) as usize;
-                vec![0.0; samples.max(1)]
-            })
-            .collect();
-        self.reverb2_early_positions = vec![0; early_delay_times_ms.len()];
-
-        // Reverb 2 - comb filters
-        let comb_delay_times_ms = [29.7, 37.1, 41.1, 43.7, 47.6, 53.0, 59.3, 67.0];
-        self.reverb2_comb_buffers = comb_delay_times_ms
-            .iter()
-            .map(|&ms| {
-                let samples = ((ms / 1000.0) * sample_rate as f32 * 2.0) as usize;
-                vec![0.0; samples.max(1)]
-            })
-            .collect();
-        self.reverb2_comb_positions = vec![0; comb_delay_times_ms.len()];
-        self.reverb2_comb_filters = vec![0.0; comb_delay_times_ms.len()];
-
-        // Reverb 2 - all-pass filters
-        let allpass_delay_times_ms = [5.0, 1.7];
-        self.reverb2_allpass_buffers = allpass_delay_times_ms
-            .iter()
-            .map(|&ms| {
-                let samples = ((ms / 1000.0) * sample_rate as f32) as usize;
-                vec![0.0; samples.max(1)]
-            })
-            .collect();
-        self.reverb2_allpass_positions = vec![0; allpass_delay_times_ms.len()];
+        // Reverb 2 - one network per side, the right one slightly longer
+        // so the two sides decorrelate
+        self.reverb2_left = ReverbNetwork::new(sample_rate, 0.0);
+        self.reverb2_right = ReverbNetwork::new(sample_rate, REVERB2_STEREO_SPREAD_MS);
 
         // Delay
         self.delay_buffer_left = vec![0.0; max_buffer_size];
@@ -250,6 +847,37 @@ impl MasterEffectState {
         self.chorus_buffer_left = vec![0.0; chorus_buffer_size];
         self.chorus_buffer_right = vec![0.0; chorus_buffer_size];
     }
+
+    /// Switches the reverbs, delay, and chorus on or off
+    ///
+    /// An effect that goes off has its buffers emptied, so turning it back
+    /// on later starts from silence instead of old echoes. Never
+    /// allocates, so it is safe on the audio thread.
+    pub fn set_enabled(&mut self, reverb1: bool, reverb2: bool, delay: bool, chorus: bool) {
+        if self.reverb1_enabled && !reverb1 {
+            self.reverb1_buffer.fill(0.0);
+        }
+        if self.reverb2_enabled && !reverb2 {
+            self.reverb2_left.clear();
+            self.reverb2_right.clear();
+        }
+        if self.delay_enabled && !delay {
+            self.delay_buffer_left.fill(0.0);
+            self.delay_buffer_right.fill(0.0);
+        }
+        if !self.delay_enabled && delay {
+            // Nothing to glide from: start at the set time
+            self.delay_glide_samples = self.delay_time_samples as f32;
+        }
+        if self.chorus_enabled && !chorus {
+            self.chorus_buffer_left.fill(0.0);
+            self.chorus_buffer_right.fill(0.0);
+        }
+        self.reverb1_enabled = reverb1;
+        self.reverb2_enabled = reverb2;
+        self.delay_enabled = delay;
+        self.chorus_enabled = chorus;
+    }
 }
 
 impl Default for MasterEffectState {
@@ -262,83 +890,296 @@ impl Default for MasterEffectState {
 // CHANNEL EFFECT PROCESSING
 // ============================================================================
 
-/// Applies channel effects to a mono sample and returns stereo (left, right)
-pub fn apply_channel_effects(
-    input_sample: f32,
+/// Applies channel effects to a block of mono samples and ADDS the stereo
+/// result into `left` and `right`
+///
+/// Custom effects run after the built-in stages, in registration order.
+/// Each stage's time goes to `timer` (see dsp_load.rs).
+///
+/// LFOs (tremolo, chorus) and settings are worked out once at each end of
+/// the block and ramped in between. `start_amplitude` and `start_pan` are
+/// the values at the end of the previous block, so amplitude and pan
+/// changes glide across the block instead of stepping.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_channel_effects_block(
+    samples: &mut [f32],
+    left: &mut [f32],
+    right: &mut [f32],
     effects: &mut ChannelEffectState,
+    custom_effects: &mut [CustomEffectSlot],
+    oversamplers: &mut ChannelOversamplers,
     sample_rate: u32,
-) -> (f32, f32) {
-    let mut sample = input_sample;
+    start_amplitude: f32,
+    start_pan: f32,
+    timer: &mut StageTimer,
+) {
+    let block_length = samples.len().min(left.len()).min(right.len());
+    if block_length == 0 {
+        return;
+    }
+    let step = 1.0 / block_length as f32;
+    let samples = &mut samples[..block_length];
+
+    // The wah and vowel filters shape the raw voice, then the mono
+    // stages run in the channel's chain order
+    let started = timer.start();
+    apply_wah_block(samples, effects, sample_rate, step);
+    timer.stop(DspStage::Wah, started);
+    let started = timer.start();
+    apply_vowel_block(samples, effects, sample_rate);
+    timer.stop(DspStage::Vowel, started);
+    for stage in effects.chain {
+        let started = timer.start();
+        match stage {
+            ChannelStage::Chorus => apply_chorus_block(samples, effects, sample_rate, step),
+            ChannelStage::Tremolo => apply_tremolo_block(samples, effects, sample_rate, step),
+            ChannelStage::Bitcrush => {
+                apply_decimate_block(samples, effects, sample_rate);
+                apply_bitcrush_block(samples, effects, &mut oversamplers.bitcrush)
+            }
+            ChannelStage::Distortion => {
+                apply_distortion_block(samples, effects, &mut oversamplers.distortion)
+            }
+        }
+        let timed_stage = match stage {
+            ChannelStage::Chorus => DspStage::Chorus,
+            ChannelStage::Tremolo => DspStage::Tremolo,
+            ChannelStage::Bitcrush => DspStage::Bitcrush,
+            ChannelStage::Distortion => DspStage::Distortion,
+        };
+        timer.stop(timed_stage, started);
+    }
+    let started = timer.start();
+    for slot in custom_effects {
+        slot.effect.process_block(samples, sample_rate);
+    }
+    timer.stop(DspStage::CustomEffects, started);
+
+    // Amplitude and pan (constant-power), ramped across the block
+    let pan_coefficients = |pan: f32| (((1.0 - pan) * 0.5).sqrt(), ((1.0 + pan) * 0.5).sqrt());
+    let (start_left, start_right) = pan_coefficients(start_pan);
+    let (end_left, end_right) = pan_coefficients(effects.pan);
+    let start_left = start_left * start_amplitude;
+    let start_right = start_right * start_amplitude;
+    let end_left = end_left * effects.amplitude;
+    let end_right = end_right * effects.amplitude;
+
+    add_ramped(samples, &mut left[..block_length], start_left, end_left);
+    add_ramped(samples, &mut right[..block_length], start_right, end_right);
+}
 
-    // Chorus
-    if effects.chorus_mix > 0.0 && effects.chorus_rate_hz > 0.0 {
-        sample = apply_mono_chorus(sample, effects, sample_rate);
+/// Chorus stage of the channel chain
+fn apply_chorus_block(
+    samples: &mut [f32],
+    effects: &mut ChannelEffectState,
+    sample_rate: u32,
+    step: f32,
+) {
+    if effects.chorus_mix > 0.0 && effects.chorus_rate_hz > 0.0 && !effects.chorus_buffer.is_empty()
+    {
+        let (start_lfo, end_lfo) = advance_lfo(
+            &mut effects.chorus_phase,
+            effects.chorus_rate_hz,
+            sample_rate,
+            samples.len(),
+        );
+        for (index, sample) in samples.iter_mut().enumerate() {
+            let lfo = lerp(start_lfo, end_lfo, index as f32 * step);
+            *sample = apply_mono_chorus(*sample, effects, sample_rate, lfo);
+        }
+        effects.chorus_buffer_used = true;
+    } else if effects.chorus_buffer_used {
+        // The chorus stopped: empty its line so it restarts from silence
+        effects.chorus_buffer.fill(0.0);
+        effects.chorus_buffer_used = false;
     }
+}
 
-    // Tremolo
+/// Wah filter, run before the chain
+/// The centre is worked out at each end of the block (from the LFO, or
+/// from the envelope follower when the rate is 0) and ramped in between.
+fn apply_wah_block(
+    samples: &mut [f32],
+    effects: &mut ChannelEffectState,
+    sample_rate: u32,
+    step: f32,
+) {
+    if effects.wah_depth <= 0.0 {
+        // Off: forget the old sound so it starts clean next time
+        effects.wah_filter.reset();
+        effects.wah_envelope = 0.0;
+        return;
+    }
+    let (start_position, end_position) = if effects.wah_rate_hz > 0.0 {
+        let (start_lfo, end_lfo) = advance_lfo(
+            &mut effects.wah_phase,
+            effects.wah_rate_hz,
+            sample_rate,
+            samples.len(),
+        );
+        (
+            lfo_wah_position(start_lfo, effects.wah_depth),
+            lfo_wah_position(end_lfo, effects.wah_depth),
+        )
+    } else {
+        let start_envelope = effects.wah_envelope;
+        effects.wah_envelope = follow_envelope(start_envelope, samples, sample_rate);
+        (
+            envelope_wah_position(start_envelope, effects.wah_depth),
+            envelope_wah_position(effects.wah_envelope, effects.wah_depth),
+        )
+    };
+    let start_tuning = StateVariableFilter::tuning(wah_frequency(start_position), sample_rate);
+    let end_tuning = StateVariableFilter::tuning(wah_frequency(end_position), sample_rate);
+    for (index, sample) in samples.iter_mut().enumerate() {
+        let tuning = lerp(start_tuning, end_tuning, index as f32 * step);
+        *sample = effects.wah_filter.band_pass(*sample, tuning, effects.wah_q);
+    }
+}
+
+/// Vowel (formant) filter, run before the chain
+/// The formants are worked out once per block from the morph.
+fn apply_vowel_block(samples: &mut [f32], effects: &mut ChannelEffectState, sample_rate: u32) {
+    let Some(vowels) = effects.vowel else {
+        for filter in &mut effects.vowel_filters {
+            filter.reset();
+        }
+        return;
+    };
+    let formants = morph_formants(vowels, effects.vowel_morph);
+    let tunings =
+        formants.map(|formant| StateVariableFilter::tuning(formant.frequency_hz, sample_rate));
+    for sample in samples.iter_mut() {
+        let input = *sample;
+        let mut output = 0.0;
+        for ((filter, formant), tuning) in
+            effects.vowel_filters.iter_mut().zip(&formants).zip(tunings)
+        {
+            let q = formant.frequency_hz / formant.bandwidth_hz;
+            output += filter.band_pass(input, tuning, q) * formant.level;
+        }
+        *sample = output * VOWEL_OUTPUT_GAIN;
+    }
+}
+
+/// Tremolo stage of the channel chain
+fn apply_tremolo_block(
+    samples: &mut [f32],
+    effects: &mut ChannelEffectState,
+    sample_rate: u32,
+    step: f32,
+) {
     if effects.tremolo_rate_hz > 0.0 && effects.tremolo_depth > 0.0 {
-        let lfo = effects.tremolo_phase.sin();
-        let amplitude_modulation = 1.0 - effects.tremolo_depth * (1.0 - lfo) / 2.0;
-        sample *= amplitude_modulation;
+        let (start_lfo, end_lfo) = advance_lfo(
+            &mut effects.tremolo_phase,
+            effects.tremolo_rate_hz,
+            sample_rate,
+            samples.len(),
+        );
+        let start_modulation = 1.0 - effects.tremolo_depth * (1.0 - start_lfo) / 2.0;
+        let end_modulation = 1.0 - effects.tremolo_depth * (1.0 - end_lfo) / 2.0;
+        for (index, sample) in samples.iter_mut().enumerate() {
+            *sample *= lerp(start_modulation, end_modulation, index as f32 * step);
+        }
+    }
+}
 
-        effects.tremolo_phase += TWO_PI * effects.tremolo_rate_hz / sample_rate as f32;
-        if effects.tremolo_phase >= TWO_PI {
-            effects.tremolo_phase -= TWO_PI;
+/// Decimator, run at the start of the bitcrush stage
+/// Sample-and-hold: a new input sample is taken decimate_rate_hz times a
+/// second and repeated until the next. At or above the output rate (or
+/// at 0) it is off.
+fn apply_decimate_block(samples: &mut [f32], effects: &mut ChannelEffectState, sample_rate: u32) {
+    let rate_hz = effects.decimate_rate_hz;
+    if rate_hz > 0.0 && rate_hz < sample_rate as f32 {
+        let step = rate_hz / sample_rate as f32;
+        for sample in samples.iter_mut() {
+            effects.decimate_phase += step;
+            if effects.decimate_phase >= 1.0 {
+                effects.decimate_phase -= 1.0;
+                effects.decimate_held_sample = *sample;
+            }
+            *sample = effects.decimate_held_sample;
         }
+    } else {
+        // Off: the first sample after it turns on is taken straight away
+        effects.decimate_phase = 1.0;
     }
+}
 
-    // Bitcrush
+/// Bitcrush stage of the channel chain
+fn apply_bitcrush_block(
+    samples: &mut [f32],
+    effects: &ChannelEffectState,
+    oversampler: &mut Oversampler,
+) {
     if effects.bitcrush_bits < 16 {
         let quantization_levels = 2.0_f32.powi(effects.bitcrush_bits as i32);
-        sample = (sample * quantization_levels).round() / quantization_levels;
+        oversampler.process_block(samples, effects.oversampling, |sample| {
+            (sample * quantization_levels).round() / quantization_levels
+        });
+    } else {
+        oversampler.idle();
     }
+}
 
-    // Distortion
+/// Distortion stage of the channel chain
+fn apply_distortion_block(
+    samples: &mut [f32],
+    effects: &ChannelEffectState,
+    oversampler: &mut Oversampler,
+) {
     if effects.distortion_amount > 0.0 {
         let drive = 1.0 + effects.distortion_amount * 10.0;
-        let driven_sample = sample * drive;
-        sample = driven_sample / (1.0 + driven_sample.abs());
+        oversampler.process_block(samples, effects.oversampling, |sample| {
+            let driven_sample = sample * drive;
+            driven_sample / (1.0 + driven_sample.abs())
+        });
+    } else {
+        oversampler.idle();
     }
-
-    // Amplitude
-    sample *= effects.amplitude;
-
-    // Pan (constant-power)
-    let pan_left_coefficient = ((1.0 - effects.pan) * 0.5).sqrt();
-    let pan_right_coefficient = ((1.0 + effects.pan) * 0.5).sqrt();
-
-    (
-        sample * pan_left_coefficient,
-        sample * pan_right_coefficient,
-    )
 }
 
-/// Calculate vibrato frequency multiplier
-pub fn calculate_vibrato_multiplier(effects: &mut ChannelEffectState, sample_rate: u32) -> f32 {
+/// Returns the vibrato frequency multipliers at the start and end of a
+/// block, and moves the vibrato LFO forward by the block length
+pub fn advance_vibrato(
+    effects: &mut ChannelEffectState,
+    sample_rate: u32,
+    block_length: usize,
+) -> (f32, f32) {
     if effects.vibrato_rate_hz > 0.0 && effects.vibrato_depth_semitones > 0.0 {
-        let lfo = effects.vibrato_phase.sin();
-        let frequency_multiplier = 2.0_f32.powf(lfo * effects.vibrato_depth_semitones / 12.0);
-
-        effects.vibrato_phase += TWO_PI * effects.vibrato_rate_hz / sample_rate as f32;
-        if effects.vibrato_phase >= TWO_PI {
-            effects.vibrato_phase -= TWO_PI;
-        }
-
-        frequency_multiplier
+        let (start_lfo, end_lfo) = advance_lfo(
+            &mut effects.vibrato_phase,
+            effects.vibrato_rate_hz,
+            sample_rate,
+            block_length,
+        );
+        let depth_octaves = effects.vibrato_depth_semitones / 12.0;
+        (
+            2.0_f32.powf(start_lfo * depth_octaves),
+            2.0_f32.powf(end_lfo * depth_octaves),
+        )
     } else {
-        1.0
+        (1.0, 1.0)
     }
 }
 
-/// Apply mono chorus effect
-fn apply_mono_chorus(input_sample: f32, effects: &mut ChannelEffectState, sample_rate: u32) -> f32 {
-    if effects.chorus_buffer.is_empty() {
-        return input_sample;
-    }
+/// Reads a sine LFO at the start and end of a block and advances its phase
+fn advance_lfo(phase: &mut f32, rate_hz: f32, sample_rate: u32, block_length: usize) -> (f32, f32) {
+    let start_lfo = lookup_sine(*phase);
+    *phase =
+        (*phase + TWO_PI * rate_hz * block_length as f32 / sample_rate as f32).rem_euclid(TWO_PI);
+    (start_lfo, lookup_sine(*phase))
+}
 
+/// Apply mono chorus effect (the LFO value comes from the block)
+fn apply_mono_chorus(
+    input_sample: f32,
+    effects: &mut ChannelEffectState,
+    sample_rate: u32,
+    lfo: f32,
+) -> f32 {
     let buffer_len = effects.chorus_buffer.len();
     let base_delay_ms = 7.0;
-    let lfo = effects.chorus_phase.sin();
     let modulated_delay_ms = base_delay_ms + lfo * effects.chorus_depth_ms;
     let delay_samples = (modulated_delay_ms / 1000.0 * sample_rate as f32).max(1.0);
 
@@ -355,14 +1196,9 @@ fn apply_mono_chorus(input_sample: f32, effects: &mut ChannelEffectState, sample
     );
 
     effects.chorus_buffer[effects.chorus_write_position] =
-        input_sample + delayed_sample * effects.chorus_feedback;
+        flush_denormal(input_sample + delayed_sample * effects.chorus_feedback);
     effects.chorus_write_position = (effects.chorus_write_position + 1) % buffer_len;
 
-    effects.chorus_phase += TWO_PI * effects.chorus_rate_hz / sample_rate as f32;
-    if effects.chorus_phase >= TWO_PI {
-        effects.chorus_phase -= TWO_PI;
-    }
-
     lerp(input_sample, delayed_sample, effects.chorus_mix)
 }
 
@@ -370,54 +1206,124 @@ fn apply_mono_chorus(input_sample: f32, effects: &mut ChannelEffectState, sample
 // MASTER EFFECT PROCESSING
 // ============================================================================
 
+/// Samples the master chain works through at a time (its dry copy is
+/// kept on the stack)
+const MASTER_BLOCK_SIZE: usize = 64;
+
 /// Applies all master effects to a stereo signal
 pub fn apply_master_effects(
-    mut left: f32,
-    mut right: f32,
+    left: f32,
+    right: f32,
     effects: &mut MasterEffectState,
     sample_rate: u32,
 ) -> (f32, f32) {
-    // Reverb 1
-    if effects.reverb1_enabled && effects.reverb1_mix > 0.001 {
-        let (l, r) = apply_reverb1(left, right, effects, sample_rate);
-        left = l;
-        right = r;
-    }
+    let (mut left, mut right) = ([left], [right]);
+    apply_master_effects_block(
+        &mut left,
+        &mut right,
+        effects,
+        sample_rate,
+        &mut StageTimer::default(),
+    );
+    (left[0], right[0])
+}
 
-    // Reverb 2
-    if effects.reverb2_enabled && effects.reverb2_mix > 0.001 {
-        let (l, r) = apply_reverb2(left, right, effects, sample_rate);
-        left = l;
-        right = r;
-    }
+/// Applies all master effects to a block of stereo samples in place
+///
+/// Each effect runs over the whole block before the next one starts.
+/// They only pass audio to each other (their state is their own), so this
+/// sounds exactly like running the chain one sample at a time, and lets
+/// `timer` measure each effect on its own (see dsp_load.rs).
+pub fn apply_master_effects_block(
+    left: &mut [f32],
+    right: &mut [f32],
+    effects: &mut MasterEffectState,
+    sample_rate: u32,
+    timer: &mut StageTimer,
+) {
+    for (left, right) in left
+        .chunks_mut(MASTER_BLOCK_SIZE)
+        .zip(right.chunks_mut(MASTER_BLOCK_SIZE))
+    {
+        let length = left.len().min(right.len());
+        let (left, right) = (&mut left[..length], &mut right[..length]);
+        let mut dry_left = [0.0; MASTER_BLOCK_SIZE];
+        let mut dry_right = [0.0; MASTER_BLOCK_SIZE];
+        dry_left[..length].copy_from_slice(left);
+        dry_right[..length].copy_from_slice(right);
 
-    // Delay
-    if effects.delay_enabled && effects.delay_feedback > 0.001 {
-        let (l, r) = apply_delay(left, right, effects);
-        left = l;
-        right = r;
-    }
+        // Reverb 1
+        if effects.reverb1_enabled && effects.reverb1_mix > 0.001 {
+            let level = effects.reverb1_level;
+            run_master_stage(left, right, level, timer, DspStage::Reverb, |l, r| {
+                apply_reverb1(l, r, effects, sample_rate)
+            });
+        }
 
-    // Chorus
-    if effects.chorus_enabled && effects.chorus_mix > 0.001 {
-        let (l, r) = apply_master_chorus(left, right, effects, sample_rate);
-        left = l;
-        right = r;
-    }
+        // Reverb 2
+        if effects.reverb2_enabled && effects.reverb2_mix > 0.001 {
+            let level = effects.reverb2_level;
+            run_master_stage(left, right, level, timer, DspStage::Reverb2, |l, r| {
+                apply_reverb2(l, r, effects, sample_rate)
+            });
+        }
 
-    // Master amplitude
-    left *= effects.amplitude;
-    right *= effects.amplitude;
+        // Delay
+        if effects.delay_enabled && effects.delay_feedback > 0.001 {
+            let level = effects.delay_level;
+            run_master_stage(left, right, level, timer, DspStage::Delay, |l, r| {
+                apply_delay(l, r, effects, sample_rate)
+            });
+        }
+
+        // Chorus
+        if effects.chorus_enabled && effects.chorus_mix > 0.001 {
+            let level = effects.chorus_level;
+            run_master_stage(left, right, level, timer, DspStage::BusChorus, |l, r| {
+                apply_master_chorus(l, r, effects, sample_rate)
+            });
+        }
 
-    // Master pan
-    if effects.pan != 0.0 {
-        let pan_left = ((1.0 - effects.pan) * 0.5).sqrt();
-        let pan_right = ((1.0 + effects.pan) * 0.5).sqrt();
-        left *= pan_left;
-        right *= pan_right;
+        // Dry/wet for the whole chain (volume and pan apply to both)
+        let pan = (effects.pan != 0.0).then(|| {
+            (
+                ((1.0 - effects.pan) * 0.5).sqrt(),
+                ((1.0 + effects.pan) * 0.5).sqrt(),
+            )
+        });
+        for (index, (left, right)) in left.iter_mut().zip(right.iter_mut()).enumerate() {
+            *left = lerp(dry_left[index], *left, effects.wet);
+            *right = lerp(dry_right[index], *right, effects.wet);
+
+            // Master amplitude
+            *left *= effects.amplitude;
+            *right *= effects.amplitude;
+
+            // Master pan
+            if let Some((pan_left, pan_right)) = pan {
+                *left *= pan_left;
+                *right *= pan_right;
+            }
+        }
     }
+}
 
-    (left, right)
+/// Runs one master effect over a block, blending its output in by `level`
+fn run_master_stage(
+    left: &mut [f32],
+    right: &mut [f32],
+    level: f32,
+    timer: &mut StageTimer,
+    stage: DspStage,
+    mut effect: impl FnMut(f32, f32) -> (f32, f32),
+) {
+    let started = timer.start();
+    for (left, right) in left.iter_mut().zip(right.iter_mut()) {
+        let (l, r) = effect(*left, *right);
+        *left = lerp(*left, l, level);
+        *right = lerp(*right, r, level);
+    }
+    timer.stop(stage, started);
 }
 
 fn apply_reverb1(
@@ -438,7 +1344,8 @@ fn apply_reverb1(
     let reverb_sample = effects.reverb1_buffer[read_pos];
 
     let mono_input = (left + right) * 0.5;
-    effects.reverb1_buffer[effects.reverb1_position] = mono_input + reverb_sample * 0.5;
+    effects.reverb1_buffer[effects.reverb1_position] =
+        flush_denormal(mono_input + reverb_sample * 0.5);
     effects.reverb1_position = (effects.reverb1_position + 1) % effects.reverb1_buffer.len();
 
     let wet = reverb_sample * effects.reverb1_mix;
@@ -453,123 +1360,150 @@ fn apply_reverb2(
     effects: &mut MasterEffectState,
     sample_rate: u32,
 ) -> (f32, f32) {
-    if effects.reverb2_comb_buffers.is_empty() {
+    if effects.reverb2_left.comb_buffers.is_empty() {
         return (left, right);
     }
 
-    let mono_input = (left + right) * 0.5;
-    let room_scale = 0.3 + effects.reverb2_room_size * 0.7;
-
-    // Early reflections
-    let mut early_reflections = 0.0;
-    for i in 0..effects.reverb2_early_buffers.len() {
-        if effects.reverb2_early_buffers[i].is_empty() {
-            continue;
-        }
+    // Each side mostly hears itself, so panned sources stay put in the tail
+    let input_left = lerp(left, right, REVERB2_CROSS_FEED);
+    let input_right = lerp(right, left, REVERB2_CROSS_FEED);
 
-        let buffer_len = effects.reverb2_early_buffers[i].len();
-        let delay = ((buffer_len as f32 * room_scale) as usize)
-            .min(buffer_len - 1)
-            .max(1);
-
-        let read_pos = (effects.reverb2_early_positions[i] + buffer_len - delay) % buffer_len;
-        early_reflections +=
-            effects.reverb2_early_buffers[i][read_pos] * (0.7_f32.powi(i as i32 + 1));
-
-        effects.reverb2_early_buffers[i][effects.reverb2_early_positions[i]] = mono_input;
-        effects.reverb2_early_positions[i] = (effects.reverb2_early_positions[i] + 1) % buffer_len;
-    }
-    early_reflections /= effects.reverb2_early_buffers.len() as f32;
+    let room_scale = 0.3 + effects.reverb2_room_size * 0.7;
+    let (decay, damping) = (effects.reverb2_decay, effects.reverb2_damping);
+    let wet_left =
+        effects
+            .reverb2_left
+            .process(input_left, room_scale, decay, damping, sample_rate);
+    let wet_right =
+        effects
+            .reverb2_right
+            .process(input_right, room_scale, decay, damping, sample_rate);
 
-    // Comb filters
-    let mut comb_output = 0.0;
-    let target_decay_samples = effects.reverb2_decay * sample_rate as f32;
+    let dry = 1.0 - effects.reverb2_mix;
+    (
+        soft_clip(left * dry + wet_left * effects.reverb2_mix),
+        soft_clip(right * dry + wet_right * effects.reverb2_mix),
+    )
+}
 
-    for i in 0..effects.reverb2_comb_buffers.len() {
-        if effects.reverb2_comb_buffers[i].is_empty() {
-            continue;
+impl ReverbNetwork {
+    /// Runs one input sample through the network and returns its output
+    fn process(
+        &mut self,
+        input: f32,
+        room_scale: f32,
+        decay: f32,
+        damping: f32,
+        sample_rate: u32,
+    ) -> f32 {
+        // Early reflections
+        let mut early_reflections = 0.0;
+        for i in 0..self.early_buffers.len() {
+            let buffer = &mut self.early_buffers[i];
+            let buffer_len = buffer.len();
+            let delay = ((buffer_len as f32 * room_scale) as usize)
+                .min(buffer_len - 1)
+                .max(1);
+
+            let read_pos = (self.early_positions[i] + buffer_len - delay) % buffer_len;
+            early_reflections += buffer[read_pos] * (0.7_f32.powi(i as i32 + 1));
+
+            buffer[self.early_positions[i]] = input;
+            self.early_positions[i] = (self.early_positions[i] + 1) % buffer_len;
         }
-
-        let buffer_len = effects.reverb2_comb_buffers[i].len();
-        let delay = ((buffer_len as f32 * room_scale) as usize)
-            .min(buffer_len - 1)
-            .max(1);
-
-        let read_pos = (effects.reverb2_comb_positions[i] + buffer_len - delay) % buffer_len;
-        let delayed = effects.reverb2_comb_buffers[i][read_pos];
-
-        effects.reverb2_comb_filters[i] = lerp(
-            delayed,
-            effects.reverb2_comb_filters[i],
-            effects.reverb2_damping,
-        );
-        let filtered = effects.reverb2_comb_filters[i];
-
-        let delay_time = delay as f32 / sample_rate as f32;
-        let feedback = if target_decay_samples > 0.0 {
-            10.0_f32
-                .powf(-3.0 * delay_time / effects.reverb2_decay)
-                .min(0.98)
-        } else {
-            0.5
-        };
-
-        let input_with_early = mono_input + early_reflections * 0.3;
-        effects.reverb2_comb_buffers[i][effects.reverb2_comb_positions[i]] =
-            input_with_early + filtered * feedback;
-        effects.reverb2_comb_positions[i] = (effects.reverb2_comb_positions[i] + 1) % buffer_len;
-
-        comb_output += delayed;
-    }
-    comb_output /= effects.reverb2_comb_buffers.len() as f32;
-
-    // All-pass filters
-    let mut allpass_output = comb_output;
-    let allpass_gain = 0.5;
-
-    for i in 0..effects.reverb2_allpass_buffers.len() {
-        if effects.reverb2_allpass_buffers[i].is_empty() {
-            continue;
+        early_reflections /= self.early_buffers.len() as f32;
+
+        // Comb filters
+        let mut comb_output = 0.0;
+        for i in 0..self.comb_buffers.len() {
+            let buffer = &mut self.comb_buffers[i];
+            let buffer_len = buffer.len();
+            let delay = ((buffer_len as f32 * room_scale) as usize)
+                .min(buffer_len - 1)
+                .max(1);
+
+            let read_pos = (self.comb_positions[i] + buffer_len - delay) % buffer_len;
+            let delayed = buffer[read_pos];
+
+            self.comb_filters[i] = flush_denormal(lerp(delayed, self.comb_filters[i], damping));
+            let filtered = self.comb_filters[i];
+
+            let delay_time = delay as f32 / sample_rate as f32;
+            let feedback = if decay > 0.0 {
+                10.0_f32.powf(-3.0 * delay_time / decay).min(0.98)
+            } else {
+                0.5
+            };
+
+            let input_with_early = input + early_reflections * 0.3;
+            buffer[self.comb_positions[i]] = flush_denormal(input_with_early + filtered * feedback);
+            self.comb_positions[i] = (self.comb_positions[i] + 1) % buffer_len;
+
+            comb_output += delayed;
+        }
+        comb_output /= self.comb_buffers.len() as f32;
+
+        // All-pass filters
+        let mut allpass_output = comb_output;
+        let allpass_gain = 0.5;
+        for i in 0..self.allpass_buffers.len() {
+            let buffer = &mut self.allpass_buffers[i];
+            let buffer_len = buffer.len();
+            let read_pos = (self.allpass_positions[i] + buffer_len - (buffer_len - 1)) % buffer_len;
+
+            let delayed = buffer[read_pos];
+            let output = -allpass_output * allpass_gain + delayed;
+            buffer[self.allpass_positions[i]] =
+                flush_denormal(allpass_output + delayed * allpass_gain);
+            self.allpass_positions[i] = (self.allpass_positions[i] + 1) % buffer_len;
+
+            allpass_output = output;
         }
 
-        let buffer_len = effects.reverb2_allpass_buffers[i].len();
-        let read_pos =
-            (effects.reverb2_allpass_positions[i] + buffer_len - (buffer_len - 1)) % buffer_len;
-
-        let delayed = effects.reverb2_allpass_buffers[i][read_pos];
-        let output = -allpass_output * allpass_gain + delayed;
-        effects.reverb2_allpass_buffers[i][effects.reverb2_allpass_positions[i]] =
-            allpass_output + delayed * allpass_gain;
-        effects.reverb2_allpass_positions[i] =
-            (effects.reverb2_allpass_positions[i] + 1) % buffer_len;
-
-        allpass_output = output;
+        allpass_output
     }
-
-    let wet = allpass_output * effects.reverb2_mix;
-    let dry = 1.0 - effects.reverb2_mix;
-
-    (soft_clip(left * dry + wet), soft_clip(right * dry + wet))
 }
 
-fn apply_delay(left: f32, right: f32, effects: &mut MasterEffectState) -> (f32, f32) {
+fn apply_delay(
+    left: f32,
+    right: f32,
+    effects: &mut MasterEffectState,
+    sample_rate: u32,
+) -> (f32, f32) {
     if effects.delay_buffer_left.is_empty() {
         return (left, right);
     }
 
+    // Glide toward the set time (one-pole smoothing)
+    let glide_step = 1.0 / (DELAY_GLIDE_SECONDS * sample_rate as f32);
+    effects.delay_glide_samples +=
+        (effects.delay_time_samples as f32 - effects.delay_glide_samples) * glide_step.min(1.0);
+
+    // Read between two samples, so a gliding time moves smoothly
     let buffer_len = effects.delay_buffer_left.len();
-    let delay_samples = (effects.delay_time_samples as usize)
-        .min(buffer_len - 1)
-        .max(1);
+    let delay_samples = effects
+        .delay_glide_samples
+        .clamp(1.0, (buffer_len - 1) as f32);
+    let delay_int = delay_samples as usize;
+    let delay_frac = delay_samples - delay_int as f32;
 
-    let read_pos = (effects.delay_write_position + buffer_len - delay_samples) % buffer_len;
-    let delayed_left = effects.delay_buffer_left[read_pos];
-    let delayed_right = effects.delay_buffer_right[read_pos];
+    let read_pos_1 = (effects.delay_write_position + buffer_len - delay_int) % buffer_len;
+    let read_pos_2 = (read_pos_1 + buffer_len - 1) % buffer_len;
+    let delayed_left = lerp(
+        effects.delay_buffer_left[read_pos_1],
+        effects.delay_buffer_left[read_pos_2],
+        delay_frac,
+    );
+    let delayed_right = lerp(
+        effects.delay_buffer_right[read_pos_1],
+        effects.delay_buffer_right[read_pos_2],
+        delay_frac,
+    );
 
     effects.delay_buffer_left[effects.delay_write_position] =
-        left + delayed_left * effects.delay_feedback;
+        flush_denormal(left + delayed_left * effects.delay_feedback);
     effects.delay_buffer_right[effects.delay_write_position] =
-        right + delayed_right * effects.delay_feedback;
+        flush_denormal(right + delayed_right * effects.delay_feedback);
     effects.delay_write_position = (effects.delay_write_position + 1) % buffer_len;
 
     (left + delayed_left * 0.5, right + delayed_right * 0.5)
@@ -588,11 +1522,11 @@ fn apply_master_chorus(
     let buffer_len = effects.chorus_buffer_left.len();
     let base_delay_ms = 7.0;
 
-    let lfo_left = effects.chorus_phase.sin();
+    let lfo_left = lookup_sine(effects.chorus_phase);
     let modulated_delay_left = base_delay_ms + lfo_left * effects.chorus_depth_ms;
     let delay_samples_left = (modulated_delay_left / 1000.0 * sample_rate as f32).max(1.0);
 
-    let lfo_right = (effects.chorus_phase + PI * effects.chorus_stereo_spread).sin();
+    let lfo_right = lookup_sine(effects.chorus_phase + PI * effects.chorus_stereo_spread);
     let modulated_delay_right = base_delay_ms + lfo_right * effects.chorus_depth_ms;
     let delay_samples_right = (modulated_delay_right / 1000.0 * sample_rate as f32).max(1.0);
 
@@ -12,17 +12,159 @@
 // 3. Dispatches cell actions to channels and master bus
 // 4. Mixes all channel outputs together
 // 5. Passes the mix through the master bus
-// 6. Outputs final audio samples
+// 6. Removes any DC offset (master_bus.rs DcBlocker)
+// 7. Outputs final audio samples
+//
+// CHANNEL GROUPS:
+// Channels named in a [groups] block are not added to the mix directly.
+// They are summed on their group's own bus (a MasterBus with its own
+// amplitude, pan, reverb, delay, and chorus), and each group bus is then
+// added to the mix, after the ungrouped channels and in the order the
+// groups were defined. The master bus sees the result like any other mix.
+//
+// FADE-OUT:
+// fade_out (Ctrl+C, EngineCommand::FadeOut, or a "master fadeout:8" cell)
+// ramps the final mix down to silence, reverb and delay tails included.
+// When the ramp reaches zero the song is over: no more rows start, and the
+// engine reports itself finished and silent.
 //
 // TIMING:
 // Each row in the CSV plays for TICK_DURATION_SEC seconds.
 // At 48000 Hz sample rate and 0.25s per row, that's 12000 samples per row.
 // The engine counts samples and advances to the next row when needed.
+//
+// SAMPLE RATE:
+// Nothing assumes a particular rate. Every time and frequency in a song is
+// in seconds or Hz and is turned into samples with EngineConfig's
+// sample_rate, and the reverb, delay, and chorus lines are sized from it
+// when the engine is built, so one song sounds the same at 44.1, 48, or
+// 96 kHz ("--sample-rate" in the player picks one).
+//
+// EXTERNAL CLOCK:
+// With ClockSource::External the sample count no longer moves the song
+// on. Rows only start when an AdvanceRow command arrives (midi_clock.rs
+// sends one every few MIDI clock pulses), so another device sets the
+// tempo. Commands are read between audio buffers, so the rows that
+// arrived since the last buffer all start in the next one, spread evenly
+// through it (a row per sample at most; any left over wait for the next
+// buffer). Rows are only as precise as the buffer is short - keep it small
+// when following a clock.
+//
+// REAL-TIME PLAYBACK:
+// For live audio, split the engine with into_realtime(). The audio thread
+// owns the RealtimeEngine outright; the main thread keeps an
+// EngineController and talks to it only through lock-free queues
+// (command_queue.rs), so the audio callback never waits on a lock.
+//
+// The audio path also never allocates: channel buffers are created up
+// front in Channel::new, triggers copy into that storage, and rows are
+// dispatched by reference instead of being cloned. Nor does it free: cell
+// actions sent with a Dispatch command go back through another queue and
+// are dropped on the main thread by EngineController::send/poll_event.
+//
+// RANDOM NUMBERS:
+// Noise comes from each channel's own random number generator, started
+// from the song's seed (the "seed" config setting, 0 if not given) and the
+// channel number. The same song therefore renders bit-for-bit the same
+// every time, and a different seed gives different noise. A "seed:N"
+// token on a trigger restarts that channel's generator, so a noise hit
+// can sound exactly the same every time it plays.
+//
+// LOOP CROSSFADE:
+// A loop can crossfade instead of jumping straight back. When it wraps,
+// the channels that played the pass just ended are swapped out for a
+// second set, which starts the loop again. The old set keeps sounding
+// (held notes, releases) while it fades out and the new pass fades in,
+// with equal-power curves so the level doesn't dip in the middle. The
+// group buses and master bus are not touched, so reverb and delay tails
+// run on across the seam.
+//
+// STREAMING:
+// A streamed song (song_stream.rs) comes with no rows. Each row is popped
+// from a RowFeed when it is due, dispatched like any other, and handed
+// back to the parser thread to be freed. The End marker finishes the song
+// as running out of rows would. Jumping, looping, and reset can't go back
+// to rows that were already played, so they are for whole songs only.
+//
+// TEMPO SYNC:
+// Effect times written as note lengths ("t:1/16'0.5", "dl:1/8.'0.4")
+// need the length of a beat. The engine hands it to every channel and bus
+// at the start (tick_duration_seconds * DEFAULT_ROWS_PER_BEAT) and again
+// on each bpm command, and they re-time whatever follows the tempo.
+//
+// OFFLINE BOUNCE:
+// Between two rows the channels never affect each other, so WAV export
+// renders each row's stretch of every channel on its own thread (rayon),
+// then adds the channel buffers together in channel order. The sum is
+// done the same way as the single-threaded mix, so the result is
+// bit-for-bit identical no matter how many threads ran.
+//
+// FROZEN CHANNELS:
+// freeze_channel renders one channel's part of the song ahead of time, on
+// a private copy of the engine, and from then on plays that recording in
+// the channel's place (freeze.rs). Cells are no longer dispatched to the
+// channel, so it costs no CPU; the recording still goes through the
+// channel's group bus and the master bus, and jumps to each row's start
+// as the row begins, so it keeps in step with jumps and loops.
+//
+// DSP LOAD:
+// The realtime engine times every callback against the length of its
+// buffer and counts the ones that ran late (dsp_load.rs). With
+// set_stage_timing on, every channel and bus also times its voice and
+// each effect, and the controller's dsp_load() report shows which of them
+// costs the most, for finding what to cut when a slow machine can't keep up.
+//
+// PLAYLISTS:
+// queue_song hands the engine the next song of a set, already built on
+// the main thread (a LoadedSong: rows, channels, group buses). When the
+// playing song runs out of rows, the next one starts on the very next
+// sample instead of the engine finishing. The old song's channels and
+// group buses move aside and keep sounding, fading out over the song
+// crossfade (equal power, like LOOP CROSSFADE) while the new song fades
+// in; with no crossfade the new song starts at full level and the old
+// one's release tails ring out under it. The master bus is never
+// replaced, so its reverb and delay carry across the join. Once the old
+// song is quiet it is parked in retired_song, for the realtime engine to
+// send back to the main thread to be freed. Whole songs only: a streamed
+// song finishes as usual, and a looped one never runs out of rows.
+//
+// AUDIO INPUT:
+// process_frame_with_input takes the samples a duplex device captured
+// alongside the output buffer. Each segment hands its stretch of them to
+// the channels playing the "input" instrument, which read it in place of
+// an oscillator, so the sound goes through the channel's envelope and
+// effects and the master bus like any other voice. Without input (or
+// when the input runs short) those channels play silence.
 // ============================================================================
 
-use crate::channel::Channel;
-use crate::master_bus::MasterBus;
-use crate::parser::{CellAction, DebugLevel, SongData};
+use crate::channel::{Channel, RENDER_BLOCK_SIZE};
+use crate::command_queue::{Consumer, Producer, command_queue};
+use crate::dsp_load::{CallbackTimer, DSP_STAGE_COUNT, DspLoadMeter, DspLoadReport};
+use crate::effects::CustomEffectSlot;
+use crate::freeze::FrozenChannel;
+use crate::helper::RandomNumberGenerator;
+use crate::master_bus::{DcBlocker, MasterBus, SILENCE_LEVEL};
+use crate::parser::{
+    CellAction, ChannelGroup, DEFAULT_ROWS_PER_BEAT, MAX_CHANNEL_COUNT, SongData,
+    tick_duration_from_bpm,
+};
+use crate::snapshot::{BusSnapshot, ChannelSnapshot, EngineSnapshot};
+use crate::song_stream::{RowFeed, StreamedRow};
+use rayon::prelude::*;
+use std::f32::consts::FRAC_PI_2;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Instant;
+
+/// Lowest sample rate the player accepts (--sample-rate)
+pub const MIN_SAMPLE_RATE: u32 = 8000;
+
+/// Highest sample rate the player accepts (--sample-rate)
+pub const MAX_SAMPLE_RATE: u32 = 192_000;
+
+/// Longest tail render_to_buffer adds after the last row, in case
+/// something (a delay with high feedback) never quite dies away
+pub const MAX_RENDER_TAIL_SECONDS: f32 = 30.0;
 
 // ============================================================================
 // ENGINE CONFIGURATION
@@ -45,9 +187,21 @@ pub struct EngineConfig {
 
     /// Fast release time to avoid pops (seconds)
     pub fast_release_seconds: f32,
+}
 
-    /// Debug output level
-    pub debug_level: DebugLevel,
+/// Reads a sample rate: "44100", "96000 Hz", or "44.1k"
+/// Returns None if it isn't a number from MIN_SAMPLE_RATE to MAX_SAMPLE_RATE.
+pub fn parse_sample_rate(text: &str) -> Option<u32> {
+    let text = text.trim().to_lowercase();
+    let text = text.strip_suffix("hz").unwrap_or(&text).trim_end();
+    let hz = match text.strip_suffix('k') {
+        Some(kilohertz) => kilohertz.parse::<f64>().ok()? * 1000.0,
+        None => text.parse::<f64>().ok()?,
+    };
+    let hz = hz.round();
+    (MIN_SAMPLE_RATE as f64..=MAX_SAMPLE_RATE as f64)
+        .contains(&hz)
+        .then_some(hz as u32)
 }
 
 impl Default for EngineConfig {
@@ -58,11 +212,21 @@ impl Default for EngineConfig {
             tick_duration_seconds: 0.25,
             default_release_seconds: 2.0,
             fast_release_seconds: 0.05,
-            debug_level: DebugLevel::Off,
         }
     }
 }
 
+/// Where the engine's row clock comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockSource {
+    /// Rows last tick_duration_seconds (changed by bpm commands)
+    Internal,
+
+    /// Rows only advance when told to (EngineCommand::AdvanceRow or
+    /// PlaybackEngine::advance_external_row), e.g. by incoming MIDI clock
+    External,
+}
+
 // ============================================================================
 // PLAYBACK ENGINE
 // ============================================================================
@@ -90,11 +254,228 @@ pub struct PlaybackEngine {
     /// The master output bus
     master_bus: MasterBus,
 
+    /// DC blockers on the final output (left, right)
+    dc_blockers: [DcBlocker; 2],
+
+    /// Submix buses for the song's channel groups
+    group_buses: Vec<GroupBus>,
+
+    /// Which group bus each channel plays into (None: straight to the mix)
+    channel_groups: Vec<Option<usize>>,
+
     /// Whether playback has finished
     playback_finished: bool,
 
     /// Total samples rendered (for statistics)
     total_samples_rendered: u64,
+
+    /// Whether running out of rows ends playback (false keeps the
+    /// channels sounding, for programs that trigger cells live)
+    stop_at_end: bool,
+
+    /// What moves the song on to the next row
+    clock_source: ClockSource,
+
+    /// External clock rows that arrived but have not started yet
+    external_rows_pending: u32,
+
+    /// Channels muted by the user (kept through reset)
+    muted: Vec<bool>,
+
+    /// Channels soloed by the user; while any are, only they are heard
+    soloed: Vec<bool>,
+
+    /// Row after the last one to play (None: the end of the song)
+    end_row: Option<usize>,
+
+    /// Row playback continues from after end_row (None: it stops there)
+    loop_start_row: Option<usize>,
+
+    /// Length of the crossfade when the loop wraps (0: jump straight back)
+    loop_crossfade_length: u32,
+
+    /// The channels of the pass before the loop wrapped, fading out
+    /// (empty unless a crossfade length is set)
+    fading_channels: Vec<Channel>,
+
+    /// Samples into the running loop crossfade (None: not crossfading)
+    loop_crossfade_position: Option<u32>,
+
+    /// One fading channel's output for one block
+    fade_left: Vec<f32>,
+    fade_right: Vec<f32>,
+
+    /// Samples left in a fade_out (None: not fading; Some(0): faded out)
+    fade_out_samples_left: Option<u32>,
+
+    /// Length of the fade_out in samples
+    fade_out_length: u32,
+
+    /// Mixing buffers for one block (allocated once, reused every block)
+    mix_left: Vec<f32>,
+    mix_right: Vec<f32>,
+
+    /// Per-channel output for multithreaded rendering (empty until used)
+    channel_buffers: Vec<ChannelBuffer>,
+
+    /// Where rows come from when the song is streamed (None: song.rows)
+    row_feed: Option<RowFeed>,
+
+    /// Whether channels and buses time their stages (see DSP LOAD)
+    stage_timing: bool,
+
+    /// Recordings played in place of frozen channels (see FROZEN
+    /// CHANNELS; empty until a channel is frozen)
+    frozen: Vec<Option<FrozenChannel>>,
+
+    /// The song that starts when this one runs out of rows (see PLAYLISTS)
+    next_song: Option<LoadedSong>,
+
+    /// Length of the crossfade from one song into the next (0: the old
+    /// song's tails ring out under the new one)
+    song_crossfade_length: u32,
+
+    /// The song before the one playing, still sounding (see PLAYLISTS)
+    outgoing_song: Option<LoadedSong>,
+
+    /// Samples into the song crossfade
+    song_crossfade_position: u32,
+
+    /// An old song that has gone quiet, waiting to be freed elsewhere
+    retired_song: Option<LoadedSong>,
+
+    /// How many queued songs have started
+    songs_started: usize,
+}
+
+/// A song built ahead of time, ready for queue_song (see PLAYLISTS)
+/// Everything that allocates is done here, off the audio thread.
+pub struct LoadedSong {
+    song: SongData,
+    config: EngineConfig,
+    channels: Vec<Channel>,
+    group_buses: Vec<GroupBus>,
+    channel_groups: Vec<Option<usize>>,
+    frozen: Vec<Option<FrozenChannel>>,
+}
+
+impl LoadedSong {
+    /// Builds a song's channels and group buses. The config's sample rate
+    /// must be the engine's; its channel count and tick duration are the
+    /// song's own.
+    pub fn new(song: SongData, config: EngineConfig) -> Self {
+        let channels = create_channels(&config, song.config.seed.unwrap_or(0));
+        let (group_buses, channel_groups) = create_group_buses(&song, &config);
+        Self {
+            song,
+            config,
+            channels,
+            group_buses,
+            channel_groups,
+            frozen: Vec::new(),
+        }
+    }
+
+    /// Whether every channel has stopped and the group buses are quiet
+    fn is_quiet(&self) -> bool {
+        !self.channels.iter().any(Channel::is_playing)
+            && self.group_buses.iter().all(|group| group.bus.is_quiet())
+    }
+}
+
+/// Creates the engine's channels, with random numbers from the song's seed
+fn create_channels(config: &EngineConfig, song_seed: u32) -> Vec<Channel> {
+    (0..config.channel_count)
+        .map(|id| {
+            let mut channel = Channel::new(id, config.sample_rate);
+            channel.random_generator = RandomNumberGenerator::for_channel(song_seed, id);
+            channel
+        })
+        .collect()
+}
+
+/// Creates a bus for each of the song's channel groups, and which group
+/// bus each channel plays into
+fn create_group_buses(
+    song: &SongData,
+    config: &EngineConfig,
+) -> (Vec<GroupBus>, Vec<Option<usize>>) {
+    let group_buses = song
+        .groups
+        .iter()
+        .map(|group| GroupBus::new(group, config.sample_rate))
+        .collect();
+    let mut channel_groups = vec![None; config.channel_count];
+    for (group_index, group) in song.groups.iter().enumerate() {
+        for &channel_index in &group.channels {
+            if let Some(slot) = channel_groups.get_mut(channel_index) {
+                *slot = Some(group_index);
+            }
+        }
+    }
+    (group_buses, channel_groups)
+}
+
+/// A channel group's bus and its mixing buffers
+struct GroupBus {
+    /// Group name, as used by "group name ..." cells
+    name: String,
+
+    /// Effects for the group's submix
+    bus: MasterBus,
+
+    /// Sum of the group's channels for the current block
+    left: Vec<f32>,
+    right: Vec<f32>,
+}
+
+impl GroupBus {
+    /// Creates the bus for a group with its starting effects applied
+    fn new(group: &ChannelGroup, sample_rate: u32) -> Self {
+        let mut bus = MasterBus::new(sample_rate);
+        for (effect_name, params) in &group.effects {
+            bus.apply_effect(effect_name, params, 0.0);
+        }
+        Self {
+            name: group.name.clone(),
+            bus,
+            left: vec![0.0; RENDER_BLOCK_SIZE],
+            right: vec![0.0; RENDER_BLOCK_SIZE],
+        }
+    }
+}
+
+/// Runs each group's sum through its bus and adds it to the mix
+/// (in group order, one block at a time like the master bus). Buses run
+/// even while their channels are silent, so reverb and delay tails go on.
+fn mix_group_buses(group_buses: &mut [GroupBus], left_mix: &mut [f32], right_mix: &mut [f32]) {
+    let length = left_mix.len();
+    for group_bus in group_buses {
+        let left_group = &mut group_bus.left[..length];
+        let right_group = &mut group_bus.right[..length];
+        for (left_block, right_block) in left_group
+            .chunks_mut(RENDER_BLOCK_SIZE)
+            .zip(right_group.chunks_mut(RENDER_BLOCK_SIZE))
+        {
+            group_bus.bus.process_block(left_block, right_block);
+        }
+        for (mix, sample) in left_mix.iter_mut().zip(left_group.iter()) {
+            *mix += sample;
+        }
+        for (mix, sample) in right_mix.iter_mut().zip(right_group.iter()) {
+            *mix += sample;
+        }
+    }
+}
+
+/// One channel's private output while channels render on several threads
+#[derive(Default)]
+struct ChannelBuffer {
+    left: Vec<f32>,
+    right: Vec<f32>,
+
+    /// Whether the channel was playing (silent channels are not mixed)
+    was_rendered: bool,
 }
 
 impl PlaybackEngine {
@@ -103,25 +484,38 @@ impl PlaybackEngine {
         // Calculate samples per row
         let samples_per_row = (config.tick_duration_seconds * config.sample_rate as f32) as u32;
 
-        // Create channels
-        let channels: Vec<Channel> = (0..config.channel_count)
-            .map(|id| Channel::new(id, config.sample_rate))
-            .collect();
+        // Create channels, with random numbers from the song's seed
+        let channels = create_channels(&config, song.config.seed.unwrap_or(0));
+
+        let channel_count = channels.len();
 
         // Create master bus
         let master_bus = MasterBus::new(config.sample_rate);
+        let dc_blockers = [
+            DcBlocker::new(config.sample_rate),
+            DcBlocker::new(config.sample_rate),
+        ];
+
+        // Create a bus for each channel group
+        let (group_buses, channel_groups) = create_group_buses(&song, &config);
+
+        // Room for any song's channels, so a queued song with more of
+        // them doesn't allocate on the audio thread
+        let mut muted = Vec::with_capacity(MAX_CHANNEL_COUNT.max(channel_count));
+        muted.resize(channel_count, false);
+        let mut soloed = Vec::with_capacity(muted.capacity());
+        soloed.resize(channel_count, false);
+
+        log::debug!(
+            target: "engine",
+            "Initialized: {} channels, {} samples/row ({:.2}s/row), {} rows total",
+            config.channel_count,
+            samples_per_row,
+            config.tick_duration_seconds,
+            song.row_count()
+        );
 
-        if config.debug_level >= DebugLevel::Basic {
-            println!(
-                "[ENGINE] Initialized: {} channels, {} samples/row ({:.2}s/row), {} rows total",
-                config.channel_count,
-                samples_per_row,
-                config.tick_duration_seconds,
-                song.row_count()
-            );
-        }
-
-        Self {
+        let mut engine = Self {
             song,
             config,
             current_row: 0,
@@ -129,46 +523,581 @@ impl PlaybackEngine {
             samples_per_row,
             channels,
             master_bus,
+            dc_blockers,
+            group_buses,
+            channel_groups,
             playback_finished: false,
             total_samples_rendered: 0,
+            stop_at_end: true,
+            clock_source: ClockSource::Internal,
+            external_rows_pending: 0,
+            muted,
+            soloed,
+            end_row: None,
+            loop_start_row: None,
+            loop_crossfade_length: 0,
+            fading_channels: Vec::new(),
+            loop_crossfade_position: None,
+            fade_left: vec![0.0; RENDER_BLOCK_SIZE],
+            fade_right: vec![0.0; RENDER_BLOCK_SIZE],
+            fade_out_samples_left: None,
+            fade_out_length: 0,
+            mix_left: vec![0.0; RENDER_BLOCK_SIZE],
+            mix_right: vec![0.0; RENDER_BLOCK_SIZE],
+            channel_buffers: Vec::new(),
+            row_feed: None,
+            stage_timing: false,
+            frozen: Vec::new(),
+            next_song: None,
+            song_crossfade_length: 0,
+            outgoing_song: None,
+            song_crossfade_position: 0,
+            retired_song: None,
+            songs_started: 0,
+        };
+        engine.set_seconds_per_beat(engine.config.tick_duration_seconds * DEFAULT_ROWS_PER_BEAT);
+        engine
+    }
+
+    /// Tells every channel and bus how long a beat is (see TEMPO SYNC)
+    fn set_seconds_per_beat(&mut self, seconds_per_beat: f32) {
+        for channel in self.channels.iter_mut().chain(&mut self.fading_channels) {
+            channel.set_tempo(seconds_per_beat);
+        }
+        self.master_bus.set_tempo(seconds_per_beat);
+        for group_bus in &mut self.group_buses {
+            group_bus.bus.set_tempo(seconds_per_beat);
+        }
+    }
+
+    /// Chooses whether playback ends after the last row (the default) or
+    /// keeps rendering so cells can still be triggered with dispatch_action
+    pub fn set_stop_at_end(&mut self, stop_at_end: bool) {
+        self.stop_at_end = stop_at_end;
+    }
+
+    /// Chooses what advances the rows (Internal by default)
+    pub fn set_clock_source(&mut self, clock_source: ClockSource) {
+        self.clock_source = clock_source;
+        self.external_rows_pending = 0;
+    }
+
+    /// Starts the next row at the next rendered sample
+    /// Only has an effect with ClockSource::External.
+    pub fn advance_external_row(&mut self) {
+        if self.clock_source == ClockSource::External {
+            self.external_rows_pending = self.external_rows_pending.saturating_add(1);
+        }
+    }
+
+    /// Mutes or unmutes a channel (fades over a few milliseconds)
+    pub fn set_channel_muted(&mut self, channel_index: usize, muted: bool) {
+        if let Some(flag) = self.muted.get_mut(channel_index) {
+            *flag = muted;
+            self.update_audible_channels();
+        }
+    }
+
+    /// Solos or unsolos a channel: while any channel is soloed, only the
+    /// soloed ones are heard (a muted channel stays muted even if soloed)
+    pub fn set_channel_soloed(&mut self, channel_index: usize, soloed: bool) {
+        if let Some(flag) = self.soloed.get_mut(channel_index) {
+            *flag = soloed;
+            self.update_audible_channels();
+        }
+    }
+
+    /// Whether a channel can be heard with the current mutes and solos
+    pub fn is_channel_audible(&self, channel_index: usize) -> bool {
+        let any_soloed = self.soloed.contains(&true);
+        !self.muted[channel_index] && (!any_soloed || self.soloed[channel_index])
+    }
+
+    /// Passes the mute/solo state on to the channels
+    fn update_audible_channels(&mut self) {
+        for channel_index in 0..self.channels.len() {
+            let audible = self.is_channel_audible(channel_index);
+            self.channels[channel_index].set_audible(audible);
+            if let Some(channel) = self.fading_channels.get_mut(channel_index) {
+                channel.set_audible(audible);
+            }
+        }
+    }
+
+    /// Returns the row that will be played next (0-indexed)
+    pub fn current_row(&self) -> usize {
+        self.current_row
+    }
+
+    /// Returns true once the song has played through
+    /// Release tails keep sounding after this; see is_silent.
+    pub fn is_finished(&self) -> bool {
+        self.playback_finished
+    }
+
+    /// Returns true once nothing more will be heard: the song has played
+    /// through, every channel has stopped, and the reverb and delay tails
+    /// have died away (or a fade_out has finished)
+    pub fn is_silent(&self) -> bool {
+        self.fade_out_samples_left == Some(0)
+            || (self.playback_finished
+                && !self.channels.iter().any(Channel::is_playing)
+                && !self.frozen.iter().flatten().any(FrozenChannel::is_playing)
+                && self.outgoing_song.is_none()
+                && self.group_buses.iter().all(|group| group.bus.is_quiet())
+                && self.master_bus.is_quiet())
+    }
+
+    /// Fades the whole mix out over the given time, then stays silent and
+    /// counts as finished (for stopping in the middle of a song without a
+    /// click, and for the fadeout command)
+    pub fn fade_out(&mut self, seconds: f32) {
+        let length = (seconds * self.config.sample_rate as f32) as u32;
+        if self.fade_out_samples_left.is_none() {
+            self.fade_out_length = length.max(1);
+            self.fade_out_samples_left = Some(length);
         }
     }
 
+    /// Gives every channel its own copy of a custom effect
+    /// (see effects::register_effect; new channels get it from there)
+    pub fn add_custom_effect(&mut self, slot: &CustomEffectSlot) {
+        for channel in self.channels.iter_mut().chain(&mut self.fading_channels) {
+            channel.add_custom_effect(slot.clone());
+        }
+    }
+
+    /// Returns the number of channels
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// True if the playing song has a group with this (lowercase) name
+    pub fn has_group(&self, name: &str) -> bool {
+        self.group_buses
+            .iter()
+            .any(|group_bus| group_bus.name == name)
+    }
+
+    /// Continues playback from the given row (0-indexed)
+    /// Notes that are sounding keep playing until the row says otherwise.
+    pub fn jump_to_row(&mut self, row: usize) {
+        self.current_row = row;
+        self.playback_finished = false;
+        // Start the row on the very next sample
+        self.samples_in_current_row = self.samples_per_row;
+    }
+
+    /// Ends playback before this row (0-indexed) instead of at the end of
+    /// the song; None plays to the end
+    pub fn set_end_row(&mut self, end_row: Option<usize>) {
+        self.end_row = end_row;
+    }
+
+    /// Makes playback continue from this row (0-indexed) whenever it
+    /// reaches the end row, so a section repeats until stopped
+    /// None (the default) stops at the end instead.
+    pub fn set_loop_start(&mut self, loop_start_row: Option<usize>) {
+        self.loop_start_row = loop_start_row;
+    }
+
+    /// Crossfades from the end of the loop into its start over this many
+    /// seconds, instead of jumping straight back (see LOOP CROSSFADE)
+    /// 0 (the default) jumps. Set it before playback starts: it sets up a
+    /// second set of channels, which allocates.
+    pub fn set_loop_crossfade(&mut self, seconds: f32) {
+        self.loop_crossfade_length = (seconds.max(0.0) * self.config.sample_rate as f32) as u32;
+        self.loop_crossfade_position = None;
+        if self.loop_crossfade_length == 0 {
+            self.fading_channels = Vec::new();
+            return;
+        }
+        self.fading_channels = create_channels(&self.config, self.song.config.seed.unwrap_or(0));
+        let seconds_per_beat = self.master_bus.seconds_per_beat;
+        for channel in &mut self.fading_channels {
+            channel.set_tempo(seconds_per_beat);
+        }
+        self.apply_stage_timing();
+        self.update_audible_channels();
+    }
+
+    /// Plays this song when the current one runs out of rows (see
+    /// PLAYLISTS), replacing any song queued before it
+    pub fn queue_song(&mut self, song: LoadedSong) {
+        self.next_song = Some(song);
+    }
+
+    /// Whether a queued song is still waiting to start
+    pub fn has_queued_song(&self) -> bool {
+        self.next_song.is_some()
+    }
+
+    /// How many queued songs have started so far
+    pub fn songs_started(&self) -> usize {
+        self.songs_started
+    }
+
+    /// Crossfades from one song into the next over this many seconds
+    /// (0, the default: the old song's tails ring out under the new one)
+    pub fn set_song_crossfade(&mut self, seconds: f32) {
+        self.song_crossfade_length = (seconds.max(0.0) * self.config.sample_rate as f32) as u32;
+    }
+
+    /// Takes an old song that has gone quiet, to free it off the audio
+    /// thread
+    pub fn take_retired_song(&mut self) -> Option<LoadedSong> {
+        self.retired_song.take()
+    }
+
+    /// Times the voice and every effect of each channel and bus while
+    /// rendering (see DSP LOAD). Costs two clock reads per stage per block.
+    pub fn set_stage_timing(&mut self, enabled: bool) {
+        self.stage_timing = enabled;
+        self.apply_stage_timing();
+    }
+
+    /// Passes stage_timing on to the channels and buses (again after they
+    /// are replaced)
+    fn apply_stage_timing(&mut self) {
+        for channel in self.channels.iter_mut().chain(&mut self.fading_channels) {
+            channel.stage_timer.set_enabled(self.stage_timing);
+        }
+        self.master_bus.stage_timer.set_enabled(self.stage_timing);
+        for group_bus in &mut self.group_buses {
+            group_bus.bus.stage_timer.set_enabled(self.stage_timing);
+        }
+    }
+
+    /// Time each stage took since the last call, in nanoseconds, summed
+    /// over every channel and bus (in DspStage::ALL order)
+    pub fn take_stage_nanos(&mut self) -> [u64; DSP_STAGE_COUNT] {
+        let mut totals = [0; DSP_STAGE_COUNT];
+        if self.stage_timing {
+            for channel in self.channels.iter_mut().chain(&mut self.fading_channels) {
+                channel.stage_timer.drain_into(&mut totals);
+            }
+            self.master_bus.stage_timer.drain_into(&mut totals);
+            for group_bus in &mut self.group_buses {
+                group_bus.bus.stage_timer.drain_into(&mut totals);
+            }
+        }
+        totals
+    }
+
+    /// Renders a channel's part of the whole song now, and from then on
+    /// plays that recording in its place (see FROZEN CHANNELS)
+    /// Returns the recording's length in seconds. Meant for before
+    /// playback starts: it renders the song once and allocates.
+    pub fn freeze_channel(&mut self, channel_index: usize) -> Result<f32, String> {
+        if channel_index >= self.channels.len() {
+            return Err(format!(
+                "There is no channel {} to freeze",
+                channel_index + 1
+            ));
+        }
+        if self.row_feed.is_some() {
+            return Err("A streamed song can't freeze channels (its rows aren't read yet)".into());
+        }
+        if self.loop_crossfade_length > 0 {
+            return Err("Frozen channels can't crossfade a loop".into());
+        }
+
+        // Pick up where playback is now (after a fast-forward, say)
+        let mut frozen = self.record_channel(channel_index);
+        let row_start = match self.current_row.checked_sub(1) {
+            Some(row) => frozen.row_starts.get(row).copied().unwrap_or(frozen.len()),
+            None => 0,
+        };
+        frozen.seek(row_start + self.samples_in_current_row as usize);
+        let seconds = frozen.len() as f32 / self.config.sample_rate as f32;
+
+        self.channels[channel_index].is_active = false;
+        self.frozen.resize_with(self.channels.len(), || None);
+        self.frozen[channel_index] = Some(frozen);
+        Ok(seconds)
+    }
+
+    /// Whether a channel plays a recording (see freeze_channel)
+    pub fn is_channel_frozen(&self, channel_index: usize) -> bool {
+        matches!(self.frozen.get(channel_index), Some(Some(_)))
+    }
+
+    /// Plays the song from the top on a copy of this engine, keeping only
+    /// one channel's output (until its tail dies away) and the sample each
+    /// row started at. Rows start in the same blocks as in render_frames.
+    fn record_channel(&self, channel_index: usize) -> FrozenChannel {
+        let mut engine = PlaybackEngine::new(self.song.clone(), self.config.clone());
+        engine.channels[channel_index].custom_effects =
+            self.channels[channel_index].custom_effects.clone();
+        let max_tail_samples = (MAX_RENDER_TAIL_SECONDS * self.config.sample_rate as f32) as usize;
+
+        let (mut left, mut right, mut row_starts) = (Vec::new(), Vec::new(), Vec::new());
+        let mut tail_samples = 0;
+        loop {
+            let length = if engine.playback_finished {
+                if !engine.channels[channel_index].is_playing() || tail_samples >= max_tail_samples
+                {
+                    break;
+                }
+                tail_samples += RENDER_BLOCK_SIZE;
+                RENDER_BLOCK_SIZE
+            } else if engine.samples_in_current_row >= engine.samples_per_row {
+                engine.advance_row();
+                if !engine.playback_finished {
+                    row_starts.push(left.len());
+                }
+                continue;
+            } else {
+                let samples_left_in_row = engine.samples_per_row - engine.samples_in_current_row;
+                RENDER_BLOCK_SIZE.min(samples_left_in_row as usize)
+            };
+
+            let start = left.len();
+            left.resize(start + length, 0.0);
+            right.resize(start + length, 0.0);
+            engine.channels[channel_index].render_block(&mut left[start..], &mut right[start..]);
+            engine.samples_in_current_row += length as u32;
+        }
+        FrozenChannel::new(left, right, row_starts)
+    }
+
+    /// Plays rows from a streamed song instead of the song's own rows
+    /// (see song_stream.rs; the engine's song should be the stream's head)
+    pub fn set_row_feed(&mut self, row_feed: RowFeed) {
+        self.row_feed = Some(row_feed);
+    }
+
+    /// Renders silently up to the given row (0-indexed), so it starts with
+    /// everything as it would be after playing the song from the top:
+    /// held notes, effects, tempo, and reverb tails
+    /// Meant for before playback starts, not the audio thread (it renders
+    /// on all cores and allocates).
+    pub fn fast_forward_to_row(&mut self, row: usize) {
+        // Past the end row a loop would wrap before ever getting there
+        let row = row.min(self.end_row_or_song_end());
+        let mut scratch = Vec::new();
+        while !self.playback_finished && self.current_row <= row {
+            let samples_left = self
+                .samples_per_row
+                .saturating_sub(self.samples_in_current_row);
+            if samples_left == 0 {
+                if self.current_row == row {
+                    // The next sample starts the row
+                    break;
+                }
+                // Start the next row here, as render_frames would, so
+                // every row renders in the same blocks as in full playback
+                self.advance_row();
+                continue;
+            }
+            scratch.resize(samples_left as usize * 2, 0.0);
+            self.process_frame_parallel(&mut scratch);
+        }
+        for channel in &mut self.channels {
+            channel.take_peak_level();
+        }
+    }
+
+    /// Copies every setting the engine holds right now (see snapshot.rs)
+    pub fn snapshot(&self) -> EngineSnapshot {
+        EngineSnapshot {
+            row: self.current_row.saturating_sub(1),
+            samples_into_row: self.samples_in_current_row,
+            samples_per_row: self.samples_per_row,
+            seconds: self.total_samples_rendered as f64 / self.config.sample_rate as f64,
+            sample_rate: self.config.sample_rate,
+            finished: self.playback_finished,
+            fade_out_samples_left: self.fade_out_samples_left,
+            channels: self.channels.iter().map(ChannelSnapshot::new).collect(),
+            groups: self
+                .group_buses
+                .iter()
+                .map(|group_bus| BusSnapshot::new(Some(&group_bus.name), &group_bus.bus))
+                .collect(),
+            master: BusSnapshot::new(None, &self.master_bus),
+        }
+    }
+
+    /// Plays silently up to a row (0-indexed), starts it, and takes a
+    /// snapshot before any of its samples are rendered, so the row's own
+    /// cells show. Like fast_forward_to_row, not for the audio thread.
+    pub fn snapshot_at_row(&mut self, row: usize) -> EngineSnapshot {
+        self.fast_forward_to_row(row);
+        if !self.playback_finished && self.samples_in_current_row >= self.samples_per_row {
+            self.advance_row();
+        }
+        self.snapshot()
+    }
+
+    /// The row playback ends (or loops) before
+    fn end_row_or_song_end(&self) -> usize {
+        let row_count = self.song.rows.len();
+        self.end_row
+            .map_or(row_count, |end_row| end_row.min(row_count))
+    }
+
     /// Advances to the next row and dispatches actions
     fn advance_row(&mut self) {
-        // Check if we've reached the end
-        if self.current_row >= self.song.rows.len() {
-            self.playback_finished = true;
+        if self.row_feed.is_some() {
+            self.advance_streamed_row();
             return;
         }
 
-        // Debug output
-        if self.config.debug_level >= DebugLevel::Verbose
-            && self.current_row < self.song.raw_lines.len()
-        {
-            println!("Row {}", self.current_row);
-            println!("{}\n", self.song.raw_lines[self.current_row]);
+        // Check if we've reached the end (or the end of the looped section)
+        let end_row = self.end_row_or_song_end();
+        if self.current_row >= end_row {
+            match self.loop_start_row.filter(|start| *start < end_row) {
+                Some(loop_start_row) => {
+                    self.current_row = loop_start_row;
+                    self.start_loop_crossfade();
+                }
+                None if self.next_song.is_some() => self.start_next_song(),
+                None => {
+                    self.finish_song();
+                    return;
+                }
+            }
         }
 
-        // Get the actions for this row (clone to avoid borrow issues)
-        let row_actions = self.song.rows[self.current_row].clone();
+        // No logging here: this runs on the audio thread during real-time
+        // playback. The main thread logs rows from RowStarted events instead.
+
+        // Borrow the rows out of the song while dispatching (moving the Vec
+        // is free, unlike cloning the row, which would allocate per row)
+        let rows = std::mem::take(&mut self.song.rows);
 
         // Dispatch each action to its channel
-        for (channel_index, action) in row_actions.iter().enumerate() {
+        for (channel_index, action) in rows[self.current_row].iter().enumerate() {
             if channel_index >= self.channels.len() {
                 break;
             }
 
             self.dispatch_action(channel_index, action);
         }
+        for channel in &mut self.channels {
+            channel.start_row();
+        }
+        for frozen in self.frozen.iter_mut().flatten() {
+            frozen.start_row(self.current_row);
+        }
+
+        self.song.rows = rows;
 
         // Move to next row
         self.current_row += 1;
         self.samples_in_current_row = 0;
     }
 
+    /// advance_row for a streamed song: the row comes from the feed
+    fn advance_streamed_row(&mut self) {
+        let Some(row_feed) = self.row_feed.as_mut() else {
+            return;
+        };
+        match row_feed.next_row() {
+            Some(StreamedRow::Row(row)) => {
+                for (channel_index, action) in row.iter().enumerate().take(self.channels.len()) {
+                    self.dispatch_action(channel_index, action);
+                }
+                for channel in &mut self.channels {
+                    channel.start_row();
+                }
+                if let Some(row_feed) = self.row_feed.as_mut() {
+                    row_feed.recycle(row);
+                }
+                self.current_row += 1;
+                self.samples_in_current_row = 0;
+            }
+            Some(StreamedRow::End) => self.finish_song(),
+            None => {
+                // The parser is behind: keep this row going one more block
+                row_feed.note_late_row();
+                self.samples_in_current_row = self
+                    .samples_per_row
+                    .saturating_sub(RENDER_BLOCK_SIZE as u32);
+            }
+        }
+    }
+
+    /// Hands the pass that just ended over to the fading channels, and
+    /// starts the next pass on the other set (see LOOP CROSSFADE)
+    fn start_loop_crossfade(&mut self) {
+        // A loop shorter than the crossfade jumps until the last one is over
+        if self.loop_crossfade_length == 0
+            || self.loop_crossfade_position.is_some()
+            || self.fading_channels.len() != self.channels.len()
+        {
+            return;
+        }
+        std::mem::swap(&mut self.channels, &mut self.fading_channels);
+        self.loop_crossfade_position = Some(0);
+    }
+
+    /// Swaps the queued song in to play from its first row, moving the
+    /// one that ended aside to fade out (see PLAYLISTS)
+    fn start_next_song(&mut self) {
+        let Some(mut song) = self.next_song.take() else {
+            return;
+        };
+
+        // Without a crossfade, held notes ring out like at a song's end
+        if self.song_crossfade_length == 0 {
+            for channel_index in 0..self.channels.len() {
+                self.dispatch_action(channel_index, &CellAction::SlowRelease);
+            }
+        }
+
+        // A song still fading out from the last join is cut off here
+        if let Some(outgoing) = self.outgoing_song.take() {
+            self.retired_song = Some(outgoing);
+        }
+        std::mem::swap(&mut self.song, &mut song.song);
+        std::mem::swap(&mut self.channels, &mut song.channels);
+        std::mem::swap(&mut self.group_buses, &mut song.group_buses);
+        std::mem::swap(&mut self.channel_groups, &mut song.channel_groups);
+        std::mem::swap(&mut self.frozen, &mut song.frozen);
+        let old_config = std::mem::replace(&mut song.config, self.config.clone());
+        self.config.channel_count = old_config.channel_count;
+        self.config.tick_duration_seconds = old_config.tick_duration_seconds;
+        self.outgoing_song = Some(song);
+        self.song_crossfade_position = 0;
+
+        // The new song's tempo and channels (muted and soloed have room
+        // for any channel count, see new)
+        self.samples_per_row =
+            ((self.config.tick_duration_seconds * self.config.sample_rate as f32) as u32).max(1);
+        self.set_seconds_per_beat(self.config.tick_duration_seconds * DEFAULT_ROWS_PER_BEAT);
+        self.apply_stage_timing();
+        let channel_count = self.channels.len();
+        self.muted.resize(channel_count, false);
+        self.soloed.resize(channel_count, false);
+        self.update_audible_channels();
+
+        self.current_row = 0;
+        self.end_row = None;
+        self.songs_started += 1;
+    }
+
+    /// Called when there are no more rows to play
+    fn finish_song(&mut self) {
+        if self.stop_at_end && !self.playback_finished {
+            // Let held notes ring out instead of cutting them off
+            self.playback_finished = true;
+            for channel_index in 0..self.channels.len() {
+                self.dispatch_action(channel_index, &CellAction::SlowRelease);
+            }
+        }
+        self.samples_in_current_row = 0;
+    }
+
     /// Dispatches a cell action to the appropriate channel
-    fn dispatch_action(&mut self, channel_index: usize, action: &CellAction) {
+    /// Master effects ignore the channel index.
+    pub fn dispatch_action(&mut self, channel_index: usize, action: &CellAction) {
+        // A frozen channel plays its recording instead (bus commands in
+        // its column still apply)
+        if self.is_channel_frozen(channel_index) && !action.is_bus_command() {
+            return;
+        }
+
         match action {
             CellAction::TriggerNote {
                 frequency_hz,
@@ -177,12 +1106,19 @@ impl PlaybackEngine {
                 effects,
                 transition_seconds,
                 clear_effects,
+                envelope,
+                seed,
             } => {
+                self.channels[channel_index].envelope.set_shape(*envelope);
+                if let Some(seed) = seed {
+                    self.channels[channel_index].random_generator =
+                        RandomNumberGenerator::new(*seed);
+                }
                 self.channels[channel_index].trigger_note(
                     *frequency_hz,
                     *instrument_id,
-                    instrument_parameters.clone(),
-                    effects.clone(),
+                    instrument_parameters,
+                    effects,
                     *transition_seconds,
                     *clear_effects,
                 );
@@ -194,11 +1130,18 @@ impl PlaybackEngine {
                 effects,
                 transition_seconds,
                 clear_effects,
+                envelope,
+                seed,
             } => {
+                self.channels[channel_index].envelope.set_shape(*envelope);
+                if let Some(seed) = seed {
+                    self.channels[channel_index].random_generator =
+                        RandomNumberGenerator::new(*seed);
+                }
                 self.channels[channel_index].trigger_pitchless(
                     *instrument_id,
-                    instrument_parameters.clone(),
-                    effects.clone(),
+                    instrument_parameters,
+                    effects,
                     *transition_seconds,
                     *clear_effects,
                 );
@@ -219,7 +1162,7 @@ impl PlaybackEngine {
 
                 // Update effects
                 self.channels[channel_index].update_effects(
-                    effects.clone(),
+                    effects,
                     *transition_seconds,
                     *clear_first,
                 );
@@ -230,7 +1173,12 @@ impl PlaybackEngine {
             }
 
             CellAction::SlowRelease => {
-                self.channels[channel_index].release(self.config.default_release_seconds);
+                // Notes with their own envelope use its release time
+                let release_seconds = self.channels[channel_index]
+                    .envelope
+                    .release_time_seconds
+                    .unwrap_or(self.config.default_release_seconds);
+                self.channels[channel_index].release(release_seconds);
             }
 
             CellAction::ChangeEffects {
@@ -239,121 +1187,989 @@ impl PlaybackEngine {
                 clear_first,
             } => {
                 self.channels[channel_index].update_effects(
-                    effects.clone(),
+                    effects,
                     *transition_seconds,
                     *clear_first,
                 );
             }
 
-            CellAction::MasterEffects {
+            CellAction::GroupEffects {
+                group,
                 clear_first,
                 transition_seconds,
                 effects,
             } => {
-                // Clear first if requested
+                let Some(group_bus) = self
+                    .group_buses
+                    .iter_mut()
+                    .find(|group_bus| group_bus.name == *group)
+                else {
+                    // Songs and trigger_cell report unknown groups when they
+                    // are parsed; a live or OSC cell naming one does nothing
+                    return;
+                };
                 if *clear_first {
-                    self.master_bus.clear_effects(*transition_seconds);
+                    group_bus.bus.clear_effects(*transition_seconds);
                 }
-
-                // Apply each effect
                 for (effect_name, params) in effects {
-                    self.master_bus
+                    group_bus
+                        .bus
                         .apply_effect(effect_name, params, *transition_seconds);
                 }
             }
-        }
-    }
 
-    /// Processes a frame of audio
-    /// Fills the output buffer with stereo samples (interleaved L R L R ...)
+            CellAction::MasterEffects {
+                clear_first,
+                transition_seconds,
+                effects,
+            } => {
+                // Clear first if requested
+                if *clear_first {
+                    self.master_bus.clear_effects(*transition_seconds);
+                }
+
+                // Apply each effect
+                for (effect_name, params) in effects {
+                    // Tempo change: the row being dispatched already uses it
+                    if effect_name == "bpm" && params.len() == 2 {
+                        let tick = tick_duration_from_bpm(params[0], params[1]);
+                        self.samples_per_row =
+                            ((tick * self.config.sample_rate as f32) as u32).max(1);
+                        self.set_seconds_per_beat(60.0 / params[0]);
+                        continue;
+                    }
+
+                    // Fade-out ending: the song ends once the fade is over
+                    if effect_name == "fadeout" {
+                        self.fade_out(params.first().copied().unwrap_or(0.0));
+                        continue;
+                    }
+
+                    // Transpose glides the notes that are already sounding
+                    if effect_name == "transpose" {
+                        let semitones = params.first().copied().unwrap_or(0.0);
+                        for channel in &mut self.channels {
+                            channel.transpose(semitones, *transition_seconds);
+                        }
+                        continue;
+                    }
+
+                    self.master_bus
+                        .apply_effect(effect_name, params, *transition_seconds);
+                }
+            }
+        }
+    }
+
+    /// Processes a frame of audio
+    /// Fills the output buffer with stereo samples (interleaved L R L R ...)
+    ///
+    /// Audio is rendered in blocks of up to RENDER_BLOCK_SIZE samples.
+    /// Blocks never cross a row boundary, so rows still start on the exact
+    /// sample they did when rendering one sample at a time.
     pub fn process_frame(&mut self, output: &mut [f32]) {
-        // Process samples in pairs (stereo)
-        for sample_pair in output.chunks_mut(2) {
+        self.render_frames(&[], output, false);
+    }
+
+    /// Same as process_frame, with the audio a duplex device captured for
+    /// this buffer (interleaved stereo, see AUDIO INPUT above)
+    pub fn process_frame_with_input(&mut self, input: &[f32], output: &mut [f32]) {
+        self.render_frames(input, output, false);
+    }
+
+    /// Same as process_frame, but renders the channels on all CPU cores
+    /// Meant for offline rendering (it allocates and may block), never for
+    /// the audio callback. The output matches process_frame exactly.
+    pub fn process_frame_parallel(&mut self, output: &mut [f32]) {
+        self.render_frames(&[], output, true);
+    }
+
+    /// Shared body of process_frame and process_frame_parallel
+    fn render_frames(&mut self, input: &[f32], output: &mut [f32], parallel: bool) {
+        let frame_count = output.len() / 2;
+        let mut frame_offset = 0;
+
+        // External clock rows waiting at the start of the buffer share it
+        // out evenly, so several AdvanceRows never pile up
+        let external_row_spacing = match self.external_rows_pending {
+            0 => frame_count,
+            rows_pending => (frame_count / rows_pending as usize).max(1),
+        };
+        let mut next_external_row = 0;
+
+        while frame_offset < frame_count {
+            // After a fade_out, the song is over: output silence
+            if self.fade_out_samples_left == Some(0) {
+                self.playback_finished = true;
+                output[frame_offset * 2..].fill(0.0);
+                return;
+            }
+
             // Check if we need to advance to the next row
-            if self.samples_in_current_row >= self.samples_per_row {
-                self.advance_row();
+            match self.clock_source {
+                ClockSource::Internal => {
+                    if self.samples_in_current_row >= self.samples_per_row {
+                        self.advance_row();
+                    }
+                }
+                ClockSource::External => {
+                    if self.external_rows_pending > 0 && frame_offset >= next_external_row {
+                        self.external_rows_pending -= 1;
+                        self.advance_row();
+                        next_external_row = frame_offset + external_row_spacing;
+                    }
+                }
             }
 
-            // If playback is finished, output silence
-            if self.playback_finished {
-                sample_pair[0] = 0.0;
-                sample_pair[1] = 0.0;
-                continue;
+            // A segment ends at the buffer end or the next row; single-
+            // threaded segments are also capped at one block
+            let samples_left_in_row = match self.clock_source {
+                ClockSource::Internal => {
+                    self.samples_per_row
+                        .saturating_sub(self.samples_in_current_row) as usize
+                }
+                ClockSource::External if self.external_rows_pending > 0 => {
+                    next_external_row.saturating_sub(frame_offset)
+                }
+                ClockSource::External => usize::MAX,
+            };
+            let segment_limit = if parallel {
+                usize::MAX
+            } else {
+                RENDER_BLOCK_SIZE
+            };
+            let segment_length = (frame_count - frame_offset)
+                .min(segment_limit)
+                .min(samples_left_in_row)
+                .max(1);
+
+            // Hand this segment's captured audio to the input channels
+            if !input.is_empty() {
+                let start = (frame_offset * 2).min(input.len());
+                let end = ((frame_offset + segment_length) * 2).min(input.len());
+                self.feed_audio_input(&input[start..end]);
             }
 
             // Mix all channels together
-            let mut left_sum = 0.0;
-            let mut right_sum = 0.0;
+            if parallel {
+                self.mix_channels_parallel(segment_length);
+            } else {
+                self.mix_channels(segment_length);
+            }
+            let left_mix = &mut self.mix_left[..segment_length];
+            let right_mix = &mut self.mix_right[..segment_length];
+
+            // Process through master bus, one block at a time
+            for (left_block, right_block) in left_mix
+                .chunks_mut(RENDER_BLOCK_SIZE)
+                .zip(right_mix.chunks_mut(RENDER_BLOCK_SIZE))
+            {
+                self.master_bus.process_block(left_block, right_block);
+            }
 
-            for channel in &mut self.channels {
-                if channel.is_playing() {
-                    let (left, right) = channel.render_sample();
-                    left_sum += left;
-                    right_sum += right;
+            // Remove any DC offset the effects left in the mix
+            self.dc_blockers[0].process_block(left_mix);
+            self.dc_blockers[1].process_block(right_mix);
+
+            // Fade the whole mix (see fade_out)
+            if let Some(samples_left) = self.fade_out_samples_left {
+                let fade_length = self.fade_out_length as f32;
+                for (index, (left, right)) in
+                    left_mix.iter_mut().zip(right_mix.iter_mut()).enumerate()
+                {
+                    let gain = samples_left.saturating_sub(index as u32) as f32 / fade_length;
+                    *left *= gain;
+                    *right *= gain;
                 }
+                self.fade_out_samples_left =
+                    Some(samples_left.saturating_sub(segment_length as u32));
             }
 
-            // Process through master bus
-            let (final_left, final_right) = self.master_bus.process(left_sum, right_sum);
-
             // Clamp to valid range to prevent clipping
-            sample_pair[0] = final_left.clamp(-1.0, 1.0);
-            sample_pair[1] = final_right.clamp(-1.0, 1.0);
+            let segment_output = &mut output[frame_offset * 2..(frame_offset + segment_length) * 2];
+            for ((sample_pair, left), right) in segment_output
+                .chunks_exact_mut(2)
+                .zip(left_mix.iter())
+                .zip(right_mix.iter())
+            {
+                sample_pair[0] = left.clamp(-1.0, 1.0);
+                sample_pair[1] = right.clamp(-1.0, 1.0);
+            }
 
             // Update counters
-            self.samples_in_current_row += 1;
-            self.total_samples_rendered += 1;
+            self.samples_in_current_row = self
+                .samples_in_current_row
+                .saturating_add(segment_length as u32);
+            self.total_samples_rendered += segment_length as u64;
+            frame_offset += segment_length;
+        }
+    }
+
+    /// Gives captured audio to every channel playing the input instrument
+    fn feed_audio_input(&mut self, input: &[f32]) {
+        for channel in &mut self.channels {
+            if channel.is_playing() && channel.plays_audio_input() {
+                channel.set_audio_input(input);
+            }
+        }
+    }
+
+    /// Renders every playing channel into the mix buffers (one block)
+    fn mix_channels(&mut self, length: usize) {
+        let left_mix = &mut self.mix_left[..length];
+        let right_mix = &mut self.mix_right[..length];
+        left_mix.fill(0.0);
+        right_mix.fill(0.0);
+        for group_bus in &mut self.group_buses {
+            group_bus.left[..length].fill(0.0);
+            group_bus.right[..length].fill(0.0);
+        }
+
+        for (channel, group) in self.channels.iter_mut().zip(&self.channel_groups) {
+            if channel.is_playing() {
+                match *group {
+                    Some(group_index) => {
+                        let group_bus = &mut self.group_buses[group_index];
+                        channel.render_block(
+                            &mut group_bus.left[..length],
+                            &mut group_bus.right[..length],
+                        );
+                    }
+                    None => channel.render_block(left_mix, right_mix),
+                }
+            }
+        }
+
+        self.mix_frozen_channels(length);
+        self.mix_loop_crossfade(length);
+        self.mix_outgoing_song(length);
+        let left_mix = &mut self.mix_left[..length];
+        let right_mix = &mut self.mix_right[..length];
+        mix_group_buses(&mut self.group_buses, left_mix, right_mix);
+    }
+
+    /// Renders the channels on a thread pool, then sums them into the mix
+    /// buffers in channel order (so the result does not depend on which
+    /// thread finished first)
+    fn mix_channels_parallel(&mut self, length: usize) {
+        self.channel_buffers
+            .resize_with(self.channels.len(), ChannelBuffer::default);
+        if self.mix_left.len() < length {
+            self.mix_left.resize(length, 0.0);
+            self.mix_right.resize(length, 0.0);
+        }
+
+        self.channels
+            .par_iter_mut()
+            .zip(self.channel_buffers.par_iter_mut())
+            .for_each(|(channel, buffer)| {
+                buffer.was_rendered = channel.is_playing();
+                if buffer.was_rendered {
+                    buffer.left.clear();
+                    buffer.left.resize(length, 0.0);
+                    buffer.right.clear();
+                    buffer.right.resize(length, 0.0);
+                    channel.render_block(&mut buffer.left, &mut buffer.right);
+                }
+            });
+
+        let left_mix = &mut self.mix_left[..length];
+        let right_mix = &mut self.mix_right[..length];
+        left_mix.fill(0.0);
+        right_mix.fill(0.0);
+        for group_bus in &mut self.group_buses {
+            if group_bus.left.len() < length {
+                group_bus.left.resize(length, 0.0);
+                group_bus.right.resize(length, 0.0);
+            }
+            group_bus.left[..length].fill(0.0);
+            group_bus.right[..length].fill(0.0);
+        }
+        for (buffer, group) in self.channel_buffers.iter().zip(&self.channel_groups) {
+            if !buffer.was_rendered {
+                continue;
+            }
+            let (left_target, right_target) = match *group {
+                Some(group_index) => {
+                    let group_bus = &mut self.group_buses[group_index];
+                    (
+                        &mut group_bus.left[..length],
+                        &mut group_bus.right[..length],
+                    )
+                }
+                None => (&mut *left_mix, &mut *right_mix),
+            };
+            for (mix, sample) in left_target.iter_mut().zip(&buffer.left) {
+                *mix += sample;
+            }
+            for (mix, sample) in right_target.iter_mut().zip(&buffer.right) {
+                *mix += sample;
+            }
+        }
+
+        self.mix_frozen_channels(length);
+        self.mix_loop_crossfade(length);
+        self.mix_outgoing_song(length);
+        let left_mix = &mut self.mix_left[..length];
+        let right_mix = &mut self.mix_right[..length];
+        mix_group_buses(&mut self.group_buses, left_mix, right_mix);
+    }
+
+    /// Adds each frozen channel's recording where the channel would have
+    /// played, after the live channels
+    fn mix_frozen_channels(&mut self, length: usize) {
+        for (channel_index, frozen) in self.frozen.iter_mut().enumerate() {
+            let Some(frozen) = frozen else {
+                continue;
+            };
+            let (left_target, right_target) = match self.channel_groups[channel_index] {
+                Some(group_index) => {
+                    let group_bus = &mut self.group_buses[group_index];
+                    (
+                        &mut group_bus.left[..length],
+                        &mut group_bus.right[..length],
+                    )
+                }
+                None => (&mut self.mix_left[..length], &mut self.mix_right[..length]),
+            };
+            let channel = &mut self.channels[channel_index];
+            let peak = frozen.play_block(
+                left_target,
+                right_target,
+                channel.audible,
+                self.config.sample_rate,
+            );
+            channel.peak_level = channel.peak_level.max(peak);
+        }
+    }
+
+    /// During a loop crossfade, fades the new pass (already in the mix and
+    /// group buffers) in, and adds the old pass fading out
+    fn mix_loop_crossfade(&mut self, length: usize) {
+        let Some(position) = self.loop_crossfade_position else {
+            return;
+        };
+        let fade_length = self.loop_crossfade_length as f32;
+        // Equal power: in^2 + out^2 = 1 all the way through
+        let angle =
+            |index: usize| ((position as usize + index) as f32 / fade_length).min(1.0) * FRAC_PI_2;
+
+        // Fade the new pass in
+        self.fade_in_mix(length, angle);
+
+        // Add the old pass, fading out
+        let left_mix = &mut self.mix_left[..length];
+        let right_mix = &mut self.mix_right[..length];
+        for (channel, group) in self.fading_channels.iter_mut().zip(&self.channel_groups) {
+            let mut offset = 0;
+            while offset < length && channel.is_playing() {
+                let block_length = (length - offset).min(RENDER_BLOCK_SIZE);
+                let fade_left = &mut self.fade_left[..block_length];
+                let fade_right = &mut self.fade_right[..block_length];
+                fade_left.fill(0.0);
+                fade_right.fill(0.0);
+                channel.render_block(fade_left, fade_right);
+
+                let (left_target, right_target) = match *group {
+                    Some(group_index) => {
+                        let group_bus = &mut self.group_buses[group_index];
+                        (
+                            &mut group_bus.left[..length],
+                            &mut group_bus.right[..length],
+                        )
+                    }
+                    None => (&mut *left_mix, &mut *right_mix),
+                };
+                for index in 0..block_length {
+                    let gain = angle(offset + index).cos();
+                    left_target[offset + index] += fade_left[index] * gain;
+                    right_target[offset + index] += fade_right[index] * gain;
+                }
+                offset += block_length;
+            }
+        }
+
+        // Once faded out, the old set waits silently for the next wrap
+        let position = position.saturating_add(length as u32);
+        if position >= self.loop_crossfade_length {
+            self.loop_crossfade_position = None;
+            for channel in &mut self.fading_channels {
+                channel.is_active = false;
+            }
+        } else {
+            self.loop_crossfade_position = Some(position);
+        }
+    }
+
+    /// Scales what is in the mix and group buffers by sin(angle(index)),
+    /// the fading-in half of an equal-power crossfade
+    fn fade_in_mix(&mut self, length: usize, angle: impl Fn(usize) -> f32) {
+        let left_mix = &mut self.mix_left[..length];
+        let right_mix = &mut self.mix_right[..length];
+        for buffer in [left_mix, right_mix]
+            .into_iter()
+            .chain(self.group_buses.iter_mut().flat_map(|group_bus| {
+                [
+                    &mut group_bus.left[..length],
+                    &mut group_bus.right[..length],
+                ]
+            }))
+        {
+            for (index, sample) in buffer.iter_mut().enumerate() {
+                *sample *= angle(index).sin();
+            }
+        }
+    }
+
+    /// After a song change, fades the new song in and adds the old one
+    /// (its channels through its own group buses) fading out, or ringing
+    /// out at full level without a crossfade (see PLAYLISTS)
+    fn mix_outgoing_song(&mut self, length: usize) {
+        if self.outgoing_song.is_none() {
+            return;
+        }
+        let fade_length = self.song_crossfade_length;
+        let position = self.song_crossfade_position;
+        // Equal power, as in mix_loop_crossfade
+        let angle = |index: usize| {
+            ((position as usize + index) as f32 / fade_length as f32).min(1.0) * FRAC_PI_2
+        };
+
+        // The new song fades in (its group buses are mixed in later)
+        if fade_length > 0 && position < fade_length {
+            self.fade_in_mix(length, angle);
+        }
+        let Some(outgoing) = self.outgoing_song.as_mut() else {
+            return;
+        };
+
+        // The old song, a block at a time through its own group buses
+        let mut offset = 0;
+        while offset < length {
+            let block_length = (length - offset).min(RENDER_BLOCK_SIZE);
+            let fade_left = &mut self.fade_left[..block_length];
+            let fade_right = &mut self.fade_right[..block_length];
+            fade_left.fill(0.0);
+            fade_right.fill(0.0);
+            for group_bus in &mut outgoing.group_buses {
+                group_bus.left[..block_length].fill(0.0);
+                group_bus.right[..block_length].fill(0.0);
+            }
+            for (channel, group) in outgoing.channels.iter_mut().zip(&outgoing.channel_groups) {
+                if channel.is_playing() {
+                    match *group {
+                        Some(group_index) => {
+                            let group_bus = &mut outgoing.group_buses[group_index];
+                            channel.render_block(
+                                &mut group_bus.left[..block_length],
+                                &mut group_bus.right[..block_length],
+                            );
+                        }
+                        None => channel.render_block(fade_left, fade_right),
+                    }
+                }
+            }
+            mix_group_buses(&mut outgoing.group_buses, fade_left, fade_right);
+
+            let left_mix = &mut self.mix_left[offset..offset + block_length];
+            let right_mix = &mut self.mix_right[offset..offset + block_length];
+            for index in 0..block_length {
+                let gain = if fade_length == 0 {
+                    1.0
+                } else {
+                    angle(offset + index).cos()
+                };
+                left_mix[index] += fade_left[index] * gain;
+                right_mix[index] += fade_right[index] * gain;
+            }
+            offset += block_length;
+        }
+
+        // Once faded out (or quiet), the old song waits to be freed
+        let position = position.saturating_add(length as u32);
+        self.song_crossfade_position = position;
+        let faded_out = fade_length > 0 && position >= fade_length;
+        if (faded_out || outgoing.is_quiet()) && self.retired_song.is_none() {
+            self.retired_song = self.outgoing_song.take();
         }
     }
 
-    /// Returns the total duration in seconds
+    /// Returns the total duration in seconds (following bpm commands)
+    /// This is the whole song, whatever end row is set.
     pub fn get_total_duration_seconds(&self) -> f32 {
-        self.song.row_count() as f32 * self.config.tick_duration_seconds
+        self.song
+            .duration_seconds(self.config.tick_duration_seconds)
     }
 
     /// Resets playback to the beginning
     pub fn reset(&mut self) {
         self.current_row = 0;
         self.samples_in_current_row = 0;
+        self.samples_per_row =
+            (self.config.tick_duration_seconds * self.config.sample_rate as f32) as u32;
         self.playback_finished = false;
         self.total_samples_rendered = 0;
+        self.external_rows_pending = 0;
+        self.fade_out_samples_left = None;
 
-        // Reset all channels
+        // Reset all channels (their random numbers start over too)
+        let song_seed = self.song.config.seed.unwrap_or(0);
         for channel in &mut self.channels {
             *channel = Channel::new(channel.channel_id, self.config.sample_rate);
+            channel.random_generator =
+                RandomNumberGenerator::for_channel(song_seed, channel.channel_id);
+        }
+        for channel in &mut self.fading_channels {
+            *channel = Channel::new(channel.channel_id, self.config.sample_rate);
+            channel.random_generator =
+                RandomNumberGenerator::for_channel(song_seed, channel.channel_id);
+        }
+        self.loop_crossfade_position = None;
+        if self.retired_song.is_none() {
+            self.retired_song = self.outgoing_song.take();
+        }
+        self.update_audible_channels();
+        for frozen in self.frozen.iter_mut().flatten() {
+            frozen.seek(0);
         }
 
-        // Reset master bus
+        // Reset master bus and group buses
         self.master_bus = MasterBus::new(self.config.sample_rate);
+        self.dc_blockers = [
+            DcBlocker::new(self.config.sample_rate),
+            DcBlocker::new(self.config.sample_rate),
+        ];
+        self.group_buses = self
+            .song
+            .groups
+            .iter()
+            .map(|group| GroupBus::new(group, self.config.sample_rate))
+            .collect();
+        self.set_seconds_per_beat(self.config.tick_duration_seconds * DEFAULT_ROWS_PER_BEAT);
+        self.apply_stage_timing();
     }
 
     /// Renders the entire song to a buffer
     /// Returns a Vec of stereo samples (interleaved L R L R ...)
     /// This is used for WAV export
+    /// The buffer runs on until the release, reverb, and delay tails are
+    /// over (at most MAX_RENDER_TAIL_SECONDS), and ends at the last
+    /// sample that can still be heard.
     pub fn render_to_buffer(&mut self) -> Vec<f32> {
+        self.render_to_buffer_with_progress(|_, _| {})
+    }
+
+    /// render_to_buffer, calling progress(row, seconds) after each row
+    /// with the row playing (0-indexed) and the seconds rendered so far
+    pub fn render_to_buffer_with_progress(
+        &mut self,
+        mut progress: impl FnMut(usize, f32),
+    ) -> Vec<f32> {
         // Calculate total samples needed
         let total_samples =
             (self.get_total_duration_seconds() * self.config.sample_rate as f32) as usize * 2;
 
-        // Add extra time for release tails (2 seconds)
-        let extra_samples = (2.0 * self.config.sample_rate as f32) as usize * 2;
-        let total_with_tail = total_samples + extra_samples;
-
-        let mut buffer = vec![0.0; total_with_tail];
+        let mut buffer = vec![0.0; total_samples];
 
         // Reset to beginning
         self.reset();
 
-        // Render in chunks
-        let chunk_size = 1024;
-        for chunk in buffer.chunks_mut(chunk_size) {
-            self.process_frame(chunk);
+        // Render the song a row at a time, with channels spread across
+        // threads. Starting each row here, as render_frames would, keeps
+        // every row in the same blocks as rendering the buffer in one go.
+        let mut rendered = 0;
+        while rendered < total_samples {
+            let samples_left =
+                self.samples_per_row
+                    .saturating_sub(self.samples_in_current_row) as usize
+                    * 2;
+            if samples_left == 0 && !self.playback_finished {
+                self.advance_row();
+                continue;
+            }
+            let end = if self.playback_finished {
+                total_samples
+            } else {
+                (rendered + samples_left).min(total_samples)
+            };
+            self.process_frame_parallel(&mut buffer[rendered..end]);
+            rendered = end;
+            progress(
+                self.current_row.saturating_sub(1),
+                (rendered / 2) as f32 / self.config.sample_rate as f32,
+            );
         }
 
+        // Then the tails, a row's worth at a time until they are over
+        let chunk_samples = self.samples_per_row.max(RENDER_BLOCK_SIZE as u32) as usize * 2;
+        let max_tail_samples =
+            (MAX_RENDER_TAIL_SECONDS * self.config.sample_rate as f32) as usize * 2;
+        let mut tail_samples = 0;
+        while !self.is_silent() && tail_samples < max_tail_samples {
+            let start = buffer.len();
+            buffer.resize(start + chunk_samples, 0.0);
+            self.process_frame_parallel(&mut buffer[start..]);
+            tail_samples += chunk_samples;
+        }
+
+        // Drop the silence after the last audible sample (keeping L R pairs)
+        let audible_length = buffer
+            .iter()
+            .rposition(|sample| sample.abs() > SILENCE_LEVEL)
+            .map_or(0, |index| (index / 2 + 1) * 2);
+        buffer.truncate(audible_length.max(total_samples));
+
         buffer
     }
 }
 
+// ============================================================================
+// REAL-TIME HANDLES
+// ============================================================================
+
+/// Messages from the main thread to the audio thread
+/// Variants stay unboxed (see EngineController::send), so a Dispatch is
+/// as big as a cell action.
+#[allow(clippy::large_enum_variant)]
+pub enum EngineCommand {
+    /// Run a cell action on a channel right away
+    Dispatch {
+        channel_index: usize,
+        action: CellAction,
+    },
+
+    /// Continue playback from this row (0-indexed)
+    JumpToRow(usize),
+
+    /// Pause (output silence, hold position) or resume
+    SetPaused(bool),
+
+    /// Start the song again from the first row
+    Reset,
+
+    /// Start the next row now (only used with ClockSource::External)
+    AdvanceRow,
+
+    /// Mute or unmute a channel
+    SetMuted { channel_index: usize, muted: bool },
+
+    /// Solo or unsolo a channel
+    SetSoloed { channel_index: usize, soloed: bool },
+
+    /// Fade everything out over this many seconds, then stay silent
+    FadeOut(f32),
+}
+
+/// Messages from the audio thread back to the main thread
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EngineEvent {
+    /// The sequencer moved on; this row (0-indexed) is next
+    RowStarted(usize),
+
+    /// The last row has played (release tails may still be sounding)
+    Finished,
+
+    /// Nothing more will be heard (see PlaybackEngine::is_silent)
+    Silent,
+
+    /// A queued song started playing (see PlaybackEngine::queue_song)
+    SongStarted,
+}
+
+/// How many commands/events can wait in each queue
+const REALTIME_QUEUE_CAPACITY: usize = 256;
+
+/// How many loaded songs can wait in each direction (one is queued at a
+/// time; the rest is room for old songs waiting to be freed)
+const SONG_QUEUE_CAPACITY: usize = 4;
+
+/// Peak levels the audio thread leaves for level meters
+/// Levels are stored as f32 bits in atomics, so neither side waits. For
+/// levels (never negative) a bigger number always has bigger bits, which
+/// lets fetch_max keep the loudest one.
+pub struct LevelMeters {
+    /// Loudest sample per channel since the last read
+    channels: Vec<AtomicU32>,
+
+    /// Loudest output sample (either side) since the last read
+    master: AtomicU32,
+}
+
+impl LevelMeters {
+    fn new(channel_count: usize) -> Self {
+        Self {
+            channels: (0..channel_count).map(|_| AtomicU32::new(0)).collect(),
+            master: AtomicU32::new(0),
+        }
+    }
+
+    /// Keeps a level if it is louder than the one waiting to be read
+    fn record(slot: &AtomicU32, level: f32) {
+        if level > 0.0 {
+            slot.fetch_max(level.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    /// Reads and clears a level
+    fn take(slot: &AtomicU32) -> f32 {
+        f32::from_bits(slot.swap(0, Ordering::Relaxed))
+    }
+}
+
+/// The audio thread's half: owns the engine and renders audio
+pub struct RealtimeEngine {
+    engine: PlaybackEngine,
+    commands: Consumer<EngineCommand>,
+    events: Producer<EngineEvent>,
+    songs: Consumer<LoadedSong>,
+    retired_songs: Producer<LoadedSong>,
+    retired_actions: Producer<CellAction>,
+    /// A dispatched action waiting for room in retired_actions
+    unreturned_action: Option<CellAction>,
+    meters: Arc<LevelMeters>,
+    load_meter: Arc<DspLoadMeter>,
+    callback_timer: CallbackTimer,
+    paused: bool,
+    finished_reported: bool,
+    silent_reported: bool,
+}
+
+/// The main thread's half: sends commands and reads events
+pub struct EngineController {
+    commands: Producer<EngineCommand>,
+    events: Consumer<EngineEvent>,
+    songs: Producer<LoadedSong>,
+    retired_songs: Consumer<LoadedSong>,
+    retired_actions: Consumer<CellAction>,
+    meters: Arc<LevelMeters>,
+    load_meter: Arc<DspLoadMeter>,
+}
+
+impl PlaybackEngine {
+    /// Splits the engine into an audio-thread half and a main-thread half
+    /// connected by lock-free queues
+    pub fn into_realtime(self) -> (RealtimeEngine, EngineController) {
+        let (command_producer, command_consumer) = command_queue(REALTIME_QUEUE_CAPACITY);
+        let (event_producer, event_consumer) = command_queue(REALTIME_QUEUE_CAPACITY);
+        let (song_producer, song_consumer) = command_queue(SONG_QUEUE_CAPACITY);
+        let (retired_producer, retired_consumer) = command_queue(SONG_QUEUE_CAPACITY);
+        let (retired_action_producer, retired_action_consumer) =
+            command_queue(REALTIME_QUEUE_CAPACITY);
+        let meters = Arc::new(LevelMeters::new(self.channels.len()));
+        let load_meter = Arc::new(DspLoadMeter::new(self.stage_timing));
+        (
+            RealtimeEngine {
+                engine: self,
+                commands: command_consumer,
+                events: event_producer,
+                songs: song_consumer,
+                retired_songs: retired_producer,
+                retired_actions: retired_action_producer,
+                unreturned_action: None,
+                meters: Arc::clone(&meters),
+                load_meter: Arc::clone(&load_meter),
+                callback_timer: CallbackTimer::new(),
+                paused: false,
+                finished_reported: false,
+                silent_reported: false,
+            },
+            EngineController {
+                commands: command_producer,
+                events: event_consumer,
+                songs: song_producer,
+                retired_songs: retired_consumer,
+                retired_actions: retired_action_consumer,
+                meters,
+                load_meter,
+            },
+        )
+    }
+}
+
+impl RealtimeEngine {
+    /// Applies waiting commands, then fills the output buffer
+    /// Call this from the audio callback. It never blocks.
+    pub fn process(&mut self, output: &mut [f32]) {
+        self.process_with_input(&[], output);
+    }
+
+    /// Same as process, with the audio a duplex device captured for this
+    /// buffer (see AUDIO INPUT at the top of the file)
+    /// Every call is timed for the DSP load report (see DSP LOAD).
+    pub fn process_with_input(&mut self, input: &[f32], output: &mut [f32]) {
+        let started = Instant::now();
+        self.render_callback(input, output);
+        let stage_nanos = self.engine.take_stage_nanos();
+        self.callback_timer.finish(
+            &self.load_meter,
+            started,
+            output.len() / 2,
+            self.engine.config.sample_rate,
+            &stage_nanos,
+        );
+    }
+
+    /// Body of process_with_input: commands, then rendering, meters, and
+    /// events
+    fn render_callback(&mut self, input: &[f32], output: &mut [f32]) {
+        // Dispatched actions own Vecs, so they go back to the main thread
+        // to be freed. While one waits for room, later commands wait in
+        // their queue too.
+        if let Some(action) = self.unreturned_action.take() {
+            self.return_action(action);
+        }
+        while self.unreturned_action.is_none()
+            && let Some(command) = self.commands.pop()
+        {
+            match command {
+                EngineCommand::Dispatch {
+                    channel_index,
+                    action,
+                } => {
+                    if action.is_bus_command() || channel_index < self.engine.channels.len() {
+                        self.engine.dispatch_action(channel_index, &action);
+                    }
+                    self.return_action(action);
+                }
+                EngineCommand::JumpToRow(row) => {
+                    self.engine.jump_to_row(row);
+                    self.finished_reported = false;
+                    self.silent_reported = false;
+                }
+                EngineCommand::SetPaused(paused) => self.paused = paused,
+                EngineCommand::Reset => {
+                    self.engine.reset();
+                    self.finished_reported = false;
+                    self.silent_reported = false;
+                }
+                EngineCommand::AdvanceRow => self.engine.advance_external_row(),
+                EngineCommand::SetMuted {
+                    channel_index,
+                    muted,
+                } => self.engine.set_channel_muted(channel_index, muted),
+                EngineCommand::SetSoloed {
+                    channel_index,
+                    soloed,
+                } => self.engine.set_channel_soloed(channel_index, soloed),
+                EngineCommand::FadeOut(seconds) => self.engine.fade_out(seconds),
+            }
+        }
+
+        if !self.engine.has_queued_song()
+            && let Some(song) = self.songs.pop()
+        {
+            self.engine.queue_song(song);
+        }
+
+        if self.paused {
+            output.fill(0.0);
+            return;
+        }
+
+        let row_before = self.engine.current_row;
+        let songs_before = self.engine.songs_started;
+        self.engine.process_frame_with_input(input, output);
+
+        for (slot, channel) in self.meters.channels.iter().zip(&mut self.engine.channels) {
+            LevelMeters::record(slot, channel.take_peak_level());
+        }
+        let output_peak = output
+            .iter()
+            .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+        LevelMeters::record(&self.meters.master, output_peak);
+
+        // Old songs go back to the main thread to be freed (one that
+        // doesn't fit waits for the next callback)
+        if let Some(song) = self.engine.take_retired_song()
+            && let Err(song) = self.retired_songs.push(song)
+        {
+            self.engine.retired_song = Some(song);
+        }
+
+        // A full event queue only means the main thread is not listening
+        if self.engine.songs_started != songs_before {
+            let _ = self.events.push(EngineEvent::SongStarted);
+        }
+        if self.engine.current_row != row_before {
+            let _ = self
+                .events
+                .push(EngineEvent::RowStarted(self.engine.current_row));
+        }
+        if self.engine.playback_finished && !self.finished_reported {
+            self.finished_reported = true;
+            let _ = self.events.push(EngineEvent::Finished);
+        }
+        if self.engine.is_silent() && !self.silent_reported {
+            self.silent_reported = true;
+            let _ = self.events.push(EngineEvent::Silent);
+        }
+    }
+
+    /// Hands a used action to the main thread, or keeps it for the next
+    /// callback if the queue is full
+    fn return_action(&mut self, action: CellAction) {
+        if let Err(action) = self.retired_actions.push(action) {
+            self.unreturned_action = Some(action);
+        }
+    }
+}
+
+impl EngineController {
+    /// Queues a command for the audio thread
+    /// Returns the command back if the queue is full. Commands stay
+    /// unboxed so the audio thread never has to free a Box, and dispatched
+    /// actions come back to be freed here (by send and poll_event).
+    #[allow(clippy::result_large_err)]
+    pub fn send(&mut self, command: EngineCommand) -> Result<(), EngineCommand> {
+        self.free_retired_actions();
+        self.commands.push(command)
+    }
+
+    /// Takes the next event from the audio thread, if any
+    pub fn poll_event(&mut self) -> Option<EngineEvent> {
+        self.free_retired_actions();
+        self.events.pop()
+    }
+
+    /// Frees the cell actions the audio thread has dispatched
+    fn free_retired_actions(&mut self) {
+        while self.retired_actions.pop().is_some() {}
+    }
+
+    /// Hands the audio thread the song to play after the current one
+    /// (see PLAYLISTS). Queue the next only after SongStarted; the song
+    /// comes back if the queue is full.
+    #[allow(clippy::result_large_err)]
+    pub fn queue_song(&mut self, song: LoadedSong) -> Result<(), LoadedSong> {
+        self.songs.push(song)
+    }
+
+    /// Frees the songs the audio thread has finished with
+    pub fn free_retired_songs(&mut self) {
+        while self.retired_songs.pop().is_some() {}
+    }
+
+    /// Each channel's loudest level since the last call (0.0 - 1.0)
+    pub fn take_channel_levels(&self) -> Vec<f32> {
+        self.meters.channels.iter().map(LevelMeters::take).collect()
+    }
+
+    /// The output's loudest level since the last call (0.0 - 1.0)
+    pub fn take_master_level(&self) -> f32 {
+        LevelMeters::take(&self.meters.master)
+    }
+
+    /// The worst DSP load since the last call (1.0: no time to spare)
+    pub fn take_recent_dsp_load(&self) -> f32 {
+        self.load_meter.take_recent_load()
+    }
+
+    /// Callback timing, xruns, and the load of each stage so far
+    pub fn dsp_load(&self) -> DspLoadReport {
+        self.load_meter.report()
+    }
+}
+
 // ============================================================================
 // UNIT TESTS
 // ============================================================================
@@ -361,8 +2177,10 @@ impl PlaybackEngine {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::helper::FrequencyTable;
-    use crate::parser::{MissingCellBehavior, parse_song};
+    use crate::dsp_load::DspStage;
+    use crate::helper::{A4_FREQUENCY_HZ, FrequencyTable, TWO_PI};
+    use crate::parser::{MissingCellBehavior, Severity, parse_song};
+    use crate::snapshot::diff_snapshots;
 
     #[test]
     fn test_engine_creation() {
@@ -371,9 +2189,9 @@ mod tests {
         let song = parse_song(
             song_text,
             &frequency_table,
+            A4_FREQUENCY_HZ,
             1,
             MissingCellBehavior::SlowRelease,
-            DebugLevel::Off,
         );
 
         let config = EngineConfig::default();
@@ -390,9 +2208,9 @@ mod tests {
         let song = parse_song(
             song_text,
             &frequency_table,
+            A4_FREQUENCY_HZ,
             1,
             MissingCellBehavior::SlowRelease,
-            DebugLevel::Off,
         );
 
         let config = EngineConfig::default();
@@ -405,4 +2223,1055 @@ mod tests {
         // Should have rendered something
         assert!(engine.total_samples_rendered > 0);
     }
+
+    #[test]
+    fn test_output_does_not_depend_on_buffer_size() {
+        let frequency_table = FrequencyTable::new();
+        let song = parse_song(
+            "Voice0\nc4 sine\ne4 square a:0.5\n-\n.",
+            &frequency_table,
+            A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+        let config = EngineConfig {
+            sample_rate: 8000,
+            channel_count: 1,
+            tick_duration_seconds: 0.0123,
+            ..Default::default()
+        };
+
+        let mut whole = PlaybackEngine::new(song.clone(), config.clone());
+        let mut expected = vec![0.0; 2 * 500];
+        whole.process_frame(&mut expected);
+
+        // Rows start on the same sample even when buffers split blocks
+        let mut pieces = PlaybackEngine::new(song, config);
+        let mut actual = vec![0.0; expected.len()];
+        for chunk in actual.chunks_mut(2 * 37) {
+            pieces.process_frame(chunk);
+        }
+
+        assert_eq!(pieces.current_row, whole.current_row);
+        for (left, right) in actual.iter().zip(&expected) {
+            assert!((left - right).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_parallel_render_matches_single_threaded() {
+        let frequency_table = FrequencyTable::new();
+        let song = parse_song(
+            "Lead,Bass,Hat\n\
+             c4 sine v:5'0.3,c2 trisaw:0.5 a:0.6,noise a:0.2\n\
+             e4 square p:-0.5'0.1,-,.\n\
+             master rv:0.4'0.3,g2 pulse:0.3,noise\n\
+             .,.,.",
+            &frequency_table,
+            A4_FREQUENCY_HZ,
+            3,
+            MissingCellBehavior::SlowRelease,
+        );
+        let config = EngineConfig {
+            sample_rate: 8000,
+            channel_count: 3,
+            tick_duration_seconds: 0.05,
+            ..Default::default()
+        };
+
+        let mut single = PlaybackEngine::new(song.clone(), config.clone());
+        let mut expected = vec![0.0; 2 * 2000];
+        single.process_frame(&mut expected);
+
+        let mut parallel = PlaybackEngine::new(song, config);
+        let mut actual = vec![0.0; expected.len()];
+        parallel.process_frame_parallel(&mut actual);
+
+        assert!(expected.iter().any(|sample| *sample != 0.0));
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_render_progress() {
+        let frequency_table = FrequencyTable::new();
+        let song = parse_song(
+            "Lead,Bass
+             c4 sine,c2 trisaw a:0.5
+             master bpm:200'4,e2
+             g4 square p:0.3,-
+             .,.",
+            &frequency_table,
+            A4_FREQUENCY_HZ,
+            2,
+            MissingCellBehavior::SlowRelease,
+        );
+        let config = EngineConfig {
+            sample_rate: 8000,
+            channel_count: 2,
+            tick_duration_seconds: 0.0123,
+            ..Default::default()
+        };
+
+        // Rendering row by row sounds the same as one whole-song call
+        let mut engine = PlaybackEngine::new(song.clone(), config.clone());
+        let mut updates = Vec::new();
+        let rendered =
+            engine.render_to_buffer_with_progress(|row, seconds| updates.push((row, seconds)));
+
+        let mut whole = PlaybackEngine::new(song, config);
+        let mut expected = vec![0.0; 2 * (whole.get_total_duration_seconds() * 8000.0) as usize];
+        whole.process_frame_parallel(&mut expected);
+        assert_eq!(rendered[..expected.len()], expected[..]);
+
+        // One update per row (plus the lead-in), ending with the last row
+        // at the end of the song
+        let rows: Vec<usize> = updates.iter().map(|(row, _)| *row).collect();
+        assert_eq!(rows, vec![0, 0, 1, 2, 3]);
+        let (_, seconds) = updates[updates.len() - 1];
+        assert!((seconds - expected.len() as f32 / 2.0 / 8000.0).abs() < 1e-6);
+        assert!(updates.windows(2).all(|pair| pair[0].1 < pair[1].1));
+    }
+
+    #[test]
+    fn test_tempo_synced_effects() {
+        let frequency_table = FrequencyTable::new();
+        let song = parse_song(
+            "Lead,Fx
+             c4 square t:1/4'0.5,master dl:1/8.'0.4
+             -,master bpm:60
+             -,-",
+            &frequency_table,
+            A4_FREQUENCY_HZ,
+            2,
+            MissingCellBehavior::SlowRelease,
+        );
+        let config = EngineConfig {
+            sample_rate: 8000,
+            channel_count: 2,
+            tick_duration_seconds: 0.05,
+            ..Default::default()
+        };
+        let mut engine = PlaybackEngine::new(song, config);
+        // The lead-in and the first row
+        let mut output = vec![0.0; 2 * 800];
+
+        // 4 rows of 0.05 s make a 0.2 s beat: a quarter-note tremolo is
+        // 5 Hz and a dotted eighth delay 0.15 s
+        engine.process_frame(&mut output);
+        assert!((engine.channels[0].effects.tremolo_rate_hz - 5.0).abs() < 1e-3);
+        assert_eq!(engine.master_bus.effects.delay_time_samples, 1200);
+
+        // At 60 BPM both follow the new beat
+        engine.process_frame(&mut output);
+        assert!((engine.channels[0].effects.tremolo_rate_hz - 1.0).abs() < 1e-3);
+        assert_eq!(engine.master_bus.effects.delay_time_samples, 6000);
+    }
+
+    #[test]
+    fn test_realtime_commands_and_events() {
+        let frequency_table = FrequencyTable::new();
+        let song = parse_song(
+            "Voice0\nc4 sine\n-\n.",
+            &frequency_table,
+            A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+        let config = EngineConfig {
+            sample_rate: 1000,
+            channel_count: 1,
+            tick_duration_seconds: 0.01,
+            ..Default::default()
+        };
+        let (mut realtime, mut controller) = PlaybackEngine::new(song, config).into_realtime();
+
+        let mut buffer = vec![0.0; 2 * 100];
+        realtime.process(&mut buffer);
+        let mut events = Vec::new();
+        while let Some(event) = controller.poll_event() {
+            events.push(event);
+        }
+        // The release tail is short enough to end within the buffer
+        assert_eq!(
+            events[events.len() - 2..],
+            [EngineEvent::Finished, EngineEvent::Silent]
+        );
+
+        // Paused output is silent even after a jump back to the start
+        assert!(controller.send(EngineCommand::JumpToRow(0)).is_ok());
+        assert!(controller.send(EngineCommand::SetPaused(true)).is_ok());
+        buffer.fill(1.0);
+        realtime.process(&mut buffer);
+        assert!(buffer.iter().all(|sample| *sample == 0.0));
+        assert_eq!(realtime.engine.current_row, 0);
+
+        // The note played on channel 0, and reading the meters clears them
+        assert!(controller.take_channel_levels()[0] > 0.0);
+        assert!(controller.take_master_level() > 0.0);
+        assert_eq!(controller.take_channel_levels(), vec![0.0]);
+    }
+
+    #[test]
+    fn test_dispatched_actions_are_freed_on_the_main_thread() {
+        let frequency_table = FrequencyTable::new();
+        let song = parse_song(
+            "Voice0\nc4 sine\n-\n-\n.",
+            &frequency_table,
+            A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+        let config = EngineConfig {
+            sample_rate: 1000,
+            channel_count: 1,
+            tick_duration_seconds: 1.0,
+            ..Default::default()
+        };
+        let (mut realtime, mut controller) = PlaybackEngine::new(song, config).into_realtime();
+        let action = CellAction::TriggerPitchless {
+            instrument_id: 4,
+            instrument_parameters: vec![0.5],
+            effects: crate::effects::ChannelEffectState::default(),
+            transition_seconds: 0.0,
+            clear_effects: false,
+            envelope: None,
+            seed: None,
+        };
+        let returned = |action: Option<CellAction>| {
+            matches!(action, Some(CellAction::TriggerPitchless { instrument_parameters, .. })
+                if instrument_parameters == [0.5])
+        };
+
+        // The action comes back with its Vec for the main thread to free
+        let dispatch = |action: &CellAction| EngineCommand::Dispatch {
+            channel_index: 0,
+            action: action.clone(),
+        };
+        assert!(controller.send(dispatch(&action)).is_ok());
+        let mut buffer = vec![0.0; 2 * 10];
+        realtime.process(&mut buffer);
+        assert!(returned(controller.retired_actions.pop()));
+
+        // With no room to send it back, it waits, and so do later commands
+        assert!(controller.send(dispatch(&action)).is_ok());
+        assert!(controller.send(EngineCommand::JumpToRow(2)).is_ok());
+        while realtime.retired_actions.push(CellAction::Sustain).is_ok() {}
+        realtime.process(&mut buffer);
+        assert!(realtime.unreturned_action.is_some());
+        assert_eq!(realtime.engine.current_row, 0);
+
+        controller.poll_event();
+        realtime.process(&mut buffer);
+        assert!(realtime.unreturned_action.is_none());
+        assert_ne!(realtime.engine.current_row, 0);
+        assert!(returned(controller.retired_actions.pop()));
+    }
+
+    #[test]
+    fn test_external_clock_advances_rows() {
+        let frequency_table = FrequencyTable::new();
+        let song = parse_song(
+            "Voice0\nc4 sine\nd4 sine\n.",
+            &frequency_table,
+            A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+        let config = EngineConfig {
+            sample_rate: 1000,
+            channel_count: 1,
+            tick_duration_seconds: 0.01,
+            ..Default::default()
+        };
+        let mut engine = PlaybackEngine::new(song, config);
+        engine.set_clock_source(ClockSource::External);
+        let (mut realtime, mut controller) = engine.into_realtime();
+
+        // Without clock the song waits on the first row, however long it runs
+        let mut buffer = vec![0.0; 2 * 100];
+        realtime.process(&mut buffer);
+        assert_eq!(realtime.engine.current_row, 0);
+        assert!(buffer.iter().all(|sample| *sample == 0.0));
+
+        // Each AdvanceRow starts exactly one row
+        assert!(controller.send(EngineCommand::AdvanceRow).is_ok());
+        realtime.process(&mut buffer);
+        assert_eq!(realtime.engine.current_row, 1);
+        assert!(buffer.iter().any(|sample| *sample != 0.0));
+        assert_eq!(controller.poll_event(), Some(EngineEvent::RowStarted(1)));
+
+        for _ in 0..3 {
+            assert!(controller.send(EngineCommand::AdvanceRow).is_ok());
+            realtime.process(&mut buffer);
+        }
+        assert!(realtime.engine.is_finished());
+    }
+
+    #[test]
+    fn test_external_clock_starts_every_pending_row() {
+        let frequency_table = FrequencyTable::new();
+        let song = parse_song(
+            "Voice0\nc4 sine\nd4 sine\ne4 sine\nf4 sine\ng4 sine",
+            &frequency_table,
+            A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+        let config = EngineConfig {
+            sample_rate: 1000,
+            channel_count: 1,
+            tick_duration_seconds: 0.01,
+            ..Default::default()
+        };
+        let mut engine = PlaybackEngine::new(song, config);
+        engine.set_clock_source(ClockSource::External);
+        let (mut realtime, mut controller) = engine.into_realtime();
+        let mut buffer = vec![0.0; 2 * 90];
+        realtime.process(&mut buffer);
+
+        // Three clock ticks between two buffers start three rows, 30
+        // samples apart, instead of leaving two for later buffers
+        for _ in 0..3 {
+            assert!(controller.send(EngineCommand::AdvanceRow).is_ok());
+        }
+        realtime.process(&mut buffer);
+        assert_eq!(realtime.engine.current_row, 3);
+        assert_eq!(realtime.engine.external_rows_pending, 0);
+        assert_eq!(realtime.engine.samples_in_current_row, 30);
+
+        // The next buffer has nothing left over to start
+        realtime.process(&mut buffer);
+        assert_eq!(realtime.engine.current_row, 3);
+    }
+
+    #[test]
+    fn test_mute_and_solo() {
+        let frequency_table = FrequencyTable::new();
+        let render = |song_text: &str, setup: &dyn Fn(&mut PlaybackEngine)| {
+            let song = parse_song(
+                song_text,
+                &frequency_table,
+                A4_FREQUENCY_HZ,
+                2,
+                MissingCellBehavior::SlowRelease,
+            );
+            let config = EngineConfig {
+                sample_rate: 8000,
+                channel_count: 2,
+                tick_duration_seconds: 0.05,
+                ..Default::default()
+            };
+            let mut engine = PlaybackEngine::new(song, config);
+            setup(&mut engine);
+            let mut buffer = vec![0.0; 2 * 800];
+            engine.process_frame(&mut buffer);
+            buffer
+        };
+        let both = "A,B\nc4 sine,e4 square\n-,-\n-,-";
+
+        // Muting a channel before it plays sounds like it was never there
+        let muted = render(both, &|engine| engine.set_channel_muted(1, true));
+        assert_eq!(muted, render("A,B\nc4 sine,\n-,\n-,", &|_| {}));
+
+        // Soloing one channel silences the others
+        let soloed = render(both, &|engine| engine.set_channel_soloed(1, true));
+        assert_eq!(soloed, render("A,B\n,e4 square\n,-\n,-", &|_| {}));
+        assert!(soloed.iter().any(|sample| *sample != 0.0));
+
+        // Muting wins over soloing, and the state survives a reset
+        let silent = render(both, &|engine| {
+            engine.set_channel_soloed(1, true);
+            engine.set_channel_muted(1, true);
+            engine.reset();
+        });
+        assert!(silent.iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn test_fast_forward_and_loop() {
+        let frequency_table = FrequencyTable::new();
+        let song = parse_song(
+            "A,B\nc4 trisaw v:5'0.3,master rv:0.5'0.4\n- a:0.4 tr:0.2,g3 square\ne4 sine,-\n.,.\nc5 noise,\n.",
+            &frequency_table,
+            A4_FREQUENCY_HZ,
+            2,
+            MissingCellBehavior::SlowRelease,
+        );
+        let config = EngineConfig {
+            sample_rate: 8000,
+            channel_count: 2,
+            tick_duration_seconds: 0.05,
+            ..Default::default()
+        };
+
+        // Starting at row 3 sounds exactly like the song from that point on
+        let mut full = PlaybackEngine::new(song.clone(), config.clone());
+        let mut expected = vec![0.0; 2 * 4000];
+        full.process_frame(&mut expected);
+
+        let mut engine = PlaybackEngine::new(song.clone(), config.clone());
+        engine.fast_forward_to_row(3);
+        let skipped = engine.total_samples_rendered as usize;
+        assert_eq!(engine.current_row, 3);
+        assert_eq!(skipped, 4 * 400);
+        let mut actual = vec![0.0; expected.len() - skipped * 2];
+        engine.process_frame(&mut actual);
+        assert_eq!(actual, expected[skipped * 2..]);
+
+        // Rows 2-3 on a loop: row 2 comes round again after row 3
+        let mut engine = PlaybackEngine::new(song, config);
+        engine.set_end_row(Some(4));
+        engine.set_loop_start(Some(2));
+        engine.fast_forward_to_row(2);
+        let mut buffer = vec![0.0; 2 * 400];
+        let mut rows = Vec::new();
+        for _ in 0..6 {
+            engine.process_frame(&mut buffer);
+            rows.push(engine.current_row);
+        }
+        assert_eq!(rows, vec![3, 4, 3, 4, 3, 4]);
+        assert!(!engine.is_finished());
+    }
+
+    #[test]
+    fn test_loop_crossfade() {
+        let song = parse_song(
+            "A\nc4 sine\n-\n-\n-",
+            &FrequencyTable::new(),
+            A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+        let config = EngineConfig {
+            sample_rate: 8000,
+            channel_count: 1,
+            tick_duration_seconds: 0.05,
+            ..Default::default()
+        };
+        let looped = |crossfade_seconds: f32| {
+            let mut engine = PlaybackEngine::new(song.clone(), config.clone());
+            engine.set_loop_start(Some(0));
+            engine.set_loop_crossfade(crossfade_seconds);
+            engine
+        };
+
+        // Nothing changes until the loop wraps (row 0 starts after one
+        // row's worth of samples, then 4 rows of 400 samples)
+        let mut jumping = looped(0.0);
+        let mut engine = looped(0.1);
+        let mut expected = vec![0.0; 2 * 2000];
+        let mut actual = vec![0.0; expected.len()];
+        jumping.process_frame(&mut expected);
+        engine.process_frame(&mut actual);
+        assert_eq!(actual, expected);
+
+        // Halfway through the crossfade both passes are sounding, and the
+        // mix doesn't drop out at the seam
+        let mut buffer = vec![0.0; 2 * 400];
+        engine.process_frame(&mut buffer);
+        assert!(engine.fading_channels[0].is_playing());
+        assert!(engine.channels[0].is_playing());
+        let peak = buffer
+            .iter()
+            .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+        assert!(peak > 0.1, "level dipped to {}", peak);
+
+        // Then the old pass is gone until the next wrap
+        engine.process_frame(&mut buffer);
+        assert!(!engine.fading_channels[0].is_playing());
+        assert_eq!(engine.loop_crossfade_position, None);
+        assert_eq!(engine.current_row, 2);
+    }
+
+    #[test]
+    fn test_queued_song_crossfades_in() {
+        let load = |text: &str, channel_count: usize| {
+            let song = parse_song(
+                text,
+                &FrequencyTable::new(),
+                A4_FREQUENCY_HZ,
+                channel_count,
+                MissingCellBehavior::SlowRelease,
+            );
+            let config = EngineConfig {
+                sample_rate: 8000,
+                channel_count,
+                tick_duration_seconds: 0.05,
+                default_release_seconds: 0.1,
+                ..Default::default()
+            };
+            (song, config)
+        };
+        let (first, config) = load("A\nc4 sine\n-", 1);
+        let (second, second_config) = load("[groups] pads = 2\nA,B\ne4 sine,g4 sine\n-,-\n.,.", 2);
+
+        // The first song's 2 rows end after 1200 samples (row 0 starts
+        // after one row's worth), and the second starts right after
+        let mut engine = PlaybackEngine::new(first, config);
+        engine.set_song_crossfade(0.1);
+        engine.queue_song(LoadedSong::new(second, second_config));
+        let mut buffer = vec![0.0; 2 * 1200];
+        engine.process_frame(&mut buffer);
+        assert_eq!(engine.songs_started(), 0);
+
+        // Both songs sound during the crossfade, without a dip in level
+        let mut buffer = vec![0.0; 2 * 400];
+        engine.process_frame(&mut buffer);
+        assert_eq!(engine.songs_started(), 1);
+        assert_eq!(engine.channel_count(), 2);
+        assert_eq!(engine.current_row(), 1);
+        assert!(engine.outgoing_song.is_some());
+        let peak = buffer
+            .iter()
+            .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+        assert!(peak > 0.1, "level dipped to {}", peak);
+
+        // Then the old song is handed over to be freed, and the new one
+        // plays on to its end
+        engine.process_frame(&mut buffer);
+        assert!(engine.outgoing_song.is_none());
+        assert!(engine.take_retired_song().is_some());
+        engine.process_frame(&mut buffer);
+        assert!(!engine.is_finished());
+        engine.process_frame(&mut buffer);
+        assert!(engine.is_finished());
+
+        // Through the realtime handles: the song goes over a queue and
+        // comes back once it has faded out
+        let (first, config) = load("A\nc4 sine\n-", 1);
+        let (second, second_config) = load("A\ne4 sine\n-", 1);
+        let mut engine = PlaybackEngine::new(first, config);
+        engine.set_song_crossfade(0.05);
+        let (mut realtime, mut controller) = engine.into_realtime();
+        assert!(
+            controller
+                .queue_song(LoadedSong::new(second, second_config))
+                .is_ok()
+        );
+        let mut events = Vec::new();
+        for _ in 0..20 {
+            realtime.process(&mut buffer);
+            events.extend(std::iter::from_fn(|| controller.poll_event()));
+        }
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| **event == EngineEvent::SongStarted)
+                .count(),
+            1
+        );
+        assert!(events.contains(&EngineEvent::Silent));
+        assert!(controller.retired_songs.pop().is_some());
+    }
+
+    #[test]
+    fn test_snapshot_at_row() {
+        let song = parse_song(
+            "Lead,Hats\n[groups] drums = Hats\nc4 sine,noise\ne4 square a:0.5,-\nmaster rv:0.6'0.3,.",
+            &FrequencyTable::new(),
+            A4_FREQUENCY_HZ,
+            2,
+            MissingCellBehavior::SlowRelease,
+        );
+        let config = EngineConfig {
+            sample_rate: 8000,
+            channel_count: 2,
+            tick_duration_seconds: 0.05,
+            ..Default::default()
+        };
+        let snapshot_at =
+            |row: usize| PlaybackEngine::new(song.clone(), config.clone()).snapshot_at_row(row);
+
+        // The row's own cells show, before any of its samples play
+        let first = snapshot_at(0);
+        assert_eq!(first.row, 0);
+        assert_eq!(first.samples_into_row, 0);
+        assert_eq!(first.channels[0].instrument, "sine");
+        assert_eq!(first.channels[1].instrument, "noise");
+        assert_eq!(first.groups[0].name.as_deref(), Some("drums"));
+        let second = snapshot_at(1);
+        assert_eq!(second.row, 1);
+        assert_eq!(second.channels[0].instrument, "square");
+        assert_eq!(second.channels[0].envelope.phase, "Attack");
+        assert!(second.seconds > 0.0);
+
+        // The same render gives the same snapshot; the diff names what changed
+        assert!(diff_snapshots(&second, &snapshot_at(1)).is_empty());
+        let differences = diff_snapshots(&second, &snapshot_at(2));
+        assert!(
+            differences
+                .iter()
+                .any(|line| line.starts_with("master.reverb1_enabled: false -> true")),
+            "{:?}",
+            differences
+        );
+        assert!(
+            differences
+                .iter()
+                .any(|line| line.starts_with("channels[1].envelope.phase: ")),
+            "{:?}",
+            differences
+        );
+        assert!(second.to_json().contains("\"frequency_hz\""));
+    }
+
+    #[test]
+    fn test_release_tails_and_fade_out() {
+        let frequency_table = FrequencyTable::new();
+        let song = parse_song(
+            "A\nc4 sine env:0.01'0'1'0.2\n-",
+            &frequency_table,
+            A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+        let config = EngineConfig {
+            sample_rate: 8000,
+            channel_count: 1,
+            tick_duration_seconds: 0.05,
+            ..Default::default()
+        };
+
+        // The held note is released at the end of the song and its tail
+        // still sounds, then the engine goes quiet by itself
+        let mut engine = PlaybackEngine::new(song.clone(), config.clone());
+        let mut buffer = vec![0.0; 2 * 400];
+        for _ in 0..4 {
+            engine.process_frame(&mut buffer);
+        }
+        assert!(engine.is_finished());
+        assert!(!engine.is_silent());
+        assert!(buffer.iter().any(|sample| sample.abs() > 0.001));
+        for _ in 0..20 {
+            engine.process_frame(&mut buffer);
+        }
+        assert!(engine.is_silent());
+
+        // A WAV render runs on through the 0.2 s release (1600 samples
+        // after the song) and stops where it ends
+        let rendered = engine.render_to_buffer();
+        let frames = rendered.len() / 2;
+        assert!((2400..=2800).contains(&frames));
+        assert!(
+            rendered[rendered.len() - 2..]
+                .iter()
+                .any(|sample| sample.abs() > 0.0)
+        );
+
+        // A fade-out ends in silence partway through the song
+        let mut engine = PlaybackEngine::new(song, config);
+        engine.fade_out(0.02);
+        engine.process_frame(&mut buffer);
+        assert!(engine.is_silent());
+        assert!(buffer[2 * 200..].iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn test_fadeout_command_ends_song() {
+        let frequency_table = FrequencyTable::new();
+        let parse = |text: &str| {
+            parse_song(
+                text,
+                &frequency_table,
+                A4_FREQUENCY_HZ,
+                1,
+                MissingCellBehavior::SlowRelease,
+            )
+        };
+        let song = parse("A\nc4 sine\n-\nmaster fadeout:0.05\n-\n-\n-\n-\n-");
+        assert!(song.diagnostics.is_empty());
+        let config = EngineConfig {
+            sample_rate: 8000,
+            channel_count: 1,
+            tick_duration_seconds: 0.05,
+            ..Default::default()
+        };
+
+        // The song lasts until the fade is over, not to its last row
+        assert!((song.duration_seconds(0.05) - 0.15).abs() < 1e-6);
+        assert!((song.duration_seconds(0.01) - 0.07).abs() < 1e-6);
+
+        // Once faded, no more rows start and the engine reports the end
+        let mut engine = PlaybackEngine::new(song, config);
+        let mut buffer = vec![0.0; 2 * 8000];
+        engine.process_frame(&mut buffer);
+        assert!(engine.is_finished() && engine.is_silent());
+        assert!(engine.current_row() < 8);
+        assert!(buffer[2 * 2000..].iter().all(|&sample| sample == 0.0));
+
+        let song = parse("A\nmaster fadeout:0");
+        assert_eq!(song.diagnostics.count(Severity::Error), 1);
+    }
+
+    #[test]
+    fn test_channel_groups() {
+        let frequency_table = FrequencyTable::new();
+        let rows = "Lead,Bass\nc4 sine,c2 square a:0.5\n-,-\n.,.";
+        let render = |header: &str, parallel: bool| {
+            let song = parse_song(
+                &format!("{}{}", header, rows),
+                &frequency_table,
+                A4_FREQUENCY_HZ,
+                2,
+                MissingCellBehavior::SlowRelease,
+            );
+            let config = EngineConfig {
+                sample_rate: 8000,
+                channel_count: 2,
+                tick_duration_seconds: 0.05,
+                ..Default::default()
+            };
+            let mut engine = PlaybackEngine::new(song, config);
+            let mut buffer = vec![0.0; 2 * 2000];
+            if parallel {
+                engine.process_frame_parallel(&mut buffer);
+            } else {
+                engine.process_frame(&mut buffer);
+            }
+            buffer
+        };
+
+        // A group with default settings passes its channels through
+        let ungrouped = render("", false);
+        assert!(ungrouped.iter().any(|sample| *sample != 0.0));
+        assert_eq!(render("[groups] both = 1,2\n", false), ungrouped);
+
+        // A silenced group silences only its own channels
+        let lead_silenced = render("[groups] lead = 1 a:0\n", false);
+        let song = parse_song(
+            "Lead,Bass\n.,c2 square a:0.5\n.,-\n.,.",
+            &frequency_table,
+            A4_FREQUENCY_HZ,
+            2,
+            MissingCellBehavior::SlowRelease,
+        );
+        let mut bass_only = PlaybackEngine::new(
+            song,
+            EngineConfig {
+                sample_rate: 8000,
+                channel_count: 2,
+                tick_duration_seconds: 0.05,
+                ..Default::default()
+            },
+        );
+        let mut expected = vec![0.0; lead_silenced.len()];
+        bass_only.process_frame(&mut expected);
+        assert!(expected.iter().any(|sample| *sample != 0.0));
+        assert_eq!(lead_silenced, expected);
+
+        // Group effects render the same on several threads
+        let header = "[groups] lead = 1 rv:0.4'0.3 p:-0.5\n";
+        assert_eq!(render(header, true), render(header, false));
+    }
+
+    #[test]
+    fn test_random_seeds() {
+        let frequency_table = FrequencyTable::new();
+        let config = EngineConfig {
+            sample_rate: 8000,
+            channel_count: 1,
+            tick_duration_seconds: 0.05,
+            ..Default::default()
+        };
+        let parse = |text: &str| {
+            parse_song(
+                text,
+                &frequency_table,
+                A4_FREQUENCY_HZ,
+                1,
+                MissingCellBehavior::SlowRelease,
+            )
+        };
+        let render = |text: &str| {
+            let mut engine = PlaybackEngine::new(parse(text), config.clone());
+            let mut buffer = vec![0.0; 2 * 800];
+            engine.process_frame(&mut buffer);
+            buffer
+        };
+
+        // The song seed picks the noise, and the same seed plays it again
+        let seeded = render("Drum\nconfig, seed: 9\nnoise\n-");
+        assert_eq!(render("Drum\nconfig, seed: 9\nnoise\n-"), seeded);
+        assert_ne!(render("Drum\nconfig, seed: 10\nnoise\n-"), seeded);
+        assert_eq!(
+            render("Drum\nconfig, seed: 0\nnoise\n-"),
+            render("Drum\nnoise\n-")
+        );
+
+        // A seed on the trigger restarts the noise, so every hit is the same
+        let song = parse("Drum\nnoise seed:5");
+        assert!(song.diagnostics.is_empty());
+        let mut engine = PlaybackEngine::new(song.clone(), config);
+        engine.dispatch_action(0, &song.rows[0][0]);
+        let first_hit = engine.channels[0].random_generator.next_u32();
+        engine.dispatch_action(0, &song.rows[0][0]);
+        assert_eq!(engine.channels[0].random_generator.next_u32(), first_hit);
+    }
+
+    #[test]
+    fn test_streamed_song_plays_like_whole_song() {
+        let song_text = "Lead,Bass\nc4 sine v:5,c2 trisaw a:0.5\ne4\n-,.\n.\n";
+        let config = EngineConfig {
+            sample_rate: 8000,
+            channel_count: 2,
+            tick_duration_seconds: 0.02,
+            ..Default::default()
+        };
+        let song = parse_song(
+            song_text,
+            &FrequencyTable::new(),
+            A4_FREQUENCY_HZ,
+            2,
+            MissingCellBehavior::SlowRelease,
+        );
+        let mut whole = PlaybackEngine::new(song, config.clone());
+        let mut expected = vec![0.0; 2 * 1000];
+        whole.process_frame(&mut expected);
+
+        // The whole song fits in the queue, so no row can be late
+        let streamed = crate::song_stream::stream_song(
+            std::io::Cursor::new(song_text),
+            A4_FREQUENCY_HZ,
+            2,
+            MissingCellBehavior::SlowRelease,
+        )
+        .unwrap();
+        let mut engine = PlaybackEngine::new(streamed.head, config);
+        engine.set_row_feed(streamed.feed);
+        let mut actual = vec![0.0; expected.len()];
+        engine.process_frame(&mut actual);
+
+        assert_eq!(actual, expected);
+        assert_eq!(engine.current_row, whole.current_row);
+        assert!(engine.is_finished() && whole.is_finished());
+        drop(engine);
+        let (diagnostics, late_rows) = streamed.parser.finish();
+        assert!(diagnostics.is_empty());
+        assert_eq!(late_rows, 0);
+    }
+
+    #[test]
+    fn test_audio_input_plays_through_input_channels() {
+        let frequency_table = FrequencyTable::new();
+        let render = |song_text: &str, with_input: bool| {
+            let song = parse_song(
+                song_text,
+                &frequency_table,
+                A4_FREQUENCY_HZ,
+                1,
+                MissingCellBehavior::SlowRelease,
+            );
+            let config = EngineConfig {
+                sample_rate: 8000,
+                channel_count: 1,
+                tick_duration_seconds: 0.05,
+                ..Default::default()
+            };
+            let mut engine = PlaybackEngine::new(song, config);
+
+            // A 440 Hz tone on the left side only
+            let mut input = vec![0.0; 2 * 800];
+            for (index, frame) in input.chunks_exact_mut(2).enumerate() {
+                frame[0] = (TWO_PI * 440.0 * index as f32 / 8000.0).sin() * 0.5;
+            }
+            let mut buffer = vec![0.0; 2 * 800];
+            if with_input {
+                engine.process_frame_with_input(&input, &mut buffer);
+            } else {
+                engine.process_frame(&mut buffer);
+            }
+            buffer
+                .iter()
+                .fold(0.0_f32, |peak, sample| peak.max(sample.abs()))
+        };
+
+        // The input channel plays what was captured, through its effects
+        assert!(render("In\ninput\n-\n-", true) > 0.1);
+        assert!(render("In\ninput:1 a:0.5\n-\n-", true) > 0.05);
+
+        // Nothing captured (or only the other side) is silence
+        assert_eq!(render("In\ninput\n-\n-", false), 0.0);
+        assert_eq!(render("In\ninput:2\n-\n-", true), 0.0);
+    }
+
+    #[test]
+    fn test_parse_sample_rate() {
+        assert_eq!(parse_sample_rate("44100"), Some(44100));
+        assert_eq!(parse_sample_rate("96000 Hz"), Some(96000));
+        assert_eq!(parse_sample_rate("44.1k"), Some(44100));
+        assert_eq!(parse_sample_rate("192k"), Some(192_000));
+        assert_eq!(parse_sample_rate("4000"), None);
+        assert_eq!(parse_sample_rate("fast"), None);
+    }
+
+    #[test]
+    fn test_song_sounds_the_same_at_any_sample_rate() {
+        let frequency_table = FrequencyTable::new();
+        let render = |sample_rate: u32| {
+            let song = parse_song(
+                "Lead,Fx
+                 a4 sine ch:0.5'1'0.5'0.2 wah:2'0.5,master rv:0.5'0.3 dl:0.1'0.4
+                 -,-
+                 -,-
+                 -,-
+                 .,",
+                &frequency_table,
+                A4_FREQUENCY_HZ,
+                2,
+                MissingCellBehavior::SlowRelease,
+            );
+            let config = EngineConfig {
+                sample_rate,
+                channel_count: 2,
+                tick_duration_seconds: 0.1,
+                ..Default::default()
+            };
+            let mut engine = PlaybackEngine::new(song, config);
+            let output = engine.render_to_buffer();
+            let delay_seconds =
+                engine.master_bus.effects.delay_time_samples as f32 / sample_rate as f32;
+            (output, delay_seconds)
+        };
+
+        for sample_rate in [MIN_SAMPLE_RATE, 44100, 96000, MAX_SAMPLE_RATE] {
+            let (output, delay_seconds) = render(sample_rate);
+            let seconds = |samples: usize| samples as f32 / sample_rate as f32;
+
+            // The echo is as long, and the note as high, at every rate
+            assert!((delay_seconds - 0.1).abs() < 1e-3);
+            let row = sample_rate as usize / 10;
+            let crossings = output[2 * row..2 * 3 * row]
+                .chunks_exact(2)
+                .map(|frame| frame[0])
+                .collect::<Vec<f32>>()
+                .windows(2)
+                .filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0))
+                .count();
+            assert!(
+                (crossings as f32 - 2.0 * 440.0 * seconds(2 * row)).abs() < 4.0,
+                "{} crossings at {} Hz",
+                crossings,
+                sample_rate
+            );
+            assert!(seconds(output.len() / 2) > 0.5);
+        }
+    }
+
+    #[test]
+    fn test_dsp_load_times_each_stage() {
+        let frequency_table = FrequencyTable::new();
+        let song_text = "A,B\nc4 saw wah:2'0.6 d:0.5,master rv2:0.5'0.5'0.5'0.3\n-,-\n-,-\n.,.";
+        let render = |stage_timing: bool| {
+            let song = parse_song(
+                song_text,
+                &frequency_table,
+                A4_FREQUENCY_HZ,
+                2,
+                MissingCellBehavior::SlowRelease,
+            );
+            assert!(song.diagnostics.is_empty());
+            let config = EngineConfig {
+                sample_rate: 8000,
+                channel_count: 2,
+                tick_duration_seconds: 0.05,
+                ..Default::default()
+            };
+            let mut engine = PlaybackEngine::new(song, config);
+            engine.set_stage_timing(stage_timing);
+            let (mut realtime, controller) = engine.into_realtime();
+            let mut output = vec![0.0; 2 * 256 * 20];
+            for buffer in output.chunks_mut(2 * 256) {
+                realtime.process(buffer);
+            }
+            (output, controller.dsp_load())
+        };
+
+        // Timing changes nothing about the sound
+        let (timed, report) = render(true);
+        let (untimed, untimed_report) = render(false);
+        assert_eq!(timed, untimed);
+        assert!(timed.iter().any(|sample| *sample != 0.0));
+
+        assert_eq!(report.callbacks, 20);
+        assert!(report.worst_load > 0.0 && report.average_load <= report.worst_load);
+        assert!(untimed_report.stage_loads.is_none());
+
+        // The effects in the song were timed; a bus effect that is off
+        // never runs at all
+        let stage_loads = report.stage_loads.unwrap();
+        let load = |stage: DspStage| stage_loads[stage as usize];
+        for stage in [
+            DspStage::Voices,
+            DspStage::Wah,
+            DspStage::Distortion,
+            DspStage::Reverb2,
+        ] {
+            assert!(load(stage) > 0.0, "{} was not timed", stage.name());
+        }
+        assert_eq!(load(DspStage::Delay), 0.0);
+    }
+
+    #[test]
+    fn test_frozen_channel_sounds_like_live() {
+        let frequency_table = FrequencyTable::new();
+        let song_text = "[groups]\npads = 2 rv:0.3'0.2\nA,B\nc4 sine,e4 saw ch:0.5'0.3 v:5'0.2\n-,master bpm:150\ng4 noise,-\n.,.\n-,-";
+        let new_engine = || {
+            let song = parse_song(
+                song_text,
+                &frequency_table,
+                A4_FREQUENCY_HZ,
+                2,
+                MissingCellBehavior::SlowRelease,
+            );
+            assert!(song.diagnostics.is_empty());
+            let config = EngineConfig {
+                sample_rate: 8000,
+                channel_count: 2,
+                tick_duration_seconds: 0.05,
+                ..Default::default()
+            };
+            PlaybackEngine::new(song, config)
+        };
+        let render = |engine: &mut PlaybackEngine| {
+            let mut output = vec![0.0; 2 * 8000];
+            engine.process_frame(&mut output);
+            output
+        };
+
+        // The last channel is mixed last either way, so the sum is the same
+        let live = render(&mut new_engine());
+        let mut engine = new_engine();
+        let seconds = engine.freeze_channel(1).unwrap();
+        assert!(seconds > 0.2 && engine.is_channel_frozen(1) && !engine.is_channel_frozen(0));
+        assert_eq!(render(&mut engine), live);
+        assert!(!engine.channels[1].is_playing());
+
+        // It keeps in step after a jump back, and starts over on reset
+        engine.reset();
+        assert_eq!(render(&mut engine), live);
+        let mut jumped = new_engine();
+        jumped.freeze_channel(1).unwrap();
+        jumped.fast_forward_to_row(2);
+        let mut live_jumped = new_engine();
+        live_jumped.fast_forward_to_row(2);
+        let (frozen_output, live_output) = (render(&mut jumped), render(&mut live_jumped));
+        let difference = frozen_output
+            .iter()
+            .zip(&live_output)
+            .fold(0.0_f32, |worst, (a, b)| worst.max((a - b).abs()));
+        assert!(difference < 1e-4, "{}", difference);
+
+        // Muting a frozen channel silences it like a live one
+        let mut muted = new_engine();
+        muted.freeze_channel(1).unwrap();
+        muted.set_channel_muted(1, true);
+        let mut live_muted = new_engine();
+        live_muted.set_channel_muted(1, true);
+        assert_eq!(render(&mut muted), render(&mut live_muted));
+
+        let mut crossfading = new_engine();
+        crossfading.set_loop_crossfade(0.1);
+        assert!(crossfading.freeze_channel(1).is_err());
+        assert!(new_engine().freeze_channel(2).is_err());
+    }
 }
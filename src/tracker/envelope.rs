@@ -30,6 +30,7 @@
 // ============================================================================
 
 use crate::helper::{exponential_interpolation, lerp, logarithmic_interpolation};
+use serde::{Deserialize, Serialize};
 
 // ============================================================================
 // ENVELOPE STATE
@@ -100,7 +101,7 @@ pub enum EnvelopeCurveType {
 
 /// Defines the parameters for an envelope type
 /// This is the "blueprint" for how an envelope behaves
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct EnvelopeDefinition {
     /// Attack time in seconds (how long to reach peak volume)
     pub attack_time_seconds: f32,
@@ -241,6 +242,68 @@ pub static ENVELOPE_REGISTRY: &[EnvelopeDefinition] = &[
     },
 ];
 
+// ============================================================================
+// ENVELOPE SHAPE (PER-NOTE OVERRIDE)
+// ============================================================================
+//
+// A note can bring its own attack/decay/sustain/release times instead of
+// the registry envelope, e.g. "c4 sine env:0.001'0.2'0.4'0.5" or an
+// instrument loaded from a TOML file. Curves stay those of the registry
+// envelope; only the times and sustain level change.
+// ============================================================================
+
+/// Attack, decay, sustain and release values for a single note
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EnvelopeShape {
+    /// Attack time in seconds
+    pub attack_time_seconds: f32,
+
+    /// Decay time in seconds
+    pub decay_time_seconds: f32,
+
+    /// Sustain level from 0.0 to 1.0
+    pub sustain_level: f32,
+
+    /// Release time in seconds for slow releases (None = engine default)
+    pub release_time_seconds: Option<f32>,
+}
+
+impl EnvelopeShape {
+    /// Builds a shape from up to four values: attack, decay, sustain, release
+    /// Missing values come from the default envelope (ID 0)
+    pub fn from_parameters(params: &[f32]) -> Self {
+        let default_definition = &ENVELOPE_REGISTRY[0];
+        Self {
+            attack_time_seconds: params
+                .first()
+                .copied()
+                .unwrap_or(default_definition.attack_time_seconds),
+            decay_time_seconds: params
+                .get(1)
+                .copied()
+                .unwrap_or(default_definition.decay_time_seconds),
+            sustain_level: params
+                .get(2)
+                .copied()
+                .unwrap_or(default_definition.sustain_level),
+            release_time_seconds: params.get(3).copied(),
+        }
+        .clamped()
+    }
+
+    /// The shape with its times held to 0-30 seconds and its sustain to 0-1
+    pub fn clamped(self) -> Self {
+        Self {
+            attack_time_seconds: self.attack_time_seconds.clamp(0.0, 30.0),
+            decay_time_seconds: self.decay_time_seconds.clamp(0.0, 30.0),
+            sustain_level: self.sustain_level.clamp(0.0, 1.0),
+            release_time_seconds: self
+                .release_time_seconds
+                .map(|value| value.clamp(0.0, 30.0)),
+        }
+    }
+}
+
 // ============================================================================
 // ENVELOPE STATE MACHINE
 // ============================================================================
@@ -276,14 +339,22 @@ pub struct EnvelopeState {
 
     /// The sample rate (needed for time calculations)
     pub sample_rate: u32,
+
+    /// The definition in use: the registry entry, possibly with its
+    /// times replaced by a per-note EnvelopeShape
+    pub definition: EnvelopeDefinition,
+
+    /// Release time for slow releases set by the current EnvelopeShape
+    pub release_time_seconds: Option<f32>,
 }
 
 impl EnvelopeState {
     /// Creates a new envelope state with the specified envelope type
     /// The envelope starts in the Idle phase until trigger() is called
     pub fn new(envelope_id: usize, sample_rate: u32) -> Self {
+        let envelope_id = envelope_id.min(ENVELOPE_REGISTRY.len() - 1);
         Self {
-            envelope_id: envelope_id.min(ENVELOPE_REGISTRY.len() - 1),
+            envelope_id,
             current_phase: EnvelopePhase::Idle,
             current_amplitude: 0.0,
             phase_elapsed_samples: 0,
@@ -291,6 +362,8 @@ impl EnvelopeState {
             phase_start_amplitude: 0.0,
             phase_target_amplitude: 0.0,
             sample_rate,
+            definition: ENVELOPE_REGISTRY[envelope_id],
+            release_time_seconds: None,
         }
     }
 
@@ -300,8 +373,23 @@ impl EnvelopeState {
     }
 
     /// Gets the envelope definition for this envelope
-    fn get_definition(&self) -> &'static EnvelopeDefinition {
-        &ENVELOPE_REGISTRY[self.envelope_id]
+    /// Returns a copy so the caller can keep it while updating the state
+    fn get_definition(&self) -> EnvelopeDefinition {
+        self.definition
+    }
+
+    /// Replaces the times and sustain level for the next notes
+    /// None goes back to the plain registry envelope
+    pub fn set_shape(&mut self, shape: Option<EnvelopeShape>) {
+        self.definition = ENVELOPE_REGISTRY[self.envelope_id];
+        self.release_time_seconds = None;
+
+        if let Some(shape) = shape {
+            self.definition.attack_time_seconds = shape.attack_time_seconds;
+            self.definition.decay_time_seconds = shape.decay_time_seconds;
+            self.definition.sustain_level = shape.sustain_level;
+            self.release_time_seconds = shape.release_time_seconds;
+        }
     }
 
     /// Triggers the envelope - starts the attack phase
@@ -545,4 +633,24 @@ mod tests {
         envelope.release_with_time(2.0);
         assert_eq!(envelope.current_phase, EnvelopePhase::Release);
     }
+
+    #[test]
+    fn test_envelope_shape_override() {
+        let mut envelope = EnvelopeState::new_default(1000);
+        envelope.set_shape(Some(EnvelopeShape::from_parameters(&[0.0, 0.0, 0.5, 0.3])));
+
+        envelope.trigger();
+        envelope.process_sample();
+        assert_eq!(envelope.current_phase, EnvelopePhase::Sustain);
+        assert_eq!(envelope.current_amplitude, 0.5);
+        assert_eq!(envelope.release_time_seconds, Some(0.3));
+
+        // Going back to the registry envelope
+        envelope.set_shape(None);
+        assert_eq!(envelope.release_time_seconds, None);
+        assert_eq!(
+            envelope.definition.sustain_level,
+            ENVELOPE_REGISTRY[0].sustain_level
+        );
+    }
 }
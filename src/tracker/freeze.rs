@@ -0,0 +1,178 @@
+// ============================================================================
+// FREEZE.RS - Frozen Channels (Bounce in Place)
+// ============================================================================
+//
+// A frozen channel is rendered once, before playback, and then played back
+// as a recording instead of being synthesized live:
+//
+//   tracker assets/song.csv --freeze 3,4
+//
+// This frees the CPU from expensive channels (thick supersaw stacks, long
+// effect chains) on a weak machine. The sound is the channel's own output
+// after its effects, so it still goes through its group bus and the master
+// bus (reverb and delay stay live, and master commands still reach it).
+//
+// HOW IT WORKS:
+// PlaybackEngine::freeze_channel plays the song from the top on a private
+// copy of the engine, rendering only that channel, and keeps its stereo
+// output in a FrozenChannel along with the sample each row started at.
+// During playback the channel's cells are no longer dispatched to it (so
+// it costs nothing), and its recording is added to the mix in its place.
+// Every time a row starts, the recording jumps to where that row started,
+// so frozen channels stay in step through --start-row, jumps, and loops.
+//
+// LIMITS:
+// - A frozen channel plays what it played the first time through each
+//   row: cells sent live (OSC, the editor) don't reach it, and at a loop
+//   seam it jumps to the loop start instead of ringing on across it.
+// - Loop crossfades and streamed songs can't be frozen (see freeze_channel).
+// - The recording is kept in memory: about 23 MB per minute of song at
+//   48 kHz (two f32 samples per frame).
+// ============================================================================
+
+use crate::channel::MUTE_FADE_SECONDS;
+use crate::helper::lerp;
+
+/// A channel's output rendered ahead of time (see the top of the file)
+#[derive(Clone, Debug)]
+pub struct FrozenChannel {
+    /// The channel's output, after its effects
+    pub left: Vec<f32>,
+    pub right: Vec<f32>,
+
+    /// Sample of the recording each row (0-indexed) started at
+    pub row_starts: Vec<usize>,
+
+    /// Next sample to play
+    position: usize,
+
+    /// Mute fade, like a live channel's (0 = muted, 1 = heard)
+    gain: f32,
+}
+
+impl FrozenChannel {
+    /// A recording ready to play from the top
+    pub fn new(left: Vec<f32>, right: Vec<f32>, row_starts: Vec<usize>) -> Self {
+        Self {
+            left,
+            right,
+            row_starts,
+            position: 0,
+            gain: 1.0,
+        }
+    }
+
+    /// Length of the recording in samples
+    pub fn len(&self) -> usize {
+        self.left.len()
+    }
+
+    /// Whether nothing was recorded
+    pub fn is_empty(&self) -> bool {
+        self.left.is_empty()
+    }
+
+    /// Whether part of the recording is still to be played
+    pub fn is_playing(&self) -> bool {
+        self.position < self.len()
+    }
+
+    /// Moves to where a row (0-indexed) started
+    pub fn start_row(&mut self, row: usize) {
+        if let Some(&start) = self.row_starts.get(row) {
+            self.position = start;
+        }
+    }
+
+    /// Moves to a sample of the recording (0 goes back to the top)
+    pub fn seek(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    /// ADDS the next stretch of the recording into `left` and `right`,
+    /// fading in or out over the block when the channel is unmuted or
+    /// muted. Returns the loudest sample added, for level meters.
+    pub fn play_block(
+        &mut self,
+        left: &mut [f32],
+        right: &mut [f32],
+        audible: bool,
+        sample_rate: u32,
+    ) -> f32 {
+        let length = left.len().min(right.len());
+        let start_gain = self.gain;
+        let target_gain = if audible { 1.0 } else { 0.0 };
+        let fade_step = length as f32 / (MUTE_FADE_SECONDS * sample_rate as f32);
+        self.gain = if target_gain > start_gain {
+            (start_gain + fade_step).min(target_gain)
+        } else {
+            (start_gain - fade_step).max(target_gain)
+        };
+
+        let start = self.position.min(self.len());
+        let end = (self.position + length).min(self.len());
+        let mut peak = 0.0_f32;
+        if start_gain > 0.0 || self.gain > 0.0 {
+            let step = 1.0 / length.max(1) as f32;
+            let recorded_left = &self.left[start..end];
+            let recorded_right = &self.right[start..end];
+            for index in 0..end - start {
+                let gain = lerp(start_gain, self.gain, index as f32 * step);
+                let (sample_left, sample_right) =
+                    (recorded_left[index] * gain, recorded_right[index] * gain);
+                left[index] += sample_left;
+                right[index] += sample_right;
+                peak = peak.max(sample_left.abs()).max(sample_right.abs());
+            }
+        }
+        self.position += length;
+        peak
+    }
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_play_block_follows_rows_and_mutes() {
+        // Sample n is n / 1024 (exact in f32)
+        let samples: Vec<f32> = (0..1000).map(|index| index as f32 / 1024.0).collect();
+        let mut frozen = FrozenChannel::new(samples.clone(), samples, vec![0, 400, 800]);
+
+        // Adds to what is already there
+        let mut left = vec![1.0; 4];
+        let mut right = vec![0.0; 4];
+        frozen.play_block(&mut left, &mut right, true, 48000);
+        assert_eq!(left, [1024.0, 1025.0, 1026.0, 1027.0].map(|n| n / 1024.0));
+        assert_eq!(right, [0.0, 1.0, 2.0, 3.0].map(|n| n / 1024.0));
+
+        // A row start jumps to where that row was recorded
+        frozen.start_row(2);
+        let (mut left, mut right) = (vec![0.0; 4], vec![0.0; 4]);
+        let peak = frozen.play_block(&mut left, &mut right, true, 48000);
+        assert_eq!(left[0], 800.0 / 1024.0);
+        assert_eq!(peak, 803.0 / 1024.0);
+
+        // Past the end it adds nothing and stops playing
+        frozen.start_row(2);
+        let (mut left, mut right) = (vec![0.0; 300], vec![0.0; 300]);
+        frozen.play_block(&mut left, &mut right, true, 48000);
+        assert_eq!(left[199], 999.0 / 1024.0);
+        assert_eq!(left[200], 0.0);
+        assert!(!frozen.is_playing());
+
+        // Muting fades out over a few blocks, then adds nothing
+        frozen.seek(0);
+        for _ in 0..10 {
+            let (mut left, mut right) = (vec![0.0; 64], vec![0.0; 64]);
+            frozen.play_block(&mut left, &mut right, false, 48000);
+        }
+        let (mut left, mut right) = (vec![0.0; 64], vec![0.0; 64]);
+        assert_eq!(frozen.play_block(&mut left, &mut right, false, 48000), 0.0);
+    }
+}
@@ -0,0 +1,305 @@
+// ============================================================================
+// FUZZ_TESTS.RS - Property Tests for the Parser
+// ============================================================================
+//
+// The parser promises never to fail: anything it doesn't understand is
+// skipped and reported. These tests hold it to that by feeding it lots of
+// generated text and checking that every input still gives a well-formed
+// SongData (and a song the engine can play) without panicking, and that
+// streaming the text (SongStream) reads the same rows as parse_song.
+//
+// HOW IT WORKS:
+// Two kinds of input, both from a seeded random number generator so a
+// failure always repeats:
+//
+//   1. Token soup: pieces of song syntax (notes, effect prefixes,
+//      directives, separators, odd numbers) glued together at random,
+//      with a few random Unicode characters mixed in
+//   2. Mutated songs: a slice of one of the example songs with characters
+//      deleted, replaced, or inserted
+//
+// A failing case prints its seed and the input text. For longer runs on
+// arbitrary bytes, see the cargo-fuzz targets in fuzz/.
+// ============================================================================
+
+use crate::helper::{A4_FREQUENCY_HZ, FrequencyTable, RandomNumberGenerator};
+use crate::parser::{
+    AUTO_CHANNEL_COUNT, MissingCellBehavior, SongData, SongStream, parse_cell_text, parse_song,
+    parse_song_collection,
+};
+use crate::{Engine, Song};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+/// Generated inputs per test (each is parsed several ways)
+const CASES: u32 = 1500;
+
+/// Every few cases, the song is also played for a moment
+const RENDER_EVERY: u32 = 25;
+
+/// Pieces of song syntax the token soup is made of
+const FRAGMENTS: &[&str] = &[
+    "c4",
+    "c#4",
+    "db",
+    "e-1",
+    "g20",
+    "x",
+    "sine",
+    "noise",
+    "trisaw",
+    "square",
+    "pulse",
+    " ",
+    " ",
+    ",",
+    ",",
+    "\n",
+    "\n",
+    ":",
+    "'",
+    ">",
+    "a:",
+    "p:",
+    "v:",
+    "t:",
+    "b:",
+    "d:",
+    "env:",
+    "ch:",
+    "q:",
+    "tp:",
+    "seed:",
+    "chain:",
+    "cl",
+    "-",
+    ".",
+    "master",
+    "group",
+    "drums",
+    "[",
+    "]",
+    "[groups]",
+    "[config]",
+    "[channels]",
+    "[tuning]",
+    "[scale]",
+    "[macro hat]",
+    "[song a]",
+    "[chain]",
+    "[instruments]",
+    "=",
+    "@hat",
+    "@",
+    "(",
+    ")",
+    "$1",
+    "config",
+    "tick:",
+    "bpm:",
+    "rv:",
+    "rv2:",
+    "dl:",
+    "off",
+    "wet:",
+    "19edo",
+    "c major",
+    "missing",
+    "attack",
+    "release",
+    "0",
+    "1",
+    "-1",
+    "1e30",
+    "-1e30",
+    "nan",
+    "inf",
+    "0.5",
+    "99999999999",
+    "//",
+    "#",
+    "hz",
+    "440hz",
+    "cents",
+    "3/2",
+    "\t",
+    "\r",
+];
+
+/// The example songs, used as a starting point for mutations
+const EXAMPLE_SONGS: &[&str] = &[
+    include_str!("../../assets/song.csv"),
+    include_str!("../../assets/test_demo.csv"),
+    include_str!("../../assets/golden/effects_tour.csv"),
+];
+
+// ============================================================================
+// INPUT GENERATORS
+// ============================================================================
+
+/// Picks a number below `limit`
+fn below(random: &mut RandomNumberGenerator, limit: usize) -> usize {
+    random.next_u32() as usize % limit.max(1)
+}
+
+/// A random character, usually from outside ASCII
+fn random_character(random: &mut RandomNumberGenerator) -> char {
+    char::from_u32(random.next_u32() % 0x3000).unwrap_or('?')
+}
+
+/// Random pieces of song syntax joined together
+fn token_soup(random: &mut RandomNumberGenerator) -> String {
+    let mut text = String::new();
+    for _ in 0..below(random, 80) {
+        if below(random, 8) == 0 {
+            text.push(random_character(random));
+        } else {
+            text.push_str(FRAGMENTS[below(random, FRAGMENTS.len())]);
+        }
+    }
+    text
+}
+
+/// A slice of an example song with a few characters changed
+fn mutated_song(random: &mut RandomNumberGenerator) -> String {
+    let song: Vec<char> = EXAMPLE_SONGS[below(random, EXAMPLE_SONGS.len())]
+        .chars()
+        .collect();
+    let start = below(random, song.len());
+    let end = (start + 200 + below(random, 2000)).min(song.len());
+    let mut characters = song[start..end].to_vec();
+
+    for _ in 0..below(random, 12) {
+        let position = below(random, characters.len() + 1);
+        match below(random, 3) {
+            0 if position < characters.len() => {
+                characters.remove(position);
+            }
+            1 if position < characters.len() => characters[position] = random_character(random),
+            _ => {
+                let fragment = FRAGMENTS[below(random, FRAGMENTS.len())];
+                for (offset, character) in fragment.chars().enumerate() {
+                    characters.insert(position + offset, character);
+                }
+            }
+        }
+    }
+    characters.into_iter().collect()
+}
+
+// ============================================================================
+// PROPERTIES
+// ============================================================================
+
+/// Checks the shape every parsed song must have
+fn check_song_shape(song: &SongData, expected_width: Option<usize>) -> Result<(), String> {
+    let width = song.channel_count();
+    if let Some(expected) = expected_width
+        && !song.rows.is_empty()
+        && width != expected
+    {
+        return Err(format!("{} channels, expected {}", width, expected));
+    }
+    if let Some(row) = song.rows.iter().position(|row| row.len() != width) {
+        return Err(format!("row {} is not {} cells wide", row, width));
+    }
+    if song.raw_lines.len() != song.rows.len() || song.row_line_numbers.len() != song.rows.len() {
+        return Err("raw lines or line numbers don't match the rows".to_string());
+    }
+    if !song.rows.is_empty() && song.channel_names.len() != width {
+        return Err(format!(
+            "{} channel names for {} channels",
+            song.channel_names.len(),
+            width
+        ));
+    }
+    Ok(())
+}
+
+/// Parses one input every way the tracker does, and maybe plays it
+fn check_input(text: &str, frequency_table: &FrequencyTable, render: bool) -> Result<(), String> {
+    let collection = parse_song_collection(
+        text,
+        frequency_table,
+        A4_FREQUENCY_HZ,
+        AUTO_CHANNEL_COUNT,
+        MissingCellBehavior::SlowRelease,
+    );
+    let selected = collection.select(&collection.default_selection())?;
+    check_song_shape(&selected, None)?;
+
+    let fixed_width = parse_song(
+        text,
+        frequency_table,
+        A4_FREQUENCY_HZ,
+        4,
+        MissingCellBehavior::Sustain,
+    );
+    check_song_shape(&fixed_width, Some(4))?;
+
+    // Streaming the same text gives the same rows
+    let streamed: Vec<String> = SongStream::new(
+        text.as_bytes(),
+        frequency_table,
+        A4_FREQUENCY_HZ,
+        4,
+        MissingCellBehavior::Sustain,
+    )
+    .map(|row| format!("{:?}", row))
+    .collect();
+    let whole: Vec<String> = fixed_width
+        .rows
+        .iter()
+        .map(|row| format!("{:?}", row))
+        .collect();
+    if streamed != whole {
+        return Err("streamed rows differ from parse_song".to_string());
+    }
+
+    for line in text.lines() {
+        parse_cell_text(line, frequency_table, A4_FREQUENCY_HZ);
+    }
+
+    if render {
+        let mut engine = Engine::new(Song::parse(text, AUTO_CHANNEL_COUNT), 8000);
+        let mut buffer = vec![0.0; 2 * 800];
+        engine.render_into(&mut buffer);
+        if buffer.iter().any(|sample| !sample.is_finite()) {
+            return Err("rendered a sample that is not a number".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Runs CASES generated inputs through check_input
+fn run_cases(seed: u32, generate: fn(&mut RandomNumberGenerator) -> String) {
+    let frequency_table = FrequencyTable::new();
+    let mut random = RandomNumberGenerator::new(seed);
+    for case in 0..CASES {
+        let text = generate(&mut random);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            check_input(&text, &frequency_table, case % RENDER_EVERY == 0)
+        }));
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(message)) => panic!(
+                "seed {} case {}: {}\ninput: {:?}",
+                seed, case, message, text
+            ),
+            Err(_) => panic!("seed {} case {} panicked\ninput: {:?}", seed, case, text),
+        }
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[test]
+fn test_token_soup_always_parses() {
+    run_cases(4350, token_soup);
+}
+
+#[test]
+fn test_mutated_songs_always_parse() {
+    run_cases(4351, mutated_song);
+}
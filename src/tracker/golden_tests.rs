@@ -0,0 +1,251 @@
+// ============================================================================
+// GOLDEN_TESTS.RS - Golden-Render Regression Tests
+// ============================================================================
+//
+// Renders known songs offline and compares them against statistics saved
+// in assets/golden/. If a change to the synth, effects, or engine changes
+// what a song sounds like, one of these tests fails and says where.
+//
+// HOW IT WORKS:
+// Each song is rendered to a buffer at a fixed sample rate. The noise
+// generators are seeded from the channel number, so every render of the
+// same song gives the same samples. The buffer is cut into half-second
+// windows, and each window is summarized as left/right RMS and peak.
+// Those numbers are compared against the golden file with a small
+// tolerance, so tiny floating-point differences between CPUs pass while
+// real changes to the sound fail.
+//
+// UPDATING THE GOLDEN FILES:
+// When a sound change is intended, regenerate the files and commit them
+// along with the change:
+//
+//   UPDATE_GOLDEN=1 cargo test --lib golden
+//
+// Look at the diff of assets/golden/*.golden before committing: it shows
+// which parts of which songs changed.
+// ============================================================================
+
+use crate::{Engine, Song};
+use std::fs;
+use std::path::PathBuf;
+
+/// Sample rate for golden renders (low, to keep the tests quick)
+const GOLDEN_SAMPLE_RATE: u32 = 16000;
+
+/// Length of one statistics window in seconds
+const WINDOW_SECONDS: f32 = 0.5;
+
+/// Largest allowed difference for any statistic
+const TOLERANCE: f32 = 1e-4;
+
+/// Channels the songs are parsed for (the tracker's default)
+const CHANNEL_COUNT: usize = 12;
+
+// ============================================================================
+// WINDOW STATISTICS
+// ============================================================================
+
+/// Loudness summary of one window of the render
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct WindowStats {
+    left_rms: f32,
+    right_rms: f32,
+    left_peak: f32,
+    right_peak: f32,
+}
+
+impl WindowStats {
+    /// Summarizes interleaved stereo samples (L R L R ...)
+    fn measure(samples: &[f32]) -> Self {
+        let mut left_sum = 0.0f64;
+        let mut right_sum = 0.0f64;
+        let mut left_peak = 0.0f32;
+        let mut right_peak = 0.0f32;
+
+        for frame in samples.chunks_exact(2) {
+            left_sum += (frame[0] as f64).powi(2);
+            right_sum += (frame[1] as f64).powi(2);
+            left_peak = left_peak.max(frame[0].abs());
+            right_peak = right_peak.max(frame[1].abs());
+        }
+
+        let frame_count = (samples.len() / 2).max(1) as f64;
+        Self {
+            left_rms: (left_sum / frame_count).sqrt() as f32,
+            right_rms: (right_sum / frame_count).sqrt() as f32,
+            left_peak,
+            right_peak,
+        }
+    }
+
+    fn values(&self) -> [f32; 4] {
+        [
+            self.left_rms,
+            self.right_rms,
+            self.left_peak,
+            self.right_peak,
+        ]
+    }
+}
+
+/// Renders a song and summarizes every window
+fn render_song_stats(song_text: &str) -> Vec<WindowStats> {
+    let song = Song::parse(song_text, CHANNEL_COUNT);
+    let mut engine = Engine::new(song, GOLDEN_SAMPLE_RATE);
+    let buffer = engine.playback_engine().render_to_buffer();
+
+    let window_samples = (WINDOW_SECONDS * GOLDEN_SAMPLE_RATE as f32) as usize * 2;
+    buffer
+        .chunks(window_samples)
+        .map(WindowStats::measure)
+        .collect()
+}
+
+// ============================================================================
+// GOLDEN FILES
+// ============================================================================
+
+/// Path of a file in the assets folder
+fn asset_path(relative_path: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join(relative_path)
+}
+
+/// Writes statistics in the golden file format: one window per line
+fn format_golden(song_path: &str, stats: &[WindowStats]) -> String {
+    let mut text = format!(
+        "# Golden render of assets/{} at {} Hz, {} s windows\n\
+         # Regenerate with: UPDATE_GOLDEN=1 cargo test --lib golden\n\
+         # window_start_seconds left_rms right_rms left_peak right_peak\n",
+        song_path, GOLDEN_SAMPLE_RATE, WINDOW_SECONDS
+    );
+    for (index, window) in stats.iter().enumerate() {
+        text.push_str(&format!(
+            "{:.1} {:.6} {:.6} {:.6} {:.6}\n",
+            index as f32 * WINDOW_SECONDS,
+            window.left_rms,
+            window.right_rms,
+            window.left_peak,
+            window.right_peak
+        ));
+    }
+    text
+}
+
+/// Reads the statistics back from a golden file
+fn parse_golden(text: &str) -> Vec<WindowStats> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let values: Vec<f32> = line
+                .split_whitespace()
+                .skip(1)
+                .map(|value| value.parse().expect("golden values are numbers"))
+                .collect();
+            assert_eq!(values.len(), 4, "malformed golden line: {}", line);
+            WindowStats {
+                left_rms: values[0],
+                right_rms: values[1],
+                left_peak: values[2],
+                right_peak: values[3],
+            }
+        })
+        .collect()
+}
+
+/// Renders a song and checks it against its golden file
+/// (or rewrites the golden file when UPDATE_GOLDEN is set)
+fn check_golden(song_path: &str, golden_name: &str) {
+    let song_text = fs::read_to_string(asset_path(song_path))
+        .unwrap_or_else(|e| panic!("failed to read assets/{}: {}", song_path, e));
+    let stats = render_song_stats(&song_text);
+
+    let golden_path = asset_path(&format!("golden/{}.golden", golden_name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&golden_path, format_golden(song_path, &stats))
+            .unwrap_or_else(|e| panic!("failed to write {}: {}", golden_path.display(), e));
+        return;
+    }
+
+    let golden_text = fs::read_to_string(&golden_path).unwrap_or_else(|e| {
+        panic!(
+            "failed to read {} ({}). Create it with: UPDATE_GOLDEN=1 cargo test --lib golden",
+            golden_path.display(),
+            e
+        )
+    });
+    let expected = parse_golden(&golden_text);
+
+    assert_eq!(
+        stats.len(),
+        expected.len(),
+        "assets/{} rendered {} windows, golden file has {} (song length changed?)",
+        song_path,
+        stats.len(),
+        expected.len()
+    );
+
+    const NAMES: [&str; 4] = ["left RMS", "right RMS", "left peak", "right peak"];
+    for (index, (actual, wanted)) in stats.iter().zip(&expected).enumerate() {
+        for ((name, actual_value), wanted_value) in
+            NAMES.iter().zip(actual.values()).zip(wanted.values())
+        {
+            assert!(
+                (actual_value - wanted_value).abs() <= TOLERANCE,
+                "assets/{} differs from its golden render at {:.1} s: {} is {:.6}, expected {:.6}\n\
+                 If the change is intended, run: UPDATE_GOLDEN=1 cargo test --lib golden",
+                song_path,
+                index as f32 * WINDOW_SECONDS,
+                name,
+                actual_value,
+                wanted_value
+            );
+        }
+    }
+}
+
+// ============================================================================
+// GOLDEN SONGS
+// ============================================================================
+
+#[test]
+fn golden_example_song() {
+    check_golden("song.csv", "song");
+}
+
+#[test]
+fn golden_feature_demo() {
+    check_golden("test_demo.csv", "test_demo");
+}
+
+#[test]
+fn golden_effects_tour() {
+    // The tour exists to cover every effect, so it must parse cleanly
+    let text = fs::read_to_string(asset_path("golden/effects_tour.csv")).unwrap();
+    let song = Song::parse(&text, CHANNEL_COUNT);
+    assert!(
+        song.diagnostics().is_empty(),
+        "effects_tour.csv has parse messages"
+    );
+
+    check_golden("golden/effects_tour.csv", "effects_tour");
+}
+
+#[test]
+fn test_renders_are_repeatable() {
+    let text = fs::read_to_string(asset_path("golden/effects_tour.csv")).unwrap();
+    assert_eq!(render_song_stats(&text), render_song_stats(&text));
+}
+
+#[test]
+fn test_golden_format_round_trip() {
+    let stats = vec![WindowStats {
+        left_rms: 0.25,
+        right_rms: 0.125,
+        left_peak: 0.5,
+        right_peak: 0.75,
+    }];
+    assert_eq!(parse_golden(&format_golden("x.csv", &stats)), stats);
+}
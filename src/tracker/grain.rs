@@ -0,0 +1,380 @@
+// ============================================================================
+// GRAIN.RS - Granular Instrument and Its Sample Bank
+// ============================================================================
+//
+// The grain instrument turns a recorded sound into a texture: it plays
+// many short, overlapping snippets ("grains") of a WAV file at once.
+//
+//   [sample] sounds/choir.wav
+//   Voice0
+//   c4 grain:0.08'20'0.3'0.1
+//
+// The parameters are grain size (seconds), density (new grains per
+// second), position (where in the file grains start, 0.0 to 1.0), and
+// spray (how far each grain's start may wander from the position, as a
+// fraction of the file). The note sets the playback speed: c4 plays the
+// file at its recorded speed, c5 an octave higher.
+//
+// HOW IT WORKS:
+// Every 1/density seconds a grain starts at the position plus a random
+// offset (the spray). Each grain reads the file for its size, shaped by a
+// Hann window so it fades in and out without clicking. All grains playing
+// at a moment are summed, scaled by 1/sqrt(overlap) so dense clouds don't
+// get much louder than sparse ones. Grains run past the end of the file
+// wrap to its start.
+//
+// Other instruments are plain functions of the phase; grains need memory
+// (which grains are playing, and how far along). So the channel keeps a
+// GrainCloud and renders it itself when its instrument is the grain
+// instrument (see Channel::generate_instrument).
+//
+// SWEEPING THE POSITION:
+// A glide ("c4 grain:0.08'20'0.9 tr:4") moves the cloud's position from
+// where it is to the new value over the transition, so grains drift
+// through the file instead of jumping.
+//
+// THE SAMPLE BANK:
+// "[sample] file.wav" loads the file into a bank shared by the whole
+// program, and grain notes below it carry the file's number in the bank
+// as their first parameter (the parser puts it there). A file is read
+// once per program and kept, so reloading a song (the editor does this
+// often) never reads it again; the audio thread only looks samples up.
+// ============================================================================
+
+use crate::audio::read_audio_file;
+use crate::helper::RandomNumberGenerator;
+use crate::simd::fast_sine;
+use std::f32::consts::FRAC_PI_2;
+use std::path::Path;
+use std::sync::RwLock;
+
+/// Most grains one channel plays at once (more are skipped)
+pub const MAX_GRAINS: usize = 32;
+
+/// The note that plays the file at its recorded speed (C4)
+pub const ORIGINAL_PITCH_HZ: f32 = 261.625_58;
+
+// ============================================================================
+// SAMPLE BANK
+// ============================================================================
+
+/// A sound file loaded for the grain instrument
+pub struct GrainSample {
+    /// The path the file was loaded from
+    pub path: String,
+
+    /// The file's samples, mixed down to mono
+    pub samples: Vec<f32>,
+
+    /// The file's sample rate in Hz
+    pub sample_rate: u32,
+}
+
+impl std::fmt::Debug for GrainSample {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("GrainSample")
+            .field("path", &self.path)
+            .field("samples", &self.samples.len())
+            .field("sample_rate", &self.sample_rate)
+            .finish()
+    }
+}
+
+/// Every loaded file, in loading order (the index is its number)
+///
+/// Samples are leaked so the audio thread can hold on to them without
+/// ever freeing one (a file is loaded once per program).
+static SAMPLE_BANK: RwLock<Vec<&'static GrainSample>> = RwLock::new(Vec::new());
+
+/// Loads a WAV file into the bank and returns its number
+/// A path loaded before gives the same number without reading it again.
+pub fn load_sample(path: &str) -> Result<usize, String> {
+    if let Some(index) = SAMPLE_BANK
+        .read()
+        .unwrap_or_else(|error| error.into_inner())
+        .iter()
+        .position(|sample| sample.path == path)
+    {
+        return Ok(index);
+    }
+
+    let (samples, sample_rate) = read_audio_file(Path::new(path))?;
+    if samples.is_empty() {
+        return Err(format!("'{}' has no samples", path));
+    }
+    let sample: &'static GrainSample = Box::leak(Box::new(GrainSample {
+        path: path.to_string(),
+        samples,
+        sample_rate,
+    }));
+
+    let mut bank = SAMPLE_BANK
+        .write()
+        .unwrap_or_else(|error| error.into_inner());
+    bank.push(sample);
+    Ok(bank.len() - 1)
+}
+
+/// Gets a loaded sample by its number
+pub fn get_sample(index: usize) -> Option<&'static GrainSample> {
+    SAMPLE_BANK
+        .read()
+        .unwrap_or_else(|error| error.into_inner())
+        .get(index)
+        .copied()
+}
+
+// ============================================================================
+// GRAIN SETTINGS
+// ============================================================================
+
+/// The grain instrument's parameters, with defaults filled in
+/// Parameter order: sample number, size, density, position, spray.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GrainSettings {
+    /// Which sample in the bank to read
+    pub sample_index: usize,
+
+    /// Length of each grain in seconds (0.005 to 1.0, default 0.08)
+    pub size_seconds: f32,
+
+    /// New grains per second (1 to 200, default 20)
+    pub density: f32,
+
+    /// Where grains start in the file (0.0 to 1.0, default 0.0)
+    pub position: f32,
+
+    /// How far a grain's start may wander, as a fraction of the file
+    /// (0.0 to 1.0, default 0.05)
+    pub spray: f32,
+}
+
+impl GrainSettings {
+    /// Reads the settings from an instrument parameter list
+    pub fn from_parameters(params: &[f32]) -> Self {
+        let value = |index: usize, default: f32, low: f32, high: f32| {
+            params
+                .get(index)
+                .map_or(default, |value| value.clamp(low, high))
+        };
+        Self {
+            sample_index: params.first().map_or(0, |index| index.max(0.0) as usize),
+            size_seconds: value(1, 0.08, 0.005, 1.0),
+            density: value(2, 20.0, 1.0, 200.0),
+            position: value(3, 0.0, 0.0, 1.0),
+            spray: value(4, 0.05, 0.0, 1.0),
+        }
+    }
+}
+
+// ============================================================================
+// GRAIN CLOUD
+// ============================================================================
+
+/// One grain playing
+#[derive(Clone, Copy, Debug, Default)]
+struct Grain {
+    active: bool,
+
+    /// Where in the sample the grain is reading (in samples)
+    read_position: f64,
+
+    /// Samples played so far
+    age: u32,
+
+    /// Samples the grain lasts
+    length: u32,
+}
+
+/// The grains one channel is playing (see the top of the file)
+#[derive(Clone, Debug)]
+pub struct GrainCloud {
+    /// The sample grains read from (None = silent)
+    sample: Option<&'static GrainSample>,
+
+    /// Fixed slots, so starting grains never allocates
+    grains: [Grain; MAX_GRAINS],
+
+    /// Output samples until the next grain starts
+    samples_to_next_grain: f32,
+
+    /// Where new grains start (0.0 to 1.0 of the file)
+    position: f32,
+
+    /// Position change per output sample while a glide runs
+    position_step: f32,
+
+    /// Output samples left in the glide
+    glide_samples_left: u32,
+}
+
+impl Default for GrainCloud {
+    fn default() -> Self {
+        Self {
+            sample: None,
+            grains: [Grain::default(); MAX_GRAINS],
+            samples_to_next_grain: 0.0,
+            position: 0.0,
+            position_step: 0.0,
+            glide_samples_left: 0,
+        }
+    }
+}
+
+impl GrainCloud {
+    /// Starts a fresh cloud for a new note (no grains left over)
+    pub fn start(&mut self, params: &[f32]) {
+        let settings = GrainSettings::from_parameters(params);
+        self.sample = get_sample(settings.sample_index);
+        for grain in &mut self.grains {
+            grain.active = false;
+        }
+        self.samples_to_next_grain = 0.0;
+        self.position = settings.position;
+        self.glide_samples_left = 0;
+    }
+
+    /// Moves the position to the new parameters' over a transition,
+    /// keeping the grains that are playing
+    pub fn glide(&mut self, params: &[f32], transition_seconds: f32, sample_rate: u32) {
+        if self.sample.is_none() {
+            self.start(params);
+            return;
+        }
+        let settings = GrainSettings::from_parameters(params);
+        self.sample = get_sample(settings.sample_index);
+        let glide_samples = (transition_seconds * sample_rate as f32) as u32;
+        if glide_samples == 0 {
+            self.position = settings.position;
+            self.glide_samples_left = 0;
+        } else {
+            self.position_step = (settings.position - self.position) / glide_samples as f32;
+            self.glide_samples_left = glide_samples;
+        }
+    }
+
+    /// Renders the cloud into `output`
+    /// `frequency_hz` is the note's pitch; ORIGINAL_PITCH_HZ plays the
+    /// file at its recorded speed.
+    pub fn render(
+        &mut self,
+        params: &[f32],
+        frequency_hz: f32,
+        sample_rate: u32,
+        rng: &mut RandomNumberGenerator,
+        output: &mut [f32],
+    ) {
+        let Some(sample) = self.sample else {
+            output.fill(0.0);
+            return;
+        };
+        let settings = GrainSettings::from_parameters(params);
+        let sample_length = sample.samples.len() as f64;
+        let speed = (frequency_hz / ORIGINAL_PITCH_HZ) as f64 * sample.sample_rate as f64
+            / sample_rate as f64;
+        let grain_length = ((settings.size_seconds * sample_rate as f32) as u32).max(1);
+        let grain_interval = sample_rate as f32 / settings.density;
+        let overlap = settings.size_seconds * settings.density;
+        let gain = 1.0 / overlap.max(1.0).sqrt();
+
+        for output_sample in output.iter_mut() {
+            if self.glide_samples_left > 0 {
+                self.position = (self.position + self.position_step).clamp(0.0, 1.0);
+                self.glide_samples_left -= 1;
+            }
+
+            // ---- START A GRAIN ----
+            self.samples_to_next_grain -= 1.0;
+            if self.samples_to_next_grain <= 0.0 {
+                self.samples_to_next_grain += grain_interval;
+                if let Some(grain) = self.grains.iter_mut().find(|grain| !grain.active) {
+                    let start =
+                        (self.position + settings.spray * rng.next_float_bipolar()).rem_euclid(1.0);
+                    *grain = Grain {
+                        active: true,
+                        read_position: start as f64 * sample_length,
+                        age: 0,
+                        length: grain_length,
+                    };
+                }
+            }
+
+            // ---- SUM THE GRAINS ----
+            let mut sum = 0.0;
+            for grain in self.grains.iter_mut().filter(|grain| grain.active) {
+                // Hann window: 0.5 - 0.5 cos(2 PI t)
+                let progress = grain.age as f32 / grain.length as f32;
+                let window = 0.5 - 0.5 * fast_sine(std::f32::consts::TAU * progress + FRAC_PI_2);
+                sum += window * read_interpolated(&sample.samples, grain.read_position);
+
+                grain.read_position = (grain.read_position + speed).rem_euclid(sample_length);
+                grain.age += 1;
+                if grain.age >= grain.length {
+                    grain.active = false;
+                }
+            }
+            *output_sample = sum * gain;
+        }
+    }
+}
+
+/// Reads a sample between two stored ones (linear interpolation, wrapping
+/// from the last sample back to the first)
+fn read_interpolated(samples: &[f32], position: f64) -> f32 {
+    let index = position as usize % samples.len();
+    let next = (index + 1) % samples.len();
+    let fraction = position.fract() as f32;
+    samples[index] + (samples[next] - samples[index]) * fraction
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::write_wav_file;
+
+    #[test]
+    fn test_grain_cloud() {
+        // A 100 ms file of a steady level, one channel written twice
+        let path = std::env::temp_dir().join("grain_test.wav");
+        write_wav_file(&path, &[0.5; 2 * 4800], 48000, true, false).unwrap();
+        let index = load_sample(path.to_str().unwrap()).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        // Loading the same path again reuses the sample
+        assert_eq!(load_sample(path.to_str().unwrap()), Ok(index));
+        assert_eq!(get_sample(index).unwrap().samples.len(), 4800);
+
+        // 10 ms grains, 100 per second: exactly one grain at a time, so
+        // the output is the file's level under each grain's window
+        let params = [index as f32, 0.01, 100.0, 0.5, 0.0];
+        let mut cloud = GrainCloud::default();
+        cloud.start(&params);
+        let mut output = vec![0.0; 960];
+        cloud.render(
+            &params,
+            ORIGINAL_PITCH_HZ,
+            48000,
+            &mut RandomNumberGenerator::new(1),
+            &mut output,
+        );
+        assert!(output.iter().all(|sample| (0.0..=0.5).contains(sample)));
+        assert!((output[240] - 0.5).abs() < 1e-3); // the middle of the first grain
+        assert!(output[480] < 1e-3); // between grains
+
+        // A glide moves the position over the transition
+        cloud.glide(&[index as f32, 0.01, 100.0, 1.0, 0.0], 0.01, 48000);
+        cloud.render(
+            &params,
+            ORIGINAL_PITCH_HZ,
+            48000,
+            &mut RandomNumberGenerator::new(1),
+            &mut output[..240],
+        );
+        assert!((cloud.position - 0.75).abs() < 1e-3);
+    }
+}
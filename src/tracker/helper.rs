@@ -246,11 +246,24 @@ impl RandomNumberGenerator {
         }
     }
 
+    /// The generator's current state (for engine snapshots)
+    pub fn state(&self) -> u32 {
+        self.state
+    }
+
     /// Creates a new generator seeded from a channel ID
     /// This ensures each channel has a unique random sequence
     pub fn from_channel_id(channel_id: usize) -> Self {
-        // Use a hash-like transformation to get a good starting state
-        let seed = (channel_id as u32)
+        Self::for_channel(0, channel_id)
+    }
+
+    /// Creates the generator for one channel of a song with a seed
+    /// Each channel still gets its own sequence, and seed 0 gives the same
+    /// sequences as from_channel_id
+    pub fn for_channel(song_seed: u32, channel_id: usize) -> Self {
+        // Spread the song seed over all bits so nearby seeds differ a lot,
+        // then use a hash-like transformation to get a good starting state
+        let seed = (channel_id as u32 ^ song_seed.wrapping_mul(0x9E37_79B9))
             .wrapping_mul(1103515245)
             .wrapping_add(12345);
         Self::new(seed)
@@ -306,18 +319,110 @@ pub fn note_letter_to_semitone(note_char: char) -> Option<i32> {
     }
 }
 
-/// Parses a pitch string like "C4", "F#3", "Bb5" and returns the frequency
-/// This function uses the pre-computed frequency table for speed
-///
-/// Parameters:
-/// - pitch_string: The note name (e.g., "C4", "f#3", "Bb5")
-/// - frequency_table: Reference to the pre-computed frequency table
+/// Converts a detune amount in cents to a frequency multiplier
+/// 100 cents = 1 semitone, 1200 cents = 1 octave
 ///
-/// Returns: The frequency in Hz, or None if the pitch string is invalid
-pub fn parse_pitch_to_frequency(
-    pitch_string: &str,
-    frequency_table: &FrequencyTable,
-) -> Option<f32> {
+/// Formula: ratio = 2^(cents / 1200)
+#[inline]
+pub fn cents_to_frequency_ratio(cents: f32) -> f32 {
+    2.0_f32.powf(cents / 1200.0)
+}
+
+/// Splits an optional cents suffix off the octave part of a pitch
+/// "4+25c" -> ("4", 25.0), "4-12c" -> ("4", -12.0), "4" -> ("4", 0.0)
+/// The trailing 'c' is optional. Returns None if the cents value is not a number
+/// or is more than an octave either way (like `dt:`, cents stop at +-1200)
+fn split_cents_suffix(octave_and_cents: &str) -> Option<(&str, f32)> {
+    // The octave is never negative, so any sign marks the start of the cents
+    match octave_and_cents.find(['+', '-']) {
+        Some(sign_position) => {
+            let octave_part = &octave_and_cents[..sign_position];
+            let cents_part = &octave_and_cents[sign_position..];
+            let cents_number = cents_part.strip_suffix('c').unwrap_or(cents_part);
+            let cents: f32 = cents_number.parse().ok()?;
+            // Also rejects inf and NaN, which would turn the note into NaN audio
+            if !(-1200.0..=1200.0).contains(&cents) {
+                return None;
+            }
+            Some((octave_part, cents))
+        }
+        None => Some((octave_and_cents, 0.0)),
+    }
+}
+
+/// The parts of a written note name like "F#3+25c", before any tuning is applied
+/// Keeping the letter and accidental separate lets tunings other than 12-TET
+/// tell C# and Db apart
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PitchName {
+    /// The note letter, lowercased ('a' to 'g')
+    pub letter: char,
+
+    /// +1 for sharp, -1 for flat, 0 for natural
+    pub accidental: i32,
+
+    /// The octave number as written (C4 = middle C)
+    pub octave: i32,
+
+    /// Extra offset in cents (0.0 if none was written)
+    pub cents: f32,
+}
+
+impl PitchName {
+    /// Returns the note's position in semitones above C0, using standard
+    /// 12-tone naming (C4 = 48, A4 = 57). The cents offset is not included.
+    pub fn semitones_from_c0(&self) -> i32 {
+        let base_semitone = note_letter_to_semitone(self.letter).unwrap_or(0);
+        self.octave * 12 + base_semitone + self.accidental
+    }
+
+    /// Builds a note name from semitones above C0, spelled with sharps
+    /// (e.g., 49 -> C#4). The cents offset is passed through unchanged.
+    pub fn from_semitones_from_c0(semitones: i32, cents: f32) -> Self {
+        const SPELLINGS: [(char, i32); 12] = [
+            ('c', 0),
+            ('c', 1),
+            ('d', 0),
+            ('d', 1),
+            ('e', 0),
+            ('f', 0),
+            ('f', 1),
+            ('g', 0),
+            ('g', 1),
+            ('a', 0),
+            ('a', 1),
+            ('b', 0),
+        ];
+        let (letter, accidental) = SPELLINGS[semitones.rem_euclid(12) as usize];
+
+        Self {
+            letter,
+            accidental,
+            octave: semitones.div_euclid(12),
+            cents,
+        }
+    }
+}
+
+/// Writes the note the way cells spell it: "c#4", "bb3", "c4+25c"
+impl std::fmt::Display for PitchName {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let accidental = match self.accidental {
+            1 => "#",
+            -1 => "b",
+            _ => "",
+        };
+        write!(formatter, "{}{}{}", self.letter, accidental, self.octave)?;
+        if self.cents != 0.0 {
+            write!(formatter, "{:+}c", self.cents)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a note name like "C4", "f#3", "Bb5" or "c4+25c" into its parts
+/// Returns None if the text is not a valid note name
+pub fn parse_pitch_name(pitch_string: &str) -> Option<PitchName> {
     let pitch_lower = pitch_string.to_lowercase();
     let chars: Vec<char> = pitch_lower.chars().collect();
 
@@ -327,16 +432,16 @@ pub fn parse_pitch_to_frequency(
 
     // First character must be a note letter (a-g)
     let note_char = chars[0];
-    let base_semitone = note_letter_to_semitone(note_char)?;
+    note_letter_to_semitone(note_char)?;
 
     let mut char_index = 1;
 
     // Check for sharp (#) or flat (b) modifier
-    let mut semitone_offset = 0;
+    let mut accidental = 0;
     if char_index < chars.len() {
         match chars[char_index] {
             '#' => {
-                semitone_offset = 1;
+                accidental = 1;
                 char_index += 1;
             }
             'b' => {
@@ -344,7 +449,7 @@ pub fn parse_pitch_to_frequency(
                 // If the next character is a digit, this 'b' could be ambiguous
                 // But since we lowercased, 'b' followed by digit means flat
                 if char_index + 1 < chars.len() || chars.len() == 2 {
-                    semitone_offset = -1;
+                    accidental = -1;
                     char_index += 1;
                 }
             }
@@ -352,18 +457,31 @@ pub fn parse_pitch_to_frequency(
         }
     }
 
-    // Parse the octave number
+    // Parse the octave number (and optional cents offset like "+25c")
     let octave_str: String = chars[char_index..].iter().collect();
-    let octave: i32 = octave_str.parse().ok()?;
+    let (octave_only, cents) = split_cents_suffix(&octave_str)?;
+    let octave: i32 = octave_only.parse().ok()?;
+
+    Some(PitchName {
+        letter: note_char,
+        accidental,
+        octave,
+        cents,
+    })
+}
 
+/// Looks up the standard (12-TET, A4 = 440 Hz) frequency of a parsed note name
+/// Returns None if the octave is outside the frequency table
+pub fn pitch_name_to_frequency(pitch: &PitchName, frequency_table: &FrequencyTable) -> Option<f32> {
     // Check if the octave is within our valid range
-    if !(FREQUENCY_TABLE_MIN_OCTAVE..=FREQUENCY_TABLE_MAX_OCTAVE).contains(&octave) {
+    if !(FREQUENCY_TABLE_MIN_OCTAVE..=FREQUENCY_TABLE_MAX_OCTAVE).contains(&pitch.octave) {
         return None;
     }
 
     // Calculate the total semitone within the octave
-    let mut semitone_in_octave = base_semitone + semitone_offset;
-    let mut adjusted_octave = octave;
+    let base_semitone = note_letter_to_semitone(pitch.letter)?;
+    let mut semitone_in_octave = base_semitone + pitch.accidental;
+    let mut adjusted_octave = pitch.octave;
 
     // Handle wrapping (e.g., Cb4 should be B3, B#4 should be C5)
     if semitone_in_octave < 0 {
@@ -374,8 +492,13 @@ pub fn parse_pitch_to_frequency(
         adjusted_octave += 1;
     }
 
-    // Look up the frequency in the table
-    frequency_table.get_frequency(adjusted_octave, semitone_in_octave)
+    // Look up the frequency in the table, then apply the cents offset
+    let frequency = frequency_table.get_frequency(adjusted_octave, semitone_in_octave)?;
+    if pitch.cents != 0.0 {
+        Some(frequency * cents_to_frequency_ratio(pitch.cents))
+    } else {
+        Some(frequency)
+    }
 }
 
 // ============================================================================
@@ -386,14 +509,13 @@ pub fn parse_pitch_to_frequency(
 /// This prevents the phase from growing infinitely large over time
 #[inline]
 pub fn wrap_phase(phase: f32) -> f32 {
-    let mut wrapped = phase;
-    while wrapped >= TWO_PI {
-        wrapped -= TWO_PI;
-    }
-    while wrapped < 0.0 {
-        wrapped += TWO_PI;
+    if (0.0..TWO_PI).contains(&phase) {
+        return phase;
     }
-    wrapped
+    // rem_euclid rather than a subtraction loop, so a huge step can't stall
+    // the audio thread. It can round up to exactly TWO_PI for tiny negatives.
+    let wrapped = phase.rem_euclid(TWO_PI);
+    if wrapped >= TWO_PI { 0.0 } else { wrapped }
 }
 
 /// Calculates the phase increment for a given frequency
@@ -426,6 +548,49 @@ mod tests {
         assert_eq!(lerp(0.0, 10.0, 0.5), 5.0);
     }
 
+    #[test]
+    fn test_parse_pitch_with_cents() {
+        let table = FrequencyTable::new();
+        let frequency = |text: &str| pitch_name_to_frequency(&parse_pitch_name(text)?, &table);
+        let a4 = frequency("a4").unwrap();
+        let a4_up = frequency("a4+100c").unwrap();
+        let a4_down = frequency("a4-12c").unwrap();
+        let a_sharp4 = frequency("a#4").unwrap();
+
+        assert!((a4_up - a_sharp4).abs() < 0.01); // +100 cents = one semitone
+        assert!(a4_down < a4);
+        assert!((frequency("a4+25").unwrap() - a4).abs() > 0.1);
+        assert_eq!(frequency("a4+xc"), None);
+
+        // Cents stop at an octave, and inf or NaN never make a note
+        assert!(frequency("a4+1200c").is_some());
+        assert_eq!(frequency("c4+30000c"), None);
+        assert_eq!(frequency("c4+infc"), None);
+        assert_eq!(frequency("c4+NaNc"), None);
+    }
+
+    #[test]
+    fn test_wrap_phase() {
+        assert_eq!(wrap_phase(1.0), 1.0);
+        assert!((wrap_phase(TWO_PI + 1.0) - 1.0).abs() < 1e-5);
+        assert!((wrap_phase(-1.0) - (TWO_PI - 1.0)).abs() < 1e-5);
+        assert!((0.0..TWO_PI).contains(&wrap_phase(-1e-9)));
+        // A huge step wraps straight away instead of looping
+        assert!((0.0..TWO_PI).contains(&wrap_phase(1.0e12)));
+    }
+
+    #[test]
+    fn test_pitch_name_display_round_trip() {
+        for text in ["c4", "c#4", "bb3", "a4+25c", "f#2-10c"] {
+            let pitch = parse_pitch_name(text).unwrap();
+            assert_eq!(pitch.to_string(), text);
+        }
+        assert_eq!(
+            PitchName::from_semitones_from_c0(49, 0.0).to_string(),
+            "c#4"
+        );
+    }
+
     #[test]
     fn test_note_letter_to_semitone() {
         assert_eq!(note_letter_to_semitone('C'), Some(0));
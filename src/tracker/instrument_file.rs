@@ -0,0 +1,359 @@
+// ============================================================================
+// INSTRUMENT_FILE.RS - Instrument Definitions Loaded From TOML Files
+// ============================================================================
+//
+// This module lets sound design live in its own file instead of inside the
+// pattern data. A song loads the file with a directive:
+//
+//   [instruments] sounds/my_kit.toml
+//
+// and can then play every instrument defined in it by name ("c4 lead").
+//
+// FILE FORMAT (TOML, read with the toml crate):
+//
+//   # Comments start with '#'
+//   [lead]                       Instrument name
+//   oscillator = "square"        Built-in sound generator to use
+//   parameters = [0.25]          Oscillator parameters (optional)
+//   attack = 0.01                Envelope times in seconds (all optional)
+//   decay = 0.2
+//   sustain = 0.7                Sustain level 0.0 to 1.0
+//   release = 0.4
+//   effects = "v:5'0.3 d:0.2"    Default effects, same syntax as in cells
+//
+//   [pad]
+//   sample = "pad.wav"           WAV file the instrument's grain notes play
+//   parameters = [0.08, 20]      (the oscillator is "grain" when not given)
+//
+// Each instrument becomes a named preset (the same thing an [instruments]
+// block line defines), so cells can override any part of it:
+// "c4 lead a:0.3" plays the lead with a different amplitude.
+//
+// Values are TOML "strings", numbers, and [arrays, of, numbers]; key
+// names are not case sensitive.
+// The instrument file's own path is relative to the working directory,
+// like Scala tuning files; a sample's path is relative to the instrument
+// file, so a file and its samples can be moved together (the FFT
+// analyzer's Export as Instrument writes them that way).
+// ============================================================================
+
+use crate::envelope::ENVELOPE_REGISTRY;
+use crate::instruments::find_instrument_by_name;
+use std::fs;
+use std::path::Path;
+
+// ============================================================================
+// INSTRUMENT DEFINITION
+// ============================================================================
+
+/// One instrument loaded from a file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FileInstrument {
+    /// Name used in cells (lowercased)
+    pub name: String,
+
+    /// Built-in oscillator name (e.g., "square")
+    pub oscillator: Option<String>,
+
+    /// Oscillator parameters (e.g., pulse width)
+    pub parameters: Vec<f32>,
+
+    /// Envelope attack time in seconds
+    pub attack: Option<f32>,
+
+    /// Envelope decay time in seconds
+    pub decay: Option<f32>,
+
+    /// Envelope sustain level
+    pub sustain: Option<f32>,
+
+    /// Envelope release time in seconds
+    pub release: Option<f32>,
+
+    /// Default effect tokens (e.g., "v:5'0.3 d:0.2")
+    pub effects: String,
+
+    /// WAV file the instrument's grain notes play instead of the song's
+    /// [sample] (resolved against the instrument file's folder on loading)
+    pub sample: Option<String>,
+}
+
+impl FileInstrument {
+    /// Converts the instrument into the cell tokens it stands for
+    /// Example: ["square:0.25", "env:0.01'0.2'0.7'0.4", "v:5'0.3"]
+    pub fn to_tokens(&self) -> Vec<String> {
+        let mut tokens = Vec::new();
+
+        // An instrument with a sample and no oscillator plays grains of it
+        let oscillator = self
+            .oscillator
+            .as_deref()
+            .or(self.sample.as_ref().map(|_| "grain"));
+        if let Some(oscillator) = oscillator {
+            if self.parameters.is_empty() {
+                tokens.push(oscillator.to_string());
+            } else {
+                tokens.push(format!(
+                    "{}:{}",
+                    oscillator,
+                    join_parameters(&self.parameters)
+                ));
+            }
+        }
+
+        // Only write an envelope if one of its values was set
+        if self.attack.is_some()
+            || self.decay.is_some()
+            || self.sustain.is_some()
+            || self.release.is_some()
+        {
+            let default_definition = &ENVELOPE_REGISTRY[0];
+            let mut envelope = vec![
+                self.attack
+                    .unwrap_or(default_definition.attack_time_seconds),
+                self.decay.unwrap_or(default_definition.decay_time_seconds),
+                self.sustain.unwrap_or(default_definition.sustain_level),
+            ];
+            if let Some(release) = self.release {
+                envelope.push(release);
+            }
+            tokens.push(format!("env:{}", join_parameters(&envelope)));
+        }
+
+        tokens.extend(self.effects.split_whitespace().map(str::to_string));
+        tokens
+    }
+}
+
+// ============================================================================
+// LOADING
+// ============================================================================
+
+/// Reads and parses an instrument file
+pub fn load_instrument_file(path: &str) -> Result<Vec<FileInstrument>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read instrument file '{}': {}", path, e))?;
+    let mut instruments = parse_instrument_text(&text).map_err(|e| format!("{}: {}", path, e))?;
+
+    // Sample paths are relative to the instrument file
+    let folder = Path::new(path).parent().unwrap_or(Path::new(""));
+    for sample in instruments
+        .iter_mut()
+        .filter_map(|instrument| instrument.sample.as_mut())
+    {
+        if Path::new(sample.as_str()).is_relative() {
+            *sample = folder.join(sample.as_str()).display().to_string();
+        }
+    }
+    Ok(instruments)
+}
+
+/// Parses the contents of an instrument file
+/// Syntax errors name the line number; value errors name the instrument
+pub fn parse_instrument_text(text: &str) -> Result<Vec<FileInstrument>, String> {
+    let document: toml::Table =
+        toml::from_str(text).map_err(|error| toml_error_message(text, &error))?;
+
+    // Tables in file order: [lead] (also accepts [instruments.lead], which
+    // nests every instrument inside one "instruments" table)
+    let mut tables = Vec::new();
+    for (key, value) in document {
+        let toml::Value::Table(table) = value else {
+            return Err(format!(
+                "'{}' must come after an [instrument_name] line",
+                key
+            ));
+        };
+        if key == "instruments" && table.values().all(toml::Value::is_table) {
+            for (name, value) in table {
+                if let toml::Value::Table(table) = value {
+                    tables.push((name, table));
+                }
+            }
+        } else {
+            tables.push((key, table));
+        }
+    }
+
+    let mut instruments: Vec<FileInstrument> = Vec::new();
+    for (name, table) in tables {
+        let name = name.trim().to_lowercase();
+        if name.is_empty() {
+            return Err("empty instrument name".to_string());
+        }
+        if instruments.iter().any(|instrument| instrument.name == name) {
+            return Err(format!("instrument '{}' defined twice", name));
+        }
+        instruments.push(parse_instrument_table(name, &table)?);
+    }
+
+    Ok(instruments)
+}
+
+/// Reads one instrument's keys
+fn parse_instrument_table(name: String, table: &toml::Table) -> Result<FileInstrument, String> {
+    let mut instrument = FileInstrument {
+        name: name.clone(),
+        ..Default::default()
+    };
+
+    for (key, value) in table {
+        let key = key.to_lowercase();
+        let wrong_type = |expected: &str| format!("[{}] '{}' must be {}", name, key, expected);
+        let number = || toml_number(value).ok_or_else(|| wrong_type("a number"));
+        let string = || {
+            value
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| wrong_type("a quoted string"))
+        };
+
+        match key.as_str() {
+            "oscillator" | "instrument" | "wave" => {
+                let oscillator = string()?;
+                match find_instrument_by_name(&oscillator) {
+                    Some(id) if id != 0 => instrument.oscillator = Some(oscillator.to_lowercase()),
+                    _ => {
+                        return Err(format!("[{}] unknown oscillator '{}'", name, oscillator));
+                    }
+                }
+            }
+            "parameters" | "params" => {
+                instrument.parameters = match value {
+                    toml::Value::Array(items) => items.iter().map(toml_number).collect(),
+                    _ => toml_number(value).map(|number| vec![number]),
+                }
+                .ok_or_else(|| wrong_type("a list of numbers"))?;
+            }
+            "attack" => instrument.attack = Some(number()?),
+            "decay" => instrument.decay = Some(number()?),
+            "sustain" => instrument.sustain = Some(number()?),
+            "release" => instrument.release = Some(number()?),
+            "effects" => instrument.effects = string()?,
+            "sample" => {
+                let sample = string()?;
+                if sample.trim().is_empty() {
+                    return Err(format!("[{}] empty sample path", name));
+                }
+                instrument.sample = Some(sample);
+            }
+            _ => {
+                return Err(format!("[{}] unknown key '{}'", name, key));
+            }
+        }
+    }
+
+    Ok(instrument)
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+/// Reads an integer or float as a number
+fn toml_number(value: &toml::Value) -> Option<f32> {
+    match value {
+        toml::Value::Integer(number) => Some(*number as f32),
+        toml::Value::Float(number) => Some(*number as f32),
+        _ => None,
+    }
+}
+
+/// Turns a TOML syntax error into one line: "line 3: <message>"
+fn toml_error_message(text: &str, error: &toml::de::Error) -> String {
+    let message = error.message().trim();
+    match error.span() {
+        Some(span) => {
+            let line_number = text[..span.start.min(text.len())].matches('\n').count() + 1;
+            format!("line {}: {}", line_number, message)
+        }
+        None => message.to_string(),
+    }
+}
+
+/// Joins numbers with the parameter separator used in cells ("0.5'0.3")
+fn join_parameters(values: &[f32]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join("'")
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_instrument_text() {
+        let text = r#"
+# A small kit
+[lead]
+oscillator = "square"   # bright
+parameters = [0.25]
+attack = 0.01
+release = 0.4
+effects = "v:5'0.3 d:0.2"
+
+[instruments.hat]
+oscillator = "noise"
+sustain = 0
+"#;
+        let instruments = parse_instrument_text(text).unwrap();
+        assert_eq!(instruments.len(), 2);
+        assert_eq!(instruments[0].name, "lead");
+        assert_eq!(instruments[1].name, "hat");
+
+        let default_definition = &ENVELOPE_REGISTRY[0];
+        assert_eq!(
+            instruments[0].to_tokens(),
+            vec![
+                "square:0.25".to_string(),
+                format!(
+                    "env:0.01'{}'{}'0.4",
+                    default_definition.decay_time_seconds, default_definition.sustain_level
+                ),
+                "v:5'0.3".to_string(),
+                "d:0.2".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_instrument_text_errors() {
+        assert!(parse_instrument_text("attack = 0.1").is_err());
+        assert!(parse_instrument_text("[a]\noscillator = \"kazoo\"").is_err());
+        assert!(parse_instrument_text("[a]\nattack = fast").is_err());
+        assert!(parse_instrument_text("[a]\ncolour = \"red\"").is_err());
+        assert!(parse_instrument_text("[a]\n[a]").is_err());
+        assert!(parse_instrument_text("[a]\nsample = pad.wav").is_err());
+    }
+
+    #[test]
+    fn test_sample_instrument() {
+        let folder = std::env::temp_dir().join("instrument_file_sample_test");
+        fs::create_dir_all(&folder).unwrap();
+        let path = folder.join("bank.toml");
+        fs::write(
+            &path,
+            "[pad]\nsample = \"pad.wav\"\nparameters = [0.1, 30]\n\n[lead]\noscillator = \"saw\"\n",
+        )
+        .unwrap();
+        let instruments = load_instrument_file(&path.display().to_string()).unwrap();
+        let _ = fs::remove_dir_all(&folder);
+
+        // The sample is found next to the instrument file, and an
+        // instrument with a sample plays grains unless it names another
+        // oscillator
+        assert_eq!(
+            instruments[0].sample.as_deref(),
+            Some(folder.join("pad.wav").display().to_string().as_str())
+        );
+        assert_eq!(instruments[0].to_tokens(), vec!["grain:0.1'30".to_string()]);
+        assert_eq!(instruments[1].sample, None);
+    }
+}
@@ -21,6 +21,15 @@
 // 2. Create a function that generates samples for your instrument
 // 3. The function signature is: fn(phase: f32, params: &[f32], rng: &mut RandomNumberGenerator) -> f32
 // 4. Return a value between -1.0 and 1.0
+// 5. Optionally add a block version (see generate_sine_block) so the
+//    instrument is vectorized; otherwise set generate_block_function: None
+//
+// CUSTOM INSTRUMENTS:
+// Programs using the library can add instruments without editing this file:
+// implement CustomInstrument and call register_instrument. Registered
+// instruments get the IDs after the built-in ones, and find_instrument_by_name,
+// get_instrument_by_id, and the generate functions look them up like the
+// built-ins, so cells can play them by name ("c4 bell").
 //
 // ANTI-ALIASING:
 // Some waveforms (square, sawtooth) have sharp edges that can cause aliasing
@@ -28,7 +37,11 @@
 // to smooth these edges and reduce aliasing artifacts.
 // ============================================================================
 
-use crate::helper::{RandomNumberGenerator, TWO_PI};
+use crate::effects::is_effect_name;
+use crate::envelope::EnvelopeShape;
+use crate::helper::{RandomNumberGenerator, TWO_PI, parse_pitch_name};
+use crate::simd::{fast_sine, map_block};
+use std::sync::RwLock;
 
 // ============================================================================
 // INSTRUMENT DEFINITION (REGISTRY PATTERN)
@@ -39,6 +52,9 @@ use crate::helper::{RandomNumberGenerator, TWO_PI};
 // what instruments are available and what parameters they accept.
 // ============================================================================
 
+/// Vectorized sample generator: fn(phases, params, output)
+pub type GenerateBlockFunction = fn(&[f32], &[f32], &mut [f32]);
+
 /// Defines an instrument type with all its properties
 #[derive(Clone)]
 pub struct InstrumentDefinition {
@@ -58,6 +74,53 @@ pub struct InstrumentDefinition {
     /// The function that generates samples for this instrument
     /// This is a function pointer - it points to the actual code that makes sound
     pub generate_sample_function: fn(f32, &[f32], &mut RandomNumberGenerator) -> f32,
+
+    /// Optional vectorized version that turns a whole block of phases into
+    /// samples at once. None means generate_sample_function is called once per sample.
+    pub generate_block_function: Option<GenerateBlockFunction>,
+
+    /// Envelope notes get when the cell has no env: token (None: the
+    /// engine default)
+    pub default_envelope: Option<EnvelopeShape>,
+
+    /// Effects every note starts with, in cell syntax ("dt:6 d:0.05"), or
+    /// "" for none. The cell's own effects win over them, and a song can
+    /// replace them in an [instruments] block ("square = dt:6").
+    pub default_effects: &'static str,
+
+    /// The registered instrument that makes the sound, for custom
+    /// instruments (None for the built-ins)
+    pub custom: Option<&'static dyn CustomInstrument>,
+}
+
+/// An instrument added at runtime by a program using the library
+///
+/// Like the built-in generators, it turns a phase (0 to 2*PI) into a
+/// sample between -1.0 and 1.0; the channel handles pitch, envelope, and
+/// effects. It runs on the audio thread, so it should not allocate.
+pub trait CustomInstrument: Send + Sync {
+    /// Name used in cells (one word, not a note or another instrument)
+    fn name(&self) -> &str;
+
+    /// Whether the instrument needs a note ("c4 bell") or plays on its
+    /// own ("kick")
+    fn requires_pitch(&self) -> bool {
+        true
+    }
+
+    /// Envelope for notes whose cell has no env: token
+    fn default_envelope(&self) -> Option<EnvelopeShape> {
+        None
+    }
+
+    /// Effects every note starts with, in cell syntax ("dt:6 d:0.05")
+    fn default_effects(&self) -> &str {
+        ""
+    }
+
+    /// Generates one sample; params are the values after the name
+    /// ("bell:0.3'2" gives [0.3, 2.0])
+    fn generate_sample(&self, phase: f32, params: &[f32], rng: &mut RandomNumberGenerator) -> f32;
 }
 
 // ============================================================================
@@ -88,6 +151,10 @@ pub static INSTRUMENT_REGISTRY: &[InstrumentDefinition] = &[
         aliases: &[],
         requires_pitch: false,
         generate_sample_function: generate_silence,
+        generate_block_function: None,
+        default_envelope: None,
+        default_effects: "",
+        custom: None,
     },
     // -------------------------------------------------------------------------
     // ID 1: Sine Wave
@@ -100,6 +167,10 @@ pub static INSTRUMENT_REGISTRY: &[InstrumentDefinition] = &[
         aliases: &["sin"],
         requires_pitch: true,
         generate_sample_function: generate_sine,
+        generate_block_function: Some(generate_sine_block),
+        default_envelope: None,
+        default_effects: "",
+        custom: None,
     },
     // -------------------------------------------------------------------------
     // ID 2: Triangle-Sawtooth Morph (TriSaw)
@@ -112,6 +183,10 @@ pub static INSTRUMENT_REGISTRY: &[InstrumentDefinition] = &[
         aliases: &["tri", "saw", "triangle", "sawtooth"],
         requires_pitch: true,
         generate_sample_function: generate_trisaw,
+        generate_block_function: Some(generate_trisaw_block),
+        default_envelope: None,
+        default_effects: "",
+        custom: None,
     },
     // -------------------------------------------------------------------------
     // ID 3: Square Wave
@@ -125,6 +200,10 @@ pub static INSTRUMENT_REGISTRY: &[InstrumentDefinition] = &[
         aliases: &["sq"],
         requires_pitch: true,
         generate_sample_function: generate_square_antialiased,
+        generate_block_function: None,
+        default_envelope: None,
+        default_effects: "",
+        custom: None,
     },
     // -------------------------------------------------------------------------
     // ID 4: White Noise
@@ -138,6 +217,10 @@ pub static INSTRUMENT_REGISTRY: &[InstrumentDefinition] = &[
         aliases: &["white", "whitenoise"],
         requires_pitch: false,
         generate_sample_function: generate_noise,
+        generate_block_function: None,
+        default_envelope: None,
+        default_effects: "",
+        custom: None,
     },
     // -------------------------------------------------------------------------
     // ID 5: Pulse Wave
@@ -152,9 +235,75 @@ pub static INSTRUMENT_REGISTRY: &[InstrumentDefinition] = &[
         aliases: &["pwm"],
         requires_pitch: true,
         generate_sample_function: generate_pulse_antialiased,
+        generate_block_function: None,
+        default_envelope: None,
+        default_effects: "",
+        custom: None,
+    },
+    // -------------------------------------------------------------------------
+    // ID 6: Organ (Additive)
+    // Sums sine waves at the first eight harmonics of the note, each at its
+    // own level, like the drawbars of a tonewheel organ: "organ:1'0.8'0'0.5".
+    // Add "click:0.3" to the cell (or "organ = click:0.3" in [instruments])
+    // for the key-click at the start of each note.
+    // -------------------------------------------------------------------------
+    InstrumentDefinition {
+        id: 6,
+        name: "organ",
+        aliases: &["additive", "drawbar"],
+        requires_pitch: true,
+        generate_sample_function: generate_organ,
+        generate_block_function: Some(generate_organ_block),
+        default_envelope: None,
+        default_effects: "",
+        custom: None,
+    },
+    // -------------------------------------------------------------------------
+    // ID 7: Grain (Granular)
+    // Scatters short grains of a WAV file loaded with "[sample] file.wav":
+    // "grain:size'density'position'spray". Grains need memory between
+    // samples, so the channel renders them itself (see grain.rs); the
+    // functions here only give silence.
+    // -------------------------------------------------------------------------
+    InstrumentDefinition {
+        id: GRAIN_INSTRUMENT_ID,
+        name: "grain",
+        aliases: &["granular"],
+        requires_pitch: true,
+        generate_sample_function: generate_silence,
+        generate_block_function: None,
+        default_envelope: None,
+        default_effects: "",
+        custom: None,
+    },
+    // -------------------------------------------------------------------------
+    // ID 8: Input (Audio In)
+    // Plays whatever a duplex device captures ("tracker live --input"), so
+    // a guitar or voice gets the channel's envelope and effects:
+    // "input ds:0.3". "input:1" takes only the left side, "input:2" only
+    // the right; plain "input" mixes both. The engine hands the captured
+    // samples to the channel (see engine.rs); the functions here only give
+    // silence.
+    // -------------------------------------------------------------------------
+    InstrumentDefinition {
+        id: INPUT_INSTRUMENT_ID,
+        name: "input",
+        aliases: &["mic", "linein"],
+        requires_pitch: false,
+        generate_sample_function: generate_silence,
+        generate_block_function: None,
+        default_envelope: None,
+        default_effects: "",
+        custom: None,
     },
 ];
 
+/// ID of the grain instrument, which channels render with a GrainCloud
+pub const GRAIN_INSTRUMENT_ID: usize = 7;
+
+/// ID of the input instrument, which channels fill from the captured audio
+pub const INPUT_INSTRUMENT_ID: usize = 8;
+
 // ============================================================================
 // SAMPLE GENERATION FUNCTIONS
 // ============================================================================
@@ -168,7 +317,7 @@ pub static INSTRUMENT_REGISTRY: &[InstrumentDefinition] = &[
 // Each function returns a sample value between -1.0 and 1.0
 // ============================================================================
 
-/// Generates silence (used for the "master" pseudo-instrument)
+/// Generates silence (used for the pseudo-instruments)
 fn generate_silence(_phase: f32, _params: &[f32], _rng: &mut RandomNumberGenerator) -> f32 {
     0.0
 }
@@ -178,7 +327,12 @@ fn generate_silence(_phase: f32, _params: &[f32], _rng: &mut RandomNumberGenerat
 ///
 /// Mathematical formula: sample = sin(phase)
 fn generate_sine(phase: f32, _params: &[f32], _rng: &mut RandomNumberGenerator) -> f32 {
-    phase.sin()
+    fast_sine(phase)
+}
+
+/// Block version of generate_sine (vectorized)
+fn generate_sine_block(phases: &[f32], _params: &[f32], output: &mut [f32]) {
+    map_block(phases, output, fast_sine);
 }
 
 /// Generates a triangle-sawtooth morphable wave
@@ -191,6 +345,17 @@ fn generate_sine(phase: f32, _params: &[f32], _rng: &mut RandomNumberGenerator)
 /// This works by controlling where the "peak" of the wave occurs.
 /// Triangle has peak at 50%, sawtooth has peak at 0% or 100%.
 fn generate_trisaw(phase: f32, params: &[f32], _rng: &mut RandomNumberGenerator) -> f32 {
+    trisaw_at(phase, trisaw_peak_position(params))
+}
+
+/// Block version of generate_trisaw (vectorized)
+fn generate_trisaw_block(phases: &[f32], params: &[f32], output: &mut [f32]) {
+    let peak_position = trisaw_peak_position(params);
+    map_block(phases, output, move |phase| trisaw_at(phase, peak_position));
+}
+
+/// Where the trisaw's peak sits in the cycle (0.0 to 1.0)
+fn trisaw_peak_position(params: &[f32]) -> f32 {
     // Get the shape parameter (defaults to 0.0 = triangle)
     let shape = if params.is_empty() {
         0.0
@@ -198,14 +363,18 @@ fn generate_trisaw(phase: f32, params: &[f32], _rng: &mut RandomNumberGenerator)
         params[0].clamp(-1.0, 1.0)
     };
 
-    // Convert phase (0 to 2*PI) to normalized time (0 to 1)
-    let normalized_time = phase / TWO_PI;
-
     // Calculate where the peak occurs based on shape
     // shape -1.0 -> peak at 0.0 (sawtooth down)
     // shape 0.0 -> peak at 0.5 (triangle)
     // shape 1.0 -> peak at 1.0 (sawtooth up)
-    let peak_position = (shape + 1.0) / 2.0;
+    (shape + 1.0) / 2.0
+}
+
+/// The trisaw sample at a phase, for a given peak position
+#[inline(always)]
+fn trisaw_at(phase: f32, peak_position: f32) -> f32 {
+    // Convert phase (0 to 2*PI) to normalized time (0 to 1)
+    let normalized_time = phase / TWO_PI;
 
     // Generate the waveform based on whether we're before or after the peak
     if normalized_time < peak_position {
@@ -334,6 +503,60 @@ fn generate_pulse_antialiased(phase: f32, params: &[f32], _rng: &mut RandomNumbe
     sample
 }
 
+/// Harmonics the organ can sum (one level per harmonic)
+pub const ORGAN_HARMONIC_COUNT: usize = 8;
+
+/// Organ levels when the cell gives none ("organ" is "organ:1'0.8'0'0.5")
+const ORGAN_DEFAULT_LEVELS: [f32; ORGAN_HARMONIC_COUNT] = [1.0, 0.8, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0];
+
+/// Generates an additive organ tone
+///
+/// Parameters:
+/// - params[0..8]: Level of harmonics 1 to 8 (0.0 to 1.0; missing = 0)
+///
+/// Harmonic N is a sine at N times the note's frequency. When the levels
+/// add up to more than 1, they are scaled down together so the sum stays
+/// within -1.0 to 1.0 (the mix keeps its balance, not its loudness).
+fn generate_organ(phase: f32, params: &[f32], _rng: &mut RandomNumberGenerator) -> f32 {
+    organ_at(phase, &organ_weights(params))
+}
+
+/// Block version of generate_organ (vectorized)
+fn generate_organ_block(phases: &[f32], params: &[f32], output: &mut [f32]) {
+    let weights = organ_weights(params);
+    map_block(phases, output, |phase| organ_at(phase, &weights));
+}
+
+/// The organ's harmonic levels, scaled so they add up to at most 1
+fn organ_weights(params: &[f32]) -> [f32; ORGAN_HARMONIC_COUNT] {
+    let mut weights = if params.is_empty() {
+        ORGAN_DEFAULT_LEVELS
+    } else {
+        [0.0; ORGAN_HARMONIC_COUNT]
+    };
+    for (weight, level) in weights.iter_mut().zip(params) {
+        *weight = level.clamp(0.0, 1.0);
+    }
+
+    let total: f32 = weights.iter().sum();
+    if total > 1.0 {
+        for weight in &mut weights {
+            *weight /= total;
+        }
+    }
+    weights
+}
+
+/// The organ sample at a phase, for given harmonic weights
+#[inline(always)]
+fn organ_at(phase: f32, weights: &[f32; ORGAN_HARMONIC_COUNT]) -> f32 {
+    let mut sample = 0.0;
+    for (harmonic, weight) in weights.iter().enumerate() {
+        sample += weight * fast_sine(phase * (harmonic + 1) as f32);
+    }
+    sample
+}
+
 // ============================================================================
 // ANTI-ALIASING HELPERS
 // ============================================================================
@@ -370,7 +593,7 @@ fn polyblep(mut phase: f32, phase_increment: f32) -> f32 {
 // ============================================================================
 
 /// Finds an instrument by name (case-insensitive)
-/// Searches both primary names and aliases
+/// Searches both primary names and aliases, then the custom instruments
 /// Returns the instrument ID if found, or None if not found
 pub fn find_instrument_by_name(name: &str) -> Option<usize> {
     let name_lower = name.to_lowercase();
@@ -389,13 +612,100 @@ pub fn find_instrument_by_name(name: &str) -> Option<usize> {
         }
     }
 
-    None
+    custom_instruments()
+        .iter()
+        .find(|instrument| instrument.name == name_lower)
+        .map(|instrument| instrument.id)
 }
 
 /// Gets an instrument definition by its ID
 /// Returns None if the ID is invalid
 pub fn get_instrument_by_id(id: usize) -> Option<&'static InstrumentDefinition> {
-    INSTRUMENT_REGISTRY.get(id)
+    match INSTRUMENT_REGISTRY.get(id) {
+        Some(instrument) => Some(instrument),
+        None => custom_instruments()
+            .get(id - INSTRUMENT_REGISTRY.len())
+            .copied(),
+    }
+}
+
+/// Whether the instrument plays a pitch (false for noise and unknown IDs)
+pub fn instrument_requires_pitch(id: usize) -> bool {
+    get_instrument_by_id(id).is_some_and(|instrument| instrument.requires_pitch)
+}
+
+// ============================================================================
+// CUSTOM INSTRUMENT REGISTRY
+// ============================================================================
+
+/// Registered custom instruments; entry N has ID INSTRUMENT_REGISTRY.len() + N
+///
+/// Definitions are leaked so they live as long as the built-in ones (an
+/// instrument is registered once per program, so this is a few bytes).
+static CUSTOM_INSTRUMENTS: RwLock<Vec<&'static InstrumentDefinition>> = RwLock::new(Vec::new());
+
+/// Read access to the custom instruments
+fn custom_instruments() -> std::sync::RwLockReadGuard<'static, Vec<&'static InstrumentDefinition>> {
+    CUSTOM_INSTRUMENTS
+        .read()
+        .unwrap_or_else(|error| error.into_inner())
+}
+
+/// Adds an instrument that cells can play by name, and returns its ID
+///
+/// Register instruments before parsing the songs that use them (and
+/// before playback starts). Registering a name again replaces the earlier
+/// instrument and keeps its ID. Names can't be a note, a built-in
+/// instrument, or an effect.
+pub fn register_instrument(instrument: Box<dyn CustomInstrument>) -> Result<usize, String> {
+    let name = instrument.name().to_lowercase();
+    let reason = if name.is_empty()
+        || !name
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || character == '_')
+    {
+        Some("is not one word (letters, digits, _)")
+    } else if INSTRUMENT_REGISTRY
+        .iter()
+        .any(|built_in| built_in.name == name || built_in.aliases.contains(&name.as_str()))
+    {
+        Some("is a built-in instrument")
+    } else if parse_pitch_name(&name).is_some() {
+        Some("looks like a note")
+    } else if is_effect_name(&name) || matches!(name.as_str(), "group" | "config") {
+        Some("is already used by cells")
+    } else {
+        None
+    };
+    if let Some(reason) = reason {
+        return Err(format!("Instrument name '{}' {}", name, reason));
+    }
+
+    let mut instruments = CUSTOM_INSTRUMENTS
+        .write()
+        .unwrap_or_else(|error| error.into_inner());
+    let index = instruments
+        .iter()
+        .position(|existing| existing.name == name)
+        .unwrap_or(instruments.len());
+    let custom: &'static dyn CustomInstrument = Box::leak(instrument);
+    let definition = Box::leak(Box::new(InstrumentDefinition {
+        id: INSTRUMENT_REGISTRY.len() + index,
+        name: Box::leak(name.into_boxed_str()),
+        aliases: &[],
+        requires_pitch: custom.requires_pitch(),
+        generate_sample_function: generate_silence,
+        generate_block_function: None,
+        default_envelope: custom.default_envelope(),
+        default_effects: custom.default_effects(),
+        custom: Some(custom),
+    }));
+    if index == instruments.len() {
+        instruments.push(definition);
+    } else {
+        instruments[index] = definition;
+    }
+    Ok(definition.id)
 }
 
 /// Generates a sample for the given instrument
@@ -406,10 +716,44 @@ pub fn generate_sample(
     params: &[f32],
     rng: &mut RandomNumberGenerator,
 ) -> f32 {
-    if let Some(instrument) = get_instrument_by_id(instrument_id) {
-        (instrument.generate_sample_function)(phase, params, rng)
-    } else {
-        0.0 // Unknown instrument - return silence
+    match get_instrument_by_id(instrument_id) {
+        Some(InstrumentDefinition {
+            custom: Some(custom),
+            ..
+        }) => custom.generate_sample(phase, params, rng),
+        Some(instrument) => (instrument.generate_sample_function)(phase, params, rng),
+        None => 0.0, // Unknown instrument - return silence
+    }
+}
+
+/// Generates a block of samples, one per phase
+/// Uses the instrument's vectorized block function when it has one.
+pub fn generate_block(
+    instrument_id: usize,
+    phases: &[f32],
+    params: &[f32],
+    rng: &mut RandomNumberGenerator,
+    output: &mut [f32],
+) {
+    let Some(instrument) = get_instrument_by_id(instrument_id) else {
+        output.fill(0.0); // Unknown instrument - silence
+        return;
+    };
+
+    if let Some(custom) = instrument.custom {
+        for (output_sample, phase) in output.iter_mut().zip(phases) {
+            *output_sample = custom.generate_sample(*phase, params, rng);
+        }
+        return;
+    }
+
+    match instrument.generate_block_function {
+        Some(generate_block_function) => generate_block_function(phases, params, output),
+        None => {
+            for (output_sample, phase) in output.iter_mut().zip(phases) {
+                *output_sample = (instrument.generate_sample_function)(*phase, params, rng);
+            }
+        }
     }
 }
 
@@ -447,6 +791,95 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_organ_harmonics() {
+        let mut rng = RandomNumberGenerator::new(1);
+        for i in 0..100 {
+            let phase = (i as f32 / 100.0) * TWO_PI;
+
+            // Only the first harmonic is a plain sine
+            let fundamental = generate_organ(phase, &[1.0], &mut rng);
+            assert!((fundamental - phase.sin()).abs() < 1e-5);
+
+            // Full levels are scaled down together: (sin x + sin 2x) / 2
+            let two = generate_organ(phase, &[1.0, 1.0], &mut rng);
+            assert!((two - (phase.sin() + (2.0 * phase).sin()) / 2.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_block_generation_matches_samples() {
+        let phases: Vec<f32> = (0..21).map(|i| (i as f32 / 21.0) * TWO_PI).collect();
+        for (instrument_id, params) in [
+            (1, vec![]),
+            (2, vec![0.4]),
+            (2, vec![-1.0]),
+            (5, vec![]),
+            (6, vec![]),
+            (6, vec![1.0, 0.0, 0.7, 0.0, 0.0, 0.0, 0.0, 0.0, 0.9]),
+        ] {
+            let mut block = vec![0.0; phases.len()];
+            generate_block(
+                instrument_id,
+                &phases,
+                &params,
+                &mut RandomNumberGenerator::new(1),
+                &mut block,
+            );
+
+            let mut rng = RandomNumberGenerator::new(1);
+            for (phase, sample) in phases.iter().zip(&block) {
+                assert_eq!(
+                    generate_sample(instrument_id, *phase, &params, &mut rng),
+                    *sample
+                );
+            }
+        }
+    }
+
+    /// Plays a constant level (its parameter, 0.5 by default)
+    struct Level(&'static str);
+
+    impl CustomInstrument for Level {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn requires_pitch(&self) -> bool {
+            false
+        }
+
+        fn generate_sample(
+            &self,
+            _phase: f32,
+            params: &[f32],
+            _rng: &mut RandomNumberGenerator,
+        ) -> f32 {
+            params.first().copied().unwrap_or(0.5)
+        }
+    }
+
+    #[test]
+    fn test_register_custom_instrument() {
+        for taken in ["saw", "c4", "d", "two words"] {
+            assert!(register_instrument(Box::new(Level(taken))).is_err());
+        }
+
+        let id = register_instrument(Box::new(Level("TestLevel"))).unwrap();
+        assert!(id >= INSTRUMENT_REGISTRY.len());
+        assert_eq!(find_instrument_by_name("testlevel"), Some(id));
+        assert!(!get_instrument_by_id(id).unwrap().requires_pitch);
+
+        // Registering again replaces it under the same ID
+        assert_eq!(register_instrument(Box::new(Level("testlevel"))), Ok(id));
+
+        let mut rng = RandomNumberGenerator::new(1);
+        assert_eq!(generate_sample(id, 0.0, &[0.25], &mut rng), 0.25);
+        let mut block = [0.0; 4];
+        generate_block(id, &[0.0; 4], &[], &mut rng, &mut block);
+        assert_eq!(block, [0.5; 4]);
+    }
+
     #[test]
     fn test_pulse_width_parameter() {
         let mut rng = RandomNumberGenerator::new(42);
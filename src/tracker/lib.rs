@@ -0,0 +1,510 @@
+// ============================================================================
+// LIB.RS - muSickBeets Tracker Library
+// ============================================================================
+//
+// The tracker engine as a library, so other Rust programs and games can
+// play muSickBeets songs or use the synth directly. The "tracker" binary
+// (main.rs and commands/) is a thin command-line player built on top of it.
+//
+// QUICK TOUR:
+// - Song::parse(text, channels)     Parse CSV song text
+// - Song::from_json(text)           Load a song saved as JSON
+// - Engine::new(song, sample_rate)  Build an engine that plays it
+// - engine.render_into(&mut buffer) Fill interleaved stereo samples
+// - engine.trigger_cell(0, "c4 sine a:0.5")
+//                                   Play a cell right now (live input)
+// - engine.register_effect("phaser", Box::new(MyPhaser))
+//                                   Add an effect cells can use by name
+// - engine.register_instrument(Box::new(MyBell))
+//                                   Add an instrument cells can play
+//
+// The modules below are public too, for programs that need more control
+// (custom parsing options, direct channel access, WAV export, ...).
+// ============================================================================
+
+//! CSV-driven music tracker synthesizer.
+//!
+//! ```
+//! use musickbeets::{Engine, Song};
+//!
+//! let song = Song::parse("Lead\nc4 sine\n-\n.", 1);
+//! let mut engine = Engine::new(song, 48000);
+//!
+//! let mut buffer = vec![0.0; 2 * 48000]; // one second, interleaved L R L R ...
+//! engine.render_into(&mut buffer);
+//! assert!(buffer.iter().any(|sample| *sample != 0.0));
+//! ```
+
+// ============================================================================
+// MODULE DECLARATIONS
+// ============================================================================
+
+pub mod audio; // WAV export and audio utilities
+pub mod backend; // Audio output backends (miniaudio, cpal, null)
+pub mod channel; // Per-channel synthesis and state
+pub mod command_queue; // Lock-free queue between the main and audio threads
+pub mod dsp_load; // Callback timing, xrun counts, and DSP load per effect
+pub mod editor; // Terminal pattern editor for song files
+pub mod effects; // Unified effects system (reverb, delay, chorus, etc.)
+pub mod engine; // Playback engine and sequencer
+pub mod envelope; // ADSR envelope system
+pub mod freeze; // Channels rendered ahead of time and played back (--freeze)
+pub mod grain; // Granular instrument and the WAV files it plays
+pub mod helper; // Math utilities, frequency table, shared algorithms
+pub mod instrument_file; // Instrument definitions loaded from TOML files
+pub mod instruments; // Sound generators (sine, square, noise, pulse, etc.)
+pub mod live; // Playing the synth from a MIDI or computer keyboard
+pub mod master_bus; // Master output bus and global effects
+pub mod midi_clock; // MIDI clock sync with drum machines and sequencers
+pub mod midi_export; // Song to Standard MIDI File conversion
+pub mod midi_import; // Standard MIDI File to tracker CSV conversion
+pub mod mod_import; // ProTracker MOD to tracker CSV conversion
+pub mod monitor; // Rolling spectrogram of the master output (--monitor)
+pub mod osc; // OSC server for remote control over the network
+pub mod oversampling; // 2x/4x oversampling for distortion and bitcrush
+pub mod parser; // CSV song file parser
+pub mod playlist; // M3U playlists of songs played back to back
+pub mod progress; // Progress line while rendering or playing
+pub mod recorder; // Recording live takes (master output and played cells)
+pub mod scale; // Scale registry and scale-constraint mode
+pub mod script; // Song scripts that write rows at parse time (Rhai)
+pub mod simd; // Vectorized inner loops (oscillators, mixing)
+pub mod snapshot; // Engine state dumped to JSON and diffed, for debugging
+pub mod song_stream; // Playing songs while they are parsed, a few rows ahead
+pub mod tui; // Terminal playback view (level meters, row display)
+pub mod tuning; // Tuning systems (12-TET, EDO, Scala scales)
+pub mod wah; // Wah and vowel (formant) filters
+
+#[cfg(test)]
+mod fuzz_tests; // Property tests: generated song text always parses
+#[cfg(test)]
+mod golden_tests; // Golden-render regression tests (assets/golden/)
+
+pub use effects::CustomEffect;
+pub use engine::EngineConfig;
+pub use instruments::CustomInstrument;
+pub use parser::{ParseDiagnostics, ParseError, Severity};
+
+use engine::PlaybackEngine;
+use helper::{A4_FREQUENCY_HZ, FrequencyTable};
+use parser::{
+    AUTO_CHANNEL_COUNT, CellAction, MissingCellBehavior, SongData, parse_cell_text,
+    parse_song_collection,
+};
+
+// ============================================================================
+// SONG
+// ============================================================================
+
+/// A parsed song, ready to hand to an Engine
+#[derive(Clone)]
+pub struct Song {
+    /// The parsed rows and config
+    data: SongData,
+
+    /// How many channels the song was parsed for
+    channel_count: usize,
+}
+
+impl Song {
+    /// Parses CSV song text for the given number of channels
+    /// (parser::AUTO_CHANNEL_COUNT: one channel per column of the song)
+    ///
+    /// Never fails: problems are skipped and listed in `diagnostics()`.
+    /// Files with several [song] sections play their [chain] (or the
+    /// first song), just like the tracker binary without --song.
+    pub fn parse(text: &str, channel_count: usize) -> Self {
+        let frequency_table = FrequencyTable::new();
+        let collection = parse_song_collection(
+            text,
+            &frequency_table,
+            A4_FREQUENCY_HZ,
+            channel_count,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        // The default selection only names songs that exist
+        let data = collection
+            .select(&collection.default_selection())
+            .expect("default song selection is always valid");
+        let channel_count = if channel_count == AUTO_CHANNEL_COUNT {
+            data.channel_count().max(1)
+        } else {
+            channel_count
+        };
+
+        Self {
+            data,
+            channel_count,
+        }
+    }
+
+    /// Wraps song data parsed with the parser module directly
+    pub fn from_data(data: SongData, channel_count: usize) -> Self {
+        Self {
+            data,
+            channel_count,
+        }
+    }
+
+    /// Loads a song saved with to_json (or generated by another program)
+    /// The channel count is the width of the widest row.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let data = SongData::from_json(text)?;
+        let channel_count = data.channel_count().max(1);
+        Ok(Self {
+            data,
+            channel_count,
+        })
+    }
+
+    /// The song as JSON: rows of cell actions plus the config row settings
+    pub fn to_json(&self) -> String {
+        self.data.to_json()
+    }
+
+    /// Warnings and errors found while parsing
+    pub fn diagnostics(&self) -> &ParseDiagnostics {
+        &self.data.diagnostics
+    }
+
+    /// Number of rows (time steps)
+    pub fn row_count(&self) -> usize {
+        self.data.row_count()
+    }
+
+    /// Seconds per row: the config row's setting, or the engine default
+    pub fn tick_duration_seconds(&self) -> f32 {
+        self.data
+            .config
+            .tick_duration
+            .unwrap_or(EngineConfig::default().tick_duration_seconds)
+    }
+
+    /// The underlying parsed data
+    pub fn data(&self) -> &SongData {
+        &self.data
+    }
+}
+
+// ============================================================================
+// ENGINE
+// ============================================================================
+
+/// Plays a Song (and/or cells triggered live) into sample buffers
+pub struct Engine {
+    /// The sequencer, channels, and master bus
+    playback: PlaybackEngine,
+
+    /// Note lookups for trigger_cell
+    frequency_table: FrequencyTable,
+
+    /// A4 frequency used by trigger_cell
+    reference_frequency_hz: f32,
+}
+
+impl Engine {
+    /// Creates an engine for a song, using the song's tick duration
+    pub fn new(song: Song, sample_rate: u32) -> Self {
+        let config = EngineConfig {
+            sample_rate,
+            channel_count: song.channel_count,
+            tick_duration_seconds: song.tick_duration_seconds(),
+            ..Default::default()
+        };
+        Self::with_config(song, config)
+    }
+
+    /// Creates an engine for a song with full control over the settings
+    pub fn with_config(song: Song, config: EngineConfig) -> Self {
+        let reference_frequency_hz = song
+            .data
+            .config
+            .reference_frequency_hz
+            .unwrap_or(A4_FREQUENCY_HZ);
+        Self {
+            playback: PlaybackEngine::new(song.data, config),
+            frequency_table: FrequencyTable::new(),
+            reference_frequency_hz,
+        }
+    }
+
+    /// Creates an engine with no song that never stops, for games and
+    /// instruments that only use trigger_cell
+    pub fn live(channel_count: usize, sample_rate: u32) -> Self {
+        let mut engine = Self::new(Song::parse("", channel_count), sample_rate);
+        engine.playback.set_stop_at_end(false);
+        engine
+    }
+
+    /// Fills the buffer with interleaved stereo samples (L R L R ...)
+    /// and advances the song. Outputs silence once the song has ended.
+    pub fn render_into(&mut self, output: &mut [f32]) {
+        self.playback.process_frame(output);
+    }
+
+    /// Same as render_into, with audio captured for this buffer
+    /// (interleaved stereo), which channels playing "input" cells hear
+    pub fn render_with_input(&mut self, input: &[f32], output: &mut [f32]) {
+        self.playback.process_frame_with_input(input, output);
+    }
+
+    /// Plays one cell on a channel right now, as if it were in the song
+    /// Example: engine.trigger_cell(2, "e4 square a:0.4")
+    ///
+    /// "master" and "group" cells work on any channel index. Returns the parser's
+    /// messages for the cell (empty if it was understood).
+    pub fn trigger_cell(&mut self, channel_index: usize, cell: &str) -> ParseDiagnostics {
+        let (action, mut diagnostics) =
+            parse_cell_text(cell, &self.frequency_table, self.reference_frequency_hz);
+
+        if !action.is_bus_command() && channel_index >= self.playback.channel_count() {
+            diagnostics.push(ParseError::error(
+                0,
+                channel_index,
+                cell,
+                format!(
+                    "Channel {} does not exist (engine has {} channels). Cell ignored.",
+                    channel_index,
+                    self.playback.channel_count()
+                ),
+            ));
+            return diagnostics;
+        }
+        if let CellAction::GroupEffects { group, .. } = &action
+            && !self.playback.has_group(group)
+        {
+            diagnostics.push(ParseError::error(
+                0,
+                channel_index,
+                cell,
+                format!(
+                    "Unknown group '{}'. The song has no such group. Cell ignored.",
+                    group
+                ),
+            ));
+            return diagnostics;
+        }
+
+        self.playback.dispatch_action(channel_index, &action);
+        diagnostics
+    }
+
+    /// Adds a channel effect that cells can use by name, e.g. "phaser:2'0.5"
+    ///
+    /// The effect is registered for the whole program (see
+    /// effects::register_effect), so register it before parsing songs that
+    /// use it; this engine's channels get their own copies right away.
+    pub fn register_effect(
+        &mut self,
+        name: &str,
+        effect: Box<dyn CustomEffect>,
+    ) -> Result<(), String> {
+        let slot = effects::CustomEffectSlot {
+            name: name.to_lowercase(),
+            effect: effect.clone_box(),
+        };
+        effects::register_effect(name, effect)?;
+        self.playback.add_custom_effect(&slot);
+        Ok(())
+    }
+
+    /// Adds an instrument that cells can play by name ("c4 bell") and
+    /// returns its instrument ID
+    ///
+    /// Like register_effect, this is for the whole program (see
+    /// instruments::register_instrument): register before parsing songs
+    /// that use the instrument.
+    pub fn register_instrument(
+        &mut self,
+        instrument: Box<dyn CustomInstrument>,
+    ) -> Result<usize, String> {
+        instruments::register_instrument(instrument)
+    }
+
+    /// Returns true once the song has played through
+    pub fn is_finished(&self) -> bool {
+        self.playback.is_finished()
+    }
+
+    /// Returns the row that will be played next (0-indexed)
+    pub fn current_row(&self) -> usize {
+        self.playback.current_row()
+    }
+
+    /// Starts the song again from the first row
+    pub fn reset(&mut self) {
+        self.playback.reset();
+    }
+
+    /// Total song length in seconds (not counting release tails)
+    pub fn duration_seconds(&self) -> f32 {
+        self.playback.get_total_duration_seconds()
+    }
+
+    /// The underlying playback engine
+    pub fn playback_engine(&mut self) -> &mut PlaybackEngine {
+        &mut self.playback
+    }
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_song_plays_and_finishes() {
+        let song = Song::parse("Lead\nconfig, tick: 0.01\nc4 sine\n.", 1);
+        assert_eq!(song.row_count(), 2);
+        assert!(song.diagnostics().is_empty());
+
+        let mut engine = Engine::new(song, 8000);
+        let mut buffer = vec![0.0; 2 * 400];
+        engine.render_into(&mut buffer);
+        assert!(buffer.iter().any(|sample| *sample != 0.0));
+        assert!(engine.is_finished());
+    }
+
+    #[test]
+    fn test_live_trigger_cell() {
+        let mut engine = Engine::live(2, 8000);
+        let mut buffer = vec![0.0; 2 * 256];
+
+        // Nothing plays until a cell is triggered
+        engine.render_into(&mut buffer);
+        assert!(buffer.iter().all(|sample| *sample == 0.0));
+
+        assert!(engine.trigger_cell(1, "a4 square a:0.5").is_empty());
+        engine.render_into(&mut buffer);
+        assert!(buffer.iter().any(|sample| *sample != 0.0));
+        assert!(!engine.is_finished());
+
+        assert!(engine.trigger_cell(5, "c4 sine").has_errors());
+        assert!(engine.trigger_cell(9, "master rv:0.3'0.2").is_empty());
+
+        // Group cells need a group the song defines
+        assert!(engine.trigger_cell(0, "group drums a:0.5").has_errors());
+        let song = Song::parse("[groups]\ndrums = 1\nKick\nnoise\n", 1);
+        let mut engine = Engine::new(song, 8000);
+        assert!(engine.trigger_cell(0, "group drums a:0.5").is_empty());
+    }
+
+    /// Scales the signal by its parameter (1.0 until a cell sets it)
+    #[derive(Clone)]
+    struct Gain(f32);
+
+    impl CustomEffect for Gain {
+        fn set_parameters(&mut self, parameters: &[f32]) {
+            self.0 = parameters.first().copied().unwrap_or(1.0);
+        }
+
+        fn process_block(&mut self, samples: &mut [f32], _sample_rate: u32) {
+            for sample in samples {
+                *sample *= self.0;
+            }
+        }
+
+        fn clone_box(&self) -> Box<dyn CustomEffect> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn test_register_custom_effect() {
+        let mut engine = Engine::live(1, 8000);
+        assert!(engine.trigger_cell(0, "c4 sine testgain:0").has_errors());
+        assert!(engine.register_effect("d", Box::new(Gain(1.0))).is_err());
+        assert!(
+            engine
+                .register_effect("TestGain", Box::new(Gain(1.0)))
+                .is_ok()
+        );
+
+        // The new name parses and silences the channel
+        assert!(engine.trigger_cell(0, "c4 sine testgain:0").is_empty());
+        let mut buffer = vec![0.0; 2 * 256];
+        engine.render_into(&mut buffer);
+        assert!(buffer.iter().all(|sample| *sample == 0.0));
+
+        // cl (clear) turns it off again
+        assert!(engine.trigger_cell(0, "cl:0").is_empty());
+        engine.render_into(&mut buffer);
+        assert!(buffer.iter().any(|sample| *sample != 0.0));
+
+        // Songs parsed after registering can use it, with their own copies
+        let song = Song::parse("Lead\nc4 sine testgain:0.5", 1);
+        assert!(song.diagnostics().is_empty());
+    }
+
+    /// A pitchless click with its own short envelope and a little crush
+    struct Click;
+
+    impl CustomInstrument for Click {
+        fn name(&self) -> &str {
+            "bclick"
+        }
+
+        fn requires_pitch(&self) -> bool {
+            false
+        }
+
+        fn default_envelope(&self) -> Option<envelope::EnvelopeShape> {
+            Some(envelope::EnvelopeShape::from_parameters(&[
+                0.0, 0.01, 0.0, 0.01,
+            ]))
+        }
+
+        fn default_effects(&self) -> &str {
+            "b:8 p:0.3"
+        }
+
+        fn generate_sample(
+            &self,
+            phase: f32,
+            _params: &[f32],
+            _rng: &mut helper::RandomNumberGenerator,
+        ) -> f32 {
+            phase.sin()
+        }
+    }
+
+    #[test]
+    fn test_register_custom_instrument() {
+        let mut engine = Engine::live(1, 8000);
+        let id = engine.register_instrument(Box::new(Click)).unwrap();
+
+        // Starts with a note letter, but plays as the instrument
+        let song = Song::parse("Drum\nbclick a:0.5\nc4 bclick env:0.1", 1);
+        assert!(song.diagnostics().is_empty());
+        match &song.data().rows[0][0] {
+            parser::CellAction::TriggerPitchless {
+                instrument_id,
+                envelope,
+                effects,
+                ..
+            } => {
+                assert_eq!(*instrument_id, id);
+                assert_eq!(*envelope, Click.default_envelope());
+                assert_eq!(effects.bitcrush_bits, 8);
+                assert_eq!(effects.amplitude, 0.5);
+            }
+            other => panic!("expected TriggerPitchless, got {:?}", other),
+        }
+        // An env: token still wins over the default
+        match &song.data().rows[1][0] {
+            parser::CellAction::TriggerNote { envelope, .. } => {
+                assert_eq!(envelope.unwrap().attack_time_seconds, 0.1);
+            }
+            other => panic!("expected TriggerNote, got {:?}", other),
+        }
+
+        assert!(engine.trigger_cell(0, "bclick").is_empty());
+        let mut buffer = vec![0.0; 2 * 256];
+        engine.render_into(&mut buffer);
+        assert!(buffer.iter().any(|sample| *sample != 0.0));
+    }
+}
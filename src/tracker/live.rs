@@ -0,0 +1,891 @@
+// ============================================================================
+// LIVE.RS - Playing the Synth from a MIDI Keyboard
+// ============================================================================
+//
+// Turns incoming MIDI messages into the same cell actions a song uses, so
+// live notes go through the normal Channel and effects path:
+//
+//   tracker live --instrument trisaw --voices 6 --cc 74=distortion
+//
+// HOW IT WORKS:
+// - Voices: a range of tracker channels is reserved for live play. Each
+//   key pressed takes a free channel (or the one that has sounded the
+//   longest, if all are busy) and plays "c4 sine a:0.79" on it.
+// - Velocity: sets a: (velocity / 127). Key release is a slow release,
+//   so notes fade with their envelope's release time.
+// - Controllers: CC messages are mapped to effects through
+//   CONTROL_TARGET_REGISTRY (by default the mod wheel, CC 1, controls
+//   vibrato and CC 10 controls pan). Turning a knob updates every voice,
+//   and new notes start with the current knob settings.
+// - Pitch bend: detunes every voice (dt:), up to 2 semitones each way.
+// - Sustain pedal (CC 64) holds released keys until the pedal goes up.
+// - Audio input: with an input cell ("tracker live --input"), the channel
+//   after the voices plays the sound card's input (the "input"
+//   instrument) for as long as the player runs.
+//
+// LivePlayer only builds the actions; whoever owns the engine dispatches
+// them (see midi_input below, and "tracker live" in commands/live.rs). With
+// log_events() it also keeps every cell it sends, with the time, so a
+// take can be saved (recorder.rs).
+//
+// COMPUTER KEYBOARD:
+// Without MIDI gear, "tracker live --keys" plays from the letter keys,
+// laid out like a piano the way classic trackers do it:
+//
+//    S D   G H J   L ;          2 3   5 6 7   9 0
+//   Z X C V B N M , . /        Q W E R T Y U I O P
+//   c d e f g a b c d e        c d e f g a b c d e   (one octave higher)
+//
+// HOW TO ADD A NEW CONTROL TARGET:
+// 1. Add a ControlTarget to CONTROL_TARGET_REGISTRY
+// 2. Its token function turns the knob position (0.0 - 1.0) into an
+//    effect token, e.g. "d:0.5"
+// 3. "--cc 74=<name>" picks it up automatically
+// ============================================================================
+
+use crate::engine::{EngineCommand, EngineController};
+use crate::helper::{A4_FREQUENCY_HZ, FrequencyTable, PitchName};
+use crate::instruments::{find_instrument_by_name, get_instrument_by_id};
+use crate::midi_import::format_number;
+use crate::parser::{CellAction, parse_cell_text};
+use crate::recorder::EventLog;
+
+/// MIDI note number of C0 (the lowest note the tracker can play)
+const MIDI_NOTE_C0: u8 = 12;
+
+/// Sustain pedal controller number
+const SUSTAIN_PEDAL_CC: u8 = 64;
+
+/// "All sound off" and "all notes off" controller numbers
+const ALL_SOUND_OFF_CC: u8 = 120;
+const ALL_NOTES_OFF_CC: u8 = 123;
+
+/// Pitch bend wheel center (14-bit value)
+const PITCH_BEND_CENTER: i32 = 8192;
+
+/// Octave of the Z key when computer-keyboard play starts (Z = c4)
+pub const DEFAULT_KEYBOARD_OCTAVE: i32 = 4;
+
+/// Computer keys and their semitone above the Z key's C
+pub const COMPUTER_KEYBOARD_NOTES: &[(char, i32)] = &[
+    // Bottom row: one octave and a bit
+    ('z', 0),
+    ('s', 1),
+    ('x', 2),
+    ('d', 3),
+    ('c', 4),
+    ('v', 5),
+    ('g', 6),
+    ('b', 7),
+    ('h', 8),
+    ('n', 9),
+    ('j', 10),
+    ('m', 11),
+    (',', 12),
+    ('l', 13),
+    ('.', 14),
+    (';', 15),
+    ('/', 16),
+    // Top row: the next octave up
+    ('q', 12),
+    ('2', 13),
+    ('w', 14),
+    ('3', 15),
+    ('e', 16),
+    ('r', 17),
+    ('5', 18),
+    ('t', 19),
+    ('6', 20),
+    ('y', 21),
+    ('7', 22),
+    ('u', 23),
+    ('i', 24),
+    ('9', 25),
+    ('o', 26),
+    ('0', 27),
+    ('p', 28),
+];
+
+// ============================================================================
+// CONTROL TARGETS
+// ============================================================================
+
+/// An effect that a MIDI controller (knob, slider, mod wheel) can drive
+pub struct ControlTarget {
+    /// Primary name (used with --cc 74=name)
+    pub name: &'static str,
+
+    /// Alternative names that also work
+    pub aliases: &'static [&'static str],
+
+    /// One-line description for messages
+    pub description: &'static str,
+
+    /// Effect token for a controller position from 0.0 to 1.0
+    pub token: fn(f32) -> String,
+}
+
+/// All effects controllers can be mapped to
+/// Amplitude is not here: it comes from the key velocity.
+pub static CONTROL_TARGET_REGISTRY: &[ControlTarget] = &[
+    ControlTarget {
+        name: "pan",
+        aliases: &["p"],
+        description: "stereo position, left to right",
+        token: |position| format!("p:{}", format_number(position * 2.0 - 1.0)),
+    },
+    ControlTarget {
+        name: "vibrato",
+        aliases: &["v"],
+        description: "5.5 Hz vibrato, up to 1 semitone deep",
+        token: |position| format!("v:5.5'{}", format_number(position)),
+    },
+    ControlTarget {
+        name: "tremolo",
+        aliases: &["t"],
+        description: "6 Hz tremolo, up to full depth",
+        token: |position| format!("t:6'{}", format_number(position)),
+    },
+    ControlTarget {
+        name: "bitcrush",
+        aliases: &["b"],
+        description: "16 bits (clean) down to 1 bit",
+        token: |position| format!("b:{}", (16.0 - position * 15.0).round()),
+    },
+    ControlTarget {
+        name: "distortion",
+        aliases: &["d", "drive"],
+        description: "overdrive amount",
+        token: |position| format!("d:{}", format_number(position)),
+    },
+    ControlTarget {
+        name: "chorus",
+        aliases: &["ch"],
+        description: "chorus mix (1 Hz, 3 ms)",
+        token: |position| format!("ch:{}'1'3'0", format_number(position)),
+    },
+];
+
+/// Looks up a control target by name or alias (case-insensitive)
+pub fn find_control_target_by_name(name: &str) -> Option<&'static ControlTarget> {
+    let name_lower = name.trim().to_lowercase();
+    CONTROL_TARGET_REGISTRY
+        .iter()
+        .find(|target| target.name == name_lower || target.aliases.contains(&&*name_lower))
+}
+
+/// Parses a "74=distortion" controller mapping into (CC number, target name)
+pub fn parse_control_mapping(text: &str) -> Result<(u8, String), String> {
+    let (number, name) = text.split_once('=').ok_or_else(|| {
+        format!(
+            "Invalid CC mapping '{}'. Use CC=effect, e.g. 74=distortion",
+            text
+        )
+    })?;
+    let number = match number.trim().parse::<u8>() {
+        Ok(number) if number < 120 => number,
+        _ => return Err(format!("Invalid CC number '{}'. Use 0 to 119.", number)),
+    };
+    match find_control_target_by_name(name) {
+        Some(target) => Ok((number, target.name.to_string())),
+        None => {
+            let available: Vec<&str> = CONTROL_TARGET_REGISTRY
+                .iter()
+                .map(|target| target.name)
+                .collect();
+            Err(format!(
+                "Unknown CC target '{}'. Available: {}",
+                name.trim(),
+                available.join(", ")
+            ))
+        }
+    }
+}
+
+/// Picks a MIDI port from its number or part of its name (None = first port)
+/// kind ("input", "output") only appears in the error messages.
+pub fn choose_port(names: &[String], port: Option<&str>, kind: &str) -> Result<usize, String> {
+    if names.is_empty() {
+        return Err(format!(
+            "No MIDI {} ports found. Is a device plugged in?",
+            kind
+        ));
+    }
+    let Some(text) = port else {
+        return Ok(0);
+    };
+    let text_lower = text.to_lowercase();
+    text.parse::<usize>()
+        .ok()
+        .filter(|index| *index < names.len())
+        .or_else(|| {
+            names
+                .iter()
+                .position(|name| name.to_lowercase().contains(&text_lower))
+        })
+        .ok_or_else(|| {
+            format!(
+                "No MIDI {} port '{}'. Available: {}",
+                kind,
+                text,
+                names.join(", ")
+            )
+        })
+}
+
+/// MIDI key for a computer key (case-insensitive), with Z in the given octave
+/// Returns None for keys that aren't notes or notes out of MIDI range.
+pub fn computer_key_to_midi(character: char, octave: i32) -> Option<u8> {
+    let character = character.to_ascii_lowercase();
+    let (_, semitone) = COMPUTER_KEYBOARD_NOTES
+        .iter()
+        .find(|(key, _)| *key == character)?;
+    let midi_key = (octave + 1) * 12 + semitone;
+    (MIDI_NOTE_C0 as i32..=127)
+        .contains(&midi_key)
+        .then_some(midi_key as u8)
+}
+
+// ============================================================================
+// OPTIONS
+// ============================================================================
+
+/// Settings for live play
+#[derive(Clone, Debug)]
+pub struct LiveOptions {
+    /// Instrument every key plays (may include parameters, e.g. "trisaw:0.3")
+    pub instrument: String,
+
+    /// First tracker channel used for live voices (0-indexed)
+    pub first_channel: usize,
+
+    /// How many notes can sound at once (one channel each)
+    pub voices: usize,
+
+    /// MIDI channel to listen to (0-15), or None for all of them
+    pub midi_channel: Option<u8>,
+
+    /// Controller mappings: (CC number, control target name)
+    pub control_changes: Vec<(u8, String)>,
+
+    /// How far the pitch bend wheel bends, in cents
+    pub bend_range_cents: f32,
+
+    /// A4 frequency for the notes
+    pub reference_frequency_hz: f32,
+
+    /// Cell the channel after the voices plays when the audio input is
+    /// open ("input ds:0.3"), or None for no input channel
+    pub input_cell: Option<String>,
+}
+
+impl Default for LiveOptions {
+    fn default() -> Self {
+        Self {
+            instrument: "sine".to_string(),
+            first_channel: 0,
+            voices: 4,
+            midi_channel: None,
+            control_changes: vec![(1, "vibrato".to_string()), (10, "pan".to_string())],
+            bend_range_cents: 200.0,
+            reference_frequency_hz: A4_FREQUENCY_HZ,
+            input_cell: None,
+        }
+    }
+}
+
+// ============================================================================
+// LIVE PLAYER
+// ============================================================================
+
+/// One tracker channel used for live notes
+#[derive(Clone, Copy, Default)]
+struct Voice {
+    /// Key being held down (None once released)
+    key: Option<u8>,
+
+    /// Key released while the sustain pedal was down
+    pedal_held: Option<u8>,
+
+    /// Amplitude of the last note (0 = never played)
+    amplitude: f32,
+
+    /// When the voice last started a note (higher = newer)
+    started: u64,
+}
+
+/// Turns MIDI notes and controllers into cell actions for the engine
+pub struct LivePlayer {
+    options: LiveOptions,
+
+    /// Controller mappings resolved to targets, with the last position
+    /// (None until the knob is first moved)
+    controls: Vec<(u8, &'static ControlTarget, Option<f32>)>,
+
+    voices: Vec<Voice>,
+
+    /// Whether notes need a pitch (false for noise)
+    is_pitched: bool,
+
+    /// Current pitch bend in cents
+    bend_cents: f32,
+
+    sustain_pedal_down: bool,
+
+    /// Counter for finding the oldest voice
+    note_counter: u64,
+
+    frequency_table: FrequencyTable,
+
+    /// Cells sent so far, when logging (see log_events)
+    event_log: Option<EventLog>,
+}
+
+impl LivePlayer {
+    /// Creates a player for an engine with channel_count channels
+    /// Fails if the instrument or a CC target is unknown, or the voices
+    /// don't fit in the engine's channels.
+    pub fn new(options: LiveOptions, channel_count: usize) -> Result<Self, String> {
+        let instrument_name = options.instrument.split(':').next().unwrap_or("");
+        let instrument = find_instrument_by_name(instrument_name)
+            .and_then(get_instrument_by_id)
+            .ok_or_else(|| format!("Unknown instrument '{}'", instrument_name))?;
+
+        if options.voices == 0 || options.first_channel + options.voices > channel_count {
+            return Err(format!(
+                "{} voices starting at channel {} don't fit in {} channels",
+                options.voices,
+                options.first_channel + 1,
+                channel_count
+            ));
+        }
+        if options.input_cell.is_some() && options.first_channel + options.voices >= channel_count {
+            return Err(format!(
+                "The input needs a channel after the voices, but channel {} is the last one",
+                channel_count
+            ));
+        }
+
+        let mut controls = Vec::new();
+        for (number, name) in &options.control_changes {
+            let target = find_control_target_by_name(name)
+                .ok_or_else(|| format!("Unknown CC target '{}'", name))?;
+            controls.push((*number, target, None));
+        }
+
+        Ok(Self {
+            voices: vec![Voice::default(); options.voices],
+            is_pitched: instrument.requires_pitch,
+            controls,
+            bend_cents: 0.0,
+            sustain_pedal_down: false,
+            note_counter: 0,
+            frequency_table: FrequencyTable::new(),
+            event_log: None,
+            options,
+        })
+    }
+
+    /// Starts keeping every cell the player sends, timed from now
+    pub fn log_events(&mut self) {
+        self.event_log = Some(EventLog::new());
+    }
+
+    /// Takes the cells logged so far (None if log_events was never called)
+    pub fn take_event_log(&mut self) -> Option<EventLog> {
+        self.event_log.take()
+    }
+
+    /// The action that starts the input channel (None without an input cell)
+    /// Dispatch it once the engine is running with audio input.
+    pub fn start_input(&mut self) -> Option<(usize, CellAction)> {
+        let cell = self.options.input_cell.clone()?;
+        let channel_index = self.options.first_channel + self.options.voices;
+        Some(self.cell_action(channel_index, &cell))
+    }
+
+    /// Handles one raw MIDI message (note on/off, controller, pitch bend)
+    /// Returns (channel index, action) pairs to dispatch in order.
+    /// Messages for other MIDI channels and unknown messages are ignored.
+    pub fn handle_midi(&mut self, message: &[u8]) -> Vec<(usize, CellAction)> {
+        let Some((&status, data)) = message.split_first() else {
+            return Vec::new();
+        };
+        let midi_channel = status & 0x0F;
+        if status >= 0xF0
+            || self
+                .options
+                .midi_channel
+                .is_some_and(|channel| channel != midi_channel)
+        {
+            return Vec::new();
+        }
+
+        let first = data.first().copied().unwrap_or(0);
+        let second = data.get(1).copied().unwrap_or(0);
+        match status & 0xF0 {
+            0x90 => self.note_on(first, second),
+            0x80 => self.note_off(first),
+            0xB0 => self.control_change(first, second),
+            0xE0 => {
+                let bend = ((second as i32) << 7) | first as i32;
+                self.pitch_bend((bend - PITCH_BEND_CENTER) as f32 / PITCH_BEND_CENTER as f32)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Starts a note (velocity 0 is a note-off, as MIDI devices send it)
+    pub fn note_on(&mut self, key: u8, velocity: u8) -> Vec<(usize, CellAction)> {
+        if velocity == 0 {
+            return self.note_off(key);
+        }
+        if key < MIDI_NOTE_C0 {
+            return Vec::new();
+        }
+
+        // A free voice if there is one, otherwise the oldest note
+        let voice_index = (0..self.voices.len())
+            .min_by_key(|index| {
+                let voice = &self.voices[*index];
+                let is_busy = voice.key.is_some() || voice.pedal_held.is_some();
+                (is_busy, voice.started)
+            })
+            .unwrap_or(0);
+
+        self.note_counter += 1;
+        let amplitude = velocity as f32 / 127.0;
+        self.voices[voice_index] = Voice {
+            key: Some(key),
+            pedal_held: None,
+            amplitude,
+            started: self.note_counter,
+        };
+
+        let cell = if self.is_pitched {
+            let pitch = PitchName::from_semitones_from_c0((key - MIDI_NOTE_C0) as i32, 0.0);
+            format!(
+                "{} {} a:{}{}",
+                pitch,
+                self.options.instrument,
+                format_number(amplitude),
+                self.effect_tokens()
+            )
+        } else {
+            format!(
+                "{} a:{}{}",
+                self.options.instrument,
+                format_number(amplitude),
+                self.effect_tokens()
+            )
+        };
+        vec![self.cell_action(self.channel_index(voice_index), &cell)]
+    }
+
+    /// Releases a note (held on while the sustain pedal is down)
+    pub fn note_off(&mut self, key: u8) -> Vec<(usize, CellAction)> {
+        let Some(voice_index) = self.voices.iter().position(|voice| voice.key == Some(key)) else {
+            // Already released, or its voice was taken by a newer note
+            return Vec::new();
+        };
+
+        self.voices[voice_index].key = None;
+        if self.sustain_pedal_down {
+            self.voices[voice_index].pedal_held = Some(key);
+            return Vec::new();
+        }
+        vec![self.release_action(voice_index)]
+    }
+
+    /// Handles a controller message: mapped effects, sustain pedal, and
+    /// "all notes off"
+    pub fn control_change(&mut self, number: u8, value: u8) -> Vec<(usize, CellAction)> {
+        let position = value as f32 / 127.0;
+        let mut is_mapped = false;
+        for (control_number, _, control_position) in &mut self.controls {
+            if *control_number == number {
+                *control_position = Some(position);
+                is_mapped = true;
+            }
+        }
+        if is_mapped {
+            return self.update_voice_effects();
+        }
+
+        match number {
+            SUSTAIN_PEDAL_CC => {
+                self.sustain_pedal_down = value >= 64;
+                if self.sustain_pedal_down {
+                    return Vec::new();
+                }
+                let mut actions = Vec::new();
+                for voice_index in 0..self.voices.len() {
+                    if self.voices[voice_index].pedal_held.take().is_some() {
+                        actions.push(self.release_action(voice_index));
+                    }
+                }
+                actions
+            }
+            ALL_SOUND_OFF_CC | ALL_NOTES_OFF_CC => self.release_all(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Bends every voice (amount from -1.0 to 1.0, 0 = no bend)
+    pub fn pitch_bend(&mut self, amount: f32) -> Vec<(usize, CellAction)> {
+        self.bend_cents = amount.clamp(-1.0, 1.0) * self.options.bend_range_cents;
+        self.update_voice_effects()
+    }
+
+    /// Releases every voice (e.g., when live play stops)
+    pub fn release_all(&mut self) -> Vec<(usize, CellAction)> {
+        self.sustain_pedal_down = false;
+        let mut actions = Vec::new();
+        for voice_index in 0..self.voices.len() {
+            let voice = &mut self.voices[voice_index];
+            let was_held = voice.key.take().is_some();
+            let was_pedal_held = voice.pedal_held.take().is_some();
+            if was_held || was_pedal_held {
+                actions.push(self.release_action(voice_index));
+            }
+        }
+        actions
+    }
+
+    /// Tracker channel of a voice
+    fn channel_index(&self, voice_index: usize) -> usize {
+        self.options.first_channel + voice_index
+    }
+
+    /// " v:5.5'0.5 dt:100 cl" for the current knobs and bend
+    /// "cl" resets what is not listed, so knobs turned back to their
+    /// default position take effect too.
+    fn effect_tokens(&self) -> String {
+        let mut tokens = String::new();
+        for (_, target, position) in &self.controls {
+            if let Some(position) = position {
+                tokens.push(' ');
+                tokens.push_str(&(target.token)(*position));
+            }
+        }
+        if self.bend_cents != 0.0 {
+            tokens.push_str(&format!(" dt:{}", format_number(self.bend_cents)));
+        }
+        tokens.push_str(" cl");
+        tokens
+    }
+
+    /// Sends the current knobs and bend to every voice that has played
+    fn update_voice_effects(&mut self) -> Vec<(usize, CellAction)> {
+        let tokens = self.effect_tokens();
+        let cells: Vec<(usize, String)> = (0..self.voices.len())
+            .filter(|voice_index| self.voices[*voice_index].amplitude > 0.0)
+            .map(|voice_index| {
+                let amplitude = format_number(self.voices[voice_index].amplitude);
+                (
+                    self.channel_index(voice_index),
+                    format!("a:{}{}", amplitude, tokens),
+                )
+            })
+            .collect();
+        cells
+            .iter()
+            .map(|(channel_index, cell)| self.cell_action(*channel_index, cell))
+            .collect()
+    }
+
+    /// Parses a cell for a channel (and logs it, when logging)
+    fn cell_action(&mut self, channel_index: usize, cell: &str) -> (usize, CellAction) {
+        if let Some(event_log) = &mut self.event_log {
+            event_log.record(channel_index, cell);
+        }
+        let (action, diagnostics) = parse_cell_text(
+            cell,
+            &self.frequency_table,
+            self.options.reference_frequency_hz,
+        );
+        if !diagnostics.is_empty() {
+            log::warn!(target: "parser", "Live cell '{}' had problems", cell);
+        }
+        (channel_index, action)
+    }
+
+    /// A slow release for a voice's channel (logged as an empty cell)
+    fn release_action(&mut self, voice_index: usize) -> (usize, CellAction) {
+        let channel_index = self.channel_index(voice_index);
+        if let Some(event_log) = &mut self.event_log {
+            event_log.record(channel_index, "");
+        }
+        (channel_index, CellAction::SlowRelease)
+    }
+}
+
+/// Sends a LivePlayer's actions to a real-time engine
+/// If the command queue is full the rest are dropped (and logged), since
+/// waiting would make the input lag.
+pub fn send_actions(controller: &mut EngineController, actions: Vec<(usize, CellAction)>) {
+    for (channel_index, action) in actions {
+        let command = EngineCommand::Dispatch {
+            channel_index,
+            action,
+        };
+        if controller.send(command).is_err() {
+            log::warn!(target: "audio", "Command queue full, live note dropped");
+            return;
+        }
+    }
+}
+
+// ============================================================================
+// MIDI INPUT (cargo feature "midir")
+// ============================================================================
+
+/// Opens MIDI input ports and feeds them to a LivePlayer
+#[cfg(feature = "midir")]
+pub mod midi_input {
+    use super::{LivePlayer, choose_port, send_actions};
+    use crate::engine::EngineController;
+    use midir::{Ignore, MidiInput, MidiInputConnection};
+
+    /// Name this program shows up as in the system's MIDI connections
+    pub(crate) const CLIENT_NAME: &str = "muSickBeets";
+
+    /// An open MIDI port playing into the engine (closed when dropped)
+    pub struct LiveConnection {
+        connection: MidiInputConnection<(LivePlayer, EngineController)>,
+
+        /// Name of the port that was opened
+        pub port_name: String,
+    }
+
+    impl LiveConnection {
+        /// Closes the port, releasing every note that is still sounding
+        /// Returns the player, so its event log can be saved.
+        pub fn close(self) -> LivePlayer {
+            let (_, (mut player, mut controller)) = self.connection.close();
+            send_actions(&mut controller, player.release_all());
+            player
+        }
+    }
+
+    /// Names of the MIDI input ports, in port-number order
+    pub fn port_names() -> Result<Vec<String>, String> {
+        let input = MidiInput::new(CLIENT_NAME)
+            .map_err(|error| format!("Failed to open MIDI input: {}", error))?;
+        Ok(input
+            .ports()
+            .iter()
+            .map(|port| {
+                input
+                    .port_name(port)
+                    .unwrap_or_else(|_| "(unnamed)".to_string())
+            })
+            .collect())
+    }
+
+    /// Opens a MIDI input port and plays everything it receives
+    /// port is a port number or part of a port name (None = first port).
+    pub fn connect(
+        port: Option<&str>,
+        player: LivePlayer,
+        controller: EngineController,
+    ) -> Result<LiveConnection, String> {
+        let mut input = MidiInput::new(CLIENT_NAME)
+            .map_err(|error| format!("Failed to open MIDI input: {}", error))?;
+        // Sysex, clock, and active sensing are not needed for playing notes
+        input.ignore(Ignore::All);
+
+        let ports = input.ports();
+        let names: Vec<String> = ports
+            .iter()
+            .map(|port| {
+                input
+                    .port_name(port)
+                    .unwrap_or_else(|_| "(unnamed)".to_string())
+            })
+            .collect();
+        let port_index = choose_port(&names, port, "input")?;
+        let port_name = names[port_index].clone();
+
+        // The callback runs on midir's thread and only talks to the audio
+        // thread through the engine's lock-free command queue
+        let connection = input
+            .connect(
+                &ports[port_index],
+                "tracker-live",
+                |_timestamp, message, data: &mut (LivePlayer, EngineController)| {
+                    let (player, controller) = data;
+                    send_actions(controller, player.handle_midi(message));
+                },
+                (player, controller),
+            )
+            .map_err(|error| format!("Failed to open MIDI port '{}': {}", port_name, error))?;
+
+        Ok(LiveConnection {
+            connection,
+            port_name,
+        })
+    }
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frequency_of(action: &CellAction) -> f32 {
+        match action {
+            CellAction::TriggerNote { frequency_hz, .. } => *frequency_hz,
+            _ => panic!("expected a note"),
+        }
+    }
+
+    #[test]
+    fn test_voice_allocation_and_release() {
+        let options = LiveOptions {
+            first_channel: 2,
+            voices: 2,
+            ..Default::default()
+        };
+        let mut player = LivePlayer::new(options, 4).unwrap();
+
+        // Raw note-on for A4 (key 69) at full velocity
+        let actions = player.handle_midi(&[0x90, 69, 127]);
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].0, 2);
+        assert!((frequency_of(&actions[0].1) - 440.0).abs() < 0.01);
+
+        // Second key takes the free voice, the third steals the oldest
+        assert_eq!(player.note_on(60, 100)[0].0, 3);
+        assert_eq!(player.note_on(64, 100)[0].0, 2);
+
+        // The stolen key's note-off no longer does anything
+        assert!(player.note_off(69).is_empty());
+        let actions = player.handle_midi(&[0x80, 64, 0]);
+        assert!(matches!(actions[..], [(2, CellAction::SlowRelease)]));
+
+        // With the pedal down, release waits for the pedal
+        player.control_change(SUSTAIN_PEDAL_CC, 127);
+        assert!(player.handle_midi(&[0x90, 60, 0]).is_empty());
+        let actions = player.control_change(SUSTAIN_PEDAL_CC, 0);
+        assert!(matches!(actions[..], [(3, CellAction::SlowRelease)]));
+
+        // Voices must fit in the engine
+        assert!(LivePlayer::new(LiveOptions::default(), 3).is_err());
+    }
+
+    #[test]
+    fn test_controllers_and_pitch_bend() {
+        let mapping = parse_control_mapping("74=drive").unwrap();
+        assert_eq!(mapping, (74, "distortion".to_string()));
+        assert!(parse_control_mapping("74=volume").is_err());
+
+        let options = LiveOptions {
+            voices: 2,
+            midi_channel: Some(0),
+            control_changes: vec![mapping],
+            ..Default::default()
+        };
+        let mut player = LivePlayer::new(options, 2).unwrap();
+        player.note_on(60, 127);
+
+        // Turning the knob updates the voice that has played
+        let actions = player.handle_midi(&[0xB0, 74, 127]);
+        match &actions[..] {
+            [(0, CellAction::ChangeEffects { effects, .. })] => {
+                assert_eq!(effects.distortion_amount, 1.0);
+                assert_eq!(effects.amplitude, 1.0);
+            }
+            _ => panic!("expected one effect change"),
+        }
+
+        // Full bend up detunes by the bend range
+        let actions = player.handle_midi(&[0xE0, 0x7F, 0x7F]);
+        match &actions[..] {
+            [(0, CellAction::ChangeEffects { effects, .. })] => {
+                assert!((effects.detune_cents - 200.0).abs() < 0.1);
+            }
+            _ => panic!("expected one effect change"),
+        }
+
+        // New notes start with the current knob settings
+        match &player.note_on(62, 64)[..] {
+            [(1, CellAction::TriggerNote { effects, .. })] => {
+                assert_eq!(effects.distortion_amount, 1.0);
+            }
+            _ => panic!("expected a note on the second voice"),
+        }
+
+        // Other MIDI channels are ignored
+        assert!(player.handle_midi(&[0x91, 60, 100]).is_empty());
+    }
+
+    #[test]
+    fn test_computer_keyboard_layout() {
+        assert_eq!(computer_key_to_midi('z', DEFAULT_KEYBOARD_OCTAVE), Some(60));
+        assert_eq!(computer_key_to_midi('Q', DEFAULT_KEYBOARD_OCTAVE), Some(72));
+        assert_eq!(computer_key_to_midi(',', 3), Some(60));
+        assert_eq!(computer_key_to_midi('p', 5), Some(100));
+        assert_eq!(computer_key_to_midi('a', 4), None);
+        assert_eq!(computer_key_to_midi('p', 9), None);
+
+        // Every key is listed once
+        let mut keys: Vec<char> = COMPUTER_KEYBOARD_NOTES
+            .iter()
+            .map(|(key, _)| *key)
+            .collect();
+        keys.sort();
+        keys.dedup();
+        assert_eq!(keys.len(), COMPUTER_KEYBOARD_NOTES.len());
+    }
+
+    #[test]
+    fn test_event_log() {
+        let mut player = LivePlayer::new(LiveOptions::default(), 4).unwrap();
+        player.note_on(60, 127);
+        assert!(player.take_event_log().is_none());
+
+        // Once logging, notes and releases are kept as song cells
+        player.log_events();
+        player.note_on(64, 127);
+        player.note_off(64);
+        let cells: Vec<(usize, String)> = player
+            .take_event_log()
+            .unwrap()
+            .events
+            .into_iter()
+            .map(|event| (event.channel_index, event.cell))
+            .collect();
+        assert_eq!(
+            cells,
+            vec![(1, "e4 sine a:1 cl".to_string()), (1, String::new())]
+        );
+    }
+
+    #[test]
+    fn test_input_channel() {
+        let mut player = LivePlayer::new(LiveOptions::default(), 4).unwrap();
+        assert!(player.start_input().is_none());
+
+        // The input plays on the channel after the voices
+        let options = LiveOptions {
+            voices: 2,
+            input_cell: Some("input ds:0.3".to_string()),
+            ..Default::default()
+        };
+        let mut player = LivePlayer::new(options.clone(), 4).unwrap();
+        let (channel_index, action) = player.start_input().unwrap();
+        assert_eq!(channel_index, 2);
+        assert!(matches!(
+            action,
+            CellAction::TriggerPitchless { instrument_id, .. }
+                if instrument_id == crate::instruments::INPUT_INSTRUMENT_ID
+        ));
+
+        // ...so the voices can't take every channel
+        assert!(LivePlayer::new(options, 2).is_err());
+    }
+}
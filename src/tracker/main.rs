@@ -9,6 +9,10 @@
 // SYSTEM OVERVIEW:
 // ================
 //
+// Everything below lives in the musickbeets library (lib.rs), which other
+// programs can use too. This file is just the command-line player: it
+// picks a subcommand, and each one lives in its own file under commands/.
+//
 // 1. PARSER (parser.rs)
 //    Reads CSV song files and converts them into playable actions.
 //    Each row is a time step, each column is a channel/voice.
@@ -54,6 +58,74 @@
 // 3. Run: cargo run --release
 // 4. Listen to your creation!
 //
+// To check a song for mistakes without playing it (exits nonzero on errors):
+//    cargo run --release --bin tracker -- check assets/song.csv
+//
+// To see why a song sounds different since some commit, dump the engine's
+// state at a row before and after, and compare the two:
+//    cargo run --release --bin tracker -- snapshot assets/song.csv --row 32 -o after.json
+//    cargo run --release --bin tracker -- snapshot --diff before.json after.json
+//
+// To save a song as JSON (the player loads .json files too):
+//    cargo run --release --bin tracker -- convert assets/song.csv song.json
+//
+// To open a song in a DAW, save it as a MIDI file (notes, velocities, tempo):
+//    cargo run --release --bin tracker -- convert assets/song.csv song.mid
+//
+// To turn a MIDI file or ProTracker module into a CSV song:
+//    cargo run --release --bin tracker -- import song.mid -o song.csv
+//    cargo run --release --bin tracker -- import song.mod -o song.csv
+//
+// To play the synth from a MIDI keyboard (needs: --features midir):
+//    cargo run --release --features midir --bin tracker -- live --instrument trisaw
+// or from the computer keyboard (Z S X D C ... like classic trackers):
+//    cargo run --release --bin tracker -- live --keys --instrument trisaw
+// and keep the take (the sound, and optionally the cells played):
+//    cargo run --release --bin tracker -- live --keys --record take.wav --record-events take.csv
+// or step-record it into a new song, quantized to 16th notes at 100 BPM:
+//    cargo run --release --bin tracker -- live --keys --capture idea.csv --bpm 100
+// and play a guitar or mic through a channel's effects alongside the keys:
+//    cargo run --release --bin tracker -- live --keys --input-cell "input ds:0.3"
+//
+// To let other programs (TouchOSC, SuperCollider, scripts) play it over OSC:
+//    cargo run --release --bin tracker -- osc --port 9000
+// (add --input so "input" cells play the sound card's input)
+//
+// To edit a song in the terminal (Space plays the row under the cursor):
+//    cargo run --release --bin tracker -- edit assets/song.csv
+//
+// To watch levels, effects, and rows in a full-screen view while playing:
+//    cargo run --release --bin tracker -- assets/song.csv --tui
+// with a rolling spectrogram of the mix (is the bass fighting the kick?):
+//    cargo run --release --bin tracker -- assets/song.csv --monitor
+//
+// To render heavy channels once before playing, so a slow machine only
+// plays them back (counted from 1):
+//    cargo run --release --bin tracker -- assets/song.csv --freeze 3,4
+//
+// To see how hard the CPU works (load, xruns, and the cost of each effect,
+// printed when the song ends - handy on a Raspberry Pi):
+//    cargo run --release --bin tracker -- assets/song.csv --dsp-load
+//
+// To listen to only some channels (counted from 1), mute or solo them:
+//    cargo run --release --bin tracker -- assets/song.csv --mute 2,3 --solo 1
+//
+// To play (and repeat) just the rows you're working on:
+//    cargo run --release --bin tracker -- assets/song.csv --start-row 32 --end-row 64 --loop
+// or play an ambient piece forever, crossfading each time it comes round:
+//    cargo run --release --bin tracker -- ambient.csv --loop --crossfade 8
+//
+// To play a set of songs back to back from an M3U playlist, each loaded
+// while the one before plays and crossfading into the next:
+//    cargo run --release --bin tracker -- play set.m3u --crossfade 6
+//
+// To play a very long song while it is parsed (memory stays flat):
+//    cargo run --release --bin tracker -- long_song.csv --stream
+//
+// To sync with a drum machine over MIDI clock (needs: --features midir):
+//    cargo run --release --features midir --bin tracker -- assets/song.csv --clock-out 0
+//    cargo run --release --features midir --bin tracker -- assets/song.csv --clock-in 0
+//
 // HOW TO ADD INSTRUMENTS:
 // =======================
 // 1. Open src/instruments.rs
@@ -75,37 +147,18 @@
 //
 // ============================================================================
 
-// ============================================================================
-// MODULE DECLARATIONS
-// ============================================================================
-//
-// These statements tell Rust about our module files.
-// Each module is in its own file in the src/ directory.
-// ============================================================================
-
-mod audio;
-mod channel; // Per-channel synthesis and state
-mod effects; // Unified effects system (reverb, delay, chorus, etc.)
-mod engine; // Playback engine and sequencer
-mod envelope; // ADSR envelope system
-mod helper; // Math utilities, frequency table, shared algorithms
-mod instruments; // Sound generators (sine, square, noise, pulse, etc.)
-mod master_bus; // Master output bus and global effects
-mod parser; // CSV song file parser // WAV export and audio utilities
-
 // ============================================================================
 // EXTERNAL DEPENDENCIES
 // ============================================================================
 
-use miniaudio::{Context, Device, DeviceConfig, DeviceType, Format, Frames, FramesMut, RawDevice};
-use std::sync::{Arc, Mutex};
-use std::{env, fs, path::Path, thread, time::Duration};
+use std::env;
 
-// Import from our modules
-use crate::audio::{analyze_audio, generate_wav_filename, write_wav_file};
-use crate::engine::{EngineConfig, PlaybackEngine};
-use crate::helper::FrequencyTable;
-use crate::parser::{DebugLevel, MissingCellBehavior, parse_song};
+// Import from the tracker library (lib.rs); this binary is a thin player
+use musickbeets::parser::MissingCellBehavior;
+
+// The subcommands (commands/mod.rs lists them)
+mod commands;
+use commands::{check, convert, edit, import, live, osc, play, snapshot};
 
 // ============================================================================
 // CONFIGURATION
@@ -124,10 +177,11 @@ const SONG_FILE_PATH: &str = "assets/song.csv";
 
 /// Sample rate in Hz (48000 is CD quality, 44100 is also common)
 /// Higher = better quality but more CPU usage
-const SAMPLE_RATE: u32 = 48000;
+/// "--sample-rate <hz>" changes it for one run (e.g. 44100 or 96k)
+const DEFAULT_SAMPLE_RATE: u32 = 48000;
 
-/// Number of audio channels (voices) to use
-/// Each column in the CSV is one channel
+/// Number of audio channels (voices) for live play, OSC, and the editor
+/// Songs get one channel per CSV column instead (see AUTO_CHANNEL_COUNT)
 /// More channels = more polyphony but more CPU usage
 const CHANNEL_COUNT: usize = 12;
 
@@ -142,10 +196,55 @@ const TICK_DURATION_SECONDS: f32 = 0.25;
 /// For heavy reverb/delay, use 2048 or higher
 const AUDIO_BUFFER_SIZE: u32 = 4096;
 
+/// Audio buffer size for live play ("tracker live")
+/// Smaller = less delay between pressing a key and hearing it
+/// 256 = 5ms, 512 = 11ms at 48kHz; raise it if the sound crackles
+const LIVE_BUFFER_SIZE: u32 = 512;
+
+// ---- Computer Keyboard Settings ("tracker live --keys") ----
+
+/// Velocity (1-127) of notes played on the computer keyboard
+/// Left/Right arrows change it while playing
+const KEYBOARD_VELOCITY: u8 = 100;
+
+/// How long a key press sounds in terminals that don't report key
+/// releases (holding the key down keeps it going through key repeat)
+const KEYBOARD_NOTE_SECONDS: f32 = 0.6;
+
+/// Tempo of the row grid "--capture" quantizes live notes to, unless
+/// "--bpm" says otherwise (4 rows per beat, or "--rows-per-beat")
+const CAPTURE_BPM: f32 = 120.0;
+
+// ---- MIDI Clock Settings ("--clock-in" / "--clock-out") ----
+
+/// Rows in one beat when following MIDI clock (24 pulses per beat, so
+/// 4 rows = 6 pulses per row); must divide 24
+#[cfg(feature = "midir")]
+const MIDI_CLOCK_ROWS_PER_BEAT: u32 = 4;
+
+/// Longest wait after the last row for release, reverb, and delay tails
+/// (playback stops as soon as everything is silent)
+const RELEASE_TAIL_SECONDS: f32 = 10.0;
+
+/// How long the music fades out when playback is stopped with Ctrl+C
+const STOP_FADE_SECONDS: f32 = 0.3;
+
+/// How long --loop crossfades from the end of the loop back into its
+/// start (reverb and delay tails carry over); 0 jumps straight back
+/// "--crossfade <seconds>" changes it for one run
+const LOOP_CROSSFADE_SECONDS: f32 = 2.0;
+
+/// How long each song of a playlist crossfades into the next (the old
+/// song's tails and the new song's start overlap); 0 starts the next song
+/// at full level while the old one's release tails ring out under it
+/// "--crossfade <seconds>" changes it for one run
+const PLAYLIST_CROSSFADE_SECONDS: f32 = 4.0;
+
 // ---- Envelope Settings ----
 
 /// Default release time for slow release / empty cells (seconds)
 /// How long it takes for a note to fade to silence
+/// A song can set its own with "release = 1.0" in a [config] block
 const DEFAULT_RELEASE_SECONDS: f32 = 2.0;
 
 /// Fast release time for '.' command (seconds)
@@ -154,19 +253,20 @@ const FAST_RELEASE_SECONDS: f32 = 0.05;
 
 // ---- Parser Settings ----
 
-/// What to do when a CSV row has fewer cells than CHANNEL_COUNT
+/// What to do when a CSV row has fewer cells than the song has columns
 /// Sustain = keep playing the current note
 /// SlowRelease = fade out the current note
+/// A song can choose for itself with "missing = sustain" in a [config] block
 const MISSING_CELL_BEHAVIOR: MissingCellBehavior = MissingCellBehavior::SlowRelease;
 
-// ---- Debug Settings ----
+// ---- Log Settings ----
 
-/// How much debug output to show
-/// Off = silent (production mode)
-/// Basic = parsing info, playback status, errors only
-/// Verbose = + channel activity, effect changes
-/// Detailed = + every token parsed, internal state (very noisy)
-const DEBUG_LEVEL: DebugLevel = DebugLevel::Basic;
+/// Which log messages to show when RUST_LOG is not set
+/// Messages are grouped by target: "parser", "engine", "audio", and "osc".
+/// Examples: RUST_LOG=parser=debug   every row and preset the parser reads
+///           RUST_LOG=trace          everything, including each cell (very noisy)
+///           RUST_LOG=warn           errors and warnings only
+const DEFAULT_LOG_FILTER: &str = "info";
 
 // ---- WAV Export Settings ----
 
@@ -183,6 +283,10 @@ const NORMALIZE_WAV: bool = true;
 /// 0.9 leaves a bit of headroom, 1.0 uses full range
 const NORMALIZE_TARGET_PEAK: f32 = 0.9;
 
+/// Whether to add TPDF dither when rounding the WAV to 16 bits
+/// Keeps quiet fades and reverb tails smooth instead of gritty
+const DITHER_WAV: bool = true;
+
 // ---- Validate-Only Mode ----
 
 /// If true, just parse the song and report errors, don't play
@@ -194,283 +298,57 @@ const VALIDATE_ONLY: bool = false;
 // ============================================================================
 
 fn main() {
-    // Print welcome banner
-    println!("\n╔═══════════════════════════════════════════════════════════╗");
-    println!("║   muSickBeets - CSV-Driven Music Tracker Synthesizer      ║");
-    println!("║   Version 2.0 - Modular Architecture                      ║");
-    println!("╚═══════════════════════════════════════════════════════════╝\n");
-
-    // ---- Parse Command Line Arguments ----
-    // Usage: tracker [song_file.csv]
-    let args: Vec<String> = env::args().collect();
-    let song_path = if args.len() > 1 {
-        &args[1]
-    } else {
-        SONG_FILE_PATH
-    };
-
-    println!("[MAIN] Song file: {}", song_path);
-    println!("[MAIN] Sample rate: {} Hz", SAMPLE_RATE);
-    println!("[MAIN] Channels: {}", CHANNEL_COUNT);
-    println!("[MAIN] Tick duration: {:.3}s", TICK_DURATION_SECONDS);
-    println!("[MAIN] Debug level: {:?}", DEBUG_LEVEL);
-
-    // ---- Load Song File ----
-    let song_text = match fs::read_to_string(song_path) {
-        Ok(text) => {
-            println!("[MAIN] Loaded song file ({} bytes)", text.len());
-            text
-        }
-        Err(error) => {
-            eprintln!(
-                "[ERROR] Failed to read song file '{}': {}",
-                song_path, error
-            );
-            eprintln!("[HINT] Make sure the file exists and is readable.");
-            eprintln!("[HINT] Usage: tracker [song_file.csv]");
-            return;
-        }
-    };
-
-    // ---- Initialize Frequency Table ----
-    // Pre-compute all note frequencies for fast lookup during playback
-    println!("[MAIN] Building frequency table (octaves 0-20)...");
-    let frequency_table = FrequencyTable::new();
-
-    // ---- Parse Song ----
-    println!("[MAIN] Parsing song...");
-    let song_data = parse_song(
-        &song_text,
-        &frequency_table,
-        CHANNEL_COUNT,
-        MISSING_CELL_BEHAVIOR,
-        DEBUG_LEVEL,
-    );
-
-    // Report parsing results
-    println!(
-        "[MAIN] Parsed {} rows, {} errors",
-        song_data.row_count(),
-        song_data.errors.len()
-    );
-
-    // Print any errors
-    if !song_data.errors.is_empty() {
-        println!("\n[PARSER MESSAGES]");
-        song_data.print_errors();
-        println!();
-    }
-
-    // Check for fatal errors
-    if song_data.has_fatal_errors() {
-        eprintln!("[ERROR] Fatal parsing errors encountered. Cannot play.");
-        return;
-    }
-
-    // Validate-only mode
-    if VALIDATE_ONLY {
-        println!("[MAIN] Validate-only mode - parsing complete.");
-        if song_data.errors.is_empty() {
-            println!("[MAIN] No errors found! Song is valid.");
-        } else {
-            println!("[MAIN] Found {} warnings/errors.", song_data.errors.len());
-        }
-        return;
-    }
-
-    // Check for empty song
-    if song_data.row_count() == 0 {
-        eprintln!("[ERROR] Song has no rows to play!");
-        return;
-    }
-
-    // ---- Apply Song Configuration Overrides ----
-    // Settings from the config row in the CSV file override the defaults
-    let tick_duration = song_data
-        .config
-        .tick_duration
-        .unwrap_or(TICK_DURATION_SECONDS);
-    let export_wav = song_data.config.export_wav.unwrap_or(EXPORT_TO_WAV);
-    let normalize_wav = song_data.config.normalize_wav.unwrap_or(NORMALIZE_WAV);
-
-    // Print config overrides if any were found
-    if song_data.config.has_any_settings() {
-        println!("[MAIN] Song configuration overrides:");
-        if let Some(title) = &song_data.config.title {
-            println!("[MAIN]   Title: {}", title);
-        }
-        if song_data.config.tick_duration.is_some() {
-            println!("[MAIN]   Tick duration: {:.3}s (overridden)", tick_duration);
-        }
-        if song_data.config.export_wav.is_some() {
-            println!("[MAIN]   Export WAV: {} (overridden)", export_wav);
-        }
-        if song_data.config.normalize_wav.is_some() {
-            println!("[MAIN]   Normalize WAV: {} (overridden)", normalize_wav);
-        }
-        if let Some(bpm) = song_data.config.tempo_bpm {
-            println!("[MAIN]   Tempo: {} BPM", bpm);
-        }
-    }
-
-    // ---- Create Engine Configuration ----
-    let engine_config = EngineConfig {
-        sample_rate: SAMPLE_RATE,
-        channel_count: CHANNEL_COUNT,
-        tick_duration_seconds: tick_duration,
-        default_release_seconds: DEFAULT_RELEASE_SECONDS,
-        fast_release_seconds: FAST_RELEASE_SECONDS,
-        debug_level: DEBUG_LEVEL,
-    };
-
-    // Calculate duration
-    let total_duration_seconds = song_data.row_count() as f32 * tick_duration;
-    println!(
-        "[MAIN] Song duration: {:.2}s ({} rows)",
-        total_duration_seconds,
-        song_data.row_count()
-    );
-
-    // ---- WAV Export (if enabled) ----
-    // When export_wav is true, we export first, then also play
-    if export_wav {
-        export_to_wav(
-            song_data.clone(),
-            engine_config.clone(),
-            song_path,
-            normalize_wav,
-        );
-    }
-
-    // ---- Real-Time Playback ----
-    // Always play the song (after exporting, if export was enabled)
-    play_realtime(song_data, engine_config, total_duration_seconds);
-}
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(DEFAULT_LOG_FILTER))
+        .format_timestamp(None)
+        .init();
 
-/// Exports the song to a WAV file
-fn export_to_wav(
-    song_data: crate::parser::SongData,
-    engine_config: EngineConfig,
-    song_path: &str,
-    normalize_wav: bool,
-) {
-    println!("\n[EXPORT] Rendering to WAV...");
-
-    // Create engine and render
-    let mut engine = PlaybackEngine::new(song_data, engine_config.clone());
-    let mut samples = engine.render_to_buffer();
-
-    // Analyze
-    let stats = analyze_audio(&samples, engine_config.sample_rate);
-    println!(
-        "[EXPORT] Rendered {} samples ({:.2}s)",
-        stats.sample_count, stats.duration_seconds
-    );
-    println!("[EXPORT] Peak amplitude: {:.3}", stats.peak_amplitude);
-    println!("[EXPORT] RMS amplitude: {:.3}", stats.rms_amplitude);
-
-    if stats.clipped_samples > 0 {
-        println!("[WARNING] {} samples clipped!", stats.clipped_samples);
-    }
-
-    // Normalize if requested
-    if normalize_wav {
-        let gain = crate::audio::normalize_audio(&mut samples, NORMALIZE_TARGET_PEAK);
-        println!("[EXPORT] Normalized with gain: {:.3}", gain);
-    }
-
-    // Generate output filename
-    let wav_path = generate_wav_filename(song_path);
-    println!("[EXPORT] Writing to: {}", wav_path);
-
-    // Write WAV file
-    match write_wav_file(
-        Path::new(&wav_path),
-        &samples,
-        engine_config.sample_rate,
-        false,
-    ) {
-        Ok(()) => {
-            println!("[EXPORT] Successfully wrote WAV file!");
-        }
-        Err(error) => {
-            eprintln!("[ERROR] Failed to write WAV: {}", error);
-        }
-    }
-}
+    let args: Vec<String> = env::args().collect();
 
-/// Plays the song in real-time
-fn play_realtime(
-    song_data: crate::parser::SongData,
-    engine_config: EngineConfig,
-    total_duration_seconds: f32,
-) {
-    // Create the playback engine wrapped in Arc<Mutex> for thread safety
-    let engine = Arc::new(Mutex::new(PlaybackEngine::new(song_data, engine_config)));
-    let engine_for_callback = Arc::clone(&engine);
-
-    // ---- Initialize Audio Device ----
-    println!("\n[AUDIO] Initializing miniaudio...");
-
-    let audio_context = match Context::new(&[], None) {
-        Ok(ctx) => ctx,
-        Err(error) => {
-            eprintln!("[ERROR] Failed to create audio context: {:?}", error);
+    // Usage: tracker [play] [song_file.csv|set.m3u] [--a4 <hz>] [--song <name>[,<name>...]]
+    //                [--backend <name>] [--clock-out <port>] [--clock-in <port>] [--tui] [--monitor]
+    //                [--mute <n>[,<n>...]] [--solo <n>[,<n>...]]
+    //                [--start-row <n>] [--end-row <n>] [--loop] [--crossfade <seconds>]
+    //                [--stream] [--sample-rate <hz>] [--dsp-load] [--freeze <n>[,<n>...]]
+    //        tracker check <song_file.csv>...
+    //        tracker convert <song_file.csv> <song_file.json|song_file.mid>
+    //        tracker import <song.mid|song.mod> [-o <song.csv>]
+    //        tracker live [--keys] [--port <n|name>] [--instrument <name>] [--cc <n>=<effect>]
+    //        tracker osc [song_file.csv] [--port <n>]
+    //        tracker edit <song_file.csv>
+    //        tracker snapshot <song_file.csv> --row <n> [-o <state.json>] [--sample-rate <hz>]
+    //        tracker snapshot --diff <before.json> <after.json>
+    let exit_code = match args.get(1).map(String::as_str) {
+        // "tracker check song.csv" only reports problems (no banner, no audio)
+        Some("check") => check::run(&args[2..]),
+
+        // "tracker convert song.csv song.json" saves the parsed song as JSON
+        // (or as a MIDI file when the output ends in .mid)
+        Some("convert") => convert::run(&args[2..]),
+
+        // "tracker import song.mid -o song.csv" converts a MIDI or MOD file to CSV
+        Some("import") => import::run(&args[2..]),
+
+        // "tracker live" plays the synth from a MIDI keyboard ("--keys": computer keyboard)
+        Some("live") => live::run(&args[2..]),
+
+        // "tracker edit song.csv" opens the song in the terminal pattern editor
+        Some("edit") => edit::run(&args[2..]),
+
+        // "tracker osc" lets other programs play the engine over the network
+        Some("osc") => osc::run(&args[2..]),
+
+        // "tracker snapshot song.csv --row 32" dumps the engine state as JSON
+        Some("snapshot") => snapshot::run(&args[2..]),
+
+        // "tracker play set.m3u" reads the same as "tracker set.m3u"
+        Some("play") => {
+            play::run(&args[2..]);
             return;
         }
-    };
-
-    // Configure audio device
-    let mut device_config = DeviceConfig::new(DeviceType::Playback);
-    device_config.playback_mut().set_format(Format::F32);
-    device_config.playback_mut().set_channels(2);
-    device_config.set_sample_rate(SAMPLE_RATE);
-    device_config.set_period_size_in_frames(AUDIO_BUFFER_SIZE);
-
-    // Set up the audio callback
-    // This function is called by the audio driver when it needs more samples
-    device_config.set_data_callback(
-        move |_device: &RawDevice, output_buffer: &mut FramesMut, _input_buffer: &Frames| {
-            // Get the output buffer as f32 samples
-            let samples = output_buffer.as_samples_mut::<f32>();
-
-            // Lock the engine and process
-            if let Ok(mut engine_guard) = engine_for_callback.lock() {
-                engine_guard.process_frame(samples);
-            }
-        },
-    );
-
-    // Create the audio device
-    let audio_device: Device = match Device::new(Some(audio_context), &device_config) {
-        Ok(device) => device,
-        Err(error) => {
-            eprintln!("[ERROR] Failed to create audio device: {:?}", error);
+        _ => {
+            play::run(&args[1..]);
             return;
         }
     };
-
-    // ---- Start Playback ----
-    println!("[AUDIO] Starting playback...");
-
-    if let Err(error) = audio_device.start() {
-        eprintln!("[ERROR] Failed to start audio device: {:?}", error);
-        return;
-    }
-
-    println!(
-        "\n▶ PLAYING... (duration: {:.2}s)\n",
-        total_duration_seconds
-    );
-
-    // Wait for playback to finish
-    // Add extra time for release tails
-    let wait_time = total_duration_seconds + 2.0;
-    thread::sleep(Duration::from_secs_f32(wait_time));
-
-    // ---- Cleanup ----
-    println!("\n[MAIN] Playback finished!");
-    println!("╔═══════════════════════════════════════════════════════════╗");
-    println!("║                THANK YOU FOR LISTENING!                   ║");
-    println!("╚═══════════════════════════════════════════════════════════╝\n");
+    std::process::exit(exit_code);
 }
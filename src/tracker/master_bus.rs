@@ -22,11 +22,95 @@
 // TRANSITIONS:
 // Like channel effects, master effects can transition smoothly to avoid clicks.
 // This allows for things like fading the entire mix to silence.
+//
+// BYPASS AND DRY/WET:
+// "rv:off" fades an effect out of the signal without touching its settings
+// ("rv:on" brings it back), and "wet:0" fades the whole chain out so you
+// hear the dry mix. Both fade over tr: (at least BYPASS_FADE_SECONDS), so
+// A/B-ing the processed and dry mix is one cell edit and never clicks.
+//
+// TEMPO-SYNCED DELAY:
+// "dl:1/8.'0.4" sets the delay time as a note length. The parser sends it
+// as "dl:sync" with the length in beats; the bus keeps the beats and works
+// out the time again whenever the engine changes its tempo (set_tempo),
+// so the echoes stay on the beat when the BPM moves.
+//
+// TAILS:
+// Reverb and delay keep sounding after the channels stop. The bus counts
+// how long its output has stayed below SILENCE_LEVEL; once that is longer
+// than anything its effects can hold back (the delay time, the longest
+// reverb line), nothing is left in their buffers worth hearing.
 // ============================================================================
 
-use crate::effects::{MasterEffectState, apply_master_effects};
+use crate::channel::DEFAULT_SECONDS_PER_BEAT;
+use crate::dsp_load::StageTimer;
+use crate::effects::{
+    MasterEffectState, apply_master_effects, apply_master_effects_block, flush_denormal,
+};
 use crate::helper::lerp;
 
+/// Output quieter than this counts as silence (-80 dB)
+pub const SILENCE_LEVEL: f32 = 0.0001;
+
+/// Shortest fade when an effect is bypassed or brought back
+pub const BYPASS_FADE_SECONDS: f32 = 0.05;
+
+/// Corner frequency of the DC blocker on the final output
+/// Far below anything audible, so only the offset is removed
+pub const DC_BLOCKER_HZ: f32 = 10.0;
+
+// ============================================================================
+// DC BLOCKER
+// ============================================================================
+//
+// Bitcrushing and asymmetric distortion can leave the mix sitting above or
+// below zero. That offset is inaudible, but it wastes headroom, makes the
+// first and last samples of a render click, and shows up as a big bin 0 in
+// the spectrogram. The engine runs its final output through one of these
+// per side, after the master bus.
+//
+// It is the classic one-pole, one-zero high-pass:
+//   y[n] = x[n] - x[n-1] + R * y[n-1]
+// with R just below 1, so it passes everything except the slowest drift.
+// ============================================================================
+
+/// Removes DC offset from one channel of audio
+#[derive(Clone, Debug)]
+pub struct DcBlocker {
+    /// How much of the previous output is kept (just below 1.0)
+    coefficient: f32,
+
+    /// Last input sample
+    previous_input: f32,
+
+    /// Last output sample
+    previous_output: f32,
+}
+
+impl DcBlocker {
+    /// Creates a DC blocker with its corner at DC_BLOCKER_HZ
+    pub fn new(sample_rate: u32) -> Self {
+        let coefficient =
+            (1.0 - std::f32::consts::TAU * DC_BLOCKER_HZ / sample_rate as f32).clamp(0.0, 1.0);
+        Self {
+            coefficient,
+            previous_input: 0.0,
+            previous_output: 0.0,
+        }
+    }
+
+    /// Filters a block of samples in place
+    pub fn process_block(&mut self, samples: &mut [f32]) {
+        for sample in samples {
+            let input = *sample;
+            let output = input - self.previous_input + self.coefficient * self.previous_output;
+            self.previous_input = input;
+            self.previous_output = flush_denormal(output);
+            *sample = output;
+        }
+    }
+}
+
 // ============================================================================
 // MASTER TRANSITION STATE
 // ============================================================================
@@ -44,6 +128,15 @@ pub struct MasterTransitionState {
     /// Starting pan position
     pub pan: f32,
 
+    /// Starting dry/wet balance of the whole chain
+    pub wet: f32,
+
+    /// Starting levels of each effect (0 = bypassed)
+    pub reverb1_level: f32,
+    pub reverb2_level: f32,
+    pub delay_level: f32,
+    pub chorus_level: f32,
+
     /// Starting reverb 1 room size
     pub reverb1_room_size: f32,
 
@@ -93,6 +186,11 @@ impl MasterTransitionState {
         Self {
             amplitude: effects.amplitude,
             pan: effects.pan,
+            wet: effects.wet,
+            reverb1_level: effects.reverb1_level,
+            reverb2_level: effects.reverb2_level,
+            delay_level: effects.delay_level,
+            chorus_level: effects.chorus_level,
             reverb1_room_size: effects.reverb1_room_size,
             reverb1_mix: effects.reverb1_mix,
             reverb1_enabled: effects.reverb1_enabled,
@@ -138,6 +236,18 @@ pub struct MasterBus {
 
     /// Target state for the transition
     pub transition_target: MasterTransitionState,
+
+    /// How many samples in a row the output has been below SILENCE_LEVEL
+    pub quiet_samples: u32,
+
+    /// Length of a beat, for a delay time set as a note length
+    pub seconds_per_beat: f32,
+
+    /// The delay time in beats, while it follows the tempo
+    pub delay_sync_beats: Option<f32>,
+
+    /// Time spent in each effect, when measured (see dsp_load.rs)
+    pub stage_timer: StageTimer,
 }
 
 impl MasterBus {
@@ -157,6 +267,10 @@ impl MasterBus {
             transition_elapsed_samples: 0,
             transition_start: default_transition.clone(),
             transition_target: default_transition,
+            quiet_samples: 0,
+            seconds_per_beat: DEFAULT_SECONDS_PER_BEAT,
+            delay_sync_beats: None,
+            stage_timer: StageTimer::default(),
         }
     }
 
@@ -171,16 +285,79 @@ impl MasterBus {
     pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
         // Update transition if one is active
         if self.transition_active {
-            self.update_transition();
+            self.update_transition(1);
         }
 
         // Apply all master effects
-        apply_master_effects(left, right, &mut self.effects, self.sample_rate)
+        let (left, right) = apply_master_effects(left, right, &mut self.effects, self.sample_rate);
+        self.track_quiet(&[left], &[right]);
+        (left, right)
     }
 
-    /// Updates the master bus transition (called each sample)
-    fn update_transition(&mut self) {
-        self.transition_elapsed_samples += 1;
+    /// Processes a block of stereo samples in place
+    /// The transition is updated once per block instead of every sample.
+    pub fn process_block(&mut self, left: &mut [f32], right: &mut [f32]) {
+        if self.transition_active {
+            self.update_transition(left.len().min(right.len()) as u32);
+        }
+
+        apply_master_effects_block(
+            left,
+            right,
+            &mut self.effects,
+            self.sample_rate,
+            &mut self.stage_timer,
+        );
+        self.track_quiet(left, right);
+    }
+
+    /// Counts quiet output samples, starting over at any louder sample
+    fn track_quiet(&mut self, left: &[f32], right: &[f32]) {
+        let loud = left
+            .iter()
+            .chain(right)
+            .any(|sample| sample.abs() > SILENCE_LEVEL);
+        self.quiet_samples = if loud {
+            0
+        } else {
+            self.quiet_samples.saturating_add(left.len() as u32)
+        };
+    }
+
+    /// Longest time sound can sit in the effect buffers before it comes
+    /// out again, in samples
+    fn effect_memory_samples(&self) -> u32 {
+        let effects = &self.effects;
+        let mut memory = 0;
+        if effects.reverb1_enabled {
+            // Same delay as reverb 1 itself uses (effects/mod.rs)
+            let delay = effects.reverb1_room_size * self.sample_rate as f32 * 0.05;
+            memory = memory.max(delay as u32 + 1);
+        }
+        if effects.reverb2_enabled {
+            let predelay = effects.reverb2_predelay_ms / 1000.0 * self.sample_rate as f32;
+            let longest_line = effects.reverb2_right.longest_line();
+            memory = memory.max(longest_line as u32 + predelay as u32);
+        }
+        if effects.delay_enabled {
+            memory = memory.max(effects.delay_time_samples);
+        }
+        if effects.chorus_enabled {
+            memory = memory.max(effects.chorus_buffer_left.len() as u32);
+        }
+        memory
+    }
+
+    /// Returns true once the output has been silent for longer than any
+    /// effect can hold sound back, so reverb and delay tails are over
+    /// (only meaningful once nothing more is coming in)
+    pub fn is_quiet(&self) -> bool {
+        self.quiet_samples > self.effect_memory_samples()
+    }
+
+    /// Moves the master bus transition forward by a number of samples
+    fn update_transition(&mut self, samples: u32) {
+        self.transition_elapsed_samples += samples;
 
         // Calculate progress (0.0 to 1.0)
         let progress = if self.transition_duration_samples > 0 {
@@ -203,6 +380,36 @@ impl MasterBus {
             progress,
         );
 
+        self.effects.wet = lerp(
+            self.transition_start.wet,
+            self.transition_target.wet,
+            progress,
+        );
+
+        self.effects.reverb1_level = lerp(
+            self.transition_start.reverb1_level,
+            self.transition_target.reverb1_level,
+            progress,
+        );
+
+        self.effects.reverb2_level = lerp(
+            self.transition_start.reverb2_level,
+            self.transition_target.reverb2_level,
+            progress,
+        );
+
+        self.effects.delay_level = lerp(
+            self.transition_start.delay_level,
+            self.transition_target.delay_level,
+            progress,
+        );
+
+        self.effects.chorus_level = lerp(
+            self.transition_start.chorus_level,
+            self.transition_target.chorus_level,
+            progress,
+        );
+
         self.effects.reverb1_room_size = lerp(
             self.transition_start.reverb1_room_size,
             self.transition_target.reverb1_room_size,
@@ -266,10 +473,12 @@ impl MasterBus {
         // Check if transition is complete
         if progress >= 1.0 {
             // Apply final enabled states (these don't interpolate)
-            self.effects.reverb1_enabled = self.transition_target.reverb1_enabled;
-            self.effects.reverb2_enabled = self.transition_target.reverb2_enabled;
-            self.effects.delay_enabled = self.transition_target.delay_enabled;
-            self.effects.chorus_enabled = self.transition_target.chorus_enabled;
+            self.effects.set_enabled(
+                self.transition_target.reverb1_enabled,
+                self.transition_target.reverb2_enabled,
+                self.transition_target.delay_enabled,
+                self.transition_target.chorus_enabled,
+            );
 
             self.transition_active = false;
         }
@@ -280,6 +489,7 @@ impl MasterBus {
     /// Parameters:
     /// - transition_seconds: How long to take for the transition (0 = instant)
     pub fn clear_effects(&mut self, transition_seconds: f32) {
+        self.delay_sync_beats = None;
         if transition_seconds > 0.0 {
             // Save current state as start
             self.transition_start = MasterTransitionState::from_master_effects(&self.effects);
@@ -288,6 +498,11 @@ impl MasterBus {
             self.transition_target = MasterTransitionState {
                 amplitude: 1.0,
                 pan: 0.0,
+                wet: 1.0,
+                reverb1_level: 1.0,
+                reverb2_level: 1.0,
+                delay_level: 1.0,
+                chorus_level: 1.0,
                 reverb1_room_size: 0.5,
                 reverb1_mix: 0.0,
                 reverb1_enabled: false,
@@ -312,10 +527,12 @@ impl MasterBus {
             // Instant clear
             self.effects.amplitude = 1.0;
             self.effects.pan = 0.0;
-            self.effects.reverb1_enabled = false;
-            self.effects.reverb2_enabled = false;
-            self.effects.delay_enabled = false;
-            self.effects.chorus_enabled = false;
+            self.effects.wet = 1.0;
+            self.effects.reverb1_level = 1.0;
+            self.effects.reverb2_level = 1.0;
+            self.effects.delay_level = 1.0;
+            self.effects.chorus_level = 1.0;
+            self.effects.set_enabled(false, false, false, false);
             self.transition_active = false;
         }
     }
@@ -418,18 +635,17 @@ impl MasterBus {
             // ---- Delay ----
             "dl" | "delay" => {
                 if parameters.len() >= 2 {
-                    let delay_time_seconds = parameters[0].clamp(0.01, 2.0);
-                    let feedback = parameters[1].clamp(0.0, 0.95);
-                    let delay_samples = (delay_time_seconds * self.sample_rate as f32) as u32;
+                    self.delay_sync_beats = None;
+                    self.set_delay(parameters[0], parameters[1], transition_seconds);
+                }
+            }
 
-                    self.apply_with_transition(
-                        |target| {
-                            target.delay_time_samples = delay_samples;
-                            target.delay_feedback = feedback;
-                            target.delay_enabled = feedback > 0.0;
-                        },
-                        transition_seconds,
-                    );
+            // Delay time as a note length: beats, feedback (see the top of the file)
+            "dl:sync" => {
+                if parameters.len() >= 2 {
+                    self.delay_sync_beats = Some(parameters[0]);
+                    let delay_time_seconds = parameters[0] * self.seconds_per_beat;
+                    self.set_delay(delay_time_seconds, parameters[1], transition_seconds);
                 }
             }
 
@@ -471,12 +687,72 @@ impl MasterBus {
                 self.effects.chorus_stereo_spread = spread;
             }
 
+            // ---- Dry/wet for the whole chain ----
+            "wet" => {
+                if !parameters.is_empty() {
+                    let wet = parameters[0].clamp(0.0, 1.0);
+                    self.apply_with_transition(
+                        |target| {
+                            target.wet = wet;
+                        },
+                        transition_seconds.max(BYPASS_FADE_SECONDS),
+                    );
+                }
+            }
+
+            // ---- Bypass (the parser writes "rv:off", "dl:on", ...) ----
+            "rv:off" | "rv:on" | "rv2:off" | "rv2:on" | "dl:off" | "dl:on" | "ch:off" | "ch:on" => {
+                let level = if effect_name.ends_with(":on") {
+                    1.0
+                } else {
+                    0.0
+                };
+                self.apply_with_transition(
+                    |target| match effect_name.split(':').next() {
+                        Some("rv") => target.reverb1_level = level,
+                        Some("rv2") => target.reverb2_level = level,
+                        Some("dl") => target.delay_level = level,
+                        _ => target.chorus_level = level,
+                    },
+                    transition_seconds.max(BYPASS_FADE_SECONDS),
+                );
+            }
+
             _ => {
                 // Unknown effect - ignore silently or could log warning
             }
         }
     }
 
+    /// Sets the delay time and feedback ("dl:0.25'0.4")
+    fn set_delay(&mut self, delay_time_seconds: f32, feedback: f32, transition_seconds: f32) {
+        let delay_time_seconds = delay_time_seconds.clamp(0.01, 2.0);
+        let feedback = feedback.clamp(0.0, 0.95);
+        let delay_samples = (delay_time_seconds * self.sample_rate as f32) as u32;
+
+        self.apply_with_transition(
+            |target| {
+                target.delay_time_samples = delay_samples;
+                target.delay_feedback = feedback;
+                target.delay_enabled = feedback > 0.0;
+            },
+            transition_seconds,
+        );
+    }
+
+    /// Sets the tempo for a delay time set as a note length, and moves a
+    /// delay that follows the tempo to the new time (the delay glides there)
+    pub fn set_tempo(&mut self, seconds_per_beat: f32) {
+        self.seconds_per_beat = seconds_per_beat;
+        if let Some(beats) = self.delay_sync_beats {
+            let delay_time_seconds = (beats * seconds_per_beat).clamp(0.01, 2.0);
+            let delay_samples = (delay_time_seconds * self.sample_rate as f32) as u32;
+            self.effects.delay_time_samples = delay_samples;
+            self.transition_start.delay_time_samples = delay_samples;
+            self.transition_target.delay_time_samples = delay_samples;
+        }
+    }
+
     /// Helper function to apply a change with optional transition
     fn apply_with_transition<F>(&mut self, modify_target: F, transition_seconds: f32)
     where
@@ -505,20 +781,27 @@ impl MasterBus {
             // Apply directly to effects
             self.effects.amplitude = immediate.amplitude;
             self.effects.pan = immediate.pan;
+            self.effects.wet = immediate.wet;
+            self.effects.reverb1_level = immediate.reverb1_level;
+            self.effects.reverb2_level = immediate.reverb2_level;
+            self.effects.delay_level = immediate.delay_level;
+            self.effects.chorus_level = immediate.chorus_level;
             self.effects.reverb1_room_size = immediate.reverb1_room_size;
             self.effects.reverb1_mix = immediate.reverb1_mix;
-            self.effects.reverb1_enabled = immediate.reverb1_enabled;
             self.effects.reverb2_room_size = immediate.reverb2_room_size;
             self.effects.reverb2_decay = immediate.reverb2_decay;
             self.effects.reverb2_damping = immediate.reverb2_damping;
             self.effects.reverb2_mix = immediate.reverb2_mix;
-            self.effects.reverb2_enabled = immediate.reverb2_enabled;
             self.effects.delay_time_samples = immediate.delay_time_samples;
             self.effects.delay_feedback = immediate.delay_feedback;
-            self.effects.delay_enabled = immediate.delay_enabled;
             self.effects.chorus_mix = immediate.chorus_mix;
             self.effects.chorus_rate_hz = immediate.chorus_rate_hz;
-            self.effects.chorus_enabled = immediate.chorus_enabled;
+            self.effects.set_enabled(
+                immediate.reverb1_enabled,
+                immediate.reverb2_enabled,
+                immediate.delay_enabled,
+                immediate.chorus_enabled,
+            );
         }
     }
 }
@@ -539,6 +822,22 @@ mod tests {
         assert_eq!(bus.effects.pan, 0.0);
     }
 
+    #[test]
+    fn test_dc_blocker_removes_offset() {
+        // A sine sitting 0.3 above zero, like a mix after asymmetric distortion
+        let mut samples: Vec<f32> = (0..48000)
+            .map(|i| 0.3 + 0.5 * (i as f32 * 440.0 * std::f32::consts::TAU / 48000.0).sin())
+            .collect();
+        DcBlocker::new(48000).process_block(&mut samples);
+
+        // After half a second the offset is gone but the tone is still there
+        let tail = &samples[24000..];
+        let mean = tail.iter().sum::<f32>() / tail.len() as f32;
+        let peak = tail.iter().fold(0.0f32, |peak, x| peak.max(x.abs()));
+        assert!(mean.abs() < 0.001, "offset left: {}", mean);
+        assert!((peak - 0.5).abs() < 0.01, "tone changed: {}", peak);
+    }
+
     #[test]
     fn test_master_bus_process() {
         let mut bus = MasterBus::new(48000);
@@ -571,4 +870,152 @@ mod tests {
         bus.clear_effects(0.0);
         assert!(!bus.effects.reverb1_enabled);
     }
+
+    #[test]
+    fn test_bypass_and_dry_wet() {
+        let mut bus = MasterBus::new(1000);
+        bus.apply_effect("dl", &[0.1, 0.5], 0.0);
+        bus.apply_effect("dl:off", &[], 0.0);
+        assert!(bus.transition_active);
+
+        // The bypass fades over BYPASS_FADE_SECONDS; the delay keeps its settings
+        let mut left = vec![0.0; 64];
+        let mut right = vec![0.0; 64];
+        bus.process_block(&mut left, &mut right);
+        assert_eq!(bus.effects.delay_level, 0.0);
+        assert!(bus.effects.delay_enabled);
+        assert_eq!(bus.effects.delay_feedback, 0.5);
+
+        bus.apply_effect("dl:on", &[], 0.2);
+        bus.apply_effect("wet", &[0.25], 0.2);
+        bus.process_block(&mut left, &mut right);
+        assert!(bus.effects.delay_level > 0.0 && bus.effects.delay_level < 1.0);
+        for _ in 0..4 {
+            bus.process_block(&mut left, &mut right);
+        }
+        assert_eq!(bus.effects.delay_level, 1.0);
+        assert_eq!(bus.effects.wet, 0.25);
+
+        // Clearing brings back the fully processed chain
+        bus.clear_effects(0.0);
+        assert_eq!(bus.effects.wet, 1.0);
+    }
+
+    #[test]
+    fn test_quiet_after_delay_tail() {
+        let mut bus = MasterBus::new(1000);
+        bus.apply_effect("dl", &[0.1, 0.5], 0.0);
+
+        // One click, then the echoes it leaves in the delay line
+        let mut left = vec![0.0; 64];
+        let mut right = vec![0.0; 64];
+        left[0] = 1.0;
+        right[0] = 1.0;
+        bus.process_block(&mut left, &mut right);
+        assert!(!bus.is_quiet());
+
+        // The gap before the first echo is not the end of the tail
+        let mut blocks = 1;
+        while !bus.is_quiet() {
+            left.fill(0.0);
+            right.fill(0.0);
+            bus.process_block(&mut left, &mut right);
+            blocks += 1;
+            assert!(blocks < 100, "delay tail never ended");
+        }
+        assert!(blocks > 2);
+    }
+
+    #[test]
+    fn test_reverb2_keeps_stereo_placement() {
+        let mut bus = MasterBus::new(8000);
+        bus.apply_effect("rv2", &[0.6, 2.0, 0.3, 1.0], 0.0);
+
+        // A burst hard left, then measure the tail on each side
+        let mut left: Vec<f32> = (0..400).map(|index| (index as f32 * 0.3).sin()).collect();
+        let mut right = vec![0.0; 400];
+        bus.process_block(&mut left, &mut right);
+        let (mut energy_left, mut energy_right) = (0.0, 0.0);
+        for _ in 0..20 {
+            left.fill(0.0);
+            right.fill(0.0);
+            bus.process_block(&mut left, &mut right);
+            energy_left += left.iter().map(|sample| sample * sample).sum::<f32>();
+            energy_right += right.iter().map(|sample| sample * sample).sum::<f32>();
+        }
+        assert!(energy_right > 0.0);
+        assert!(energy_left > energy_right * 4.0);
+    }
+
+    #[test]
+    fn test_delay_time_change_glides() {
+        let mut bus = MasterBus::new(1000);
+        bus.apply_effect("dl", &[0.1, 0.5], 0.0);
+
+        // A slow sine through the delay, with the time doubled halfway
+        let mut previous = 0.0;
+        let mut largest_step: f32 = 0.0;
+        for block in 0..40 {
+            if block == 20 {
+                bus.apply_effect("dl", &[0.2, 0.5], 0.0);
+            }
+            let mut left: Vec<f32> = (0..50)
+                .map(|index| ((block * 50 + index) as f32 * 0.03).sin() * 0.5)
+                .collect();
+            let mut right = left.clone();
+            bus.process_block(&mut left, &mut right);
+            for sample in left {
+                largest_step = largest_step.max((sample - previous).abs());
+                previous = sample;
+            }
+        }
+
+        // Jumping straight to the new tap steps the output by about 0.3
+        assert!(largest_step < 0.1, "output jumped by {}", largest_step);
+        assert!((bus.effects.delay_glide_samples - 200.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_feedback_lines_flush_and_clear() {
+        let mut bus = MasterBus::new(1000);
+        bus.apply_effect("dl", &[0.1, 0.5], 0.0);
+
+        // A click, then 135 echoes: each halves it, so without flushing the
+        // delay line would now hold denormals (below 1.2e-38)
+        let mut left = vec![0.0; 100];
+        let mut right = vec![0.0; 100];
+        left[0] = 1.0;
+        for _ in 0..135 {
+            bus.process_block(&mut left, &mut right);
+            left.fill(0.0);
+            right.fill(0.0);
+        }
+        let effects = &bus.effects;
+        for sample in effects
+            .delay_buffer_left
+            .iter()
+            .chain(&effects.reverb1_buffer)
+        {
+            assert!(!sample.is_subnormal());
+        }
+
+        // A fresh click, then the delay goes off and on again: no old echoes
+        left[0] = 1.0;
+        bus.process_block(&mut left, &mut right);
+        bus.apply_effect("dl", &[0.1, 0.0], 0.0);
+        assert!(
+            bus.effects
+                .delay_buffer_left
+                .iter()
+                .all(|&sample| sample == 0.0)
+        );
+        bus.clear_effects(0.0);
+        bus.apply_effect("dl", &[0.1, 0.5], 0.0);
+        for _ in 0..5 {
+            left.fill(0.0);
+            right.fill(0.0);
+            bus.process_block(&mut left, &mut right);
+            assert!(left.iter().all(|&sample| sample == 0.0));
+        }
+    }
 }
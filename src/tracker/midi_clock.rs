@@ -0,0 +1,459 @@
+// ============================================================================
+// MIDI_CLOCK.RS - Syncing with Drum Machines over MIDI Clock
+// ============================================================================
+//
+// MIDI clock is how hardware sequencers keep time together: the leader
+// sends a clock byte 24 times per beat, plus Start, Stop, and Continue.
+// The tracker can do either side:
+//
+//   tracker song.csv --clock-out 0          Lead: the drum machine follows
+//   tracker song.csv --clock-in "TR-8"      Follow: the drum machine leads
+//
+// HOW IT WORKS:
+// - Sending (clock_schedule): a song's timing is known before it plays -
+//   every row lasts tick_duration until a bpm command changes it - so
+//   the time of every clock pulse is worked out up front. A thread then
+//   sends each one at its moment, evenly spaced no matter how big the
+//   audio buffer is. Beats are rows_per_beat rows long (the bpm
+//   command's second number, 4 until one says otherwise).
+// - Following (ClockFollower): the engine is switched to
+//   ClockSource::External, and every (24 / rows_per_beat) clock pulses
+//   start one row. Start plays the song from the top, Stop pauses,
+//   Continue resumes, and Song Position jumps to the matching row.
+//
+// The port code (midi_ports) needs the optional midir crate:
+//   cargo build --features midir
+// ============================================================================
+
+use crate::engine::EngineCommand;
+use crate::parser::{CellAction, DEFAULT_ROWS_PER_BEAT, SongData, tick_duration_from_bpm};
+
+/// MIDI clock pulses in one beat (fixed by the MIDI standard)
+pub const MIDI_CLOCK_PULSES_PER_BEAT: u32 = 24;
+
+/// MIDI real-time and system common status bytes
+pub const MIDI_CLOCK: u8 = 0xF8;
+pub const MIDI_START: u8 = 0xFA;
+pub const MIDI_CONTINUE: u8 = 0xFB;
+pub const MIDI_STOP: u8 = 0xFC;
+const MIDI_SONG_POSITION: u8 = 0xF2;
+
+/// Clock pulses per Song Position step (one step is a sixteenth note)
+const PULSES_PER_SONG_POSITION_STEP: u32 = 6;
+
+// ============================================================================
+// SENDING CLOCK
+// ============================================================================
+
+/// Every clock message a song needs, as (seconds from the start, status byte)
+/// Starts with Start, ends with Stop after the last row, and follows bpm
+/// commands (tick_duration_seconds is the speed until the first one).
+pub fn clock_schedule(song: &SongData, tick_duration_seconds: f32) -> Vec<(f64, u8)> {
+    let mut tick = tick_duration_seconds as f64;
+    let mut rows_per_beat = DEFAULT_ROWS_PER_BEAT as f64;
+    let mut schedule = vec![(0.0, MIDI_START)];
+
+    // Time and clock position (in pulses, may be fractional) of the row start
+    let mut row_start_time = 0.0;
+    let mut row_start_pulse = 0.0;
+    let mut next_pulse = 0.0_f64;
+
+    for row in &song.rows {
+        for action in row {
+            if let CellAction::MasterEffects { effects, .. } = action {
+                for (name, params) in effects {
+                    if name == "bpm" && params.len() == 2 {
+                        tick = tick_duration_from_bpm(params[0], params[1]) as f64;
+                        rows_per_beat = params[1] as f64;
+                    }
+                }
+            }
+        }
+
+        let pulses_in_row = MIDI_CLOCK_PULSES_PER_BEAT as f64 / rows_per_beat;
+        let row_end_pulse = row_start_pulse + pulses_in_row;
+        // The small margin keeps rounding error from doubling a pulse
+        while next_pulse < row_end_pulse - 1e-9 {
+            let fraction = (next_pulse - row_start_pulse) / pulses_in_row;
+            schedule.push((row_start_time + fraction * tick, MIDI_CLOCK));
+            next_pulse += 1.0;
+        }
+        row_start_time += tick;
+        row_start_pulse = row_end_pulse;
+    }
+
+    schedule.push((row_start_time, MIDI_STOP));
+    schedule
+}
+
+// ============================================================================
+// FOLLOWING CLOCK
+// ============================================================================
+
+/// Turns incoming MIDI clock into engine commands
+/// The engine must use ClockSource::External for AdvanceRow to matter.
+pub struct ClockFollower {
+    /// Clock pulses in one row
+    pulses_per_row: u32,
+
+    /// Pulses since Start (or the last Song Position)
+    pulse_count: u32,
+
+    /// Between Start/Continue and Stop
+    running: bool,
+}
+
+impl ClockFollower {
+    /// rows_per_beat must divide 24 evenly (1, 2, 3, 4, 6, 8, 12, or 24)
+    pub fn new(rows_per_beat: u32) -> Result<Self, String> {
+        if rows_per_beat == 0 || !MIDI_CLOCK_PULSES_PER_BEAT.is_multiple_of(rows_per_beat) {
+            return Err(format!(
+                "Can't follow MIDI clock at {} rows per beat. Use 1, 2, 3, 4, 6, 8, 12, or 24.",
+                rows_per_beat
+            ));
+        }
+        Ok(Self {
+            pulses_per_row: MIDI_CLOCK_PULSES_PER_BEAT / rows_per_beat,
+            pulse_count: 0,
+            running: false,
+        })
+    }
+
+    /// Whether the leader has started (and not stopped) playback
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Commands for one raw MIDI message (everything but clock is ignored)
+    pub fn handle_message(&mut self, message: &[u8]) -> Vec<EngineCommand> {
+        match message {
+            [MIDI_CLOCK, ..] => {
+                // Leaders often send clock while stopped, to show the tempo
+                if !self.running {
+                    return Vec::new();
+                }
+                let starts_row = self.pulse_count.is_multiple_of(self.pulses_per_row);
+                self.pulse_count = self.pulse_count.wrapping_add(1);
+                if starts_row {
+                    vec![EngineCommand::AdvanceRow]
+                } else {
+                    Vec::new()
+                }
+            }
+            [MIDI_START, ..] => {
+                // The first row plays on the first clock pulse after Start
+                self.running = true;
+                self.pulse_count = 0;
+                vec![EngineCommand::Reset, EngineCommand::SetPaused(false)]
+            }
+            [MIDI_CONTINUE, ..] => {
+                self.running = true;
+                vec![EngineCommand::SetPaused(false)]
+            }
+            [MIDI_STOP, ..] => {
+                self.running = false;
+                vec![EngineCommand::SetPaused(true)]
+            }
+            [MIDI_SONG_POSITION, low, high, ..] => {
+                // Position is in sixteenth notes; a row that is already
+                // under way is skipped so the next pulse starts a full row
+                let steps = (*high as u32 & 0x7F) << 7 | (*low as u32 & 0x7F);
+                self.pulse_count = steps * PULSES_PER_SONG_POSITION_STEP;
+                let row = self.pulse_count.div_ceil(self.pulses_per_row);
+                self.pulse_count = row * self.pulses_per_row;
+                vec![EngineCommand::JumpToRow(row as usize)]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+// ============================================================================
+// MIDI PORTS (needs the midir feature)
+// ============================================================================
+
+#[cfg(feature = "midir")]
+pub mod midi_ports {
+    use super::{ClockFollower, MIDI_STOP};
+    use crate::engine::{EngineController, EngineEvent};
+    use crate::live::choose_port;
+    use crate::live::midi_input::CLIENT_NAME;
+    use midir::{Ignore, MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread::{self, JoinHandle};
+    use std::time::{Duration, Instant};
+
+    /// Longest single sleep, so stop() is noticed quickly
+    const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+    /// Sends a clock schedule on a MIDI output port from its own thread
+    pub struct ClockOutput {
+        stop_requested: Arc<AtomicBool>,
+        thread: Option<JoinHandle<()>>,
+
+        /// Name of the port that was opened
+        pub port_name: String,
+    }
+
+    impl ClockOutput {
+        /// Opens a port and starts sending the schedule (see clock_schedule)
+        /// delay_seconds moves every message later, to line up with the
+        /// audio device's latency. port works like live::choose_port.
+        pub fn start(
+            port: Option<&str>,
+            schedule: Vec<(f64, u8)>,
+            delay_seconds: f64,
+        ) -> Result<Self, String> {
+            let output = MidiOutput::new(CLIENT_NAME)
+                .map_err(|error| format!("Failed to open MIDI output: {}", error))?;
+            let ports = output.ports();
+            let names: Vec<String> = ports
+                .iter()
+                .map(|port| {
+                    output
+                        .port_name(port)
+                        .unwrap_or_else(|_| "(unnamed)".to_string())
+                })
+                .collect();
+            let port_index = choose_port(&names, port, "output")?;
+            let port_name = names[port_index].clone();
+            let connection = output
+                .connect(&ports[port_index], "tracker-clock")
+                .map_err(|error| format!("Failed to open MIDI port '{}': {}", port_name, error))?;
+
+            let stop_requested = Arc::new(AtomicBool::new(false));
+            let thread_stop = Arc::clone(&stop_requested);
+            let thread = thread::spawn(move || {
+                send_schedule(connection, &schedule, delay_seconds, &thread_stop)
+            });
+
+            Ok(Self {
+                stop_requested,
+                thread: Some(thread),
+                port_name,
+            })
+        }
+
+        /// Sends Stop (if the song is still going) and closes the port
+        pub fn stop(mut self) {
+            self.shut_down();
+        }
+
+        fn shut_down(&mut self) {
+            self.stop_requested.store(true, Ordering::Relaxed);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    impl Drop for ClockOutput {
+        fn drop(&mut self) {
+            self.shut_down();
+        }
+    }
+
+    /// Body of the output thread
+    fn send_schedule(
+        mut connection: MidiOutputConnection,
+        schedule: &[(f64, u8)],
+        delay_seconds: f64,
+        stop_requested: &AtomicBool,
+    ) {
+        let start = Instant::now() + Duration::from_secs_f64(delay_seconds.max(0.0));
+        for (seconds, status) in schedule {
+            let due = start + Duration::from_secs_f64(*seconds);
+            loop {
+                if stop_requested.load(Ordering::Relaxed) {
+                    // Don't leave the drum machine running on its own
+                    let _ = connection.send(&[MIDI_STOP]);
+                    connection.close();
+                    return;
+                }
+                let now = Instant::now();
+                if now >= due {
+                    break;
+                }
+                thread::sleep((due - now).min(STOP_CHECK_INTERVAL));
+            }
+            if let Err(error) = connection.send(&[*status]) {
+                log::warn!(target: "engine", "MIDI clock send failed: {}", error);
+            }
+        }
+        connection.close();
+    }
+
+    /// State owned by the input callback
+    struct FollowerState {
+        follower: ClockFollower,
+        controller: EngineController,
+        finished: Arc<AtomicBool>,
+    }
+
+    /// An open MIDI input port driving the engine's rows (closed when dropped)
+    pub struct ClockInput {
+        connection: MidiInputConnection<FollowerState>,
+        finished: Arc<AtomicBool>,
+
+        /// Name of the port that was opened
+        pub port_name: String,
+    }
+
+    impl ClockInput {
+        /// Whether the engine has played the last row
+        pub fn is_finished(&self) -> bool {
+            self.finished.load(Ordering::Relaxed)
+        }
+
+        /// Closes the port and hands the controller back
+        pub fn close(self) -> EngineController {
+            let (_, state) = self.connection.close();
+            state.controller
+        }
+    }
+
+    /// Opens a MIDI input port and advances the engine with its clock
+    /// The engine should already use ClockSource::External.
+    pub fn follow_clock(
+        port: Option<&str>,
+        follower: ClockFollower,
+        controller: EngineController,
+    ) -> Result<ClockInput, String> {
+        let mut input = MidiInput::new(CLIENT_NAME)
+            .map_err(|error| format!("Failed to open MIDI input: {}", error))?;
+        // Timing messages are exactly what we need here
+        input.ignore(Ignore::SysexAndActiveSense);
+
+        let ports = input.ports();
+        let names: Vec<String> = ports
+            .iter()
+            .map(|port| {
+                input
+                    .port_name(port)
+                    .unwrap_or_else(|_| "(unnamed)".to_string())
+            })
+            .collect();
+        let port_index = choose_port(&names, port, "input")?;
+        let port_name = names[port_index].clone();
+
+        // The callback also reads the engine's events (this thread owns
+        // the controller), so the caller learns when the song is over
+        let finished = Arc::new(AtomicBool::new(false));
+        let state = FollowerState {
+            follower,
+            controller,
+            finished: Arc::clone(&finished),
+        };
+        let connection = input
+            .connect(
+                &ports[port_index],
+                "tracker-clock",
+                |_timestamp, message, state: &mut FollowerState| {
+                    for command in state.follower.handle_message(message) {
+                        if state.controller.send(command).is_err() {
+                            log::warn!(target: "engine", "Command queue full, clock message dropped");
+                        }
+                    }
+                    while let Some(event) = state.controller.poll_event() {
+                        match event {
+                            EngineEvent::RowStarted(row) => {
+                                log::info!(target: "audio", "Row {}", row)
+                            }
+                            EngineEvent::Finished => state.finished.store(true, Ordering::Relaxed),
+                            // The caller waits for the tails after closing
+                            EngineEvent::Silent | EngineEvent::SongStarted => {}
+                        }
+                    }
+                },
+                state,
+            )
+            .map_err(|error| format!("Failed to open MIDI port '{}': {}", port_name, error))?;
+
+        Ok(ClockInput {
+            connection,
+            finished,
+            port_name,
+        })
+    }
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::{A4_FREQUENCY_HZ, FrequencyTable};
+    use crate::parser::{MissingCellBehavior, parse_song};
+
+    #[test]
+    fn test_clock_schedule_follows_bpm() {
+        let frequency_table = FrequencyTable::new();
+        // Two rows at 0.125s (4 rows per beat = 120 BPM), then 60 BPM
+        let song = parse_song(
+            "Voice0\nc4 sine\n-\nmaster bpm:60'4\n-",
+            &frequency_table,
+            A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+        let schedule = clock_schedule(&song, 0.125);
+
+        assert_eq!(schedule.first(), Some(&(0.0, MIDI_START)));
+        let clocks: Vec<f64> = schedule
+            .iter()
+            .filter(|(_, status)| *status == MIDI_CLOCK)
+            .map(|(seconds, _)| *seconds)
+            .collect();
+        // 6 pulses per row at 4 rows per beat
+        assert_eq!(clocks.len(), 4 * 6);
+        assert!((clocks[1] - 0.125 / 6.0).abs() < 1e-9);
+        assert!((clocks[12] - 0.25).abs() < 1e-9);
+        assert!((clocks[13] - (0.25 + 0.25 / 6.0)).abs() < 1e-9);
+        let (end, status) = *schedule.last().unwrap();
+        assert_eq!(status, MIDI_STOP);
+        assert!((end - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_follower_advances_every_row_of_pulses() {
+        assert!(ClockFollower::new(5).is_err());
+        let mut follower = ClockFollower::new(4).unwrap();
+
+        // Clock before Start only shows the tempo
+        assert!(follower.handle_message(&[MIDI_CLOCK]).is_empty());
+        assert!(matches!(
+            follower.handle_message(&[MIDI_START])[..],
+            [EngineCommand::Reset, EngineCommand::SetPaused(false)]
+        ));
+
+        let rows_started = (0..12)
+            .filter(|_| {
+                matches!(
+                    follower.handle_message(&[MIDI_CLOCK])[..],
+                    [EngineCommand::AdvanceRow]
+                )
+            })
+            .count();
+        assert_eq!(rows_started, 2);
+
+        assert!(matches!(
+            follower.handle_message(&[MIDI_STOP])[..],
+            [EngineCommand::SetPaused(true)]
+        ));
+        assert!(!follower.is_running());
+
+        // Song position 3 sixteenths = 18 pulses = row 3 at 6 pulses per row
+        assert!(matches!(
+            follower.handle_message(&[MIDI_SONG_POSITION, 3, 0])[..],
+            [EngineCommand::JumpToRow(3)]
+        ));
+        follower.handle_message(&[MIDI_CONTINUE]);
+        assert!(matches!(
+            follower.handle_message(&[MIDI_CLOCK])[..],
+            [EngineCommand::AdvanceRow]
+        ));
+    }
+}
@@ -0,0 +1,289 @@
+// ============================================================================
+// MIDI_EXPORT.RS - Standard MIDI File Export
+// ============================================================================
+//
+// Writes a parsed song as a .mid file, so it can be opened in a DAW or
+// notation program for further arrangement:
+//
+//   tracker convert song.csv song.mid
+//
+// HOW IT WORKS:
+// - Timing: every row is MIDI_TICKS_PER_ROW ticks, 4 rows to a beat. The
+//   song's tick_duration (and every "master bpm" change) becomes a MIDI
+//   tempo, so the file plays at the same speed as the tracker.
+// - Tracks: track 1 holds the title and tempo changes, then one track
+//   per tracker channel.
+// - Notes: each note trigger becomes a MIDI note at the nearest key
+//   (velocity = a: amplitude x 127). It ends at the next release or note
+//   on the same channel. Pitchless instruments (noise) are written as
+//   snare hits on the General MIDI drum channel.
+//
+// Only what MIDI can hold is exported: instruments, effects, envelopes,
+// and master effects other than tempo are left out.
+// ============================================================================
+
+use crate::helper::{A4_FREQUENCY_HZ, A4_MIDI_NOTE};
+use crate::parser::{CellAction, DEFAULT_ROWS_PER_BEAT, SongData, tick_duration_from_bpm};
+use midly::num::{u4, u7, u15, u24, u28};
+use midly::{Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+use std::fs;
+
+/// MIDI ticks per row (so a beat of 4 rows is 96 ticks)
+pub const MIDI_TICKS_PER_ROW: u32 = 24;
+
+/// General MIDI drum channel (channel 10, index 9)
+const DRUM_MIDI_CHANNEL: u8 = 9;
+
+/// Drum key for pitchless hits (General MIDI acoustic snare)
+const PITCHLESS_DRUM_KEY: u8 = 38;
+
+// ============================================================================
+// EXPORT
+// ============================================================================
+
+/// Writes the song to a .mid file (see export_midi)
+pub fn export_midi_file(
+    song: &SongData,
+    tick_duration_seconds: f32,
+    path: &str,
+) -> Result<(), String> {
+    fs::write(path, export_midi(song, tick_duration_seconds))
+        .map_err(|e| format!("Failed to write MIDI file '{}': {}", path, e))
+}
+
+/// A note waiting for its note-off
+#[derive(Clone, Copy)]
+struct SoundingNote {
+    midi_channel: u8,
+    key: u8,
+}
+
+/// Converts a song to the bytes of a Standard MIDI File
+/// tick_duration_seconds is the row length until the first bpm command
+/// (normally the config row's tick_duration).
+pub fn export_midi(song: &SongData, tick_duration_seconds: f32) -> Vec<u8> {
+    let reference_frequency_hz = song
+        .config
+        .reference_frequency_hz
+        .unwrap_or(A4_FREQUENCY_HZ);
+    let channel_count = song.channel_count();
+    let row_count = song.row_count();
+
+    // ---- Track 1: title and tempo changes ----
+    let mut tempo_track: Vec<(u32, TrackEventKind)> = Vec::new();
+    let title = song.config.title.clone().unwrap_or_default();
+    if !title.is_empty() {
+        tempo_track.push((
+            0,
+            TrackEventKind::Meta(MetaMessage::TrackName(title.as_bytes())),
+        ));
+    }
+    tempo_track.push((0, tempo_event(tick_duration_seconds)));
+    for (row_index, row) in song.rows.iter().enumerate() {
+        for action in row {
+            if let CellAction::MasterEffects { effects, .. } = action {
+                for (name, params) in effects {
+                    if name == "bpm" && params.len() == 2 {
+                        let tick = tick_duration_from_bpm(params[0], params[1]);
+                        tempo_track
+                            .push((row_index as u32 * MIDI_TICKS_PER_ROW, tempo_event(tick)));
+                    }
+                }
+            }
+        }
+    }
+
+    // ---- One track per tracker channel ----
+    let track_names: Vec<String> = (0..channel_count)
+        .map(|channel_index| format!("Channel {}", channel_index + 1))
+        .collect();
+    let mut tracks: Vec<Vec<(u32, TrackEventKind)>> = vec![tempo_track];
+
+    for (channel_index, track_name) in track_names.iter().enumerate() {
+        // Pitched notes use one MIDI channel per tracker channel, skipping drums
+        let pitched_midi_channel = match channel_index % 15 {
+            index if index >= DRUM_MIDI_CHANNEL as usize => index as u8 + 1,
+            index => index as u8,
+        };
+        let mut events = vec![(
+            0,
+            TrackEventKind::Meta(MetaMessage::TrackName(track_name.as_bytes())),
+        )];
+        let mut sounding: Option<SoundingNote> = None;
+
+        for (row_index, row) in song.rows.iter().enumerate() {
+            let time = row_index as u32 * MIDI_TICKS_PER_ROW;
+            let (note, velocity) = match row.get(channel_index) {
+                Some(CellAction::TriggerNote {
+                    frequency_hz,
+                    effects,
+                    ..
+                }) => (
+                    SoundingNote {
+                        midi_channel: pitched_midi_channel,
+                        key: frequency_to_midi_key(*frequency_hz, reference_frequency_hz),
+                    },
+                    effects.amplitude,
+                ),
+                Some(CellAction::TriggerPitchless { effects, .. }) => (
+                    SoundingNote {
+                        midi_channel: DRUM_MIDI_CHANNEL,
+                        key: PITCHLESS_DRUM_KEY,
+                    },
+                    effects.amplitude,
+                ),
+                Some(CellAction::FastRelease | CellAction::SlowRelease) => {
+                    if let Some(note) = sounding.take() {
+                        events.push((time, note_off(note)));
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+
+            // A new note ends the one before it (channels are monophonic)
+            if let Some(note) = sounding.take() {
+                events.push((time, note_off(note)));
+            }
+            let velocity = (velocity.clamp(0.0, 1.0) * 127.0).round().max(1.0) as u8;
+            events.push((
+                time,
+                TrackEventKind::Midi {
+                    channel: u4::new(note.midi_channel),
+                    message: MidiMessage::NoteOn {
+                        key: u7::new(note.key),
+                        vel: u7::new(velocity),
+                    },
+                },
+            ));
+            sounding = Some(note);
+        }
+
+        // Notes still sounding end with the song
+        if let Some(note) = sounding {
+            events.push((row_count as u32 * MIDI_TICKS_PER_ROW, note_off(note)));
+        }
+        tracks.push(events);
+    }
+
+    // ---- Absolute times to delta times ----
+    let mut smf = Smf::new(Header::new(
+        Format::Parallel,
+        Timing::Metrical(u15::new(
+            MIDI_TICKS_PER_ROW as u16 * DEFAULT_ROWS_PER_BEAT as u16,
+        )),
+    ));
+    for events in tracks {
+        let mut previous_time = 0;
+        let mut track: Vec<TrackEvent> = events
+            .into_iter()
+            .map(|(time, kind)| {
+                let delta = time - previous_time;
+                previous_time = time;
+                TrackEvent {
+                    delta: u28::new(delta),
+                    kind,
+                }
+            })
+            .collect();
+        track.push(TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+        });
+        smf.tracks.push(track);
+    }
+
+    let mut bytes = Vec::new();
+    smf.write_std(&mut bytes)
+        .expect("writing MIDI to memory cannot fail");
+    bytes
+}
+
+/// MIDI tempo event for a row length (4 rows per beat)
+fn tempo_event(tick_duration_seconds: f32) -> TrackEventKind<'static> {
+    let microseconds_per_beat =
+        (tick_duration_seconds as f64 * DEFAULT_ROWS_PER_BEAT as f64 * 1_000_000.0).round();
+    TrackEventKind::Meta(MetaMessage::Tempo(u24::new(
+        microseconds_per_beat.clamp(1.0, 0xFF_FFFF as f64) as u32,
+    )))
+}
+
+fn note_off(note: SoundingNote) -> TrackEventKind<'static> {
+    TrackEventKind::Midi {
+        channel: u4::new(note.midi_channel),
+        message: MidiMessage::NoteOff {
+            key: u7::new(note.key),
+            vel: u7::new(0),
+        },
+    }
+}
+
+/// Nearest MIDI key for a frequency (69 = A4), clamped to 0..=127
+/// Microtonal notes round to the closest key.
+pub fn frequency_to_midi_key(frequency_hz: f32, reference_frequency_hz: f32) -> u8 {
+    let key = A4_MIDI_NOTE as f32 + 12.0 * (frequency_hz / reference_frequency_hz).log2();
+    key.round().clamp(0.0, 127.0) as u8
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Song;
+    use crate::midi_import::{MidiImportOptions, import_midi};
+
+    #[test]
+    fn test_frequency_to_midi_key() {
+        assert_eq!(frequency_to_midi_key(440.0, 440.0), 69);
+        assert_eq!(frequency_to_midi_key(261.63, 440.0), 60);
+        assert_eq!(frequency_to_midi_key(432.0, 432.0), 69);
+        assert_eq!(frequency_to_midi_key(1.0, 440.0), 0);
+    }
+
+    #[test]
+    fn test_export_then_import_round_trip() {
+        let text = "Lead,Bass,Drums\n\
+                    config, title: Round Trip, tick_duration: 0.125\n\
+                    c4 sine a:0.5,c3 square,noise a:0.8\n\
+                    -,-,.\n\
+                    master bpm:150,.,\n\
+                    e4 sine,,\n\
+                    .,,\n";
+        let song = Song::parse(text, 3);
+        assert!(song.diagnostics().is_empty());
+        let bytes = export_midi(song.data(), song.tick_duration_seconds());
+
+        let smf = Smf::parse(&bytes).unwrap();
+        assert_eq!(smf.tracks.len(), 4); // tempo track + 3 channels
+        let tempos: Vec<u32> = smf.tracks[0]
+            .iter()
+            .filter_map(|event| match event.kind {
+                TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => Some(tempo.as_int()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(tempos, vec![500_000, 400_000]);
+
+        // Importing the file again gives back the same notes and timing
+        let import = import_midi(&bytes, "song", &MidiImportOptions::default()).unwrap();
+        let lines: Vec<&str> = import.csv.lines().collect();
+        assert_eq!(lines[0], "Master,Channel 1,Channel 2,Channel 3");
+        assert!(lines[1].starts_with("config, title: Round Trip,"));
+        assert_eq!(lines[3], ",c4 sine a:0.504,c3 sine a:1,noise a:0.803");
+        assert_eq!(lines[4], ",-,-,.");
+        assert_eq!(lines[5], "master bpm:150,-,.,");
+        assert_eq!(lines[6], ",e4 sine a:1,,");
+        let reimported = Song::parse(&import.csv, import.channel_count);
+        assert!(
+            (reimported
+                .data()
+                .duration_seconds(reimported.tick_duration_seconds())
+                - song.data().duration_seconds(song.tick_duration_seconds()))
+            .abs()
+                < 1e-5
+        );
+    }
+}
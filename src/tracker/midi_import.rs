@@ -0,0 +1,510 @@
+// ============================================================================
+// MIDI_IMPORT.RS - Standard MIDI File Import
+// ============================================================================
+//
+// Turns a .mid file from a DAW or notation program into tracker CSV, so it
+// can be played, edited, and re-voiced like any other song:
+//
+//   tracker import song.mid -o song.csv
+//
+// HOW IT WORKS:
+// - Rows: MIDI time is measured in ticks per beat. Every note start and end
+//   is rounded to the nearest row (4 rows per beat by default).
+// - Channels: each MIDI track (and each MIDI channel inside a track) gets
+//   its own tracker channels. Tracker channels play one note at a time, so
+//   chords spread over several channels.
+// - Notes: a note becomes "c4 sine a:0.79" (velocity / 127 as amplitude),
+//   "-" while it is held, and "." (fast release) on its note-off.
+//   MIDI channel 10 is the drum channel and plays noise instead.
+// - Tempo: the first tempo becomes the config row; later tempo changes
+//   become "master bpm:N" cells in an extra Master column.
+//
+// Anything that could not be imported (too many voices, notes below C0)
+// is reported in the warnings list instead of stopping the import.
+// ============================================================================
+
+use crate::helper::PitchName;
+use crate::parser::{DEFAULT_ROWS_PER_BEAT, tick_duration_from_bpm};
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// MIDI channel 10 (index 9) is reserved for drums in General MIDI
+const DRUM_MIDI_CHANNEL: u8 = 9;
+
+/// Tempo used when the file has no tempo event (the MIDI default)
+const DEFAULT_MIDI_BPM: f32 = 120.0;
+
+/// MIDI note number of C0 (the lowest note the tracker can play)
+const MIDI_NOTE_C0: u8 = 12;
+
+// ============================================================================
+// OPTIONS AND RESULT
+// ============================================================================
+
+/// Settings for converting a MIDI file
+#[derive(Clone, Debug)]
+pub struct MidiImportOptions {
+    /// Row grid: 4 = sixteenth notes, 2 = eighths, 8 = thirty-seconds
+    pub rows_per_beat: u32,
+
+    /// Most tracker channels to use (notes that don't fit are dropped)
+    pub max_channels: usize,
+
+    /// Instrument for pitched notes
+    pub instrument: String,
+
+    /// Instrument for the drum channel (should not need a pitch)
+    pub drum_instrument: String,
+}
+
+impl Default for MidiImportOptions {
+    fn default() -> Self {
+        Self {
+            rows_per_beat: DEFAULT_ROWS_PER_BEAT as u32,
+            max_channels: 12,
+            instrument: "sine".to_string(),
+            drum_instrument: "noise".to_string(),
+        }
+    }
+}
+
+/// A converted song
+#[derive(Clone, Debug)]
+pub struct MidiImport {
+    /// The song as tracker CSV text
+    pub csv: String,
+
+    /// Number of tracker channels (columns) used
+    pub channel_count: usize,
+
+    /// Number of rows written
+    pub row_count: usize,
+
+    /// Things that could not be converted exactly
+    pub warnings: Vec<String>,
+}
+
+// ============================================================================
+// IMPORT
+// ============================================================================
+
+/// Reads a .mid file and converts it (the title is the file name)
+pub fn import_midi_file(path: &str, options: &MidiImportOptions) -> Result<MidiImport, String> {
+    let bytes =
+        fs::read(path).map_err(|e| format!("Failed to read MIDI file '{}': {}", path, e))?;
+    let title = Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    import_midi(&bytes, &title, options).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// One note with its start and end in MIDI ticks
+#[derive(Clone, Copy)]
+struct MidiNote {
+    key: u8,
+    velocity: u8,
+    start_tick: u64,
+    end_tick: u64,
+}
+
+/// The notes of one track on one MIDI channel
+struct NoteSource {
+    name: String,
+    is_drums: bool,
+    notes: Vec<MidiNote>,
+}
+
+/// Converts the bytes of a Standard MIDI File into tracker CSV
+/// A named first track without notes (the usual tempo track) overrides
+/// the title.
+pub fn import_midi(
+    bytes: &[u8],
+    title: &str,
+    options: &MidiImportOptions,
+) -> Result<MidiImport, String> {
+    let smf = Smf::parse(bytes).map_err(|e| format!("Not a valid MIDI file ({})", e))?;
+    let ticks_per_beat = match smf.header.timing {
+        Timing::Metrical(ticks) => ticks.as_int() as f64,
+        Timing::Timecode(..) => {
+            return Err("MIDI files timed in SMPTE frames are not supported".to_string());
+        }
+    };
+    let rows_per_beat = options.rows_per_beat.max(1);
+    let ticks_per_row = ticks_per_beat / rows_per_beat as f64;
+    let tick_to_row = |tick: u64| (tick as f64 / ticks_per_row).round() as usize;
+
+    let mut warnings = Vec::new();
+    let mut tempo_changes: Vec<(u64, f32)> = Vec::new();
+    let mut sources: Vec<NoteSource> = Vec::new();
+    let mut title = title.to_string();
+
+    // ---- Collect notes and tempo changes from every track ----
+    for (track_index, track) in smf.tracks.iter().enumerate() {
+        let mut tick: u64 = 0;
+        let mut track_name = format!("Track {}", track_index + 1);
+        let mut has_name = false;
+
+        // Notes waiting for their note-off, by (MIDI channel, key)
+        let mut held: HashMap<(u8, u8), Vec<(u64, u8)>> = HashMap::new();
+        let mut track_notes: Vec<(u8, MidiNote)> = Vec::new();
+
+        for event in track {
+            tick += event.delta.as_int() as u64;
+            match event.kind {
+                TrackEventKind::Meta(MetaMessage::TrackName(name)) => {
+                    let name = String::from_utf8_lossy(name).trim().replace(',', " ");
+                    if !name.is_empty() {
+                        track_name = name;
+                        has_name = true;
+                    }
+                }
+                TrackEventKind::Meta(MetaMessage::Tempo(microseconds_per_beat)) => {
+                    let bpm = 60_000_000.0 / microseconds_per_beat.as_int().max(1) as f32;
+                    tempo_changes.push((tick, bpm));
+                }
+                TrackEventKind::Midi { channel, message } => {
+                    let channel = channel.as_int();
+                    let (key, velocity) = match message {
+                        MidiMessage::NoteOn { key, vel } => (key.as_int(), vel.as_int()),
+                        MidiMessage::NoteOff { key, .. } => (key.as_int(), 0),
+                        _ => continue,
+                    };
+                    if velocity > 0 {
+                        held.entry((channel, key))
+                            .or_default()
+                            .push((tick, velocity));
+                    } else if let Some(starts) = held.get_mut(&(channel, key))
+                        && !starts.is_empty()
+                    {
+                        // The oldest held copy of the key ends first
+                        let (start_tick, velocity) = starts.remove(0);
+                        track_notes.push((
+                            channel,
+                            MidiNote {
+                                key,
+                                velocity,
+                                start_tick,
+                                end_tick: tick,
+                            },
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Notes never switched off end with the track
+        for ((channel, key), starts) in held {
+            for (start_tick, velocity) in starts {
+                track_notes.push((
+                    channel,
+                    MidiNote {
+                        key,
+                        velocity,
+                        start_tick,
+                        end_tick: tick.max(start_tick),
+                    },
+                ));
+            }
+        }
+
+        if track_index == 0 && has_name && track_notes.is_empty() {
+            title = track_name.clone();
+        }
+
+        // One source per MIDI channel used in this track
+        let mut channels: Vec<u8> = track_notes.iter().map(|(channel, _)| *channel).collect();
+        channels.sort_unstable();
+        channels.dedup();
+        for channel in &channels {
+            let name = if channels.len() > 1 {
+                format!("{} Ch{}", track_name, channel + 1)
+            } else {
+                track_name.clone()
+            };
+            let notes = track_notes
+                .iter()
+                .filter(|(note_channel, _)| note_channel == channel)
+                .map(|(_, note)| *note)
+                .collect();
+            sources.push(NoteSource {
+                name,
+                is_drums: *channel == DRUM_MIDI_CHANNEL,
+                notes,
+            });
+        }
+    }
+
+    // ---- Tempo: the first becomes the config row, the rest bpm commands ----
+    tempo_changes.sort_by_key(|(tick, _)| *tick);
+    let starting_bpm = tempo_changes
+        .first()
+        .filter(|(tick, _)| tick_to_row(*tick) == 0)
+        .map(|(_, bpm)| *bpm)
+        .unwrap_or(DEFAULT_MIDI_BPM);
+    let mut bpm_cells: Vec<(usize, String)> = Vec::new();
+    let mut current_bpm = starting_bpm;
+    for (tick, bpm) in &tempo_changes {
+        let row = tick_to_row(*tick);
+        if row == 0 || (bpm - current_bpm).abs() < 0.01 {
+            continue;
+        }
+        current_bpm = *bpm;
+        let cell = if rows_per_beat == DEFAULT_ROWS_PER_BEAT as u32 {
+            format!("master bpm:{}", format_number(*bpm))
+        } else {
+            format!("master bpm:{}'{}", format_number(*bpm), rows_per_beat)
+        };
+        // Two changes rounded onto the same row: the later one wins
+        bpm_cells.retain(|(existing_row, _)| *existing_row != row);
+        bpm_cells.push((row, cell));
+    }
+
+    // ---- Spread each source's notes over monophonic tracker channels ----
+    let has_master_column = !bpm_cells.is_empty();
+    let note_channel_limit = options
+        .max_channels
+        .saturating_sub(has_master_column as usize);
+    let mut column_names: Vec<String> = Vec::new();
+    let mut columns: Vec<Vec<String>> = Vec::new();
+    let mut dropped_notes = 0;
+    let mut notes_below_c0 = 0;
+
+    for source in &mut sources {
+        source
+            .notes
+            .sort_by_key(|note| (note.start_tick, std::cmp::Reverse(note.key)));
+
+        // (column index, first row the column is free again)
+        let mut voices: Vec<(usize, usize)> = Vec::new();
+
+        for note in &source.notes {
+            if note.key < MIDI_NOTE_C0 && !source.is_drums {
+                notes_below_c0 += 1;
+                continue;
+            }
+            let start_row = tick_to_row(note.start_tick);
+            let end_row = tick_to_row(note.end_tick).max(start_row + 1);
+
+            let voice = match voices
+                .iter()
+                .position(|(_, free_row)| *free_row <= start_row)
+            {
+                Some(voice) => voice,
+                None if columns.len() < note_channel_limit => {
+                    let voice_number = voices.len() + 1;
+                    column_names.push(if voice_number == 1 {
+                        source.name.clone()
+                    } else {
+                        format!("{} {}", source.name, voice_number)
+                    });
+                    columns.push(Vec::new());
+                    voices.push((columns.len() - 1, 0));
+                    voices.len() - 1
+                }
+                None => {
+                    dropped_notes += 1;
+                    continue;
+                }
+            };
+            let column = &mut columns[voices[voice].0];
+            voices[voice].1 = end_row;
+
+            if column.len() <= end_row {
+                column.resize(end_row + 1, String::new());
+            }
+            let amplitude = format_number(note.velocity as f32 / 127.0);
+            column[start_row] = if source.is_drums {
+                format!("{} a:{}", options.drum_instrument, amplitude)
+            } else {
+                let pitch =
+                    PitchName::from_semitones_from_c0((note.key - MIDI_NOTE_C0) as i32, 0.0);
+                format!("{} {} a:{}", pitch, options.instrument, amplitude)
+            };
+            for cell in &mut column[start_row + 1..end_row] {
+                *cell = "-".to_string();
+            }
+            column[end_row] = ".".to_string();
+        }
+    }
+
+    if dropped_notes > 0 {
+        warnings.push(format!(
+            "{} notes dropped: more than {} notes at once. Try a larger channel limit.",
+            dropped_notes, note_channel_limit
+        ));
+    }
+    if notes_below_c0 > 0 {
+        warnings.push(format!(
+            "{} notes below C0 skipped (the tracker's lowest note)",
+            notes_below_c0
+        ));
+    }
+    if columns.is_empty() {
+        warnings.push("The file has no notes".to_string());
+    }
+
+    if has_master_column {
+        column_names.insert(0, "Master".to_string());
+        columns.insert(0, column_from_cells(bpm_cells));
+    }
+
+    // ---- Write the CSV ----
+    let preamble = [
+        config_line(&title, starting_bpm, rows_per_beat),
+        format!("// Imported from MIDI: {} rows per beat", rows_per_beat),
+    ];
+    Ok(MidiImport {
+        csv: write_song_csv(&column_names, &columns, &preamble),
+        channel_count: columns.len(),
+        row_count: columns.iter().map(Vec::len).max().unwrap_or(0),
+        warnings,
+    })
+}
+
+// ============================================================================
+// CSV OUTPUT (SHARED WITH MOD_IMPORT, THE RECORDER, AND THE FFT ANALYZER)
+// ============================================================================
+
+/// The config row for an imported song
+pub fn config_line(title: &str, bpm: f32, rows_per_beat: u32) -> String {
+    format!(
+        "config, title: {}, tempo_bpm: {}, tick_duration: {}",
+        title.replace(',', " "),
+        format_number(bpm),
+        tick_duration_from_bpm(bpm, rows_per_beat as f32)
+    )
+}
+
+/// Builds a column from (row, cell) pairs; the other rows stay empty
+pub(crate) fn column_from_cells(cells: Vec<(usize, String)>) -> Vec<String> {
+    let mut column = Vec::new();
+    for (row, cell) in cells {
+        if column.len() <= row {
+            column.resize(row + 1, String::new());
+        }
+        column[row] = cell;
+    }
+    column
+}
+
+/// Writes columns of cells as song CSV: the header, the preamble lines
+/// (config row, comments), then one line per row
+pub fn write_song_csv(
+    column_names: &[String],
+    columns: &[Vec<String>],
+    preamble: &[String],
+) -> String {
+    let row_count = columns.iter().map(Vec::len).max().unwrap_or(0);
+    let mut csv = String::new();
+    csv.push_str(&column_names.join(","));
+    csv.push('\n');
+    for line in preamble {
+        csv.push_str(line);
+        csv.push('\n');
+    }
+
+    for row in 0..row_count {
+        let cells: Vec<&str> = columns
+            .iter()
+            .map(|column| column.get(row).map(String::as_str).unwrap_or(""))
+            .collect();
+        let line = cells.join(",");
+        // A blank line would be skipped, so a silent one-column row keeps a "-"
+        csv.push_str(if line.is_empty() { "-" } else { &line });
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Writes a number with up to 3 decimals and no trailing zeros (0.5, 120)
+pub fn format_number(value: f32) -> String {
+    let text = format!("{:.3}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Song;
+    use crate::parser::CellAction;
+    use midly::num::{u4, u7, u15, u24, u28};
+    use midly::{Format, Header, TrackEvent};
+
+    fn note_event(delta: u32, key: u8, velocity: u8) -> TrackEvent<'static> {
+        TrackEvent {
+            delta: u28::new(delta),
+            kind: TrackEventKind::Midi {
+                channel: u4::new(0),
+                message: MidiMessage::NoteOn {
+                    key: u7::new(key),
+                    vel: u7::new(velocity),
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn test_import_chord_and_tempo_change() {
+        // 96 ticks per beat: a C major chord for one beat, then a 150 BPM E4
+        let mut smf = Smf::new(Header::new(
+            Format::SingleTrack,
+            Timing::Metrical(u15::new(96)),
+        ));
+        smf.tracks.push(vec![
+            TrackEvent {
+                delta: u28::new(0),
+                kind: TrackEventKind::Meta(MetaMessage::TrackName(b"Piano")),
+            },
+            TrackEvent {
+                delta: u28::new(0),
+                kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(500_000))),
+            },
+            note_event(0, 60, 127),
+            note_event(0, 64, 64),
+            note_event(0, 67, 64),
+            note_event(96, 60, 0),
+            note_event(0, 64, 0),
+            note_event(0, 67, 0),
+            TrackEvent {
+                delta: u28::new(0),
+                kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(400_000))),
+            },
+            note_event(0, 64, 100),
+            note_event(48, 64, 0),
+        ]);
+        let mut bytes = Vec::new();
+        smf.write_std(&mut bytes).unwrap();
+
+        let import = import_midi(&bytes, "Test", &MidiImportOptions::default()).unwrap();
+        assert!(import.warnings.is_empty(), "{:?}", import.warnings);
+        assert_eq!(import.channel_count, 4); // master + three chord voices
+        assert_eq!(import.row_count, 7);
+
+        let lines: Vec<&str> = import.csv.lines().collect();
+        assert_eq!(lines[0], "Master,Piano,Piano 2,Piano 3");
+        assert_eq!(
+            lines[1],
+            "config, title: Test, tempo_bpm: 120, tick_duration: 0.125"
+        );
+        assert_eq!(lines[3], ",g4 sine a:0.504,e4 sine a:0.504,c4 sine a:1");
+        assert_eq!(lines[7], "master bpm:150,e4 sine a:0.787,.,.");
+
+        // The result plays without parser complaints, at the new tempo
+        let song = Song::parse(&import.csv, import.channel_count);
+        assert!(song.diagnostics().is_empty());
+        assert!(matches!(
+            song.data().rows[4][1],
+            CellAction::TriggerNote { .. }
+        ));
+        let duration = song.data().duration_seconds(song.tick_duration_seconds());
+        assert!((duration - (4.0 * 0.125 + 3.0 * 0.1)).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,790 @@
+// ============================================================================
+// MOD_IMPORT.RS - ProTracker MOD Import
+// ============================================================================
+//
+// Turns a classic Amiga .mod module into tracker CSV. muSickBeets borrows
+// its row-per-line layout from these trackers, so the patterns map over
+// almost one to one:
+//
+//   tracker import song.mod -o song.csv
+//
+// HOW IT WORKS:
+// - Rows: the order list is played through (following pattern breaks and
+//   position jumps) and every MOD row becomes a tracker row. Songs that
+//   use arpeggio get 3 tracker rows per MOD row, one per arpeggio note.
+// - Tempo: MOD speed/tempo (Fxx) become the config row and "master bpm"
+//   cells, so rows last exactly as long as they did on the Amiga.
+// - Samples: the tracker has no samples, so every sample plays on one
+//   synth instrument. Samples named like drums (kick, snare, hat, ...)
+//   play noise instead.
+// - Notes: Amiga periods become pitches (C-1 = c4), sample volume and
+//   Cxx become a:, and one-shot samples end when the sample would have.
+//
+// EFFECTS:
+//   0xy arpeggio        Notes stepping through +0, +x, +y semitones
+//   1xx/2xx porta       Glide up/down over the row (tr:)
+//   3xx/5xy tone porta  Glide to the new note at the MOD's speed
+//   Axy/5xy/6xy slide   a: change gliding over the row
+//   Cxx volume          a:xx/64
+//   Bxx, Dxx, Fxx       Position jump, pattern break, speed/tempo
+// Other effects are skipped and counted in the warnings.
+// ============================================================================
+
+use crate::helper::PitchName;
+use crate::midi_import::{column_from_cells, config_line, format_number, write_song_csv};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Samples in a 31-sample (ProTracker) module
+const MOD_SAMPLE_COUNT: usize = 31;
+
+/// Rows in every MOD pattern
+const MOD_ROWS_PER_PATTERN: usize = 64;
+
+/// Where the "M.K." style signature sits
+const MOD_SIGNATURE_OFFSET: usize = 1080;
+
+/// Where the pattern data starts (right after the signature)
+const MOD_PATTERN_OFFSET: usize = 1084;
+
+/// Speed (ticks per row) and tempo at the start of every MOD
+const MOD_DEFAULT_SPEED: u32 = 6;
+const MOD_DEFAULT_TEMPO: u32 = 125;
+
+/// Loudest MOD volume
+const MOD_MAX_VOLUME: f32 = 64.0;
+
+/// Amiga period of ProTracker's lowest note C-1, imported as c4
+const PERIOD_C4: f32 = 856.0;
+
+/// Amiga period of ProTracker's highest note B-3
+const PERIOD_HIGHEST: f32 = 113.0;
+
+/// Amiga PAL clock: a sample plays PAL_CLOCK_HZ / (period * 2) samples/second
+const PAL_CLOCK_HZ: f32 = 7_093_789.2;
+
+/// Glide for pitch jumps (arpeggio steps): short enough to sound like a
+/// jump, but a glide never retriggers the envelope the way a new note does
+const PITCH_JUMP_SECONDS: f32 = 0.005;
+
+/// Longest glide the tracker accepts (tr: range)
+const MAX_GLIDE_SECONDS: f32 = 5.0;
+
+/// Tracker rows per MOD row when arpeggio is used (one per arpeggio note)
+const ARPEGGIO_STEPS: u32 = 3;
+
+/// Sample-name words that mean "play this on the drum instrument"
+const DRUM_SAMPLE_WORDS: &[&str] = &[
+    "drum", "kick", "snare", "hat", "cymbal", "crash", "ride", "clap", "perc", "noise", "tom",
+];
+
+// ============================================================================
+// OPTIONS AND RESULT
+// ============================================================================
+
+/// Settings for converting a MOD file
+#[derive(Clone, Debug)]
+pub struct ModImportOptions {
+    /// Instrument for pitched samples
+    pub instrument: String,
+
+    /// Instrument for samples named like drums
+    pub drum_instrument: String,
+
+    /// Tracker rows per MOD row (None = 3 if the song uses arpeggio, else 1)
+    pub rows_per_mod_row: Option<u32>,
+}
+
+impl Default for ModImportOptions {
+    fn default() -> Self {
+        Self {
+            instrument: "square".to_string(),
+            drum_instrument: "noise".to_string(),
+            rows_per_mod_row: None,
+        }
+    }
+}
+
+/// A converted module
+#[derive(Clone, Debug)]
+pub struct ModImport {
+    /// The song as tracker CSV text
+    pub csv: String,
+
+    /// Number of tracker channels (columns) used
+    pub channel_count: usize,
+
+    /// Number of rows written
+    pub row_count: usize,
+
+    /// Things that could not be converted exactly
+    pub warnings: Vec<String>,
+}
+
+// ============================================================================
+// MOD FILE LAYOUT
+// ============================================================================
+
+/// One sample header (the sample data itself is not needed)
+struct ModSample {
+    name: String,
+    length_bytes: usize,
+    volume: u8,
+    is_looping: bool,
+}
+
+/// One note slot of a pattern
+#[derive(Clone, Copy, Default)]
+struct ModCell {
+    sample: u8,
+    period: u16,
+    effect: u8,
+    parameter: u8,
+}
+
+/// Number of channels named by the signature ("M.K." = 4, "6CHN" = 6, ...)
+fn channels_from_signature(signature: &[u8]) -> Option<usize> {
+    let channels = match signature {
+        b"M.K." | b"M!K!" | b"M&K!" | b"FLT4" => 4,
+        b"FLT8" | b"OCTA" | b"CD81" => 8,
+        _ => {
+            let text = std::str::from_utf8(signature).ok()?;
+            let digits = text
+                .strip_suffix("CHN")
+                .or_else(|| text.strip_suffix("CH"))?;
+            digits.parse().ok()?
+        }
+    };
+    (1..=32).contains(&channels).then_some(channels)
+}
+
+fn read_u16_be(bytes: &[u8], offset: usize) -> usize {
+    u16::from_be_bytes([bytes[offset], bytes[offset + 1]]) as usize
+}
+
+/// Reads a fixed-size text field (padded with zero bytes)
+fn read_text(bytes: &[u8]) -> String {
+    let end = bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end])
+        .trim()
+        .replace(',', " ")
+}
+
+fn read_sample(bytes: &[u8], offset: usize) -> ModSample {
+    ModSample {
+        name: read_text(&bytes[offset..offset + 22]),
+        length_bytes: read_u16_be(bytes, offset + 22) * 2,
+        volume: bytes[offset + 25].min(MOD_MAX_VOLUME as u8),
+        is_looping: read_u16_be(bytes, offset + 28) > 1,
+    }
+}
+
+fn read_cell(bytes: &[u8], offset: usize) -> ModCell {
+    let cell = &bytes[offset..offset + 4];
+    ModCell {
+        sample: (cell[0] & 0xF0) | (cell[2] >> 4),
+        period: (((cell[0] & 0x0F) as u16) << 8) | cell[1] as u16,
+        effect: cell[2] & 0x0F,
+        parameter: cell[3],
+    }
+}
+
+/// Returns true if a sample name sounds like a drum
+fn is_drum_name(name: &str) -> bool {
+    let name = name.to_lowercase();
+    DRUM_SAMPLE_WORDS.iter().any(|word| name.contains(word))
+}
+
+/// Pitch of an Amiga period (856 = c4, halving the period = an octave up)
+/// Off-table periods from slides keep their cents.
+fn period_to_pitch(period: f32, semitone_offset: u8) -> PitchName {
+    let cents = (1200.0 * (PERIOD_C4 / period).log2()).round() + semitone_offset as f32 * 100.0;
+    let semitones = (cents / 100.0).round();
+    let mut cents_offset = cents - semitones * 100.0;
+    if cents_offset.abs() <= 5.0 {
+        // The period table is rounded to whole numbers; don't spell that out
+        cents_offset = 0.0;
+    }
+    PitchName::from_semitones_from_c0(48 + semitones as i32, cents_offset)
+}
+
+// ============================================================================
+// IMPORT
+// ============================================================================
+
+/// Reads a .mod file and converts it
+pub fn import_mod_file(path: &str, options: &ModImportOptions) -> Result<ModImport, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read MOD file '{}': {}", path, e))?;
+    import_mod(&bytes, options).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// What a channel is playing while the patterns are walked through
+#[derive(Default)]
+struct ChannelState {
+    /// Current sample number (0 = none yet)
+    sample: usize,
+    volume: f32,
+    period: f32,
+    portamento_target: f32,
+    portamento_speed: u8,
+    is_sounding: bool,
+
+    /// Tracker row where a one-shot sample runs out
+    release_row: Option<usize>,
+
+    /// The pitch or volume changed but could not be written yet
+    needs_update: bool,
+
+    /// The tracker cells written so far
+    column: Vec<String>,
+}
+
+/// Row timing while walking the song
+struct Timing {
+    speed: u32,
+    tempo: u32,
+    steps: u32,
+}
+
+impl Timing {
+    /// Seconds per MOD row (2.5 / tempo seconds per tick)
+    fn row_seconds(&self) -> f32 {
+        self.speed as f32 * 2.5 / self.tempo as f32
+    }
+
+    /// Slide ticks per row (slides skip the first tick of the row)
+    fn slide_ticks(&self) -> f32 {
+        self.speed.saturating_sub(1) as f32
+    }
+
+    /// Tracker tempo: speed 6 at tempo 125 is 125 BPM at 4 MOD rows per beat
+    fn bpm(&self) -> f32 {
+        self.tempo as f32 * MOD_DEFAULT_SPEED as f32 / self.speed as f32
+    }
+
+    fn rows_per_beat(&self) -> u32 {
+        4 * self.steps
+    }
+}
+
+/// Converts the bytes of a ProTracker MOD into tracker CSV
+pub fn import_mod(bytes: &[u8], options: &ModImportOptions) -> Result<ModImport, String> {
+    if bytes.starts_with(b"Extended Module:") {
+        return Err(
+            "XM modules are not supported. Save the module as .mod (e.g. in OpenMPT) first."
+                .to_string(),
+        );
+    }
+    if bytes.len() < MOD_PATTERN_OFFSET {
+        return Err("Not a MOD file (too short)".to_string());
+    }
+    let channel_count = channels_from_signature(&bytes[MOD_SIGNATURE_OFFSET..MOD_PATTERN_OFFSET])
+        .ok_or_else(|| {
+        "Not a ProTracker MOD (unknown signature; 15-sample modules are not supported)".to_string()
+    })?;
+
+    // ---- Header: title, samples, order list ----
+    let title = read_text(&bytes[..20]);
+    let samples: Vec<ModSample> = (0..MOD_SAMPLE_COUNT)
+        .map(|index| read_sample(bytes, 20 + index * 30))
+        .collect();
+    let song_length = (bytes[950] as usize).clamp(1, 128);
+    let orders = &bytes[952..1080];
+    let pattern_count = orders.iter().copied().max().unwrap_or(0) as usize + 1;
+    let pattern_bytes = MOD_ROWS_PER_PATTERN * channel_count * 4;
+    if bytes.len() < MOD_PATTERN_OFFSET + pattern_count * pattern_bytes {
+        return Err(format!(
+            "MOD file is cut short ({} patterns expected)",
+            pattern_count
+        ));
+    }
+    let cell_at = |pattern: usize, row: usize, channel: usize| {
+        read_cell(
+            bytes,
+            MOD_PATTERN_OFFSET + pattern * pattern_bytes + (row * channel_count + channel) * 4,
+        )
+    };
+
+    let sample_instruments: Vec<(&str, bool)> = samples
+        .iter()
+        .map(|sample| {
+            if is_drum_name(&sample.name) {
+                (options.drum_instrument.as_str(), true)
+            } else {
+                (options.instrument.as_str(), false)
+            }
+        })
+        .collect();
+
+    // Arpeggio needs several tracker rows per MOD row
+    let uses_arpeggio = orders[..song_length].iter().any(|pattern| {
+        (0..MOD_ROWS_PER_PATTERN).any(|row| {
+            (0..channel_count).any(|channel| {
+                let cell = cell_at(*pattern as usize, row, channel);
+                cell.effect == 0 && cell.parameter != 0
+            })
+        })
+    });
+    let steps = options
+        .rows_per_mod_row
+        .unwrap_or(if uses_arpeggio { ARPEGGIO_STEPS } else { 1 })
+        .clamp(1, 16);
+
+    let mut timing = Timing {
+        speed: MOD_DEFAULT_SPEED,
+        tempo: MOD_DEFAULT_TEMPO,
+        steps,
+    };
+    let mut starting_bpm = timing.bpm();
+    let mut bpm_cells: Vec<(usize, String)> = Vec::new();
+    let mut channels: Vec<ChannelState> = (0..channel_count)
+        .map(|_| ChannelState::default())
+        .collect();
+    let mut skipped_effects: BTreeMap<char, usize> = BTreeMap::new();
+    let mut skipped_arpeggios = 0;
+    let mut visited: HashSet<(usize, usize)> = HashSet::new();
+    let mut output_row = 0;
+    let mut order_index = 0;
+    let mut start_row = 0;
+
+    // ---- Walk the order list ----
+    'song: while order_index < song_length {
+        // Jumping back to a position already played means the song loops
+        if !visited.insert((order_index, start_row)) {
+            break;
+        }
+        let pattern = orders[order_index] as usize;
+        let mut next_position = (order_index + 1, 0);
+
+        for row in start_row..MOD_ROWS_PER_PATTERN {
+            let cells: Vec<ModCell> = (0..channel_count)
+                .map(|channel| cell_at(pattern, row, channel))
+                .collect();
+
+            // Row-wide commands first: speed/tempo apply to this row
+            let mut jump_to = None;
+            let mut break_to = None;
+            let bpm_before = (timing.bpm(), timing.rows_per_beat());
+            for cell in &cells {
+                match (cell.effect, cell.parameter) {
+                    (0xF, 0) => break 'song,
+                    (0xF, speed @ 1..=31) => timing.speed = speed as u32,
+                    (0xF, tempo) => timing.tempo = tempo as u32,
+                    (0xB, position) => jump_to = Some(position as usize),
+                    (0xD, row) => {
+                        break_to = Some(((row >> 4) * 10 + (row & 0x0F)) as usize);
+                    }
+                    _ => {}
+                }
+            }
+            if (timing.bpm(), timing.rows_per_beat()) != bpm_before {
+                if output_row == 0 {
+                    starting_bpm = timing.bpm();
+                } else {
+                    bpm_cells.retain(|(row, _)| *row != output_row);
+                    bpm_cells.push((output_row, bpm_cell(&timing)));
+                }
+            }
+
+            for (state, cell) in channels.iter_mut().zip(&cells) {
+                let step_cells = convert_cell(
+                    state,
+                    cell,
+                    &samples,
+                    &sample_instruments,
+                    &timing,
+                    output_row,
+                    &mut skipped_effects,
+                    &mut skipped_arpeggios,
+                );
+                state.column.extend(step_cells);
+            }
+            output_row += steps as usize;
+
+            if jump_to.is_some() || break_to.is_some() {
+                next_position = (
+                    jump_to.unwrap_or(order_index + 1),
+                    break_to.unwrap_or(0).min(MOD_ROWS_PER_PATTERN - 1),
+                );
+                break;
+            }
+        }
+        (order_index, start_row) = next_position;
+    }
+
+    // Notes still sounding end with the song
+    for state in &mut channels {
+        if state.is_sounding {
+            state.column.push(".".to_string());
+        }
+    }
+
+    // ---- Warnings ----
+    let mut warnings = Vec::new();
+    if !skipped_effects.is_empty() {
+        let list: Vec<String> = skipped_effects
+            .iter()
+            .map(|(effect, count)| format!("{}xx ({}x)", effect, count))
+            .collect();
+        warnings.push(format!("Effects not converted: {}", list.join(", ")));
+    }
+    if skipped_arpeggios > 0 {
+        warnings.push(format!(
+            "{} arpeggios skipped: they need at least {} rows per MOD row",
+            skipped_arpeggios, ARPEGGIO_STEPS
+        ));
+    }
+
+    // ---- Write the CSV ----
+    let mut column_names: Vec<String> = (1..=channel_count)
+        .map(|channel| format!("Channel {}", channel))
+        .collect();
+    let mut columns: Vec<Vec<String>> = channels.into_iter().map(|state| state.column).collect();
+    if !bpm_cells.is_empty() {
+        column_names.insert(0, "Master".to_string());
+        columns.insert(0, column_from_cells(bpm_cells));
+    }
+    let title = if title.is_empty() { "Untitled" } else { &title };
+    let preamble = [
+        config_line(title, starting_bpm, 4 * steps),
+        format!(
+            "// Imported from ProTracker MOD: {} tracker rows per MOD row",
+            steps
+        ),
+    ];
+
+    Ok(ModImport {
+        csv: write_song_csv(&column_names, &columns, &preamble),
+        channel_count: columns.len(),
+        row_count: columns.iter().map(Vec::len).max().unwrap_or(0),
+        warnings,
+    })
+}
+
+/// The master cell for the current speed and tempo
+fn bpm_cell(timing: &Timing) -> String {
+    if timing.rows_per_beat() == 4 {
+        format!("master bpm:{}", format_number(timing.bpm()))
+    } else {
+        format!(
+            "master bpm:{}'{}",
+            format_number(timing.bpm()),
+            timing.rows_per_beat()
+        )
+    }
+}
+
+/// Converts one MOD cell into the tracker cells for its rows
+#[allow(clippy::too_many_arguments)]
+fn convert_cell(
+    state: &mut ChannelState,
+    cell: &ModCell,
+    samples: &[ModSample],
+    sample_instruments: &[(&str, bool)],
+    timing: &Timing,
+    output_row: usize,
+    skipped_effects: &mut BTreeMap<char, usize>,
+    skipped_arpeggios: &mut usize,
+) -> Vec<String> {
+    let steps = timing.steps as usize;
+    let mut step_cells: Vec<Option<String>> = vec![None; steps];
+    let mut glide_seconds: f32 = 0.0;
+    let mut pitch_changed = false;
+    let mut volume_changed = false;
+
+    // A sample number resets the volume to the sample's own
+    if (1..=samples.len()).contains(&(cell.sample as usize)) {
+        state.sample = cell.sample as usize;
+        state.volume = samples[state.sample - 1].volume as f32;
+        volume_changed = true;
+    }
+    if cell.effect == 0xC {
+        state.volume = (cell.parameter as f32).min(MOD_MAX_VOLUME);
+        volume_changed = true;
+    }
+    let (instrument, is_drum) = match state.sample {
+        0 => ("", false),
+        sample => sample_instruments[sample - 1],
+    };
+
+    // ---- New note ----
+    let is_tone_portamento = cell.effect == 0x3 || cell.effect == 0x5;
+    if cell.period > 0 && !is_tone_portamento {
+        if state.sample == 0 || samples[state.sample - 1].length_bytes == 0 {
+            // An empty sample plays silence
+            if state.is_sounding {
+                step_cells[0] = Some(".".to_string());
+            }
+            state.is_sounding = false;
+        } else {
+            let sample = &samples[state.sample - 1];
+            state.period = (cell.period as f32).clamp(PERIOD_HIGHEST, PERIOD_C4);
+            state.portamento_target = state.period;
+            state.is_sounding = true;
+            state.needs_update = false;
+            state.release_row = (!sample.is_looping).then(|| {
+                let sample_seconds = sample.length_bytes as f32 * state.period * 2.0 / PAL_CLOCK_HZ;
+                let step_seconds = timing.row_seconds() / steps as f32;
+                output_row + ((sample_seconds / step_seconds).ceil() as usize).max(1)
+            });
+            step_cells[0] = Some(note_cell(state, instrument, is_drum, 0, None));
+            volume_changed = false;
+        }
+    }
+
+    // ---- Continuous effects ----
+    let x = cell.parameter >> 4;
+    let y = cell.parameter & 0x0F;
+    match cell.effect {
+        0x0 if cell.parameter != 0 => {
+            if steps < ARPEGGIO_STEPS as usize {
+                *skipped_arpeggios += 1;
+            } else if state.is_sounding && !is_drum {
+                for (step, step_cell) in step_cells.iter_mut().enumerate() {
+                    let offset = [0, x, y][step % 3];
+                    if step_cell.is_none() {
+                        *step_cell = Some(note_cell(
+                            state,
+                            instrument,
+                            is_drum,
+                            offset,
+                            Some(PITCH_JUMP_SECONDS),
+                        ));
+                    }
+                }
+                // The next row goes back to the base note
+                state.needs_update = true;
+            }
+        }
+        0x1 | 0x2 if cell.parameter != 0 && state.is_sounding => {
+            let change = cell.parameter as f32 * timing.slide_ticks();
+            let period = if cell.effect == 0x1 {
+                state.period - change
+            } else {
+                state.period + change
+            };
+            state.period = period.clamp(PERIOD_HIGHEST, PERIOD_C4);
+            pitch_changed = true;
+            glide_seconds = timing.row_seconds();
+        }
+        0x3 | 0x5 => {
+            if cell.period > 0 {
+                state.portamento_target = (cell.period as f32).clamp(PERIOD_HIGHEST, PERIOD_C4);
+            }
+            if cell.effect == 0x3 && cell.parameter != 0 {
+                state.portamento_speed = cell.parameter;
+            }
+            let per_row = state.portamento_speed as f32 * timing.slide_ticks();
+            let distance = (state.portamento_target - state.period).abs();
+            if state.is_sounding && distance > 0.0 && per_row > 0.0 {
+                // One glide for the whole slide; later 300 rows just continue it
+                glide_seconds = (distance / per_row * timing.row_seconds()).min(MAX_GLIDE_SECONDS);
+                state.period = state.portamento_target;
+                pitch_changed = true;
+            }
+        }
+        _ => {}
+    }
+    if matches!(cell.effect, 0x5 | 0x6 | 0xA) && cell.parameter != 0 {
+        let change = if x > 0 { x as f32 } else { -(y as f32) } * timing.slide_ticks();
+        state.volume = (state.volume + change).clamp(0.0, MOD_MAX_VOLUME);
+        volume_changed = true;
+        glide_seconds = glide_seconds.max(timing.row_seconds());
+    }
+    match cell.effect {
+        0x0 | 0x1 | 0x2 | 0x3 | 0x5 | 0xA | 0xB | 0xC | 0xD | 0xF => {}
+        _ if cell.parameter == 0 && cell.effect != 0xE => {}
+        effect => {
+            let letter = char::from_digit(effect as u32, 16)
+                .unwrap_or('?')
+                .to_ascii_uppercase();
+            *skipped_effects.entry(letter).or_default() += 1;
+        }
+    }
+
+    // ---- Pitch/volume changes go in the first free row ----
+    if state.is_sounding && (pitch_changed || volume_changed || state.needs_update) {
+        match step_cells.iter().position(Option::is_none) {
+            Some(step) => {
+                let glide = (glide_seconds > 0.0).then_some(glide_seconds);
+                step_cells[step] = Some(if pitch_changed || (state.needs_update && !is_drum) {
+                    note_cell(
+                        state,
+                        instrument,
+                        is_drum,
+                        0,
+                        Some(glide.unwrap_or(PITCH_JUMP_SECONDS)),
+                    )
+                } else {
+                    volume_cell(state, glide)
+                });
+                state.needs_update = false;
+            }
+            None => state.needs_update = true,
+        }
+    }
+
+    // ---- Fill the rest: held notes, and one-shot samples running out ----
+    step_cells
+        .into_iter()
+        .enumerate()
+        .map(|(step, step_cell)| {
+            step_cell.unwrap_or_else(|| {
+                if !state.is_sounding {
+                    String::new()
+                } else if state
+                    .release_row
+                    .is_some_and(|release_row| output_row + step >= release_row)
+                {
+                    state.is_sounding = false;
+                    ".".to_string()
+                } else {
+                    "-".to_string()
+                }
+            })
+        })
+        .collect()
+}
+
+/// A note cell at the channel's pitch (plus an offset in semitones)
+/// With a glide time the note slides there instead of restarting.
+fn note_cell(
+    state: &ChannelState,
+    instrument: &str,
+    is_drum: bool,
+    semitone_offset: u8,
+    glide_seconds: Option<f32>,
+) -> String {
+    let amplitude = format_number(state.volume / MOD_MAX_VOLUME);
+    let mut text = if is_drum {
+        format!("{} a:{}", instrument, amplitude)
+    } else {
+        format!(
+            "{} {} a:{}",
+            period_to_pitch(state.period, semitone_offset),
+            instrument,
+            amplitude
+        )
+    };
+    if let Some(glide_seconds) = glide_seconds {
+        if is_drum {
+            // Pitchless sounds can't glide; only the volume changes
+            return volume_cell(state, Some(glide_seconds));
+        }
+        text.push_str(&format!(" tr:{}", format_number(glide_seconds)));
+    }
+    text
+}
+
+/// A volume change that keeps the note playing
+fn volume_cell(state: &ChannelState, glide_seconds: Option<f32>) -> String {
+    let amplitude = format_number(state.volume / MOD_MAX_VOLUME);
+    match glide_seconds {
+        Some(glide_seconds) => format!("- a:{} tr:{}", amplitude, format_number(glide_seconds)),
+        None => format!("- a:{}", amplitude),
+    }
+}
+
+/// Returns true for paths ending in ".mod" (any case)
+pub fn is_mod_path(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("mod"))
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Song;
+
+    /// Builds a 4-channel module with one pattern from (row, channel, cell)
+    fn build_mod(cells: &[(usize, usize, ModCell)]) -> Vec<u8> {
+        let mut bytes = vec![0u8; MOD_PATTERN_OFFSET + MOD_ROWS_PER_PATTERN * 4 * 4];
+        bytes[..9].copy_from_slice(b"Test Tune");
+
+        // Sample 1: a looping lead, sample 2: a short one-shot snare
+        let mut write_sample = |index: usize, name: &[u8], words: u16, volume: u8, loop_words| {
+            let offset = 20 + index * 30;
+            bytes[offset..offset + name.len()].copy_from_slice(name);
+            bytes[offset + 22..offset + 24].copy_from_slice(&u16::to_be_bytes(words));
+            bytes[offset + 25] = volume;
+            bytes[offset + 28..offset + 30].copy_from_slice(&u16::to_be_bytes(loop_words));
+        };
+        write_sample(0, b"lead", 1000, 64, 500);
+        write_sample(1, b"snare", 200, 48, 1);
+
+        bytes[950] = 1; // song length: one pattern
+        bytes[MOD_SIGNATURE_OFFSET..MOD_PATTERN_OFFSET].copy_from_slice(b"M.K.");
+        for (row, channel, cell) in cells {
+            let offset = MOD_PATTERN_OFFSET + (row * 4 + channel) * 4;
+            bytes[offset] = (cell.sample & 0xF0) | (cell.period >> 8) as u8;
+            bytes[offset + 1] = cell.period as u8;
+            bytes[offset + 2] = (cell.sample << 4) | cell.effect;
+            bytes[offset + 3] = cell.parameter;
+        }
+        bytes
+    }
+
+    fn cell(sample: u8, period: u16, effect: u8, parameter: u8) -> ModCell {
+        ModCell {
+            sample,
+            period,
+            effect,
+            parameter,
+        }
+    }
+
+    #[test]
+    fn test_period_to_pitch() {
+        assert_eq!(period_to_pitch(856.0, 0).to_string(), "c4");
+        assert_eq!(period_to_pitch(428.0, 0).to_string(), "c5");
+        assert_eq!(period_to_pitch(453.0, 0).to_string(), "b4"); // table rounding
+        assert_eq!(period_to_pitch(428.0, 7).to_string(), "g5");
+        assert_eq!(period_to_pitch(440.0, 0).to_string(), "c5-48c");
+    }
+
+    #[test]
+    fn test_import_effects() {
+        let bytes = build_mod(&[
+            (0, 0, cell(1, 856, 0xC, 32)), // c4, volume 32
+            (0, 1, cell(2, 428, 0, 0)),    // snare hit
+            (1, 0, cell(0, 0, 0xA, 0x02)), // volume slide down 2/tick
+            (2, 0, cell(0, 428, 0x3, 16)), // tone portamento to c5
+            (4, 0, cell(0, 0, 0x0, 0x47)), // arpeggio +4 +7
+            (5, 0, cell(0, 0, 0xF, 3)),    // speed 3
+            (6, 0, cell(0, 0, 0xD, 0)),    // pattern break (ends the song)
+        ]);
+        let import = import_mod(&bytes, &ModImportOptions::default()).unwrap();
+        assert!(import.warnings.is_empty(), "{:?}", import.warnings);
+
+        let lines: Vec<&str> = import.csv.lines().collect();
+        assert_eq!(lines[0], "Master,Channel 1,Channel 2,Channel 3,Channel 4");
+        assert_eq!(
+            lines[1],
+            "config, title: Test Tune, tempo_bpm: 125, tick_duration: 0.04"
+        );
+        // Rows are 3 tracker rows per MOD row because of the arpeggio
+        let rows: Vec<&str> = lines[3..].to_vec();
+        assert_eq!(rows[0], ",c4 square a:0.5,noise a:0.75,,");
+        assert_eq!(rows[2], ",-,.,,");
+        assert_eq!(rows[3], ",- a:0.344 tr:0.12,,,");
+        assert_eq!(rows[6], ",c5 square a:0.344 tr:0.642,,,");
+        assert_eq!(rows[12], ",c5 square a:0.344 tr:0.005,,,");
+        assert_eq!(rows[13], ",e5 square a:0.344 tr:0.005,,,");
+        assert_eq!(rows[14], ",g5 square a:0.344 tr:0.005,,,");
+        assert_eq!(rows[15], "master bpm:250'12,c5 square a:0.344 tr:0.005,,,");
+        assert_eq!(rows[21], ",.,,,");
+        assert_eq!(import.row_count, 22);
+
+        let song = Song::parse(&import.csv, import.channel_count);
+        assert!(song.diagnostics().is_empty());
+    }
+}
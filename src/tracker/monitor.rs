@@ -0,0 +1,358 @@
+// ============================================================================
+// MONITOR.RS - Rolling Spectrogram of the Master Output
+// ============================================================================
+//
+// Shows where the mix's energy is while a song plays, so a bass line and
+// a kick fighting over the same low end can be seen, not just heard:
+//
+//   tracker assets/song.csv --monitor
+//
+// (the --tui view, with a Spectrum panel above the rows: time runs left
+// to right, low notes at the bottom, louder is brighter).
+//
+// HOW IT WORKS:
+// Like a recording (recorder.rs), a MonitorTap in the audio callback
+// copies each block of output, mixed down to mono, into fixed-size chunks
+// on a lock-free ring buffer (command_queue.rs). It never waits: if the
+// view falls a whole queue behind, chunks are dropped, and the picture
+// only skips a moment. The view's SpectrumMonitor empties the queue every
+// frame and runs a short-time FFT over it (Hann window, MONITOR_FFT_SIZE
+// samples, a new column every MONITOR_HOP_SAMPLES), keeping the newest
+// MONITOR_HISTORY_COLUMNS columns. The columns are grouped into bands
+// spaced evenly in pitch, so the octaves where kick and bass live get as
+// many rows on screen as the high ones.
+// ============================================================================
+
+use crate::command_queue::{Consumer, Producer, command_queue};
+use realfft::num_complex::Complex;
+use realfft::{RealFftPlanner, RealToComplex};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Samples per FFT (4096 at 48 kHz: about 12 Hz between bins, fine
+/// enough to tell a 50 Hz kick from a 70 Hz bass note)
+pub const MONITOR_FFT_SIZE: usize = 4096;
+
+/// Samples between columns (about 21 ms at 48 kHz)
+pub const MONITOR_HOP_SAMPLES: usize = 1024;
+
+/// Columns kept (about 11 seconds at 48 kHz; wider than any terminal)
+pub const MONITOR_HISTORY_COLUMNS: usize = 512;
+
+/// Lowest and highest frequencies the bands cover
+pub const MONITOR_MIN_HZ: f32 = 30.0;
+pub const MONITOR_MAX_HZ: f32 = 16000.0;
+
+/// Quietest level shown; band levels never go below it
+pub const MONITOR_FLOOR_DB: f32 = -80.0;
+
+/// Mono samples per chunk sent from the audio callback
+const MONITOR_CHUNK_SAMPLES: usize = 1024;
+
+/// Chunks the queue holds (about 1.4 seconds at 48 kHz)
+const MONITOR_QUEUE_CHUNKS: usize = 64;
+
+// ============================================================================
+// AUDIO CAPTURE
+// ============================================================================
+
+/// A piece of the output, copied by value so pushing it never allocates
+struct MonitorChunk {
+    samples: [f32; MONITOR_CHUNK_SAMPLES],
+
+    /// How many of the samples are used
+    length: usize,
+}
+
+/// The audio thread's end of the monitor: copies output into the queue
+pub struct MonitorTap {
+    chunks: Producer<MonitorChunk>,
+}
+
+impl MonitorTap {
+    /// Sends a block of interleaved stereo output, mixed to mono
+    /// Call this from the audio callback, after rendering. It never blocks.
+    pub fn capture(&mut self, samples: &[f32]) {
+        for block in samples.chunks(MONITOR_CHUNK_SAMPLES * 2) {
+            let mut chunk = MonitorChunk {
+                samples: [0.0; MONITOR_CHUNK_SAMPLES],
+                length: block.len() / 2,
+            };
+            for (mono, frame) in chunk.samples.iter_mut().zip(block.chunks_exact(2)) {
+                *mono = 0.5 * (frame[0] + frame[1]);
+            }
+            // A full queue means the view is behind; it misses this chunk
+            let _ = self.chunks.push(chunk);
+        }
+    }
+}
+
+/// Starts a monitor; put the tap in the audio callback and call
+/// SpectrumMonitor::update from the view
+pub fn start_monitor(sample_rate: u32) -> (MonitorTap, SpectrumMonitor) {
+    let (producer, consumer) = command_queue(MONITOR_QUEUE_CHUNKS);
+    (
+        MonitorTap { chunks: producer },
+        SpectrumMonitor::new(sample_rate, Some(consumer)),
+    )
+}
+
+// ============================================================================
+// SPECTRUM ANALYSIS
+// ============================================================================
+
+/// The view's end of the monitor: the spectrogram of the newest output
+pub struct SpectrumMonitor {
+    sample_rate: u32,
+
+    /// Chunks from the tap (None: samples come from push_samples only)
+    chunks: Option<Consumer<MonitorChunk>>,
+
+    fft: Arc<dyn RealToComplex<f32>>,
+    window: Vec<f32>,
+
+    /// Scales bin magnitudes so a full-scale sine reads 1.0
+    magnitude_scale: f32,
+
+    /// Samples not analyzed yet (less than one FFT's worth between calls)
+    pending: Vec<f32>,
+
+    /// Bin magnitudes of each column, oldest first
+    columns: VecDeque<Vec<f32>>,
+
+    /// FFT buffers (allocated once, reused for every column)
+    input: Vec<f32>,
+    spectrum: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+}
+
+impl SpectrumMonitor {
+    /// A monitor fed by a tap's chunks, or by push_samples when there is
+    /// no tap
+    fn new(sample_rate: u32, chunks: Option<Consumer<MonitorChunk>>) -> Self {
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(MONITOR_FFT_SIZE);
+        let window: Vec<f32> = (0..MONITOR_FFT_SIZE)
+            .map(|i| {
+                let phase = std::f32::consts::TAU * i as f32 / MONITOR_FFT_SIZE as f32;
+                0.5 - 0.5 * phase.cos()
+            })
+            .collect();
+        let magnitude_scale = 2.0 / window.iter().sum::<f32>();
+        Self {
+            sample_rate: sample_rate.max(1),
+            chunks,
+            input: fft.make_input_vec(),
+            spectrum: fft.make_output_vec(),
+            scratch: fft.make_scratch_vec(),
+            fft,
+            window,
+            magnitude_scale,
+            pending: Vec::with_capacity(MONITOR_FFT_SIZE + MONITOR_CHUNK_SAMPLES),
+            columns: VecDeque::with_capacity(MONITOR_HISTORY_COLUMNS),
+        }
+    }
+
+    /// A monitor with no tap, for analyzing samples handed to it directly
+    pub fn without_tap(sample_rate: u32) -> Self {
+        Self::new(sample_rate, None)
+    }
+
+    /// Analyzes everything the tap has sent since the last call
+    /// Returns how many columns were added.
+    pub fn update(&mut self) -> usize {
+        let mut added = 0;
+        while let Some(chunk) = self.chunks.as_mut().and_then(Consumer::pop) {
+            added += self.push_samples(&chunk.samples[..chunk.length]);
+        }
+        added
+    }
+
+    /// Analyzes mono samples that follow the ones before
+    /// Returns how many columns were added.
+    pub fn push_samples(&mut self, samples: &[f32]) -> usize {
+        self.pending.extend_from_slice(samples);
+        let mut added = 0;
+        let mut start = 0;
+        while self.pending.len() - start >= MONITOR_FFT_SIZE {
+            self.analyze(start);
+            start += MONITOR_HOP_SAMPLES;
+            added += 1;
+        }
+        self.pending.drain(..start);
+        added
+    }
+
+    /// Adds the column for the FFT_SIZE pending samples from start
+    fn analyze(&mut self, start: usize) {
+        let frame = &self.pending[start..start + MONITOR_FFT_SIZE];
+        for ((input, sample), weight) in self.input.iter_mut().zip(frame).zip(&self.window) {
+            *input = sample * weight;
+        }
+        // Only fails for buffers of the wrong length, which these never are
+        let _ =
+            self.fft
+                .process_with_scratch(&mut self.input, &mut self.spectrum, &mut self.scratch);
+
+        // Reuse the oldest column's memory once the history is full
+        let mut column = if self.columns.len() >= MONITOR_HISTORY_COLUMNS {
+            self.columns.pop_front().unwrap_or_default()
+        } else {
+            Vec::with_capacity(self.spectrum.len())
+        };
+        column.clear();
+        column.extend(
+            self.spectrum
+                .iter()
+                .map(|bin| bin.norm() * self.magnitude_scale),
+        );
+        self.columns.push_back(column);
+    }
+
+    /// Number of columns analyzed so far (at most MONITOR_HISTORY_COLUMNS)
+    pub fn column_count(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Seconds between columns
+    pub fn column_seconds(&self) -> f32 {
+        MONITOR_HOP_SAMPLES as f32 / self.sample_rate as f32
+    }
+
+    /// Edges of band_count bands spaced evenly in pitch, from
+    /// MONITOR_MIN_HZ to MONITOR_MAX_HZ (or Nyquist, if lower):
+    /// band_count + 1 frequencies, low to high
+    pub fn band_edges(&self, band_count: usize) -> Vec<f32> {
+        let top = MONITOR_MAX_HZ.min(self.sample_rate as f32 / 2.0);
+        let ratio = (top / MONITOR_MIN_HZ).max(1.0);
+        (0..=band_count)
+            .map(|edge| MONITOR_MIN_HZ * ratio.powf(edge as f32 / band_count.max(1) as f32))
+            .collect()
+    }
+
+    /// Levels of the newest column_count columns (fewer until that many
+    /// have been analyzed), oldest first. Each column has band_count
+    /// levels in dB, low to high (see band_edges): the loudest bin in the
+    /// band, or the nearest bin for bands narrower than one. A full-scale
+    /// sine reads about 0 dB; nothing reads below MONITOR_FLOOR_DB.
+    pub fn band_levels(&self, column_count: usize, band_count: usize) -> Vec<Vec<f32>> {
+        let bin_hz = self.sample_rate as f32 / MONITOR_FFT_SIZE as f32;
+        let last_bin = MONITOR_FFT_SIZE / 2;
+        let bin_ranges: Vec<(usize, usize)> = self
+            .band_edges(band_count)
+            .windows(2)
+            .map(|edges| {
+                let first = ((edges[0] / bin_hz).ceil() as usize).min(last_bin);
+                let last = ((edges[1] / bin_hz).floor() as usize).min(last_bin);
+                if first <= last {
+                    (first, last)
+                } else {
+                    let center = (edges[0] * edges[1]).sqrt();
+                    let nearest = ((center / bin_hz).round() as usize).min(last_bin);
+                    (nearest, nearest)
+                }
+            })
+            .collect();
+
+        let skip = self.columns.len().saturating_sub(column_count);
+        self.columns
+            .iter()
+            .skip(skip)
+            .map(|column| {
+                bin_ranges
+                    .iter()
+                    .map(|&(first, last)| {
+                        let peak = column[first..=last].iter().copied().fold(0.0, f32::max);
+                        if peak > 0.0 {
+                            (20.0 * peak.log10()).max(MONITOR_FLOOR_DB)
+                        } else {
+                            MONITOR_FLOOR_DB
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency_hz: f32, amplitude: f32, sample_rate: u32, length: usize) -> Vec<f32> {
+        (0..length)
+            .map(|i| {
+                let phase = std::f32::consts::TAU * frequency_hz * i as f32 / sample_rate as f32;
+                amplitude * phase.sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bass_and_kick_land_in_their_bands() {
+        let mut monitor = SpectrumMonitor::without_tap(48000);
+        let mut samples = sine(55.0, 1.0, 48000, 24000);
+        for (sample, high) in samples.iter_mut().zip(sine(2000.0, 0.1, 48000, 24000)) {
+            *sample += high;
+        }
+        let added = monitor.push_samples(&samples);
+        assert_eq!(added, (24000 - MONITOR_FFT_SIZE) / MONITOR_HOP_SAMPLES + 1);
+
+        let edges = monitor.band_edges(48);
+        assert_eq!(edges.len(), 49);
+        assert_eq!(edges[0], MONITOR_MIN_HZ);
+        assert!((edges[48] - MONITOR_MAX_HZ).abs() < 1.0);
+
+        let levels = monitor.band_levels(4, 48);
+        assert_eq!(levels.len(), 4);
+        let column = &levels[3];
+        let band_of = |hz: f32| {
+            edges
+                .windows(2)
+                .position(|e| e[0] <= hz && hz < e[1])
+                .unwrap()
+        };
+
+        // Full scale reads about 0 dB, a tenth about -20 dB
+        assert!(column[band_of(55.0)] > -2.0, "{:?}", column);
+        assert!((column[band_of(2000.0)] + 20.0).abs() < 2.0, "{:?}", column);
+        assert!(column[band_of(500.0)] < -40.0);
+        assert!(column.iter().all(|&level| level >= MONITOR_FLOOR_DB));
+    }
+
+    #[test]
+    fn test_tap_feeds_the_monitor_and_history_is_kept_short() {
+        let (mut tap, mut monitor) = start_monitor(8000);
+        assert_eq!(monitor.update(), 0);
+        assert!((monitor.column_seconds() - 0.128).abs() < 1e-6);
+
+        // Stereo, left only: the mono mix is half as loud
+        let left = sine(1000.0, 1.0, 8000, MONITOR_FFT_SIZE);
+        let stereo: Vec<f32> = left.iter().flat_map(|&sample| [sample, 0.0]).collect();
+        tap.capture(&stereo);
+        assert_eq!(monitor.update(), 1);
+        let edges = monitor.band_edges(24);
+        assert!((edges[24] - 4000.0).abs() < 1.0);
+        let band = edges
+            .windows(2)
+            .position(|e| e[0] <= 1000.0 && 1000.0 < e[1]);
+        let level = monitor.band_levels(1, 24)[0][band.unwrap()];
+        assert!((level + 6.0).abs() < 2.0, "level = {}", level);
+
+        // Only the newest columns are kept
+        let block = vec![0.0; MONITOR_HOP_SAMPLES];
+        for _ in 0..MONITOR_HISTORY_COLUMNS + 10 {
+            monitor.push_samples(&block);
+        }
+        assert_eq!(monitor.column_count(), MONITOR_HISTORY_COLUMNS);
+        assert_eq!(monitor.band_levels(1000, 8).len(), MONITOR_HISTORY_COLUMNS);
+        assert!(
+            monitor.band_levels(1, 8)[0]
+                .iter()
+                .all(|&level| level == MONITOR_FLOOR_DB)
+        );
+    }
+}
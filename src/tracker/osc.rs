@@ -0,0 +1,720 @@
+// ============================================================================
+// OSC.RS - Open Sound Control Server
+// ============================================================================
+//
+// Lets other programs (TouchOSC, SuperCollider, Max, Python scripts, ...)
+// drive the engine over the network while it plays:
+//
+//   tracker osc --port 9000
+//   tracker osc assets/song.csv --port 9000
+//
+// ADDRESSES (channels and rows are counted from 1):
+//   /channel/3/trigger "c4 sine a:0.8"   Play any cell on channel 3
+//   /channel/3/release                   Slow release (like an empty cell)
+//   /channel/3/cut                       Fast release (like ".")
+//   /channel/3/mute [1|0]                Mute (or unmute with 0)
+//   /channel/3/solo [1|0]                Solo (or unsolo with 0)
+//   /all/release                         Release every channel
+//   /master/reverb 0.5 0.3               Master effect with its parameters
+//   /master "rv:0.5'0.3 tr:2"            Any master cell
+//   /transport/jump 17                   Continue from row 17
+//   /transport/pause [1|0]               Pause (or resume with 0)
+//   /transport/play                      Resume
+//   /transport/reset                     Start the song again
+//
+// HOW IT WORKS:
+// - A network thread receives UDP packets and decodes them (messages and
+//   bundles; bundle time tags are ignored and everything runs right away).
+// - OscRouter turns each message into EngineCommands by parsing the same
+//   cell text a song would use.
+// - The commands go into the real-time engine's lock-free queue, so the
+//   audio thread never waits for the network.
+// - With log_events(), the router also keeps the channel cells it played
+//   (trigger, release, cut), so "tracker osc --capture" can save them as
+//   a song (recorder.rs).
+//
+// Only the OSC basics are decoded: int32, float32, string, blob, int64,
+// double, and the True/False/Nil/Impulse tags.
+// ============================================================================
+
+use crate::engine::{EngineCommand, EngineController};
+use crate::helper::FrequencyTable;
+use crate::parser::{CellAction, Severity, parse_cell_text};
+use crate::recorder::EventLog;
+use std::collections::BTreeSet;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Port the server listens on unless told otherwise
+pub const DEFAULT_OSC_PORT: u16 = 9000;
+
+/// Largest UDP packet accepted
+const MAX_PACKET_BYTES: usize = 65_536;
+
+/// How often the network thread checks whether it should stop
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// ============================================================================
+// MESSAGES
+// ============================================================================
+
+/// One OSC argument
+#[derive(Clone, Debug, PartialEq)]
+pub enum OscArgument {
+    Int(i32),
+    Float(f32),
+    String(String),
+    Blob(Vec<u8>),
+    Long(i64),
+    Double(f64),
+    Bool(bool),
+    /// Nil and Impulse (no value)
+    Nothing,
+}
+
+impl OscArgument {
+    /// The argument as a number (true = 1, false = 0), if it is one
+    pub fn as_number(&self) -> Option<f32> {
+        match self {
+            OscArgument::Int(value) => Some(*value as f32),
+            OscArgument::Float(value) => Some(*value),
+            OscArgument::Long(value) => Some(*value as f32),
+            OscArgument::Double(value) => Some(*value as f32),
+            OscArgument::Bool(value) => Some(if *value { 1.0 } else { 0.0 }),
+            OscArgument::String(text) => text.trim().parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// One OSC message: an address like "/channel/1/trigger" and its arguments
+#[derive(Clone, Debug, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub arguments: Vec<OscArgument>,
+}
+
+impl OscMessage {
+    /// Encodes the message as an OSC packet (for clients and tests)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_padded_string(&mut bytes, self.address.as_bytes());
+        let mut type_tags = String::from(",");
+        let mut data = Vec::new();
+        for argument in &self.arguments {
+            match argument {
+                OscArgument::Int(value) => {
+                    type_tags.push('i');
+                    data.extend_from_slice(&value.to_be_bytes());
+                }
+                OscArgument::Float(value) => {
+                    type_tags.push('f');
+                    data.extend_from_slice(&value.to_be_bytes());
+                }
+                OscArgument::String(text) => {
+                    type_tags.push('s');
+                    write_padded_string(&mut data, text.as_bytes());
+                }
+                OscArgument::Blob(blob) => {
+                    type_tags.push('b');
+                    data.extend_from_slice(&(blob.len() as i32).to_be_bytes());
+                    data.extend_from_slice(blob);
+                    data.resize(data.len().next_multiple_of(4), 0);
+                }
+                OscArgument::Long(value) => {
+                    type_tags.push('h');
+                    data.extend_from_slice(&value.to_be_bytes());
+                }
+                OscArgument::Double(value) => {
+                    type_tags.push('d');
+                    data.extend_from_slice(&value.to_be_bytes());
+                }
+                OscArgument::Bool(value) => type_tags.push(if *value { 'T' } else { 'F' }),
+                OscArgument::Nothing => type_tags.push('N'),
+            }
+        }
+        write_padded_string(&mut bytes, type_tags.as_bytes());
+        bytes.extend_from_slice(&data);
+        bytes
+    }
+}
+
+/// Writes a string with its zero terminator, padded to a multiple of 4
+fn write_padded_string(bytes: &mut Vec<u8>, text: &[u8]) {
+    bytes.extend_from_slice(text);
+    bytes.push(0);
+    bytes.resize(bytes.len().next_multiple_of(4), 0);
+}
+
+// ============================================================================
+// DECODING
+// ============================================================================
+
+/// Reads OSC data front to back
+struct OscReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> OscReader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let end = self.position + count;
+        let slice = self
+            .bytes
+            .get(self.position..end)
+            .ok_or_else(|| "Packet ends in the middle of a value".to_string())?;
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().expect("take returns N bytes"))
+    }
+
+    fn read_string(&mut self) -> Result<String, String> {
+        let rest = &self.bytes[self.position..];
+        let length = rest
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or_else(|| "String without a zero terminator".to_string())?;
+        let text = String::from_utf8_lossy(&rest[..length]).into_owned();
+        self.take((length + 1).next_multiple_of(4))?;
+        Ok(text)
+    }
+}
+
+/// Decodes a UDP packet into its messages (bundles are flattened)
+pub fn decode_packet(bytes: &[u8]) -> Result<Vec<OscMessage>, String> {
+    let mut messages = Vec::new();
+    decode_into(bytes, &mut messages)?;
+    Ok(messages)
+}
+
+fn decode_into(bytes: &[u8], messages: &mut Vec<OscMessage>) -> Result<(), String> {
+    let mut reader = OscReader { bytes, position: 0 };
+    let address = reader.read_string()?;
+
+    if address == "#bundle" {
+        reader.take(8)?; // time tag: bundles play right away
+        while reader.position < bytes.len() {
+            let size = i32::from_be_bytes(reader.take_array()?);
+            let element = reader.take(size.max(0) as usize)?;
+            decode_into(element, messages)?;
+        }
+        return Ok(());
+    }
+    if !address.starts_with('/') {
+        return Err(format!("Not an OSC address: '{}'", address));
+    }
+
+    // Very old senders leave out the type tags; treat that as no arguments
+    let type_tags = if reader.position < bytes.len() {
+        reader.read_string()?
+    } else {
+        String::from(",")
+    };
+    let mut arguments = Vec::new();
+    for tag in type_tags.chars().skip(1) {
+        let argument = match tag {
+            'i' => OscArgument::Int(i32::from_be_bytes(reader.take_array()?)),
+            'f' => OscArgument::Float(f32::from_be_bytes(reader.take_array()?)),
+            's' | 'S' => OscArgument::String(reader.read_string()?),
+            'b' => {
+                let size = i32::from_be_bytes(reader.take_array()?).max(0) as usize;
+                let blob = reader.take(size)?.to_vec();
+                reader.take(size.next_multiple_of(4) - size)?;
+                OscArgument::Blob(blob)
+            }
+            'h' => OscArgument::Long(i64::from_be_bytes(reader.take_array()?)),
+            'd' => OscArgument::Double(f64::from_be_bytes(reader.take_array()?)),
+            'T' => OscArgument::Bool(true),
+            'F' => OscArgument::Bool(false),
+            'N' | 'I' => OscArgument::Nothing,
+            _ => return Err(format!("Unsupported OSC type tag '{}' in {}", tag, address)),
+        };
+        arguments.push(argument);
+    }
+    messages.push(OscMessage { address, arguments });
+    Ok(())
+}
+
+// ============================================================================
+// ROUTING
+// ============================================================================
+
+/// Turns OSC messages into engine commands
+pub struct OscRouter {
+    frequency_table: FrequencyTable,
+
+    /// A4 frequency for notes in triggered cells
+    reference_frequency_hz: f32,
+
+    /// Channels the engine has (higher channel numbers are rejected)
+    channel_count: usize,
+
+    /// Channel cells played so far, when logging (see log_events)
+    event_log: Option<EventLog>,
+}
+
+impl OscRouter {
+    pub fn new(channel_count: usize, reference_frequency_hz: f32) -> Self {
+        Self {
+            frequency_table: FrequencyTable::new(),
+            reference_frequency_hz,
+            channel_count,
+            event_log: None,
+        }
+    }
+
+    /// Starts keeping every channel cell played, timed from now
+    pub fn log_events(&mut self) {
+        self.event_log = Some(EventLog::new());
+    }
+
+    /// Takes the cells logged so far (None if log_events was never called)
+    pub fn take_event_log(&mut self) -> Option<EventLog> {
+        self.event_log.take()
+    }
+
+    /// Commands for one message (see the address list at the top)
+    /// Returns an error message for unknown addresses and bad arguments.
+    pub fn route(&mut self, message: &OscMessage) -> Result<Vec<EngineCommand>, String> {
+        let parts: Vec<&str> = message
+            .address
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .collect();
+        let first_number = message.arguments.first().and_then(OscArgument::as_number);
+        let dispatch = |channel_index, action| EngineCommand::Dispatch {
+            channel_index,
+            action,
+        };
+
+        match parts.as_slice() {
+            ["channel", number, command] => {
+                let channel_index = match number.parse::<usize>() {
+                    Ok(number) if (1..=self.channel_count).contains(&number) => number - 1,
+                    _ => {
+                        return Err(format!(
+                            "{}: channel must be 1 to {}",
+                            message.address, self.channel_count
+                        ));
+                    }
+                };
+                // Like /transport/pause, no argument means on
+                let switched_on = first_number.is_none_or(|value| value != 0.0);
+                let (action, cell) = match *command {
+                    "mute" => {
+                        return Ok(vec![EngineCommand::SetMuted {
+                            channel_index,
+                            muted: switched_on,
+                        }]);
+                    }
+                    "solo" => {
+                        return Ok(vec![EngineCommand::SetSoloed {
+                            channel_index,
+                            soloed: switched_on,
+                        }]);
+                    }
+                    "trigger" => {
+                        let cell = self.text_argument(message)?;
+                        (self.parse_cell(&cell)?, cell)
+                    }
+                    "release" => (CellAction::SlowRelease, String::new()),
+                    "cut" => (CellAction::FastRelease, ".".to_string()),
+                    _ => return Err(format!("Unknown OSC address {}", message.address)),
+                };
+                if let Some(event_log) = &mut self.event_log {
+                    event_log.record(channel_index, &cell);
+                }
+                Ok(vec![dispatch(channel_index, action)])
+            }
+            ["all", "release"] => {
+                // Only channels that have played show up in the log
+                if let Some(event_log) = &mut self.event_log {
+                    let played: BTreeSet<usize> = event_log
+                        .events
+                        .iter()
+                        .map(|event| event.channel_index)
+                        .collect();
+                    for channel_index in played {
+                        event_log.record(channel_index, "");
+                    }
+                }
+                Ok((0..self.channel_count)
+                    .map(|channel_index| dispatch(channel_index, CellAction::SlowRelease))
+                    .collect())
+            }
+            ["master"] => {
+                let cell = format!("master {}", self.text_argument(message)?);
+                Ok(vec![dispatch(0, self.parse_master_cell(&cell)?)])
+            }
+            ["master", effect] => {
+                let parameters: Vec<String> = message
+                    .arguments
+                    .iter()
+                    .map(|argument| {
+                        argument
+                            .as_number()
+                            .map(|value| value.to_string())
+                            .ok_or_else(|| {
+                                format!("{}: arguments must be numbers", message.address)
+                            })
+                    })
+                    .collect::<Result<_, _>>()?;
+                let cell = format!("master {}:{}", effect, parameters.join("'"));
+                Ok(vec![dispatch(0, self.parse_master_cell(&cell)?)])
+            }
+            ["transport", "jump"] => match first_number {
+                Some(row) if row >= 1.0 => Ok(vec![EngineCommand::JumpToRow(row as usize - 1)]),
+                _ => Err(format!("{}: needs a row number from 1", message.address)),
+            },
+            ["transport", "pause"] => Ok(vec![EngineCommand::SetPaused(
+                first_number.is_none_or(|value| value != 0.0),
+            )]),
+            ["transport", "play"] => Ok(vec![EngineCommand::SetPaused(false)]),
+            ["transport", "reset"] => Ok(vec![EngineCommand::Reset]),
+            _ => Err(format!("Unknown OSC address {}", message.address)),
+        }
+    }
+
+    /// All arguments joined with spaces, so "c4 sine" can also be sent as
+    /// two strings
+    fn text_argument(&self, message: &OscMessage) -> Result<String, String> {
+        let words: Vec<String> = message
+            .arguments
+            .iter()
+            .filter_map(|argument| match argument {
+                OscArgument::String(text) => Some(text.clone()),
+                OscArgument::Nothing | OscArgument::Blob(_) => None,
+                other => other.as_number().map(|value| value.to_string()),
+            })
+            .collect();
+        if words.is_empty() {
+            return Err(format!("{}: needs the cell text", message.address));
+        }
+        Ok(words.join(" "))
+    }
+
+    /// Parses a cell, failing on parser errors (warnings are logged)
+    fn parse_cell(&self, cell: &str) -> Result<CellAction, String> {
+        let (action, diagnostics) =
+            parse_cell_text(cell, &self.frequency_table, self.reference_frequency_hz);
+        if let Some(error) = diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.severity == Severity::Error)
+        {
+            return Err(format!("'{}': {}", cell, error.message));
+        }
+        for warning in diagnostics.iter() {
+            log::warn!(target: "osc", "'{}': {}", cell, warning.message);
+        }
+        Ok(action)
+    }
+
+    fn parse_master_cell(&self, cell: &str) -> Result<CellAction, String> {
+        match self.parse_cell(cell)? {
+            action @ CellAction::MasterEffects { .. } => Ok(action),
+            _ => Err(format!("'{}' is not a master effect", cell)),
+        }
+    }
+}
+
+// ============================================================================
+// SERVER
+// ============================================================================
+
+/// A running OSC server (stops when dropped)
+pub struct OscServer {
+    /// Address the server is listening on
+    pub local_address: SocketAddr,
+
+    running: Arc<AtomicBool>,
+
+    /// The network thread; hands the controller and router back when it ends
+    thread: Option<JoinHandle<(EngineController, OscRouter)>>,
+}
+
+impl OscServer {
+    /// Listens on bind_address (e.g. "0.0.0.0:9000") and sends every
+    /// command it receives to the engine
+    pub fn start(
+        bind_address: &str,
+        mut router: OscRouter,
+        mut controller: EngineController,
+    ) -> Result<Self, String> {
+        let socket = UdpSocket::bind(bind_address)
+            .map_err(|error| format!("Failed to listen on {}: {}", bind_address, error))?;
+        socket
+            .set_read_timeout(Some(STOP_CHECK_INTERVAL))
+            .map_err(|error| format!("Failed to set up the OSC socket: {}", error))?;
+        let local_address = socket
+            .local_addr()
+            .map_err(|error| format!("Failed to set up the OSC socket: {}", error))?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = Arc::clone(&running);
+        let thread = thread::Builder::new()
+            .name("osc".to_string())
+            .spawn(move || {
+                let mut buffer = vec![0u8; MAX_PACKET_BYTES];
+                while thread_running.load(Ordering::Relaxed) {
+                    // Timeouts just loop around to check the running flag
+                    let Ok((length, sender)) = socket.recv_from(&mut buffer) else {
+                        continue;
+                    };
+                    let messages = match decode_packet(&buffer[..length]) {
+                        Ok(messages) => messages,
+                        Err(message) => {
+                            log::warn!(target: "osc", "Bad packet from {}: {}", sender, message);
+                            continue;
+                        }
+                    };
+                    for message in messages {
+                        log::debug!(target: "osc", "{} {:?}", message.address, message.arguments);
+                        match router.route(&message) {
+                            Ok(commands) => {
+                                for command in commands {
+                                    if controller.send(command).is_err() {
+                                        log::warn!(target: "osc", "Command queue full, {} dropped", message.address);
+                                        break;
+                                    }
+                                }
+                            }
+                            Err(error) => log::warn!(target: "osc", "{}", error),
+                        }
+                    }
+                }
+                (controller, router)
+            })
+            .map_err(|error| format!("Failed to start the OSC thread: {}", error))?;
+
+        Ok(Self {
+            local_address,
+            running,
+            thread: Some(thread),
+        })
+    }
+
+    /// Stops listening and returns the engine controller, and the router
+    /// with its event log
+    pub fn stop(mut self) -> Option<(EngineController, OscRouter)> {
+        self.shut_down()
+    }
+
+    fn shut_down(&mut self) -> Option<(EngineController, OscRouter)> {
+        self.running.store(false, Ordering::Relaxed);
+        self.thread.take().and_then(|thread| thread.join().ok())
+    }
+}
+
+impl Drop for OscServer {
+    fn drop(&mut self) {
+        self.shut_down();
+    }
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{EngineConfig, PlaybackEngine};
+    use crate::parser::SongData;
+
+    fn message(address: &str, arguments: Vec<OscArgument>) -> OscMessage {
+        OscMessage {
+            address: address.to_string(),
+            arguments,
+        }
+    }
+
+    #[test]
+    fn test_decode_messages_and_bundles() {
+        let trigger = message(
+            "/channel/3/trigger",
+            vec![OscArgument::String("c4 sine a:0.8".to_string())],
+        );
+        let reverb = message(
+            "/master/reverb",
+            vec![
+                OscArgument::Float(0.5),
+                OscArgument::Int(1),
+                OscArgument::Blob(vec![1, 2, 3]),
+                OscArgument::Bool(true),
+            ],
+        );
+        assert_eq!(
+            decode_packet(&trigger.to_bytes()).unwrap(),
+            vec![trigger.clone()]
+        );
+
+        // A bundle holding both messages
+        let mut bundle = Vec::new();
+        write_padded_string(&mut bundle, b"#bundle");
+        bundle.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        for element in [&trigger, &reverb] {
+            let bytes = element.to_bytes();
+            bundle.extend_from_slice(&(bytes.len() as i32).to_be_bytes());
+            bundle.extend_from_slice(&bytes);
+        }
+        assert_eq!(decode_packet(&bundle).unwrap(), vec![trigger, reverb]);
+
+        assert!(decode_packet(b"hello\0\0\0").is_err());
+        assert!(decode_packet(&[b'/', b'a', 0, 0, b',', b'i', 0, 0, 1]).is_err());
+    }
+
+    #[test]
+    fn test_route_messages() {
+        let mut router = OscRouter::new(4, 440.0);
+        let mut route =
+            |address: &str, arguments: Vec<OscArgument>| router.route(&message(address, arguments));
+
+        match route(
+            "/channel/3/trigger",
+            vec![OscArgument::String("a4 sine a:0.8".to_string())],
+        )
+        .unwrap()
+        .as_slice()
+        {
+            [
+                EngineCommand::Dispatch {
+                    channel_index: 2,
+                    action:
+                        CellAction::TriggerNote {
+                            frequency_hz,
+                            effects,
+                            ..
+                        },
+                },
+            ] => {
+                assert!((frequency_hz - 440.0).abs() < 0.01);
+                assert_eq!(effects.amplitude, 0.8);
+            }
+            _ => panic!("expected a note on channel 3"),
+        }
+
+        match route(
+            "/master/reverb",
+            vec![OscArgument::Float(0.5), OscArgument::Float(0.3)],
+        )
+        .unwrap()
+        .as_slice()
+        {
+            [
+                EngineCommand::Dispatch {
+                    action: CellAction::MasterEffects { effects, .. },
+                    ..
+                },
+            ] => assert_eq!(effects[0].1, vec![0.5, 0.3]),
+            _ => panic!("expected a master effect"),
+        }
+
+        assert_eq!(route("/all/release", Vec::new()).unwrap().len(), 4);
+        assert!(matches!(
+            route("/transport/jump", vec![OscArgument::Int(17)]).unwrap()[..],
+            [EngineCommand::JumpToRow(16)]
+        ));
+        assert!(matches!(
+            route("/transport/pause", vec![OscArgument::Int(0)]).unwrap()[..],
+            [EngineCommand::SetPaused(false)]
+        ));
+        assert!(matches!(
+            route("/channel/2/mute", Vec::new()).unwrap()[..],
+            [EngineCommand::SetMuted {
+                channel_index: 1,
+                muted: true
+            }]
+        ));
+        assert!(matches!(
+            route("/channel/2/solo", vec![OscArgument::Int(0)]).unwrap()[..],
+            [EngineCommand::SetSoloed {
+                channel_index: 1,
+                soloed: false
+            }]
+        ));
+
+        assert!(route("/channel/5/release", Vec::new()).is_err());
+        assert!(route("/channel/1/trigger", Vec::new()).is_err());
+        assert!(route("/master/c4", vec![OscArgument::Float(1.0)]).is_err());
+        assert!(route("/nothing/here", Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_route_logs_cells() {
+        let mut router = OscRouter::new(4, 440.0);
+        let trigger = message(
+            "/channel/2/trigger",
+            vec![OscArgument::String("c4 sine".to_string())],
+        );
+        router.route(&trigger).unwrap();
+        assert!(router.take_event_log().is_none());
+
+        router.log_events();
+        router.route(&trigger).unwrap();
+        router
+            .route(&message("/channel/3/cut", Vec::new()))
+            .unwrap();
+        router.route(&message("/all/release", Vec::new())).unwrap();
+        router
+            .route(&message("/master/reverb", vec![OscArgument::Float(0.5)]))
+            .unwrap();
+        let cells: Vec<(usize, String)> = router
+            .take_event_log()
+            .unwrap()
+            .events
+            .into_iter()
+            .map(|event| (event.channel_index, event.cell))
+            .collect();
+        assert_eq!(
+            cells,
+            vec![
+                (1, "c4 sine".to_string()),
+                (2, ".".to_string()),
+                (1, String::new()),
+                (2, String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_server_feeds_the_engine() {
+        let song = SongData::from_json(r#"{"rows": []}"#).unwrap();
+        let config = EngineConfig {
+            channel_count: 2,
+            ..Default::default()
+        };
+        let mut engine = PlaybackEngine::new(song, config);
+        engine.set_stop_at_end(false);
+        let (mut realtime_engine, controller) = engine.into_realtime();
+
+        let server = OscServer::start("127.0.0.1:0", OscRouter::new(2, 440.0), controller).unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let note = message(
+            "/channel/1/trigger",
+            vec![OscArgument::String("c4 square".to_string())],
+        );
+        client
+            .send_to(&note.to_bytes(), server.local_address)
+            .unwrap();
+
+        // The note arrives through the network thread and the command queue
+        let mut buffer = vec![0.0; 256];
+        let mut heard_note = false;
+        for _ in 0..200 {
+            realtime_engine.process(&mut buffer);
+            if buffer.iter().any(|sample| *sample != 0.0) {
+                heard_note = true;
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(heard_note);
+        assert!(server.stop().is_some());
+    }
+}
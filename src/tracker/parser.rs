@@ -16,8 +16,68 @@
 // - "-"        Sustain = keep playing
 // - "."        Fast release = quick fade to avoid pops
 // - "c4 sine"  Note trigger = play C4 with sine wave
+// - "c4+25c"   Note trigger detuned by 25 cents
 // - "a:0.5"    Effect change = set amplitude to 50%
+// - "sr:8000"  Decimator = hold samples as if recorded at 8 kHz
+//              ("sr:100 tr:2" glides down, like a tape stopping)
+// - "c2 saw sub:0.5"  Mix in a square an octave below at half level
+//                     ("sub:0.5'sine" for a sine)
+// - "wah:2'0.6'5"  Wah = band-pass swept at 2 Hz, depth 0.6, resonance 5
+//                  ("wah:0'0.8" follows the note's level: an auto-wah)
+// - "vowel:a>o tr:1"  Formant filter sliding from "a" to "o" over 1 second
+// - "c4 sq t:1/16'0.5"  Tremolo once every 16th note, following the tempo
+//                       (also v:, ch: rate, wah: rate, and master dl: time;
+//                       "1/8." is dotted, "1/8t" a triplet)
+// - "c4 sq t:4'0.5'sync"  Tremolo that restarts its cycle on each note
+//                         ("'row" restarts it every row, "'free" never)
+// - "c4 organ:1'0.8'0'0.5 click:0.3"  Additive organ (harmonic levels),
+//                                     each note starting with a key-click
+// - "c4 grain:0.08'20'0.3'0.1"  Grains of the [sample] file: size,
+//                               density, position, spray
+// - "c4 sq a:0.6 vel:d'0.5"  The note's velocity (its a: value) also
+//                            adds up to 0.5 distortion (see VELOCITY MAPPING)
 // - "master rv:0.5'0.3"  Master effect = reverb on master bus
+// - "master a4:432"       Retune A4 for every note after this cell
+// - "master transpose:3"  Shift every note after this cell up 3 semitones
+// - "master fadeout:8"    Fade everything out over 8 seconds; the song ends there
+// - "group drums a:0.5"   Group effect = change the "drums" submix bus
+//
+// DIRECTIVES:
+// A line starting with "[name]" is a directive instead of a row of cells.
+// - "[tuning] 19edo"          Switch tuning for all following rows
+// - "[tuning] scl:just.scl"   Use a Scala scale file
+// - "[scale] a minor"         Snap following notes into A minor
+// - "[scale] a minor warn"    Warn about notes outside A minor instead
+// - "[instruments] kit.toml"  Load named instruments from a TOML file
+// - "[instruments]"           Start a block of named presets, one per line:
+//                             lead = square:0.25 d:0.2 v:5'0.3
+//                             Cells can then use "c4 lead" or "c4 lead a:0.5"
+//                             A line naming an instrument sets the effects
+//                             its notes start with: square = dt:6 d:0.05
+// - "[sample] choir.wav"      Load the sound following grain notes play
+// - "[macro hat] noise a:0.3 b:8"
+//                             Cells can say "@hat" instead of the whole text
+// - "[macro hat(0.3)] noise a:$1 b:8"
+//                             "@hat(0.5)" fills in $1; "@hat" uses 0.3
+// - "[groups]"                Start a block of channel groups, one per line:
+//                             drums = 4,5,6 a:0.8 rv:0.3'0.2
+//                             (channel numbers from 1, then bus effects)
+// - "[config]"                Start a block of song defaults, one per line:
+//                             missing = sustain   (rows with too few cells)
+//                             attack = 0.05       (notes without env:)
+//                             release = 1.0       (fade on empty cells)
+// - "[script seed:42]"        Start a script that writes rows, up to an
+//                             "[end]" line (see script.rs)
+// - "[gen ch4] euclid(5,16,"noise a:0.4")"
+//                             Put 5 hits spread over 16 rows into channel
+//                             4's empty cells in the rows that follow
+// - "[song intro]"            Start a named song; one file can hold several
+// - "[chain] intro main"      Default play order for those songs
+//
+// STREAMING:
+// parse_song reads the whole text at once. SongStream reads a song a row
+// at a time from any reader, for songs too long to keep in memory (see
+// song_stream.rs). Both hand each line to the same SongReader.
 //
 // ERROR HANDLING:
 // The parser reports errors with line and column numbers, then continues
@@ -25,31 +85,28 @@
 // them one at a time. Invalid cells are treated as slow release.
 // ============================================================================
 
-use crate::effects::ChannelEffectState;
-use crate::helper::{FrequencyTable, parse_pitch_to_frequency};
-use crate::instruments::{find_instrument_by_name, get_instrument_by_id};
-use std::collections::HashSet;
-
-// ============================================================================
-// DEBUG LEVELS
-// ============================================================================
-
-/// Debug output level for the parser
-/// Configure this in main.rs to control how much output you see
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
-pub enum DebugLevel {
-    /// No debug output
-    Off = 0,
-
-    /// Basic info: parsing start/end, row counts, errors
-    Basic = 1,
-
-    /// Verbose: + channel activity, effect changes
-    Verbose = 2,
-
-    /// Detailed: + individual token parsing, all internal state
-    Detailed = 3,
-}
+use crate::effects::{
+    ChannelEffectState, LfoRestart, SubOscillatorShape, is_custom_effect, is_effect_name,
+    parse_chain_order,
+};
+use crate::envelope::EnvelopeShape;
+use crate::grain::load_sample;
+use crate::helper::{FrequencyTable, PitchName, cents_to_frequency_ratio, parse_pitch_name};
+use crate::instrument_file::load_instrument_file;
+use crate::instruments::{
+    GRAIN_INSTRUMENT_ID, find_instrument_by_name, get_instrument_by_id, instrument_requires_pitch,
+};
+use crate::oversampling::nearest_oversampling_factor;
+use crate::scale::{ScaleConstraint, ScaleMode};
+use crate::script::{euclid_pattern, random_script_seed, run_song_script};
+use crate::tuning::{
+    MAX_REFERENCE_FREQUENCY_HZ, MIN_REFERENCE_FREQUENCY_HZ, Tuning, parse_reference_frequency,
+};
+use crate::wah::{DEFAULT_WAH_Q, parse_vowels};
+use log::{debug, trace};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{BufRead, Lines};
 
 // ============================================================================
 // SONG CONFIGURATION
@@ -66,8 +123,44 @@ pub enum DebugLevel {
 //
 // ============================================================================
 
+/// Rows per beat assumed by tempo_bpm and the bpm command (16th notes)
+pub const DEFAULT_ROWS_PER_BEAT: f32 = 4.0;
+
+/// Seconds per row for a tempo: 60 / (bpm * rows_per_beat)
+pub fn tick_duration_from_bpm(bpm: f32, rows_per_beat: f32) -> f32 {
+    60.0 / (bpm * rows_per_beat)
+}
+
+/// Whether the bpm command accepts a tempo: 1-1000 BPM, 1-64 rows per beat
+pub fn is_valid_tempo(bpm: f32, rows_per_beat: f32) -> bool {
+    (1.0..=1000.0).contains(&bpm) && (1.0..=64.0).contains(&rows_per_beat)
+}
+
+/// Length of a fadeout command's fade (at most 10 minutes), or None if
+/// the length isn't positive
+fn fade_out_length(seconds: f32) -> Option<f32> {
+    (seconds > 0.0).then(|| seconds.min(600.0))
+}
+
+/// Length in beats of a note length like "1/8" (a quarter note is a beat)
+/// A "." after it makes it dotted (half as long again) and a "t" makes it
+/// a triplet (two thirds as long): "1/8." is 0.75 beats, "1/8t" is 1/3.
+pub fn parse_note_length(text: &str) -> Option<f32> {
+    let (fraction, scale) = if let Some(rest) = text.strip_suffix('.') {
+        (rest, 1.5)
+    } else if let Some(rest) = text.strip_suffix('t') {
+        (rest, 2.0 / 3.0)
+    } else {
+        (text, 1.0)
+    };
+    let (numerator, denominator) = fraction.split_once('/')?;
+    let numerator: f32 = numerator.trim().parse().ok()?;
+    let denominator: f32 = denominator.trim().parse().ok()?;
+    (numerator > 0.0 && denominator > 0.0).then(|| 4.0 * numerator / denominator * scale)
+}
+
 /// Per-song configuration options that can be set in the CSV file
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct SongConfig {
     /// Override tick duration (seconds per row)
     pub tick_duration: Option<f32>,
@@ -78,14 +171,20 @@ pub struct SongConfig {
     /// Whether to normalize the WAV output
     pub normalize_wav: Option<bool>,
 
-    /// Debug level override
-    pub debug_level: Option<DebugLevel>,
+    /// Whether to dither the WAV output when rounding to 16 bits
+    pub dither_wav: Option<bool>,
 
     /// Song title (for display/metadata)
     pub title: Option<String>,
 
     /// Song tempo in BPM (informational, calculated from tick_duration)
     pub tempo_bpm: Option<f32>,
+
+    /// Frequency of A4 in Hz (e.g., 432 or 415 instead of 440)
+    pub reference_frequency_hz: Option<f32>,
+
+    /// Seed for the channels' random numbers (noise); None plays seed 0
+    pub seed: Option<u32>,
 }
 
 impl SongConfig {
@@ -119,14 +218,8 @@ impl SongConfig {
                         config.normalize_wav =
                             Some(value == "true" || value == "1" || value == "yes");
                     }
-                    "debug_level" | "debug" => {
-                        config.debug_level = match value.to_lowercase().as_str() {
-                            "off" | "0" | "none" => Some(DebugLevel::Off),
-                            "basic" | "1" => Some(DebugLevel::Basic),
-                            "verbose" | "2" => Some(DebugLevel::Verbose),
-                            "detailed" | "3" | "all" => Some(DebugLevel::Detailed),
-                            _ => None,
-                        };
+                    "dither_wav" | "dither" => {
+                        config.dither_wav = Some(value == "true" || value == "1" || value == "yes");
                     }
                     "title" | "name" | "song" => {
                         config.title = Some(value.to_string());
@@ -137,10 +230,17 @@ impl SongConfig {
                             // Also calculate tick_duration from BPM if not already set
                             // Assuming 4 rows per beat: tick = 60 / (bpm * 4)
                             if config.tick_duration.is_none() {
-                                config.tick_duration = Some(60.0 / (v * 4.0));
+                                config.tick_duration =
+                                    Some(tick_duration_from_bpm(v, DEFAULT_ROWS_PER_BEAT));
                             }
                         }
                     }
+                    "a4" | "reference_pitch" | "pitch_reference" => {
+                        config.reference_frequency_hz = parse_reference_frequency(value);
+                    }
+                    "seed" | "random_seed" => {
+                        config.seed = value.parse::<u32>().ok();
+                    }
                     _ => {
                         // Unknown setting - ignore
                     }
@@ -156,20 +256,48 @@ impl SongConfig {
         self.tick_duration.is_some()
             || self.export_wav.is_some()
             || self.normalize_wav.is_some()
-            || self.debug_level.is_some()
+            || self.dither_wav.is_some()
             || self.title.is_some()
             || self.tempo_bpm.is_some()
+            || self.reference_frequency_hz.is_some()
+            || self.seed.is_some()
     }
 }
 
 // ============================================================================
-// PARSE ERROR
+// PARSE DIAGNOSTICS
 // ============================================================================
 
-/// Represents a parsing error with location information
-#[derive(Clone, Debug)]
+/// How serious a parser message is
+///
+/// The parser never stops on either: errors are cells or lines it could
+/// not understand (and skipped or replaced), warnings are things it
+/// adjusted. "tracker check" exits nonzero when there are errors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Played, but not exactly as written (e.g., extra cells ignored)
+    Warning,
+
+    /// Not understood, so ignored or replaced (e.g., an invalid pitch)
+    Error,
+}
+
+impl Severity {
+    /// Lowercase label used in messages ("warning" / "error")
+    pub fn label(self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// One parser message with its location in the song file
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ParseError {
     /// Line number in the original file (1-indexed for human readability)
+    /// 0 means the message is about the file as a whole
     pub line_number: usize,
 
     /// Column number (channel index, 0-indexed)
@@ -181,30 +309,128 @@ pub struct ParseError {
     /// Human-readable error message
     pub message: String,
 
-    /// Whether parsing can continue (warning) or must stop (fatal)
-    pub is_fatal: bool,
+    /// Warning or error
+    pub severity: Severity,
 }
 
 impl ParseError {
-    /// Creates a new non-fatal error (warning)
+    /// Creates a warning: the song plays, but not exactly as written
     pub fn warning(line: usize, column: usize, cell: &str, message: String) -> Self {
         Self {
             line_number: line,
             column_number: column,
             cell_content: cell.to_string(),
             message,
-            is_fatal: false,
+            severity: Severity::Warning,
+        }
+    }
+
+    /// Creates an error: something could not be understood and was skipped
+    pub fn error(line: usize, column: usize, cell: &str, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            ..Self::warning(line, column, cell, message)
         }
     }
 
     /// Formats the error for display
     pub fn format(&self) -> String {
-        let error_type = if self.is_fatal { "ERROR" } else { "WARNING" };
+        let error_type = self.severity.label().to_uppercase();
         format!(
             "[{}] Line {}, Channel {}: {} (cell: '{}')",
             error_type, self.line_number, self.column_number, self.message, self.cell_content
         )
     }
+
+    /// Formats the error as "file:line: severity: message" for scripts
+    /// and editors that jump to "file:line" locations
+    pub fn format_for_file(&self, path: &str) -> String {
+        let location = if self.line_number == 0 {
+            path.to_string()
+        } else {
+            format!("{}:{}", path, self.line_number)
+        };
+        let mut text = format!("{}: {}: {}", location, self.severity.label(), self.message);
+        if self.line_number != 0 {
+            text.push_str(&format!(" (channel {}", self.column_number));
+            if !self.cell_content.is_empty() {
+                text.push_str(&format!(", cell '{}'", self.cell_content));
+            }
+            text.push(')');
+        }
+        text
+    }
+}
+
+/// All messages from parsing one song, in the order they were found
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ParseDiagnostics {
+    entries: Vec<ParseError>,
+}
+
+impl ParseDiagnostics {
+    /// Adds a message
+    pub fn push(&mut self, diagnostic: ParseError) {
+        self.entries.push(diagnostic);
+    }
+
+    /// Adds a message unless the same one (same line, channel, and text)
+    /// is already there
+    pub fn push_unique(&mut self, diagnostic: ParseError) {
+        let already_reported = self.entries.iter().any(|existing| {
+            existing.line_number == diagnostic.line_number
+                && existing.column_number == diagnostic.column_number
+                && existing.message == diagnostic.message
+        });
+        if !already_reported {
+            self.entries.push(diagnostic);
+        }
+    }
+
+    /// Iterates over all messages
+    pub fn iter(&self) -> std::slice::Iter<'_, ParseError> {
+        self.entries.iter()
+    }
+
+    /// Total number of messages
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// True if parsing found nothing to report
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of messages with the given severity
+    pub fn count(&self, severity: Severity) -> usize {
+        self.entries
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    }
+
+    /// True if any message is an error (not just a warning)
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+
+    /// Prints all messages to stdout
+    pub fn print(&self) {
+        for diagnostic in &self.entries {
+            println!("{}", diagnostic.format());
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a ParseDiagnostics {
+    type Item = &'a ParseError;
+    type IntoIter = std::slice::Iter<'a, ParseError>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
 }
 
 // ============================================================================
@@ -216,7 +442,7 @@ impl ParseError {
 // ============================================================================
 
 /// What action to take for a cell in the song
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum CellAction {
     /// Trigger a pitched note (e.g., "c4 sine")
     TriggerNote {
@@ -237,6 +463,12 @@ pub enum CellAction {
 
         /// Whether to clear effects to default first
         clear_effects: bool,
+
+        /// Envelope times for this note (None = default envelope)
+        envelope: Option<EnvelopeShape>,
+
+        /// Restart the channel's random numbers from this seed ("seed:7")
+        seed: Option<u32>,
     },
 
     /// Trigger a pitchless instrument (e.g., "noise a:0.5")
@@ -255,6 +487,13 @@ pub enum CellAction {
 
         /// Whether to clear effects first
         clear_effects: bool,
+
+        /// Envelope times for this hit (None = default envelope)
+        envelope: Option<EnvelopeShape>,
+
+        /// Restart the channel's random numbers from this seed ("seed:7"),
+        /// so every hit of a noise sound is the same
+        seed: Option<u32>,
     },
 
     /// Keep playing the current sound
@@ -301,6 +540,47 @@ pub enum CellAction {
         /// List of effects to apply: (effect_name, parameters)
         effects: Vec<(String, Vec<f32>)>,
     },
+
+    /// Group bus effect command (e.g., "group drums a:0.5")
+    GroupEffects {
+        /// Name of the group from the [groups] block
+        group: String,
+
+        /// Whether to clear the group's effects first
+        clear_first: bool,
+
+        /// Transition time
+        transition_seconds: f32,
+
+        /// List of effects to apply: (effect_name, parameters)
+        effects: Vec<(String, Vec<f32>)>,
+    },
+}
+
+impl CellAction {
+    /// Returns true for master and group commands, which act on a bus
+    /// instead of the channel whose column they are written in
+    pub fn is_bus_command(&self) -> bool {
+        matches!(
+            self,
+            CellAction::MasterEffects { .. } | CellAction::GroupEffects { .. }
+        )
+    }
+}
+
+/// A named set of channels mixed on their own bus before the master
+/// (defined in a [groups] block)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChannelGroup {
+    /// Name used by "group name ..." cells
+    pub name: String,
+
+    /// Channels in the group (0-indexed)
+    pub channels: Vec<usize>,
+
+    /// Bus effects the group starts with, like a master cell's
+    #[serde(default)]
+    pub effects: Vec<(String, Vec<f32>)>,
 }
 
 // ============================================================================
@@ -308,40 +588,242 @@ pub enum CellAction {
 // ============================================================================
 
 /// Parsed song data ready for playback
-#[derive(Clone)]
+///
+/// Saves to and loads from JSON (see to_json), so other programs can
+/// generate songs without writing CSV. Only `rows` is required in JSON.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SongData {
     /// Grid of cell actions: rows[row_index][channel_index]
     pub rows: Vec<Vec<CellAction>>,
 
-    /// Original line content for each row (for debug display)
+    /// Original line content for each row (for display), with the cells in
+    /// channel order if a [channels] line rearranged the columns
+    #[serde(default)]
     pub raw_lines: Vec<String>,
 
-    /// Any errors encountered during parsing
-    pub errors: Vec<ParseError>,
+    /// Name of each channel: its header cell, or its name in [channels]
+    #[serde(default)]
+    pub channel_names: Vec<String>,
+
+    /// Line number in the file (1-based) of each row, for editors
+    #[serde(skip)]
+    pub row_line_numbers: Vec<usize>,
+
+    /// Warnings and errors found during parsing
+    #[serde(default)]
+    pub diagnostics: ParseDiagnostics,
 
     /// Per-song configuration (from config row, if present)
+    #[serde(default)]
     pub config: SongConfig,
+
+    /// Channel groups mixed on their own buses (from [groups] blocks)
+    #[serde(default)]
+    pub groups: Vec<ChannelGroup>,
+
+    /// Random seed each [script] block ran with, in file order (written
+    /// into "[script seed:N]" it writes the same rows again)
+    #[serde(default)]
+    pub script_seeds: Vec<u32>,
 }
 
 impl SongData {
-    /// Returns true if there were any fatal errors
-    pub fn has_fatal_errors(&self) -> bool {
-        self.errors.iter().any(|e| e.is_fatal)
-    }
-
     /// Returns the total duration in rows
     pub fn row_count(&self) -> usize {
         self.rows.len()
     }
 
-    /// Prints all errors to stdout
-    pub fn print_errors(&self) {
-        for error in &self.errors {
-            println!("{}", error.format());
+    /// Playing time of the rows in seconds, following any bpm commands
+    /// (tick_duration_seconds is the speed until the first one)
+    /// A fadeout command ends the song when its fade is over.
+    pub fn duration_seconds(&self, tick_duration_seconds: f32) -> f32 {
+        let durations = self.row_durations(tick_duration_seconds);
+        let total: f32 = durations.iter().sum();
+        let mut elapsed = 0.0;
+        for (row, duration) in self.rows.iter().zip(&durations) {
+            if let Some(fade_seconds) = fade_out_seconds(row) {
+                return (elapsed + fade_seconds).min(total);
+            }
+            elapsed += duration;
+        }
+        total
+    }
+
+    /// How long each row plays in seconds, following any bpm commands
+    pub fn row_durations(&self, tick_duration_seconds: f32) -> Vec<f32> {
+        let mut tick = tick_duration_seconds;
+        self.rows
+            .iter()
+            .map(|row| {
+                for action in row {
+                    if let CellAction::MasterEffects { effects, .. } = action {
+                        for (name, params) in effects {
+                            if name == "bpm" && params.len() == 2 {
+                                tick = tick_duration_from_bpm(params[0], params[1]);
+                            }
+                        }
+                    }
+                }
+                tick
+            })
+            .collect()
+    }
+
+    /// Number of channels the widest row uses
+    pub fn channel_count(&self) -> usize {
+        self.rows.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    /// "3" for an unnamed channel, "3 Kick" for a named one (counted from 1)
+    pub fn channel_label(&self, channel_index: usize) -> String {
+        match self.channel_names.get(channel_index) {
+            Some(name) if !name.is_empty() => format!("{} {}", channel_index + 1, name),
+            _ => (channel_index + 1).to_string(),
+        }
+    }
+
+    /// Writes the song as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("song data always serializes")
+    }
+
+    /// Reads a song written by to_json (or generated by another program)
+    /// Errors name the line and column of the problem in the JSON text.
+    /// Values are held to the ranges the CSV parser allows; ones it would
+    /// reject (a tempo of 0, a number too large for the engine) are errors.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let mut song: SongData =
+            serde_json::from_str(text).map_err(|e| format!("Invalid song JSON: {}", e))?;
+        let value: serde_json::Value =
+            serde_json::from_str(text).map_err(|e| format!("Invalid song JSON: {}", e))?;
+        if let Some(number) = first_unplayable_number(&value) {
+            return Err(format!("Invalid song JSON: {} is too large", number));
+        }
+        song.clamp_to_ranges()
+            .map_err(|message| format!("Invalid song JSON: {}", message))?;
+        Ok(song)
+    }
+
+    /// Pulls every effect, envelope and transition into the range the CSV
+    /// parser gives it, and checks the settings it can't pull in
+    fn clamp_to_ranges(&mut self) -> Result<(), String> {
+        if let Some(tick_duration) = self.config.tick_duration
+            && tick_duration <= 0.0
+        {
+            return Err(format!(
+                "tick_duration {} must be more than 0 seconds",
+                tick_duration
+            ));
+        }
+        if let Some(reference) = self.config.reference_frequency_hz
+            && !(MIN_REFERENCE_FREQUENCY_HZ..=MAX_REFERENCE_FREQUENCY_HZ).contains(&reference)
+        {
+            return Err(format!(
+                "reference_frequency_hz {} must be from 100 to 1000 Hz",
+                reference
+            ));
+        }
+
+        for (row_index, row) in self.rows.iter_mut().enumerate() {
+            for action in row {
+                match action {
+                    CellAction::TriggerNote {
+                        effects,
+                        transition_seconds,
+                        envelope,
+                        ..
+                    }
+                    | CellAction::TriggerPitchless {
+                        effects,
+                        transition_seconds,
+                        envelope,
+                        ..
+                    } => {
+                        effects.clamp_to_ranges();
+                        *transition_seconds = transition_seconds.max(0.0);
+                        *envelope = envelope.map(EnvelopeShape::clamped);
+                    }
+                    CellAction::SustainWithEffects {
+                        effects,
+                        transition_seconds,
+                        ..
+                    }
+                    | CellAction::ChangeEffects {
+                        effects,
+                        transition_seconds,
+                        ..
+                    } => {
+                        effects.clamp_to_ranges();
+                        *transition_seconds = transition_seconds.max(0.0);
+                    }
+                    CellAction::MasterEffects {
+                        transition_seconds,
+                        effects,
+                        ..
+                    } => {
+                        *transition_seconds = transition_seconds.max(0.0);
+                        for (name, params) in effects {
+                            match name.as_str() {
+                                "bpm"
+                                    if params.len() != 2
+                                        || !is_valid_tempo(params[0], params[1]) =>
+                                {
+                                    return Err(format!(
+                                        "row {}: bpm {:?} needs 1-1000 BPM and 1-64 rows per beat",
+                                        row_index + 1,
+                                        params
+                                    ));
+                                }
+                                "fadeout" => {
+                                    let Some(seconds) = params
+                                        .first()
+                                        .and_then(|&seconds| fade_out_length(seconds))
+                                    else {
+                                        return Err(format!(
+                                            "row {}: fadeout needs a length of more than 0 seconds",
+                                            row_index + 1
+                                        ));
+                                    };
+                                    params[0] = seconds;
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    CellAction::GroupEffects {
+                        transition_seconds, ..
+                    } => *transition_seconds = transition_seconds.max(0.0),
+                    _ => {}
+                }
+            }
         }
+        Ok(())
     }
 }
 
+/// The first number in a JSON value that an f32 can't hold, if any
+fn first_unplayable_number(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::Number(number) => number
+            .as_f64()
+            .filter(|&number| !(number as f32).is_finite()),
+        serde_json::Value::Array(items) => items.iter().find_map(first_unplayable_number),
+        serde_json::Value::Object(fields) => fields.values().find_map(first_unplayable_number),
+        _ => None,
+    }
+}
+
+/// Length of the fade if a row has a fadeout command
+fn fade_out_seconds(row: &[CellAction]) -> Option<f32> {
+    row.iter().find_map(|action| match action {
+        CellAction::MasterEffects { effects, .. } => effects
+            .iter()
+            .find(|(name, params)| name == "fadeout" && !params.is_empty())
+            .map(|(_, params)| params[0]),
+        _ => None,
+    })
+}
+
 // ============================================================================
 // PARSER CONTEXT
 // ============================================================================
@@ -357,11 +839,163 @@ struct ParserContext<'a> {
     /// Current column/channel (for error messages)
     current_column: usize,
 
-    /// Collected errors
-    errors: Vec<ParseError>,
+    /// Collected warnings and errors
+    diagnostics: ParseDiagnostics,
 
     /// Behavior for missing cells at end of row
     missing_cell_behavior: MissingCellBehavior,
+
+    /// Tuning used to turn note names into frequencies (set by [tuning])
+    tuning: Tuning,
+
+    /// Semitones added to every note trigger (set by "master transpose:N")
+    transpose_semitones: f32,
+
+    /// Scale that note triggers are checked against (set by [scale])
+    scale: Option<ScaleConstraint>,
+
+    /// Named presets from [instruments] blocks: name -> tokens it expands to
+    instrument_presets: HashMap<String, Vec<String>>,
+
+    /// True while reading "name = tokens" lines after an [instruments] line
+    instruments_block_open: bool,
+
+    /// Default effects set in [instruments] blocks: instrument ID -> effect
+    /// tokens (replacing the instrument's own default_effects)
+    instrument_default_effects: HashMap<usize, Vec<String>>,
+
+    /// Channel groups defined so far
+    groups: Vec<ChannelGroup>,
+
+    /// True while reading "name = channels" lines after a [groups] line
+    groups_block_open: bool,
+
+    /// True while reading "setting = value" lines after a [config] line
+    config_block_open: bool,
+
+    /// Attack time for notes without env: (set by [config]; None: default)
+    default_attack_seconds: Option<f32>,
+
+    /// Release time for notes without one (set by [config]; None: default)
+    default_release_seconds: Option<f32>,
+
+    /// Channels in the song (group definitions are checked against it)
+    channel_count: usize,
+
+    /// Header names from a [channels] line, in channel order (None: the
+    /// columns play on channels in file order)
+    channel_columns: Option<Vec<String>>,
+
+    /// True once the header row has been read
+    header_read: bool,
+
+    /// Macros from [macro name] lines, used in cells as "@name"
+    macros: HashMap<String, MacroDefinition>,
+
+    /// Sample bank number of the last [sample] file (what grain notes play)
+    grain_sample: Option<usize>,
+
+    /// Sample bank numbers of presets that bring their own sample (from an
+    /// instrument file's "sample" key): preset name -> sample number
+    preset_samples: HashMap<String, usize>,
+
+    /// Sample of the preset used by the cell being parsed, which grain
+    /// notes play instead of grain_sample
+    cell_sample: Option<usize>,
+
+    /// The [script] block being read, until its [end] line
+    script: Option<ScriptBlock>,
+
+    /// Lines written by the last script, waiting to be read as if they
+    /// were in the file: (line number of the [script] line, text)
+    script_lines: VecDeque<(usize, String)>,
+
+    /// Seed each script ran with, in file order
+    script_seeds: Vec<u32>,
+
+    /// [gen] generators still writing into the rows that follow
+    generators: Vec<CellGenerator>,
+}
+
+/// A [gen] generator: cells written into one channel, a row at a time
+#[derive(Clone, Debug)]
+struct CellGenerator {
+    /// Channel the cells go to (0-indexed)
+    channel: usize,
+
+    /// One cell per row ("" where the pattern has no hit)
+    cells: Vec<String>,
+
+    /// Index of the cell for the next row
+    next_step: usize,
+}
+
+/// Most rows one [gen] generator may cover (steps times repeats)
+const MAX_GENERATOR_STEPS: usize = 100_000;
+
+/// A [script] block while its lines are read
+#[derive(Clone, Debug)]
+struct ScriptBlock {
+    /// Line number of the "[script]" line
+    start_line: usize,
+
+    /// Seed from "[script seed:N]" (None: a new one each run)
+    seed: Option<u32>,
+
+    /// The script so far
+    source: String,
+}
+
+/// A text macro defined with "[macro name(defaults)] body"
+#[derive(Clone, Debug)]
+struct MacroDefinition {
+    /// Text that replaces "@name" ($1 to $9 are the arguments)
+    body: String,
+
+    /// Values used for $1 to $9 when a cell doesn't pass them
+    default_arguments: Vec<String>,
+}
+
+/// How many times macros may expand inside each other before giving up
+/// (stops a macro that uses itself from looping forever)
+const MAX_MACRO_DEPTH: usize = 8;
+
+impl<'a> ParserContext<'a> {
+    /// Creates a fresh context in standard tuning
+    fn new(
+        frequency_table: &'a FrequencyTable,
+        missing_cell_behavior: MissingCellBehavior,
+    ) -> Self {
+        Self {
+            frequency_table,
+            current_line: 0,
+            current_column: 0,
+            diagnostics: ParseDiagnostics::default(),
+            missing_cell_behavior,
+            tuning: Tuning::default(),
+            transpose_semitones: 0.0,
+            scale: None,
+            instrument_presets: HashMap::new(),
+            instruments_block_open: false,
+            instrument_default_effects: HashMap::new(),
+            groups: Vec::new(),
+            groups_block_open: false,
+            config_block_open: false,
+            default_attack_seconds: None,
+            default_release_seconds: None,
+            channel_count: 0,
+            channel_columns: None,
+            header_read: false,
+            macros: HashMap::new(),
+            grain_sample: None,
+            preset_samples: HashMap::new(),
+            cell_sample: None,
+            script: None,
+            script_lines: VecDeque::new(),
+            script_seeds: Vec::new(),
+            generators: Vec::new(),
+        }
+    }
 }
 
 /// What to do when a row has fewer cells than channels
@@ -369,768 +1003,4433 @@ struct ParserContext<'a> {
 pub enum MissingCellBehavior {
     /// Treat missing cells as slow release (fade out)
     SlowRelease,
+
+    /// Treat missing cells as sustain (keep playing)
+    Sustain,
+}
+
+impl MissingCellBehavior {
+    /// Reads the value of "missing = ..." in a [config] block
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "release" | "slow_release" | "fade" => Some(MissingCellBehavior::SlowRelease),
+            "sustain" | "hold" => Some(MissingCellBehavior::Sustain),
+            _ => None,
+        }
+    }
 }
 
 // ============================================================================
 // MAIN PARSER FUNCTION
 // ============================================================================
 
+/// Pass as channel_count to give the song one channel per column
+///
+/// The count is the width of the header row, or of the widest data row if
+/// that is wider (empty cells at the end of a row don't count). Shorter
+/// rows are filled in as missing cells.
+pub const AUTO_CHANNEL_COUNT: usize = 0;
+
+/// Most channels a song can have (cells past this column are ignored)
+pub const MAX_CHANNEL_COUNT: usize = 256;
+
+/// Number of cells in a CSV line, not counting empty ones at the end
+fn used_cell_count(line: &str) -> usize {
+    let cells: Vec<&str> = line.split(',').collect();
+    cells
+        .iter()
+        .rposition(|cell| !cell.trim().is_empty())
+        .map_or(0, |last| last + 1)
+}
+
 /// Parses a CSV song string into playable SongData
 ///
 /// Parameters:
 /// - song_text: The raw CSV content
 /// - frequency_table: Pre-computed frequency table for pitch lookups
-/// - channel_count: How many channels to parse
+/// - reference_frequency_hz: Starting A4 frequency (the config row can override it)
+/// - channel_count: How many channels to parse (AUTO_CHANNEL_COUNT: one per column)
 /// - missing_cell_behavior: What to do for missing cells
-/// - debug_level: How much debug output to print
 pub fn parse_song(
     song_text: &str,
     frequency_table: &FrequencyTable,
+    reference_frequency_hz: f32,
     channel_count: usize,
     missing_cell_behavior: MissingCellBehavior,
-    debug_level: DebugLevel,
 ) -> SongData {
-    if debug_level >= DebugLevel::Basic {
-        println!("\n[PARSER] ========== PARSING SONG ==========");
-    }
+    debug!(target: "parser", "========== PARSING SONG ==========");
 
-    let mut context = ParserContext {
+    let mut reader = SongReader::new(
         frequency_table,
-        current_line: 0,
-        current_column: 0,
-        errors: Vec::new(),
+        reference_frequency_hz,
+        channel_count,
         missing_cell_behavior,
-    };
+    );
 
     let mut rows: Vec<Vec<CellAction>> = Vec::new();
     let mut raw_lines: Vec<String> = Vec::new();
-    let mut is_first_data_row = true;
-    let mut song_config = SongConfig::default();
-    let mut config_parsed = false;
+    let mut row_line_numbers: Vec<usize> = Vec::new();
+    let mut row_missing_behaviors: Vec<MissingCellBehavior> = Vec::new();
 
+    let mut read_rows: Vec<ReadRow> = Vec::new();
     for (line_index, line) in song_text.lines().enumerate() {
-        context.current_line = line_index + 1; // 1-indexed for humans
-
-        // Strip comments from the line
-        let line_without_comments = strip_comments(line);
-        let trimmed_line = line_without_comments.trim();
+        // 1-indexed for humans
+        read_rows.extend(reader.read_lines(line_index + 1, line));
+    }
+    read_rows.extend(reader.finish());
+
+    let mut widest_row = 0;
+    for row in read_rows {
+        widest_row = widest_row.max(row.used_width);
+        rows.push(row.actions);
+        raw_lines.push(row.raw_line);
+        row_line_numbers.push(row.line_number);
+        row_missing_behaviors.push(row.missing_cell_behavior);
+    }
 
-        // Skip empty lines
-        if trimmed_line.is_empty() {
-            if debug_level >= DebugLevel::Detailed {
-                println!(
-                    "[PARSER] Line {}: Skipping empty/comment line",
-                    context.current_line
-                );
-            }
-            continue;
+    // With automatic sizing, rows were parsed as wide as they are and are
+    // padded to the widest now that the whole song has been read
+    let width = if reader.auto_size {
+        reader.header_width.max(widest_row).min(MAX_CHANNEL_COUNT)
+    } else {
+        channel_count
+    };
+    // One name per channel ("" for channels the header doesn't name)
+    reader.channel_names.resize(width, String::new());
+
+    if reader.auto_size {
+        debug!(target: "parser", "Sizing song to {} channels", width);
+
+        // Rows shorter than the song get missing cells, as they would have
+        // with a fixed channel count
+        for (row, behavior) in rows.iter_mut().zip(&row_missing_behaviors) {
+            let missing_action = match behavior {
+                MissingCellBehavior::SlowRelease => CellAction::SlowRelease,
+                MissingCellBehavior::Sustain => CellAction::Sustain,
+            };
+            row.resize(width, missing_action);
         }
 
-        // Skip header row (first non-empty line)
-        if is_first_data_row {
-            is_first_data_row = false;
-            if debug_level >= DebugLevel::Verbose {
-                println!(
-                    "[PARSER] Line {}: Skipping header: '{}'",
-                    context.current_line, trimmed_line
-                );
-            }
-            continue;
-        }
+        reader.drop_group_channels_past(width);
+    }
 
-        // Check for config row (first cell is "config")
-        // This must come right after the header row
-        if !config_parsed {
-            config_parsed = true;
-            let cells: Vec<&str> = trimmed_line.split(',').collect();
-            if !cells.is_empty() && cells[0].trim().to_lowercase() == "config" {
-                song_config = SongConfig::parse_config_row(&cells);
-                if debug_level >= DebugLevel::Basic {
-                    println!("[PARSER] Line {}: Found config row", context.current_line);
-                    if let Some(title) = &song_config.title {
-                        println!("[PARSER]   Title: {}", title);
-                    }
-                    if let Some(tick) = song_config.tick_duration {
-                        println!("[PARSER]   Tick duration: {}s", tick);
-                    }
-                    if let Some(export) = song_config.export_wav {
-                        println!("[PARSER]   Export WAV: {}", export);
-                    }
-                    if let Some(bpm) = song_config.tempo_bpm {
-                        println!("[PARSER]   Tempo: {} BPM", bpm);
-                    }
-                }
-                continue; // Don't parse this as song data
-            }
-        }
+    debug!(
+        target: "parser",
+        "========== PARSING COMPLETE: {} rows, {} messages ==========",
+        rows.len(),
+        reader.context.diagnostics.len()
+    );
 
-        // Store raw line for debug display
-        raw_lines.push(trimmed_line.to_string());
+    SongData {
+        rows,
+        raw_lines,
+        channel_names: reader.channel_names,
+        row_line_numbers,
+        diagnostics: reader.context.diagnostics,
+        config: reader.song_config,
+        groups: reader.context.groups,
+        script_seeds: reader.context.script_seeds,
+    }
+}
 
-        if debug_level >= DebugLevel::Verbose {
-            println!("[PARSER] Row {}: '{}'", rows.len(), trimmed_line);
-        }
+// ============================================================================
+// LINE-BY-LINE READER
+// ============================================================================
 
-        // Split into cells
-        let cells: Vec<&str> = trimmed_line.split(',').collect();
-        let mut row_actions: Vec<CellAction> = Vec::new();
+/// Reads a song one line at a time
+///
+/// Holds everything a line can change for the lines after it (tuning,
+/// presets, groups, the header, ...), so parse_song (the whole text at
+/// once) and SongStream (a row at a time) parse lines the same way.
+struct SongReader<'a> {
+    context: ParserContext<'a>,
 
-        // Parse each cell
-        for channel_index in 0..channel_count {
-            context.current_column = channel_index;
+    /// Channels to parse (AUTO_CHANNEL_COUNT: as many as each row has)
+    channel_count: usize,
 
-            let cell_content = if channel_index < cells.len() {
-                cells[channel_index].trim()
-            } else {
-                // Missing cell - use configured behavior
-                if debug_level >= DebugLevel::Detailed {
-                    println!(
-                        "[PARSER]   Channel {}: MISSING (using {:?})",
-                        channel_index, context.missing_cell_behavior
-                    );
-                }
-                match context.missing_cell_behavior {
-                    MissingCellBehavior::SlowRelease => {
-                        row_actions.push(CellAction::SlowRelease);
-                        continue;
-                    }
-                }
-            };
+    /// True when the caller asked for AUTO_CHANNEL_COUNT
+    auto_size: bool,
 
-            if debug_level >= DebugLevel::Detailed {
-                println!("[PARSER]   Channel {}: '{}'", channel_index, cell_content);
-            }
+    /// True until the header row has been read
+    is_first_data_row: bool,
 
-            let action = parse_cell(cell_content, &mut context);
-            row_actions.push(action);
-        }
+    /// True once the line after the header has been checked for a config row
+    config_parsed: bool,
 
-        // Warn about extra cells
-        if cells.len() > channel_count {
-            context.errors.push(ParseError::warning(
-                context.current_line,
-                channel_count,
+    /// Settings from the config row
+    song_config: SongConfig,
+
+    /// Columns in the header row (or channels in a [channels] line)
+    header_width: usize,
+
+    /// Name of each channel, from the header or a [channels] line
+    channel_names: Vec<String>,
+
+    /// File column each channel reads, from a [channels] line
+    column_map: Option<Vec<Option<usize>>>,
+
+    /// Rows read so far (for log messages)
+    rows_read: usize,
+}
+
+/// One row of the song as read by SongReader
+struct ReadRow {
+    actions: Vec<CellAction>,
+
+    /// The line as shown in playback views (cells in channel order)
+    raw_line: String,
+
+    /// Line number in the file (1-based)
+    line_number: usize,
+
+    /// What missing cells meant when the row was read (for padding later)
+    missing_cell_behavior: MissingCellBehavior,
+
+    /// How many channels the row needs, not counting empty cells at the end
+    /// (with automatic sizing, the song is as wide as its widest row)
+    used_width: usize,
+}
+
+impl<'a> SongReader<'a> {
+    fn new(
+        frequency_table: &'a FrequencyTable,
+        reference_frequency_hz: f32,
+        channel_count: usize,
+        missing_cell_behavior: MissingCellBehavior,
+    ) -> Self {
+        let mut context = ParserContext::new(frequency_table, missing_cell_behavior);
+        context.tuning.reference_frequency_hz = reference_frequency_hz;
+        let auto_size = channel_count == AUTO_CHANNEL_COUNT;
+        context.channel_count = if auto_size {
+            MAX_CHANNEL_COUNT
+        } else {
+            channel_count
+        };
+
+        Self {
+            context,
+            channel_count,
+            auto_size,
+            is_first_data_row: true,
+            config_parsed: false,
+            song_config: SongConfig::default(),
+            header_width: 0,
+            channel_names: Vec::new(),
+            column_map: None,
+            rows_read: 0,
+        }
+    }
+
+    /// Reads one line of the file, then the lines a script on it wrote
+    /// Returns the rows among them.
+    fn read_lines(&mut self, line_number: usize, line: &str) -> Vec<ReadRow> {
+        let mut rows: Vec<ReadRow> = self.read_line(line_number, line).into_iter().collect();
+        rows.extend(self.read_script_output());
+        rows
+    }
+
+    /// Called after the last line: runs a script missing its [end] line,
+    /// then adds rows for [gen] generators that haven't finished
+    /// Returns the rows this adds.
+    fn finish(&mut self) -> Vec<ReadRow> {
+        let mut rows = Vec::new();
+        if let Some(script) = &self.context.script {
+            let start_line = script.start_line;
+            self.context.diagnostics.push(ParseError::warning(
+                start_line,
+                0,
+                "[script]",
+                "Script has no [end] line. It runs at the end of the file.".to_string(),
+            ));
+            run_script(&mut self.context);
+            rows = self.read_script_output();
+        }
+
+        // Empty rows for the generators to fill (once there is a header)
+        let remaining_steps = self
+            .context
+            .generators
+            .iter()
+            .map(|generator| generator.cells.len() - generator.next_step)
+            .max()
+            .unwrap_or(0);
+        if self.context.header_read {
+            for _ in 0..remaining_steps {
+                rows.extend(self.read_line(self.context.current_line, ","));
+            }
+        }
+        rows
+    }
+
+    /// Reads the lines the last script wrote, as if they were in the file
+    fn read_script_output(&mut self) -> Vec<ReadRow> {
+        let mut rows = Vec::new();
+        while let Some((script_line, written)) = self.context.script_lines.pop_front() {
+            rows.extend(self.read_line(script_line, &written));
+        }
+        rows
+    }
+
+    /// Reads one line of the file
+    /// Returns the row if the line is one (not a header, directive, ...).
+    fn read_line(&mut self, line_number: usize, line: &str) -> Option<ReadRow> {
+        let context = &mut self.context;
+        context.current_line = line_number;
+
+        // Inside a [script] block every line is script, kept as written
+        if context.script.is_some() {
+            read_script_line(line, context);
+            return None;
+        }
+
+        // Strip comments from the line
+        let line_without_comments = strip_comments(line);
+        let trimmed_line = line_without_comments.trim();
+
+        // Skip empty lines
+        if trimmed_line.is_empty() {
+            trace!(target: "parser", "Line {}: Skipping empty/comment line", context.current_line);
+            return None;
+        }
+
+        // Preset definitions inside an [instruments] block
+        // The block ends at the first line that is not "name = tokens"
+        if context.instruments_block_open {
+            if !trimmed_line.starts_with('[') && trimmed_line.contains('=') {
+                parse_preset_definition(trimmed_line, context);
+                return None;
+            }
+            context.instruments_block_open = false;
+        }
+
+        // Group definitions inside a [groups] block (ends the same way)
+        if context.groups_block_open {
+            if !trimmed_line.starts_with('[') && trimmed_line.contains('=') {
+                parse_group_definition(trimmed_line, context);
+                return None;
+            }
+            context.groups_block_open = false;
+        }
+
+        // Song defaults inside a [config] block (ends the same way)
+        if context.config_block_open {
+            if !trimmed_line.starts_with('[') && trimmed_line.contains('=') {
+                parse_config_definition(trimmed_line, context);
+                return None;
+            }
+            context.config_block_open = false;
+        }
+
+        // Directive lines like "[tuning] 19edo" can appear anywhere
+        if trimmed_line.starts_with('[') {
+            parse_directive_line(trimmed_line, context);
+            return None;
+        }
+
+        // Skip header row (first non-empty line)
+        if self.is_first_data_row {
+            self.is_first_data_row = false;
+            self.read_header(trimmed_line);
+            return None;
+        }
+
+        // Check for config row (first cell is "config")
+        // This must come right after the header row
+        if !self.config_parsed {
+            self.config_parsed = true;
+            let cells: Vec<&str> = trimmed_line.split(',').collect();
+            if !cells.is_empty() && cells[0].trim().to_lowercase() == "config" {
+                self.song_config = SongConfig::parse_config_row(&cells);
+                debug!(target: "parser", "Line {}: Found config row {:?}", context.current_line, self.song_config);
+                if let Some(reference) = self.song_config.reference_frequency_hz {
+                    context.tuning.reference_frequency_hz = reference;
+                }
+                return None; // Don't parse this as song data
+            }
+        }
+
+        // [gen] generators fill in this row's empty cells
+        let generated_line =
+            fill_generated_cells(trimmed_line, context, self.column_map.as_deref());
+        let trimmed_line = generated_line.as_deref().unwrap_or(trimmed_line);
+
+        debug!(target: "parser", "Row {}: '{}'", self.rows_read, trimmed_line);
+        self.rows_read += 1;
+
+        // Split into cells, each with the file column it came from
+        // With a [channels] line, each channel takes the column it names
+        let file_cells: Vec<&str> = trimmed_line.split(',').collect();
+        let cells: Vec<Option<(usize, &str)>> = match &self.column_map {
+            Some(columns) => columns
+                .iter()
+                .map(|column| column.and_then(|column| Some((column, *file_cells.get(column)?))))
+                .collect(),
+            None => file_cells.iter().copied().enumerate().map(Some).collect(),
+        };
+
+        // Store raw line for debug display
+        let raw_line = match &self.column_map {
+            Some(_) => {
+                let channel_cells: Vec<&str> = cells
+                    .iter()
+                    .map(|cell| cell.map_or("", |(_, text)| text.trim()))
+                    .collect();
+                channel_cells.join(",")
+            }
+            None => trimmed_line.to_string(),
+        };
+
+        let mut row_actions: Vec<CellAction> = Vec::new();
+        // Cells written empty at the end of a row are still parsed as empty
+        // cells, they just don't make the song any wider
+        let (row_width, used_width) = match (&self.column_map, self.auto_size) {
+            (_, false) => (self.channel_count, self.channel_count),
+            (Some(columns), true) => {
+                let width = columns.len().min(MAX_CHANNEL_COUNT);
+                (width, width)
+            }
+            (None, true) => (
+                cells.len().min(MAX_CHANNEL_COUNT),
+                used_cell_count(trimmed_line).min(MAX_CHANNEL_COUNT),
+            ),
+        };
+
+        // Parse each cell
+        for channel_index in 0..row_width {
+            let cell_content = if let Some((column, cell)) =
+                cells.get(channel_index).copied().flatten()
+            {
+                context.current_column = column;
+                cell.trim()
+            } else {
+                // Missing cell - use configured behavior
+                trace!(target: "parser", "  Channel {}: MISSING (using {:?})", channel_index, context.missing_cell_behavior);
+                match context.missing_cell_behavior {
+                    MissingCellBehavior::SlowRelease => {
+                        row_actions.push(CellAction::SlowRelease);
+                        continue;
+                    }
+                    MissingCellBehavior::Sustain => {
+                        row_actions.push(CellAction::Sustain);
+                        continue;
+                    }
+                }
+            };
+
+            trace!(
+                target: "parser",
+                "  Channel {} ({}): '{}'",
+                channel_index,
+                self.channel_names.get(channel_index).map_or("", String::as_str),
+                cell_content
+            );
+
+            let action = parse_cell(cell_content, context);
+            row_actions.push(action);
+        }
+
+        // Warn about extra cells
+        if self.column_map.is_none()
+            && file_cells.len() > context.channel_count
+            && row_width == context.channel_count
+        {
+            context.diagnostics.push(ParseError::warning(
+                context.current_line,
+                context.channel_count,
                 "",
                 format!(
                     "Row has {} cells but only {} channels configured. Extra cells ignored.",
-                    cells.len(),
-                    channel_count
+                    file_cells.len(),
+                    context.channel_count
                 ),
             ));
         }
 
-        rows.push(row_actions);
+        Some(ReadRow {
+            actions: row_actions,
+            raw_line,
+            line_number,
+            missing_cell_behavior: context.missing_cell_behavior,
+            used_width,
+        })
     }
 
-    if debug_level >= DebugLevel::Basic {
-        println!(
-            "[PARSER] ========== PARSING COMPLETE: {} rows, {} errors ==========\n",
-            rows.len(),
-            context.errors.len()
-        );
+    /// Reads the channel names from the header row, and matches them up
+    /// with a [channels] line if there was one
+    fn read_header(&mut self, header_line: &str) {
+        let context = &mut self.context;
+        context.header_read = true;
+        self.header_width = used_cell_count(header_line);
+        let header_names: Vec<&str> = header_line.split(',').map(str::trim).collect();
+        self.channel_names = header_names[..self.header_width]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+
+        if let Some(names) = context.channel_columns.clone() {
+            let mut columns = Vec::new();
+            for name in &names {
+                let column = header_names
+                    .iter()
+                    .position(|header| header.eq_ignore_ascii_case(name));
+                if column.is_none() {
+                    context.diagnostics.push(ParseError::error(
+                        context.current_line,
+                        0,
+                        name,
+                        format!(
+                            "[channels] names column '{}', but the header row has no such column. Its channel stays silent.",
+                            name
+                        ),
+                    ));
+                }
+                columns.push(column);
+            }
+            self.header_width = names.len();
+            self.channel_names = names;
+            self.column_map = Some(columns);
+        }
+        debug!(target: "parser", "Line {}: Skipping header: '{}'", context.current_line, header_line);
     }
 
-    SongData {
-        rows,
-        raw_lines,
-        errors: context.errors,
-        config: song_config,
+    /// Removes channels past the song's width from its groups
+    /// (groups are read before the width is known when it is automatic)
+    fn drop_group_channels_past(&mut self, width: usize) {
+        let context = &mut self.context;
+        for group in &mut context.groups {
+            let name = &group.name;
+            group.channels.retain(|&channel_index| {
+                if channel_index < width {
+                    return true;
+                }
+                context.diagnostics.push(ParseError::error(
+                    0,
+                    0,
+                    name,
+                    format!(
+                        "Group '{}' names channel {}, but the song has {} columns. Channel ignored.",
+                        name,
+                        channel_index + 1,
+                        width
+                    ),
+                ));
+                false
+            });
+        }
     }
 }
 
 // ============================================================================
-// COMMENT STRIPPING
+// STREAMING
 // ============================================================================
 
-/// Removes comments from a line
-/// Supports // comments and # comments (but preserves # in sharp notes like c#4)
-fn strip_comments(line: &str) -> &str {
-    // Handle // comments (always a comment)
-    if let Some(slash_position) = line.find("//") {
-        return &line[..slash_position];
+/// Reads a song a row at a time instead of all at once
+///
+/// parse_song keeps every row of the song in memory; a SongStream only
+/// holds the row it is reading, so an hour-long song costs no more than a
+/// short one. Call read_head first for everything above the rows (config,
+/// groups, channel names), then take the rows from the iterator.
+///
+/// Differences from parse_song:
+/// - The width is fixed once the header and first row are read (with
+///   AUTO_CHANNEL_COUNT: whichever is wider). Later rows with more cells
+///   get the usual "Extra cells ignored" warning.
+/// - "[song]" sections and [chain] are not supported (the directives are
+///   reported as unknown); stream one song per file.
+/// - A read error (e.g. a file that isn't UTF-8) is reported and ends the
+///   song there.
+pub struct SongStream<'a, R: BufRead> {
+    reader: SongReader<'a>,
+    lines: Lines<R>,
+
+    /// Line number of the last line read (1-based)
+    line_number: usize,
+
+    /// The first row, read by read_head to find the song's width
+    pending_row: Option<Vec<CellAction>>,
+
+    /// Rows read but not yet returned (a script writes many at once)
+    queued_rows: VecDeque<ReadRow>,
+
+    /// True once read_head has run
+    head_read: bool,
+
+    /// True once the input has run out (or failed)
+    finished: bool,
+}
+
+impl<'a, R: BufRead> SongStream<'a, R> {
+    /// Starts reading a song (parameters are the same as parse_song)
+    pub fn new(
+        input: R,
+        frequency_table: &'a FrequencyTable,
+        reference_frequency_hz: f32,
+        channel_count: usize,
+        missing_cell_behavior: MissingCellBehavior,
+    ) -> Self {
+        Self {
+            reader: SongReader::new(
+                frequency_table,
+                reference_frequency_hz,
+                channel_count,
+                missing_cell_behavior,
+            ),
+            lines: input.lines(),
+            line_number: 0,
+            pending_row: None,
+            queued_rows: VecDeque::new(),
+            head_read: false,
+            finished: false,
+        }
     }
 
-    // Handle # comments (but preserve sharp notes)
-    if let Some(hash_position) = line.find('#') {
-        // Check if the # is a sharp note modifier
-        let is_sharp_note = if hash_position > 0 {
-            let char_before = line.as_bytes()[hash_position - 1] as char;
-            matches!(char_before.to_ascii_lowercase(), 'a'..='g')
+    /// Reads up to the first row and returns the song without its rows:
+    /// config, groups, channel names (one per channel), and the messages
+    /// found so far
+    pub fn read_head(&mut self) -> SongData {
+        self.head_read = true;
+        let first_row = self.read_row();
+
+        let width = if self.reader.auto_size {
+            let first_row_width = first_row.as_ref().map_or(0, |row| row.used_width);
+            self.reader
+                .header_width
+                .max(first_row_width)
+                .clamp(1, MAX_CHANNEL_COUNT)
         } else {
-            false
+            self.reader.channel_count
         };
+        debug!(target: "parser", "Streaming song with {} channels", width);
+
+        // From here on every row is parsed at this width
+        self.reader.channel_count = width;
+        self.reader.context.channel_count = width;
+        self.reader.auto_size = false;
+        self.reader.channel_names.resize(width, String::new());
+        self.reader.drop_group_channels_past(width);
+
+        self.pending_row = first_row.map(|row| {
+            let missing_action = match row.missing_cell_behavior {
+                MissingCellBehavior::SlowRelease => CellAction::SlowRelease,
+                MissingCellBehavior::Sustain => CellAction::Sustain,
+            };
+            let mut actions = row.actions;
+            actions.resize(width, missing_action);
+            actions
+        });
+
+        SongData {
+            rows: Vec::new(),
+            raw_lines: Vec::new(),
+            channel_names: self.reader.channel_names.clone(),
+            row_line_numbers: Vec::new(),
+            diagnostics: self.take_diagnostics(),
+            config: self.reader.song_config.clone(),
+            groups: self.reader.context.groups.clone(),
+            script_seeds: self.reader.context.script_seeds.clone(),
+        }
+    }
 
-        if !is_sharp_note {
-            return &line[..hash_position];
+    /// Returns the messages found since the last call (or since read_head)
+    pub fn take_diagnostics(&mut self) -> ParseDiagnostics {
+        std::mem::take(&mut self.reader.context.diagnostics)
+    }
+
+    /// Reads lines until one is a row
+    fn read_row(&mut self) -> Option<ReadRow> {
+        loop {
+            if let Some(row) = self.queued_rows.pop_front() {
+                return Some(row);
+            }
+            if self.finished {
+                return None;
+            }
+            match self.lines.next() {
+                Some(Ok(line)) => {
+                    self.line_number += 1;
+                    let rows = self.reader.read_lines(self.line_number, &line);
+                    self.queued_rows.extend(rows);
+                }
+                Some(Err(error)) => {
+                    self.finished = true;
+                    self.reader.context.diagnostics.push(ParseError::error(
+                        self.line_number + 1,
+                        0,
+                        "",
+                        format!("Could not read the song: {}. The song ends here.", error),
+                    ));
+                    self.queued_rows.extend(self.reader.finish());
+                }
+                None => {
+                    self.finished = true;
+                    self.queued_rows.extend(self.reader.finish());
+                }
+            }
         }
     }
+}
 
-    line
+impl<R: BufRead> Iterator for SongStream<'_, R> {
+    type Item = Vec<CellAction>;
+
+    /// Returns the next row (reading the head first if read_head wasn't called)
+    fn next(&mut self) -> Option<Vec<CellAction>> {
+        if !self.head_read {
+            let head = self.read_head();
+            self.reader.context.diagnostics = head.diagnostics;
+        }
+        if let Some(row) = self.pending_row.take() {
+            return Some(row);
+        }
+        self.read_row().map(|row| row.actions)
+    }
+}
+
+/// Parses the text of a single cell, outside of any song
+///
+/// Used to trigger cells live (e.g., from a game). Macros, presets, and
+/// directives from a song file are not available here.
+pub fn parse_cell_text(
+    cell: &str,
+    frequency_table: &FrequencyTable,
+    reference_frequency_hz: f32,
+) -> (CellAction, ParseDiagnostics) {
+    let mut context = ParserContext::new(frequency_table, MissingCellBehavior::SlowRelease);
+    context.tuning.reference_frequency_hz = reference_frequency_hz;
+    let action = parse_cell(cell.trim(), &mut context);
+    (action, context.diagnostics)
 }
 
 // ============================================================================
-// CELL PARSING
+// MULTIPLE SONGS PER FILE
 // ============================================================================
 
-/// Parses a single cell into a CellAction
-fn parse_cell(cell: &str, context: &mut ParserContext) -> CellAction {
-    let cell = cell.trim();
+/// Name given to the only song of a file without [song] sections
+pub const DEFAULT_SONG_NAME: &str = "main";
 
-    // Empty cell = Slow Release
-    if cell.is_empty() {
-        return CellAction::SlowRelease;
-    }
+/// Every song in one file, from "[song name]" sections
+///
+/// Lines before the first [song] line are shared: each song sees them, so
+/// tunings, presets, and macros defined there work in all songs. Each
+/// section has its own header row and optional config row.
+#[derive(Clone)]
+pub struct SongCollection {
+    /// Parsed songs by (lowercase) name
+    pub songs: HashMap<String, SongData>,
 
-    // Sustain (plain hyphen)
-    if cell == "-" {
-        return CellAction::Sustain;
-    }
+    /// Song names in the order they appear in the file
+    pub song_order: Vec<String>,
 
-    // Fast Release (period)
-    if cell == "." {
-        return CellAction::FastRelease;
-    }
+    /// Play order from a "[chain] intro main" line (empty if none)
+    pub chain: Vec<String>,
 
-    // Split into tokens (handles multiple spaces)
-    let tokens: Vec<&str> = cell.split_whitespace().collect();
-    if tokens.is_empty() {
-        return CellAction::SlowRelease;
+    /// Problems with the [song] and [chain] lines themselves
+    pub diagnostics: ParseDiagnostics,
+}
+
+impl SongCollection {
+    /// Wraps one song (e.g., loaded from JSON) as a collection named "main"
+    pub fn single(song: SongData) -> Self {
+        Self {
+            songs: HashMap::from([(DEFAULT_SONG_NAME.to_string(), song)]),
+            song_order: vec![DEFAULT_SONG_NAME.to_string()],
+            chain: Vec::new(),
+            diagnostics: ParseDiagnostics::default(),
+        }
     }
 
-    // Check for sustain with effects: "- a:0.5"
-    if tokens[0] == "-" && tokens.len() > 1 {
-        return parse_sustain_with_effects(&tokens[1..], context);
+    /// What to play when no song is requested:
+    /// the [chain] if there is one, otherwise the first song
+    pub fn default_selection(&self) -> Vec<String> {
+        if !self.chain.is_empty() {
+            return self.chain.clone();
+        }
+        self.song_order.iter().take(1).cloned().collect()
     }
 
-    // Determine what kind of cell this is by looking at the first token
-    let first_token = tokens[0];
-    let first_char = first_token.chars().next().unwrap().to_ascii_lowercase();
+    /// Joins the named songs (in order) into one playable SongData
+    ///
+    /// The first song's config row and groups apply to the whole chain, so
+    /// the songs play back to back with no gap and notes can ring across.
+    pub fn select(&self, names: &[String]) -> Result<SongData, String> {
+        let Some(first_name) = names.first() else {
+            return Err("No song selected".to_string());
+        };
 
-    // Is it a note? (starts with a-g)
-    // But NOT if it contains a colon - that's an effect like "a:0.4", not a note like "a4"
-    let is_note = matches!(first_char, 'a'..='g') && !first_token.contains(':');
+        let mut selected = SongData {
+            rows: Vec::new(),
+            raw_lines: Vec::new(),
+            channel_names: Vec::new(),
+            row_line_numbers: Vec::new(),
+            diagnostics: self.diagnostics.clone(),
+            config: SongConfig::default(),
+            groups: Vec::new(),
+            script_seeds: Vec::new(),
+        };
+        let mut used_names: HashSet<&str> = HashSet::new();
 
-    if is_note {
-        // Note trigger: "c4 sine a:0.8"
-        return parse_note_trigger(&tokens, context);
+        for name in names {
+            let song = self.songs.get(&name.to_lowercase()).ok_or_else(|| {
+                format!(
+                    "No song named '{}'. Songs in this file: {}",
+                    name,
+                    self.song_order.join(", ")
+                )
+            })?;
+
+            if name == first_name {
+                selected.config = song.config.clone();
+                selected.groups = song.groups.clone();
+                selected.channel_names = song.channel_names.clone();
+            } else if song.config.tick_duration != selected.config.tick_duration {
+                selected.diagnostics.push(ParseError::warning(
+                    0,
+                    0,
+                    name,
+                    format!(
+                        "Song '{}' has a different tick duration than '{}'. \
+                         Chained songs all play at the first song's speed.",
+                        name, first_name
+                    ),
+                ));
+            }
+
+            selected.rows.extend(song.rows.iter().cloned());
+            selected.raw_lines.extend(song.raw_lines.iter().cloned());
+            selected
+                .row_line_numbers
+                .extend(song.row_line_numbers.iter().copied());
+
+            // Shared lines are parsed once per song; report their warnings once
+            if used_names.insert(name.as_str()) {
+                selected.script_seeds.extend(&song.script_seeds);
+                for diagnostic in &song.diagnostics {
+                    selected.diagnostics.push_unique(diagnostic.clone());
+                }
+            }
+        }
+
+        // Songs sized to their own columns can differ in width; channels
+        // a narrower song doesn't have release while it plays
+        let width = selected.channel_count();
+        for row in &mut selected.rows {
+            row.resize(width, CellAction::SlowRelease);
+        }
+        selected.channel_names.resize(width, String::new());
+
+        Ok(selected)
     }
+}
 
-    // Check if first token is an instrument name
-    if let Some(instrument_id) = find_instrument_by_name(first_token) {
-        if instrument_id == 0 {
-            // Instrument 0 = master bus effects
-            return parse_master_effects(&tokens, context);
+/// Parses a file that may hold several songs in "[song name]" sections
+///
+/// A file without [song] lines becomes one song named "main". Parameters
+/// are the same as parse_song, which does the work for each section.
+pub fn parse_song_collection(
+    song_text: &str,
+    frequency_table: &FrequencyTable,
+    reference_frequency_hz: f32,
+    channel_count: usize,
+    missing_cell_behavior: MissingCellBehavior,
+) -> SongCollection {
+    let lines: Vec<&str> = song_text.lines().collect();
+    let mut diagnostics = ParseDiagnostics::default();
+    let mut chain: Vec<String> = Vec::new();
+
+    // Which section each line belongs to: None = shared, Some(i) = song i
+    let mut line_sections: Vec<Option<usize>> = Vec::with_capacity(lines.len());
+    let mut current_section: Option<usize> = None;
+    let mut song_order: Vec<String> = Vec::new();
+
+    // The [song] and [chain] lines themselves, hidden from every song
+    let mut section_line_indices: HashSet<usize> = HashSet::new();
+
+    for (line_index, line) in lines.iter().enumerate() {
+        let line_number = line_index + 1;
+        let trimmed_line = strip_comments(line).trim().to_string();
+
+        let directive = if trimmed_line.starts_with('[') {
+            split_directive(&trimmed_line)
         } else {
-            // Check if this instrument requires a pitch
-            if let Some(instrument) = get_instrument_by_id(instrument_id) {
-                if !instrument.requires_pitch {
-                    // Pitchless instrument (like noise)
-                    return parse_pitchless_trigger(&tokens, context);
-                } else {
-                    // Requires pitch but none given
-                    context.errors.push(ParseError::warning(
-                        context.current_line,
-                        context.current_column,
-                        cell,
+            None
+        };
+
+        match directive {
+            Some((name, label, argument)) if name == "song" => {
+                // "[song intro]" or "[song] intro"
+                let song_name = if label.is_empty() { argument } else { label }.to_lowercase();
+                if song_name.is_empty() {
+                    diagnostics.push(ParseError::error(
+                        line_number,
+                        0,
+                        &trimmed_line,
+                        "Song section needs a name, like '[song intro]'.".to_string(),
+                    ));
+                } else if let Some(existing) = song_order.iter().position(|name| *name == song_name)
+                {
+                    current_section = Some(existing);
+                    diagnostics.push(ParseError::warning(
+                        line_number,
+                        0,
+                        &trimmed_line,
                         format!(
-                            "Instrument '{}' requires a note (e.g., 'c4 {}')",
-                            instrument.name, instrument.name
+                            "Song '{}' is defined twice. The second section adds to the first.",
+                            song_name
                         ),
                     ));
-                    return CellAction::SlowRelease;
+                } else {
+                    song_order.push(song_name);
+                    current_section = Some(song_order.len() - 1);
                 }
+                section_line_indices.insert(line_index);
+            }
+            Some((name, _, argument)) if name == "chain" => {
+                chain = split_song_names(argument);
+                section_line_indices.insert(line_index);
             }
+            _ => {}
         }
+
+        line_sections.push(current_section);
     }
 
-    // Check if it's a master-only effect
-    if is_master_effect(first_token) {
-        return parse_master_effects(&tokens, context);
+    let mut songs: HashMap<String, SongData> = HashMap::new();
+
+    if song_order.is_empty() {
+        let song = parse_song(
+            song_text,
+            frequency_table,
+            reference_frequency_hz,
+            channel_count,
+            missing_cell_behavior,
+        );
+        song_order.push(DEFAULT_SONG_NAME.to_string());
+        songs.insert(DEFAULT_SONG_NAME.to_string(), song);
+    } else {
+        for (song_index, song_name) in song_order.iter().enumerate() {
+            debug!(target: "parser", "Song section '{}'", song_name);
+
+            // Keep shared lines and this song's lines; blank everything else
+            // so line numbers in messages still match the file
+            let section_text: Vec<&str> = lines
+                .iter()
+                .enumerate()
+                .map(|(line_index, line)| {
+                    let belongs = !section_line_indices.contains(&line_index)
+                        && line_sections[line_index].is_none_or(|section| section == song_index);
+                    if belongs { *line } else { "" }
+                })
+                .collect();
+
+            let song = parse_song(
+                &section_text.join("\n"),
+                frequency_table,
+                reference_frequency_hz,
+                channel_count,
+                missing_cell_behavior,
+            );
+            songs.insert(song_name.clone(), song);
+        }
     }
 
-    // Otherwise, it's a channel effect change
-    parse_effect_change(&tokens, context)
+    // Check the chain only names songs that exist, and leave out any that
+    // don't so the default selection can always be played
+    chain.retain(|name| {
+        if songs.contains_key(name) {
+            return true;
+        }
+        diagnostics.push(ParseError::error(
+            0,
+            0,
+            name,
+            format!("[chain] names unknown song '{}'. Song skipped.", name),
+        ));
+        false
+    });
+
+    SongCollection {
+        songs,
+        song_order,
+        chain,
+        diagnostics,
+    }
 }
 
-/// Parses "- a:0.5 tr:2" (sustain with effect changes)
-fn parse_sustain_with_effects(tokens: &[&str], context: &mut ParserContext) -> CellAction {
-    let (effects, transition_seconds, clear_first) = parse_effect_tokens(tokens, context);
+/// Splits "intro, main main" into lowercase song names
+pub fn split_song_names(text: &str) -> Vec<String> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|name| !name.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
 
-    CellAction::SustainWithEffects {
-        effects,
-        transition_seconds,
-        clear_first,
-    }
-}
+// ============================================================================
+// DIRECTIVES
+// ============================================================================
 
-/// Parses a note trigger like "c4 sine a:0.8"
-fn parse_note_trigger(tokens: &[&str], context: &mut ParserContext) -> CellAction {
-    let pitch = tokens[0].to_string();
+/// Splits "[name label] argument" into its three parts
+/// Returns None if the closing ']' is missing.
+fn split_directive(line: &str) -> Option<(String, &str, &str)> {
+    let close_position = line.find(']')?;
 
-    // Look up frequency from table
-    let frequency_hz = match parse_pitch_to_frequency(&pitch, context.frequency_table) {
-        Some(freq) => freq,
-        None => {
-            context.errors.push(ParseError::warning(
-                context.current_line,
-                context.current_column,
-                &pitch,
-                format!("Invalid pitch '{}'. Using A4 (440 Hz).", pitch),
-            ));
-            440.0
-        }
+    // "[macro hat]" has a label after the directive name
+    let inside_brackets = line[1..close_position].trim();
+    let (name, label) = match inside_brackets.split_once(char::is_whitespace) {
+        Some((name, label)) => (name.to_lowercase(), label.trim()),
+        None => (inside_brackets.to_lowercase(), ""),
     };
+    let argument = line[close_position + 1..]
+        .trim()
+        .trim_end_matches(',')
+        .trim();
 
-    let mut instrument_id = 1; // Default to sine
-    let mut instrument_parameters: Vec<f32> = Vec::new();
-    let mut seen_effects: HashSet<String> = HashSet::new();
+    Some((name, label, argument))
+}
 
-    // First pass: find clear flag and instrument
-    let mut clear_effects = false;
-    for token in &tokens[1..] {
-        let token_lower = token.to_lowercase();
-        if token_lower == "clear" || token_lower == "cl" {
-            clear_effects = true;
-        }
+/// Parses a directive line like "[tuning] 19edo"
+/// The name goes between the brackets; everything after is the argument.
+/// Trailing commas are ignored so directives survive spreadsheet editors.
+fn parse_directive_line(line: &str, context: &mut ParserContext) {
+    let Some((name, label, argument)) = split_directive(line) else {
+        context.diagnostics.push(ParseError::error(
+            context.current_line,
+            0,
+            line,
+            "Directive is missing a closing ']'. Line ignored.".to_string(),
+        ));
+        return;
+    };
 
-        // Check for instrument (without colon)
-        if !token.contains(':')
-            && let Some(id) = find_instrument_by_name(token)
-        {
-            if id == 0 {
-                context.errors.push(ParseError::warning(
-                    context.current_line,
-                    context.current_column,
-                    token,
-                    "Cannot play notes on 'master'. Use a playable instrument.".to_string(),
-                ));
-                return CellAction::SlowRelease;
+    match name.as_str() {
+        "macro" | "m" => parse_macro_definition(label, argument, context),
+        "instruments" | "presets" => {
+            if argument.to_lowercase().ends_with(".toml") {
+                // "[instruments] my_kit.toml" loads a whole file of instruments
+                load_instrument_file_presets(argument, context);
+            } else {
+                // Definitions can follow on the same line or on the next lines
+                context.instruments_block_open = true;
+                if !argument.is_empty() {
+                    parse_preset_definition(argument, context);
+                }
             }
-            instrument_id = id;
         }
-    }
-
-    // Second pass: parse instrument params and effects
-    let mut effects = ChannelEffectState::default();
-    effects.initialize_chorus_buffer(48000); // Will be re-initialized if needed
-    let mut transition_seconds = 0.0;
-
-    for token in &tokens[1..] {
-        let token_lower = token.to_lowercase();
-
-        // Skip clear token
-        if token_lower == "clear" || token_lower == "cl" {
-            continue;
+        "groups" | "group" => {
+            // Definitions can follow on the same line or on the next lines
+            context.groups_block_open = true;
+            if !argument.is_empty() {
+                parse_group_definition(argument, context);
+            }
         }
-
-        // Skip if it's a standalone instrument name (already handled)
-        if !token.contains(':') {
-            if find_instrument_by_name(token).is_some() {
-                continue;
+        "config" | "defaults" => {
+            // Settings can follow on the same line or on the next lines
+            context.config_block_open = true;
+            if !argument.is_empty() {
+                parse_config_definition(argument, context);
             }
-            // Unknown standalone token
-            context.errors.push(ParseError::warning(
+        }
+        "channels" | "columns" => parse_channel_columns(argument, context),
+        "script" => open_script(label, argument, context),
+        "gen" | "generate" => parse_generator(label, argument, context),
+        "end" => {
+            context.diagnostics.push(ParseError::warning(
                 context.current_line,
-                context.current_column,
-                token,
-                format!("Unrecognized token '{}' - ignoring", token),
+                0,
+                line,
+                "[end] without a [script] before it. Line ignored.".to_string(),
             ));
-            continue;
         }
-
-        // Parse colon-separated token
-        if let Some(colon_pos) = token.find(':') {
-            let prefix = &token[..colon_pos].to_lowercase();
-            let value_str = &token[colon_pos + 1..];
-
-            // Check if it's an instrument with parameters (e.g., "trisaw:0.5")
-            if let Some(id) = find_instrument_by_name(prefix) {
-                if id == 0 {
-                    context.errors.push(ParseError::warning(
-                        context.current_line,
-                        context.current_column,
-                        token,
-                        "Cannot play notes on 'master'.".to_string(),
-                    ));
-                    return CellAction::SlowRelease;
+        "sample" => match load_sample(argument) {
+            Ok(index) => {
+                debug!(
+                    target: "parser",
+                    "Line {}: Grain sample set to {}",
+                    context.current_line,
+                    argument
+                );
+                context.grain_sample = Some(index);
+            }
+            Err(message) => {
+                context.diagnostics.push(ParseError::error(
+                    context.current_line,
+                    0,
+                    argument,
+                    format!("{}. Keeping previous sample.", message),
+                ));
+            }
+        },
+        "tuning" => match Tuning::parse_directive(argument) {
+            Ok(mut tuning) => {
+                // Changing the tuning system keeps the current A4 reference
+                tuning.reference_frequency_hz = context.tuning.reference_frequency_hz;
+                debug!(
+                    target: "parser",
+                    "Line {}: Tuning set to {}",
+                    context.current_line,
+                    tuning.display_name()
+                );
+                context.tuning = tuning;
+            }
+            Err(message) => {
+                context.diagnostics.push(ParseError::error(
+                    context.current_line,
+                    0,
+                    argument,
+                    format!("{}. Keeping previous tuning.", message),
+                ));
+            }
+        },
+        "scale" => match ScaleConstraint::parse_directive(argument) {
+            Ok(scale) => {
+                match &scale {
+                    Some(scale) => debug!(
+                        target: "parser",
+                        "Line {}: Scale set to {} ({:?})",
+                        context.current_line, scale.display_name, scale.mode
+                    ),
+                    None => {
+                        debug!(target: "parser", "Line {}: Scale check off", context.current_line)
+                    }
                 }
-                instrument_id = id;
-                instrument_parameters = parse_parameter_list(value_str);
-                continue;
+                context.scale = scale;
             }
-
-            // It's an effect
-            if seen_effects.contains(prefix) {
-                context.errors.push(ParseError::warning(
+            Err(message) => {
+                context.diagnostics.push(ParseError::error(
                     context.current_line,
-                    context.current_column,
-                    token,
-                    format!("Effect '{}' specified multiple times - using first", prefix),
+                    0,
+                    argument,
+                    format!("{}. Keeping previous scale.", message),
                 ));
-                continue;
             }
-            seen_effects.insert(prefix.clone());
+        },
+        _ => {
+            context.diagnostics.push(ParseError::error(
+                context.current_line,
+                0,
+                line,
+                format!("Unknown directive '[{}]'. Line ignored.", name),
+            ));
+        }
+    }
+}
 
-            apply_effect_token(
-                prefix,
-                value_str,
-                &mut effects,
-                &mut transition_seconds,
-                &mut clear_effects,
-            );
+/// Starts a [script] block: "[script]", "[script seed:42]", or
+/// "[script] seed:42". The lines up to [end] are the script.
+fn open_script(label: &str, argument: &str, context: &mut ParserContext) {
+    let settings = if label.is_empty() { argument } else { label };
+    let mut seed = None;
+    for setting in settings.split_whitespace() {
+        match setting
+            .to_lowercase()
+            .strip_prefix("seed:")
+            .map(str::parse::<u32>)
+        {
+            Some(Ok(value)) => seed = Some(value),
+            _ => context.diagnostics.push(ParseError::warning(
+                context.current_line,
+                0,
+                setting,
+                format!(
+                    "Unknown [script] setting '{}' (expected seed:N). Setting ignored.",
+                    setting
+                ),
+            )),
         }
     }
 
-    CellAction::TriggerNote {
-        frequency_hz,
-        instrument_id,
-        instrument_parameters,
-        effects,
-        transition_seconds,
-        clear_effects,
+    context.script = Some(ScriptBlock {
+        start_line: context.current_line,
+        seed,
+        // Blank lines stand in for the file above the script, so the line
+        // numbers in script errors match the file's
+        source: "\n".repeat(context.current_line),
+    });
+}
+
+/// Adds a line to the open [script] block, or runs it at its [end] line
+fn read_script_line(line: &str, context: &mut ParserContext) {
+    let is_end = strip_comments(line)
+        .trim()
+        .trim_end_matches(',')
+        .trim()
+        .eq_ignore_ascii_case("[end]");
+    if is_end {
+        run_script(context);
+    } else if let Some(script) = &mut context.script {
+        script.source.push_str(line);
+        script.source.push('\n');
     }
 }
 
-/// Parses a pitchless instrument trigger like "noise a:0.5"
-fn parse_pitchless_trigger(tokens: &[&str], context: &mut ParserContext) -> CellAction {
-    let instrument_id = find_instrument_by_name(tokens[0]).unwrap_or(4); // Default to noise
-    let (effects, transition_seconds, clear_effects) = parse_effect_tokens(&tokens[1..], context);
+/// Runs the open [script] block and queues the lines it wrote
+fn run_script(context: &mut ParserContext) {
+    let Some(script) = context.script.take() else {
+        return;
+    };
+    let seed = script.seed.unwrap_or_else(random_script_seed);
+    context.script_seeds.push(seed);
+    if script.seed.is_none() {
+        context.diagnostics.push(ParseError::warning(
+            script.start_line,
+            0,
+            "[script]",
+            format!(
+                "Script ran with random seed {}. Write '[script seed:{}]' to get the same rows again.",
+                seed, seed
+            ),
+        ));
+    }
 
-    CellAction::TriggerPitchless {
-        instrument_id,
-        instrument_parameters: Vec::new(),
-        effects,
-        transition_seconds,
-        clear_effects,
+    match run_song_script(&script.source, seed) {
+        Ok(lines) => {
+            debug!(
+                target: "parser",
+                "Line {}: Script wrote {} lines (seed {})",
+                script.start_line,
+                lines.len(),
+                seed
+            );
+            context
+                .script_lines
+                .extend(lines.into_iter().map(|line| (script.start_line, line)));
+        }
+        Err(message) => {
+            context.diagnostics.push(ParseError::error(
+                script.start_line,
+                0,
+                "[script]",
+                format!("{}. No rows written.", message),
+            ));
+        }
     }
 }
 
-/// Parses effect-only changes like "a:0.5 p:-0.3"
-fn parse_effect_change(tokens: &[&str], context: &mut ParserContext) -> CellAction {
-    let (effects, transition_seconds, clear_first) = parse_effect_tokens(tokens, context);
+/// Parses "[gen ch4] euclid(5,16,"noise a:0.4")": channel 4's empty cells
+/// in the next 16 rows get the cell on 5 evenly spread rows. A new
+/// generator on a channel replaces the one running there.
+fn parse_generator(label: &str, argument: &str, context: &mut ParserContext) {
+    let label_lower = label.to_lowercase();
+    let channel_number = label_lower
+        .strip_prefix("ch")
+        .unwrap_or(&label_lower)
+        .parse::<usize>()
+        .ok()
+        .filter(|&number| (1..=context.channel_count).contains(&number));
+    let Some(channel_number) = channel_number else {
+        context.diagnostics.push(ParseError::error(
+            context.current_line,
+            0,
+            label,
+            format!(
+                "[gen] needs a channel from 1 to {}, like '[gen ch4]'. Line ignored.",
+                context.channel_count
+            ),
+        ));
+        return;
+    };
 
-    CellAction::ChangeEffects {
-        effects,
-        transition_seconds,
-        clear_first,
+    match parse_generator_pattern(argument) {
+        Ok(cells) => {
+            debug!(
+                target: "parser",
+                "Line {}: Channel {} generates {} rows",
+                context.current_line,
+                channel_number,
+                cells.len()
+            );
+            let channel = channel_number - 1;
+            context
+                .generators
+                .retain(|generator| generator.channel != channel);
+            context.generators.push(CellGenerator {
+                channel,
+                cells,
+                next_step: 0,
+            });
+        }
+        Err(message) => {
+            context.diagnostics.push(ParseError::error(
+                context.current_line,
+                0,
+                argument,
+                format!("{}. Line ignored.", message),
+            ));
+        }
     }
 }
 
-/// Parses master bus effects
-fn parse_master_effects(tokens: &[&str], context: &mut ParserContext) -> CellAction {
-    // Determine starting index (skip "master" if present)
-    let start_index = if find_instrument_by_name(tokens[0]).is_some() {
-        1
-    } else {
-        0
+/// Turns 'euclid(hits, steps, "cell")' or 'euclid(hits, steps, "cell",
+/// repeats)' into one cell per row ("" between hits)
+fn parse_generator_pattern(text: &str) -> Result<Vec<String>, String> {
+    let usage = "like 'euclid(5,16,\"noise a:0.4\")'";
+    let Some((name, arguments)) = text.split_once('(') else {
+        return Err(format!("[gen] needs a pattern, {}", usage));
+    };
+    if !name.trim().eq_ignore_ascii_case("euclid") {
+        return Err(format!(
+            "Unknown generator '{}', expected one {}",
+            name.trim(),
+            usage
+        ));
+    }
+    let Some(arguments) = arguments.trim_end().strip_suffix(')') else {
+        return Err(format!("Generator is missing its closing ')', {}", usage));
     };
-    let effect_tokens = &tokens[start_index..];
 
-    let mut should_clear = false;
-    let mut transition_seconds = 0.0;
-    let mut master_effects: Vec<(String, Vec<f32>)> = Vec::new();
-    let mut seen_effects: HashSet<String> = HashSet::new();
+    let arguments: Vec<&str> = arguments.split(',').map(str::trim).collect();
+    let number = |index: usize, default: Option<usize>| match arguments.get(index) {
+        Some(text) => text.parse::<usize>().ok(),
+        None => default,
+    };
+    let (Some(hits), Some(steps), Some(cell), Some(repeats)) = (
+        number(0, None),
+        number(1, None).filter(|&steps| steps > 0),
+        arguments.get(2).map(|cell| cell.trim_matches('"').trim()),
+        number(3, Some(1)).filter(|&repeats| repeats > 0),
+    ) else {
+        return Err(format!(
+            "euclid takes hits, steps (at least 1), a cell, and optionally a repeat count, {}",
+            usage
+        ));
+    };
+    if arguments.len() > 4 {
+        return Err(format!("euclid has too many arguments, {}", usage));
+    }
+    if steps.saturating_mul(repeats) > MAX_GENERATOR_STEPS {
+        return Err(format!(
+            "A generator can cover at most {} rows",
+            MAX_GENERATOR_STEPS
+        ));
+    }
 
-    // First pass: check for clear
-    for token in effect_tokens {
-        let token_lower = token.to_lowercase();
-        if token_lower == "clear"
-            || token_lower == "cl"
-            || token_lower.starts_with("clear:")
-            || token_lower.starts_with("cl:")
-        {
-            should_clear = true;
+    let pattern = euclid_pattern(hits, steps);
+    Ok((0..repeats)
+        .flat_map(|_| pattern.iter())
+        .map(|&hit| if hit { cell.to_string() } else { String::new() })
+        .collect())
+}
 
-            // Extract transition time from clear:X
-            if (token_lower.starts_with("clear:") || token_lower.starts_with("cl:"))
-                && let Some(colon_pos) = token.find(':')
-            {
-                let params = parse_parameter_list(&token[colon_pos + 1..]);
-                if !params.is_empty() {
-                    transition_seconds = params[0].max(0.0);
-                }
-            }
-        }
+/// Writes the next cell of each running [gen] generator into a row
+///
+/// A cell is only written where the row's own cell is empty, so written
+/// notes win. Returns the new line, or None if no generator is running.
+fn fill_generated_cells(
+    line: &str,
+    context: &mut ParserContext,
+    column_map: Option<&[Option<usize>]>,
+) -> Option<String> {
+    if context.generators.is_empty() {
+        return None;
     }
 
-    // Second pass: parse effects
-    for token in effect_tokens {
-        let token_lower = token.to_lowercase();
-
-        // Skip clear tokens
-        if token_lower == "clear" || token_lower == "cl" {
-            continue;
+    let mut cells: Vec<String> = line.split(',').map(str::to_string).collect();
+    for generator in &mut context.generators {
+        let cell = &generator.cells[generator.next_step];
+        generator.next_step += 1;
+
+        // With a [channels] line, the channel's cell is in the column it names
+        let column = match column_map {
+            Some(columns) => match columns.get(generator.channel).copied().flatten() {
+                Some(column) => column,
+                None => continue,
+            },
+            None => generator.channel,
+        };
+        if cells.len() <= column {
+            cells.resize(column + 1, String::new());
         }
-        if token_lower.starts_with("clear:") || token_lower.starts_with("cl:") {
-            continue;
+        if cells[column].trim().is_empty() && !cell.is_empty() {
+            cells[column] = cell.clone();
         }
+    }
+    context
+        .generators
+        .retain(|generator| generator.next_step < generator.cells.len());
 
-        if let Some(colon_pos) = token.find(':') {
-            let effect_name = token[..colon_pos].to_lowercase();
-            let value_str = &token[colon_pos + 1..];
-
-            // Handle transition
-            if effect_name == "tr" || effect_name == "transition" {
-                let params = parse_parameter_list(value_str);
-                if !params.is_empty() {
-                    transition_seconds = params[0].max(0.0);
-                }
-                continue;
-            }
+    Some(cells.join(","))
+}
 
-            // Validate it's a master effect
-            match effect_name.as_str() {
-                "rv" | "reverb" | "rv2" | "reverb2" | "dl" | "delay" | "a" | "amplitude" | "p"
-                | "pan" | "ch" | "chorus" => {
-                    if seen_effects.contains(&effect_name) {
-                        context.errors.push(ParseError::warning(
-                            context.current_line,
-                            context.current_column,
-                            token,
-                            format!("Master effect '{}' specified multiple times", effect_name),
-                        ));
-                        continue;
-                    }
-                    seen_effects.insert(effect_name.clone());
+/// Parses "[channels] Kick, Snare, Bass": channel 1 plays the header
+/// column named Kick wherever it is, and so on. Columns not named are skipped.
+fn parse_channel_columns(argument: &str, context: &mut ParserContext) {
+    if context.header_read {
+        context.diagnostics.push(ParseError::error(
+            context.current_line,
+            0,
+            argument,
+            "[channels] must come before the header row. Line ignored.".to_string(),
+        ));
+        return;
+    }
 
-                    let params = parse_parameter_list(value_str);
-                    master_effects.push((effect_name, params));
-                }
-                _ => {
-                    context.errors.push(ParseError::warning(
-                        context.current_line,
-                        context.current_column,
-                        token,
-                        format!(
-                            "Effect '{}' cannot be applied to master bus. Use: a, p, rv, rv2, dl, ch",
-                            effect_name
-                        ),
-                    ));
-                }
-            }
-        }
+    let names: Vec<String> = argument
+        .split(',')
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect();
+    if names.is_empty() {
+        context.diagnostics.push(ParseError::error(
+            context.current_line,
+            0,
+            "",
+            "[channels] needs header names, like '[channels] Kick, Snare, Bass'".to_string(),
+        ));
+        return;
     }
 
-    CellAction::MasterEffects {
-        clear_first: should_clear,
-        transition_seconds,
-        effects: master_effects,
+    debug!(target: "parser", "Line {}: Channels mapped to columns {:?}", context.current_line, names);
+    context.channel_columns = Some(names);
+}
+
+/// Parses a macro definition: the label is "name" or "name(default'default)"
+fn parse_macro_definition(label: &str, body: &str, context: &mut ParserContext) {
+    let (name, default_arguments) = match label.split_once('(') {
+        Some((name, arguments)) => (
+            name.trim().to_lowercase(),
+            split_macro_arguments(arguments.trim_end_matches(')')),
+        ),
+        None => (label.trim().to_lowercase(), Vec::new()),
+    };
+
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || character == '_')
+    {
+        context.diagnostics.push(ParseError::error(
+            context.current_line,
+            0,
+            label,
+            "Macro needs a one-word name, e.g. '[macro hat] noise a:0.3'. Macro ignored."
+                .to_string(),
+        ));
+        return;
     }
+
+    debug!(target: "parser", "Line {}: Macro '@{}' = {}", context.current_line, name, body);
+    context.macros.insert(
+        name,
+        MacroDefinition {
+            body: body.to_string(),
+            default_arguments,
+        },
+    );
 }
 
-// ============================================================================
-// HELPER FUNCTIONS
-// ============================================================================
+/// Replaces every "@name" or "@name(arg'arg)" in a cell with the macro text
+/// Returns None if the cell uses no macros
+fn expand_macros(cell: &str, context: &mut ParserContext) -> Option<String> {
+    if !cell.contains('@') {
+        return None;
+    }
 
-/// Parses effect tokens and returns (effects, transition_seconds, clear_first)
-fn parse_effect_tokens(
-    tokens: &[&str],
-    context: &mut ParserContext,
-) -> (ChannelEffectState, f32, bool) {
-    let mut effects = ChannelEffectState::default();
-    effects.initialize_chorus_buffer(48000);
-    let mut transition_seconds = 0.0;
-    let mut clear_first = false;
-    let mut seen_effects: HashSet<String> = HashSet::new();
-
-    // First pass: check for clear
-    for token in tokens {
-        let token_lower = token.to_lowercase();
-        if token_lower == "clear" || token_lower == "cl" {
-            clear_first = true;
-            break;
+    let mut text = cell.to_string();
+    for _ in 0..MAX_MACRO_DEPTH {
+        if !text.split_whitespace().any(|token| token.starts_with('@')) {
+            return Some(text);
         }
-    }
-
-    // Second pass: parse effects
-    for token in tokens {
-        let token_lower = token.to_lowercase();
 
-        if token_lower == "clear" || token_lower == "cl" {
-            continue;
-        }
+        let mut expanded_tokens: Vec<String> = Vec::new();
+        for token in text.split_whitespace() {
+            let Some(call) = token.strip_prefix('@') else {
+                expanded_tokens.push(token.to_string());
+                continue;
+            };
 
-        if let Some(colon_pos) = token.find(':') {
-            let effect_name = token[..colon_pos].to_lowercase();
-            let value_str = &token[colon_pos + 1..];
+            let (name, arguments) = match call.split_once('(') {
+                Some((name, arguments)) => (
+                    name.to_lowercase(),
+                    split_macro_arguments(arguments.trim_end_matches(')')),
+                ),
+                None => (call.to_lowercase(), Vec::new()),
+            };
 
-            if seen_effects.contains(&effect_name) {
-                context.errors.push(ParseError::warning(
+            let Some(definition) = context.macros.get(&name) else {
+                context.diagnostics.push(ParseError::error(
                     context.current_line,
                     context.current_column,
                     token,
-                    format!("Effect '{}' specified multiple times", effect_name),
+                    format!("Unknown macro '@{}' - ignoring", name),
                 ));
                 continue;
+            };
+
+            // Fill in $1 to $9. Placeholders are a single digit, so "$12"
+            // is $1 followed by a literal "2"
+            let mut body = definition.body.clone();
+            let mut missing_argument = None;
+            for index in (1..=9).rev() {
+                let placeholder = format!("${}", index);
+                if !body.contains(&placeholder) {
+                    continue;
+                }
+                let value = arguments
+                    .get(index - 1)
+                    .or_else(|| definition.default_arguments.get(index - 1));
+                match value {
+                    Some(value) => body = body.replace(&placeholder, value),
+                    None => {
+                        missing_argument = Some(index);
+                        body = body.replace(&placeholder, "");
+                    }
+                }
+            }
+            if let Some(index) = missing_argument {
+                context.diagnostics.push(ParseError::error(
+                    context.current_line,
+                    context.current_column,
+                    token,
+                    format!("Macro '@{}' is missing argument ${}", name, index),
+                ));
             }
-            seen_effects.insert(effect_name.clone());
 
-            apply_effect_token(
-                &effect_name,
-                value_str,
-                &mut effects,
-                &mut transition_seconds,
-                &mut clear_first,
-            );
+            expanded_tokens.push(body);
         }
+        text = expanded_tokens.join(" ");
     }
 
-    (effects, transition_seconds, clear_first)
+    context.diagnostics.push(ParseError::error(
+        context.current_line,
+        context.current_column,
+        cell,
+        format!(
+            "Macros nested more than {} deep (does a macro use itself?). Cell ignored.",
+            MAX_MACRO_DEPTH
+        ),
+    ));
+    Some(String::new())
 }
 
-/// Applies an effect token to an effect state
-fn apply_effect_token(
-    effect_name: &str,
-    value_str: &str,
-    effects: &mut ChannelEffectState,
-    transition_seconds: &mut f32,
-    clear_effects: &mut bool,
-) {
-    let params = parse_parameter_list(value_str);
+/// Splits macro arguments like "0.5'0.2" into ["0.5", "0.2"]
+fn split_macro_arguments(arguments: &str) -> Vec<String> {
+    arguments
+        .split('\'')
+        .map(|argument| argument.trim().to_string())
+        .filter(|argument| !argument.is_empty())
+        .collect()
+}
 
-    match effect_name {
-        "a" | "amplitude" => {
-            if !params.is_empty() {
-                effects.amplitude = params[0].clamp(0.0, 1.0);
-            }
-        }
-        "p" | "pan" => {
-            if !params.is_empty() {
-                effects.pan = params[0].clamp(-1.0, 1.0);
-            }
+/// Parses one group definition like "drums = 4,5,6 a:0.8 rv:0.3'0.2"
+/// Channels are numbered from 1, as in the file's columns; the tokens
+/// after them are bus effects the group starts with.
+fn parse_group_definition(line: &str, context: &mut ParserContext) {
+    let line = line.trim().trim_end_matches(',').trim();
+    let Some((name, body)) = line.split_once('=') else {
+        return;
+    };
+    let name = name.trim().to_lowercase();
+
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || character == '_')
+    {
+        context.diagnostics.push(ParseError::error(
+            context.current_line,
+            0,
+            line,
+            "Group needs a one-word name, e.g. 'drums = 4,5,6'. Group ignored.".to_string(),
+        ));
+        return;
+    }
+    if context.groups.iter().any(|group| group.name == name) {
+        context.diagnostics.push(ParseError::error(
+            context.current_line,
+            0,
+            line,
+            format!("Group '{}' is defined twice. Keeping the first.", name),
+        ));
+        return;
+    }
+
+    // The channel list may have spaces after its commas
+    let tokens: Vec<&str> = body.split_whitespace().collect();
+    let list_length = tokens
+        .iter()
+        .take_while(|token| token.chars().all(|c| c.is_ascii_digit() || c == ','))
+        .count();
+    let mut channels = Vec::new();
+    for number in tokens[..list_length]
+        .join("")
+        .split(',')
+        .filter(|number| !number.is_empty())
+    {
+        let channel = number.parse::<usize>().unwrap_or(0);
+        if channel == 0 || channel > context.channel_count {
+            context.diagnostics.push(ParseError::error(
+                context.current_line,
+                0,
+                number,
+                format!(
+                    "Group '{}' names channel {}, but channels go from 1 to {}. Channel ignored.",
+                    name, number, context.channel_count
+                ),
+            ));
+            continue;
         }
-        "v" | "vibrato" => {
-            if params.len() >= 2 {
-                effects.vibrato_rate_hz = params[0].max(0.0);
-                effects.vibrato_depth_semitones = params[1].max(0.0);
-            }
+        let channel_index = channel - 1;
+        if let Some(other) = context
+            .groups
+            .iter()
+            .find(|group| group.channels.contains(&channel_index))
+        {
+            context.diagnostics.push(ParseError::error(
+                context.current_line,
+                0,
+                number,
+                format!(
+                    "Channel {} is already in group '{}'. A channel can be in one group.",
+                    channel, other.name
+                ),
+            ));
+            continue;
         }
-        "t" | "tremolo" => {
-            if params.len() >= 2 {
-                effects.tremolo_rate_hz = params[0].max(0.0);
-                effects.tremolo_depth = params[1].clamp(0.0, 1.0);
-            }
+        if !channels.contains(&channel_index) {
+            channels.push(channel_index);
         }
-        "b" | "bitcrush" => {
-            if !params.is_empty() {
-                effects.bitcrush_bits = (params[0] as u8).clamp(1, 16);
-            }
+    }
+    if channels.is_empty() {
+        context.diagnostics.push(ParseError::warning(
+            context.current_line,
+            0,
+            line,
+            format!("Group '{}' has no channels.", name),
+        ));
+    }
+
+    let mut effects = Vec::new();
+    let mut seen_effects = HashSet::new();
+    for token in &tokens[list_length..] {
+        let parsed = token.split_once(':').is_some_and(|(effect_name, value)| {
+            parse_bus_effect(
+                &effect_name.to_lowercase(),
+                value,
+                token,
+                &mut seen_effects,
+                &mut effects,
+                context,
+            )
+        });
+        if !parsed {
+            context.diagnostics.push(ParseError::error(
+                context.current_line,
+                0,
+                token,
+                format!(
+                    "'{}' is not a group effect. Use: a, p, rv, rv2, dl, ch, wet",
+                    token
+                ),
+            ));
         }
-        "d" | "distortion" => {
-            if !params.is_empty() {
-                effects.distortion_amount = params[0].clamp(0.0, 1.0);
-            }
+    }
+
+    debug!(
+        target: "parser",
+        "Line {}: Group '{}' = channels {:?}",
+        context.current_line,
+        name,
+        channels
+    );
+    context.groups.push(ChannelGroup {
+        name,
+        channels,
+        effects,
+    });
+}
+
+/// Parses song defaults like "release = 1.0" from a [config] block
+/// A line can hold several, separated by commas ("missing = sustain,
+/// release = 1.0"). The settings apply to the rows after them.
+fn parse_config_definition(line: &str, context: &mut ParserContext) {
+    for setting in line.split(',').map(str::trim) {
+        if !setting.is_empty() {
+            parse_config_setting(setting, context);
         }
-        "ch" | "chorus" => {
-            if !params.is_empty() {
-                effects.chorus_mix = params[0].clamp(0.0, 1.0);
-            }
-            if params.len() > 1 {
-                effects.chorus_rate_hz = params[1].clamp(0.1, 5.0);
+    }
+}
+
+/// Parses one song default like "release = 1.0"
+fn parse_config_setting(line: &str, context: &mut ParserContext) {
+    let Some((name, value)) = line.split_once('=') else {
+        context.diagnostics.push(ParseError::error(
+            context.current_line,
+            0,
+            line,
+            format!(
+                "[config] setting '{}' needs a value, like 'release = 1.0'. Setting ignored.",
+                line
+            ),
+        ));
+        return;
+    };
+    let name = name.trim().to_lowercase();
+    let value = value.trim();
+    let seconds = value
+        .parse::<f32>()
+        .ok()
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0);
+
+    let problem = match name.as_str() {
+        "missing" | "missing_cells" => match MissingCellBehavior::from_name(value) {
+            Some(behavior) => {
+                context.missing_cell_behavior = behavior;
+                None
             }
-            if params.len() > 2 {
-                effects.chorus_depth_ms = params[2].clamp(0.5, 10.0);
+            None => Some(format!(
+                "Unknown missing-cell behavior '{}'. Use 'sustain' or 'release'.",
+                value
+            )),
+        },
+        "attack" => match seconds {
+            Some(seconds) => {
+                context.default_attack_seconds = Some(seconds.min(30.0));
+                None
             }
-            if params.len() > 3 {
-                effects.chorus_feedback = params[3].clamp(0.0, 0.9);
+            None => Some(format!(
+                "Invalid attack time '{}'. Use seconds, e.g. 0.05.",
+                value
+            )),
+        },
+        "release" => match seconds {
+            Some(seconds) => {
+                context.default_release_seconds = Some(seconds.min(30.0));
+                None
             }
+            None => Some(format!(
+                "Invalid release time '{}'. Use seconds, e.g. 1.0.",
+                value
+            )),
+        },
+        _ => Some(format!(
+            "Unknown [config] setting '{}'. Use missing, attack, or release.",
+            name
+        )),
+    };
+
+    match problem {
+        Some(message) => context.diagnostics.push(ParseError::error(
+            context.current_line,
+            0,
+            line,
+            format!("{} Setting ignored.", message),
+        )),
+        None => {
+            debug!(target: "parser", "Line {}: Song default {} = {}", context.current_line, name, value)
         }
-        "tr" | "transition" => {
-            if !params.is_empty() {
-                *transition_seconds = params[0].max(0.0);
-            }
+    }
+}
+
+/// Parses one preset definition like "lead = square:0.25 d:0.2"
+/// Presets defined earlier can be used inside later ones. A line naming
+/// an instrument ("square = dt:6") sets that instrument's default effects.
+fn parse_preset_definition(line: &str, context: &mut ParserContext) {
+    let line = line.trim().trim_end_matches(',').trim();
+    let Some((name, body)) = line.split_once('=') else {
+        return;
+    };
+
+    let body_tokens: Vec<&str> = body.split_whitespace().collect();
+    match find_instrument_by_name(name.trim()) {
+        Some(instrument_id) if instrument_id != 0 => {
+            set_instrument_default_effects(instrument_id, &body_tokens, line, context)
         }
-        "cl" | "clear" => {
-            *clear_effects = true;
-            if !params.is_empty() {
-                *transition_seconds = params[0].max(0.0);
+        _ => register_preset(name, &body_tokens, line, context),
+    }
+}
+
+/// Sets the effects an instrument's notes start with, for the rest of the
+/// song (an empty list turns the instrument's own defaults off)
+fn set_instrument_default_effects(
+    instrument_id: usize,
+    tokens: &[&str],
+    source: &str,
+    context: &mut ParserContext,
+) {
+    // Only effect settings: no instrument, envelope, transition, or clear
+    if let Some(token) = tokens.iter().find(|token| !is_default_effect_token(token)) {
+        context.diagnostics.push(ParseError::error(
+            context.current_line,
+            0,
+            source,
+            format!(
+                "'{}' can't be a default effect (use effects like 'dt:6 d:0.05'). Defaults ignored.",
+                token
+            ),
+        ));
+        return;
+    }
+
+    // Report bad values now rather than on every note
+    let errors_before = context.diagnostics.count(Severity::Error);
+    let mut velocity_mappings = Vec::new();
+    let mut effect_tokens = Vec::new();
+    for token in tokens {
+        match token.split_once(':') {
+            Some((prefix, value_str)) if is_velocity_token(&prefix.to_lowercase()) => {
+                read_velocity_token(token, value_str, &mut velocity_mappings, context);
             }
+            _ => effect_tokens.push(*token),
         }
-        _ => {
-            // Unknown effect - ignore (error already reported if needed)
-        }
     }
+    parse_effect_tokens(&effect_tokens, context);
+    if context.diagnostics.count(Severity::Error) > errors_before {
+        return;
+    }
+
+    debug!(
+        target: "parser",
+        "Line {}: Default effects for instrument {} = {}",
+        context.current_line,
+        instrument_id,
+        tokens.join(" ")
+    );
+    context.instrument_default_effects.insert(
+        instrument_id,
+        tokens.iter().map(|token| token.to_string()).collect(),
+    );
 }
 
-/// Parses a parameter list like "0.5'0.3" into [0.5, 0.3]
-fn parse_parameter_list(params_str: &str) -> Vec<f32> {
-    params_str
-        .split('\'')
-        .filter_map(|s| s.parse::<f32>().ok())
+/// Whether a token can be one of an instrument's default effects
+fn is_default_effect_token(token: &str) -> bool {
+    let Some((prefix, _)) = token.split_once(':') else {
+        return false;
+    };
+    let prefix = prefix.to_lowercase();
+    is_effect_name(&prefix)
+        && !matches!(
+            prefix.as_str(),
+            "tr" | "transition" | "cl" | "clear" | "env" | "envelope" | "seed"
+        )
+}
+
+/// The default effects of an instrument (the song's, else its own) that
+/// a cell doesn't set itself, to be read before the cell's tokens
+fn instrument_default_effect_tokens(
+    instrument_id: usize,
+    cell_tokens: &[&str],
+    context: &ParserContext,
+) -> Vec<String> {
+    let defaults: Vec<&str> = match context.instrument_default_effects.get(&instrument_id) {
+        Some(tokens) => tokens.iter().map(String::as_str).collect(),
+        None => get_instrument_by_id(instrument_id)
+            .map(|instrument| instrument.default_effects.split_whitespace().collect())
+            .unwrap_or_default(),
+    };
+    let own_settings: HashSet<String> =
+        cell_tokens.iter().map(|token| setting_key(token)).collect();
+    defaults
+        .into_iter()
+        .filter(|token| !own_settings.contains(&setting_key(token)))
+        .map(str::to_string)
         .collect()
 }
 
-/// Checks if an effect name is a master-only effect
-fn is_master_effect(token: &str) -> bool {
-    let token_lower = token.to_lowercase();
+/// Loads every instrument from a TOML instrument file as a preset
+fn load_instrument_file_presets(path: &str, context: &mut ParserContext) {
+    match load_instrument_file(path) {
+        Ok(instruments) => {
+            debug!(
+                target: "parser",
+                "Line {}: Loaded {} instruments from '{}'",
+                context.current_line,
+                instruments.len(),
+                path
+            );
+            for instrument in instruments {
+                let tokens = instrument.to_tokens();
+                let token_refs: Vec<&str> = tokens.iter().map(String::as_str).collect();
+                register_preset(&instrument.name, &token_refs, path, context);
+
+                let Some(sample_path) = &instrument.sample else {
+                    continue;
+                };
+                match load_sample(sample_path) {
+                    Ok(index) => {
+                        context
+                            .preset_samples
+                            .insert(instrument.name.clone(), index);
+                    }
+                    Err(message) => {
+                        context.diagnostics.push(ParseError::error(
+                            context.current_line,
+                            0,
+                            path,
+                            format!(
+                                "{}. Instrument '{}' plays the [sample] sound instead.",
+                                message, instrument.name
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+        Err(message) => {
+            context.diagnostics.push(ParseError::error(
+                context.current_line,
+                0,
+                path,
+                format!("{}. Instruments not loaded.", message),
+            ));
+        }
+    }
+}
 
-    // Check for effects that are master-only when they appear first
-    if let Some(colon_pos) = token.find(':') {
-        let effect_name = &token_lower[..colon_pos];
-        matches!(
-            effect_name,
-            "rv" | "reverb" | "rv2" | "reverb2" | "dl" | "delay"
-        )
+/// Adds a named preset after checking that its name can't be mistaken for
+/// anything else. Presets already defined are expanded inside the new one.
+fn register_preset(name: &str, tokens: &[&str], source: &str, context: &mut ParserContext) {
+    let name = name.trim().to_lowercase();
+
+    // The name must be one word that can't be mistaken for anything else
+    let reason = if name.is_empty()
+        || !name
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || character == '_')
+    {
+        Some("must be a single word (letters, digits, _)")
+    } else if find_instrument_by_name(&name).is_some() {
+        Some("is already an instrument name")
+    } else if parse_pitch_name(&name).is_some() {
+        Some("looks like a note")
+    } else if matches!(name.as_str(), "clear" | "cl") {
+        Some("is a reserved word")
     } else {
-        false
+        None
+    };
+    if let Some(reason) = reason {
+        context.diagnostics.push(ParseError::error(
+            context.current_line,
+            0,
+            source,
+            format!("Preset name '{}' {}. Preset ignored.", name, reason),
+        ));
+        return;
     }
+
+    let expanded = expand_presets(tokens, &context.instrument_presets)
+        .unwrap_or_else(|| tokens.iter().map(|token| token.to_string()).collect());
+
+    // A preset built on one with its own sample plays that sample too
+    match preset_sample(tokens, context) {
+        Some(index) => context.preset_samples.insert(name.clone(), index),
+        None => context.preset_samples.remove(&name),
+    };
+
+    debug!(
+        target: "parser",
+        "Line {}: Preset '{}' = {}",
+        context.current_line,
+        name,
+        expanded.join(" ")
+    );
+    context.instrument_presets.insert(name, expanded);
 }
 
-// ============================================================================
-// UNIT TESTS
-// ============================================================================
+/// Sample of the first preset among the tokens that has its own sample
+fn preset_sample(tokens: &[&str], context: &ParserContext) -> Option<usize> {
+    tokens
+        .iter()
+        .find_map(|token| context.preset_samples.get(&token.to_lowercase()).copied())
+}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// The setting a token changes, for telling when a cell's own token
+/// replaces one from a preset or the instrument's defaults: the effect
+/// name, or for velocity mappings the name and target ("vel:d")
+fn setting_key(token: &str) -> String {
+    let (prefix, value_str) = token.split_once(':').unwrap_or((token, ""));
+    let prefix = prefix.to_lowercase();
+    if is_velocity_token(&prefix) {
+        let target = value_str.split('\'').next().unwrap_or("");
+        match VelocityTarget::from_name(target) {
+            Some(target) => format!("vel:{:?}", target),
+            None => format!("vel:{}", target.to_lowercase()),
+        }
+    } else {
+        prefix
+    }
+}
 
-    #[test]
-    fn test_strip_comments() {
-        assert_eq!(strip_comments("test // comment"), "test ");
-        assert_eq!(strip_comments("c#4 sine // sharp note"), "c#4 sine ");
-        assert_eq!(strip_comments("# full comment"), "");
+/// Replaces preset names in a cell's tokens with the tokens they stand for
+/// Tokens written in the cell itself win over the same setting in a preset,
+/// so "c4 lead a:0.5" uses the preset but with a different amplitude.
+/// Returns None if the cell doesn't use any presets.
+fn expand_presets(tokens: &[&str], presets: &HashMap<String, Vec<String>>) -> Option<Vec<String>> {
+    if presets.is_empty()
+        || !tokens
+            .iter()
+            .any(|token| presets.contains_key(&token.to_lowercase()))
+    {
+        return None;
     }
 
-    #[test]
-    fn test_parse_parameter_list() {
-        assert_eq!(parse_parameter_list("0.5"), vec![0.5]);
-        assert_eq!(parse_parameter_list("0.5'0.3"), vec![0.5, 0.3]);
-        assert_eq!(parse_parameter_list("1'2'3"), vec![1.0, 2.0, 3.0]);
+    // What each token sets: "instrument" for instruments, else the effect
+    let setting_of = |token: &str| -> String {
+        let prefix = token.split(':').next().unwrap_or(token).to_lowercase();
+        if find_instrument_by_name(&prefix).is_some() {
+            "instrument".to_string()
+        } else {
+            setting_key(token)
+        }
+    };
+    let own_settings: HashSet<String> = tokens
+        .iter()
+        .filter(|token| !presets.contains_key(&token.to_lowercase()))
+        .map(|token| setting_of(token))
+        .collect();
+
+    let mut expanded = Vec::new();
+    for token in tokens {
+        match presets.get(&token.to_lowercase()) {
+            Some(preset_tokens) => expanded.extend(
+                preset_tokens
+                    .iter()
+                    .filter(|preset_token| !own_settings.contains(&setting_of(preset_token)))
+                    .cloned(),
+            ),
+            None => expanded.push(token.to_string()),
+        }
     }
+    Some(expanded)
+}
 
-    #[test]
-    fn test_effect_only_change_not_parsed_as_note() {
-        // "a:0.4" should be parsed as an effect change, not as a note trigger
-        // This tests the fix for cells like "a:0.4" being incorrectly treated as notes
-        use crate::helper::FrequencyTable;
+// ============================================================================
+// COMMENT STRIPPING
+// ============================================================================
 
-        let freq_table = FrequencyTable::new();
-        let mut context = ParserContext {
-            frequency_table: &freq_table,
-            current_line: 1,
-            current_column: 0,
-            errors: Vec::new(),
-            missing_cell_behavior: MissingCellBehavior::SlowRelease,
+/// Removes comments from a line
+/// Supports // comments and # comments (but preserves # in sharp notes like c#4)
+pub(crate) fn strip_comments(line: &str) -> &str {
+    // Handle // comments (always a comment)
+    if let Some(slash_position) = line.find("//") {
+        return &line[..slash_position];
+    }
+
+    // Handle # comments (but preserve sharp notes)
+    if let Some(hash_position) = line.find('#') {
+        // Check if the # is a sharp note modifier
+        let is_sharp_note = if hash_position > 0 {
+            let char_before = line.as_bytes()[hash_position - 1] as char;
+            matches!(char_before.to_ascii_lowercase(), 'a'..='g')
+        } else {
+            false
         };
 
-        // "a:0.4" should be ChangeEffects (amplitude change), not TriggerNote
-        let action = parse_cell("a:0.4", &mut context);
-        assert!(
-            matches!(action, CellAction::ChangeEffects { .. }),
-            "a:0.4 should be parsed as ChangeEffects, not as a note"
-        );
+        if !is_sharp_note {
+            return &line[..hash_position];
+        }
+    }
 
-        // "a4 sine" should still be TriggerNote
-        let action2 = parse_cell("a4 sine", &mut context);
-        assert!(
-            matches!(action2, CellAction::TriggerNote { .. }),
+    line
+}
+
+// ============================================================================
+// CELL PARSING
+// ============================================================================
+
+/// Parses a single cell into a CellAction
+fn parse_cell(cell: &str, context: &mut ParserContext) -> CellAction {
+    // Expand "@name" macros first; the result is parsed like any other cell
+    let expanded_cell = expand_macros(cell, context);
+    let cell = expanded_cell.as_deref().unwrap_or(cell).trim();
+
+    // Empty cell = Slow Release
+    if cell.is_empty() {
+        return CellAction::SlowRelease;
+    }
+
+    // Sustain (plain hyphen)
+    if cell == "-" {
+        return CellAction::Sustain;
+    }
+
+    // Fast Release (period)
+    if cell == "." {
+        return CellAction::FastRelease;
+    }
+
+    // Split into tokens (handles multiple spaces)
+    let mut tokens: Vec<&str> = cell.split_whitespace().collect();
+
+    // Expand named presets from [instruments] blocks
+    context.cell_sample = preset_sample(&tokens, context);
+    let expanded_tokens = expand_presets(&tokens, &context.instrument_presets);
+    if let Some(expanded) = &expanded_tokens {
+        tokens = expanded.iter().map(String::as_str).collect();
+    }
+
+    if tokens.is_empty() {
+        return CellAction::SlowRelease;
+    }
+
+    // Check for sustain with effects: "- a:0.5"
+    if tokens[0] == "-" && tokens.len() > 1 {
+        return parse_sustain_with_effects(&tokens[1..], context);
+    }
+
+    // Determine what kind of cell this is by looking at the first token
+    let first_token = tokens[0];
+    let Some(first_char) = first_token.chars().next() else {
+        return CellAction::SlowRelease;
+    };
+    let first_char = first_char.to_ascii_lowercase();
+
+    // Group bus command: "group drums a:0.5" (checked before notes, since
+    // "group" starts with a note letter)
+    if first_token.eq_ignore_ascii_case("group") {
+        return parse_group_effects(&tokens, context);
+    }
+
+    // Is it a note? (starts with a-g)
+    // But NOT if it contains a colon - that's an effect like "a:0.4", not a note like "a4"
+    // (nor a custom instrument whose name starts with a note letter, like "bell")
+    let is_note = matches!(first_char, 'a'..='g')
+        && !first_token.contains(':')
+        && find_instrument_by_name(first_token).is_none();
+
+    if is_note {
+        // Note trigger: "c4 sine a:0.8"
+        return parse_note_trigger(&tokens, context);
+    }
+
+    // Check if first token is an instrument name (pitchless instruments
+    // may carry parameters here, like "input:1")
+    let instrument_lookup = find_instrument_by_name(first_token).or_else(|| {
+        let (name, _) = first_token.split_once(':')?;
+        find_instrument_by_name(name).filter(|&id| id != 0 && !instrument_requires_pitch(id))
+    });
+    if let Some(instrument_id) = instrument_lookup {
+        if instrument_id == 0 {
+            // Instrument 0 = master bus effects
+            return parse_master_effects(&tokens, context);
+        } else {
+            // Check if this instrument requires a pitch
+            if let Some(instrument) = get_instrument_by_id(instrument_id) {
+                if !instrument.requires_pitch {
+                    // Pitchless instrument (like noise)
+                    return parse_pitchless_trigger(&tokens, context);
+                } else {
+                    // Requires pitch but none given
+                    context.diagnostics.push(ParseError::error(
+                        context.current_line,
+                        context.current_column,
+                        cell,
+                        format!(
+                            "Instrument '{}' requires a note (e.g., 'c4 {}')",
+                            instrument.name, instrument.name
+                        ),
+                    ));
+                    return CellAction::SlowRelease;
+                }
+            }
+        }
+    }
+
+    // Check if it's a master-only effect
+    if is_master_effect(first_token) {
+        return parse_master_effects(&tokens, context);
+    }
+
+    // Otherwise, it's a channel effect change
+    parse_effect_change(&tokens, context)
+}
+
+/// Parses "- a:0.5 tr:2" (sustain with effect changes)
+fn parse_sustain_with_effects(tokens: &[&str], context: &mut ParserContext) -> CellAction {
+    let (effects, transition_seconds, clear_first) = parse_effect_tokens(tokens, context);
+
+    CellAction::SustainWithEffects {
+        effects,
+        transition_seconds,
+        clear_first,
+    }
+}
+
+/// Applies the declared scale (if any) to a note
+/// Snap mode returns the nearest scale note; warn mode reports the note
+/// and returns it unchanged
+fn apply_scale(pitch: PitchName, pitch_text: &str, context: &mut ParserContext) -> PitchName {
+    let Some(scale) = &context.scale else {
+        return pitch;
+    };
+    if scale.contains(&pitch) {
+        return pitch;
+    }
+
+    match scale.mode {
+        ScaleMode::Snap => scale.snap(&pitch, &context.tuning),
+        ScaleMode::Warn => {
+            context.diagnostics.push(ParseError::warning(
+                context.current_line,
+                context.current_column,
+                pitch_text,
+                format!(
+                    "Note '{}' is outside the {} scale",
+                    pitch_text, scale.display_name
+                ),
+            ));
+            pitch
+        }
+    }
+}
+
+/// Parses a note trigger like "c4 sine a:0.8"
+fn parse_note_trigger(tokens: &[&str], context: &mut ParserContext) -> CellAction {
+    let pitch = tokens[0].to_string();
+
+    // Check the note against the declared scale, then look up its
+    // frequency in the current tuning
+    let pitch_name = parse_pitch_name(&pitch).map(|name| apply_scale(name, &pitch, context));
+    let frequency_hz = match pitch_name.and_then(|name| {
+        context
+            .tuning
+            .pitch_to_frequency(&name, context.frequency_table)
+    }) {
+        Some(freq) => freq,
+        None => {
+            context.diagnostics.push(ParseError::error(
+                context.current_line,
+                context.current_column,
+                &pitch,
+                format!("Invalid pitch '{}'. Using A4 (440 Hz).", pitch),
+            ));
+            440.0
+        }
+    };
+
+    // Apply the song-wide transpose (100 cents per semitone)
+    let frequency_hz = if context.transpose_semitones != 0.0 {
+        frequency_hz * cents_to_frequency_ratio(context.transpose_semitones * 100.0)
+    } else {
+        frequency_hz
+    };
+
+    let mut instrument_id = 1; // Default to sine
+    let mut instrument_parameters: Vec<f32> = Vec::new();
+    let mut envelope: Option<EnvelopeShape> = None;
+    let mut seed: Option<u32> = None;
+    let mut velocity_mappings: Vec<VelocityMapping> = Vec::new();
+    let mut seen_effects: HashSet<String> = HashSet::new();
+
+    // First pass: find clear flag and instrument
+    let mut clear_effects = false;
+    for token in &tokens[1..] {
+        let token_lower = token.to_lowercase();
+        if token_lower == "clear" || token_lower == "cl" {
+            clear_effects = true;
+        }
+
+        // Check for instrument (without colon)
+        if !token.contains(':')
+            && let Some(id) = find_instrument_by_name(token)
+        {
+            if id == 0 {
+                context.diagnostics.push(ParseError::error(
+                    context.current_line,
+                    context.current_column,
+                    token,
+                    "Cannot play notes on 'master'. Use a playable instrument.".to_string(),
+                ));
+                return CellAction::SlowRelease;
+            }
+            instrument_id = id;
+        }
+    }
+
+    // Second pass: parse instrument params and effects, starting with the
+    // instrument's default effects (the cell's own come after, and win)
+    let mut effects = ChannelEffectState::default();
+    effects.initialize_chorus_buffer(48000); // Will be re-initialized if needed
+    let mut transition_seconds = 0.0;
+    let default_tokens = instrument_default_effect_tokens(instrument_id, &tokens[1..], context);
+
+    for token in default_tokens
+        .iter()
+        .map(String::as_str)
+        .chain(tokens[1..].iter().copied())
+    {
+        let token_lower = token.to_lowercase();
+
+        // Skip clear token
+        if token_lower == "clear" || token_lower == "cl" {
+            continue;
+        }
+
+        // Skip if it's a standalone instrument name (already handled)
+        if !token.contains(':') {
+            if find_instrument_by_name(token).is_some() {
+                continue;
+            }
+            // Unknown standalone token
+            context.diagnostics.push(ParseError::error(
+                context.current_line,
+                context.current_column,
+                token,
+                format!("Unrecognized token '{}' - ignoring", token),
+            ));
+            continue;
+        }
+
+        // Parse colon-separated token
+        if let Some(colon_pos) = token.find(':') {
+            let prefix = &token[..colon_pos].to_lowercase();
+            let value_str = &token[colon_pos + 1..];
+
+            // Check if it's an instrument with parameters (e.g., "trisaw:0.5")
+            if let Some(id) = find_instrument_by_name(prefix) {
+                if id == 0 {
+                    context.diagnostics.push(ParseError::error(
+                        context.current_line,
+                        context.current_column,
+                        token,
+                        "Cannot play notes on 'master'.".to_string(),
+                    ));
+                    return CellAction::SlowRelease;
+                }
+                instrument_id = id;
+                instrument_parameters = parse_parameter_list(value_str);
+                continue;
+            }
+
+            // Per-note envelope: "env:attack'decay'sustain'release"
+            if is_envelope_token(prefix) {
+                if let Some(shape) = parse_envelope_token(token, value_str, context) {
+                    envelope = Some(shape);
+                }
+                continue;
+            }
+
+            // Random seed for this trigger: "seed:7"
+            if prefix == "seed" {
+                seed = parse_seed_token(token, value_str, context);
+                continue;
+            }
+
+            // Velocity mapping: "vel:d'0.5"
+            if is_velocity_token(prefix) {
+                read_velocity_token(token, value_str, &mut velocity_mappings, context);
+                continue;
+            }
+
+            // It's an effect
+            if seen_effects.contains(prefix) {
+                context.diagnostics.push(ParseError::warning(
+                    context.current_line,
+                    context.current_column,
+                    token,
+                    format!("Effect '{}' specified multiple times - using first", prefix),
+                ));
+                continue;
+            }
+            seen_effects.insert(prefix.clone());
+
+            if let Err(message) = apply_effect_token(
+                prefix,
+                value_str,
+                &mut effects,
+                &mut transition_seconds,
+                &mut clear_effects,
+            ) {
+                context.diagnostics.push(ParseError::error(
+                    context.current_line,
+                    context.current_column,
+                    token,
+                    message,
+                ));
+            }
+        }
+    }
+
+    // Grain notes carry the sample they play as their first parameter
+    // (their preset's own sample, else the last [sample] file)
+    if instrument_id == GRAIN_INSTRUMENT_ID {
+        let Some(sample_index) = context.cell_sample.or(context.grain_sample) else {
+            context.diagnostics.push(ParseError::error(
+                context.current_line,
+                context.current_column,
+                tokens[0],
+                "'grain' needs a sound: add a '[sample] file.wav' line above it".to_string(),
+            ));
+            return CellAction::SlowRelease;
+        };
+        instrument_parameters.insert(0, sample_index as f32);
+    }
+
+    let envelope = envelope.or_else(|| default_trigger_envelope(instrument_id, context));
+    let envelope = apply_velocity_mappings(&velocity_mappings, &mut effects, envelope, context);
+
+    CellAction::TriggerNote {
+        frequency_hz,
+        instrument_id,
+        instrument_parameters,
+        effects,
+        transition_seconds,
+        clear_effects,
+        envelope,
+        seed,
+    }
+}
+
+/// Parses a pitchless instrument trigger like "noise a:0.5" or "input:1"
+fn parse_pitchless_trigger(tokens: &[&str], context: &mut ParserContext) -> CellAction {
+    let (instrument_name, parameter_text) = tokens[0].split_once(':').unwrap_or((tokens[0], ""));
+    let instrument_id = find_instrument_by_name(instrument_name).unwrap_or(4); // Default to noise
+    let instrument_parameters = parse_parameter_list(parameter_text);
+
+    // Pull out the envelope, seed, and velocity tokens; everything else is
+    // an effect (after the instrument's default effects, so the cell's own win)
+    let mut envelope: Option<EnvelopeShape> = None;
+    let mut seed: Option<u32> = None;
+    let mut velocity_mappings: Vec<VelocityMapping> = Vec::new();
+    let default_tokens = instrument_default_effect_tokens(instrument_id, &tokens[1..], context);
+    let mut effect_tokens: Vec<&str> = Vec::new();
+    for token in default_tokens
+        .iter()
+        .map(String::as_str)
+        .chain(tokens[1..].iter().copied())
+    {
+        match token.split_once(':') {
+            Some((prefix, value_str)) if is_envelope_token(&prefix.to_lowercase()) => {
+                if let Some(shape) = parse_envelope_token(token, value_str, context) {
+                    envelope = Some(shape);
+                }
+            }
+            Some((prefix, value_str)) if prefix.eq_ignore_ascii_case("seed") => {
+                seed = parse_seed_token(token, value_str, context);
+            }
+            Some((prefix, value_str)) if is_velocity_token(&prefix.to_lowercase()) => {
+                read_velocity_token(token, value_str, &mut velocity_mappings, context);
+            }
+            _ => effect_tokens.push(token),
+        }
+    }
+    let (mut effects, transition_seconds, clear_effects) =
+        parse_effect_tokens(&effect_tokens, context);
+    let envelope = envelope.or_else(|| default_trigger_envelope(instrument_id, context));
+    let envelope = apply_velocity_mappings(&velocity_mappings, &mut effects, envelope, context);
+
+    CellAction::TriggerPitchless {
+        instrument_id,
+        instrument_parameters,
+        effects,
+        transition_seconds,
+        clear_effects,
+        envelope,
+        seed,
+    }
+}
+
+/// Reads the value of a "seed:N" token (a whole number from 0 to 4294967295)
+fn parse_seed_token(token: &str, value_str: &str, context: &mut ParserContext) -> Option<u32> {
+    match value_str.trim().parse::<u32>() {
+        Ok(seed) => Some(seed),
+        Err(_) => {
+            context.diagnostics.push(ParseError::error(
+                context.current_line,
+                context.current_column,
+                token,
+                format!(
+                    "Invalid seed '{}' - use a whole number from 0 to 4294967295",
+                    value_str
+                ),
+            ));
+            None
+        }
+    }
+}
+
+/// The envelope an instrument gives notes without an env: token
+fn instrument_default_envelope(instrument_id: usize) -> Option<EnvelopeShape> {
+    get_instrument_by_id(instrument_id).and_then(|instrument| instrument.default_envelope)
+}
+
+/// Reads an "env:" token's value, reporting values that aren't finite
+/// (inf or NaN would pass through the envelope's clamping into the audio)
+fn parse_envelope_token(
+    token: &str,
+    value_str: &str,
+    context: &mut ParserContext,
+) -> Option<EnvelopeShape> {
+    if parse_parameter_list(value_str)
+        .iter()
+        .any(|value| !value.is_finite())
+    {
+        context.diagnostics.push(ParseError::error(
+            context.current_line,
+            context.current_column,
+            token,
+            format!(
+                "Invalid envelope '{}' - attack, decay, sustain, and release must be finite numbers",
+                value_str
+            ),
+        ));
+        return None;
+    }
+    Some(parse_envelope_value(value_str, context))
+}
+
+/// Reads an "env:" value; times it leaves out come from the song's
+/// [config] defaults, then from the default envelope
+fn parse_envelope_value(value_str: &str, context: &ParserContext) -> EnvelopeShape {
+    let params = parse_parameter_list(value_str);
+    let mut shape = EnvelopeShape::from_parameters(&params);
+    if params.is_empty()
+        && let Some(attack) = context.default_attack_seconds
+    {
+        shape.attack_time_seconds = attack;
+    }
+    if shape.release_time_seconds.is_none() {
+        shape.release_time_seconds = context.default_release_seconds;
+    }
+    shape
+}
+
+/// The envelope for a trigger without an env: token: the instrument's
+/// own, else the song's [config] defaults (None: the engine's default)
+fn default_trigger_envelope(
+    instrument_id: usize,
+    context: &ParserContext,
+) -> Option<EnvelopeShape> {
+    match instrument_default_envelope(instrument_id) {
+        Some(mut shape) => {
+            if shape.release_time_seconds.is_none() {
+                shape.release_time_seconds = context.default_release_seconds;
+            }
+            Some(shape)
+        }
+        None if context.default_attack_seconds.is_some()
+            || context.default_release_seconds.is_some() =>
+        {
+            Some(parse_envelope_value("", context))
+        }
+        None => None,
+    }
+}
+
+/// Parses effect-only changes like "a:0.5 p:-0.3"
+fn parse_effect_change(tokens: &[&str], context: &mut ParserContext) -> CellAction {
+    let (effects, transition_seconds, clear_first) = parse_effect_tokens(tokens, context);
+
+    CellAction::ChangeEffects {
+        effects,
+        transition_seconds,
+        clear_first,
+    }
+}
+
+/// Parses master bus effects
+fn parse_master_effects(tokens: &[&str], context: &mut ParserContext) -> CellAction {
+    // Determine starting index (skip "master" if present)
+    let start_index = if find_instrument_by_name(tokens[0]).is_some() {
+        1
+    } else {
+        0
+    };
+    let effect_tokens = &tokens[start_index..];
+
+    let mut should_clear = false;
+    let mut transition_seconds = 0.0;
+    let mut master_effects: Vec<(String, Vec<f32>)> = Vec::new();
+    let mut seen_effects: HashSet<String> = HashSet::new();
+    let mut new_transpose: Option<f32> = None;
+
+    // First pass: check for clear
+    for token in effect_tokens {
+        let token_lower = token.to_lowercase();
+        if token_lower == "clear"
+            || token_lower == "cl"
+            || token_lower.starts_with("clear:")
+            || token_lower.starts_with("cl:")
+        {
+            should_clear = true;
+
+            // Extract transition time from clear:X
+            if (token_lower.starts_with("clear:") || token_lower.starts_with("cl:"))
+                && let Some(colon_pos) = token.find(':')
+            {
+                let params = parse_parameter_list(&token[colon_pos + 1..]);
+                if !params.is_empty() {
+                    transition_seconds = params[0].max(0.0);
+                }
+            }
+        }
+    }
+
+    // Second pass: parse effects
+    for token in effect_tokens {
+        let token_lower = token.to_lowercase();
+
+        // Skip clear tokens
+        if token_lower == "clear" || token_lower == "cl" {
+            continue;
+        }
+        if token_lower.starts_with("clear:") || token_lower.starts_with("cl:") {
+            continue;
+        }
+
+        if let Some(colon_pos) = token.find(':') {
+            let effect_name = token[..colon_pos].to_lowercase();
+            let value_str = &token[colon_pos + 1..];
+
+            // Handle transition
+            if effect_name == "tr" || effect_name == "transition" {
+                let params = parse_parameter_list(value_str);
+                if !params.is_empty() {
+                    transition_seconds = params[0].max(0.0);
+                }
+                continue;
+            }
+
+            // A4 reference change: applies to every note parsed after this cell
+            if effect_name == "a4" {
+                match parse_reference_frequency(value_str) {
+                    Some(reference) => context.tuning.reference_frequency_hz = reference,
+                    None => context.diagnostics.push(ParseError::error(
+                        context.current_line,
+                        context.current_column,
+                        token,
+                        format!(
+                            "Invalid A4 reference '{}'. Use a frequency from 100 to 1000 Hz.",
+                            value_str
+                        ),
+                    )),
+                }
+                continue;
+            }
+
+            // Tempo change: bpm:140 or bpm:140'8 (beats per minute, rows per beat)
+            // Takes effect from this row on
+            if effect_name == "bpm" || effect_name == "tempo" {
+                let params = parse_parameter_list(value_str);
+                let bpm = params.first().copied().unwrap_or(0.0);
+                let rows_per_beat = params.get(1).copied().unwrap_or(DEFAULT_ROWS_PER_BEAT);
+                if is_valid_tempo(bpm, rows_per_beat) {
+                    master_effects.push(("bpm".to_string(), vec![bpm, rows_per_beat]));
+                } else {
+                    context.diagnostics.push(ParseError::error(
+                        context.current_line,
+                        context.current_column,
+                        token,
+                        format!(
+                            "Invalid tempo '{}'. Use bpm:120 (1-1000 BPM) or bpm:120'8 (with rows per beat).",
+                            value_str
+                        ),
+                    ));
+                }
+                continue;
+            }
+
+            // Fade-out ending: fadeout:8 fades the whole mix to silence
+            // over 8 seconds, and the song ends there
+            if effect_name == "fadeout" {
+                match parse_parameter_list(value_str)
+                    .first()
+                    .and_then(|&seconds| fade_out_length(seconds))
+                {
+                    Some(seconds) => master_effects.push(("fadeout".to_string(), vec![seconds])),
+                    None => context.diagnostics.push(ParseError::error(
+                        context.current_line,
+                        context.current_column,
+                        token,
+                        format!(
+                            "Invalid fade-out '{}'. Use a time in seconds like fadeout:8",
+                            value_str
+                        ),
+                    )),
+                }
+                continue;
+            }
+
+            // Transpose: shifts every note triggered after this cell
+            if effect_name == "transpose" || effect_name == "tp" {
+                match parse_parameter_list(value_str).first() {
+                    Some(semitones) => new_transpose = Some(semitones.clamp(-48.0, 48.0)),
+                    None => context.diagnostics.push(ParseError::error(
+                        context.current_line,
+                        context.current_column,
+                        token,
+                        format!(
+                            "Invalid transpose '{}'. Use semitones like transpose:+3",
+                            value_str
+                        ),
+                    )),
+                }
+                continue;
+            }
+
+            if !parse_bus_effect(
+                &effect_name,
+                value_str,
+                token,
+                &mut seen_effects,
+                &mut master_effects,
+                context,
+            ) {
+                context.diagnostics.push(ParseError::error(
+                    context.current_line,
+                    context.current_column,
+                    token,
+                    format!(
+                        "Effect '{}' cannot be applied to master bus. Use: a, p, rv, rv2, dl, ch, wet",
+                        effect_name
+                    ),
+                ));
+            }
+        }
+    }
+
+    // With a transition time, notes that are already sounding glide into
+    // the new key. The engine receives the change in semitones.
+    if let Some(semitones) = new_transpose {
+        let shift = semitones - context.transpose_semitones;
+        context.transpose_semitones = semitones;
+        if transition_seconds > 0.0 && shift != 0.0 {
+            master_effects.push(("transpose".to_string(), vec![shift]));
+        }
+    }
+
+    CellAction::MasterEffects {
+        clear_first: should_clear,
+        transition_seconds,
+        effects: master_effects,
+    }
+}
+
+/// Parses one bus effect (master or group) like "rv:0.5'0.3" or "dl:off"
+/// into the effect list. Returns false if the name isn't a bus effect.
+fn parse_bus_effect(
+    effect_name: &str,
+    value_str: &str,
+    token: &str,
+    seen_effects: &mut HashSet<String>,
+    effects: &mut Vec<(String, Vec<f32>)>,
+    context: &mut ParserContext,
+) -> bool {
+    // Bypass: rv:off fades the reverb out of the mix (rv:on brings it back)
+    let value_lower = value_str.to_lowercase();
+    if value_lower == "off" || value_lower == "on" {
+        let short_name = match effect_name {
+            "rv" | "reverb" => "rv",
+            "rv2" | "reverb2" => "rv2",
+            "dl" | "delay" => "dl",
+            "ch" | "chorus" => "ch",
+            _ => {
+                context.diagnostics.push(ParseError::error(
+                    context.current_line,
+                    context.current_column,
+                    token,
+                    format!(
+                        "Effect '{}' cannot be bypassed. Use: rv, rv2, dl, ch",
+                        effect_name
+                    ),
+                ));
+                return true;
+            }
+        };
+        effects.push((format!("{}:{}", short_name, value_lower), Vec::new()));
+        return true;
+    }
+
+    match effect_name {
+        "rv" | "reverb" | "rv2" | "reverb2" | "dl" | "delay" | "a" | "amplitude" | "p" | "pan"
+        | "ch" | "chorus" | "wet" => {
+            if !seen_effects.insert(effect_name.to_string()) {
+                context.diagnostics.push(ParseError::warning(
+                    context.current_line,
+                    context.current_column,
+                    token,
+                    format!("Bus effect '{}' specified multiple times", effect_name),
+                ));
+                return true;
+            }
+            // inf or NaN would carry through the bus into the whole mix
+            if parse_parameter_list(value_str)
+                .iter()
+                .any(|value| !value.is_finite())
+            {
+                context.diagnostics.push(ParseError::error(
+                    context.current_line,
+                    context.current_column,
+                    token,
+                    format!("Invalid '{}' - bus effect values must be finite numbers", token),
+                ));
+                return true;
+            }
+            if matches!(effect_name, "dl" | "delay") {
+                // A note length ("dl:1/8.'0.4") follows the tempo; the
+                // engine turns its beats into seconds
+                match parse_synced_parameter_list(value_str, 0) {
+                    Ok((mut parameters, Some(beats))) => {
+                        parameters[0] = beats;
+                        effects.push(("dl:sync".to_string(), parameters));
+                    }
+                    Ok((parameters, None)) => effects.push((effect_name.to_string(), parameters)),
+                    Err(message) => context.diagnostics.push(ParseError::error(
+                        context.current_line,
+                        context.current_column,
+                        token,
+                        message,
+                    )),
+                }
+                return true;
+            }
+            effects.push((effect_name.to_string(), parse_parameter_list(value_str)));
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Parses a group bus command like "group drums a:0.5 rv:0.3'0.2 tr:1"
+fn parse_group_effects(tokens: &[&str], context: &mut ParserContext) -> CellAction {
+    let Some(name) = tokens.get(1).map(|name| name.to_lowercase()) else {
+        context.diagnostics.push(ParseError::error(
+            context.current_line,
+            context.current_column,
+            tokens[0],
+            "Group command needs a group name, like 'group drums a:0.5'".to_string(),
+        ));
+        return CellAction::SlowRelease;
+    };
+    // A cell parsed on its own (live input, OSC) has no song to check
+    // against, so the engine ignores names it doesn't know instead
+    let parsing_song = context.channel_count > 0;
+    if parsing_song && !context.groups.iter().any(|group| group.name == name) {
+        context.diagnostics.push(ParseError::error(
+            context.current_line,
+            context.current_column,
+            &name,
+            format!(
+                "Unknown group '{}'. Define it in a [groups] block first.",
+                name
+            ),
+        ));
+        return CellAction::SlowRelease;
+    }
+
+    let mut clear_first = false;
+    let mut transition_seconds = 0.0;
+    let mut effects = Vec::new();
+    let mut seen_effects = HashSet::new();
+    for token in &tokens[2..] {
+        let token_lower = token.to_lowercase();
+        let (effect_name, value_str) = token_lower.split_once(':').unwrap_or((&token_lower, ""));
+        match effect_name {
+            "clear" | "cl" => {
+                clear_first = true;
+                if let Some(seconds) = parse_parameter_list(value_str).first() {
+                    transition_seconds = seconds.max(0.0);
+                }
+            }
+            "tr" | "transition" => {
+                if let Some(seconds) = parse_parameter_list(value_str).first() {
+                    transition_seconds = seconds.max(0.0);
+                }
+            }
+            _ => {
+                if value_str.is_empty()
+                    || !parse_bus_effect(
+                        effect_name,
+                        value_str,
+                        token,
+                        &mut seen_effects,
+                        &mut effects,
+                        context,
+                    )
+                {
+                    context.diagnostics.push(ParseError::error(
+                        context.current_line,
+                        context.current_column,
+                        token,
+                        format!(
+                            "'{}' cannot be applied to a group. Use: a, p, rv, rv2, dl, ch, wet",
+                            token
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    CellAction::GroupEffects {
+        group: name,
+        clear_first,
+        transition_seconds,
+        effects,
+    }
+}
+
+// ============================================================================
+// VELOCITY MAPPING
+// ============================================================================
+//
+// A note's velocity is its a: value (1.0 when the cell doesn't set one).
+// Besides the volume, a "vel:target'amount'curve" token lets it drive
+// another setting, so harder notes can also sound different:
+//
+//   c4 square a:0.9 vel:d'0.5     distortion + 0.5 * 0.9
+//   c4 square a:0.3 vel:d'0.5'2   distortion + 0.5 * 0.3^2 (a curve)
+//   c4 pad vel:attack'-0.2        harder notes start up to 0.2 s sooner
+//
+// The amount is added times velocity^curve (curve 1 is a straight line;
+// above 1 only the hardest notes get much of it). Each target is one
+// token, so a cell or preset can map the velocity to several. Mappings
+// usually live in an [instruments] preset or an instrument's default
+// effects ("square = vel:d'0.4"), so every note of that instrument gets
+// them. They are worked out here, into the note's own settings, so the
+// engine never sees them.
+// ============================================================================
+
+/// A setting the velocity can drive
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum VelocityTarget {
+    /// Distortion amount ("d")
+    Distortion,
+
+    /// Envelope attack time in seconds ("attack")
+    Attack,
+}
+
+impl VelocityTarget {
+    /// Finds a target by the name used in "vel:" tokens
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "d" | "distortion" => Some(VelocityTarget::Distortion),
+            "attack" | "atk" => Some(VelocityTarget::Attack),
+            _ => None,
+        }
+    }
+}
+
+/// One "vel:target'amount'curve" token
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct VelocityMapping {
+    target: VelocityTarget,
+
+    /// Added at full velocity (may be negative)
+    amount: f32,
+
+    /// Exponent applied to the velocity first (1 = straight line)
+    curve: f32,
+}
+
+impl VelocityMapping {
+    /// Reads the value of a "vel:" token, like "d'0.5" or "attack'-0.1'2"
+    fn parse(value_str: &str) -> Result<Self, String> {
+        let (target_name, numbers) = value_str.split_once('\'').unwrap_or((value_str, ""));
+        let target = VelocityTarget::from_name(target_name).ok_or_else(|| {
+            format!(
+                "Velocity can't drive '{}'. Use: d, attack (e.g. vel:d'0.5)",
+                target_name
+            )
+        })?;
+        let params = parse_parameter_list(numbers);
+        let Some(&amount) = params.first() else {
+            return Err(format!(
+                "'vel:{}' needs an amount (e.g. vel:{}'0.5)",
+                target_name, target_name
+            ));
+        };
+        Ok(Self {
+            target,
+            amount,
+            curve: params.get(1).map_or(1.0, |curve| curve.clamp(0.1, 10.0)),
+        })
+    }
+
+    /// What the mapping adds to its target at this velocity
+    fn offset(&self, velocity: f32) -> f32 {
+        self.amount * velocity.clamp(0.0, 1.0).powf(self.curve)
+    }
+}
+
+/// Whether an effect prefix is a velocity mapping
+fn is_velocity_token(prefix: &str) -> bool {
+    matches!(prefix, "vel" | "velocity")
+}
+
+/// Reads a "vel:" token into the cell's mappings (the first one for a
+/// target wins, like other effects given twice)
+fn read_velocity_token(
+    token: &str,
+    value_str: &str,
+    mappings: &mut Vec<VelocityMapping>,
+    context: &mut ParserContext,
+) {
+    match VelocityMapping::parse(value_str) {
+        Ok(mapping) if mappings.iter().any(|other| other.target == mapping.target) => {
+            context.diagnostics.push(ParseError::warning(
+                context.current_line,
+                context.current_column,
+                token,
+                "Velocity target specified multiple times - using first".to_string(),
+            ));
+        }
+        Ok(mapping) => mappings.push(mapping),
+        Err(message) => context.diagnostics.push(ParseError::error(
+            context.current_line,
+            context.current_column,
+            token,
+            message,
+        )),
+    }
+}
+
+/// Applies a trigger's velocity mappings to its effects and envelope
+/// Returns the envelope (one is made from the song's defaults when the
+/// attack is mapped and the note has none).
+fn apply_velocity_mappings(
+    mappings: &[VelocityMapping],
+    effects: &mut ChannelEffectState,
+    mut envelope: Option<EnvelopeShape>,
+    context: &ParserContext,
+) -> Option<EnvelopeShape> {
+    let velocity = effects.amplitude;
+    for mapping in mappings {
+        let offset = mapping.offset(velocity);
+        match mapping.target {
+            VelocityTarget::Distortion => {
+                effects.distortion_amount = (effects.distortion_amount + offset).clamp(0.0, 1.0);
+            }
+            VelocityTarget::Attack => {
+                let shape = envelope.get_or_insert_with(|| parse_envelope_value("", context));
+                shape.attack_time_seconds = (shape.attack_time_seconds + offset).clamp(0.0, 30.0);
+            }
+        }
+    }
+    envelope
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+/// Parses effect tokens and returns (effects, transition_seconds, clear_first)
+fn parse_effect_tokens(
+    tokens: &[&str],
+    context: &mut ParserContext,
+) -> (ChannelEffectState, f32, bool) {
+    let mut effects = ChannelEffectState::default();
+    effects.initialize_chorus_buffer(48000);
+    let mut transition_seconds = 0.0;
+    let mut clear_first = false;
+    let mut seen_effects: HashSet<String> = HashSet::new();
+
+    // First pass: check for clear
+    for token in tokens {
+        let token_lower = token.to_lowercase();
+        if token_lower == "clear" || token_lower == "cl" {
+            clear_first = true;
+            break;
+        }
+    }
+
+    // Second pass: parse effects
+    for token in tokens {
+        let token_lower = token.to_lowercase();
+
+        if token_lower == "clear" || token_lower == "cl" {
+            continue;
+        }
+
+        if let Some(colon_pos) = token.find(':') {
+            let effect_name = token[..colon_pos].to_lowercase();
+            let value_str = &token[colon_pos + 1..];
+
+            if seen_effects.contains(&effect_name) {
+                context.diagnostics.push(ParseError::warning(
+                    context.current_line,
+                    context.current_column,
+                    token,
+                    format!("Effect '{}' specified multiple times", effect_name),
+                ));
+                continue;
+            }
+            seen_effects.insert(effect_name.clone());
+
+            if let Err(message) = apply_effect_token(
+                &effect_name,
+                value_str,
+                &mut effects,
+                &mut transition_seconds,
+                &mut clear_first,
+            ) {
+                context.diagnostics.push(ParseError::error(
+                    context.current_line,
+                    context.current_column,
+                    token,
+                    message,
+                ));
+            }
+        }
+    }
+
+    (effects, transition_seconds, clear_first)
+}
+
+/// Applies an effect token to an effect state
+/// Names that aren't built in are looked up among the registered custom
+/// effects. Returns an error message for an unknown name or a bad chain.
+/// Values outside an effect's range are pulled back into it.
+fn apply_effect_token(
+    effect_name: &str,
+    value_str: &str,
+    effects: &mut ChannelEffectState,
+    transition_seconds: &mut f32,
+    clear_effects: &mut bool,
+) -> Result<(), String> {
+    let result = set_effect_values(
+        effect_name,
+        value_str,
+        effects,
+        transition_seconds,
+        clear_effects,
+    );
+    effects.clamp_to_ranges();
+    result
+}
+
+/// Sets the values an effect token names, as written
+fn set_effect_values(
+    effect_name: &str,
+    value_str: &str,
+    effects: &mut ChannelEffectState,
+    transition_seconds: &mut f32,
+    clear_effects: &mut bool,
+) -> Result<(), String> {
+    if effect_name == "chain" {
+        effects.chain = parse_chain_order(value_str)?;
+        return Ok(());
+    }
+    if effect_name == "vowel" {
+        // The morph heads to the second vowel ("a>o"); see setup_effect_transition
+        effects.vowel = Some(parse_vowels(value_str)?);
+        effects.vowel_morph = 1.0;
+        return Ok(());
+    }
+
+    let params = parse_parameter_list(value_str);
+
+    match effect_name {
+        "a" | "amplitude" => {
+            if !params.is_empty() {
+                effects.amplitude = params[0];
+            }
+        }
+        "p" | "pan" => {
+            if !params.is_empty() {
+                effects.pan = params[0];
+            }
+        }
+        "dt" | "detune" => {
+            if !params.is_empty() {
+                effects.detune_cents = params[0];
+            }
+        }
+        "v" | "vibrato" => {
+            let (params, beats) = parse_synced_parameter_list(value_str, 0)?;
+            if params.len() >= 2 {
+                effects.vibrato_rate_hz = params[0];
+                effects.vibrato_depth_semitones = params[1];
+                effects.synced_rates.vibrato_beats = beats.unwrap_or(0.0);
+                effects.lfo_restarts.vibrato = parse_lfo_restart(value_str)?;
+            }
+        }
+        "t" | "tremolo" => {
+            let (params, beats) = parse_synced_parameter_list(value_str, 0)?;
+            if params.len() >= 2 {
+                effects.tremolo_rate_hz = params[0];
+                effects.tremolo_depth = params[1];
+                effects.synced_rates.tremolo_beats = beats.unwrap_or(0.0);
+                effects.lfo_restarts.tremolo = parse_lfo_restart(value_str)?;
+            }
+        }
+        "b" | "bitcrush" => {
+            if !params.is_empty() {
+                effects.bitcrush_bits = params[0] as u8;
+            }
+        }
+        "sr" | "decimate" => {
+            if !params.is_empty() {
+                effects.decimate_rate_hz = params[0];
+            }
+        }
+        "d" | "distortion" => {
+            if !params.is_empty() {
+                effects.distortion_amount = params[0];
+            }
+        }
+        "q" | "quality" => {
+            if !params.is_empty() {
+                effects.oversampling = nearest_oversampling_factor(params[0]);
+            }
+        }
+        "sub" => {
+            // The level is a number and the shape a name: "sub:0.5'sine"
+            let (level, shape_name) = value_str.split_once('\'').unwrap_or((value_str, ""));
+            if let Some(&level) = parse_parameter_list(level).first() {
+                effects.sub_level = level;
+            }
+            effects.sub_shape = SubOscillatorShape::from_name(shape_name).ok_or_else(|| {
+                format!(
+                    "Unknown sub-oscillator shape '{}'. Use: square, sine",
+                    shape_name
+                )
+            })?;
+        }
+        "wah" => {
+            let (params, beats) = parse_synced_parameter_list(value_str, 0)?;
+            if !params.is_empty() {
+                effects.synced_rates.wah_beats = beats.unwrap_or(0.0);
+                effects.lfo_restarts.wah = parse_lfo_restart(value_str)?;
+                effects.wah_rate_hz = params[0];
+                effects.wah_depth = params.get(1).copied().unwrap_or(0.5);
+                effects.wah_q = params.get(2).copied().unwrap_or(DEFAULT_WAH_Q);
+            }
+        }
+        "click" => {
+            if !params.is_empty() {
+                effects.click_level = params[0];
+            }
+        }
+        "ch" | "chorus" => {
+            let (params, beats) = parse_synced_parameter_list(value_str, 1)?;
+            if !params.is_empty() {
+                effects.chorus_mix = params[0];
+            }
+            if params.len() > 1 {
+                effects.chorus_rate_hz = params[1];
+                effects.synced_rates.chorus_beats = beats.unwrap_or(0.0);
+                effects.lfo_restarts.chorus = parse_lfo_restart(value_str)?;
+            }
+            if params.len() > 2 {
+                effects.chorus_depth_ms = params[2];
+            }
+            if params.len() > 3 {
+                effects.chorus_feedback = params[3];
+            }
+        }
+        "tr" | "transition" => {
+            if !params.is_empty() {
+                *transition_seconds = params[0].max(0.0);
+            }
+        }
+        "cl" | "clear" => {
+            *clear_effects = true;
+            if !params.is_empty() {
+                *transition_seconds = params[0].max(0.0);
+            }
+        }
+        "vel" | "velocity" => {
+            return Err("'vel:' only works in a cell that plays a note - ignoring".to_string());
+        }
+        _ => {
+            // Effects added by a program using the library
+            if !is_custom_effect(effect_name) {
+                return Err(format!("Unknown effect '{}' - ignoring", effect_name));
+            }
+            effects
+                .custom_effects
+                .push((effect_name.to_string(), params));
+        }
+    }
+    Ok(())
+}
+
+/// Parses a parameter list like "0.5'0.3" into [0.5, 0.3]
+fn parse_parameter_list(params_str: &str) -> Vec<f32> {
+    params_str
+        .split('\'')
+        .filter_map(|s| s.parse::<f32>().ok())
+        .collect()
+}
+
+/// Parses effect parameters where the one at `index` may be a note length
+/// ("t:1/16'0.5"). Returns the numbers, with 0 standing in for the note
+/// length, and the note length in beats.
+fn parse_synced_parameter_list(
+    params_str: &str,
+    index: usize,
+) -> Result<(Vec<f32>, Option<f32>), String> {
+    let mut parts: Vec<&str> = params_str.split('\'').collect();
+    let Some(part) = parts.get(index).copied().filter(|part| part.contains('/')) else {
+        return Ok((parse_parameter_list(params_str), None));
+    };
+    let beats = parse_note_length(part).ok_or_else(|| {
+        format!(
+            "Invalid note length '{}'. Use a length like 1/8, 1/8. (dotted), or 1/8t (triplet)",
+            part
+        )
+    })?;
+    parts[index] = "0";
+    Ok((parse_parameter_list(&parts.join("'")), Some(beats)))
+}
+
+/// Reads the word at the end of an LFO effect's parameters that says when
+/// the LFO restarts ("t:4'0.5'sync"); Free if there is none
+fn parse_lfo_restart(params_str: &str) -> Result<LfoRestart, String> {
+    let last = params_str.rsplit('\'').next().unwrap_or("");
+    if last.is_empty()
+        || !last
+            .chars()
+            .all(|character| character.is_ascii_alphabetic())
+    {
+        return Ok(LfoRestart::Free);
+    }
+    LfoRestart::from_name(last).ok_or_else(|| {
+        format!(
+            "Unknown LFO restart '{}'. Use: sync (each note), row (each row), free",
+            last
+        )
+    })
+}
+
+/// Checks if a token prefix sets the per-note envelope ("env:0.01'0.1'0.8'1.0")
+fn is_envelope_token(prefix: &str) -> bool {
+    prefix == "env" || prefix == "envelope"
+}
+
+/// Checks if an effect name is a master-only effect
+fn is_master_effect(token: &str) -> bool {
+    let token_lower = token.to_lowercase();
+
+    // Check for effects that are master-only when they appear first
+    // (the colon is found in the lowercase text: lowercasing can change
+    // how many bytes a character takes)
+    if let Some(colon_pos) = token_lower.find(':') {
+        let effect_name = &token_lower[..colon_pos];
+        matches!(
+            effect_name,
+            "rv" | "reverb"
+                | "rv2"
+                | "reverb2"
+                | "dl"
+                | "delay"
+                | "a4"
+                | "transpose"
+                | "tp"
+                | "bpm"
+                | "tempo"
+                | "wet"
+                | "fadeout"
+        )
+    } else {
+        false
+    }
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_comments() {
+        assert_eq!(strip_comments("test // comment"), "test ");
+        assert_eq!(strip_comments("c#4 sine // sharp note"), "c#4 sine ");
+        assert_eq!(strip_comments("# full comment"), "");
+    }
+
+    #[test]
+    fn test_parse_parameter_list() {
+        assert_eq!(parse_parameter_list("0.5"), vec![0.5]);
+        assert_eq!(parse_parameter_list("0.5'0.3"), vec![0.5, 0.3]);
+        assert_eq!(parse_parameter_list("1'2'3"), vec![1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_effect_only_change_not_parsed_as_note() {
+        // "a:0.4" should be parsed as an effect change, not as a note trigger
+        // This tests the fix for cells like "a:0.4" being incorrectly treated as notes
+        use crate::helper::FrequencyTable;
+
+        let freq_table = FrequencyTable::new();
+        let mut context = ParserContext::new(&freq_table, MissingCellBehavior::SlowRelease);
+
+        // "a:0.4" should be ChangeEffects (amplitude change), not TriggerNote
+        let action = parse_cell("a:0.4", &mut context);
+        assert!(
+            matches!(action, CellAction::ChangeEffects { .. }),
+            "a:0.4 should be parsed as ChangeEffects, not as a note"
+        );
+
+        // "a4 sine" should still be TriggerNote
+        let action2 = parse_cell("a4 sine", &mut context);
+        assert!(
+            matches!(action2, CellAction::TriggerNote { .. }),
             "a4 sine should be parsed as TriggerNote"
         );
 
-        // Verify no errors were generated for "a:0.4"
+        // Verify no errors were generated for "a:0.4"
+        assert!(
+            context.diagnostics.is_empty(),
+            "No errors should be generated for effect-only change 'a:0.4'"
+        );
+    }
+
+    #[test]
+    fn test_cents_and_detune_parsing() {
+        let freq_table = FrequencyTable::new();
+        let mut context = ParserContext::new(&freq_table, MissingCellBehavior::SlowRelease);
+
+        let c4 = crate::helper::pitch_name_to_frequency(
+            &crate::helper::parse_pitch_name("c4").unwrap(),
+            &freq_table,
+        )
+        .unwrap();
+        match parse_cell("c4+25c sine dt:-12", &mut context) {
+            CellAction::TriggerNote {
+                frequency_hz,
+                effects,
+                ..
+            } => {
+                assert!(frequency_hz > c4);
+                assert_eq!(effects.detune_cents, -12.0);
+            }
+            other => panic!("expected TriggerNote, got {:?}", other),
+        }
+        assert!(context.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_tuning_directive() {
+        let freq_table = FrequencyTable::new();
+        let song = "[tuning] 19edo,,\nV0\nc#4 sine\ndb4 sine\n[tuning] nonsense\n[wobble]\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        let frequencies: Vec<f32> = song_data
+            .rows
+            .iter()
+            .filter_map(|row| match &row[0] {
+                CellAction::TriggerNote { frequency_hz, .. } => Some(*frequency_hz),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(frequencies.len(), 2);
         assert!(
-            context.errors.is_empty(),
-            "No errors should be generated for effect-only change 'a:0.4'"
+            frequencies[0] < frequencies[1],
+            "C# should be below Db in 19-EDO"
+        );
+        assert_eq!(song_data.diagnostics.len(), 2);
+    }
+
+    #[test]
+    fn test_reference_pitch_config_and_master_command() {
+        let freq_table = FrequencyTable::new();
+        let song = "V0\nconfig, a4: 432\na4 sine\nmaster a4:415\na4 sine\nmaster a4:5\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        assert_eq!(song_data.config.reference_frequency_hz, Some(432.0));
+        let frequencies: Vec<f32> = song_data
+            .rows
+            .iter()
+            .filter_map(|row| match &row[0] {
+                CellAction::TriggerNote { frequency_hz, .. } => Some(*frequency_hz),
+                _ => None,
+            })
+            .collect();
+        assert!((frequencies[0] - 432.0).abs() < 0.001);
+        assert!((frequencies[1] - 415.0).abs() < 0.001);
+        assert_eq!(song_data.diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn test_transpose_command() {
+        let freq_table = FrequencyTable::new();
+        let song = "V0,V1\nc4 sine,\nmaster transpose:+12 tr:0.5,-\nc4 sine,\ntp:0,\nc4 sine,\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            2,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        match &song_data.rows[1][0] {
+            CellAction::MasterEffects { effects, .. } => {
+                assert_eq!(effects, &vec![("transpose".to_string(), vec![12.0])]);
+            }
+            other => panic!("expected MasterEffects, got {:?}", other),
+        }
+
+        let frequencies: Vec<f32> = song_data
+            .rows
+            .iter()
+            .filter_map(|row| match &row[0] {
+                CellAction::TriggerNote { frequency_hz, .. } => Some(*frequency_hz),
+                _ => None,
+            })
+            .collect();
+        assert!((frequencies[1] / frequencies[0] - 2.0).abs() < 0.001);
+        assert!((frequencies[2] - frequencies[0]).abs() < 0.001);
+        assert!(song_data.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_master_bypass_and_wet() {
+        let freq_table = FrequencyTable::new();
+        let song = "V0\nmaster rv:0.5'0.4 dl:0.2'0.3\nmaster reverb:off DL:On wet:0.5 tr:1\nmaster a:off\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        match &song_data.rows[1][0] {
+            CellAction::MasterEffects {
+                effects,
+                transition_seconds,
+                ..
+            } => {
+                let expected = vec![
+                    ("rv:off".to_string(), vec![]),
+                    ("dl:on".to_string(), vec![]),
+                    ("wet".to_string(), vec![0.5]),
+                ];
+                assert_eq!(effects, &expected);
+                assert_eq!(*transition_seconds, 1.0);
+            }
+            other => panic!("expected MasterEffects, got {:?}", other),
+        }
+        assert_eq!(song_data.diagnostics.len(), 1);
+        let diagnostic = song_data.diagnostics.iter().next().unwrap();
+        assert!(diagnostic.message.contains("cannot be bypassed"));
+
+        // inf and NaN are refused before they reach the mix
+        for cell in ["master wet:NaN", "master rv:inf'0.5", "group drums a:NaN"] {
+            let (action, diagnostics) =
+                parse_cell_text(cell, &freq_table, crate::helper::A4_FREQUENCY_HZ);
+            assert_eq!(diagnostics.count(Severity::Error), 1, "{}", cell);
+            match action {
+                CellAction::MasterEffects { effects, .. }
+                | CellAction::GroupEffects { effects, .. } => assert!(effects.is_empty()),
+                other => panic!("expected a bus command, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_effect_chain_order() {
+        use crate::effects::ChannelStage::*;
+
+        let freq_table = FrequencyTable::new();
+        let song = "V0\nc4 sine d:0.5 b:4 chain:d>b>a\nchain:t>ch\nchain:b>x\nchain:a>d\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        match &song_data.rows[0][0] {
+            CellAction::TriggerNote { effects, .. } => {
+                assert_eq!(effects.chain, [Distortion, Bitcrush, Chorus, Tremolo]);
+            }
+            other => panic!("expected TriggerNote, got {:?}", other),
+        }
+        match &song_data.rows[1][0] {
+            CellAction::ChangeEffects { effects, .. } => {
+                assert_eq!(effects.chain, [Tremolo, Chorus, Bitcrush, Distortion]);
+            }
+            other => panic!("expected ChangeEffects, got {:?}", other),
+        }
+        let messages: Vec<&str> = song_data
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].contains("'x' can't be placed in the chain"));
+        assert!(messages[1].contains("always come last"));
+    }
+
+    #[test]
+    fn test_channel_groups() {
+        let freq_table = FrequencyTable::new();
+        let song = "[groups]\ndrums = 2, 3 a:0.8 rv:0.3'0.2\nlead = 1\nbad = 3,9\n\nV0,V1,V2\ngroup drums a:0.5 tr:1,c4 sine,group nope a:1\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            3,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        assert_eq!(song_data.groups.len(), 3);
+        assert_eq!(song_data.groups[0].name, "drums");
+        assert_eq!(song_data.groups[0].channels, vec![1, 2]);
+        assert_eq!(
+            song_data.groups[0].effects,
+            vec![
+                ("a".to_string(), vec![0.8]),
+                ("rv".to_string(), vec![0.3, 0.2]),
+            ]
+        );
+        assert_eq!(song_data.groups[1].channels, vec![0]);
+        // Channel 3 is taken and channel 9 doesn't exist
+        assert!(song_data.groups[2].channels.is_empty());
+
+        match &song_data.rows[0][0] {
+            CellAction::GroupEffects {
+                group,
+                transition_seconds,
+                effects,
+                ..
+            } => {
+                assert_eq!(group, "drums");
+                assert_eq!(*transition_seconds, 1.0);
+                assert_eq!(effects, &vec![("a".to_string(), vec![0.5])]);
+            }
+            other => panic!("expected GroupEffects, got {:?}", other),
+        }
+        assert!(matches!(song_data.rows[0][2], CellAction::SlowRelease));
+        let messages: Vec<&str> = song_data
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert!(
+            messages
+                .iter()
+                .any(|message| message.contains("already in group 'drums'"))
+        );
+        assert!(
+            messages
+                .iter()
+                .any(|message| message.contains("channels go from 1 to 3"))
+        );
+        assert!(
+            messages
+                .iter()
+                .any(|message| message.contains("Unknown group 'nope'"))
+        );
+    }
+
+    #[test]
+    fn test_config_block_defaults() {
+        let freq_table = FrequencyTable::new();
+        let song = "[config]\nmissing = sustain\nattack = 0.05\nrelease = 1.0\nspeed = 2\n\nV0,V1\nc4 sine,noise\nc4 sine env:0.2\nc4 sine env:0'0'1'0.3\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            2,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        // Only the unknown setting is reported
+        assert_eq!(song_data.diagnostics.len(), 1);
+        assert_eq!(song_data.diagnostics.iter().next().unwrap().line_number, 5);
+
+        let envelope = |row: usize, channel: usize| match &song_data.rows[row][channel] {
+            CellAction::TriggerNote { envelope, .. }
+            | CellAction::TriggerPitchless { envelope, .. } => envelope.unwrap(),
+            other => panic!("expected a trigger, got {:?}", other),
+        };
+        // Notes without env: get the song's attack and release
+        assert_eq!(envelope(0, 0).attack_time_seconds, 0.05);
+        assert_eq!(envelope(0, 0).release_time_seconds, Some(1.0));
+        assert_eq!(envelope(0, 1).release_time_seconds, Some(1.0));
+        // env: values win, and the ones it leaves out use the song defaults
+        assert_eq!(envelope(1, 0).attack_time_seconds, 0.2);
+        assert_eq!(envelope(1, 0).release_time_seconds, Some(1.0));
+        assert_eq!(envelope(2, 0).release_time_seconds, Some(0.3));
+
+        // The short rows keep channel 2 sounding
+        assert!(matches!(song_data.rows[1][1], CellAction::Sustain));
+    }
+
+    #[test]
+    fn test_config_settings_on_one_line() {
+        let freq_table = FrequencyTable::new();
+        let parse = |song: &str| {
+            parse_song(
+                song,
+                &freq_table,
+                crate::helper::A4_FREQUENCY_HZ,
+                2,
+                MissingCellBehavior::SlowRelease,
+            )
+        };
+
+        let song_data =
+            parse("[config] missing=sustain, release=1.0, attack=0.05\nV0,V1\nc4 sine\n");
+        assert!(song_data.diagnostics.is_empty());
+        assert!(matches!(song_data.rows[0][1], CellAction::Sustain));
+        match &song_data.rows[0][0] {
+            CellAction::TriggerNote { envelope, .. } => {
+                let envelope = envelope.unwrap();
+                assert_eq!(envelope.attack_time_seconds, 0.05);
+                assert_eq!(envelope.release_time_seconds, Some(1.0));
+            }
+            other => panic!("expected a note, got {:?}", other),
+        }
+
+        // Times must be finite and not negative
+        let song_data = parse("[config] attack=inf, release=-1, attack=NaN\nV0,V1\nc4 sine\n");
+        assert_eq!(song_data.diagnostics.count(Severity::Error), 3);
+    }
+
+    #[test]
+    fn test_envelope_rejects_non_finite_values() {
+        let freq_table = FrequencyTable::new();
+        for cell in ["c4 sine env:NaN'NaN'NaN'NaN", "c4 sine env:0.1'inf"] {
+            let (action, diagnostics) =
+                parse_cell_text(cell, &freq_table, crate::helper::A4_FREQUENCY_HZ);
+            assert_eq!(diagnostics.count(Severity::Error), 1, "{}", cell);
+            // The note still plays, on its usual envelope
+            match action {
+                CellAction::TriggerNote { envelope, .. } => assert_eq!(envelope, None),
+                other => panic!("expected a note, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_auto_channel_count() {
+        let freq_table = FrequencyTable::new();
+        let parse = |song: &str| {
+            parse_song(
+                song,
+                &freq_table,
+                crate::helper::A4_FREQUENCY_HZ,
+                AUTO_CHANNEL_COUNT,
+                MissingCellBehavior::SlowRelease,
+            )
+        };
+
+        // The header sets the width; empty cells at the end don't count
+        let song_data = parse("[groups]\ndrums = 2,5\nA,B,C,,\nc4 sine\nc4 sine,c4 sine,,,\n");
+        assert_eq!(song_data.channel_count(), 3);
+        assert!(song_data.rows.iter().all(|row| row.len() == 3));
+        assert!(matches!(song_data.rows[0][2], CellAction::SlowRelease));
+        assert_eq!(song_data.groups[0].channels, vec![1]);
+        assert_eq!(song_data.diagnostics.count(Severity::Error), 1);
+
+        // A data row wider than the header adds channels instead of being cut
+        let song_data = parse("A,B\nc4 sine,-,-,-,c5 sine\n");
+        assert_eq!(song_data.channel_count(), 5);
+        assert!(song_data.diagnostics.is_empty());
+
+        // Empty cells written at the end of a row are empty cells, not
+        // missing ones, so `missing = sustain` doesn't hold those channels
+        let song_data = parse("[config] missing = sustain\nA,B,C\nc4 sine,,\nc4 sine\n");
+        assert!(matches!(song_data.rows[0][1], CellAction::SlowRelease));
+        assert!(matches!(song_data.rows[0][2], CellAction::SlowRelease));
+        assert!(matches!(song_data.rows[1][1], CellAction::Sustain));
+    }
+
+    #[test]
+    fn test_channel_columns_directive() {
+        let freq_table = FrequencyTable::new();
+        let song = "[channels] Kick, Bass, Pad\nBass,Hats,Kick\nc2 square,noise,c1 sine\n-,noise\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            AUTO_CHANNEL_COUNT,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        // Channels follow the [channels] order; Hats is skipped and the
+        // missing Pad column is reported
+        assert_eq!(song_data.channel_names, vec!["Kick", "Bass", "Pad"]);
+        assert_eq!(song_data.channel_label(1), "2 Bass");
+        assert_eq!(song_data.diagnostics.count(Severity::Error), 1);
+        let instrument = |row: usize, channel: usize| match &song_data.rows[row][channel] {
+            CellAction::TriggerNote { instrument_id, .. } => *instrument_id,
+            other => panic!("expected a note, got {:?}", other),
+        };
+        assert_eq!(
+            instrument(0, 0),
+            crate::instruments::find_instrument_by_name("sine").unwrap()
+        );
+        assert_eq!(
+            instrument(0, 1),
+            crate::instruments::find_instrument_by_name("square").unwrap()
+        );
+        assert!(matches!(song_data.rows[0][2], CellAction::SlowRelease));
+        assert!(matches!(song_data.rows[1][0], CellAction::SlowRelease));
+        assert!(matches!(song_data.rows[1][1], CellAction::Sustain));
+        assert_eq!(song_data.raw_lines[0], "c1 sine,c2 square,");
+    }
+
+    #[test]
+    fn test_scale_directive() {
+        let freq_table = FrequencyTable::new();
+        let song = "V0\n[scale] c major\nc#4 sine\n[scale] c major warn\nc#4 sine\n[scale] off\nc#4 sine\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        let frequencies: Vec<f32> = song_data
+            .rows
+            .iter()
+            .filter_map(|row| match &row[0] {
+                CellAction::TriggerNote { frequency_hz, .. } => Some(*frequency_hz),
+                _ => None,
+            })
+            .collect();
+        let frequency = |text: &str| {
+            crate::helper::pitch_name_to_frequency(
+                &crate::helper::parse_pitch_name(text).unwrap(),
+                &freq_table,
+            )
+            .unwrap()
+        };
+        let c4 = frequency("c4");
+        let c_sharp4 = frequency("c#4");
+
+        assert!(
+            (frequencies[0] - c4).abs() < 0.001,
+            "snap mode moves C# to C"
+        );
+        assert!(
+            (frequencies[1] - c_sharp4).abs() < 0.001,
+            "warn mode keeps C#"
+        );
+        assert!((frequencies[2] - c_sharp4).abs() < 0.001);
+        assert_eq!(song_data.diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn test_instrument_presets() {
+        let freq_table = FrequencyTable::new();
+        let song = "[instruments]\nlead = square:0.25 a:0.4 p:-0.5\nsoft_lead = lead a:0.2,,\nV0\nc4 lead\nc4 soft_lead p:0.5\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        assert_eq!(song_data.rows.len(), 2);
+        match &song_data.rows[0][0] {
+            CellAction::TriggerNote {
+                instrument_id,
+                instrument_parameters,
+                effects,
+                ..
+            } => {
+                assert_eq!(*instrument_id, 3);
+                assert_eq!(instrument_parameters, &vec![0.25]);
+                assert_eq!(effects.amplitude, 0.4);
+            }
+            other => panic!("expected TriggerNote, got {:?}", other),
+        }
+        match &song_data.rows[1][0] {
+            CellAction::TriggerNote { effects, .. } => {
+                // Nested preset overrides amplitude, the cell overrides pan
+                assert_eq!(effects.amplitude, 0.2);
+                assert_eq!(effects.pan, 0.5);
+            }
+            other => panic!("expected TriggerNote, got {:?}", other),
+        }
+        assert!(
+            song_data.diagnostics.is_empty(),
+            "{:?}",
+            song_data.diagnostics
+        );
+    }
+
+    #[test]
+    fn test_instrument_default_effects() {
+        let freq_table = FrequencyTable::new();
+        let song = "[instruments]\nsquare = dt:6 d:0.05\nnoise = b:8\nsine = sine:1\nV0\nc4 square\nc4 sq d:0.3 dt:0\nnoise a:0.5\nc4\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        let effects_of = |row: usize| match &song_data.rows[row][0] {
+            CellAction::TriggerNote { effects, .. }
+            | CellAction::TriggerPitchless { effects, .. } => effects.clone(),
+            other => panic!("expected a trigger, got {:?}", other),
+        };
+        // Every square note starts with the defaults...
+        assert_eq!(effects_of(0).detune_cents, 6.0);
+        assert_eq!(effects_of(0).distortion_amount, 0.05);
+        // ...unless the cell sets them itself (even back to zero)
+        assert_eq!(effects_of(1).detune_cents, 0.0);
+        assert_eq!(effects_of(1).distortion_amount, 0.3);
+        assert_eq!(effects_of(2).bitcrush_bits, 8);
+        assert_eq!(effects_of(2).amplitude, 0.5);
+        // "sine = sine:1" is not an effect, so sine keeps no defaults
+        assert_eq!(effects_of(3).detune_cents, 0.0);
+        assert_eq!(song_data.diagnostics.count(Severity::Error), 1);
+    }
+
+    #[test]
+    fn test_velocity_mapping() {
+        let freq_table = FrequencyTable::new();
+        let song = "[instruments]\nsquare = vel:d'0.5\nV0\nc4 square a:0.6\nc4 square a:0.6 vel:d'0.5'2 d:0.1\nnoise a:0.5 vel:attack'-0.2 env:0.3\nc4 vel:x'1\na:0.5 vel:d'0.3\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        let trigger_of = |row: usize| match &song_data.rows[row][0] {
+            CellAction::TriggerNote {
+                effects, envelope, ..
+            }
+            | CellAction::TriggerPitchless {
+                effects, envelope, ..
+            } => (effects.clone(), *envelope),
+            other => panic!("expected a trigger, got {:?}", other),
+        };
+        // The instrument's mapping: 0.5 * 0.6
+        assert!((trigger_of(0).0.distortion_amount - 0.3).abs() < 1e-6);
+        // The cell's own mapping replaces it, on top of the cell's d:
+        // 0.1 + 0.5 * 0.6^2
+        assert!((trigger_of(1).0.distortion_amount - 0.28).abs() < 1e-6);
+        // Attack 0.3 - 0.2 * 0.5
+        let attack = trigger_of(2).1.unwrap().attack_time_seconds;
+        assert!((attack - 0.2).abs() < 1e-6);
+        // An unknown target, and a mapping without a note
+        assert_eq!(song_data.diagnostics.count(Severity::Error), 2);
+    }
+
+    #[test]
+    fn test_sub_oscillator_token() {
+        let freq_table = FrequencyTable::new();
+        let song = "V0\nc2 saw sub:0.5\nc2 saw sub:2'sine\nc2 saw sub:0.5'saw\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        let sub_of = |row: usize| match &song_data.rows[row][0] {
+            CellAction::TriggerNote { effects, .. } => (effects.sub_level, effects.sub_shape),
+            other => panic!("expected a note, got {:?}", other),
+        };
+        assert_eq!(sub_of(0), (0.5, SubOscillatorShape::Square));
+        // The level is kept to 0..1
+        assert_eq!(sub_of(1), (1.0, SubOscillatorShape::Sine));
+        // Only square and sine are shapes
+        assert_eq!(song_data.diagnostics.count(Severity::Error), 1);
+    }
+
+    #[test]
+    fn test_note_length_tokens() {
+        assert_eq!(parse_note_length("1/4"), Some(1.0));
+        assert_eq!(parse_note_length("1/8."), Some(0.75));
+        assert!((parse_note_length("1/8t").unwrap() - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(parse_note_length("1/0"), None);
+        assert_eq!(parse_note_length("0.25"), None);
+
+        let freq_table = FrequencyTable::new();
+        let song = "V0\nc4 saw t:1/16'0.5 ch:0.5'1/2\nmaster dl:1/8.'0.4\nc4 saw v:1/x'0.3\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+        match &song_data.rows[0][0] {
+            CellAction::TriggerNote { effects, .. } => {
+                assert_eq!(effects.synced_rates.tremolo_beats, 0.25);
+                assert_eq!(effects.tremolo_depth, 0.5);
+                assert_eq!(effects.synced_rates.chorus_beats, 2.0);
+            }
+            other => panic!("expected a note, got {:?}", other),
+        }
+        match &song_data.rows[1][0] {
+            CellAction::MasterEffects { effects, .. } => {
+                assert_eq!(effects, &vec![("dl:sync".to_string(), vec![0.75, 0.4])]);
+            }
+            other => panic!("expected master effects, got {:?}", other),
+        }
+        assert_eq!(song_data.diagnostics.count(Severity::Error), 1);
+    }
+
+    #[test]
+    fn test_lfo_restart_tokens() {
+        let freq_table = FrequencyTable::new();
+        let song = "V0\nc4 saw t:4'0.5'sync v:1/8'0.3'row\nc4 saw t:4'0.5\nc4 saw t:4'0.5'often\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        let restarts_of = |row: usize| match &song_data.rows[row][0] {
+            CellAction::TriggerNote { effects, .. } => effects.lfo_restarts,
+            other => panic!("expected a note, got {:?}", other),
+        };
+        assert_eq!(restarts_of(0).tremolo, LfoRestart::Note);
+        assert_eq!(restarts_of(0).vibrato, LfoRestart::Row);
+        assert_eq!(restarts_of(1).tremolo, LfoRestart::Free);
+        assert_eq!(song_data.diagnostics.count(Severity::Error), 1);
+    }
+
+    #[test]
+    fn test_wah_and_vowel_tokens() {
+        use crate::wah::Vowel;
+
+        let freq_table = FrequencyTable::new();
+        let song = "V0\nc4 saw wah:2'0.6\nc4 saw wah:0'2'8 vowel:a>O tr:1\nc4 saw vowel:a>y\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        let effects_of = |row: usize| match &song_data.rows[row][0] {
+            CellAction::TriggerNote { effects, .. } => effects.clone(),
+            other => panic!("expected a note, got {:?}", other),
+        };
+        let wah = effects_of(0);
+        assert_eq!((wah.wah_rate_hz, wah.wah_depth, wah.wah_q), (2.0, 0.6, 5.0));
+        assert_eq!(wah.vowel, None);
+        // Depth is kept to 0..1; the morph heads for the second vowel
+        let both = effects_of(1);
+        assert_eq!(
+            (both.wah_rate_hz, both.wah_depth, both.wah_q),
+            (0.0, 1.0, 8.0)
+        );
+        assert_eq!(both.vowel, Some((Vowel::A, Vowel::O)));
+        assert_eq!(both.vowel_morph, 1.0);
+        // "y" is not a vowel
+        assert_eq!(song_data.diagnostics.count(Severity::Error), 1);
+    }
+
+    #[test]
+    fn test_grain_sample_directive() {
+        let path = std::env::temp_dir().join("parser_grain_test.wav");
+        crate::audio::write_wav_file(&path, &[0.1; 200], 48000, true, false).unwrap();
+        let song = format!(
+            "V0\nc4 grain\n[sample] no_such_file.wav\n[sample] {}\nc4 grain:0.1'30\n",
+            path.display()
+        );
+        let freq_table = FrequencyTable::new();
+        let song_data = parse_song(
+            &song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+        let _ = std::fs::remove_file(&path);
+
+        // A grain note needs a [sample] above it, and the file must load
+        assert!(matches!(song_data.rows[0][0], CellAction::SlowRelease));
+        assert_eq!(song_data.diagnostics.count(Severity::Error), 2);
+
+        // The sample's number goes in front of the cell's parameters
+        let index = crate::grain::load_sample(&path.display().to_string()).unwrap();
+        match &song_data.rows[1][0] {
+            CellAction::TriggerNote {
+                instrument_id,
+                instrument_parameters,
+                ..
+            } => {
+                assert_eq!(*instrument_id, GRAIN_INSTRUMENT_ID);
+                assert_eq!(instrument_parameters, &vec![index as f32, 0.1, 30.0]);
+            }
+            other => panic!("expected a note, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_instrument_file_sample() {
+        let folder = std::env::temp_dir().join("parser_preset_sample_test");
+        std::fs::create_dir_all(&folder).unwrap();
+        let sample_path = folder.join("pad.wav");
+        let song_sample_path = folder.join("song.wav");
+        crate::audio::write_wav_file(&sample_path, &[0.1; 200], 48000, true, false).unwrap();
+        crate::audio::write_wav_file(&song_sample_path, &[0.2; 200], 48000, true, false).unwrap();
+        let bank_path = folder.join("bank.toml");
+        std::fs::write(&bank_path, "[pad]\nsample = \"pad.wav\"\n").unwrap();
+        let song = format!(
+            "[instruments] {}\n[sample] {}\n[instruments]\nsoft_pad = pad a:0.3\nV0\nc4 pad\nc4 soft_pad\nc4 grain\n",
+            bank_path.display(),
+            song_sample_path.display()
+        );
+        let freq_table = FrequencyTable::new();
+        let song_data = parse_song(
+            &song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+        let _ = std::fs::remove_dir_all(&folder);
+        assert!(
+            song_data.diagnostics.is_empty(),
+            "{:?}",
+            song_data.diagnostics
+        );
+
+        // The preset (and presets built on it) play its own sample, other
+        // grain notes the [sample] file
+        let pad = crate::grain::load_sample(&sample_path.display().to_string()).unwrap();
+        let song_sample =
+            crate::grain::load_sample(&song_sample_path.display().to_string()).unwrap();
+        let sample_of = |row: usize| match &song_data.rows[row][0] {
+            CellAction::TriggerNote {
+                instrument_id,
+                instrument_parameters,
+                ..
+            } => {
+                assert_eq!(*instrument_id, GRAIN_INSTRUMENT_ID);
+                instrument_parameters[0] as usize
+            }
+            other => panic!("expected a note, got {:?}", other),
+        };
+        assert_eq!(sample_of(0), pad);
+        assert_eq!(sample_of(1), pad);
+        assert_eq!(sample_of(2), song_sample);
+    }
+
+    #[test]
+    fn test_macro_expansion() {
+        let freq_table = FrequencyTable::new();
+        let song = "[macro hat(0.3)] noise a:$1 b:8\n[macro loud] a:0.9 d:0.3\n[macro kick] c2 sine @loud\nV0\n@hat\n@hat(0.5) p:-0.5\n@kick\n@snare\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        let amplitudes: Vec<f32> = song_data
+            .rows
+            .iter()
+            .filter_map(|row| match &row[0] {
+                CellAction::TriggerPitchless { effects, .. } => Some(effects.amplitude),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(amplitudes, vec![0.3, 0.5]);
+
+        // A macro inside a macro: "@kick" becomes "c2 sine a:0.9 d:0.3"
+        match &song_data.rows[2][0] {
+            CellAction::TriggerNote { effects, .. } => assert_eq!(effects.amplitude, 0.9),
+            other => panic!("expected TriggerNote, got {:?}", other),
+        }
+
+        // Unknown macro is reported
+        assert_eq!(song_data.diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn test_script_writes_rows() {
+        let freq_table = FrequencyTable::new();
+        let parse = |song: &str| {
+            parse_song(
+                song,
+                &freq_table,
+                crate::helper::A4_FREQUENCY_HZ,
+                AUTO_CHANNEL_COUNT,
+                MissingCellBehavior::SlowRelease,
+            )
+        };
+
+        // The example from the top of script.rs, then a written row
+        let song = "V0,V1\n[script seed:42]\nlet notes = scale_notes(\"a minor\", 3);\nlet step = 0;\nfor hit in euclid(5, 16) {\n    step = (step + rand_int(-1, 1)).max(0).min(notes.len() - 1);\n    row([notes[step] + \" sine\", if hit { \"noise a:0.4\" } else { \"\" }]);\n}\n[end]\nc4 sine,.\n";
+        let song_data = parse(song);
+        assert!(
+            song_data.diagnostics.is_empty(),
+            "{:?}",
+            song_data.diagnostics
+        );
+        assert_eq!(song_data.row_count(), 17);
+        assert_eq!(song_data.script_seeds, vec![42]);
+        assert_eq!(song_data.row_line_numbers[0], 2);
+        assert_eq!(song_data.raw_lines[16], "c4 sine,.");
+        assert!(matches!(
+            song_data.rows[0][0],
+            CellAction::TriggerNote { .. }
+        ));
+        assert!(matches!(
+            song_data.rows[0][1],
+            CellAction::TriggerPitchless { .. }
+        ));
+        assert!(matches!(song_data.rows[1][1], CellAction::SlowRelease));
+
+        // The same seed writes the same rows, streamed or not
+        assert_eq!(parse(song).raw_lines, song_data.raw_lines);
+        let mut stream = SongStream::new(
+            song.as_bytes(),
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            AUTO_CHANNEL_COUNT,
+            MissingCellBehavior::SlowRelease,
+        );
+        stream.read_head();
+        assert_eq!(stream.count(), 17);
+
+        // Without a seed, the one used is kept and reported
+        let unseeded = parse(&song.replace("[script seed:42]", "[script]"));
+        let seed = unseeded.script_seeds[0];
+        assert_eq!(unseeded.diagnostics.count(Severity::Warning), 1);
+        let reseeded = parse(&song.replace("seed:42", &format!("seed:{}", seed)));
+        assert_eq!(reseeded.raw_lines, unseeded.raw_lines);
+
+        // Script errors name the file line; a missing [end] still runs
+        let song_data = parse("V0\n[script]\nrow(\"c4 sine\");\nrow(nope);\n[end]\n");
+        assert_eq!(song_data.row_count(), 0);
+        assert!(
+            song_data
+                .diagnostics
+                .iter()
+                .any(|diagnostic| diagnostic.message.contains("line 4"))
+        );
+        let song_data = parse("V0\n[script seed:1]\nrow(\"c4 sine\");\n");
+        assert_eq!(song_data.row_count(), 1);
+        assert_eq!(song_data.diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn test_euclid_generator() {
+        let freq_table = FrequencyTable::new();
+        let parse = |song: &str| {
+            parse_song(
+                song,
+                &freq_table,
+                crate::helper::A4_FREQUENCY_HZ,
+                AUTO_CHANNEL_COUNT,
+                MissingCellBehavior::SlowRelease,
+            )
+        };
+
+        // Two generators layered over written rows, then rows of their own
+        let song_data = parse(
+            "V0,V1,V2\n[gen ch2] euclid(3,8,\"noise a:0.4\")\n[gen 3] euclid(2, 4, c2 sine, 2)\nc4 sine\n-,c5 sine\n-,.\n-\n",
+        );
+        assert!(
+            song_data.diagnostics.is_empty(),
+            "{:?}",
+            song_data.diagnostics
+        );
+        assert_eq!(song_data.row_count(), 8);
+        assert_eq!(song_data.raw_lines[0], "c4 sine,noise a:0.4,c2 sine");
+        assert_eq!(song_data.raw_lines[3], "-,noise a:0.4,");
+        assert_eq!(song_data.raw_lines[6], ",noise a:0.4,c2 sine");
+        let hits: Vec<bool> = song_data
+            .rows
+            .iter()
+            .map(|row| matches!(row[1], CellAction::TriggerPitchless { .. }))
+            .collect();
+        assert_eq!(hits, [true, false, false, true, false, false, true, false]);
+
+        // Written cells win over generated ones
+        assert!(matches!(
+            song_data.rows[1][1],
+            CellAction::TriggerNote { .. }
+        ));
+
+        // Mistakes are reported and the line ignored
+        let song_data = parse(
+            "V0\n[gen ch0] euclid(3,8,noise)\n[gen ch1] euclid(3,0,noise)\n[gen ch1] wobble(3,8,noise)\nc4 sine\n",
+        );
+        assert_eq!(song_data.diagnostics.count(Severity::Error), 3);
+        assert_eq!(song_data.row_count(), 1);
+    }
+
+    #[test]
+    fn test_song_collection() {
+        let freq_table = FrequencyTable::new();
+        let song = "[macro hat] noise a:0.3\n[chain] intro main\n[song intro]\nV0\nconfig, tick_duration: 0.2\nc4 sine\n[song main]\nV0\n@hat\n-\n";
+        let collection = parse_song_collection(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        assert_eq!(collection.song_order, vec!["intro", "main"]);
+        assert_eq!(collection.songs["intro"].row_count(), 1);
+        assert_eq!(collection.songs["intro"].config.tick_duration, Some(0.2));
+
+        // Shared macro works in a later section
+        assert!(matches!(
+            collection.songs["main"].rows[0][0],
+            CellAction::TriggerPitchless { .. }
+        ));
+
+        // The chain plays both songs back to back with the first config
+        let chained = collection.select(&collection.default_selection()).unwrap();
+        assert_eq!(chained.row_count(), 3);
+        assert_eq!(chained.config.tick_duration, Some(0.2));
+        assert_eq!(chained.diagnostics.len(), 1); // main has a different tick duration
+
+        assert!(collection.select(&["outro".to_string()]).is_err());
+
+        // A file without sections is one song called "main"
+        let collection = parse_song_collection(
+            "V0\nc4 sine\n",
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            1,
+            MissingCellBehavior::SlowRelease,
+        );
+        assert_eq!(collection.default_selection(), vec![DEFAULT_SONG_NAME]);
+    }
+
+    #[test]
+    fn test_diagnostic_severity() {
+        let freq_table = FrequencyTable::new();
+        let song = "V0,V1\ncx9 sine,c4 sine,e4 sine\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            2,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        // Invalid pitch is an error, the extra cell only a warning
+        assert_eq!(song_data.diagnostics.count(Severity::Error), 1);
+        assert_eq!(song_data.diagnostics.count(Severity::Warning), 1);
+        assert!(song_data.diagnostics.has_errors());
+
+        let first = song_data.diagnostics.iter().next().unwrap();
+        assert!(
+            first
+                .format_for_file("song.csv")
+                .starts_with("song.csv:2: error: Invalid pitch 'cx9'")
+        );
+    }
+
+    #[test]
+    fn test_song_json_round_trip() {
+        let freq_table = FrequencyTable::new();
+        let song = "V0,V1\nconfig, title: Json, tick_duration: 0.2\nc4 sine v:5'0.3 env:0.1'0.2'0.5, noise ch:0.5'1'3\n- a:0.5 tr:0.5, .\nmaster rv:0.4'0.3 dl:0.2'0.4, cx9\n";
+        let song_data = parse_song(
+            song,
+            &freq_table,
+            crate::helper::A4_FREQUENCY_HZ,
+            2,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        let json = song_data.to_json();
+        let loaded = SongData::from_json(&json).unwrap();
+        assert_eq!(loaded.to_json(), json);
+        assert_eq!(loaded.config.title.as_deref(), Some("Json"));
+        assert_eq!(loaded.diagnostics.count(Severity::Error), 1);
+
+        // Other programs only need to write the rows; effects can be partial
+        let loaded = SongData::from_json(
+            r#"{"rows": [[{"ChangeEffects": {"effects": {"amplitude": 0.5},
+                "transition_seconds": 0.0, "clear_first": false}}, "Sustain"]]}"#,
+        )
+        .unwrap();
+        assert_eq!(loaded.channel_count(), 2);
+        match &loaded.rows[0][0] {
+            CellAction::ChangeEffects { effects, .. } => {
+                assert_eq!(effects.amplitude, 0.5);
+                assert_eq!(effects.bitcrush_bits, 16);
+            }
+            other => panic!("unexpected action {:?}", other),
+        }
+
+        assert!(SongData::from_json("{\"rows\": 3}").is_err());
+    }
+
+    #[test]
+    fn test_song_json_out_of_range_values() {
+        // Values the CSV parser would clamp are clamped the same way
+        let loaded = SongData::from_json(
+            r#"{"rows": [[{"ChangeEffects": {"effects": {"chorus_depth_ms": 100.0,
+                "amplitude": 3.0, "bitcrush_bits": 0, "oversampling": 3},
+                "transition_seconds": -1.0, "clear_first": false}}],
+                [{"TriggerPitchless": {"instrument_id": 4, "instrument_parameters": [],
+                "effects": {}, "transition_seconds": 0.0, "clear_effects": false,
+                "envelope": {"attack_time_seconds": 90.0, "decay_time_seconds": 0.1,
+                "sustain_level": 2.0, "release_time_seconds": -1.0}, "seed": null}}],
+                [{"MasterEffects": {"clear_first": false, "transition_seconds": 0.0,
+                "effects": [["fadeout", [9000.0]]]}}]]}"#,
+        )
+        .unwrap();
+        match &loaded.rows[0][0] {
+            CellAction::ChangeEffects {
+                effects,
+                transition_seconds,
+                ..
+            } => {
+                assert_eq!(effects.chorus_depth_ms, 10.0);
+                assert_eq!(effects.amplitude, 1.0);
+                assert_eq!(effects.bitcrush_bits, 1);
+                assert_eq!(effects.oversampling, 4);
+                assert_eq!(*transition_seconds, 0.0);
+            }
+            other => panic!("unexpected action {:?}", other),
+        }
+        match &loaded.rows[1][0] {
+            CellAction::TriggerPitchless { envelope, .. } => {
+                let envelope = envelope.unwrap();
+                assert_eq!(envelope.attack_time_seconds, 30.0);
+                assert_eq!(envelope.sustain_level, 1.0);
+                assert_eq!(envelope.release_time_seconds, Some(0.0));
+            }
+            other => panic!("unexpected action {:?}", other),
+        }
+        assert_eq!(loaded.row_durations(0.1).len(), 3);
+        match &loaded.rows[2][0] {
+            CellAction::MasterEffects { effects, .. } => {
+                assert_eq!(effects, &vec![("fadeout".to_string(), vec![600.0])]);
+            }
+            other => panic!("unexpected action {:?}", other),
+        }
+
+        // Values it would reject are errors
+        let master = |effect: &str| {
+            SongData::from_json(&format!(
+                r#"{{"rows": [[{{"MasterEffects": {{"clear_first": false,
+                    "transition_seconds": 0.0, "effects": [{}]}}}}]]}}"#,
+                effect
+            ))
+        };
+        assert!(master(r#"["bpm", [120.0, 4.0]]"#).is_ok());
+        assert!(master(r#"["bpm", [0.0, 4.0]]"#).is_err());
+        assert!(master(r#"["bpm", [120.0]]"#).is_err());
+        assert!(master(r#"["fadeout", [0.0]]"#).is_err());
+        assert!(SongData::from_json(r#"{"rows": [], "config": {"tick_duration": 0.0}}"#).is_err());
+        assert!(
+            SongData::from_json(
+                r#"{"rows": [[{"ChangeEffects": {"effects": {"vibrato_rate_hz": 1e300},
+                    "transition_seconds": 0.0, "clear_first": false}}]]}"#
+            )
+            .is_err_and(|message| message.contains("too large"))
         );
     }
 }
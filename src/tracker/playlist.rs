@@ -0,0 +1,142 @@
+// ============================================================================
+// PLAYLIST.RS - Playlists for Live Sets
+// ============================================================================
+//
+// A playlist is an M3U file listing songs to play one after another:
+//
+//   #EXTM3U
+//   #EXTINF:-1,Warm Up
+//   warmup.csv
+//   # a comment
+//   ../ambient/drift.json
+//   /home/me/songs/closer.csv
+//
+//   tracker play set.m3u --crossfade 6
+//
+// HOW IT WORKS:
+// Every line that isn't blank or a "#" comment is a song file. Relative
+// paths are read from the playlist's own folder, so a set can be moved
+// around with its songs. An "#EXTINF:<seconds>,<title>" line names the
+// song on the next line (the seconds are ignored: the tracker knows how
+// long its songs are).
+//
+// The player (commands/playlist.rs) loads the next song while the current
+// one plays and queues it on the engine (engine.rs PLAYLISTS), which starts
+// it the moment the current song runs out of rows. Both songs share the master
+// bus, so its reverb and delay tails carry across the join.
+// ============================================================================
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// One song in a playlist
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlaylistEntry {
+    /// The song file (relative paths already joined to the playlist's folder)
+    pub path: PathBuf,
+
+    /// Title from an #EXTINF line, if there was one
+    pub title: Option<String>,
+}
+
+impl PlaylistEntry {
+    /// The title to show: the #EXTINF title, or the file name
+    pub fn display_name(&self) -> String {
+        match &self.title {
+            Some(title) => title.clone(),
+            None => self.path.file_name().map_or_else(
+                || self.path.display().to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            ),
+        }
+    }
+}
+
+/// Whether a file name looks like a playlist (.m3u or .m3u8)
+pub fn is_playlist_path(path: &str) -> bool {
+    let lower = path.to_lowercase();
+    lower.ends_with(".m3u") || lower.ends_with(".m3u8")
+}
+
+/// Reads the songs listed in an M3U playlist (see the top of the file)
+/// Relative paths are joined to `folder`, the playlist's own folder.
+pub fn parse_playlist(text: &str, folder: &Path) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut title: Option<String> = None;
+    for line in text.lines() {
+        // A UTF-8 byte order mark may start an .m3u8 file
+        let line = line.trim_start_matches('\u{feff}').trim();
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            title = info
+                .split_once(',')
+                .map(|(_, name)| name.trim().to_string())
+                .filter(|name| !name.is_empty());
+            continue;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        entries.push(PlaylistEntry {
+            path: folder.join(line),
+            title: title.take(),
+        });
+    }
+    entries
+}
+
+/// Reads a playlist file; an empty playlist is an error
+pub fn read_playlist(path: &str) -> Result<Vec<PlaylistEntry>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|error| format!("Failed to read playlist '{}': {}", path, error))?;
+    let folder = Path::new(path).parent().unwrap_or(Path::new(""));
+    let entries = parse_playlist(&text, folder);
+    if entries.is_empty() {
+        return Err(format!("Playlist '{}' lists no songs", path));
+    }
+    Ok(entries)
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_playlist() {
+        let text = "\u{feff}#EXTM3U\n\
+                    #EXTINF:-1,Warm Up\n\
+                    warmup.csv\n\
+                    \n\
+                    # a comment\n\
+                    ../ambient/drift.json\n\
+                    #EXTINF:240,\n\
+                    /songs/closer.csv\n";
+        let entries = parse_playlist(text, Path::new("sets"));
+        assert_eq!(
+            entries,
+            vec![
+                PlaylistEntry {
+                    path: PathBuf::from("sets/warmup.csv"),
+                    title: Some("Warm Up".into()),
+                },
+                PlaylistEntry {
+                    path: PathBuf::from("sets/../ambient/drift.json"),
+                    title: None,
+                },
+                PlaylistEntry {
+                    path: PathBuf::from("/songs/closer.csv"),
+                    title: None,
+                },
+            ]
+        );
+        assert_eq!(entries[0].display_name(), "Warm Up");
+        assert_eq!(entries[1].display_name(), "drift.json");
+
+        assert!(is_playlist_path("set.M3U"));
+        assert!(is_playlist_path("set.m3u8"));
+        assert!(!is_playlist_path("song.csv"));
+    }
+}
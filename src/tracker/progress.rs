@@ -0,0 +1,216 @@
+// ============================================================================
+// PROGRESS.RS - Progress Line for Rendering and Playback
+// ============================================================================
+//
+// While a song renders to WAV or plays without the TUI, one line at the
+// bottom of the terminal shows how far it has got, redrawn in place:
+//
+//   [EXPORT] [##########··········]   row 30/59   0:07.4 / 0:14.8   12.3x realtime
+//
+// HOW IT WORKS:
+// The caller works out where the song is (a Progress) and hands it to a
+// ProgressBar every so often. The bar redraws at most REDRAW_INTERVAL
+// apart, returning to the start of the line with "\r" instead of printing
+// a new one. It draws on stderr, so the output still reads well when
+// stdout goes to a file.
+//
+// When stderr is not a terminal (a log file, a CI job), "\r" would only
+// make a mess, so nothing is drawn until the end: finish prints the last
+// line once, as a summary.
+//
+// The realtime factor is seconds of song per second of waiting: above 1
+// an export is faster than playing the song, and during playback it
+// drops below 1 if the song can't keep up (a streamed song whose rows
+// arrive late).
+// ============================================================================
+
+use crate::tui::format_time;
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+/// Shortest time between two redraws of the line
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Characters in the bar itself
+const BAR_WIDTH: usize = 20;
+
+/// Where a render or playback has got to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// The row playing (0-indexed)
+    pub row: usize,
+
+    /// Rows in the song, if known (a streamed song doesn't know)
+    pub row_count: Option<usize>,
+
+    /// Seconds of the song done so far
+    pub seconds: f32,
+
+    /// Seconds in the song, if known
+    pub total_seconds: Option<f32>,
+
+    /// Seconds of song per second of waiting, if measured yet
+    pub realtime_factor: Option<f32>,
+}
+
+impl Progress {
+    /// The progress line, without a label
+    /// Parts that aren't known are left out (no bar without a total).
+    pub fn line(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(total_seconds) = self.total_seconds.filter(|total| *total > 0.0) {
+            let fraction = (self.seconds / total_seconds).clamp(0.0, 1.0);
+            let filled = (fraction * BAR_WIDTH as f32).round() as usize;
+            parts.push(format!(
+                "[{}{}]",
+                "#".repeat(filled),
+                "·".repeat(BAR_WIDTH - filled)
+            ));
+        }
+
+        parts.push(match self.row_count {
+            Some(row_count) => format!("row {}/{}", (self.row + 1).min(row_count), row_count),
+            None => format!("row {}", self.row + 1),
+        });
+
+        parts.push(match self.total_seconds {
+            Some(total_seconds) => format!(
+                "{} / {}",
+                format_time(self.seconds.min(total_seconds)),
+                format_time(total_seconds)
+            ),
+            None => format_time(self.seconds),
+        });
+
+        if let Some(factor) = self.realtime_factor {
+            parts.push(format!("{:.1}x realtime", factor));
+        }
+        parts.join("   ")
+    }
+}
+
+/// Draws a Progress line in place on the terminal (see the top of the file)
+pub struct ProgressBar {
+    /// Printed at the start of the line, like "[EXPORT]"
+    label: &'static str,
+
+    /// Whether stderr is a terminal the line can be redrawn on
+    redraws: bool,
+
+    /// When the line was last drawn
+    last_drawn: Option<Instant>,
+
+    /// The last progress passed to update
+    latest: Option<Progress>,
+}
+
+impl ProgressBar {
+    /// Creates a bar; nothing is drawn until the first update
+    pub fn new(label: &'static str) -> Self {
+        Self {
+            label,
+            redraws: std::io::stderr().is_terminal(),
+            last_drawn: None,
+            latest: None,
+        }
+    }
+
+    /// Shows new progress (redrawn at most every REDRAW_INTERVAL)
+    pub fn update(&mut self, progress: Progress) {
+        self.latest = Some(progress);
+        if !self.redraws
+            || self
+                .last_drawn
+                .is_some_and(|time| time.elapsed() < REDRAW_INTERVAL)
+        {
+            return;
+        }
+        self.draw(progress);
+        self.last_drawn = Some(Instant::now());
+    }
+
+    /// Draws the latest progress one last time and ends the line
+    /// (safe to call more than once; later calls do nothing)
+    pub fn finish(&mut self) {
+        if let Some(progress) = self.latest.take() {
+            self.draw(progress);
+            eprintln!();
+        }
+        self.last_drawn = None;
+    }
+
+    /// Writes the line over the previous one
+    fn draw(&self, progress: Progress) {
+        let mut stderr = std::io::stderr().lock();
+        // "\x1b[K" clears what is left of a longer previous line
+        let line_end = if self.redraws { "\x1b[K" } else { "" };
+        let start = if self.redraws { "\r" } else { "" };
+        let _ = write!(
+            stderr,
+            "{}{} {}{}",
+            start,
+            self.label,
+            progress.line(),
+            line_end
+        );
+        let _ = stderr.flush();
+    }
+}
+
+/// Measures the realtime factor: song seconds done per second since start
+pub struct RealtimeClock {
+    start_time: Instant,
+}
+
+impl RealtimeClock {
+    /// Starts measuring now
+    pub fn start() -> Self {
+        Self {
+            start_time: Instant::now(),
+        }
+    }
+
+    /// Seconds since start
+    pub fn elapsed_seconds(&self) -> f32 {
+        self.start_time.elapsed().as_secs_f32()
+    }
+
+    /// The factor for this many seconds of song done so far
+    pub fn factor(&self, song_seconds: f32) -> f32 {
+        song_seconds / self.elapsed_seconds().max(f32::EPSILON)
+    }
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_line() {
+        let progress = Progress {
+            row: 29,
+            row_count: Some(59),
+            seconds: 7.4,
+            total_seconds: Some(14.8),
+            realtime_factor: Some(12.34),
+        };
+        assert_eq!(
+            progress.line(),
+            "[##########··········]   row 30/59   0:07.4 / 0:14.8   12.3x realtime"
+        );
+
+        // A streamed song knows neither its length nor its row count
+        let streamed = Progress {
+            row: 1000,
+            row_count: None,
+            seconds: 65.0,
+            total_seconds: None,
+            realtime_factor: None,
+        };
+        assert_eq!(streamed.line(), "row 1001   1:05.0");
+    }
+}
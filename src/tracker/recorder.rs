@@ -0,0 +1,411 @@
+// ============================================================================
+// RECORDER.RS - Recording Live Takes
+// ============================================================================
+//
+// Keeps what was played live, so a good improvisation isn't lost:
+//
+//   tracker live --keys --record take.wav --record-events take-events.csv
+//
+// The WAV is the master output exactly as it was heard. The events file
+// lists every cell the live player sent, with the time it was sent:
+//
+//   seconds,channel,cell
+//   0.512,1,c4 trisaw a:0.787 cl
+//   0.930,1,
+//
+// (an empty cell is a key release, as in a song).
+//
+// STEP CAPTURE:
+// "--capture song.csv" turns the same cells into a song instead: each one
+// is rounded to the nearest row of a tempo grid (--bpm, --rows-per-beat),
+// every channel that was played becomes a column, and rows where a note
+// is still held get "-" so it keeps sounding. The first cell lands on row
+// 0. Two cells that round to the same row of a channel are both kept, the
+// later one moved down a row, so a quick tap never loses its release.
+//
+// HOW IT WORKS:
+// The audio callback must never wait or allocate, so it doesn't write the
+// file itself. A RecordTap in the callback copies each block of output
+// into fixed-size chunks and pushes them onto a lock-free queue
+// (command_queue.rs). A collector thread empties the queue every few
+// milliseconds into a growing buffer, and the WAV is written from that
+// buffer when playing stops. If the collector ever falls a whole queue
+// behind, chunks are dropped (and counted) instead of stalling the sound.
+// ============================================================================
+
+use crate::audio::write_wav_file;
+use crate::command_queue::{Consumer, Producer, command_queue};
+use crate::helper::{A4_FREQUENCY_HZ, FrequencyTable};
+use crate::midi_import::{config_line, write_song_csv};
+use crate::parser::{CellAction, parse_cell_text, tick_duration_from_bpm};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Interleaved samples per chunk sent from the audio callback
+const RECORD_CHUNK_SAMPLES: usize = 1024;
+
+/// Chunks the queue holds (about 5 seconds of stereo at 48 kHz, so the
+/// collector can fall far behind before anything is dropped)
+const RECORD_QUEUE_CHUNKS: usize = 512;
+
+/// How often the collector thread empties the queue
+const COLLECT_INTERVAL: Duration = Duration::from_millis(10);
+
+// ============================================================================
+// AUDIO CAPTURE
+// ============================================================================
+
+/// A piece of the output, copied by value so pushing it never allocates
+struct RecordChunk {
+    samples: [f32; RECORD_CHUNK_SAMPLES],
+
+    /// How many of the samples are used
+    length: usize,
+}
+
+/// The audio thread's end of a recording: copies output into the queue
+pub struct RecordTap {
+    chunks: Producer<RecordChunk>,
+
+    /// Samples dropped because the queue was full
+    dropped_samples: Arc<AtomicUsize>,
+}
+
+impl RecordTap {
+    /// Records a block of interleaved output
+    /// Call this from the audio callback, after rendering. It never blocks.
+    pub fn capture(&mut self, samples: &[f32]) {
+        for block in samples.chunks(RECORD_CHUNK_SAMPLES) {
+            let mut chunk = RecordChunk {
+                samples: [0.0; RECORD_CHUNK_SAMPLES],
+                length: block.len(),
+            };
+            chunk.samples[..block.len()].copy_from_slice(block);
+            if self.chunks.push(chunk).is_err() {
+                self.dropped_samples
+                    .fetch_add(block.len(), Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// The main thread's end of a recording (see the top of the file)
+pub struct LiveRecording {
+    sample_rate: u32,
+    stop: Arc<AtomicBool>,
+    collector: JoinHandle<Vec<f32>>,
+    dropped_samples: Arc<AtomicUsize>,
+}
+
+/// Starts recording; put the tap in the audio callback
+pub fn start_recording(sample_rate: u32) -> (RecordTap, LiveRecording) {
+    let (producer, mut consumer) = command_queue(RECORD_QUEUE_CHUNKS);
+    let stop = Arc::new(AtomicBool::new(false));
+    let dropped_samples = Arc::new(AtomicUsize::new(0));
+
+    let collector_stop = Arc::clone(&stop);
+    let collector = thread::spawn(move || {
+        let mut samples = Vec::new();
+        loop {
+            // Check before draining, so the last chunks are never left behind
+            let stopping = collector_stop.load(Ordering::Acquire);
+            drain_chunks(&mut consumer, &mut samples);
+            if stopping {
+                return samples;
+            }
+            thread::sleep(COLLECT_INTERVAL);
+        }
+    });
+
+    (
+        RecordTap {
+            chunks: producer,
+            dropped_samples: Arc::clone(&dropped_samples),
+        },
+        LiveRecording {
+            sample_rate,
+            stop,
+            collector,
+            dropped_samples,
+        },
+    )
+}
+
+/// Moves every waiting chunk into the recorded samples
+fn drain_chunks(consumer: &mut Consumer<RecordChunk>, samples: &mut Vec<f32>) {
+    while let Some(chunk) = consumer.pop() {
+        samples.extend_from_slice(&chunk.samples[..chunk.length]);
+    }
+}
+
+/// What a finished recording captured
+#[derive(Debug)]
+pub struct RecordedTake {
+    /// Interleaved stereo samples
+    pub samples: Vec<f32>,
+
+    pub sample_rate: u32,
+
+    /// Samples lost because the collector fell behind (normally 0)
+    pub dropped_samples: usize,
+}
+
+impl LiveRecording {
+    /// Stops recording and collects everything captured
+    /// Stop the audio backend first, so the tap has sent its last block.
+    pub fn finish(self) -> RecordedTake {
+        self.stop.store(true, Ordering::Release);
+        let samples = self.collector.join().unwrap_or_default();
+        RecordedTake {
+            samples,
+            sample_rate: self.sample_rate,
+            dropped_samples: self.dropped_samples.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl RecordedTake {
+    /// Length of the take in seconds
+    pub fn duration_seconds(&self) -> f32 {
+        self.samples.len() as f32 / 2.0 / self.sample_rate as f32
+    }
+
+    /// Writes the take as a 16-bit WAV file (dithered, like song exports)
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        write_wav_file(path, &self.samples, self.sample_rate, false, true)
+    }
+}
+
+// ============================================================================
+// EVENT LOG
+// ============================================================================
+
+/// One cell sent while playing live
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoggedEvent {
+    /// Seconds since the log started
+    pub seconds: f64,
+
+    /// Tracker channel (0-indexed)
+    pub channel_index: usize,
+
+    /// The cell text ("" = release)
+    pub cell: String,
+}
+
+/// The cells a live player sent, with when it sent them
+#[derive(Clone, Debug)]
+pub struct EventLog {
+    start: Instant,
+    pub events: Vec<LoggedEvent>,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventLog {
+    /// Starts an empty log; times count from now
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Adds a cell sent just now
+    pub fn record(&mut self, channel_index: usize, cell: &str) {
+        let seconds = self.start.elapsed().as_secs_f64();
+        self.record_at(seconds, channel_index, cell);
+    }
+
+    /// Adds a cell sent at a given time
+    pub fn record_at(&mut self, seconds: f64, channel_index: usize, cell: &str) {
+        self.events.push(LoggedEvent {
+            seconds,
+            channel_index,
+            cell: cell.to_string(),
+        });
+    }
+
+    /// The log as CSV: a header, then "seconds,channel,cell" per event
+    /// Channels are counted from 1, like song columns.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("seconds,channel,cell\n");
+        for event in &self.events {
+            let _ = writeln!(
+                csv,
+                "{:.3},{},{}",
+                event.seconds,
+                event.channel_index + 1,
+                event.cell
+            );
+        }
+        csv
+    }
+
+    /// Writes the log as CSV
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_csv())
+            .map_err(|error| format!("Failed to write '{}': {}", path.display(), error))
+    }
+}
+
+// ============================================================================
+// STEP CAPTURE
+// ============================================================================
+
+/// A channel's cells while the log is laid out on rows
+#[derive(Default)]
+struct CaptureColumn {
+    cells: Vec<String>,
+
+    /// Whether the last note is still playing (empty rows get "-")
+    is_sounding: bool,
+}
+
+impl EventLog {
+    /// The log as song CSV, quantized to a row grid (see the top of the
+    /// file). Returns None if nothing was played.
+    pub fn to_song_csv(&self, title: &str, bpm: f32, rows_per_beat: u32) -> Option<String> {
+        let first_seconds = self.events.first()?.seconds;
+        let row_seconds = tick_duration_from_bpm(bpm, rows_per_beat as f32) as f64;
+        let frequency_table = FrequencyTable::new();
+
+        let mut columns: BTreeMap<usize, CaptureColumn> = BTreeMap::new();
+        for event in &self.events {
+            let column = columns.entry(event.channel_index).or_default();
+            let nearest_row = ((event.seconds - first_seconds) / row_seconds).round() as usize;
+            let row = nearest_row.max(column.cells.len());
+
+            let filler = if column.is_sounding { "-" } else { "" };
+            column.cells.resize(row, filler.to_string());
+            column.cells.push(event.cell.clone());
+
+            // Only the kind of cell matters here, not its pitch
+            let (action, _) = parse_cell_text(&event.cell, &frequency_table, A4_FREQUENCY_HZ);
+            column.is_sounding = match action {
+                CellAction::TriggerNote { .. } | CellAction::TriggerPitchless { .. } => true,
+                CellAction::SlowRelease | CellAction::FastRelease => false,
+                _ => column.is_sounding,
+            };
+        }
+
+        let column_names: Vec<String> = columns
+            .keys()
+            .map(|channel_index| format!("Channel {}", channel_index + 1))
+            .collect();
+        let cells: Vec<Vec<String>> = columns.into_values().map(|column| column.cells).collect();
+        let preamble = [
+            config_line(title, bpm, rows_per_beat),
+            format!("// Captured live: {} rows per beat", rows_per_beat),
+        ];
+        Some(write_song_csv(&column_names, &cells, &preamble))
+    }
+
+    /// Writes the log as a song (titled after the file name)
+    /// Fails if nothing was played.
+    pub fn save_song(&self, path: &Path, bpm: f32, rows_per_beat: u32) -> Result<(), String> {
+        let title = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let csv = self
+            .to_song_csv(&title, bpm, rows_per_beat)
+            .ok_or_else(|| "Nothing was played, so there is no song to save".to_string())?;
+        std::fs::write(path, csv)
+            .map_err(|error| format!("Failed to write '{}': {}", path.display(), error))
+    }
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording_keeps_every_sample() {
+        let (mut tap, recording) = start_recording(48000);
+        // Blocks that don't line up with the chunk size
+        let output: Vec<f32> = (0..3000).map(|index| index as f32).collect();
+        for block in output.chunks(700) {
+            tap.capture(block);
+        }
+        let take = recording.finish();
+        assert_eq!(take.samples, output);
+        assert_eq!(take.dropped_samples, 0);
+        assert!((take.duration_seconds() - 1500.0 / 48000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_full_queue_drops_instead_of_waiting() {
+        // A queue with room for one chunk and no collector emptying it
+        let (producer, mut consumer) = command_queue(1);
+        let mut tap = RecordTap {
+            chunks: producer,
+            dropped_samples: Arc::new(AtomicUsize::new(0)),
+        };
+        tap.capture(&[0.5; 3 * RECORD_CHUNK_SAMPLES]);
+        assert_eq!(
+            tap.dropped_samples.load(Ordering::Relaxed),
+            2 * RECORD_CHUNK_SAMPLES
+        );
+
+        let mut samples = Vec::new();
+        drain_chunks(&mut consumer, &mut samples);
+        assert_eq!(samples, vec![0.5; RECORD_CHUNK_SAMPLES]);
+    }
+
+    #[test]
+    fn test_event_log_csv() {
+        let mut log = EventLog::new();
+        log.record_at(0.5, 0, "c4 sine a:0.787 cl");
+        log.record_at(1.25, 2, "");
+        assert_eq!(
+            log.to_csv(),
+            "seconds,channel,cell\n0.500,1,c4 sine a:0.787 cl\n1.250,3,\n"
+        );
+    }
+
+    #[test]
+    fn test_song_capture() {
+        assert_eq!(EventLog::new().to_song_csv("take", 120.0, 4), None);
+
+        // 120 BPM at 4 rows per beat: a row is 0.125 s
+        let mut log = EventLog::new();
+        log.record_at(2.0, 0, "c4 sine a:1 cl");
+        log.record_at(2.01, 2, "e4 sine a:1 cl");
+        log.record_at(2.26, 0, "a:0.5 cl"); // rounds to row 2
+        log.record_at(2.49, 0, ""); // rounds to row 4
+        log.record_at(2.51, 2, ""); // also row 4
+        log.record_at(2.52, 2, "g4 sine a:1 cl"); // same row, moved down one
+        log.record_at(2.62, 2, ""); // row 5 is taken, so row 6
+
+        let csv = log.to_song_csv("take", 120.0, 4).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "Channel 1,Channel 3");
+        assert!(lines[1].starts_with("config, title: take, tempo_bpm: 120"));
+        assert_eq!(
+            lines[3..],
+            [
+                "c4 sine a:1 cl,e4 sine a:1 cl",
+                "-,-",
+                "a:0.5 cl,-",
+                "-,-",
+                ",",
+                ",g4 sine a:1 cl",
+                ",",
+            ]
+        );
+    }
+}
@@ -0,0 +1,479 @@
+// ============================================================================
+// SCALE.RS - Scale Registry and Scale-Constraint Mode
+// ============================================================================
+//
+// This module lets a song declare the scale (key) it is written in. Every
+// note trigger after the declaration is checked against the scale, and
+// notes that fall outside it are either snapped to the nearest scale note
+// or reported as warnings:
+//
+//   [scale] a minor          Snap out-of-scale notes into A minor
+//   [scale] d dorian warn    Leave notes alone, but warn about each one
+//   [scale] off              Stop checking
+//
+// Snapping is great for teaching (every note sounds "right") and for
+// keeping randomly chosen notes in key. Warn mode is handy for finding
+// typos in a hand-written part.
+//
+// Scales are checked using the written note names in standard 12-tone
+// terms, before any transpose or tuning is applied. A snapped note is
+// written the way the scale writes it (F major gets Bb, not A#), and the
+// nearest scale note is found in the active tuning, so in tunings where
+// C# and Db are different pitches the note lands where it sounds closest.
+//
+// HOW TO ADD A NEW SCALE:
+// 1. Add a new ScaleDefinition to SCALE_REGISTRY below
+// 2. List its notes as semitones above the root (0 to 11)
+// 3. The [scale] directive recognizes it automatically
+// ============================================================================
+
+use crate::helper::{PitchName, note_letter_to_semitone};
+use crate::tuning::Tuning;
+
+// ============================================================================
+// SCALE DEFINITION (REGISTRY PATTERN)
+// ============================================================================
+
+/// Defines a scale type by the semitones its notes sit above the root
+#[derive(Clone)]
+pub struct ScaleDefinition {
+    /// Primary name of the scale (used in [scale] directives)
+    pub name: &'static str,
+
+    /// Alternative names that also work
+    pub aliases: &'static [&'static str],
+
+    /// Semitones above the root for each note in the scale (0 to 11)
+    pub intervals: &'static [i32],
+}
+
+/// Master registry of all known scales
+pub static SCALE_REGISTRY: &[ScaleDefinition] = &[
+    ScaleDefinition {
+        name: "major",
+        aliases: &["ionian", "maj"],
+        intervals: &[0, 2, 4, 5, 7, 9, 11],
+    },
+    ScaleDefinition {
+        name: "minor",
+        aliases: &["aeolian", "natural_minor", "min"],
+        intervals: &[0, 2, 3, 5, 7, 8, 10],
+    },
+    ScaleDefinition {
+        name: "harmonic_minor",
+        aliases: &["harmonic"],
+        intervals: &[0, 2, 3, 5, 7, 8, 11],
+    },
+    ScaleDefinition {
+        name: "melodic_minor",
+        aliases: &["melodic"],
+        intervals: &[0, 2, 3, 5, 7, 9, 11],
+    },
+    ScaleDefinition {
+        name: "dorian",
+        aliases: &[],
+        intervals: &[0, 2, 3, 5, 7, 9, 10],
+    },
+    ScaleDefinition {
+        name: "phrygian",
+        aliases: &[],
+        intervals: &[0, 1, 3, 5, 7, 8, 10],
+    },
+    ScaleDefinition {
+        name: "lydian",
+        aliases: &[],
+        intervals: &[0, 2, 4, 6, 7, 9, 11],
+    },
+    ScaleDefinition {
+        name: "mixolydian",
+        aliases: &[],
+        intervals: &[0, 2, 4, 5, 7, 9, 10],
+    },
+    ScaleDefinition {
+        name: "locrian",
+        aliases: &[],
+        intervals: &[0, 1, 3, 5, 6, 8, 10],
+    },
+    ScaleDefinition {
+        name: "pentatonic",
+        aliases: &["major_pentatonic"],
+        intervals: &[0, 2, 4, 7, 9],
+    },
+    ScaleDefinition {
+        name: "minor_pentatonic",
+        aliases: &[],
+        intervals: &[0, 3, 5, 7, 10],
+    },
+    ScaleDefinition {
+        name: "blues",
+        aliases: &[],
+        intervals: &[0, 3, 5, 6, 7, 10],
+    },
+    ScaleDefinition {
+        name: "whole_tone",
+        aliases: &["whole"],
+        intervals: &[0, 2, 4, 6, 8, 10],
+    },
+    ScaleDefinition {
+        name: "chromatic",
+        aliases: &[],
+        intervals: &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+    },
+];
+
+/// Looks up a scale by name or alias (case-insensitive)
+/// Spaces and dashes count as underscores, so "harmonic minor" works too
+pub fn find_scale_by_name(name: &str) -> Option<&'static ScaleDefinition> {
+    let name_normalized = name.trim().to_lowercase().replace([' ', '-'], "_");
+    SCALE_REGISTRY
+        .iter()
+        .find(|scale| scale.name == name_normalized || scale.aliases.contains(&&*name_normalized))
+}
+
+// ============================================================================
+// SCALE CONSTRAINT
+// ============================================================================
+
+/// What to do with a note that is outside the declared scale
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScaleMode {
+    /// Move the note to the nearest note in the scale
+    Snap,
+
+    /// Keep the note, but report a parser warning
+    Warn,
+}
+
+/// A declared scale: root note, scale type, and what to do with wrong notes
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScaleConstraint {
+    /// Which notes are in the scale, indexed by semitones above C
+    pub allowed_pitch_classes: [bool; 12],
+
+    /// How each note is written (letter and accidental), indexed by
+    /// semitones above C (see scale_spellings)
+    pub spellings: [(char, i32); 12],
+
+    /// Human-readable name like "A minor" (for messages)
+    pub display_name: String,
+
+    /// Snap or warn
+    pub mode: ScaleMode,
+}
+
+impl ScaleConstraint {
+    /// Parses the text after a `[scale]` directive
+    ///
+    /// Format: <root> <scale name> [snap|warn]
+    /// Examples: "a minor", "F# major warn", "c harmonic minor"
+    ///
+    /// Returns Ok(None) for "off" or "none", which turns checking off.
+    pub fn parse_directive(text: &str) -> Result<Option<Self>, String> {
+        let mut words: Vec<&str> = text.split_whitespace().collect();
+
+        match words.first().map(|word| word.to_lowercase()) {
+            None => return Err("Missing scale (e.g. 'a minor' or 'c major warn')".to_string()),
+            Some(word) if word == "off" || word == "none" => return Ok(None),
+            _ => {}
+        }
+
+        // Optional mode at the end
+        let mode = match words.last().map(|word| word.to_lowercase()).as_deref() {
+            Some("warn") => {
+                words.pop();
+                ScaleMode::Warn
+            }
+            Some("snap") => {
+                words.pop();
+                ScaleMode::Snap
+            }
+            _ => ScaleMode::Snap,
+        };
+
+        let Some(&root_text) = words.first() else {
+            return Err("Missing scale root before the mode".to_string());
+        };
+        let root = parse_root(root_text).ok_or_else(|| {
+            format!(
+                "Invalid scale root '{}'. Use a note like 'a' or 'f#'",
+                root_text
+            )
+        })?;
+        let root_semitone = root_pitch_class(root);
+
+        let scale_name = words[1..].join("_");
+        if scale_name.is_empty() {
+            return Err(format!("Missing scale type after '{}'", root_text));
+        }
+        let definition = find_scale_by_name(&scale_name)
+            .ok_or_else(|| format!("Unknown scale '{}'", words[1..].join(" ")))?;
+
+        let mut allowed_pitch_classes = [false; 12];
+        for interval in definition.intervals {
+            allowed_pitch_classes[(root_semitone + interval).rem_euclid(12) as usize] = true;
+        }
+
+        Ok(Some(Self {
+            allowed_pitch_classes,
+            spellings: scale_spellings(root, definition.intervals),
+            display_name: format!(
+                "{} {}",
+                capitalize(root_text),
+                definition.name.replace('_', " ")
+            ),
+            mode,
+        }))
+    }
+
+    /// Returns true if the note belongs to the scale
+    pub fn contains(&self, pitch: &PitchName) -> bool {
+        let pitch_class = pitch.semitones_from_c0().rem_euclid(12);
+        self.allowed_pitch_classes[pitch_class as usize]
+    }
+
+    /// Returns the nearest note in the scale (the note itself if it fits),
+    /// written the way the scale writes it
+    /// Distances are measured in the given tuning; when two scale notes are
+    /// equally close, the lower one wins. The note's cents offset is kept.
+    pub fn snap(&self, pitch: &PitchName, tuning: &Tuning) -> PitchName {
+        if self.contains(pitch) {
+            return *pitch;
+        }
+
+        let semitones = pitch.semitones_from_c0();
+        let pitch_cents = tuning.note_cents_from_c4(pitch);
+        let distance =
+            |candidate: &PitchName| match (pitch_cents, tuning.note_cents_from_c4(candidate)) {
+                (Some(pitch_cents), Some(candidate_cents)) => (candidate_cents - pitch_cents).abs(),
+                _ => 100.0 * (candidate.semitones_from_c0() - semitones).abs() as f32,
+            };
+
+        // min_by keeps the first of equal distances, which is the lower note
+        (semitones - 6..=semitones + 6)
+            .filter(|candidate| self.allowed_pitch_classes[candidate.rem_euclid(12) as usize])
+            .map(|candidate| spelled_pitch(&self.spellings, candidate, pitch.cents))
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            // Unreachable for any scale with at least one note
+            .unwrap_or(*pitch)
+    }
+}
+
+// ============================================================================
+// SCALE NOTES
+// ============================================================================
+
+/// Lists the notes of a scale like "a minor", going up from its root in
+/// the given octave for the given number of octaves ("a minor", 3, 1 ->
+/// a3 b3 c4 d4 e4 f4 g4). Used by song scripts to pick notes in key.
+pub fn scale_note_names(text: &str, octave: i32, octaves: i32) -> Result<Vec<String>, String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let Some(&root_text) = words.first() else {
+        return Err("Missing scale (e.g. 'a minor')".to_string());
+    };
+    let root = parse_root(root_text).ok_or_else(|| {
+        format!(
+            "Invalid scale root '{}'. Use a note like 'a' or 'f#'",
+            root_text
+        )
+    })?;
+    let definition = find_scale_by_name(&words[1..].join("_"))
+        .ok_or_else(|| format!("Unknown scale '{}'", words[1..].join(" ")))?;
+    let spellings = scale_spellings(root, definition.intervals);
+
+    let root_semitones = octave * 12 + root_pitch_class(root);
+    Ok((0..octaves.max(1))
+        .flat_map(|octave| {
+            definition
+                .intervals
+                .iter()
+                .map(move |interval| root_semitones + octave * 12 + interval)
+        })
+        .map(|semitones| spelled_pitch(&spellings, semitones, 0.0).to_string())
+        .collect())
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+/// Parses a scale root like "a", "F#", or "bb" into its letter and
+/// accidental (+1 sharp, -1 flat)
+fn parse_root(text: &str) -> Option<(char, i32)> {
+    let lower = text.to_lowercase();
+    let mut chars = lower.chars();
+    let letter = chars.next()?;
+    note_letter_to_semitone(letter)?;
+    let accidental = match chars.as_str() {
+        "" => 0,
+        "#" => 1,
+        "b" => -1,
+        _ => return None,
+    };
+    Some((letter, accidental))
+}
+
+/// Semitones above C of a root parsed by parse_root
+fn root_pitch_class((letter, accidental): (char, i32)) -> i32 {
+    (note_letter_to_semitone(letter).unwrap_or(0) + accidental).rem_euclid(12)
+}
+
+/// Names every note for a scale on the given root, indexed by semitones
+/// above C
+///
+/// Seven-note scales use each letter once, starting from the root's
+/// letter (F major has Bb, D harmonic minor has C#). Other scales, and the
+/// notes outside the scale, take flats on a flat root or F and sharps
+/// otherwise.
+fn scale_spellings(root: (char, i32), intervals: &[i32]) -> [(char, i32); 12] {
+    const LETTERS: [char; 7] = ['c', 'd', 'e', 'f', 'g', 'a', 'b'];
+    let (root_letter, root_accidental) = root;
+    let letter_index = |letter: char| LETTERS.iter().position(|&other| other == letter);
+
+    let uses_flats = root_accidental < 0 || root == ('f', 0);
+    let mut spellings = [('c', 0); 12];
+    for (pitch_class, spelling) in (0..).zip(spellings.iter_mut()) {
+        let sharp = PitchName::from_semitones_from_c0(pitch_class, 0.0);
+        *spelling = match letter_index(sharp.letter) {
+            Some(index) if uses_flats && sharp.accidental == 1 => (LETTERS[(index + 1) % 7], -1),
+            _ => (sharp.letter, sharp.accidental),
+        };
+    }
+
+    if let (7, Some(root_index)) = (intervals.len(), letter_index(root_letter)) {
+        let root_semitone = root_pitch_class(root);
+        for (degree, interval) in intervals.iter().enumerate() {
+            let letter = LETTERS[(root_index + degree) % 7];
+            let pitch_class = (root_semitone + interval).rem_euclid(12);
+            let letter_semitone = note_letter_to_semitone(letter).unwrap_or(0);
+            let accidental = (pitch_class - letter_semitone + 6).rem_euclid(12) - 6;
+            spellings[pitch_class as usize] = (letter, accidental);
+        }
+    }
+    spellings
+}
+
+/// The note this many semitones above C0, written with the given spellings
+/// (Cb4 is written in octave 4, though it sounds as B3)
+fn spelled_pitch(spellings: &[(char, i32); 12], semitones: i32, cents: f32) -> PitchName {
+    let (letter, accidental) = spellings[semitones.rem_euclid(12) as usize];
+    let letter_semitone = note_letter_to_semitone(letter).unwrap_or(0);
+    PitchName {
+        letter,
+        accidental,
+        octave: (semitones - letter_semitone - accidental).div_euclid(12),
+        cents,
+    }
+}
+
+/// Uppercases the first letter of a root name ("f#" -> "F#")
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::parse_pitch_name;
+
+    #[test]
+    fn test_parse_scale_directive() {
+        let scale = ScaleConstraint::parse_directive("a minor")
+            .unwrap()
+            .unwrap();
+        assert_eq!(scale.display_name, "A minor");
+        assert_eq!(scale.mode, ScaleMode::Snap);
+        assert!(scale.contains(&parse_pitch_name("c4").unwrap()));
+        assert!(!scale.contains(&parse_pitch_name("c#4").unwrap()));
+
+        let scale = ScaleConstraint::parse_directive("Eb harmonic minor warn")
+            .unwrap()
+            .unwrap();
+        assert_eq!(scale.mode, ScaleMode::Warn);
+        assert!(scale.contains(&parse_pitch_name("d4").unwrap()));
+
+        assert_eq!(ScaleConstraint::parse_directive("off"), Ok(None));
+        assert!(ScaleConstraint::parse_directive("h minor").is_err());
+        assert!(ScaleConstraint::parse_directive("a wobbly").is_err());
+    }
+
+    #[test]
+    fn test_snap_to_scale() {
+        let scale = ScaleConstraint::parse_directive("c major")
+            .unwrap()
+            .unwrap();
+
+        let standard = Tuning::default();
+
+        // C# is equally close to C and D: the lower note wins
+        let snapped = scale.snap(&parse_pitch_name("c#4+10c").unwrap(), &standard);
+        assert_eq!(
+            snapped.semitones_from_c0(),
+            parse_pitch_name("c4").unwrap().semitones_from_c0()
+        );
+        assert_eq!(snapped.cents, 10.0);
+
+        // Notes already in the scale are untouched
+        let e4 = parse_pitch_name("e4").unwrap();
+        assert_eq!(scale.snap(&e4, &standard), e4);
+
+        // Snapping can cross the octave boundary
+        let scale = ScaleConstraint::parse_directive("d minor_pentatonic")
+            .unwrap()
+            .unwrap();
+        let snapped = scale.snap(&parse_pitch_name("b3").unwrap(), &standard);
+        assert_eq!(snapped, parse_pitch_name("c4").unwrap());
+    }
+
+    #[test]
+    fn test_snap_spells_notes_like_the_scale() {
+        let snap = |scale: &str, note: &str, tuning: &Tuning| {
+            ScaleConstraint::parse_directive(scale)
+                .unwrap()
+                .unwrap()
+                .snap(&parse_pitch_name(note).unwrap(), tuning)
+                .to_string()
+        };
+        let standard = Tuning::default();
+        let nineteen_edo = Tuning::equal_division(19);
+
+        // F major writes its fourth as Bb, which in 19-EDO is not A#
+        assert_eq!(snap("f major", "b4", &standard), "bb4");
+        assert_eq!(snap("f major", "b4", &nineteen_edo), "bb4");
+        assert_eq!(snap("d harmonic minor", "c4", &standard), "c#4");
+        assert_eq!(snap("gb major", "c4", &standard), "cb4");
+
+        // 12-TET splits Db evenly between C and D; in 19-EDO it is one
+        // step below D and two above C
+        assert_eq!(snap("c major", "db4", &standard), "c4");
+        assert_eq!(snap("c major", "db4", &nineteen_edo), "d4");
+        assert_eq!(snap("c major", "c#4", &nineteen_edo), "c4");
+    }
+
+    #[test]
+    fn test_scale_note_names() {
+        assert_eq!(
+            scale_note_names("a minor", 3, 1).unwrap(),
+            ["a3", "b3", "c4", "d4", "e4", "f4", "g4"]
+        );
+        assert_eq!(
+            scale_note_names("F# major pentatonic", 2, 2).unwrap(),
+            [
+                "f#2", "g#2", "a#2", "c#3", "d#3", "f#3", "g#3", "a#3", "c#4", "d#4"
+            ]
+        );
+        assert_eq!(
+            scale_note_names("bb major", 3, 1).unwrap(),
+            ["bb3", "c4", "d4", "eb4", "f4", "g4", "a4"]
+        );
+        assert!(scale_note_names("a wobbly", 3, 1).is_err());
+        assert!(scale_note_names("", 3, 1).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+// ============================================================================
+// SCRIPT.RS - Song Scripts (Generated Rows)
+// ============================================================================
+//
+// A song can include a script that writes rows for it, for parts that are
+// easier to describe than to type: euclidean rhythms, random walks through
+// a scale, arpeggios that change every bar, ...
+//
+//   V0,V1
+//   [script seed:42]
+//   let notes = scale_notes("a minor", 3);
+//   let step = 0;
+//   for hit in euclid(5, 16) {
+//       step = (step + rand_int(-1, 1)).max(0).min(notes.len() - 1);
+//       row([notes[step] + " sine", if hit { "noise a:0.4" } else { "" }]);
+//   }
+//   [end]
+//
+// Scripts are written in Rhai (https://rhai.rs), a small language that
+// looks like Rust and JavaScript. It is built into the tracker, so there
+// is nothing else to install.
+//
+// HOW IT WORKS:
+// The parser collects the lines between "[script]" and "[end]" and runs
+// them as soon as it reaches "[end]", while the song is being parsed.
+// Every row() call adds one line of cells, exactly as if it had been
+// typed where the script is, so macros, presets, and [scale] still apply.
+// Scripts can also write directives, like row("[scale] a minor").
+//
+// FUNCTIONS SCRIPTS CAN CALL:
+// - row("c4 sine,,noise")      Add a row written as CSV text
+// - row(["c4 sine", "", "noise"])  Add a row from a list of cells
+// - rand()                     Random number from 0 to 1
+// - rand_int(1, 6)             Random whole number from 1 to 6
+// - chance(0.3)                True 30% of the time
+// - pick(["c4", "e4", "g4"])   One of the items at random
+// - euclid(5, 16)              16 true/false steps with 5 hits spread
+//                              as evenly as possible
+// - scale_notes("a minor", 3)  Note names of A minor from A3 up an octave
+//                              (scale_notes("a minor", 3, 2): two octaves)
+// - print(...)                 Logs a message (RUST_LOG=parser=debug)
+//
+// RANDOM SEED:
+// The random functions replay the same numbers for the same seed, so
+// "[script seed:42]" always writes the same rows. Without a seed the
+// script gets a new one each time the song is loaded; the seed it used
+// is kept in SongData::script_seeds and shown as a warning, so a take
+// you like can be repeated by writing that seed into the file.
+//
+// LIMITS:
+// A script may run MAX_SCRIPT_OPERATIONS steps and write MAX_SCRIPT_ROWS
+// rows, so a script that never ends reports an error instead of hanging.
+// ============================================================================
+
+use crate::helper::RandomNumberGenerator;
+use crate::scale::scale_note_names;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, FLOAT, INT};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Most steps a script may run before it is stopped
+pub const MAX_SCRIPT_OPERATIONS: u64 = 50_000_000;
+
+/// Most rows one script may write
+pub const MAX_SCRIPT_ROWS: usize = 100_000;
+
+/// A seed for scripts that don't name one (different every run)
+pub fn random_script_seed() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(1, |elapsed| {
+            elapsed.subsec_nanos() ^ elapsed.as_secs() as u32
+        })
+}
+
+/// Runs a song script and returns the lines it wrote, in order
+///
+/// The same source and seed always give the same lines. Errors carry
+/// Rhai's message, which names the line within the script.
+pub fn run_song_script(source: &str, seed: u32) -> Result<Vec<String>, String> {
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let random = Rc::new(RefCell::new(RandomNumberGenerator::new(seed)));
+
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_SCRIPT_OPERATIONS);
+    engine.on_print(|text| log::debug!(target: "parser", "[script] {}", text));
+
+    let written = lines.clone();
+    engine.register_fn("row", move |text: &str| {
+        add_line(&written, text.to_string())
+    });
+    let written = lines.clone();
+    engine.register_fn("row", move |cells: Array| {
+        let cells: Vec<String> = cells.iter().map(Dynamic::to_string).collect();
+        add_line(&written, cells.join(","))
+    });
+
+    let generator = random.clone();
+    engine.register_fn("rand", move || next_fraction(&generator));
+    let generator = random.clone();
+    engine.register_fn("rand_int", move |low: INT, high: INT| {
+        let (low, high) = (low.min(high), low.max(high));
+        let range = (high - low) as u64 + 1;
+        low + (generator.borrow_mut().next_u32() as u64 % range) as INT
+    });
+    let generator = random.clone();
+    engine.register_fn("chance", move |probability: FLOAT| {
+        next_fraction(&generator) < probability
+    });
+    let generator = random;
+    engine.register_fn("pick", move |items: Array| {
+        if items.is_empty() {
+            return Dynamic::UNIT;
+        }
+        let index = generator.borrow_mut().next_u32() as usize % items.len();
+        items[index].clone()
+    });
+
+    engine.register_fn("euclid", |hits: INT, steps: INT| -> Array {
+        euclid_pattern(hits.max(0) as usize, steps.max(0) as usize)
+            .into_iter()
+            .map(Dynamic::from)
+            .collect()
+    });
+    engine.register_fn("scale_notes", |scale: &str, octave: INT| {
+        scale_notes(scale, octave, 1)
+    });
+    engine.register_fn("scale_notes", scale_notes);
+
+    engine
+        .run(source)
+        .map_err(|error| format!("Script failed: {}", error))?;
+
+    Ok(lines.take())
+}
+
+/// Spreads `hits` onsets over `steps` as evenly as possible, starting with
+/// a hit (3 over 8 is x..x..x., the tresillo)
+pub fn euclid_pattern(hits: usize, steps: usize) -> Vec<bool> {
+    let hits = hits.min(steps);
+    (0..steps).map(|step| step * hits % steps < hits).collect()
+}
+
+/// Adds one written line, unless the script has written too many
+fn add_line(lines: &RefCell<Vec<String>>, line: String) -> Result<(), Box<EvalAltResult>> {
+    let mut lines = lines.borrow_mut();
+    if lines.len() >= MAX_SCRIPT_ROWS {
+        return Err(format!("a script can write at most {} rows", MAX_SCRIPT_ROWS).into());
+    }
+    lines.push(line);
+    Ok(())
+}
+
+/// A random number from 0 up to (not including) 1
+fn next_fraction(generator: &RefCell<RandomNumberGenerator>) -> FLOAT {
+    generator.borrow_mut().next_u32() as FLOAT / (u32::MAX as FLOAT + 1.0)
+}
+
+/// scale_notes() for scripts: the scale's note names as a Rhai array
+fn scale_notes(scale: &str, octave: INT, octaves: INT) -> Result<Array, Box<EvalAltResult>> {
+    let names = scale_note_names(scale, octave as i32, octaves as i32)?;
+    Ok(names.into_iter().map(Dynamic::from).collect())
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_euclid_pattern() {
+        let text = |hits, steps| -> String {
+            euclid_pattern(hits, steps)
+                .iter()
+                .map(|&hit| if hit { 'x' } else { '.' })
+                .collect()
+        };
+        assert_eq!(text(3, 8), "x..x..x.");
+        assert_eq!(text(4, 16), "x...x...x...x...");
+        assert_eq!(text(5, 16).matches('x').count(), 5);
+        assert_eq!(text(9, 4), "xxxx");
+        assert_eq!(text(0, 4), "....");
+        assert_eq!(text(3, 0), "");
+    }
+
+    #[test]
+    fn test_run_song_script() {
+        let source = r#"
+            let notes = scale_notes("c major", 4);
+            for hit in euclid(3, 8) {
+                row([pick(notes) + " sine", if hit { "noise" } else { "" }]);
+            }
+            row("-,.");
+        "#;
+        let lines = run_song_script(source, 42).unwrap();
+        assert_eq!(lines.len(), 9);
+        assert!(lines[0].ends_with(" sine,noise"));
+        assert!(lines[1].ends_with(" sine,"));
+        assert_eq!(lines[8], "-,.");
+
+        // The same seed writes the same rows; another seed picks other notes
+        assert_eq!(run_song_script(source, 42).unwrap(), lines);
+        assert!((1..20).any(|seed| run_song_script(source, seed).unwrap() != lines));
+
+        // Errors name the line in the script
+        let error = run_song_script("row(\"c4\");\nrow(nope);", 1).unwrap_err();
+        assert!(error.contains("line 2"), "{}", error);
+        let error = run_song_script("scale_notes(\"a wobbly\", 3);", 1).unwrap_err();
+        assert!(error.contains("Unknown scale"), "{}", error);
+
+        // A script that never ends is stopped
+        assert!(run_song_script("loop {}", 1).is_err());
+    }
+}
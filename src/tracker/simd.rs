@@ -0,0 +1,320 @@
+// ============================================================================
+// SIMD.RS - Vectorized Inner Loops
+// ============================================================================
+//
+// The hottest loops in the synth (oscillators and channel mixing) do the
+// same math on every sample of a block. Modern CPUs can do that math on
+// 4 or 8 samples at once with SIMD ("single instruction, multiple data")
+// instructions.
+//
+// HOW IT WORKS:
+// Each loop here walks its buffers LANES samples at a time, using plain
+// fixed-size arrays. The compiler turns those into SIMD instructions. Every
+// loop is compiled twice:
+//
+//   - a portable version that runs on any CPU (SSE2 on x86_64)
+//   - an AVX2 version (8 floats per instruction) on x86_64
+//
+// The AVX2 version is picked at runtime if the CPU supports it. Both
+// versions do exactly the same arithmetic, so the output is identical
+// whichever one runs.
+//
+// Keep the per-lane functions free of branches that depend on earlier
+// samples; anything that needs the previous sample (phases, envelopes,
+// filters) stays in ordinary loops.
+//
+// LFOs that need one sine at a time use lookup_sine, an interpolated table
+// built at compile time.
+// ============================================================================
+
+use crate::helper::{TWO_PI, lerp};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+/// Samples handled per step (one AVX2 register of f32s)
+pub const LANES: usize = 8;
+
+// ============================================================================
+// RUNTIME FEATURE DETECTION
+// ============================================================================
+
+/// Returns true if the AVX2 versions of the loops can run on this CPU
+#[inline]
+fn has_avx2() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        // The standard library caches the CPUID result after the first call
+        std::is_x86_feature_detected!("avx2")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+/// Name of the instruction set the loops use on this CPU (for startup info)
+pub fn instruction_set_name() -> &'static str {
+    if has_avx2() { "avx2" } else { "portable" }
+}
+
+// ============================================================================
+// BLOCK OPERATIONS
+// ============================================================================
+
+/// Applies a per-sample function: output[i] = function(input[i])
+/// Used by the oscillators to turn a block of phases into samples.
+#[inline]
+pub fn map_block(input: &[f32], output: &mut [f32], function: impl Fn(f32) -> f32 + Copy) {
+    #[cfg(target_arch = "x86_64")]
+    if has_avx2() {
+        // SAFETY: the CPU supports AVX2 (checked just above)
+        unsafe { map_block_avx2(input, output, function) };
+        return;
+    }
+    map_block_lanes(input, output, function);
+}
+
+/// Adds input into output with a gain that ramps across the block:
+/// output[i] += input[i] * lerp(start_gain, end_gain, (i + 1) / length)
+///
+/// The ramp ends exactly on end_gain, so the next block can start there.
+#[inline]
+pub fn add_ramped(input: &[f32], output: &mut [f32], start_gain: f32, end_gain: f32) {
+    #[cfg(target_arch = "x86_64")]
+    if has_avx2() {
+        // SAFETY: the CPU supports AVX2 (checked just above)
+        unsafe { add_ramped_avx2(input, output, start_gain, end_gain) };
+        return;
+    }
+    add_ramped_lanes(input, output, start_gain, end_gain);
+}
+
+// ============================================================================
+// LANE LOOPS (SHARED BY EVERY INSTRUCTION SET)
+// ============================================================================
+
+#[inline(always)]
+fn map_block_lanes(input: &[f32], output: &mut [f32], function: impl Fn(f32) -> f32 + Copy) {
+    let length = input.len().min(output.len());
+    let mut input_chunks = input[..length].chunks_exact(LANES);
+    let mut output_chunks = output[..length].chunks_exact_mut(LANES);
+
+    for (input_chunk, output_chunk) in (&mut input_chunks).zip(&mut output_chunks) {
+        let input_lanes = as_lanes(input_chunk);
+        let output_lanes = as_lanes_mut(output_chunk);
+        for lane in 0..LANES {
+            output_lanes[lane] = function(input_lanes[lane]);
+        }
+    }
+
+    // Leftover samples when the length is not a multiple of LANES
+    for (input_sample, output_sample) in input_chunks
+        .remainder()
+        .iter()
+        .zip(output_chunks.into_remainder())
+    {
+        *output_sample = function(*input_sample);
+    }
+}
+
+#[inline(always)]
+fn add_ramped_lanes(input: &[f32], output: &mut [f32], start_gain: f32, end_gain: f32) {
+    let length = input.len().min(output.len());
+    if length == 0 {
+        return;
+    }
+    let step = 1.0 / length as f32;
+
+    let mut input_chunks = input[..length].chunks_exact(LANES);
+    let mut output_chunks = output[..length].chunks_exact_mut(LANES);
+    let mut index = 0;
+
+    for (input_chunk, output_chunk) in (&mut input_chunks).zip(&mut output_chunks) {
+        let input_lanes = as_lanes(input_chunk);
+        let output_lanes = as_lanes_mut(output_chunk);
+        let first_position = (index + 1) as f32;
+        for lane in 0..LANES {
+            let gain = lerp(
+                start_gain,
+                end_gain,
+                (first_position + LANE_OFFSETS[lane]) * step,
+            );
+            output_lanes[lane] += input_lanes[lane] * gain;
+        }
+        index += LANES;
+    }
+
+    for (input_sample, output_sample) in input_chunks
+        .remainder()
+        .iter()
+        .zip(output_chunks.into_remainder())
+    {
+        index += 1;
+        *output_sample += *input_sample * lerp(start_gain, end_gain, index as f32 * step);
+    }
+}
+
+/// 0, 1, 2, ... as floats, so ramps never convert integers inside the loop
+const LANE_OFFSETS: [f32; LANES] = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0];
+
+/// Views a chunk from chunks_exact(LANES) as a fixed-size array, which
+/// lets the compiler drop bounds checks and use vector instructions
+#[inline(always)]
+fn as_lanes(chunk: &[f32]) -> &[f32; LANES] {
+    chunk.try_into().expect("chunk is LANES long")
+}
+
+#[inline(always)]
+fn as_lanes_mut(chunk: &mut [f32]) -> &mut [f32; LANES] {
+    chunk.try_into().expect("chunk is LANES long")
+}
+
+// AVX2 builds of the lane loops. The bodies are inlined here, so the
+// compiler is free to use 256-bit instructions for them.
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn map_block_avx2(input: &[f32], output: &mut [f32], function: impl Fn(f32) -> f32 + Copy) {
+    map_block_lanes(input, output, function);
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn add_ramped_avx2(input: &[f32], output: &mut [f32], start_gain: f32, end_gain: f32) {
+    add_ramped_lanes(input, output, start_gain, end_gain);
+}
+
+// ============================================================================
+// VECTOR-FRIENDLY MATH
+// ============================================================================
+
+/// Sine of any phase (in radians), without calling the C math library
+///
+/// f32::sin cannot be vectorized, so the oscillators use this instead. It
+/// wraps the phase into -PI..PI, folds it into -PI/2..PI/2, and evaluates
+/// the Taylor series up to x^11 there (error below 1e-6).
+#[inline(always)]
+pub fn fast_sine(phase: f32) -> f32 {
+    // Wrap into -PI..PI
+    let x = phase - TWO_PI * (phase * (1.0 / TWO_PI) + 0.5).floor();
+
+    // sin(PI - x) = sin(x), so fold the outer quarters back in
+    let x = if x > FRAC_PI_2 {
+        PI - x
+    } else if x < -FRAC_PI_2 {
+        -PI - x
+    } else {
+        x
+    };
+
+    let x2 = x * x;
+    x * (1.0
+        + x2 * (-1.0 / 6.0
+            + x2 * (1.0 / 120.0
+                + x2 * (-1.0 / 5040.0 + x2 * (1.0 / 362_880.0 + x2 * (-1.0 / 39_916_800.0))))))
+}
+
+/// Entries in one cycle of the sine table (a power of two)
+const SINE_TABLE_SIZE: usize = 1024;
+
+/// One cycle of sine, plus a copy of the first entry so interpolation
+/// never has to wrap. Built at compile time, so lookups need no
+/// initialization check.
+static SINE_TABLE: [f32; SINE_TABLE_SIZE + 1] = build_sine_table();
+
+/// Fills the sine table using a Taylor series in f64 (f32::sin is not
+/// available in const code)
+const fn build_sine_table() -> [f32; SINE_TABLE_SIZE + 1] {
+    let mut table = [0.0; SINE_TABLE_SIZE + 1];
+    let mut index = 0;
+    while index <= SINE_TABLE_SIZE {
+        // Angle in -PI..PI so the series converges quickly
+        let mut x = index as f64 * std::f64::consts::TAU / SINE_TABLE_SIZE as f64;
+        if x > std::f64::consts::PI {
+            x -= std::f64::consts::TAU;
+        }
+        let mut term = x;
+        let mut sum = x;
+        let mut power = 1;
+        while power < 30 {
+            term *= -x * x / ((power + 1) * (power + 2)) as f64;
+            sum += term;
+            power += 2;
+        }
+        table[index] = sum as f32;
+        index += 1;
+    }
+    table
+}
+
+/// Sine of any phase (in radians) from an interpolated lookup table
+///
+/// Cheaper than f32::sin and accurate to about 5e-6, which is plenty for
+/// LFOs (vibrato, tremolo, chorus). Oscillators keep using fast_sine,
+/// which is more accurate.
+#[inline]
+pub fn lookup_sine(phase: f32) -> f32 {
+    let position = phase * (SINE_TABLE_SIZE as f32 / TWO_PI);
+    // Truncate and step down for negative phases. This is floor() without
+    // the library call it compiles to on baseline x86_64.
+    let truncated = position as i32;
+    let whole = truncated - (position < truncated as f32) as i32;
+    // Masking wraps any phase (even negative ones) into one cycle
+    let index = whole as usize & (SINE_TABLE_SIZE - 1);
+    lerp(
+        SINE_TABLE[index],
+        SINE_TABLE[index + 1],
+        position - whole as f32,
+    )
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fast_sine_accuracy() {
+        for index in -2000..2000 {
+            let phase = index as f32 * 0.01;
+            assert!(
+                (fast_sine(phase) - phase.sin()).abs() < 1e-6,
+                "phase {}",
+                phase
+            );
+        }
+    }
+
+    #[test]
+    fn test_lookup_sine_accuracy() {
+        for index in -20000..20000 {
+            let phase = index as f32 * 0.001_7;
+            assert!(
+                (lookup_sine(phase) - phase.sin()).abs() < 1e-5,
+                "phase {}",
+                phase
+            );
+        }
+    }
+
+    #[test]
+    fn test_block_operations_match_scalar_math() {
+        let input: Vec<f32> = (0..29).map(|index| index as f32 * 0.37 - 4.0).collect();
+
+        let mut mapped = vec![0.0; input.len()];
+        map_block(&input, &mut mapped, fast_sine);
+        let mut portable = vec![0.0; input.len()];
+        map_block_lanes(&input, &mut portable, fast_sine);
+        assert_eq!(mapped, portable);
+
+        let mut output = vec![1.0; input.len()];
+        add_ramped(&input, &mut output, 0.5, 1.5);
+        let step = 1.0 / input.len() as f32;
+        for (index, (sample, result)) in input.iter().zip(&output).enumerate() {
+            let expected = 1.0 + sample * lerp(0.5, 1.5, (index + 1) as f32 * step);
+            assert!((result - expected).abs() < 1e-6);
+        }
+    }
+}
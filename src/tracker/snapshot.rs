@@ -0,0 +1,440 @@
+// ============================================================================
+// SNAPSHOT.RS - Engine State Snapshots (Debugging)
+// ============================================================================
+//
+// When a song sounds different after a change, listening only tells you
+// THAT something changed. A snapshot tells you WHAT: it is every setting
+// the engine holds at one moment - each channel's note, instrument,
+// envelope, effects, and running slides, each group bus and the master
+// bus - written out as JSON:
+//
+//   tracker snapshot assets/song.csv --row 32 -o before.json
+//   (check out the other commit, build)
+//   tracker snapshot assets/song.csv --row 32 -o after.json
+//   tracker snapshot --diff before.json after.json
+//
+// The diff prints one line per value that differs, like
+//   channels[2].effects.amplitude: 0.5 -> 0.7
+// and exits nonzero if there were any, so it works in scripts too.
+//
+// HOW IT WORKS:
+// PlaybackEngine::snapshot_at_row renders silently up to a row (the same
+// fast-forward --start-row uses), starts that row so its cells are
+// dispatched, and copies the state into an EngineSnapshot before any of
+// the row's samples are played. Snapshots are plain data (serde), so
+// they can be saved, loaded again as JSON, and compared with
+// diff_snapshots, field by field.
+//
+// WHAT IS LEFT OUT:
+// Audio buffers (reverb, delay, and chorus lines) are far too big to be
+// useful in a text diff; their positions and settings are kept. LFO
+// phases and the random number state are kept, since they change the
+// sound from one render to the next.
+// ============================================================================
+
+use crate::channel::{Channel, EffectParameter};
+use crate::effects::ChannelEffectState;
+use crate::instruments::get_instrument_by_id;
+use crate::master_bus::MasterBus;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Everything the engine holds at one moment (see the top of the file)
+#[derive(Clone, Debug, Serialize)]
+pub struct EngineSnapshot {
+    /// Row about to play (0-indexed); its cells have been dispatched
+    pub row: usize,
+
+    /// Samples already played in that row
+    pub samples_into_row: u32,
+
+    /// Length of the row in samples (follows bpm commands)
+    pub samples_per_row: u32,
+
+    /// Time since the song started, in seconds
+    pub seconds: f64,
+
+    pub sample_rate: u32,
+
+    /// Whether the song has played through
+    pub finished: bool,
+
+    /// Samples left in a fade-out, if one is running
+    pub fade_out_samples_left: Option<u32>,
+
+    pub channels: Vec<ChannelSnapshot>,
+
+    /// Channel group buses, in the order the groups were defined
+    pub groups: Vec<BusSnapshot>,
+
+    pub master: BusSnapshot,
+}
+
+/// One channel's state
+#[derive(Clone, Debug, Serialize)]
+pub struct ChannelSnapshot {
+    /// Channel number (0-indexed)
+    pub channel: usize,
+
+    /// Whether a note is sounding (or releasing)
+    pub is_active: bool,
+
+    /// Whether mutes and solos let the channel be heard
+    pub audible: bool,
+    pub mute_gain: f32,
+
+    pub instrument: String,
+    pub instrument_parameters: Vec<f32>,
+    pub frequency_hz: f32,
+    pub phase: f32,
+    pub sub_phase: f32,
+
+    pub envelope: EnvelopeSnapshot,
+
+    /// Effect settings, as cells write them
+    pub effects: ChannelEffectState,
+
+    /// Where each LFO is in its cycle (0..1)
+    pub lfo_phases: LfoPhases,
+
+    /// Effect parameters still moving towards their targets
+    pub ramps: Vec<RampSnapshot>,
+
+    /// A pitch glide in progress: (from Hz, to Hz, seconds done, seconds long)
+    pub pitch_slide: Option<(f32, f32, f32, f32)>,
+
+    /// An instrument crossfade in progress: (from, to, seconds done,
+    /// seconds long)
+    pub instrument_crossfade: Option<(String, String, f32, f32)>,
+
+    pub seconds_per_beat: f32,
+    pub samples_played: u64,
+
+    /// State of the channel's noise generator
+    pub random_state: u32,
+}
+
+/// A channel's envelope
+#[derive(Clone, Debug, Serialize)]
+pub struct EnvelopeSnapshot {
+    pub envelope_id: usize,
+    pub phase: String,
+    pub amplitude: f32,
+    pub phase_elapsed_samples: u64,
+    pub phase_total_samples: u64,
+    pub attack_time_seconds: f32,
+    pub decay_time_seconds: f32,
+    pub sustain_level: f32,
+    pub release_time_seconds: Option<f32>,
+}
+
+/// The LFO phases ChannelEffectState doesn't serialize
+#[derive(Clone, Debug, Serialize)]
+pub struct LfoPhases {
+    pub vibrato: f32,
+    pub tremolo: f32,
+    pub wah: f32,
+    pub chorus: f32,
+}
+
+/// One effect parameter moving during a transition
+#[derive(Clone, Debug, Serialize)]
+pub struct RampSnapshot {
+    pub parameter: String,
+    pub start: f32,
+    pub target: f32,
+    pub elapsed_samples: u32,
+    pub duration_samples: u32,
+}
+
+/// The master bus or a group bus
+#[derive(Clone, Debug, Serialize)]
+pub struct BusSnapshot {
+    /// Group name (None for the master bus)
+    pub name: Option<String>,
+
+    pub amplitude: f32,
+    pub pan: f32,
+    pub wet: f32,
+
+    pub reverb1_enabled: bool,
+    pub reverb1_level: f32,
+    pub reverb1_room_size: f32,
+    pub reverb1_mix: f32,
+
+    pub reverb2_enabled: bool,
+    pub reverb2_level: f32,
+    pub reverb2_room_size: f32,
+    pub reverb2_decay: f32,
+    pub reverb2_damping: f32,
+    pub reverb2_mix: f32,
+    pub reverb2_predelay_ms: f32,
+
+    pub delay_enabled: bool,
+    pub delay_level: f32,
+    pub delay_time_samples: u32,
+    pub delay_glide_samples: f32,
+    pub delay_feedback: f32,
+    pub delay_sync_beats: Option<f32>,
+
+    pub chorus_enabled: bool,
+    pub chorus_level: f32,
+    pub chorus_mix: f32,
+    pub chorus_rate_hz: f32,
+    pub chorus_depth_ms: f32,
+    pub chorus_stereo_spread: f32,
+    pub chorus_phase: f32,
+
+    /// A transition in progress: (samples done, samples long)
+    pub transition: Option<(u32, u32)>,
+
+    pub seconds_per_beat: f32,
+
+    /// How long the bus's output has been silent, in samples
+    pub quiet_samples: u32,
+}
+
+/// Name of an instrument ID (the number itself if it isn't known)
+fn instrument_name(id: usize) -> String {
+    get_instrument_by_id(id).map_or_else(|| id.to_string(), |instrument| instrument.name.into())
+}
+
+impl ChannelSnapshot {
+    /// Copies a channel's state
+    pub fn new(channel: &Channel) -> Self {
+        let envelope = &channel.envelope;
+        let effects = &channel.effects;
+        let ramps = EffectParameter::ALL
+            .iter()
+            .zip(&channel.effect_ramps)
+            .filter_map(|(parameter, ramp)| {
+                ramp.map(|ramp| RampSnapshot {
+                    parameter: format!("{:?}", parameter),
+                    start: ramp.start,
+                    target: ramp.target,
+                    elapsed_samples: ramp.elapsed_samples,
+                    duration_samples: ramp.duration_samples,
+                })
+            })
+            .collect();
+        Self {
+            channel: channel.channel_id,
+            is_active: channel.is_active,
+            audible: channel.audible,
+            mute_gain: channel.mute_gain,
+            instrument: instrument_name(channel.instrument_id),
+            instrument_parameters: channel.instrument_parameters.clone(),
+            frequency_hz: channel.frequency_hz,
+            phase: channel.phase,
+            sub_phase: channel.sub_phase,
+            envelope: EnvelopeSnapshot {
+                envelope_id: envelope.envelope_id,
+                phase: format!("{:?}", envelope.current_phase),
+                amplitude: envelope.current_amplitude,
+                phase_elapsed_samples: envelope.phase_elapsed_samples,
+                phase_total_samples: envelope.phase_total_samples,
+                attack_time_seconds: envelope.definition.attack_time_seconds,
+                decay_time_seconds: envelope.definition.decay_time_seconds,
+                sustain_level: envelope.definition.sustain_level,
+                release_time_seconds: envelope.release_time_seconds,
+            },
+            effects: effects.clone(),
+            lfo_phases: LfoPhases {
+                vibrato: effects.vibrato_phase,
+                tremolo: effects.tremolo_phase,
+                wah: effects.wah_phase,
+                chorus: effects.chorus_phase,
+            },
+            ramps,
+            pitch_slide: channel.pitch_slide.as_ref().map(|slide| {
+                (
+                    slide.start_frequency_hz,
+                    slide.target_frequency_hz,
+                    slide.elapsed_seconds,
+                    slide.duration_seconds,
+                )
+            }),
+            instrument_crossfade: channel.crossfade.as_ref().map(|crossfade| {
+                (
+                    instrument_name(crossfade.from_instrument_id),
+                    instrument_name(crossfade.to_instrument_id),
+                    crossfade.elapsed_seconds,
+                    crossfade.duration_seconds,
+                )
+            }),
+            seconds_per_beat: channel.seconds_per_beat,
+            samples_played: channel.total_samples_processed,
+            random_state: channel.random_generator.state(),
+        }
+    }
+}
+
+impl BusSnapshot {
+    /// Copies a bus's settings (name: the group's, None for the master)
+    pub fn new(name: Option<&str>, bus: &MasterBus) -> Self {
+        let effects = &bus.effects;
+        Self {
+            name: name.map(String::from),
+            amplitude: effects.amplitude,
+            pan: effects.pan,
+            wet: effects.wet,
+            reverb1_enabled: effects.reverb1_enabled,
+            reverb1_level: effects.reverb1_level,
+            reverb1_room_size: effects.reverb1_room_size,
+            reverb1_mix: effects.reverb1_mix,
+            reverb2_enabled: effects.reverb2_enabled,
+            reverb2_level: effects.reverb2_level,
+            reverb2_room_size: effects.reverb2_room_size,
+            reverb2_decay: effects.reverb2_decay,
+            reverb2_damping: effects.reverb2_damping,
+            reverb2_mix: effects.reverb2_mix,
+            reverb2_predelay_ms: effects.reverb2_predelay_ms,
+            delay_enabled: effects.delay_enabled,
+            delay_level: effects.delay_level,
+            delay_time_samples: effects.delay_time_samples,
+            delay_glide_samples: effects.delay_glide_samples,
+            delay_feedback: effects.delay_feedback,
+            delay_sync_beats: bus.delay_sync_beats,
+            chorus_enabled: effects.chorus_enabled,
+            chorus_level: effects.chorus_level,
+            chorus_mix: effects.chorus_mix,
+            chorus_rate_hz: effects.chorus_rate_hz,
+            chorus_depth_ms: effects.chorus_depth_ms,
+            chorus_stereo_spread: effects.chorus_stereo_spread,
+            chorus_phase: effects.chorus_phase,
+            transition: bus.transition_active.then_some((
+                bus.transition_elapsed_samples,
+                bus.transition_duration_samples,
+            )),
+            seconds_per_beat: bus.seconds_per_beat,
+            quiet_samples: bus.quiet_samples,
+        }
+    }
+}
+
+impl EngineSnapshot {
+    /// The snapshot as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("snapshots always serialize")
+    }
+
+    /// The snapshot as a JSON value, for diff_snapshot_json
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).expect("snapshots always serialize")
+    }
+}
+
+/// Reads a snapshot written by to_json (as a JSON value, for
+/// diff_snapshot_json)
+pub fn parse_snapshot_json(text: &str) -> Result<Value, String> {
+    serde_json::from_str(text).map_err(|error| format!("Invalid snapshot JSON: {}", error))
+}
+
+/// Every value that differs between two snapshots, one line each
+/// ("channels[2].effects.amplitude: 0.5 -> 0.7"); empty if they match
+pub fn diff_snapshots(old: &EngineSnapshot, new: &EngineSnapshot) -> Vec<String> {
+    diff_snapshot_json(&old.to_value(), &new.to_value())
+}
+
+/// diff_snapshots for snapshots read back from JSON files (or any two
+/// JSON values). Lists that grew or shrank show the items only one has.
+pub fn diff_snapshot_json(old: &Value, new: &Value) -> Vec<String> {
+    let mut differences = Vec::new();
+    diff_values("", old, new, &mut differences);
+    differences
+}
+
+/// Walks two values side by side, noting each difference under `path`
+fn diff_values(path: &str, old: &Value, new: &Value, differences: &mut Vec<String>) {
+    let shown = |path: &str| {
+        if path.is_empty() {
+            "(snapshot)".to_string()
+        } else {
+            path.to_string()
+        }
+    };
+    match (old, new) {
+        (Value::Object(old_fields), Value::Object(new_fields)) => {
+            for (key, old_value) in old_fields {
+                let field_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                match new_fields.get(key) {
+                    Some(new_value) => diff_values(&field_path, old_value, new_value, differences),
+                    None => differences.push(format!("{}: {} -> (missing)", field_path, old_value)),
+                }
+            }
+            for (key, new_value) in new_fields {
+                if !old_fields.contains_key(key) {
+                    let field_path = if path.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{}.{}", path, key)
+                    };
+                    differences.push(format!("{}: (missing) -> {}", field_path, new_value));
+                }
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) => {
+            for index in 0..old_items.len().max(new_items.len()) {
+                let item_path = format!("{}[{}]", path, index);
+                match (old_items.get(index), new_items.get(index)) {
+                    (Some(old_item), Some(new_item)) => {
+                        diff_values(&item_path, old_item, new_item, differences)
+                    }
+                    (Some(old_item), None) => {
+                        differences.push(format!("{}: {} -> (missing)", item_path, old_item))
+                    }
+                    (None, Some(new_item)) => {
+                        differences.push(format!("{}: (missing) -> {}", item_path, new_item))
+                    }
+                    (None, None) => {}
+                }
+            }
+        }
+        _ if old != new => differences.push(format!("{}: {} -> {}", shown(path), old, new)),
+        _ => {}
+    }
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_snapshot_json() {
+        let old = json!({
+            "row": 4,
+            "channels": [{"effects": {"amplitude": 0.5}, "instrument": "sine"}],
+            "master": {"wet": 1.0},
+        });
+        let mut new = old.clone();
+        assert!(diff_snapshot_json(&old, &new).is_empty());
+
+        new["channels"][0]["effects"]["amplitude"] = json!(0.7);
+        new["channels"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({"instrument": "noise"}));
+        new["master"]["pan"] = json!(-0.5);
+        assert_eq!(
+            diff_snapshot_json(&old, &new),
+            vec![
+                "channels[0].effects.amplitude: 0.5 -> 0.7",
+                "channels[1]: (missing) -> {\"instrument\":\"noise\"}",
+                "master.pan: (missing) -> -0.5",
+            ]
+        );
+        assert_eq!(
+            diff_snapshot_json(&json!(1), &json!(2)),
+            vec!["(snapshot): 1 -> 2"]
+        );
+    }
+}
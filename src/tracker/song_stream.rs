@@ -0,0 +1,333 @@
+// ============================================================================
+// SONG_STREAM.RS - Playing Songs While They Are Parsed
+// ============================================================================
+//
+// parse_song turns the whole song into rows before playback starts. For
+// an hour-long generated song that is a lot of memory: every cell holds
+// its own effect lists. Streaming keeps only a few seconds of rows in
+// memory, however long the song is.
+//
+// HOW IT WORKS:
+// A parser thread reads the song file with a SongStream (parser.rs) and
+// pushes each row into a lock-free queue (command_queue.rs) that holds
+// ROWS_AHEAD rows. The engine pops a row whenever the next one is due:
+//
+//   parser thread  --- rows (ROWS_AHEAD at most) --->  audio thread
+//   parser thread  <-- played rows, to be freed -----  audio thread
+//
+// Played rows go back to the parser thread because freeing memory isn't
+// allowed on the audio thread. When the row queue is full, the parser
+// thread frees the played rows and sleeps for a moment.
+//
+// LATE ROWS:
+// If the parser falls behind (a very slow disk, or a much too short tick),
+// the engine keeps the current row going for one more block and tries
+// again, so the song stretches a little instead of skipping. Each wait is
+// counted; the player prints the count when the song is over.
+// ============================================================================
+
+use crate::command_queue::{Consumer, Producer, command_queue};
+use crate::helper::FrequencyTable;
+use crate::parser::{CellAction, MissingCellBehavior, ParseDiagnostics, SongData, SongStream};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Rows parsed ahead of playback (more rides out slower disks, but
+/// holds more of the song in memory)
+pub const ROWS_AHEAD: usize = 64;
+
+/// How long the parser thread sleeps when the row queue is full
+const FULL_QUEUE_WAIT: Duration = Duration::from_millis(2);
+
+/// What the parser thread sends the engine
+pub enum StreamedRow {
+    /// The next row of the song
+    Row(Vec<CellAction>),
+
+    /// The song has no more rows
+    End,
+}
+
+// ============================================================================
+// ROW FEED (AUDIO THREAD SIDE)
+// ============================================================================
+
+/// The engine's end of a streamed song (see PlaybackEngine::set_row_feed)
+///
+/// Never allocates, frees, or waits, so it is safe on the audio thread.
+pub struct RowFeed {
+    /// Rows from the parser thread
+    rows: Consumer<StreamedRow>,
+
+    /// Played rows, sent back to be freed on the parser thread
+    played_rows: Producer<Vec<CellAction>>,
+
+    /// True once End has been popped (every later call returns End)
+    ended: bool,
+
+    /// Tells the parser thread to stop (set when the feed is dropped)
+    stop: Arc<AtomicBool>,
+
+    /// Times a row wasn't ready when it was due
+    late_rows: Arc<AtomicU32>,
+}
+
+impl RowFeed {
+    /// Takes the next row, or None if the parser hasn't got to it yet
+    pub fn next_row(&mut self) -> Option<StreamedRow> {
+        if self.ended {
+            return Some(StreamedRow::End);
+        }
+        let row = self.rows.pop();
+        if matches!(row, Some(StreamedRow::End)) {
+            self.ended = true;
+        }
+        row
+    }
+
+    /// Hands a played row back to the parser thread to be freed
+    pub fn recycle(&mut self, row: Vec<CellAction>) {
+        // The queue has room for twice as many rows as can be ahead, so
+        // this only drops the row here if the parser thread has stopped
+        let _ = self.played_rows.push(row);
+    }
+
+    /// Counts a row that wasn't ready in time
+    pub fn note_late_row(&self) {
+        self.late_rows.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Drop for RowFeed {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+// ============================================================================
+// PARSER THREAD
+// ============================================================================
+
+/// A song being streamed from a file
+pub struct StreamedSong {
+    /// The song without its rows: config, groups, channel names, and the
+    /// messages from above the first row
+    pub head: SongData,
+
+    /// The rows, for the engine
+    pub feed: RowFeed,
+
+    /// The thread reading the rows
+    pub parser: StreamParser,
+}
+
+/// Handle to the parser thread of a streamed song
+pub struct StreamParser {
+    handle: JoinHandle<ParseDiagnostics>,
+    stop: Arc<AtomicBool>,
+    late_rows: Arc<AtomicU32>,
+}
+
+impl StreamParser {
+    /// Stops the thread (if the song is still being read) and returns the
+    /// messages from the rows it read, and how many rows were late
+    pub fn finish(self) -> (ParseDiagnostics, u32) {
+        self.stop.store(true, Ordering::Relaxed);
+        let diagnostics = self.handle.join().unwrap_or_default();
+        (diagnostics, self.late_rows.load(Ordering::Relaxed))
+    }
+}
+
+/// Opens a song file and starts streaming it
+///
+/// Returns once the head has been read and the first ROWS_AHEAD rows are
+/// waiting in the queue, so playback can start right away. Parameters are
+/// the same as parse_song.
+pub fn stream_song_file(
+    path: &str,
+    reference_frequency_hz: f32,
+    channel_count: usize,
+    missing_cell_behavior: MissingCellBehavior,
+) -> Result<StreamedSong, String> {
+    let file = File::open(path)
+        .map_err(|error| format!("Failed to read song file '{}': {}", path, error))?;
+    stream_song(
+        BufReader::new(file),
+        reference_frequency_hz,
+        channel_count,
+        missing_cell_behavior,
+    )
+}
+
+/// Starts streaming a song from any reader (see stream_song_file)
+pub fn stream_song<R: BufRead + Send + 'static>(
+    input: R,
+    reference_frequency_hz: f32,
+    channel_count: usize,
+    missing_cell_behavior: MissingCellBehavior,
+) -> Result<StreamedSong, String> {
+    let (row_producer, row_consumer) = command_queue(ROWS_AHEAD);
+    let (played_producer, played_consumer) = command_queue(2 * ROWS_AHEAD);
+    let stop = Arc::new(AtomicBool::new(false));
+    let late_rows = Arc::new(AtomicU32::new(0));
+    let (head_sender, head_receiver) = mpsc::channel();
+
+    let thread_stop = Arc::clone(&stop);
+    let handle = thread::Builder::new()
+        .name("song-stream".to_string())
+        .spawn(move || {
+            let frequency_table = FrequencyTable::new();
+            let mut stream = SongStream::new(
+                input,
+                &frequency_table,
+                reference_frequency_hz,
+                channel_count,
+                missing_cell_behavior,
+            );
+            let head = stream.read_head();
+            feed_rows(
+                &mut stream,
+                row_producer,
+                played_consumer,
+                &thread_stop,
+                (head_sender, head),
+            );
+            stream.take_diagnostics()
+        })
+        .map_err(|error| format!("Could not start the song stream thread: {}", error))?;
+
+    let head = head_receiver
+        .recv()
+        .map_err(|_| "The song stream thread stopped before the song started".to_string())?;
+
+    Ok(StreamedSong {
+        head,
+        feed: RowFeed {
+            rows: row_consumer,
+            played_rows: played_producer,
+            ended: false,
+            stop: Arc::clone(&stop),
+            late_rows: Arc::clone(&late_rows),
+        },
+        parser: StreamParser {
+            handle,
+            stop,
+            late_rows,
+        },
+    })
+}
+
+/// The parser thread's loop: keeps the row queue full until the song ends
+/// or playback stops
+/// The head is sent once the queue is first full (or the song is over).
+fn feed_rows<R: BufRead>(
+    stream: &mut SongStream<R>,
+    mut rows: Producer<StreamedRow>,
+    mut played_rows: Consumer<Vec<CellAction>>,
+    stop: &AtomicBool,
+    head: (mpsc::Sender<SongData>, SongData),
+) {
+    let mut head = Some(head);
+    let send_head = |head: &mut Option<(mpsc::Sender<SongData>, SongData)>| {
+        if let Some((sender, head)) = head.take() {
+            let _ = sender.send(head);
+        }
+    };
+
+    let mut next = stream.next().map_or(StreamedRow::End, StreamedRow::Row);
+    loop {
+        // Free the rows the engine is done with
+        while played_rows.pop().is_some() {}
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let is_end = matches!(next, StreamedRow::End);
+        match rows.push(next) {
+            Ok(()) if is_end => {
+                send_head(&mut head);
+                return;
+            }
+            Ok(()) => next = stream.next().map_or(StreamedRow::End, StreamedRow::Row),
+            Err(row) => {
+                send_head(&mut head);
+                next = row;
+                thread::sleep(FULL_QUEUE_WAIT);
+            }
+        }
+    }
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::A4_FREQUENCY_HZ;
+    use crate::parser::{AUTO_CHANNEL_COUNT, parse_song};
+    use std::io::Cursor;
+
+    /// A song longer than ROWS_AHEAD, with a config row and a short row
+    fn long_song() -> String {
+        let mut text = String::from("[groups]\ndrums = 2\nlead,drums\nconfig,tick:0.01\n");
+        for row in 0..3 * ROWS_AHEAD {
+            match row % 4 {
+                0 => text.push_str("c4 sine a:0.5,noise\n"),
+                1 => text.push_str("e4\n"),
+                2 => text.push_str("-,\n"),
+                _ => text.push_str("g4 v:5,.\n"),
+            }
+        }
+        text
+    }
+
+    #[test]
+    fn test_streamed_rows_match_parse_song() {
+        let text = long_song();
+        let whole = parse_song(
+            &text,
+            &FrequencyTable::new(),
+            A4_FREQUENCY_HZ,
+            AUTO_CHANNEL_COUNT,
+            MissingCellBehavior::SlowRelease,
+        );
+
+        let streamed = stream_song(
+            Cursor::new(text.into_bytes()),
+            A4_FREQUENCY_HZ,
+            AUTO_CHANNEL_COUNT,
+            MissingCellBehavior::SlowRelease,
+        )
+        .unwrap();
+        assert_eq!(streamed.head.channel_names, whole.channel_names);
+        assert_eq!(streamed.head.config.tick_duration, Some(0.01));
+        assert_eq!(streamed.head.groups[0].channels, vec![1]);
+        assert!(streamed.head.rows.is_empty());
+
+        // Pop every row the way the engine does, recycling as it goes
+        let mut feed = streamed.feed;
+        let mut rows = Vec::new();
+        loop {
+            match feed.next_row() {
+                Some(StreamedRow::Row(row)) => {
+                    rows.push(format!("{:?}", row));
+                    feed.recycle(row);
+                }
+                Some(StreamedRow::End) => break,
+                None => thread::sleep(Duration::from_millis(1)),
+            }
+        }
+        let expected: Vec<String> = whole.rows.iter().map(|row| format!("{:?}", row)).collect();
+        assert_eq!(rows, expected);
+
+        let (diagnostics, _) = streamed.parser.finish();
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+}
@@ -0,0 +1,1016 @@
+// ============================================================================
+// TUI.RS - Terminal Playback View
+// ============================================================================
+//
+// A full-screen view of the song while it plays, instead of a log line
+// per row:
+//
+//   tracker assets/song.csv --tui
+//
+//   ┌ Silicon Dreams ──────────────────────────────────────────────┐
+//   │ ▶ Playing   Row 12 / 64   0:03.0 / 0:16.0   Master ██████▌   │
+//   │ DSP  18%   Xruns 0                                           │
+//   └──────────────────────────────────────────────────────────────┘
+//   ┌ Channels ────────────────────────────────────────────────────┐
+//   │ 1   Lead ████████▍      c4     trisaw     a:0.5 v:5'0.3      │
+//   │ 2   Bass ███▏           g3     square     p:-0.5             │
+//   └──────────────────────────────────────────────────────────────┘
+//   ┌ Rows ────────────────────────────────────────────────────────┐
+//   │         Lead          Bass                                   │
+//   │   11  c4 trisaw     -             .                          │
+//   │ ▶ 12  e4 trisaw     g3 square     -                          │
+//   └──────────────────────────────────────────────────────────────┘
+//
+// With --monitor, a Spectrum panel above the rows shows a rolling
+// spectrogram of the master output (see monitor.rs).
+//
+// HOW IT WORKS:
+// - Levels: the audio thread keeps each channel's loudest sample in
+//   atomics (see LevelMeters in engine.rs). The view takes them about 30
+//   times a second, shows them on a decibel scale, and lets the bars
+//   fall back slowly like a VU meter.
+// - DSP load: the worst callback load since the last frame and the xrun
+//   count, from the same kind of atomics (dsp_load.rs). The load turns
+//   yellow, then red, as the audio thread runs out of time.
+// - Note, instrument, and effects come from the song itself: when the
+//   engine reports RowStarted, the view applies that row's cell actions
+//   to its own copy of each channel's settings, the same way the channel
+//   merges them. The audio thread is never asked for anything more.
+// - Spectrum: each character is two bands, the upper half block in the
+//   higher band's color and the background in the lower one's, with a
+//   frequency scale down the left edge.
+// - Rows are shown as written in the file (SongData::raw_lines), with
+//   the playing row highlighted and kept in the middle of the screen.
+//   Channel names come from the song's header row (or [channels] line).
+//
+// Keys: Space pauses and resumes, Up/Down pick a channel, M mutes it,
+// S solos it, U clears all mutes and solos, Q or Esc quits.
+// ============================================================================
+
+use crate::channel::merge_effects;
+use crate::effects::{ChannelEffectState, SubOscillatorShape};
+use crate::engine::{EngineCommand, EngineController, EngineEvent};
+use crate::helper::{A4_FREQUENCY_HZ, PitchName};
+use crate::instruments::get_instrument_by_id;
+use crate::midi_export::frequency_to_midi_key;
+use crate::midi_import::format_number;
+use crate::monitor::{MONITOR_FLOOR_DB, SpectrumMonitor};
+use crate::parser::{CellAction, SongData};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+use std::time::{Duration, Instant};
+
+/// How often the screen is redrawn (about 30 times a second)
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+/// Quietest level a meter shows; anything below is an empty bar
+const METER_FLOOR_DB: f32 = -48.0;
+
+/// DSP loads shown in yellow and red (1.0: the callback used all its time)
+const DSP_LOAD_YELLOW: f32 = 0.5;
+const DSP_LOAD_RED: f32 = 0.8;
+
+/// How far a meter bar falls each frame (fraction of the full bar)
+const METER_FALL_PER_FRAME: f32 = 0.04;
+
+/// Characters in a level bar
+const METER_WIDTH: usize = 20;
+
+/// Characters per channel column in the row view
+pub(crate) const ROW_CELL_WIDTH: u16 = 14;
+
+/// Most characters of a channel's header name shown next to its number
+const CHANNEL_NAME_WIDTH: usize = 10;
+
+/// MIDI note number of C0 (for naming the notes channels play)
+const MIDI_NOTE_C0: u8 = 12;
+
+/// Partial blocks for the end of a level bar, in eighths
+const PARTIAL_BLOCKS: [&str; 8] = [" ", "▏", "▎", "▍", "▌", "▋", "▊", "▉"];
+
+/// Lines of spectrogram in the Spectrum panel (two bands each)
+const SPECTRUM_HEIGHT: u16 = 12;
+
+/// Characters of frequency scale left of the spectrogram
+const SPECTRUM_SCALE_WIDTH: u16 = 5;
+
+/// Frequencies marked on the spectrogram's scale
+const SPECTRUM_SCALE_HZ: [f32; 9] = [
+    50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0, 10000.0, 15000.0,
+];
+
+/// Spectrogram colors from MONITOR_FLOOR_DB (first) to 0 dB (last)
+const SPECTRUM_COLORS: [(u8, u8, u8); 6] = [
+    (0, 0, 0),
+    (20, 20, 120),
+    (130, 20, 150),
+    (220, 50, 30),
+    (250, 200, 20),
+    (255, 255, 230),
+];
+
+// ============================================================================
+// CHANNEL DISPLAY
+// ============================================================================
+
+/// What the view knows about one channel
+#[derive(Clone, Default)]
+struct ChannelDisplay {
+    /// Note name of the last trigger ("c4"), "-" for pitchless hits
+    note: String,
+
+    /// Name of the instrument playing
+    instrument: &'static str,
+
+    /// Effect settings, merged the way the channel merges them
+    effects: ChannelEffectState,
+
+    /// Whether the note has been released
+    released: bool,
+
+    /// Meter bar length shown (0.0 - 1.0)
+    meter: f32,
+
+    /// Muted / soloed by the user
+    muted: bool,
+    soloed: bool,
+}
+
+impl ChannelDisplay {
+    /// Follows one cell action
+    fn apply(&mut self, action: &CellAction, reference_frequency_hz: f32) {
+        match action {
+            CellAction::TriggerNote {
+                frequency_hz,
+                instrument_id,
+                effects,
+                clear_effects,
+                ..
+            } => {
+                self.note = pitch_name(*frequency_hz, reference_frequency_hz);
+                self.instrument = instrument_name(*instrument_id);
+                self.merge(effects, *clear_effects);
+                self.released = false;
+            }
+            CellAction::TriggerPitchless {
+                instrument_id,
+                effects,
+                clear_effects,
+                ..
+            } => {
+                self.note = "-".to_string();
+                self.instrument = instrument_name(*instrument_id);
+                self.merge(effects, *clear_effects);
+                self.released = false;
+            }
+            CellAction::SustainWithEffects {
+                effects,
+                clear_first,
+                ..
+            }
+            | CellAction::ChangeEffects {
+                effects,
+                clear_first,
+                ..
+            } => self.merge(effects, *clear_first),
+            CellAction::FastRelease | CellAction::SlowRelease => self.released = true,
+            CellAction::Sustain
+            | CellAction::MasterEffects { .. }
+            | CellAction::GroupEffects { .. } => {}
+        }
+    }
+
+    fn merge(&mut self, effects: &ChannelEffectState, clear_first: bool) {
+        if clear_first {
+            self.effects = ChannelEffectState::default();
+        }
+        merge_effects(&mut self.effects, effects);
+    }
+}
+
+/// Name of an instrument by ID ("?" if unknown)
+fn instrument_name(instrument_id: usize) -> &'static str {
+    get_instrument_by_id(instrument_id)
+        .map(|instrument| instrument.name)
+        .unwrap_or("?")
+}
+
+/// Nearest note name for a frequency ("c4")
+fn pitch_name(frequency_hz: f32, reference_frequency_hz: f32) -> String {
+    let key = frequency_to_midi_key(frequency_hz, reference_frequency_hz).max(MIDI_NOTE_C0);
+    PitchName::from_semitones_from_c0((key - MIDI_NOTE_C0) as i32, 0.0).to_string()
+}
+
+/// The effects that differ from the defaults, written as cell tokens
+/// ("a:0.5 v:5'0.3")
+pub fn effect_summary(effects: &ChannelEffectState) -> String {
+    let default = ChannelEffectState::default();
+    let mut tokens = Vec::new();
+    if effects.amplitude != default.amplitude {
+        tokens.push(format!("a:{}", format_number(effects.amplitude)));
+    }
+    if effects.pan != default.pan {
+        tokens.push(format!("p:{}", format_number(effects.pan)));
+    }
+    if effects.detune_cents != default.detune_cents {
+        tokens.push(format!("dt:{}", format_number(effects.detune_cents)));
+    }
+    if effects.vibrato_rate_hz > 0.0 && effects.vibrato_depth_semitones > 0.0 {
+        tokens.push(format!(
+            "v:{}'{}",
+            format_number(effects.vibrato_rate_hz),
+            format_number(effects.vibrato_depth_semitones)
+        ));
+    }
+    if effects.tremolo_rate_hz > 0.0 && effects.tremolo_depth > 0.0 {
+        tokens.push(format!(
+            "t:{}'{}",
+            format_number(effects.tremolo_rate_hz),
+            format_number(effects.tremolo_depth)
+        ));
+    }
+    if effects.bitcrush_bits != default.bitcrush_bits {
+        tokens.push(format!("b:{}", effects.bitcrush_bits));
+    }
+    if effects.decimate_rate_hz > 0.0 {
+        tokens.push(format!("sr:{}", format_number(effects.decimate_rate_hz)));
+    }
+    if effects.distortion_amount > 0.0 {
+        tokens.push(format!("d:{}", format_number(effects.distortion_amount)));
+    }
+    if effects.oversampling != default.oversampling {
+        tokens.push(format!("q:{}", effects.oversampling));
+    }
+    if effects.sub_level > 0.0 {
+        let shape = match effects.sub_shape {
+            SubOscillatorShape::Square => "",
+            SubOscillatorShape::Sine => "'sine",
+        };
+        tokens.push(format!("sub:{}{}", format_number(effects.sub_level), shape));
+    }
+    if effects.click_level > 0.0 {
+        tokens.push(format!("click:{}", format_number(effects.click_level)));
+    }
+    if effects.wah_depth > 0.0 {
+        tokens.push(format!(
+            "wah:{}'{}",
+            format_number(effects.wah_rate_hz),
+            format_number(effects.wah_depth)
+        ));
+    }
+    if let Some((first, second)) = effects.vowel {
+        if first == second {
+            tokens.push(format!("vowel:{}", first.name()));
+        } else {
+            tokens.push(format!("vowel:{}>{}", first.name(), second.name()));
+        }
+    }
+    if effects.chorus_mix > 0.0 {
+        tokens.push(format!("ch:{}", format_number(effects.chorus_mix)));
+    }
+    tokens.join(" ")
+}
+
+/// Meter bar length for a level: METER_FLOOR_DB..0 dB maps to 0.0..1.0
+fn level_to_meter(level: f32) -> f32 {
+    if level <= 0.0 {
+        return 0.0;
+    }
+    let decibels = 20.0 * level.log10();
+    ((decibels - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0)
+}
+
+/// A level bar, green then yellow then red toward the top
+fn meter_line(meter: f32, width: usize) -> Line<'static> {
+    let eighths = (meter * (width * 8) as f32).round() as usize;
+    let spans = (0..width)
+        .map(|position| {
+            let fill = eighths.saturating_sub(position * 8).min(8);
+            let symbol = if fill == 8 {
+                "█"
+            } else {
+                PARTIAL_BLOCKS[fill]
+            };
+            let color = match position as f32 / width as f32 {
+                fraction if fraction < 0.6 => Color::Green,
+                fraction if fraction < 0.85 => Color::Yellow,
+                _ => Color::Red,
+            };
+            Span::styled(symbol, Style::default().fg(color))
+        })
+        .collect::<Vec<_>>();
+    Line::from(spans)
+}
+
+/// Spectrogram color for a level in dB (dark when quiet, white at 0 dB)
+fn spectrum_color(level_db: f32) -> Color {
+    let position = ((level_db - MONITOR_FLOOR_DB) / -MONITOR_FLOOR_DB).clamp(0.0, 1.0)
+        * (SPECTRUM_COLORS.len() - 1) as f32;
+    let index = (position as usize).min(SPECTRUM_COLORS.len() - 2);
+    let fraction = position - index as f32;
+    let (low, high) = (SPECTRUM_COLORS[index], SPECTRUM_COLORS[index + 1]);
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * fraction).round() as u8;
+    Color::Rgb(mix(low.0, high.0), mix(low.1, high.1), mix(low.2, high.2))
+}
+
+/// Short frequency label ("50", "2k")
+fn frequency_label(frequency_hz: f32) -> String {
+    if frequency_hz >= 1000.0 {
+        format!("{}k", format_number(frequency_hz / 1000.0))
+    } else {
+        format_number(frequency_hz)
+    }
+}
+
+/// Formats seconds as m:ss.s
+pub(crate) fn format_time(seconds: f32) -> String {
+    let tenths = (seconds.max(0.0) * 10.0).round() as u32;
+    format!("{}:{:02}.{}", tenths / 600, (tenths / 10) % 60, tenths % 10)
+}
+
+// ============================================================================
+// PLAYBACK VIEW
+// ============================================================================
+
+/// Everything the playback screen shows, updated from engine events
+pub struct PlaybackView {
+    title: String,
+    song: SongData,
+    channels: Vec<ChannelDisplay>,
+
+    /// Start time of each row in seconds, plus the song's end
+    row_start_seconds: Vec<f32>,
+
+    /// Tuning used to name notes
+    reference_frequency_hz: f32,
+
+    /// Row being played (None before the first row)
+    playing_row: Option<usize>,
+
+    /// Meter bar length for the whole mix
+    master_meter: f32,
+
+    /// Worst DSP load since the last frame, and xruns so far
+    dsp_load: f32,
+    xruns: u64,
+
+    /// Channel the mute and solo keys act on
+    selected_channel: usize,
+
+    /// Spectrogram of the output (None: no Spectrum panel)
+    monitor: Option<SpectrumMonitor>,
+
+    paused: bool,
+    finished: bool,
+}
+
+impl PlaybackView {
+    /// Creates the view for a song about to play on channel_count channels
+    pub fn new(
+        song: SongData,
+        title: &str,
+        channel_count: usize,
+        tick_duration_seconds: f32,
+    ) -> Self {
+        let mut row_start_seconds = vec![0.0];
+        for duration in song.row_durations(tick_duration_seconds) {
+            let last = row_start_seconds[row_start_seconds.len() - 1];
+            row_start_seconds.push(last + duration);
+        }
+        let reference_frequency_hz = song
+            .config
+            .reference_frequency_hz
+            .unwrap_or(A4_FREQUENCY_HZ);
+        Self {
+            title: title.to_string(),
+            song,
+            channels: vec![ChannelDisplay::default(); channel_count],
+            row_start_seconds,
+            reference_frequency_hz,
+            playing_row: None,
+            master_meter: 0.0,
+            dsp_load: 0.0,
+            xruns: 0,
+            selected_channel: 0,
+            monitor: None,
+            paused: false,
+            finished: false,
+        }
+    }
+
+    /// Follows EngineEvent::RowStarted(next_row): the row before it was
+    /// just dispatched to the channels
+    pub fn row_started(&mut self, next_row: usize) {
+        let Some(row) = next_row.checked_sub(1) else {
+            return;
+        };
+        self.playing_row = Some(row);
+        if let Some(actions) = self.song.rows.get(row) {
+            for (channel, action) in self.channels.iter_mut().zip(actions) {
+                channel.apply(action, self.reference_frequency_hz);
+            }
+        }
+    }
+
+    /// Catches the channels up with the rows before this one (0-indexed),
+    /// for playback that starts part way through the song
+    pub fn skip_to_row(&mut self, row: usize) {
+        for next_row in 1..=row.min(self.song.row_count()) {
+            self.row_started(next_row);
+        }
+        self.playing_row = None;
+    }
+
+    /// Takes new peak levels (from EngineController::take_channel_levels)
+    /// Louder levels show at once; quieter ones let the bar fall slowly.
+    pub fn update_levels(&mut self, channel_levels: &[f32], master_level: f32) {
+        let fall = |meter: &mut f32, level: f32| {
+            *meter = level_to_meter(level).max(*meter - METER_FALL_PER_FRAME);
+        };
+        for (channel, level) in self.channels.iter_mut().zip(channel_levels) {
+            fall(&mut channel.meter, *level);
+        }
+        fall(&mut self.master_meter, master_level);
+    }
+
+    /// Takes the DSP load (from EngineController::take_recent_dsp_load)
+    /// and the xrun count
+    pub fn update_dsp_load(&mut self, load: f32, xruns: u64) {
+        self.dsp_load = load;
+        self.xruns = xruns;
+    }
+
+    /// Adds the Spectrum panel, drawn from this monitor (its tap goes in
+    /// the audio callback; see monitor::start_monitor)
+    pub fn set_monitor(&mut self, monitor: SpectrumMonitor) {
+        self.monitor = Some(monitor);
+    }
+
+    /// Takes the output the monitor's tap has sent since the last frame
+    pub fn update_monitor(&mut self) {
+        if let Some(monitor) = &mut self.monitor {
+            monitor.update();
+        }
+    }
+
+    /// Shows a channel as muted or not (the engine is told separately)
+    pub fn set_muted(&mut self, channel_index: usize, muted: bool) {
+        if let Some(channel) = self.channels.get_mut(channel_index) {
+            channel.muted = muted;
+        }
+    }
+
+    /// Shows a channel as soloed or not (the engine is told separately)
+    pub fn set_soloed(&mut self, channel_index: usize, soloed: bool) {
+        if let Some(channel) = self.channels.get_mut(channel_index) {
+            channel.soloed = soloed;
+        }
+    }
+
+    /// Whether a channel is heard, by the same rule as the engine
+    fn is_audible(&self, channel_index: usize) -> bool {
+        let any_soloed = self.channels.iter().any(|channel| channel.soloed);
+        let channel = &self.channels[channel_index];
+        !channel.muted && (!any_soloed || channel.soloed)
+    }
+
+    /// Draws the whole screen
+    pub fn draw(&self, frame: &mut Frame) {
+        let spectrum_height = if self.monitor.is_some() {
+            SPECTRUM_HEIGHT + 2
+        } else {
+            0
+        };
+        let [
+            header_area,
+            channel_area,
+            spectrum_area,
+            row_area,
+            footer_area,
+        ] = Layout::vertical([
+            Constraint::Length(4),
+            Constraint::Length(self.channels.len() as u16 + 2),
+            Constraint::Length(spectrum_height),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        self.draw_header(frame, header_area);
+        self.draw_channels(frame, channel_area);
+        if let Some(monitor) = &self.monitor {
+            draw_spectrum(frame, spectrum_area, monitor);
+        }
+        self.draw_rows(frame, row_area);
+        frame.render_widget(
+            Paragraph::new(
+                " Space pause/resume   Up/Down channel   M mute   S solo   U unmute all   Q quit",
+            )
+            .style(Style::default().fg(Color::DarkGray)),
+            footer_area,
+        );
+    }
+
+    fn draw_header(&self, frame: &mut Frame, area: Rect) {
+        let status = if self.finished {
+            "■ Finished"
+        } else if self.paused {
+            "⏸ Paused"
+        } else {
+            "▶ Playing"
+        };
+        let row_count = self.song.row_count();
+        let shown_row = self.playing_row.map_or(0, |row| row + 1);
+        let elapsed = self
+            .playing_row
+            .and_then(|row| self.row_start_seconds.get(row))
+            .copied()
+            .unwrap_or(0.0);
+        let total = self.row_start_seconds[self.row_start_seconds.len() - 1];
+
+        let mut spans = vec![
+            Span::styled(status, Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(format!(
+                "   Row {} / {}   {} / {}   Master ",
+                shown_row,
+                row_count,
+                format_time(elapsed),
+                format_time(total)
+            )),
+        ];
+        spans.extend(meter_line(self.master_meter, METER_WIDTH).spans);
+
+        let load_color = if self.dsp_load >= DSP_LOAD_RED {
+            Color::Red
+        } else if self.dsp_load >= DSP_LOAD_YELLOW {
+            Color::Yellow
+        } else {
+            Color::Green
+        };
+        let load_line = Line::from(vec![
+            Span::raw("DSP "),
+            Span::styled(
+                format!("{:>3.0}%", self.dsp_load * 100.0),
+                Style::default().fg(load_color),
+            ),
+            Span::raw(format!("   Xruns {}", self.xruns)),
+        ]);
+        frame.render_widget(
+            Paragraph::new(vec![Line::from(spans), load_line])
+                .block(Block::bordered().title(self.title.as_str())),
+            area,
+        );
+    }
+
+    /// Header name of a channel, cut to fit ("" if it has none)
+    fn channel_name(&self, channel_index: usize) -> String {
+        self.song
+            .channel_names
+            .get(channel_index)
+            .map(|name| name.chars().take(CHANNEL_NAME_WIDTH).collect())
+            .unwrap_or_default()
+    }
+
+    fn has_channel_names(&self) -> bool {
+        self.song.channel_names.iter().any(|name| !name.is_empty())
+    }
+
+    fn draw_channels(&self, frame: &mut Frame, area: Rect) {
+        let rows = self.channels.iter().enumerate().map(|(index, channel)| {
+            let text_style =
+                if channel.released || channel.instrument.is_empty() || !self.is_audible(index) {
+                    Style::default().fg(Color::DarkGray)
+                } else {
+                    Style::default()
+                };
+            let marker = if index == self.selected_channel {
+                "›"
+            } else {
+                " "
+            };
+            let state = match (channel.muted, channel.soloed) {
+                (true, _) => Span::styled("M", Style::default().fg(Color::Red)),
+                (false, true) => Span::styled("S", Style::default().fg(Color::Yellow)),
+                (false, false) => Span::raw(" "),
+            };
+            Row::new(vec![
+                Line::from(vec![
+                    Span::raw(format!("{}{:>2} ", marker, index + 1)),
+                    state,
+                ]),
+                Line::raw(self.channel_name(index)),
+                meter_line(channel.meter, METER_WIDTH),
+                Line::styled(channel.note.clone(), text_style),
+                Line::styled(channel.instrument, text_style),
+                Line::styled(effect_summary(&channel.effects), text_style),
+            ])
+        });
+        let name_width = (0..self.channels.len())
+            .map(|index| self.channel_name(index).chars().count())
+            .max()
+            .unwrap_or(0);
+        let widths = [
+            Constraint::Length(5),
+            Constraint::Length(name_width as u16),
+            Constraint::Length(METER_WIDTH as u16),
+            Constraint::Length(6),
+            Constraint::Length(10),
+            Constraint::Fill(1),
+        ];
+        frame.render_widget(
+            Table::new(rows, widths).block(Block::bordered().title("Channels")),
+            area,
+        );
+    }
+
+    fn draw_rows(&self, frame: &mut Frame, area: Rect) {
+        // Keep the playing row in the middle once the song is under way
+        // (below the channel names, if the header has any)
+        let header_lines = if self.has_channel_names() { 1 } else { 0 };
+        let visible = area.height.saturating_sub(2 + header_lines) as usize;
+        let playing = self.playing_row.unwrap_or(0);
+        let first = playing
+            .saturating_sub(visible / 2)
+            .min(self.song.row_count().saturating_sub(visible));
+        let last = (first + visible).min(self.song.row_count());
+
+        let rows = (first..last).map(|row| {
+            let marker = if Some(row) == self.playing_row {
+                "▶"
+            } else {
+                " "
+            };
+            let mut cells = vec![format!("{} {:>4}", marker, row + 1)];
+            cells.extend(self.cell_texts(row));
+            let row_widget = Row::new(cells);
+            if Some(row) == self.playing_row {
+                row_widget.style(
+                    Style::default()
+                        .bg(Color::Blue)
+                        .fg(Color::White)
+                        .add_modifier(Modifier::BOLD),
+                )
+            } else {
+                row_widget
+            }
+        });
+        let widths = std::iter::once(Constraint::Length(6))
+            .chain((0..self.channels.len()).map(|_| Constraint::Length(ROW_CELL_WIDTH)));
+        let mut table = Table::new(rows, widths).block(Block::bordered().title("Rows"));
+        if self.has_channel_names() {
+            let names = (0..self.channels.len()).map(|index| self.channel_name(index));
+            table = table.header(
+                Row::new(std::iter::once(String::new()).chain(names))
+                    .style(Style::default().add_modifier(Modifier::BOLD)),
+            );
+        }
+        frame.render_widget(table, area);
+    }
+
+    /// The cells of a row as written in the file
+    /// Songs loaded from JSON have no text, so their actions are described.
+    fn cell_texts(&self, row: usize) -> Vec<String> {
+        if let Some(line) = self.song.raw_lines.get(row) {
+            return line
+                .split(',')
+                .take(self.channels.len())
+                .map(|cell| cell.trim().to_string())
+                .collect();
+        }
+        self.song.rows[row]
+            .iter()
+            .map(|action| match action {
+                CellAction::TriggerNote {
+                    frequency_hz,
+                    instrument_id,
+                    ..
+                } => format!(
+                    "{} {}",
+                    pitch_name(*frequency_hz, self.reference_frequency_hz),
+                    instrument_name(*instrument_id)
+                ),
+                CellAction::TriggerPitchless { instrument_id, .. } => {
+                    instrument_name(*instrument_id).to_string()
+                }
+                CellAction::Sustain => "-".to_string(),
+                CellAction::SustainWithEffects { effects, .. } => {
+                    format!("- {}", effect_summary(effects))
+                }
+                CellAction::ChangeEffects { effects, .. } => effect_summary(effects),
+                CellAction::FastRelease => ".".to_string(),
+                CellAction::SlowRelease => String::new(),
+                CellAction::MasterEffects { .. } => "master".to_string(),
+                CellAction::GroupEffects { group, .. } => format!("group {}", group),
+            })
+            .collect()
+    }
+}
+
+/// Draws the monitor's spectrogram with the newest column at the right
+/// edge and a frequency scale on the left
+fn draw_spectrum(frame: &mut Frame, area: Rect, monitor: &SpectrumMonitor) {
+    let block = Block::bordered().title("Spectrum");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let lines = inner.height as usize;
+    let band_count = lines * 2;
+    let width = inner.width.saturating_sub(SPECTRUM_SCALE_WIDTH) as usize;
+    let columns = monitor.band_levels(width, band_count);
+    let edges = monitor.band_edges(band_count);
+    let blank = width - columns.len();
+
+    let text: Vec<Line> = (0..lines)
+        .map(|line| {
+            // Line 0 is the top: the two highest bands
+            let upper = band_count - 1 - line * 2;
+            let lower = upper - 1;
+            let label = SPECTRUM_SCALE_HZ
+                .iter()
+                .find(|&&hz| edges[lower] <= hz && hz < edges[upper + 1])
+                .map(|&hz| frequency_label(hz))
+                .unwrap_or_default();
+            let mut spans = vec![Span::styled(
+                format!("{:>4} ", label),
+                Style::default().fg(Color::DarkGray),
+            )];
+            spans.push(Span::raw(" ".repeat(blank)));
+            spans.extend(columns.iter().map(|levels| {
+                Span::styled(
+                    "▀",
+                    Style::default()
+                        .fg(spectrum_color(levels[upper]))
+                        .bg(spectrum_color(levels[lower])),
+                )
+            }));
+            Line::from(spans)
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(text), inner);
+}
+
+// ============================================================================
+// RUNNING THE VIEW
+// ============================================================================
+
+/// Shows the view until the song ends and its release tails die away
+/// (waiting at most tail_time for them) or the user quits. Returns true if
+/// the song played to the end.
+/// Log output is turned off while the view is up, since it would write
+/// over the screen.
+pub fn run_playback_view(
+    view: &mut PlaybackView,
+    controller: &mut EngineController,
+    tail_time: Duration,
+) -> Result<bool, String> {
+    let mut terminal = ratatui::try_init()
+        .map_err(|error| format!("Failed to start the terminal view: {}", error))?;
+    let log_level = log::max_level();
+    log::set_max_level(log::LevelFilter::Off);
+
+    let result = view_loop(&mut terminal, view, controller, tail_time);
+
+    ratatui::restore();
+    log::set_max_level(log_level);
+    result
+}
+
+/// Body of run_playback_view, between setting up and restoring the terminal
+fn view_loop(
+    terminal: &mut DefaultTerminal,
+    view: &mut PlaybackView,
+    controller: &mut EngineController,
+    tail_time: Duration,
+) -> Result<bool, String> {
+    let mut finished_at: Option<Instant> = None;
+    loop {
+        while let Some(event) = controller.poll_event() {
+            match event {
+                EngineEvent::RowStarted(row) => view.row_started(row),
+                EngineEvent::Finished => {
+                    view.finished = true;
+                    finished_at.get_or_insert_with(Instant::now);
+                }
+                EngineEvent::Silent => return Ok(true),
+                EngineEvent::SongStarted => {}
+            }
+        }
+        view.update_levels(
+            &controller.take_channel_levels(),
+            controller.take_master_level(),
+        );
+        view.update_dsp_load(
+            controller.take_recent_dsp_load(),
+            controller.dsp_load().xruns(),
+        );
+        view.update_monitor();
+        terminal
+            .draw(|frame| view.draw(frame))
+            .map_err(|error| format!("Failed to draw: {}", error))?;
+
+        if finished_at.is_some_and(|time| time.elapsed() >= tail_time) {
+            return Ok(true);
+        }
+
+        let has_event =
+            event::poll(FRAME_INTERVAL).map_err(|error| format!("Terminal error: {}", error))?;
+        if !has_event {
+            continue;
+        }
+        let Event::Key(key) =
+            event::read().map_err(|error| format!("Terminal error: {}", error))?
+        else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(false);
+            }
+            KeyCode::Char(' ') => {
+                view.paused = !view.paused;
+                if controller
+                    .send(EngineCommand::SetPaused(view.paused))
+                    .is_err()
+                {
+                    view.paused = !view.paused;
+                }
+            }
+            KeyCode::Up => view.selected_channel = view.selected_channel.saturating_sub(1),
+            KeyCode::Down => {
+                view.selected_channel =
+                    (view.selected_channel + 1).min(view.channels.len().saturating_sub(1));
+            }
+            KeyCode::Char('m') | KeyCode::Char('s') if !view.channels.is_empty() => {
+                let channel_index = view.selected_channel;
+                let channel = &view.channels[channel_index];
+                let (muted, soloed) = if key.code == KeyCode::Char('m') {
+                    (!channel.muted, channel.soloed)
+                } else {
+                    (channel.muted, !channel.soloed)
+                };
+                set_mute_solo(view, controller, channel_index, muted, soloed);
+            }
+            KeyCode::Char('u') => {
+                for channel_index in 0..view.channels.len() {
+                    set_mute_solo(view, controller, channel_index, false, false);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Mutes/solos a channel in the engine, and on screen once the engine
+/// has been told
+fn set_mute_solo(
+    view: &mut PlaybackView,
+    controller: &mut EngineController,
+    channel_index: usize,
+    muted: bool,
+    soloed: bool,
+) {
+    let commands = [
+        EngineCommand::SetMuted {
+            channel_index,
+            muted,
+        },
+        EngineCommand::SetSoloed {
+            channel_index,
+            soloed,
+        },
+    ];
+    for command in commands {
+        if controller.send(command).is_err() {
+            return;
+        }
+    }
+    view.set_muted(channel_index, muted);
+    view.set_soloed(channel_index, soloed);
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::FrequencyTable;
+    use crate::parser::{MissingCellBehavior, parse_song};
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    fn test_song() -> SongData {
+        parse_song(
+            "Lead,Drums\nc4 trisaw a:0.5 v:5'0.3,noise\n- p:-0.5,.\ne4 sine cl,-",
+            &FrequencyTable::new(),
+            A4_FREQUENCY_HZ,
+            2,
+            MissingCellBehavior::SlowRelease,
+        )
+    }
+
+    #[test]
+    fn test_rows_update_channel_display() {
+        let mut view = PlaybackView::new(test_song(), "Test", 2, 0.25);
+
+        view.row_started(1);
+        assert_eq!(view.channels[0].note, "c4");
+        assert_eq!(view.channels[0].instrument, "trisaw");
+        assert_eq!(effect_summary(&view.channels[0].effects), "a:0.5 v:5'0.3");
+        assert_eq!(view.channels[1].instrument, "noise");
+
+        // Effects merge into what is already set; "." releases
+        view.row_started(2);
+        assert_eq!(
+            effect_summary(&view.channels[0].effects),
+            "a:0.5 p:-0.5 v:5'0.3"
+        );
+        assert!(view.channels[1].released);
+
+        // "cl" starts from the defaults again
+        view.row_started(3);
+        assert_eq!(view.channels[0].note, "e4");
+        assert_eq!(effect_summary(&view.channels[0].effects), "");
+    }
+
+    #[test]
+    fn test_draw_shows_rows_meters_and_time() {
+        let mut view = PlaybackView::new(test_song(), "Test Song", 2, 0.25);
+        view.row_started(2);
+        view.update_levels(&[1.0, 0.0], 0.5);
+        assert_eq!(view.channels[0].meter, 1.0);
+        view.update_levels(&[0.0, 0.0], 0.0);
+        assert!(view.channels[0].meter < 1.0 && view.channels[0].meter > 0.9);
+        view.update_dsp_load(0.42, 3);
+
+        let mut terminal = Terminal::new(TestBackend::new(90, 20)).unwrap();
+        terminal.draw(|frame| view.draw(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("Test Song"));
+        assert!(screen.contains("Row 2 / 3"));
+        assert!(screen.contains("0:00.3 / 0:00.8"));
+        assert!(screen.contains("c4 trisaw a:0"));
+        assert!(screen.contains("trisaw"));
+        assert!(screen.contains("Lead") && screen.contains("Drums"));
+        assert!(screen.contains("DSP  42%   Xruns 3"));
+
+        // Soloing channel 2 greys out channel 1; a muted channel shows "M"
+        view.set_soloed(1, true);
+        assert!(!view.is_audible(0) && view.is_audible(1));
+        view.set_muted(1, true);
+        assert!(!view.is_audible(1));
+        terminal.draw(|frame| view.draw(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains(" 2 M"));
+    }
+
+    #[test]
+    fn test_spectrum_panel_shows_the_monitor() {
+        let mut view = PlaybackView::new(test_song(), "Test Song", 2, 0.25);
+        let mut monitor = SpectrumMonitor::without_tap(48000);
+        let bass: Vec<f32> = (0..8192)
+            .map(|i| (std::f32::consts::TAU * 55.0 * i as f32 / 48000.0).sin())
+            .collect();
+        monitor.push_samples(&bass);
+        view.set_monitor(monitor);
+
+        let mut terminal = Terminal::new(TestBackend::new(90, 34)).unwrap();
+        terminal.draw(|frame| view.draw(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let screen: String = buffer.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("Spectrum"));
+        assert!(screen.contains("100") && screen.contains("10k"));
+        assert!(screen.contains("Rows"));
+
+        // The newest column is at the right edge of the panel's inner
+        // rows 9..21; 55 Hz is band 2, the lower half of the second line
+        // from the bottom
+        let level = view.monitor.as_ref().unwrap().band_levels(1, 24)[0][2];
+        assert!(level > -3.0);
+        let bass_cell = buffer.cell((88, 19)).unwrap();
+        assert_eq!(bass_cell.symbol(), "▀");
+        assert_eq!(bass_cell.bg, spectrum_color(level));
+        assert_eq!(buffer.cell((10, 19)).unwrap().symbol(), " ");
+    }
+}
@@ -0,0 +1,455 @@
+// ============================================================================
+// TUNING.RS - Pluggable Tuning Systems (12-TET, EDO, Scala)
+// ============================================================================
+//
+// This module decides which frequency a written note name like "C#4" gets.
+// By default the tracker uses standard tuning (12 equal steps per octave,
+// A4 = 440 Hz), but a song can switch to another tuning with a directive line:
+//
+//   [tuning] 19edo                  19 equal divisions of the octave
+//   [tuning] 31edo                  31 equal divisions of the octave
+//   [tuning] scl:assets/just.scl    Scale loaded from a Scala (.scl) file
+//   [tuning] 12edo                  Back to standard tuning
+//
+// The directive affects every row after it, so a song can even change
+// tuning halfway through.
+//
+// HOW NOTE NAMES MAP ONTO EDO TUNINGS:
+// Letters are placed along a chain of fifths (F C G D A E B), using the
+// EDO step closest to a pure 3:2 fifth. A sharp raises the note by the
+// difference between seven fifths and four octaves. In 12-EDO this gives the
+// usual semitone; in 19-EDO a sharp is one step and C# and Db are different
+// pitches, just like musicians in that tuning expect.
+//
+// HOW NOTE NAMES MAP ONTO SCALA SCALES:
+// The scale is rooted on C. Note names count up in standard semitones
+// (C=0, C#=1, ... B=11) and each semitone moves to the next scale degree,
+// wrapping at the scale's period (usually an octave). A 12-note .scl file
+// therefore maps one-to-one onto the familiar keyboard.
+//
+// In every tuning, A4 stays at the reference frequency. That is 440 Hz
+// unless changed with the --a4 command line option, "a4: 432" in the config
+// row, or a "master a4:415" command partway through the song.
+//
+// HOW TO ADD A NEW TUNING SYSTEM:
+// 1. Add a variant to TuningSystem
+// 2. Handle it in Tuning::note_cents_from_c4()
+// 3. Recognize its directive text in Tuning::parse_directive()
+// ============================================================================
+
+use crate::helper::{
+    A4_FREQUENCY_HZ, FrequencyTable, PitchName, cents_to_frequency_ratio, note_letter_to_semitone,
+    pitch_name_to_frequency,
+};
+use std::fs;
+
+// ============================================================================
+// TUNING SYSTEM
+// ============================================================================
+
+/// The different ways the octave can be divided into notes
+#[derive(Clone, Debug, PartialEq)]
+pub enum TuningSystem {
+    /// The octave is split into equal steps (12 = standard tuning)
+    EqualDivision {
+        /// How many equal steps make one octave (e.g., 12, 19, 24, 31)
+        steps_per_octave: u32,
+    },
+
+    /// A scale loaded from a Scala (.scl) file
+    Scala {
+        /// The description line from the file
+        description: String,
+
+        /// Pitch of each scale degree above the root, in cents
+        /// Degree 0 (the root, 0 cents) is not stored
+        degree_cents: Vec<f32>,
+
+        /// The interval at which the scale repeats, in cents (1200 = octave)
+        period_cents: f32,
+    },
+}
+
+// ============================================================================
+// TUNING
+// ============================================================================
+
+/// A complete tuning: the system plus the frequency A4 is pinned to
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tuning {
+    /// How the octave is divided
+    pub system: TuningSystem,
+
+    /// Frequency of A4 in Hz
+    pub reference_frequency_hz: f32,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self::equal_division(12)
+    }
+}
+
+impl Tuning {
+    /// Creates an equal-division tuning with the given number of steps per octave
+    pub fn equal_division(steps_per_octave: u32) -> Self {
+        Self {
+            system: TuningSystem::EqualDivision {
+                steps_per_octave: steps_per_octave.max(1),
+            },
+            reference_frequency_hz: A4_FREQUENCY_HZ,
+        }
+    }
+
+    /// Returns true for plain 12-TET, which can use the fast frequency table
+    pub fn is_standard(&self) -> bool {
+        self.system
+            == TuningSystem::EqualDivision {
+                steps_per_octave: 12,
+            }
+            && self.reference_frequency_hz == A4_FREQUENCY_HZ
+    }
+
+    /// A short human-readable name for debug output
+    pub fn display_name(&self) -> String {
+        match &self.system {
+            TuningSystem::EqualDivision { steps_per_octave } => {
+                format!("{}-EDO", steps_per_octave)
+            }
+            TuningSystem::Scala {
+                description,
+                degree_cents,
+                ..
+            } => format!("Scala '{}' ({} notes)", description, degree_cents.len()),
+        }
+    }
+
+    /// Parses the text after a `[tuning]` directive
+    ///
+    /// Accepts:
+    /// - "19edo", "19-edo", "19tet", "19-tet" (any number of steps)
+    /// - "scl:path/to/scale.scl" or just "path/to/scale.scl"
+    ///
+    /// Scala paths are relative to the working directory, the same as the
+    /// song path given on the command line.
+    pub fn parse_directive(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let lower = text.to_lowercase();
+
+        if lower.is_empty() {
+            return Err("Missing tuning (e.g. '19edo' or 'scl:my_scale.scl')".to_string());
+        }
+
+        // Scala file reference
+        if lower.starts_with("scl:") || lower.ends_with(".scl") {
+            let path = if lower.starts_with("scl:") {
+                text[4..].trim()
+            } else {
+                text
+            };
+            let contents = fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read Scala file '{}': {}", path, e))?;
+            return Self::from_scala_text(&contents);
+        }
+
+        // Equal divisions: strip the suffix and read the number
+        let number_part = ["-edo", "edo", "-tet", "tet"]
+            .iter()
+            .find_map(|suffix| lower.strip_suffix(suffix))
+            .unwrap_or(&lower);
+
+        match number_part.trim().parse::<u32>() {
+            Ok(steps) if (1..=1200).contains(&steps) => Ok(Self::equal_division(steps)),
+            _ => Err(format!(
+                "Unknown tuning '{}'. Use e.g. '19edo' or 'scl:my_scale.scl'",
+                text
+            )),
+        }
+    }
+
+    /// Builds a tuning from the contents of a Scala (.scl) file
+    ///
+    /// Format (lines starting with '!' are comments):
+    ///   description line
+    ///   number of notes
+    ///   one pitch per line - cents if it contains a '.', otherwise a ratio
+    ///   like "3/2" or a whole number like "2"
+    /// The last pitch is the period (usually 2/1 = one octave).
+    pub fn from_scala_text(text: &str) -> Result<Self, String> {
+        let mut lines = text
+            .lines()
+            .map(|line| line.trim_end())
+            .filter(|line| !line.trim_start().starts_with('!'));
+
+        let description = lines
+            .next()
+            .ok_or("Scala file is empty")?
+            .trim()
+            .to_string();
+
+        let note_count: usize = lines
+            .next()
+            .and_then(|line| line.split_whitespace().next())
+            .and_then(|count| count.parse().ok())
+            .ok_or("Scala file is missing the note count line")?;
+
+        if note_count == 0 {
+            return Err("Scala file has no notes".to_string());
+        }
+
+        let mut degree_cents = Vec::with_capacity(note_count);
+        for line in lines.take(note_count) {
+            let token = line.split_whitespace().next().unwrap_or("");
+            let cents = parse_scala_pitch(token)
+                .ok_or_else(|| format!("Invalid Scala pitch '{}'", token))?;
+            degree_cents.push(cents);
+        }
+
+        if degree_cents.len() != note_count {
+            return Err(format!(
+                "Scala file promises {} notes but only has {}",
+                note_count,
+                degree_cents.len()
+            ));
+        }
+
+        // The last entry is the period; the rest are the degrees inside it
+        let period_cents = degree_cents.pop().unwrap_or(1200.0);
+        if period_cents <= 0.0 {
+            return Err("Scala period must be greater than zero".to_string());
+        }
+
+        Ok(Self {
+            system: TuningSystem::Scala {
+                description,
+                degree_cents,
+                period_cents,
+            },
+            reference_frequency_hz: A4_FREQUENCY_HZ,
+        })
+    }
+
+    /// Returns the frequency for a parsed note name in this tuning
+    ///
+    /// Standard tuning goes through the pre-computed frequency table;
+    /// everything else is calculated directly (this only runs at parse time).
+    pub fn pitch_to_frequency(
+        &self,
+        pitch: &PitchName,
+        frequency_table: &FrequencyTable,
+    ) -> Option<f32> {
+        if self.is_standard() {
+            return pitch_name_to_frequency(pitch, frequency_table);
+        }
+
+        let reference = PitchName {
+            letter: 'a',
+            accidental: 0,
+            octave: 4,
+            cents: 0.0,
+        };
+        let cents_from_a4 =
+            self.note_cents_from_c4(pitch)? - self.note_cents_from_c4(&reference)?;
+        let frequency = self.reference_frequency_hz
+            * cents_to_frequency_ratio(cents_from_a4)
+            * cents_to_frequency_ratio(pitch.cents);
+
+        if frequency.is_finite() && frequency > 0.0 {
+            Some(frequency)
+        } else {
+            None
+        }
+    }
+
+    /// Returns how many cents a note sits above C4 in this tuning
+    /// (not counting the note's own cents offset)
+    pub fn note_cents_from_c4(&self, pitch: &PitchName) -> Option<f32> {
+        match &self.system {
+            TuningSystem::EqualDivision { steps_per_octave } => {
+                let steps = *steps_per_octave as i32;
+                let step_cents = 1200.0 / steps as f32;
+
+                // Nearest EDO step to a pure fifth (3:2 = 701.955 cents)
+                let fifth_steps = (steps as f32 * 1.5_f32.log2()).round() as i32;
+
+                // Position of each letter on the chain of fifths, relative to C
+                let fifths_from_c = match pitch.letter {
+                    'f' => -1,
+                    'c' => 0,
+                    'g' => 1,
+                    'd' => 2,
+                    'a' => 3,
+                    'e' => 4,
+                    'b' => 5,
+                    _ => return None,
+                };
+
+                // Stack the fifths, then drop octaves so the letter lands
+                // in the same octave as its 12-TET counterpart
+                let letter_semitone = note_letter_to_semitone(pitch.letter)?;
+                let octaves_to_drop = (fifths_from_c * 7 - letter_semitone).div_euclid(12);
+                let letter_steps = fifths_from_c * fifth_steps - octaves_to_drop * steps;
+
+                // Chromatic step: seven fifths minus four octaves
+                let sharp_steps = 7 * fifth_steps - 4 * steps;
+
+                let total_steps =
+                    (pitch.octave - 4) * steps + letter_steps + pitch.accidental * sharp_steps;
+                Some(total_steps as f32 * step_cents)
+            }
+
+            TuningSystem::Scala {
+                degree_cents,
+                period_cents,
+                ..
+            } => {
+                let notes_per_period = degree_cents.len() as i32 + 1;
+                let semitones_from_c4 = pitch.semitones_from_c0() - 48;
+
+                let periods = semitones_from_c4.div_euclid(notes_per_period);
+                let degree = semitones_from_c4.rem_euclid(notes_per_period) as usize;
+                let degree_offset = if degree == 0 {
+                    0.0
+                } else {
+                    degree_cents[degree - 1]
+                };
+
+                Some(periods as f32 * period_cents + degree_offset)
+            }
+        }
+    }
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+/// Lowest accepted A4 reference frequency in Hz
+pub const MIN_REFERENCE_FREQUENCY_HZ: f32 = 100.0;
+
+/// Highest accepted A4 reference frequency in Hz
+pub const MAX_REFERENCE_FREQUENCY_HZ: f32 = 1000.0;
+
+/// Parses an A4 reference frequency like "432" or "415.3"
+/// Returns None if the text is not a number in the accepted range
+pub fn parse_reference_frequency(text: &str) -> Option<f32> {
+    let lower = text.trim().to_lowercase();
+    let value: f32 = lower.trim_end_matches("hz").trim().parse().ok()?;
+    if (MIN_REFERENCE_FREQUENCY_HZ..=MAX_REFERENCE_FREQUENCY_HZ).contains(&value) {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// Parses one Scala pitch value into cents
+/// "701.955" -> cents, "3/2" -> ratio, "2" -> ratio
+fn parse_scala_pitch(token: &str) -> Option<f32> {
+    if token.contains('.') {
+        return token.parse::<f32>().ok();
+    }
+
+    let ratio = match token.split_once('/') {
+        Some((numerator, denominator)) => {
+            let numerator: f64 = numerator.parse().ok()?;
+            let denominator: f64 = denominator.parse().ok()?;
+            if denominator == 0.0 {
+                return None;
+            }
+            numerator / denominator
+        }
+        None => token.parse::<f64>().ok()?,
+    };
+
+    if ratio <= 0.0 {
+        return None;
+    }
+
+    Some((1200.0 * ratio.log2()) as f32)
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::parse_pitch_name;
+
+    fn frequency(tuning: &Tuning, note: &str) -> f32 {
+        let table = FrequencyTable::new();
+        tuning
+            .pitch_to_frequency(&parse_pitch_name(note).unwrap(), &table)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_twelve_edo_matches_table() {
+        // Force the calculated path by nudging the reference, then compare
+        let mut tuning = Tuning::equal_division(12);
+        tuning.reference_frequency_hz = 440.0001;
+        let table = FrequencyTable::new();
+
+        for note in ["c4", "f#3", "bb5", "a4", "cb4", "b#3"] {
+            let pitch = parse_pitch_name(note).unwrap();
+            let expected = pitch_name_to_frequency(&pitch, &table).unwrap();
+            assert!(
+                (frequency(&tuning, note) - expected).abs() < 0.01,
+                "{}",
+                note
+            );
+        }
+    }
+
+    #[test]
+    fn test_nineteen_edo_separates_enharmonics() {
+        let tuning = Tuning::parse_directive("19edo").unwrap();
+
+        assert!((frequency(&tuning, "a4") - 440.0).abs() < 0.001);
+        assert!(frequency(&tuning, "c#4") < frequency(&tuning, "db4"));
+        assert!((frequency(&tuning, "a5") - 880.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_scala_text() {
+        let text = "! pentatonic.scl\nJust pentatonic\n 5\n!\n 9/8\n 5/4\n 3/2\n 5/3\n 2/1\n";
+        let tuning = Tuning::from_scala_text(text).unwrap();
+
+        match &tuning.system {
+            TuningSystem::Scala {
+                degree_cents,
+                period_cents,
+                ..
+            } => {
+                assert_eq!(degree_cents.len(), 4);
+                assert!((period_cents - 1200.0).abs() < 0.01);
+                assert!((degree_cents[2] - 701.955).abs() < 0.01);
+            }
+            other => panic!("expected Scala tuning, got {:?}", other),
+        }
+
+        assert!(Tuning::from_scala_text("Broken\n 2\n 3/2\n").is_err());
+    }
+
+    #[test]
+    fn test_reference_frequency() {
+        let tuning = Tuning {
+            reference_frequency_hz: parse_reference_frequency("432").unwrap(),
+            ..Default::default()
+        };
+        assert!((frequency(&tuning, "a4") - 432.0).abs() < 0.001);
+        assert!((frequency(&tuning, "a3") - 216.0).abs() < 0.001);
+
+        assert_eq!(parse_reference_frequency("415.3Hz"), Some(415.3));
+        assert_eq!(parse_reference_frequency("5"), None);
+        assert_eq!(parse_reference_frequency("abc"), None);
+    }
+
+    #[test]
+    fn test_parse_directive_errors() {
+        assert!(Tuning::parse_directive("").is_err());
+        assert!(Tuning::parse_directive("wobbly").is_err());
+        assert!(Tuning::parse_directive("12-tet").unwrap().is_standard());
+    }
+}
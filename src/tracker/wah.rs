@@ -0,0 +1,309 @@
+// ============================================================================
+// WAH.RS - Wah and Vowel (Formant) Filters
+// ============================================================================
+//
+// Two resonant filters a channel can put on its voice:
+//
+//   wah:2'0.6'5      A band-pass whose centre sweeps up and down twice a second
+//   wah:0'0.8        Auto-wah: the centre follows how loud the note is
+//   vowel:a          Three band-passes at the formants of an "a" vowel
+//   vowel:a>o tr:1   Slides from "a" to "o" over the transition time
+//
+// Both run first in the channel chain, on the raw voice, before chorus,
+// tremolo, bitcrush, and distortion (in whatever order those are).
+//
+// HOW IT WORKS:
+// Both are built from the state-variable filter below, in its "TPT" form
+// (topology-preserving transform), which stays stable and click-free
+// while its frequency moves. Its band-pass output is scaled so the centre
+// frequency passes at full level and everything else is quieter.
+//
+// The wah's centre moves between WAH_LOW_HZ and WAH_HIGH_HZ on a log
+// scale, so equal steps of the LFO sound like equal steps in pitch. Depth
+// sets how much of that range it covers (around the middle for the LFO,
+// up from the bottom for the auto-wah); q sets how narrow, and how vocal,
+// the peak is. With a rate of 0 an envelope follower moves the centre
+// instead: a loud attack opens the filter and it closes as the note dies.
+//
+// A vowel is three formants (the resonant peaks of a singing voice), each
+// a frequency, a width, and a level. The three band-passes run side by
+// side and are summed. A morph interpolates all nine numbers between two
+// vowels, so "a>o" slides straight from one to the other instead of
+// passing through the vowels in between.
+// ============================================================================
+
+use crate::effects::flush_denormal;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// Lowest centre of the wah sweep
+pub const WAH_LOW_HZ: f32 = 350.0;
+
+/// Highest centre of the wah sweep
+pub const WAH_HIGH_HZ: f32 = 2500.0;
+
+/// Resonance of "wah:2'0.5" when no q is given
+pub const DEFAULT_WAH_Q: f32 = 5.0;
+
+/// How quickly the auto-wah opens on a louder sound
+const ENVELOPE_ATTACK_SECONDS: f32 = 0.01;
+
+/// How quickly the auto-wah closes as the sound gets quieter
+const ENVELOPE_RELEASE_SECONDS: f32 = 0.15;
+
+/// Level at which the auto-wah is fully open
+const ENVELOPE_FULL_LEVEL: f32 = 0.5;
+
+/// Gain on the summed formants (the narrow bands let through only a small
+/// part of the voice; this brings it back near the unfiltered level)
+pub const VOWEL_OUTPUT_GAIN: f32 = 4.0;
+
+// ============================================================================
+// STATE-VARIABLE FILTER
+// ============================================================================
+
+/// One resonant band-pass filter (see the top of the file)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StateVariableFilter {
+    low_state: f32,
+    band_state: f32,
+}
+
+impl StateVariableFilter {
+    /// The tuning of a filter centred on this frequency
+    /// Worked out once per block (it needs a tan), then passed to band_pass.
+    pub fn tuning(frequency_hz: f32, sample_rate: u32) -> f32 {
+        let nyquist_fraction = (frequency_hz / sample_rate as f32).clamp(0.0, 0.49);
+        (PI * nyquist_fraction).tan()
+    }
+
+    /// Filters one sample, returning the band-pass output
+    /// The centre frequency comes out at the level it went in.
+    pub fn band_pass(&mut self, input: f32, tuning: f32, q: f32) -> f32 {
+        let damping = 1.0 / q.max(0.1);
+        let a1 = 1.0 / (1.0 + tuning * (tuning + damping));
+        let a2 = tuning * a1;
+        let a3 = tuning * a2;
+
+        let v3 = input - self.low_state;
+        let band = a1 * self.band_state + a2 * v3;
+        let low = self.low_state + a2 * self.band_state + a3 * v3;
+        self.band_state = flush_denormal(2.0 * band - self.band_state);
+        self.low_state = flush_denormal(2.0 * low - self.low_state);
+        band * damping
+    }
+
+    /// Forgets the sound it has filtered
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+// ============================================================================
+// WAH
+// ============================================================================
+
+/// Centre of the wah for a position in its range (0 = WAH_LOW_HZ, 1 = WAH_HIGH_HZ)
+pub fn wah_frequency(position: f32) -> f32 {
+    WAH_LOW_HZ * (WAH_HIGH_HZ / WAH_LOW_HZ).powf(position.clamp(0.0, 1.0))
+}
+
+/// Position of an LFO-swept wah (lfo runs -1..1)
+pub fn lfo_wah_position(lfo: f32, depth: f32) -> f32 {
+    0.5 + 0.5 * lfo * depth
+}
+
+/// Position of the auto-wah for the envelope follower's level
+pub fn envelope_wah_position(envelope: f32, depth: f32) -> f32 {
+    (envelope / ENVELOPE_FULL_LEVEL).min(1.0) * depth
+}
+
+/// Moves the envelope follower towards the loudest sample of a block
+pub fn follow_envelope(envelope: f32, block: &[f32], sample_rate: u32) -> f32 {
+    let peak = block
+        .iter()
+        .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+    let seconds = if peak > envelope {
+        ENVELOPE_ATTACK_SECONDS
+    } else {
+        ENVELOPE_RELEASE_SECONDS
+    };
+    let coefficient = 1.0 - (-(block.len() as f32) / (seconds * sample_rate as f32)).exp();
+    flush_denormal(envelope + (peak - envelope) * coefficient)
+}
+
+// ============================================================================
+// VOWELS
+// ============================================================================
+
+/// A vowel the formant filter can sing ("vowel:a", "vowel:a>o")
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Vowel {
+    A,
+    E,
+    I,
+    O,
+    U,
+}
+
+/// One resonant peak of a vowel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Formant {
+    pub frequency_hz: f32,
+    pub bandwidth_hz: f32,
+    pub level: f32,
+}
+
+impl Formant {
+    const fn new(frequency_hz: f32, bandwidth_hz: f32, level: f32) -> Self {
+        Self {
+            frequency_hz,
+            bandwidth_hz,
+            level,
+        }
+    }
+
+    /// The formant part of the way (0..1) to another one
+    pub fn lerp(self, other: Formant, amount: f32) -> Formant {
+        let mix = |a: f32, b: f32| a + (b - a) * amount;
+        Formant {
+            frequency_hz: mix(self.frequency_hz, other.frequency_hz),
+            bandwidth_hz: mix(self.bandwidth_hz, other.bandwidth_hz),
+            level: mix(self.level, other.level),
+        }
+    }
+}
+
+impl Vowel {
+    /// Looks up a vowel by its letter
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "a" => Some(Vowel::A),
+            "e" => Some(Vowel::E),
+            "i" => Some(Vowel::I),
+            "o" => Some(Vowel::O),
+            "u" => Some(Vowel::U),
+            _ => None,
+        }
+    }
+
+    /// The letter cells use for this vowel
+    pub fn name(self) -> &'static str {
+        match self {
+            Vowel::A => "a",
+            Vowel::E => "e",
+            Vowel::I => "i",
+            Vowel::O => "o",
+            Vowel::U => "u",
+        }
+    }
+
+    /// The three formants of this vowel (an adult male voice)
+    pub fn formants(self) -> [Formant; 3] {
+        match self {
+            Vowel::A => [
+                Formant::new(800.0, 80.0, 1.0),
+                Formant::new(1150.0, 90.0, 0.5),
+                Formant::new(2900.0, 120.0, 0.025),
+            ],
+            Vowel::E => [
+                Formant::new(400.0, 60.0, 1.0),
+                Formant::new(1600.0, 80.0, 0.063),
+                Formant::new(2700.0, 120.0, 0.032),
+            ],
+            Vowel::I => [
+                Formant::new(250.0, 60.0, 1.0),
+                Formant::new(1750.0, 90.0, 0.032),
+                Formant::new(2600.0, 100.0, 0.158),
+            ],
+            Vowel::O => [
+                Formant::new(400.0, 40.0, 1.0),
+                Formant::new(750.0, 80.0, 0.282),
+                Formant::new(2400.0, 100.0, 0.089),
+            ],
+            Vowel::U => [
+                Formant::new(350.0, 40.0, 1.0),
+                Formant::new(600.0, 80.0, 0.1),
+                Formant::new(2400.0, 100.0, 0.025),
+            ],
+        }
+    }
+}
+
+/// Parses a vowel or a morph between two: "a" or "a>o"
+pub fn parse_vowels(text: &str) -> Result<(Vowel, Vowel), String> {
+    let (first, second) = text.split_once('>').unwrap_or((text, text));
+    let vowel = |name: &str| {
+        let name = name.trim().to_lowercase();
+        Vowel::from_name(&name)
+            .ok_or_else(|| format!("Unknown vowel '{}'. Use: a, e, i, o, u", name))
+    };
+    Ok((vowel(first)?, vowel(second)?))
+}
+
+/// The formants part of the way (0..1) through a morph between two vowels
+pub fn morph_formants(vowels: (Vowel, Vowel), morph: f32) -> [Formant; 3] {
+    let from = vowels.0.formants();
+    let to = vowels.1.formants();
+    let amount = morph.clamp(0.0, 1.0);
+    [
+        from[0].lerp(to[0], amount),
+        from[1].lerp(to[1], amount),
+        from[2].lerp(to[2], amount),
+    ]
+}
+
+// ============================================================================
+// UNIT TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Level of a sine after the filter, once it has settled
+    fn band_pass_gain(frequency_hz: f32, centre_hz: f32) -> f32 {
+        let sample_rate = 48000;
+        let tuning = StateVariableFilter::tuning(centre_hz, sample_rate);
+        let mut filter = StateVariableFilter::default();
+        let mut peak = 0.0_f32;
+        for index in 0..9600 {
+            let input = (2.0 * PI * frequency_hz * index as f32 / sample_rate as f32).sin();
+            let output = filter.band_pass(input, tuning, DEFAULT_WAH_Q);
+            if index >= 4800 {
+                peak = peak.max(output.abs());
+            }
+        }
+        peak
+    }
+
+    #[test]
+    fn test_band_pass() {
+        // The centre passes at full level; an octave either side is cut
+        assert!((band_pass_gain(1000.0, 1000.0) - 1.0).abs() < 0.02);
+        assert!(band_pass_gain(500.0, 1000.0) < 0.15);
+        assert!(band_pass_gain(2000.0, 1000.0) < 0.15);
+
+        // The sweep covers the wah's range on a log scale
+        assert_eq!(wah_frequency(0.0), WAH_LOW_HZ);
+        assert!((wah_frequency(1.0) - WAH_HIGH_HZ).abs() < 0.1);
+        let middle = (WAH_LOW_HZ * WAH_HIGH_HZ).sqrt();
+        assert!((wah_frequency(lfo_wah_position(0.0, 1.0)) - middle).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_vowels() {
+        assert_eq!(parse_vowels("a").unwrap(), (Vowel::A, Vowel::A));
+        assert_eq!(parse_vowels("A>o").unwrap(), (Vowel::A, Vowel::O));
+        assert!(parse_vowels("a>y").is_err());
+
+        // Halfway through a morph every formant is halfway
+        let halfway = morph_formants((Vowel::A, Vowel::O), 0.5);
+        assert_eq!(halfway[0].frequency_hz, 600.0);
+        assert_eq!(halfway[1].frequency_hz, 950.0);
+        assert_eq!(
+            morph_formants((Vowel::A, Vowel::O), 1.0),
+            Vowel::O.formants()
+        );
+    }
+}